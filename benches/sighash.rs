@@ -0,0 +1,84 @@
+//! Signature hashes of every input of a 100 input transaction,
+//! with one shared [SighashCache] and with a fresh one per input
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use waller::{
+    OutPoint, SighashCache, Transaction, TransactionInput, TransactionOutput, TransactionType,
+    SIGHASH_ALL,
+};
+
+const INPUTS: usize = 100;
+
+fn transaction() -> Transaction {
+    let script_code = hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap();
+
+    let inputs = (0..INPUTS)
+        .map(|index| {
+            let outpoint = OutPoint::new(format!("{:064x}", index), index as i32);
+            let utxo = TransactionOutput::from_script(100_000, script_code.clone());
+            TransactionInput::new(utxo, outpoint.hash(), outpoint.index())
+        })
+        .collect();
+    let outputs = vec![TransactionOutput::from_script(
+        INPUTS as i64 * 99_000,
+        script_code,
+    )];
+
+    Transaction::new(TransactionType::Pay2PubKeyHash, inputs, outputs, None)
+}
+
+fn segwit_v0(c: &mut Criterion) {
+    let tx = transaction();
+    let script_code = tx.get_input(0).unwrap().utxo_pk_script().to_vec();
+
+    let mut group = c.benchmark_group("bip143 100 inputs");
+    group.bench_function("shared cache", |b| {
+        b.iter(|| {
+            let mut cache = SighashCache::new();
+            for index in 0..INPUTS {
+                cache
+                    .segwit_v0_sighash(&tx, index, &script_code, 100_000, SIGHASH_ALL)
+                    .unwrap();
+            }
+        })
+    });
+    group.bench_function("cache per input", |b| {
+        b.iter(|| {
+            for index in 0..INPUTS {
+                SighashCache::new()
+                    .segwit_v0_sighash(&tx, index, &script_code, 100_000, SIGHASH_ALL)
+                    .unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn taproot(c: &mut Criterion) {
+    let tx = transaction();
+
+    let mut group = c.benchmark_group("bip341 100 inputs");
+    group.bench_function("shared cache", |b| {
+        b.iter(|| {
+            let mut cache = SighashCache::new();
+            for index in 0..INPUTS {
+                cache
+                    .taproot_key_spend_sighash(&tx, index, 0x00, None)
+                    .unwrap();
+            }
+        })
+    });
+    group.bench_function("cache per input", |b| {
+        b.iter(|| {
+            for index in 0..INPUTS {
+                SighashCache::new()
+                    .taproot_key_spend_sighash(&tx, index, 0x00, None)
+                    .unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, segwit_v0, taproot);
+criterion_main!(benches);
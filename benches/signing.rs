@@ -0,0 +1,79 @@
+//! Signing a batch of 20 withdrawals of 10 inputs each, with
+//! [Wallet::sign_all] and with [Wallet::sign_transaction] per transaction
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use waller::{
+    AccountType, Network, Transaction, TransactionInput, TransactionOutput, TransactionType, Wallet,
+};
+
+const TRANSACTIONS: usize = 20;
+const INPUTS: usize = 10;
+
+fn batch(wallet: &mut Wallet) -> Vec<Transaction> {
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let coins: Vec<TransactionOutput> = (0..INPUTS)
+        .map(|_| {
+            let address = wallet.new_receive_address(account).unwrap();
+            TransactionOutput::new(
+                TransactionType::Pay2WitnessPubKeyHash,
+                wallet.get_address(address).unwrap(),
+                100_000,
+            )
+        })
+        .collect();
+    let script = hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+
+    (0..TRANSACTIONS)
+        .map(|tx| {
+            let inputs = coins
+                .iter()
+                .enumerate()
+                .map(|(index, coin)| {
+                    TransactionInput::new(coin.clone(), format!("{:064x}", tx), index as i32)
+                })
+                .collect();
+            let outputs = vec![TransactionOutput::from_script(
+                INPUTS as i64 * 99_000,
+                script.clone(),
+            )];
+            Transaction::new(
+                TransactionType::Pay2WitnessPubKeyHash,
+                inputs,
+                outputs,
+                None,
+            )
+        })
+        .collect()
+}
+
+fn signing(c: &mut Criterion) {
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        std::env::temp_dir().join("waller_bench_signing"),
+        false,
+    )
+    .unwrap();
+    let txs = batch(&mut wallet);
+
+    let mut group = c.benchmark_group("20 transactions of 10 inputs");
+    group.bench_function("sign_all", |b| {
+        b.iter(|| {
+            let mut batch = txs.clone();
+            wallet.sign_all(&mut batch).unwrap();
+        })
+    });
+    group.bench_function("sign_transaction per transaction", |b| {
+        b.iter(|| {
+            let mut batch = txs.clone();
+            for tx in batch.iter_mut() {
+                wallet.sign_transaction(tx).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, signing);
+criterion_main!(benches);
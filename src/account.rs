@@ -0,0 +1,236 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    native_segwit_address, nested_segwit_address, p2pkh_address, taproot_address,
+    with_descriptor_checksum, ChildNumber, Key, KeyError, Network, TransactionType,
+};
+
+/// the bit set in the serialized index of a hardened child, see [crate::ChildNumber]
+pub const HARDENED_OFFSET: u32 = 2147483648;
+
+/// the BIP44 purpose level of an account's derivation path
+pub const BIP44_PURPOSE: u32 = 44;
+
+/// the BIP49 purpose level, for accounts of P2SH-P2WPKH addresses
+pub const BIP49_PURPOSE: u32 = 49;
+
+/// the BIP84 purpose level, for accounts of P2WPKH addresses
+pub const BIP84_PURPOSE: u32 = 84;
+
+/// the BIP86 purpose level, for accounts of single key P2TR addresses
+pub const BIP86_PURPOSE: u32 = 86;
+
+/// The kind of addresses an account hands out, each with its own purpose level
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AccountType {
+    /// BIP44 P2PKH addresses (`1...`)
+    #[default]
+    Legacy,
+    /// BIP49 P2SH-P2WPKH addresses (`3...`)
+    NestedSegwit,
+    /// BIP84 P2WPKH addresses (`bc1q...`)
+    NativeSegwit,
+    /// BIP86 P2TR addresses (`bc1p...`)
+    Taproot,
+}
+
+impl AccountType {
+    /// the purpose level of the account's derivation path
+    pub fn purpose(&self) -> u32 {
+        match self {
+            AccountType::Legacy => BIP44_PURPOSE,
+            AccountType::NestedSegwit => BIP49_PURPOSE,
+            AccountType::NativeSegwit => BIP84_PURPOSE,
+            AccountType::Taproot => BIP86_PURPOSE,
+        }
+    }
+
+    /// the hardened path of an account of this type, `m/purpose'/coin'/account'`
+    pub(crate) fn account_path(&self, network: Network, account: u32) -> [ChildNumber; 3] {
+        [
+            ChildNumber::Hardened(self.purpose()),
+            ChildNumber::Hardened(network.coin_type()),
+            ChildNumber::Hardened(account),
+        ]
+    }
+
+    /// the address of a key in this account
+    pub fn address(&self, key: &Key) -> Result<String, KeyError> {
+        match self {
+            AccountType::Legacy => key.address(),
+            AccountType::NestedSegwit => key.nested_segwit_address(),
+            AccountType::NativeSegwit => key.native_segwit_address(),
+            AccountType::Taproot => key.taproot_address(),
+        }
+    }
+
+    /// the address of a public key in this account, as [AccountType::address]
+    /// of its key, eg for keys derived while the wallet is locked
+    pub fn public_key_address(
+        &self,
+        public_key: &[u8],
+        network: Network,
+    ) -> Result<String, KeyError> {
+        match self {
+            AccountType::Legacy => Ok(p2pkh_address(public_key, network)),
+            AccountType::NestedSegwit => nested_segwit_address(public_key, network),
+            AccountType::NativeSegwit => native_segwit_address(public_key, network),
+            AccountType::Taproot => taproot_address(public_key, network),
+        }
+    }
+
+    /// the type of output paying to this account
+    pub fn tx_type(&self) -> TransactionType {
+        match self {
+            AccountType::Legacy => TransactionType::Pay2PubKeyHash,
+            AccountType::NestedSegwit => TransactionType::NestedPay2WitnessPubKeyHash,
+            AccountType::NativeSegwit => TransactionType::Pay2WitnessPubKeyHash,
+            AccountType::Taproot => TransactionType::Pay2Taproot,
+        }
+    }
+
+    /// The output descriptor of one chain of an account, `0` for receive
+    /// addresses and `1` for change, with its checksum, eg
+    /// `wpkh([fp/84'/0'/0']xpub.../0/*)#checksum`
+    pub fn descriptor(&self, key: &AccountXpub, chain: u32) -> String {
+        let key = format!("{}/{}/*", key, chain);
+
+        let descriptor = match self {
+            AccountType::Legacy => format!("pkh({})", key),
+            AccountType::NestedSegwit => format!("sh(wpkh({}))", key),
+            AccountType::NativeSegwit => format!("wpkh({})", key),
+            AccountType::Taproot => format!("tr({})", key),
+        };
+        // hex, base58 and the path only hold characters descriptors can
+        with_descriptor_checksum(&descriptor).unwrap()
+    }
+}
+
+/// The chains of an account, each deriving its own sequence of addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Chain {
+    /// receive addresses handed out to payers
+    External,
+    /// change addresses the wallet pays itself with
+    Internal,
+}
+
+impl Chain {
+    /// the index of the chain in the account's derivation path
+    pub fn index(&self) -> u32 {
+        match self {
+            Chain::External => 0,
+            Chain::Internal => 1,
+        }
+    }
+}
+
+/// An account living at `m/purpose'/coin'/account'`
+/// receive addresses are derived from its external chain
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Account {
+    index: u32,
+    #[serde(default)]
+    account_type: AccountType,
+    /// the arena node of the account key
+    node: usize,
+    archived: bool,
+    /// index of the next receive address
+    #[serde(default)]
+    next_external_index: u32,
+    /// index of the next change address
+    #[serde(default)]
+    next_internal_index: u32,
+}
+
+impl Account {
+    pub(crate) fn new(index: u32, account_type: AccountType, node: usize) -> Self {
+        Self {
+            index,
+            account_type,
+            node,
+            archived: false,
+            next_external_index: 0,
+            next_internal_index: 0,
+        }
+    }
+
+    /// the account number used in the derivation path,
+    /// counted separately for every account type
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn account_type(&self) -> AccountType {
+        self.account_type
+    }
+
+    /// archived accounts no longer hand out receive addresses
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
+    /// the derivation path of the account key
+    pub fn path(&self, network: Network) -> String {
+        format!(
+            "m/{}'/{}'/{}'",
+            self.account_type.purpose(),
+            network.coin_type(),
+            self.index
+        )
+    }
+
+    /// the index the next address of a chain will be derived at
+    pub fn next_index(&self, chain: Chain) -> u32 {
+        match chain {
+            Chain::External => self.next_external_index,
+            Chain::Internal => self.next_internal_index,
+        }
+    }
+
+    /// move past the current index of a chain once its address was handed out
+    pub(crate) fn advance(&mut self, chain: Chain) {
+        match chain {
+            Chain::External => self.next_external_index += 1,
+            Chain::Internal => self.next_internal_index += 1,
+        }
+    }
+
+    pub(crate) fn node(&self) -> usize {
+        self.node
+    }
+
+    pub(crate) fn set_node(&mut self, node: usize) {
+        self.node = node;
+    }
+
+    pub(crate) fn archive(&mut self) {
+        self.archived = true;
+    }
+}
+
+/// An account level extended public key with its origin, safe to
+/// share with watch-only wallets and multisig coordinators
+#[derive(Debug, Clone)]
+pub struct AccountXpub {
+    /// fingerprint of the wallet's master key
+    pub fingerprint: [u8; 4],
+    /// derivation path of the account from the master key
+    pub path: String,
+    pub xpub: String,
+}
+
+impl Display for AccountXpub {
+    /// formats as `[fingerprint/path]xpub`, the key origin format used by descriptors
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}/{}]{}",
+            hex::encode(self.fingerprint),
+            self.path.trim_start_matches("m/"),
+            self.xpub
+        )
+    }
+}
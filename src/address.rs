@@ -0,0 +1,205 @@
+use std::fmt;
+
+use crate::{base58check_decode, decode_segwit_address, KeyError, Network, Script};
+
+/// The kind of output an address pays to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressType {
+    P2pkh,
+    P2sh,
+    /// segwit v0 paying to a 20 byte key hash
+    P2wpkh,
+    /// segwit v0 paying to a 32 byte script hash
+    P2wsh,
+    /// segwit v1 paying to a 32 byte taproot output key
+    P2tr,
+    /// a witness version or program length without a known spending rule,
+    /// anyone can spend it until a soft fork gives it one
+    UnknownWitness {
+        version: u8,
+    },
+}
+
+/// Why an address can't be paid to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressProblem {
+    /// neither base58 nor bech32, or mixing upper and lower case
+    Encoding,
+    /// the checksum doesn't match, most likely a typo
+    Checksum,
+    /// a valid address of another network
+    WrongNetwork(Network),
+    /// decodes but doesn't hold an address, eg an unknown version byte,
+    /// a program of the wrong length or the wrong bech32 variant
+    Payload(String),
+}
+
+impl fmt::Display for AddressProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressProblem::Encoding => write!(f, "not a base58 or bech32 address"),
+            AddressProblem::Checksum => write!(f, "the checksum doesn't match, check for typos"),
+            AddressProblem::WrongNetwork(network) => {
+                write!(f, "an address of the {} network", network)
+            }
+            AddressProblem::Payload(reason) => write!(f, "not an address: {}", reason),
+        }
+    }
+}
+
+/// What [validate_address] found out about an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressValidation {
+    /// the address as given
+    pub address: String,
+    /// the network the address was made for, when it could be read
+    pub network: Option<Network>,
+    pub address_type: Option<AddressType>,
+    /// the output script paying to the address, only when it's valid
+    pub script: Option<Script>,
+    /// why the address can't be paid to, none when it's valid
+    pub problem: Option<AddressProblem>,
+}
+
+impl AddressValidation {
+    pub fn is_valid(&self) -> bool {
+        self.problem.is_none()
+    }
+
+    fn invalid(address: &str, problem: AddressProblem) -> Self {
+        Self {
+            address: address.to_string(),
+            network: None,
+            address_type: None,
+            script: None,
+            problem: Some(problem),
+        }
+    }
+}
+
+/// Check an address can be paid to on a network: its encoding and checksum,
+/// the network it was made for and the kind of output it pays to
+pub fn validate_address(address: &str, network: Network) -> AddressValidation {
+    let networks = [Network::Mainnet, Network::Testnet, Network::Regtest];
+
+    // segwit addresses start with the human readable part of their network
+    let lowercase = address.to_lowercase();
+    let segwit = networks
+        .iter()
+        .find(|network| lowercase.starts_with(&format!("{}1", network.bech32_hrp())));
+
+    let (found, address_type) = match segwit {
+        Some(found) => {
+            if let Err(e) = bech32::decode(address) {
+                return AddressValidation::invalid(
+                    address,
+                    match e {
+                        bech32::Error::InvalidChecksum => AddressProblem::Checksum,
+                        _ => AddressProblem::Encoding,
+                    },
+                );
+            }
+
+            let (version, program) = match decode_segwit_address(*found, address) {
+                Ok(decoded) => decoded,
+                Err(_) => {
+                    return AddressValidation {
+                        network: Some(*found),
+                        ..AddressValidation::invalid(
+                            address,
+                            AddressProblem::Payload(
+                                "an invalid witness version, program or bech32 variant".to_string(),
+                            ),
+                        )
+                    }
+                }
+            };
+            let address_type = match (version, program.len()) {
+                (0, 20) => AddressType::P2wpkh,
+                (0, 32) => AddressType::P2wsh,
+                (1, 32) => AddressType::P2tr,
+                (version, _) => AddressType::UnknownWitness { version },
+            };
+            (*found, address_type)
+        }
+        None => {
+            let decoded = match base58check_decode(address) {
+                Ok(decoded) => decoded,
+                Err(KeyError::ChecksumMismatch) => {
+                    return AddressValidation::invalid(address, AddressProblem::Checksum)
+                }
+                Err(_) => return AddressValidation::invalid(address, AddressProblem::Encoding),
+            };
+
+            // regtest shares the base58 prefixes of testnet
+            let prefix = match decoded.first() {
+                Some(prefix) => *prefix,
+                None => {
+                    return AddressValidation::invalid(
+                        address,
+                        AddressProblem::Payload("no version byte".to_string()),
+                    )
+                }
+            };
+            let found = match networks
+                .iter()
+                .find(|found| found.p2pkh_prefix() == prefix || found.p2sh_prefix() == prefix)
+            {
+                Some(found) if found.p2pkh_prefix() == network.p2pkh_prefix() => network,
+                Some(found) => *found,
+                None => {
+                    return AddressValidation::invalid(
+                        address,
+                        AddressProblem::Payload(format!("unknown version byte {:#04x}", prefix)),
+                    )
+                }
+            };
+            if decoded.len() != 21 {
+                return AddressValidation {
+                    network: Some(found),
+                    ..AddressValidation::invalid(
+                        address,
+                        AddressProblem::Payload(format!(
+                            "a {} byte hash, not 20",
+                            decoded.len() - 1
+                        )),
+                    )
+                };
+            }
+
+            let address_type = match prefix == found.p2pkh_prefix() {
+                true => AddressType::P2pkh,
+                false => AddressType::P2sh,
+            };
+            (found, address_type)
+        }
+    };
+
+    let problem = match found == network {
+        true => None,
+        false => Some(AddressProblem::WrongNetwork(found)),
+    };
+    AddressValidation {
+        address: address.to_string(),
+        network: Some(found),
+        address_type: Some(address_type),
+        script: match problem {
+            Some(_) => None,
+            None => Script::from_address(address, network).ok(),
+        },
+        problem,
+    }
+}
+
+/// [validate_address] every address of a batch, eg the recipients of a
+/// transaction before it's built, in the order given
+pub fn validate_addresses<I, S>(addresses: I, network: Network) -> Vec<AddressValidation>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    addresses
+        .into_iter()
+        .map(|address| validate_address(address.as_ref(), network))
+        .collect()
+}
@@ -0,0 +1,107 @@
+use crate::{OutPoint, Transaction};
+
+/// An input of an [AnnotatedTransaction]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotatedInput {
+    pub previous_output: OutPoint,
+    /// satoshis of the coin spent, 0 when the coin isn't known
+    pub value: i64,
+    /// the address the coin was paid to, none for scripts without an
+    /// address or coins that aren't known
+    pub address: Option<String>,
+    /// whether the coin belongs to the wallet
+    pub is_mine: bool,
+    /// the label of the address, see [crate::Wallet::set_label]
+    pub label: Option<String>,
+}
+
+/// An output of an [AnnotatedTransaction]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotatedOutput {
+    pub value: i64,
+    /// the address paid, none for scripts without an address, eg `OP_RETURN`
+    pub address: Option<String>,
+    /// whether it pays to an address of the wallet
+    pub is_mine: bool,
+    /// whether it pays to the change chain of an account, see [crate::Wallet::is_change]
+    pub is_change: bool,
+    /// the label of the address, see [crate::Wallet::set_label]
+    pub label: Option<String>,
+}
+
+/// A transaction with what the wallet knows of its inputs and outputs, for
+/// front ends to show it without reading its scripts. Made by
+/// [crate::Wallet::annotate]
+#[derive(Debug, Clone)]
+pub struct AnnotatedTransaction {
+    transaction: Transaction,
+    inputs: Vec<AnnotatedInput>,
+    outputs: Vec<AnnotatedOutput>,
+}
+
+impl AnnotatedTransaction {
+    pub(crate) fn new(
+        transaction: Transaction,
+        inputs: Vec<AnnotatedInput>,
+        outputs: Vec<AnnotatedOutput>,
+    ) -> Self {
+        Self {
+            transaction,
+            inputs,
+            outputs,
+        }
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn into_transaction(self) -> Transaction {
+        self.transaction
+    }
+
+    pub fn tx_id(&self) -> String {
+        self.transaction.tx_id()
+    }
+
+    /// the inputs, in the order of the transaction
+    pub fn inputs(&self) -> &[AnnotatedInput] {
+        &self.inputs
+    }
+
+    /// the outputs, in the order of the transaction
+    pub fn outputs(&self) -> &[AnnotatedOutput] {
+        &self.outputs
+    }
+
+    /// satoshis paid in fees, counted from the values of the coins spent
+    pub fn fee(&self) -> i64 {
+        self.transaction.fee()
+    }
+
+    /// satoshis the transaction changes the balance of the wallet by,
+    /// negative when spending
+    pub fn net(&self) -> i64 {
+        let received: i64 = self
+            .outputs
+            .iter()
+            .filter(|output| output.is_mine)
+            .map(|output| output.value)
+            .sum();
+        let spent: i64 = self
+            .inputs
+            .iter()
+            .filter(|input| input.is_mine)
+            .map(|input| input.value)
+            .sum();
+        received - spent
+    }
+
+    /// the outputs paying outside the wallet
+    pub fn payments(&self) -> Vec<&AnnotatedOutput> {
+        self.outputs
+            .iter()
+            .filter(|output| !output.is_mine)
+            .collect()
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+
+use bech32::{ToBase32, Variant};
+use secp256k1::{schnorrsig, Message, PublicKey, Secp256k1, SecretKey};
+use zeroize::Zeroize;
+
+use crate::{
+    hmac_sha512_hash, tagged_hash, trace::REDACTED, ChildNumber, EntropySource, Key, KeyError,
+    OsEntropy,
+};
+
+/// the BIP85 purpose, keys derived under it are never used on chain
+pub const BIP85_PURPOSE: u32 = 83696968;
+
+/// A key for another protocol than bitcoin, eg nostr, derived from a wallet
+/// key with [Key::derive_app_key]. It signs and gives its public key, its
+/// private key only leaves through an explicit export
+pub struct AppKey {
+    secret: [u8; 32],
+    tag: String,
+}
+
+impl AppKey {
+    /// the purpose the key was derived for
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// the compressed public key
+    pub fn public_key(&self) -> Vec<u8> {
+        PublicKey::from_secret_key(&Secp256k1::new(), &self.secret_key())
+            .serialize()
+            .to_vec()
+    }
+
+    /// the BIP340 public key, as used by nostr
+    pub fn x_only_public_key(&self) -> Vec<u8> {
+        self.public_key()[1..].to_vec()
+    }
+
+    /// sign a 32 byte digest with BIP340 Schnorr, eg the id of a nostr event
+    pub fn sign_schnorr(&self, digest: &[u8]) -> Result<Vec<u8>, KeyError> {
+        self.sign_schnorr_with(digest, &OsEntropy)
+    }
+
+    /// sign a 32 byte digest with BIP340 Schnorr, the auxiliary random data
+    /// read from a source
+    pub fn sign_schnorr_with(
+        &self,
+        digest: &[u8],
+        source: &dyn EntropySource,
+    ) -> Result<Vec<u8>, KeyError> {
+        let message = Message::from_slice(digest).map_err(|e| KeyError::Other(e.to_string()))?;
+        let secp = Secp256k1::new();
+        let keypair = schnorrsig::KeyPair::from_seckey_slice(&secp, &self.secret)
+            .map_err(|e| KeyError::Other(e.to_string()))?;
+
+        let mut aux = [0u8; 32];
+        source.fill_bytes(&mut aux);
+        Ok(secp
+            .schnorrsig_sign_with_aux_rand(&message, &keypair, &aux)
+            .as_ref()
+            .to_vec())
+    }
+
+    /// sign a 32 byte digest with ECDSA, returning the DER encoded signature
+    pub fn sign_ecdsa(&self, digest: &[u8]) -> Result<Vec<u8>, KeyError> {
+        let message = Message::from_slice(digest).map_err(|e| KeyError::Other(e.to_string()))?;
+        Ok(Secp256k1::new()
+            .sign(&message, &self.secret_key())
+            .serialize_der()
+            .to_vec())
+    }
+
+    /// the public key as a nostr `npub` (NIP-19)
+    pub fn to_npub(&self) -> Result<String, KeyError> {
+        bech32::encode(
+            "npub",
+            self.x_only_public_key().to_base32(),
+            Variant::Bech32,
+        )
+        .map_err(|e| KeyError::Other(e.to_string()))
+    }
+
+    /// Export the private key as a nostr `nsec` (NIP-19), to import it in a
+    /// nostr client. Anyone holding it can sign as the key
+    pub fn export_nsec(&self) -> Result<String, KeyError> {
+        bech32::encode("nsec", self.secret.to_base32(), Variant::Bech32)
+            .map_err(|e| KeyError::Other(e.to_string()))
+    }
+
+    fn secret_key(&self) -> SecretKey {
+        // checked when derived
+        SecretKey::from_slice(&self.secret).unwrap()
+    }
+}
+
+impl Drop for AppKey {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+/// the private key is redacted
+impl fmt::Debug for AppKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AppKey")
+            .field("secret", &REDACTED)
+            .field("tag", &self.tag)
+            .finish()
+    }
+}
+
+impl Key {
+    /// Derive a key for another protocol than bitcoin, so the keys of the
+    /// wallet are never reused outside of it. Call it on the master key, the
+    /// same key and tag always give the same app key.
+    ///
+    /// The tag picks a hardened BIP85 application, the first 31 bits of
+    /// `tagged_hash("waller/app-key", tag)`, and the app key is the BIP85
+    /// entropy of the key at `m/83696968'/<application>'/0'`, the first 32
+    /// bytes of `HMAC-SHA512("bip-entropy-from-k", k)`
+    pub fn derive_app_key(&self, tag: &str) -> Result<AppKey, KeyError> {
+        if tag.is_empty() {
+            return Err(KeyError::Other("an app key needs a tag".to_string()));
+        }
+        let hash = tagged_hash("waller/app-key", tag.as_bytes());
+        let application = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]) >> 1;
+
+        let mut key = self.clone();
+        for index in [BIP85_PURPOSE, application, 0] {
+            key = key.derive_child_private_key(ChildNumber::Hardened(index))?;
+        }
+
+        let mut entropy = bip85_entropy(&key);
+        let mut secret = [0u8; 32];
+        secret.copy_from_slice(&entropy[..32]);
+        entropy.zeroize();
+        // an entropy past the curve order is too unlikely to derive around
+        SecretKey::from_slice(&secret).map_err(|e| KeyError::Other(e.to_string()))?;
+
+        Ok(AppKey {
+            secret,
+            tag: tag.to_string(),
+        })
+    }
+}
+
+/// the 64 bytes of BIP85 entropy of a key derived under [BIP85_PURPOSE]
+pub(crate) fn bip85_entropy(key: &Key) -> Vec<u8> {
+    hmac_sha512_hash(&key.bytes().to_vec(), &b"bip-entropy-from-k".to_vec())
+}
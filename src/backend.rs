@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BackendError, FeeHistogram, OutPoint, PackageSubmission, TransactionOutput};
+
+/// A connection to the bitcoin network used to relay transactions
+/// and, for backends that can, read the chain
+pub trait Backend {
+    /// relay a hex encoded transaction, returns its txid
+    fn broadcast(&self, raw_tx: &str) -> Result<String, BackendError>;
+
+    /// relay hex encoded transactions as a single package, parents before children.
+    /// backends without package relay return [BackendError::Unsupported]
+    fn submit_package(&self, raw_txs: &[String]) -> Result<PackageSubmission, BackendError> {
+        let _ = raw_txs;
+        Err(BackendError::Unsupported("submitpackage".to_string()))
+    }
+
+    /// Check whether the mempool would accept a hex encoded transaction,
+    /// without relaying it. Backends without a mempool to ask return
+    /// [BackendError::Unsupported]
+    fn test_accept(&self, raw_tx: &str) -> Result<MempoolAcceptance, BackendError> {
+        let _ = raw_tx;
+        Err(BackendError::Unsupported("testmempoolaccept".to_string()))
+    }
+
+    /// The fee rates paid by the transactions of the mempool, to estimate
+    /// fees and tell how congested the network is. Backends without a
+    /// mempool to read return [BackendError::Unsupported]
+    fn mempool_histogram(&self) -> Result<FeeHistogram, BackendError> {
+        Err(BackendError::Unsupported("mempool_histogram".to_string()))
+    }
+
+    /// the height of the chain tip, backends that can't
+    /// read the chain return [BackendError::Unsupported]
+    fn tip_height(&self) -> Result<u32, BackendError> {
+        Err(BackendError::Unsupported("tip_height".to_string()))
+    }
+
+    /// the block at a height of the best chain, backends that can't
+    /// read the chain return [BackendError::Unsupported]
+    fn block(&self, height: u32) -> Result<Block, BackendError> {
+        let _ = height;
+        Err(BackendError::Unsupported("block".to_string()))
+    }
+
+    /// The hex encoded transaction with a txid, eg one paying a coin whose
+    /// value its spends don't sign for. Backends that can't look transactions
+    /// up return [BackendError::Unsupported]
+    fn transaction(&self, tx_id: &str) -> Result<String, BackendError> {
+        let _ = tx_id;
+        Err(BackendError::Unsupported("getrawtransaction".to_string()))
+    }
+
+    /// The [crate::silent_payment_tweak] of the transactions of the block at a
+    /// height that can hold silent payments, by txid, read by wallets scanning
+    /// for them. Backends that can't read the outputs spent by the inputs
+    /// return [BackendError::Unsupported]
+    fn silent_payment_tweaks(&self, height: u32) -> Result<HashMap<String, Vec<u8>>, BackendError> {
+        let _ = height;
+        Err(BackendError::Unsupported(
+            "silent_payment_tweaks".to_string(),
+        ))
+    }
+
+    /// The hash of the block at a height of the best chain, compared with
+    /// the blocks scanned to tell the chain reorganized. Reads the whole
+    /// block unless the backend can read the hash alone
+    fn block_hash(&self, height: u32) -> Result<String, BackendError> {
+        self.block(height).map(|block| block.hash)
+    }
+}
+
+/// The answer of a backend to [Backend::test_accept]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MempoolAcceptance {
+    pub txid: String,
+    pub allowed: bool,
+    /// the virtual size counted by the node, when accepted
+    pub vsize: Option<u64>,
+    /// the fee in satoshis counted by the node, when accepted
+    pub fee: Option<i64>,
+    /// why it was rejected, eg `min relay fee not met`
+    pub reject_reason: Option<String>,
+}
+
+/// A block scanned by a wallet, see [crate::Wallet::sync]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BlockId {
+    pub height: u32,
+    pub hash: String,
+}
+
+/// A block of the chain, with what a wallet needs of its transactions
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub height: u32,
+    pub hash: String,
+    /// the timestamp of the block header, unix seconds
+    pub time: u64,
+    pub transactions: Vec<BlockTransaction>,
+}
+
+/// A transaction of a [Block], the outputs it spends and creates
+#[derive(Debug, Clone)]
+pub struct BlockTransaction {
+    pub tx_id: String,
+    /// the outputs spent by the inputs, empty for a coinbase
+    pub inputs: Vec<OutPoint>,
+    pub outputs: Vec<TransactionOutput>,
+}
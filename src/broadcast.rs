@@ -0,0 +1,134 @@
+use std::{fmt, thread};
+
+use crate::{Backend, BackendError, Transaction};
+
+/// Relays transactions to several backends at once, so a single broken or
+/// censoring server can't keep a transaction from the network
+#[derive(Default)]
+pub struct MultiBroadcaster {
+    backends: Vec<(String, Box<dyn Backend + Send + Sync>)>,
+}
+
+impl fmt::Debug for MultiBroadcaster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self
+            .backends
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        f.debug_struct("MultiBroadcaster")
+            .field("backends", &names)
+            .finish()
+    }
+}
+
+/// What a backend answered to a [MultiBroadcaster]
+#[derive(Debug, Clone)]
+pub struct BackendBroadcast {
+    /// the name the backend was added with
+    pub backend: String,
+    /// the txid the backend relayed
+    pub result: Result<String, BackendError>,
+}
+
+/// The answers of the backends of a [MultiBroadcaster] to a transaction,
+/// in the order they were added
+#[derive(Debug, Clone)]
+pub struct BroadcastReport {
+    pub results: Vec<BackendBroadcast>,
+}
+
+impl BroadcastReport {
+    /// whether a backend relayed the transaction
+    pub fn is_success(&self) -> bool {
+        self.results.iter().any(|result| result.result.is_ok())
+    }
+
+    /// the txid given by the first backend that relayed the transaction
+    pub fn txid(&self) -> Option<&str> {
+        self.results
+            .iter()
+            .find_map(|result| result.result.as_deref().ok())
+    }
+
+    /// the backends that relayed the transaction
+    pub fn accepted(&self) -> Vec<&str> {
+        self.results
+            .iter()
+            .filter(|result| result.result.is_ok())
+            .map(|result| result.backend.as_str())
+            .collect()
+    }
+
+    /// the backends that failed to, with their error
+    pub fn failed(&self) -> Vec<(&str, &BackendError)> {
+        self.results
+            .iter()
+            .filter_map(|result| match &result.result {
+                Ok(_) => None,
+                Err(e) => Some((result.backend.as_str(), e)),
+            })
+            .collect()
+    }
+}
+
+impl MultiBroadcaster {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// relay to a backend too, named in the reports
+    pub fn add_backend(
+        &mut self,
+        name: &str,
+        backend: Box<dyn Backend + Send + Sync>,
+    ) -> &mut Self {
+        self.backends.push((name.to_string(), backend));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.backends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.backends.is_empty()
+    }
+
+    /// relay a signed transaction to every backend, see [MultiBroadcaster::broadcast_raw]
+    pub fn broadcast(&self, tx: &Transaction) -> BroadcastReport {
+        self.broadcast_raw(&tx.to_hex())
+    }
+
+    /// Relay a hex encoded transaction to every backend at once, waiting
+    /// for all of them to answer. The report tells which relayed it
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, fields(backends = self.backends.len()))
+    )]
+    pub fn broadcast_raw(&self, raw_tx: &str) -> BroadcastReport {
+        let results = thread::scope(|scope| {
+            let relays: Vec<_> = self
+                .backends
+                .iter()
+                .map(|(name, backend)| (name, scope.spawn(move || backend.broadcast(raw_tx))))
+                .collect();
+
+            relays
+                .into_iter()
+                .map(|(name, relay)| {
+                    let result = relay.join().unwrap_or_else(|_| {
+                        Err(BackendError::Connection("the backend panicked".to_string()))
+                    });
+                    debug!(backend = %name, result = ?result, "broadcast");
+                    BackendBroadcast {
+                        backend: name.clone(),
+                        result,
+                    }
+                })
+                .collect()
+        });
+
+        BroadcastReport { results }
+    }
+}
@@ -0,0 +1,575 @@
+use std::{collections::VecDeque, fmt, sync::Arc, time::Duration};
+
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compact_size, estimate_mixed_vsize, validate_address, AddressProblem, Clock, EntropyRng,
+    EntropySource, LockTime, Network, OsEntropy, Script, ScriptType, SystemClock, Transaction,
+    TransactionError, TransactionInput, TransactionOutput, TransactionType, TransactionVersion,
+    DUST_LIMIT, FINAL_SEQUENCE,
+};
+
+/// the highest fee rate accepted by default in satoshis per vbyte, Bitcoin Core's `maxfeerate` of 0.1 BTC/kvB
+pub const DEFAULT_MAX_FEE_RATE: u64 = 10_000;
+
+/// the highest fee accepted by default in satoshis, Bitcoin Core's `maxtxfee` of 0.1 BTC
+pub const DEFAULT_MAX_ABSOLUTE_FEE: i64 = 10_000_000;
+
+/// Bounds on the fee of a built transaction, catching amounts given in the
+/// wrong unit, eg bitcoins for satoshis, before the coins are spent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FeeLimits {
+    /// satoshis per vbyte, none to accept any rate
+    pub max_fee_rate: Option<u64>,
+    /// satoshis, none to accept any fee
+    pub max_absolute_fee: Option<i64>,
+}
+
+impl FeeLimits {
+    /// accept any fee
+    pub fn none() -> Self {
+        Self {
+            max_fee_rate: None,
+            max_absolute_fee: None,
+        }
+    }
+
+    /// Check the fee of a transaction, its rate taken over its size estimated once signed.
+    /// Transactions spending less than they create are left to be rejected by the network
+    pub fn check(&self, tx: &Transaction) -> Result<(), TransactionError> {
+        let fee = tx.fee();
+        if let Some(limit) = self.max_absolute_fee {
+            if fee > limit {
+                return Err(TransactionError::AbsurdFee { fee, limit });
+            }
+        }
+
+        if let Some(limit) = self.max_fee_rate {
+            let vsize = tx.estimate_vsize().max(1);
+            if fee > 0 && fee as u64 > limit.saturating_mul(vsize) {
+                return Err(TransactionError::AbsurdFeeRate {
+                    rate: fee as u64 / vsize,
+                    limit,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for FeeLimits {
+    fn default() -> Self {
+        Self {
+            max_fee_rate: Some(DEFAULT_MAX_FEE_RATE),
+            max_absolute_fee: Some(DEFAULT_MAX_ABSOLUTE_FEE),
+        }
+    }
+}
+
+/// the most a transaction relayed by Bitcoin Core may weigh, its `MAX_STANDARD_TX_WEIGHT`
+pub const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+
+/// An address to pay an amount in satoshis to, see [TransactionBuilder::add_recipients].
+/// A recipient subtracting the fee receives its amount less its share of
+/// the fee, eg the withdrawal of an exchange, instead of the sender paying it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient {
+    pub address: String,
+    pub amount: i64,
+    pub subtract_fee_from: bool,
+}
+
+impl Recipient {
+    pub fn new(address: &str, amount: i64) -> Self {
+        Self {
+            address: address.to_string(),
+            amount,
+            subtract_fee_from: false,
+        }
+    }
+
+    /// pay the fee out of the amount of this recipient, shared with the
+    /// others paying it
+    pub fn subtracting_fee(mut self) -> Self {
+        self.subtract_fee_from = true;
+        self
+    }
+}
+
+impl<S: AsRef<str>> From<(S, i64)> for Recipient {
+    fn from((address, amount): (S, i64)) -> Self {
+        Recipient::new(address.as_ref(), amount)
+    }
+}
+
+/// Why a recipient given to [TransactionBuilder::add_recipients] was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipientProblem {
+    /// the address can't be paid to on the network
+    Address(AddressProblem),
+    /// an amount of zero or less
+    InvalidAmount(i64),
+    /// an amount below [DUST_LIMIT], which nodes don't relay
+    Dust(i64),
+}
+
+impl fmt::Display for RecipientProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecipientProblem::Address(problem) => problem.fmt(f),
+            RecipientProblem::InvalidAmount(amount) => write!(f, "an amount of {}", amount),
+            RecipientProblem::Dust(amount) => {
+                write!(f, "{} is below the dust limit of {}", amount, DUST_LIMIT)
+            }
+        }
+    }
+}
+
+/// A recipient left out by [TransactionBuilder::add_recipients]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedRecipient {
+    /// the position of the recipient in the ones given, counting from 0
+    pub index: usize,
+    pub address: String,
+    pub amount: i64,
+    pub problem: RecipientProblem,
+}
+
+/// A transaction of [TransactionBuilder::build_batches], with what it pays
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub tx: Transaction,
+    /// how many outputs of the builder it pays, its change output aside
+    pub recipients: usize,
+    /// the satoshis paid to those outputs
+    pub amount: i64,
+    /// the fee in satoshis, with the change too small to keep
+    pub fee: i64,
+    /// the virtual size of the transaction once signed
+    pub vsize: u64,
+}
+
+/// The inputs and outputs of a transaction of [TransactionBuilder::build_batches]
+/// being filled, with the sums its size and fee are estimated from
+#[derive(Default)]
+struct PendingBatch {
+    inputs: Vec<TransactionInput>,
+    input_types: Vec<ScriptType>,
+    spent: i64,
+    outputs: Vec<TransactionOutput>,
+    paid: i64,
+    /// the serialized size of the outputs, kept to size hundreds of them
+    /// without serializing them again for each one added
+    outputs_len: u64,
+}
+
+impl PendingBatch {
+    fn push_input(&mut self, input: TransactionInput) {
+        self.input_types
+            .push(Script::new(input.utxo_pk_script().to_vec()).classify());
+        self.spent += input.utxo_value();
+        self.inputs.push(input);
+    }
+
+    fn pop_input(&mut self) -> TransactionInput {
+        let input = self.inputs.pop().expect("an input was pushed");
+        self.input_types.pop();
+        self.spent -= input.utxo_value();
+        input
+    }
+
+    fn push_output(&mut self, output: &TransactionOutput) {
+        self.paid += output.value();
+        self.outputs_len += output.serialize().len() as u64;
+        self.outputs.push(output.clone());
+    }
+
+    fn pop_output(&mut self) {
+        if let Some(output) = self.outputs.pop() {
+            self.paid -= output.value();
+            self.outputs_len -= output.serialize().len() as u64;
+        }
+    }
+
+    /// the virtual size once signed with a change output, as [estimate_mixed_vsize]
+    /// gives it, which only gets smaller when the change is dropped
+    fn vsize(&self, tx_type: &TransactionType, change: &TransactionOutput) -> u64 {
+        let count = compact_size(self.outputs.len() + 1).len() as u64;
+        estimate_mixed_vsize(tx_type, &self.input_types, std::slice::from_ref(change))
+            + self.outputs_len
+            + count
+            - 1
+    }
+
+    /// the satoshis the inputs are short of the outputs and the fee, below
+    /// zero for what's left to the change
+    fn missing(&self, tx_type: &TransactionType, change: &TransactionOutput, fee_rate: u64) -> i64 {
+        self.paid + (self.vsize(tx_type, change) * fee_rate) as i64 - self.spent
+    }
+}
+
+/// How the inputs and outputs of a built transaction are ordered.
+/// Keeping insertion order leaks which output is change, since
+/// wallets tend to add it last
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TxOrdering {
+    /// keep the order inputs and outputs were added in
+    Insertion,
+    /// BIP69 lexicographic ordering, deterministic for a set of inputs and outputs
+    Bip69,
+    /// shuffle inputs and outputs, as Bitcoin Core does
+    #[default]
+    Shuffle,
+}
+
+/// Assembles a [Transaction] from inputs and outputs
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    tx_type: TransactionType,
+    version: TransactionVersion,
+    inputs: Vec<TransactionInput>,
+    outputs: Vec<TransactionOutput>,
+    lock_time: Option<LockTime>,
+    ordering: TxOrdering,
+    /// the time [TransactionBuilder::lock_time_after] counts from
+    clock: Arc<dyn Clock>,
+    /// what inputs and outputs are shuffled and fee sniping heights picked with
+    entropy: Arc<dyn EntropySource>,
+    fee_limits: FeeLimits,
+    anti_fee_snipe: bool,
+    /// the height of the chain tip, for [TransactionBuilder::anti_fee_snipe]
+    tip_height: Option<u32>,
+    /// the outputs of the recipients subtracting the fee, by position
+    fee_payers: Vec<usize>,
+}
+
+impl TransactionBuilder {
+    pub fn new(tx_type: TransactionType) -> Self {
+        Self {
+            tx_type,
+            version: TransactionVersion::One,
+            inputs: vec![],
+            outputs: vec![],
+            lock_time: None,
+            ordering: TxOrdering::default(),
+            clock: Arc::new(SystemClock),
+            entropy: Arc::new(OsEntropy),
+            fee_limits: FeeLimits::default(),
+            anti_fee_snipe: false,
+            tip_height: None,
+            fee_payers: vec![],
+        }
+    }
+
+    pub fn add_input(&mut self, input: TransactionInput) -> &mut Self {
+        self.inputs.push(input);
+        self
+    }
+
+    pub fn add_output(&mut self, output: TransactionOutput) -> &mut Self {
+        self.outputs.push(output);
+        self
+    }
+
+    pub fn version(&mut self, version: TransactionVersion) -> &mut Self {
+        self.version = version;
+        self
+    }
+
+    pub fn lock_time(&mut self, lock_time: LockTime) -> &mut Self {
+        self.lock_time = Some(lock_time);
+        self
+    }
+
+    /// lock the transaction until a duration from now by the builder's clock
+    pub fn lock_time_after(&mut self, duration: Duration) -> &mut Self {
+        let time = self.clock.now().saturating_add(duration.as_secs());
+        self.lock_time(LockTime::Seconds(time.min(u32::MAX as u64) as u32))
+    }
+
+    /// change the clock the current time is read from, the system clock by default
+    pub fn clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.clock = clock;
+        self
+    }
+
+    /// change the source of the randomness of the transaction, the os by default
+    pub fn entropy(&mut self, entropy: Arc<dyn EntropySource>) -> &mut Self {
+        self.entropy = entropy;
+        self
+    }
+
+    /// change how inputs and outputs are ordered, shuffled by default
+    pub fn ordering(&mut self, ordering: TxOrdering) -> &mut Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// change the bounds on the fee of the transaction, [FeeLimits::default] unless set
+    pub fn fee_limits(&mut self, fee_limits: FeeLimits) -> &mut Self {
+        self.fee_limits = fee_limits;
+        self
+    }
+
+    /// Pay many addresses, eg the withdrawals of an exchange, each a
+    /// [Recipient] or an address and an amount in satoshis. Every recipient
+    /// is checked on its own and the valid ones are added as outputs, the
+    /// others are returned with their problem
+    pub fn add_recipients<I, R>(
+        &mut self,
+        recipients: I,
+        network: Network,
+    ) -> Vec<RejectedRecipient>
+    where
+        I: IntoIterator<Item = R>,
+        R: Into<Recipient>,
+    {
+        let mut rejected = vec![];
+        for (index, recipient) in recipients.into_iter().enumerate() {
+            let Recipient {
+                address,
+                amount,
+                subtract_fee_from,
+            } = recipient.into();
+            let validation = validate_address(&address, network);
+            let problem = match (validation.problem, validation.script) {
+                (Some(problem), _) => RecipientProblem::Address(problem),
+                _ if amount <= 0 => RecipientProblem::InvalidAmount(amount),
+                _ if amount < DUST_LIMIT => RecipientProblem::Dust(amount),
+                (None, Some(script)) => {
+                    if subtract_fee_from {
+                        self.subtract_fee_from(self.outputs.len());
+                    }
+                    self.add_output(TransactionOutput::from_script(amount, script.into_bytes()));
+                    continue;
+                }
+                (None, None) => RecipientProblem::Address(AddressProblem::Encoding),
+            };
+            rejected.push(RejectedRecipient {
+                index,
+                address,
+                amount,
+                problem,
+            });
+        }
+        rejected
+    }
+
+    /// have the output at a position pay part of the fee, see [TransactionBuilder::subtract_fee]
+    pub fn subtract_fee_from(&mut self, output: usize) -> &mut Self {
+        if !self.fee_payers.contains(&output) {
+            self.fee_payers.push(output);
+        }
+        self
+    }
+
+    /// whether an output pays the fee, see [TransactionBuilder::subtract_fee]
+    pub fn subtracts_fee(&self) -> bool {
+        !self.fee_payers.is_empty()
+    }
+
+    /// Take a fee in satoshis out of the outputs of the recipients subtracting
+    /// it, split evenly with what's left over taken from the first, as Bitcoin
+    /// Core does. Fails when no recipient subtracts the fee or when one
+    /// would be left under [DUST_LIMIT]
+    pub fn subtract_fee(&mut self, fee: i64) -> Result<&mut Self, TransactionError> {
+        let payers = self.fee_payers.len() as i64;
+        if payers == 0 {
+            return Err(TransactionError::FeeExceedsRecipients(fee));
+        }
+        let share = fee / payers;
+        let mut outputs = self.outputs.clone();
+        for (position, payer) in self.fee_payers.iter().enumerate() {
+            let output = match outputs.get_mut(*payer) {
+                Some(output) => output,
+                None => return Err(TransactionError::FeeExceedsRecipients(fee)),
+            };
+            let share = match position {
+                0 => share + fee % payers,
+                _ => share,
+            };
+            let value = output.value() - share;
+            if value < DUST_LIMIT {
+                return Err(TransactionError::FeeExceedsRecipients(fee));
+            }
+            *output = TransactionOutput::from_script(value, output.pk_script().to_vec());
+        }
+        self.outputs = outputs;
+        Ok(self)
+    }
+
+    /// build the transactions paying the outputs, see [TransactionBuilder::build_batches_with_rng]
+    pub fn build_batches(
+        &self,
+        fee_rate: u64,
+        change_script: &[u8],
+    ) -> Result<Vec<Batch>, TransactionError> {
+        let mut rng = EntropyRng::new(&*self.entropy);
+        self.build_batches_with_rng(fee_rate, change_script, &mut rng)
+    }
+
+    /// Build as many transactions as needed to pay the outputs while each stays
+    /// under [MAX_STANDARD_TX_WEIGHT]. Outputs are paid in the order they were
+    /// added and the inputs are spent in theirs, each transaction taking the
+    /// next ones until its outputs and fee, at a rate in satoshis per vbyte,
+    /// are covered. Inputs not needed are left out. What's left goes to an
+    /// output of the change script, or to the fee when it would be dust
+    pub fn build_batches_with_rng<R: Rng + ?Sized>(
+        &self,
+        fee_rate: u64,
+        change_script: &[u8],
+        rng: &mut R,
+    ) -> Result<Vec<Batch>, TransactionError> {
+        let change = TransactionOutput::from_script(0, change_script.to_vec());
+        let mut batches = vec![];
+        let mut coins: VecDeque<TransactionInput> = self.inputs.iter().cloned().collect();
+        let mut batch = PendingBatch::default();
+
+        let mut next = self.outputs.iter().peekable();
+        while let Some(output) = next.peek() {
+            batch.push_output(output);
+            let mut taken = 0;
+            while batch.missing(&self.tx_type, &change, fee_rate) > 0 {
+                match coins.pop_front() {
+                    Some(coin) => batch.push_input(coin),
+                    None => break,
+                }
+                taken += 1;
+            }
+
+            let missing = batch.missing(&self.tx_type, &change, fee_rate);
+            let weight = batch.vsize(&self.tx_type, &change) * 4;
+            if missing <= 0 && weight <= MAX_STANDARD_TX_WEIGHT {
+                next.next();
+                continue;
+            }
+            if batch.outputs.len() == 1 {
+                return Err(match missing > 0 {
+                    true => TransactionError::InsufficientFunds(missing),
+                    false => TransactionError::NonStandardWeight(weight),
+                });
+            }
+
+            // the output starts the next transaction, with the inputs it took
+            batch.pop_output();
+            for _ in 0..taken {
+                coins.push_front(batch.pop_input());
+            }
+            let full = std::mem::take(&mut batch);
+            batches.push(self.batch(full, &change, fee_rate, rng)?);
+        }
+        if !batch.outputs.is_empty() {
+            batches.push(self.batch(batch, &change, fee_rate, rng)?);
+        }
+
+        Ok(batches)
+    }
+
+    /// build a transaction of [TransactionBuilder::build_batches], with its change
+    fn batch<R: Rng + ?Sized>(
+        &self,
+        batch: PendingBatch,
+        change: &TransactionOutput,
+        fee_rate: u64,
+        rng: &mut R,
+    ) -> Result<Batch, TransactionError> {
+        let left = -batch.missing(&self.tx_type, change, fee_rate);
+        let (recipients, amount) = (batch.outputs.len(), batch.paid);
+
+        let mut builder = self.clone();
+        builder.inputs = batch.inputs;
+        builder.outputs = batch.outputs;
+        if left >= DUST_LIMIT {
+            builder.add_output(TransactionOutput::from_script(
+                left,
+                change.pk_script().to_vec(),
+            ));
+        }
+
+        let tx = builder.build_with_rng(rng)?;
+        Ok(Batch {
+            recipients,
+            amount,
+            fee: tx.fee(),
+            vsize: tx.estimate_vsize(),
+            tx,
+        })
+    }
+
+    /// Lock the transaction to the height of the chain tip, as Bitcoin Core
+    /// and Electrum do, so a miner can't take its fee by mining the tip again.
+    /// Needs the [TransactionBuilder::tip_height], a lock time set with
+    /// [TransactionBuilder::lock_time] is kept. Off unless set
+    pub fn anti_fee_snipe(&mut self, enabled: bool) -> &mut Self {
+        self.anti_fee_snipe = enabled;
+        self
+    }
+
+    /// the height of the chain tip as read from a backend, see [TransactionBuilder::anti_fee_snipe]
+    pub fn tip_height(&mut self, height: u32) -> &mut Self {
+        self.tip_height = Some(height);
+        self
+    }
+
+    /// build the transaction, shuffling with the entropy of the builder if needed
+    pub fn build(&self) -> Result<Transaction, TransactionError> {
+        self.build_with_rng(&mut EntropyRng::new(&*self.entropy))
+    }
+
+    /// Build the transaction, shuffling with the given rng if needed.
+    /// Fails when its fee is over the [FeeLimits] of the builder
+    pub fn build_with_rng<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Result<Transaction, TransactionError> {
+        let mut inputs = self.inputs.clone();
+        let mut outputs = self.outputs.clone();
+
+        match self.ordering {
+            TxOrdering::Insertion => {}
+            TxOrdering::Bip69 => {
+                inputs.sort_by_cached_key(|input| {
+                    let outpoint = input.previous_output();
+                    (
+                        hex::decode(outpoint.hash()).unwrap_or_default(),
+                        outpoint.index() as u32,
+                    )
+                });
+                outputs.sort_by(|a, b| {
+                    a.value()
+                        .cmp(&b.value())
+                        .then_with(|| a.pk_script().cmp(b.pk_script()))
+                });
+            }
+            TxOrdering::Shuffle => {
+                inputs.shuffle(rng);
+                outputs.shuffle(rng);
+            }
+        }
+
+        let mut lock_time = self.lock_time;
+        if self.anti_fee_snipe && lock_time.is_none() {
+            let tip = self.tip_height.ok_or(TransactionError::UnknownTipHeight)?;
+            // now and then a height further back, as Core does, so the transactions
+            // held back before being broadcast don't stand out
+            let height = match rng.gen_range(0..10) {
+                0 => tip.saturating_sub(rng.gen_range(0..100)),
+                _ => tip,
+            };
+            lock_time = Some(LockTime::Blocks(height));
+            // the lock time only applies once an input isn't final
+            for input in inputs.iter_mut() {
+                if input.sequence() == FINAL_SEQUENCE {
+                    input.set_sequence(FINAL_SEQUENCE - 1);
+                }
+            }
+        }
+
+        let mut tx = Transaction::new(self.tx_type.clone(), inputs, outputs, lock_time);
+        tx.set_version(self.version.clone());
+        self.fee_limits.check(&tx)?;
+        Ok(tx)
+    }
+}
@@ -0,0 +1,217 @@
+use std::{env, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{KdfParams, WalletError, WALLET_FILE_NAME};
+
+/// the directory made for the wallet in the platform data and cache directories
+pub const APP_DIR_NAME: &str = "waller";
+
+/// the name of the file the utxos and history of a wallet are flushed to
+/// inside its cache directory
+pub const CACHE_FILE_NAME: &str = "cache.json";
+
+/// the name of the cache file of wallets using [CacheFormat::Cbor]
+pub const COMPACT_CACHE_FILE_NAME: &str = "cache.cbor";
+
+/// How the utxos and history of a wallet are written to its cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheFormat {
+    /// json in [CACHE_FILE_NAME]
+    #[default]
+    Json,
+    /// CBOR in [COMPACT_CACHE_FILE_NAME], smaller and faster to write for
+    /// wallets with many utxos. Needs the `compact-cache` feature
+    Cbor,
+}
+
+impl CacheFormat {
+    /// the name of the cache file in this format
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Self::Json => CACHE_FILE_NAME,
+            Self::Cbor => COMPACT_CACHE_FILE_NAME,
+        }
+    }
+}
+
+/// Where a wallet is kept on disk. The wallet file, holding the keys and
+/// accounts, is written to the data directory. The utxos and history are
+/// written to the cache directory, they can be rebuilt with [crate::Wallet::rescan].
+/// Both are the same directory unless set apart. The [KdfParams] set how hard
+/// the passphrase of the wallet is to guess, and to unlock the wallet with
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "StoredConfig")]
+pub struct WalletConfig {
+    data_dir: PathBuf,
+    cache_dir: PathBuf,
+    cache_format: CacheFormat,
+    kdf: KdfParams,
+}
+
+impl WalletConfig {
+    /// keep the wallet file and its cache in a single directory
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            cache_dir: data_dir.clone(),
+            data_dir,
+            cache_format: CacheFormat::Json,
+            kdf: KdfParams::default(),
+        }
+    }
+
+    /// The directories of the platform, under [APP_DIR_NAME]:
+    /// - Linux: `$XDG_DATA_HOME` and `$XDG_CACHE_HOME`, `~/.local/share` and `~/.cache` by default
+    /// - macOS: `~/Library/Application Support` and `~/Library/Caches`
+    /// - Windows: `%APPDATA%` and `%LOCALAPPDATA%`
+    pub fn platform() -> Result<Self, WalletError> {
+        Self::platform_with(env::consts::OS, |name| env::var(name).ok())
+    }
+
+    /// the directories of an os, reading its environment with a function
+    pub(crate) fn platform_with<F: Fn(&str) -> Option<String>>(
+        os: &str,
+        env: F,
+    ) -> Result<Self, WalletError> {
+        // relative paths are ignored, as the XDG spec asks
+        let var = |name: &str| {
+            env(name)
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+        };
+        let home = var("HOME");
+
+        let dirs = match os {
+            "macos" => home.map(|home| {
+                (
+                    home.join("Library").join("Application Support"),
+                    home.join("Library").join("Caches"),
+                )
+            }),
+            "windows" => var("APPDATA").map(|data| {
+                let cache = var("LOCALAPPDATA").unwrap_or_else(|| data.clone());
+                (data, cache)
+            }),
+            _ => {
+                let data = var("XDG_DATA_HOME")
+                    .or_else(|| home.as_ref().map(|home| home.join(".local").join("share")));
+                let cache =
+                    var("XDG_CACHE_HOME").or_else(|| home.as_ref().map(|home| home.join(".cache")));
+                data.zip(cache)
+            }
+        };
+
+        match dirs {
+            Some((data, cache)) => Ok(Self {
+                data_dir: data.join(APP_DIR_NAME),
+                cache_dir: cache.join(APP_DIR_NAME),
+                cache_format: CacheFormat::Json,
+                kdf: KdfParams::default(),
+            }),
+            None => Err(WalletError::Read(format!(
+                "No data directory found for {}",
+                os
+            ))),
+        }
+    }
+
+    /// keep the wallet file in another directory
+    pub fn with_data_dir(&self, data_dir: PathBuf) -> Self {
+        Self {
+            data_dir,
+            ..self.clone()
+        }
+    }
+
+    /// keep the utxos and history in another directory
+    pub fn with_cache_dir(&self, cache_dir: PathBuf) -> Self {
+        Self {
+            cache_dir,
+            ..self.clone()
+        }
+    }
+
+    /// write the cache in another format, the cache of the old format is removed on flush
+    pub fn with_cache_format(&self, cache_format: CacheFormat) -> Self {
+        Self {
+            cache_format,
+            ..self.clone()
+        }
+    }
+
+    /// Derive the wallet key with other parameters, eg from [crate::calibrate_kdf].
+    /// They apply when the wallet is encrypted or its passphrase changed
+    pub fn with_kdf(&self, kdf: KdfParams) -> Self {
+        Self {
+            kdf,
+            ..self.clone()
+        }
+    }
+
+    pub fn data_dir(&self) -> &PathBuf {
+        &self.data_dir
+    }
+
+    pub fn cache_dir(&self) -> &PathBuf {
+        &self.cache_dir
+    }
+
+    /// the path of the [WALLET_FILE_NAME] in the data directory
+    pub fn wallet_file(&self) -> PathBuf {
+        self.data_dir.join(WALLET_FILE_NAME)
+    }
+
+    pub fn cache_format(&self) -> CacheFormat {
+        self.cache_format
+    }
+
+    pub fn kdf(&self) -> KdfParams {
+        self.kdf
+    }
+
+    /// the path of the cache file of its [CacheFormat] in the cache directory
+    pub fn cache_file(&self) -> PathBuf {
+        self.cache_dir.join(self.cache_format.file_name())
+    }
+}
+
+impl From<PathBuf> for WalletConfig {
+    fn from(data_dir: PathBuf) -> Self {
+        Self::new(data_dir)
+    }
+}
+
+/// a config as written to a wallet file, older files only hold the data directory
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredConfig {
+    Path(PathBuf),
+    Dirs {
+        data_dir: PathBuf,
+        cache_dir: PathBuf,
+        #[serde(default)]
+        cache_format: CacheFormat,
+        #[serde(default)]
+        kdf: KdfParams,
+    },
+}
+
+impl From<StoredConfig> for WalletConfig {
+    fn from(config: StoredConfig) -> Self {
+        match config {
+            StoredConfig::Path(data_dir) => Self::new(data_dir),
+            StoredConfig::Dirs {
+                data_dir,
+                cache_dir,
+                cache_format,
+                kdf,
+            } => Self {
+                data_dir,
+                cache_dir,
+                cache_format,
+                kdf,
+            },
+        }
+    }
+}
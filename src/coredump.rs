@@ -0,0 +1,275 @@
+use serde_json::Value;
+
+use crate::{verify_descriptor_checksum, AccountType, Key, WalletError};
+
+/// An entry of a Bitcoin Core dump that wasn't imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedEntry {
+    /// the line of a `dumpwallet` file, or the position of a descriptor
+    /// in a `listdescriptors` export, counting from 1
+    pub entry: usize,
+    pub reason: String,
+}
+
+/// What [crate::Wallet::import_core_dump] imported
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoreDumpImport {
+    /// the addresses of the keys imported, in the order of the dump
+    pub imported: Vec<String>,
+    /// entries without a private key the wallet can hold, eg scripts or
+    /// extended keys, in the order of the dump
+    pub skipped: Vec<SkippedEntry>,
+    /// the unix time in seconds of the oldest key imported, the wallet
+    /// birthday is moved back to it
+    pub earliest_key_time: Option<u64>,
+}
+
+/// a private key read from a dump, with the type of address Core used it for
+pub(crate) struct DumpedKey {
+    pub(crate) entry: usize,
+    pub(crate) key: Key,
+    pub(crate) account_type: AccountType,
+    pub(crate) label: Option<String>,
+    pub(crate) time: Option<u64>,
+}
+
+/// Read the keys of a `dumpwallet` file or of the json printed by
+/// `listdescriptors true`, told apart by their first character
+pub(crate) fn parse_core_dump(
+    contents: &str,
+) -> Result<(Vec<DumpedKey>, Vec<SkippedEntry>), WalletError> {
+    match contents.trim_start().chars().next() {
+        Some('{') | Some('[') => parse_descriptors(contents),
+        _ => Ok(parse_dumpwallet(contents)),
+    }
+}
+
+/// Lines of `dumpwallet` read `<wif> <time> <flags> # addr=<addresses>`,
+/// comments start with a `#`
+fn parse_dumpwallet(contents: &str) -> (Vec<DumpedKey>, Vec<SkippedEntry>) {
+    let mut keys = vec![];
+    let mut skipped = vec![];
+
+    for (index, line) in contents.lines().enumerate() {
+        let entry = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (fields, comment) = line.split_once('#').unwrap_or((line, ""));
+        let mut fields = fields.split_whitespace();
+        let (wif, time) = (fields.next().unwrap_or_default(), fields.next());
+        let flags: Vec<&str> = fields.collect();
+        if flags.contains(&"script=1") {
+            skipped.push(SkippedEntry {
+                entry,
+                reason: "a script without a key".to_string(),
+            });
+            continue;
+        }
+
+        let key = match Key::from_wif(wif.to_string()) {
+            Ok(key) => key,
+            Err(e) => {
+                skipped.push(SkippedEntry {
+                    entry,
+                    reason: format!("not a WIF key: {}", e),
+                });
+                continue;
+            }
+        };
+
+        // the first address listed that the key pays to, Core lists the one it handed out
+        let addresses = comment
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("addr="))
+            .unwrap_or_default();
+        let account_type = addresses
+            .split(',')
+            .find_map(|address| account_type_of(&key, address))
+            .unwrap_or(AccountType::Legacy);
+
+        keys.push(DumpedKey {
+            entry,
+            key,
+            account_type,
+            label: flags
+                .iter()
+                .find_map(|flag| flag.strip_prefix("label="))
+                .map(decode_dump_string),
+            time: time.and_then(parse_iso8601),
+        });
+    }
+
+    (keys, skipped)
+}
+
+/// the export of `listdescriptors true`, or the array of its descriptors
+fn parse_descriptors(contents: &str) -> Result<(Vec<DumpedKey>, Vec<SkippedEntry>), WalletError> {
+    let json: Value = serde_json::from_str(contents)
+        .map_err(|e| WalletError::Read(format!("Failed to deserialize descriptors: {}", e)))?;
+    let descriptors = match &json {
+        Value::Array(descriptors) => descriptors,
+        json => match &json["descriptors"] {
+            Value::Array(descriptors) => descriptors,
+            _ => {
+                return Err(WalletError::Read(
+                    "Failed to deserialize descriptors: no descriptors".to_string(),
+                ))
+            }
+        },
+    };
+
+    let mut keys = vec![];
+    let mut skipped = vec![];
+    for (index, descriptor) in descriptors.iter().enumerate() {
+        let entry = index + 1;
+        let mut skip = |reason: String| skipped.push(SkippedEntry { entry, reason });
+
+        let desc = match descriptor["desc"].as_str() {
+            Some(desc) => desc,
+            None => {
+                skip("no descriptor".to_string());
+                continue;
+            }
+        };
+        let desc = match verify_descriptor_checksum(desc) {
+            Ok(desc) => desc,
+            Err(e) => {
+                skip(e.to_string());
+                continue;
+            }
+        };
+
+        let (account_type, key) = match single_key_descriptor(desc) {
+            Some(parsed) => parsed,
+            None => {
+                skip(format!("not a single private key descriptor: {}", desc));
+                continue;
+            }
+        };
+        let key = match Key::from_wif(key.to_string()) {
+            Ok(key) => key,
+            Err(_) => {
+                skip(format!("not a single private key descriptor: {}", desc));
+                continue;
+            }
+        };
+
+        keys.push(DumpedKey {
+            entry,
+            key,
+            account_type,
+            label: descriptor["label"].as_str().map(str::to_string),
+            // `now` is written as a number once imported
+            time: descriptor["timestamp"].as_u64(),
+        });
+    }
+
+    Ok((keys, skipped))
+}
+
+/// the type and key of `pkh(K)`, `wpkh(K)`, `sh(wpkh(K))`, `tr(K)` or
+/// `combo(K)`, without the origin of the key
+fn single_key_descriptor(descriptor: &str) -> Option<(AccountType, &str)> {
+    let (account_type, key) = [
+        ("sh(wpkh(", "))", AccountType::NestedSegwit),
+        ("pkh(", ")", AccountType::Legacy),
+        ("wpkh(", ")", AccountType::NativeSegwit),
+        ("tr(", ")", AccountType::Taproot),
+        // combo pays to every type of the key, legacy first
+        ("combo(", ")", AccountType::Legacy),
+    ]
+    .iter()
+    .find_map(|(prefix, suffix, account_type)| {
+        let key = descriptor.strip_prefix(prefix)?.strip_suffix(suffix)?;
+        Some((*account_type, key))
+    })?;
+
+    let key = match key.split_once(']') {
+        Some((origin, key)) if origin.starts_with('[') => key,
+        Some(_) => return None,
+        None => key,
+    };
+    Some((account_type, key))
+}
+
+/// the type of address a key pays to, when it pays to it
+fn account_type_of(key: &Key, address: &str) -> Option<AccountType> {
+    [
+        AccountType::Legacy,
+        AccountType::NestedSegwit,
+        AccountType::NativeSegwit,
+        AccountType::Taproot,
+    ]
+    .iter()
+    .copied()
+    .find(|account_type| account_type.address(key).ok().as_deref() == Some(address))
+}
+
+/// undo the `%XX` escapes Core writes labels with
+fn decode_dump_string(encoded: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = match (byte, tail) {
+            (b'%', [high, low, ..]) => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Read a time written by Core as `YYYY-MM-DDTHH:MM:SSZ`, in unix seconds
+pub(crate) fn parse_iso8601(time: &str) -> Option<u64> {
+    let (date, time) = time.strip_suffix('Z')?.split_once('T')?;
+    let number = |field: Option<&str>| field?.parse::<i64>().ok();
+
+    let mut date = date.splitn(3, '-');
+    let (year, month, day) = (
+        number(date.next())?,
+        number(date.next())?,
+        number(date.next())?,
+    );
+    let mut time = time.splitn(3, ':');
+    let (hour, minute, second) = (
+        number(time.next())?,
+        number(time.next())?,
+        number(time.next())?,
+    );
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    // days since the epoch of the proleptic gregorian calendar, as in `days_from_civil`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second;
+    match seconds >= 0 {
+        true => Some(seconds as u64),
+        false => None,
+    }
+}
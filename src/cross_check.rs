@@ -0,0 +1,57 @@
+//! A second derivation of wallet addresses with rust-bitcoin, used by
+//! [crate::Wallet::verify_address] to catch derivation bugs in waller itself
+
+use bitcoin::{
+    bip32::{self, Xpriv},
+    secp256k1::{PublicKey, Secp256k1},
+    Address, CompressedPublicKey,
+};
+
+use crate::{AccountType, ChildNumber, KeyError, Network};
+
+/// Derive the address of an account type at a path of child indexes from a
+/// seed, the master key included, so a bug in any step of waller's derivation
+/// gives another address
+pub(crate) fn derive_address(
+    seed: &[u8],
+    network: Network,
+    compress_public_keys: bool,
+    account_type: AccountType,
+    path: &[ChildNumber],
+) -> Result<String, KeyError> {
+    let network = match network {
+        Network::Mainnet => bitcoin::Network::Bitcoin,
+        Network::Testnet => bitcoin::Network::Testnet,
+        Network::Regtest => bitcoin::Network::Regtest,
+    };
+
+    let secp = Secp256k1::new();
+    let xpriv = Xpriv::new_master(network, seed).map_err(|e| KeyError::Other(e.to_string()))?;
+
+    let path: Vec<bip32::ChildNumber> = path
+        .iter()
+        .map(|child| bip32::ChildNumber::from(u32::from(*child)))
+        .collect();
+    let child = xpriv
+        .derive_priv(&secp, &path)
+        .map_err(|e| KeyError::Other(e.to_string()))?;
+    let public_key = PublicKey::from_secret_key(&secp, &child.private_key);
+    let compressed = CompressedPublicKey(public_key);
+
+    let address = match account_type {
+        AccountType::Legacy => {
+            let public_key = bitcoin::PublicKey {
+                compressed: compress_public_keys,
+                inner: public_key,
+            };
+            Address::p2pkh(public_key.pubkey_hash(), network)
+        }
+        AccountType::NestedSegwit => Address::p2shwpkh(&compressed, network),
+        AccountType::NativeSegwit => Address::p2wpkh(&compressed, network),
+        AccountType::Taproot => {
+            Address::p2tr(&secp, public_key.x_only_public_key().0, None, network)
+        }
+    };
+
+    Ok(address.to_string())
+}
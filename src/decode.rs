@@ -0,0 +1,369 @@
+use std::fmt;
+
+use crate::{
+    parse_pushes, read_varint, reverse_bytes, sha256_hash_twice, LockTime, Network, OutPoint,
+    Script, ScriptType, TransactionError, TransactionOutput, TransactionType, FINAL_SEQUENCE, OP_0,
+};
+
+/// inputs with a sequence below this signal they can be replaced (BIP125)
+pub const RBF_SEQUENCE_THRESHOLD: u32 = FINAL_SEQUENCE - 1;
+
+/// An input of a [TxSummary]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputSummary {
+    pub previous_output: OutPoint,
+    pub sequence: u32,
+    pub signature_script: Vec<u8>,
+    pub witness: Vec<Vec<u8>>,
+    /// the type of the output spent, read from its pk script when it's known
+    /// and guessed from the signature script and witness otherwise
+    pub script_type: Option<TransactionType>,
+    /// satoshis held by the output spent, when it's known
+    pub value: Option<i64>,
+    /// the address of the output spent, when it's known
+    pub address: Option<String>,
+}
+
+/// An output of a [TxSummary]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputSummary {
+    pub value: i64,
+    pub pk_script: Vec<u8>,
+    /// none for scripts without an address, eg `OP_RETURN`
+    pub address: Option<String>,
+    pub script_type: ScriptType,
+}
+
+/// A raw transaction decoded by [decode_transaction], for inspecting what
+/// was built or received
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxSummary {
+    pub tx_id: String,
+    /// the hash of the transaction with its witnesses, its txid without any
+    pub wtx_id: String,
+    pub version: u32,
+    pub inputs: Vec<InputSummary>,
+    pub outputs: Vec<OutputSummary>,
+    pub lock_time: LockTime,
+    /// bytes serialized
+    pub size: usize,
+    pub vsize: u64,
+    pub weight: u64,
+    /// satoshis paid in fees, when the value of every output spent is known
+    pub fee: Option<i64>,
+    /// whether an input signals the transaction can be replaced (BIP125)
+    pub rbf: bool,
+}
+
+impl TxSummary {
+    /// whether the transaction mints coins, spending no output
+    pub fn is_coinbase(&self) -> bool {
+        matches!(self.inputs.as_slice(), [input] if input.previous_output.hash() == "00".repeat(32))
+    }
+
+    /// satoshis paid per vbyte, when the fee is known
+    pub fn fee_rate(&self) -> Option<f64> {
+        self.fee.map(|fee| fee as f64 / self.vsize as f64)
+    }
+}
+
+/// laid out like `decoderawtransaction`, one line per input and output
+impl fmt::Display for TxSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "txid {}", self.tx_id)?;
+        writeln!(f, "wtxid {}", self.wtx_id)?;
+        writeln!(
+            f,
+            "version {}, lock time {}, {} bytes, {} vbytes, {} weight",
+            self.version, self.lock_time, self.size, self.vsize, self.weight
+        )?;
+        match self.fee {
+            Some(fee) => writeln!(
+                f,
+                "fee {} sat ({:.2} sat/vB){}",
+                fee,
+                self.fee_rate().unwrap_or_default(),
+                if self.rbf { ", replaceable" } else { "" }
+            )?,
+            None => writeln!(
+                f,
+                "fee unknown{}",
+                if self.rbf { ", replaceable" } else { "" }
+            )?,
+        }
+
+        for (index, input) in self.inputs.iter().enumerate() {
+            write!(
+                f,
+                "input {} {}:{} sequence {:#010x}",
+                index,
+                input.previous_output.hash(),
+                input.previous_output.index() as u32,
+                input.sequence
+            )?;
+            if let Some(script_type) = &input.script_type {
+                write!(f, " {:?}", script_type)?;
+            }
+            if let Some(value) = input.value {
+                write!(f, " {} sat", value)?;
+            }
+            if let Some(address) = &input.address {
+                write!(f, " from {}", address)?;
+            }
+            writeln!(f)?;
+        }
+
+        for (index, output) in self.outputs.iter().enumerate() {
+            write!(f, "output {} {} sat", index, output.value)?;
+            match &output.address {
+                Some(address) => write!(f, " to {}", address)?,
+                None => write!(f, " script {}", hex::encode(&output.pk_script))?,
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode a hex encoded raw transaction, with or without witnesses. The
+/// outputs it spends aren't known, see [decode_transaction_with]
+pub fn decode_transaction(hex: &str, network: Network) -> Result<TxSummary, TransactionError> {
+    decode_transaction_with(hex, network, |_| None)
+}
+
+/// Decode a hex encoded raw transaction, looking up the outputs it spends
+/// to tell their types, addresses and values, and the fee when all are found
+pub fn decode_transaction_with<F>(
+    hex: &str,
+    network: Network,
+    prevout: F,
+) -> Result<TxSummary, TransactionError>
+where
+    F: Fn(&OutPoint) -> Option<TransactionOutput>,
+{
+    let bytes =
+        hex::decode(hex.trim()).map_err(|e| TransactionError::Decode(format!("not hex: {}", e)))?;
+    let mut reader = Reader::new(&bytes);
+
+    let version = reader.u32()?;
+    // the marker of a segwit transaction reads as a transaction without inputs
+    let segwit = reader.peek(2) == Some(&[0x00, 0x01][..]);
+    if segwit {
+        reader.take(2)?;
+    }
+    let start_of_inputs = reader.position();
+
+    let mut inputs = Vec::new();
+    for _ in 0..reader.compact_size()? {
+        let tx_id = reverse_bytes(reader.take(32)?);
+        let previous_output = OutPoint::new(hex::encode(tx_id), reader.u32()? as i32);
+        let script_length = reader.compact_size()?;
+        let signature_script = reader.take(script_length)?.to_vec();
+        let sequence = reader.u32()?;
+
+        inputs.push(InputSummary {
+            previous_output,
+            sequence,
+            signature_script,
+            witness: vec![],
+            script_type: None,
+            value: None,
+            address: None,
+        });
+    }
+
+    let mut outputs = Vec::new();
+    for _ in 0..reader.compact_size()? {
+        let value = reader.i64()?;
+        let script_length = reader.compact_size()?;
+        let pk_script = reader.take(script_length)?.to_vec();
+        let script = Script::from(pk_script.clone());
+
+        outputs.push(OutputSummary {
+            value,
+            address: script.to_address(network),
+            script_type: script.classify(),
+            pk_script,
+        });
+    }
+    let end_of_outputs = reader.position();
+
+    if segwit {
+        for input in inputs.iter_mut() {
+            for _ in 0..reader.compact_size()? {
+                let length = reader.compact_size()?;
+                input.witness.push(reader.take(length)?.to_vec());
+            }
+        }
+    }
+    let lock_time = LockTime::from_consensus(reader.u32()?);
+    if reader.position() != bytes.len() {
+        return Err(TransactionError::Decode(format!(
+            "{} bytes after the lock time",
+            bytes.len() - reader.position()
+        )));
+    }
+
+    // the txid hashes the transaction without its marker, flag and witnesses
+    let stripped = [
+        &bytes[..4],
+        &bytes[start_of_inputs..end_of_outputs],
+        &bytes[bytes.len() - 4..],
+    ]
+    .concat();
+    let reversed_hash =
+        |bytes: &[u8]| hex::encode(reverse_bytes(&sha256_hash_twice(&bytes.to_vec())));
+    let weight = (stripped.len() * 3 + bytes.len()) as u64;
+
+    for input in inputs.iter_mut() {
+        match prevout(&input.previous_output) {
+            Some(output) => {
+                let script = Script::from(output.pk_script().to_vec());
+                input.script_type = spent_type(&script, &input.signature_script, &input.witness);
+                input.value = Some(output.value());
+                input.address = script.to_address(network);
+            }
+            None => input.script_type = guess_spent_type(&input.signature_script, &input.witness),
+        }
+    }
+
+    let mut summary = TxSummary {
+        tx_id: reversed_hash(&stripped),
+        wtx_id: reversed_hash(&bytes),
+        version,
+        rbf: inputs
+            .iter()
+            .any(|input| input.sequence < RBF_SEQUENCE_THRESHOLD),
+        inputs,
+        outputs,
+        lock_time,
+        size: bytes.len(),
+        vsize: weight.div_ceil(4),
+        weight,
+        fee: None,
+    };
+    if !summary.is_coinbase() {
+        let spent: Option<i64> = summary.inputs.iter().map(|input| input.value).sum();
+        let created: i64 = summary.outputs.iter().map(|output| output.value).sum();
+        summary.fee = spent.map(|spent| spent - created);
+    }
+
+    Ok(summary)
+}
+
+/// the type of an output spent, from its pk script
+fn spent_type(
+    pk_script: &Script,
+    signature_script: &[u8],
+    witness: &[Vec<u8>],
+) -> Option<TransactionType> {
+    match pk_script.classify() {
+        // a script hash only tells it's nested segwit once spent
+        ScriptType::P2sh => match guess_spent_type(signature_script, witness) {
+            Some(TransactionType::NestedPay2WitnessPubKeyHash) => {
+                Some(TransactionType::NestedPay2WitnessPubKeyHash)
+            }
+            _ => None,
+        },
+        script_type => script_type.tx_type(),
+    }
+}
+
+/// the type of an output spent, guessed from how it's unlocked
+fn guess_spent_type(signature_script: &[u8], witness: &[Vec<u8>]) -> Option<TransactionType> {
+    let is_pubkey = |item: &[u8]| {
+        matches!(
+            (item.len(), item.first()),
+            (33, Some(0x02 | 0x03)) | (65, Some(0x04))
+        )
+    };
+
+    match (signature_script, witness) {
+        // a signature and a compressed key
+        ([], [_, key]) if key.len() == 33 && is_pubkey(key) => {
+            Some(TransactionType::Pay2WitnessPubKeyHash)
+        }
+        // a schnorr signature, with a sighash type unless SIGHASH_DEFAULT
+        ([], [signature]) if matches!(signature.len(), 64 | 65) => {
+            Some(TransactionType::Pay2Taproot)
+        }
+        // the push of a P2WPKH redeem script
+        ([0x16, OP_0, 0x14, ..], [_, key]) if signature_script.len() == 23 && is_pubkey(key) => {
+            Some(TransactionType::NestedPay2WitnessPubKeyHash)
+        }
+        (_, []) => match parse_pushes(signature_script)?.as_slice() {
+            [_, key] if is_pubkey(key) => Some(TransactionType::Pay2PubKeyHash),
+            // a DER signature alone
+            [signature]
+                if signature.first() == Some(&0x30) && (9..=73).contains(&signature.len()) =>
+            {
+                Some(TransactionType::Pay2PubKey)
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// reads the fields of a serialized transaction in order
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    fn peek(&self, length: usize) -> Option<&'a [u8]> {
+        self.bytes.get(self.position..self.position + length)
+    }
+
+    pub(crate) fn take(&mut self, length: usize) -> Result<&'a [u8], TransactionError> {
+        let taken = self.peek(length).ok_or_else(|| {
+            TransactionError::Decode(format!(
+                "{} bytes wanted at byte {}, the transaction ends first",
+                length, self.position
+            ))
+        })?;
+        self.position += length;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, TransactionError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn i64(&mut self) -> Result<i64, TransactionError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    /// a compact size unsigned integer, as written by `compact_size`
+    pub(crate) fn compact_size(&mut self) -> Result<usize, TransactionError> {
+        let (length, read) = read_varint(&self.bytes[self.position..]).map_err(|e| match e {
+            TransactionError::Decode(reason) => {
+                TransactionError::Decode(format!("{} at byte {}", reason, self.position))
+            }
+            e => e,
+        })?;
+        self.position += read;
+
+        // nothing in a transaction is longer than the transaction
+        match length <= self.bytes.len() as u64 {
+            true => Ok(length as usize),
+            false => Err(TransactionError::Decode(format!(
+                "a length of {} at byte {}",
+                length, self.position
+            ))),
+        }
+    }
+}
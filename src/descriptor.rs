@@ -0,0 +1,94 @@
+use crate::DescriptorError;
+
+/// the characters a descriptor can hold, in the order the checksum groups them
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+
+/// the characters of a checksum, the bech32 alphabet
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// the number of characters of a checksum, after the `#`
+pub const DESCRIPTOR_CHECKSUM_LENGTH: usize = 8;
+
+fn polymod(c: u64, value: u64) -> u64 {
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7ffffffff) << 5) ^ value;
+    for (bit, generator) in [
+        0xf5dee51989,
+        0xa9fdca3312,
+        0x1bab10e32d,
+        0x3706b1677a,
+        0x644d626ffd,
+    ]
+    .iter()
+    .enumerate()
+    {
+        if c0 & (1 << bit) != 0 {
+            c ^= generator;
+        }
+    }
+    c
+}
+
+/// The checksum of a descriptor without one, as computed by Bitcoin Core's
+/// `getdescriptorinfo`. It catches up to 4 typos and swapped characters
+pub fn descriptor_checksum(descriptor: &str) -> Result<String, DescriptorError> {
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET
+            .find(ch)
+            .ok_or(DescriptorError::InvalidCharacter(ch))? as u64;
+        c = polymod(c, position & 31);
+        // the character groups are folded in three at a time
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..DESCRIPTOR_CHECKSUM_LENGTH {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    Ok((0..DESCRIPTOR_CHECKSUM_LENGTH)
+        .map(|i| CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect())
+}
+
+/// the descriptor followed by `#` and its checksum, ready for `importdescriptors`
+pub fn with_descriptor_checksum(descriptor: &str) -> Result<String, DescriptorError> {
+    Ok(format!(
+        "{}#{}",
+        descriptor,
+        descriptor_checksum(descriptor)?
+    ))
+}
+
+/// Check the checksum of a pasted descriptor, returning the descriptor
+/// without it. Like `importdescriptors`, a descriptor without a checksum is
+/// refused, a typo in it would pay to addresses nobody holds keys to
+pub fn verify_descriptor_checksum(descriptor: &str) -> Result<&str, DescriptorError> {
+    let (body, checksum) = descriptor
+        .rsplit_once('#')
+        .ok_or(DescriptorError::MissingChecksum)?;
+    if checksum.len() != DESCRIPTOR_CHECKSUM_LENGTH {
+        return Err(DescriptorError::InvalidChecksumLength(checksum.len()));
+    }
+
+    let expected = descriptor_checksum(body)?;
+    match expected == checksum {
+        true => Ok(body),
+        false => Err(DescriptorError::ChecksumMismatch {
+            expected,
+            found: checksum.to_string(),
+        }),
+    }
+}
@@ -0,0 +1,253 @@
+use std::{collections::HashMap, env};
+
+use serde_json::{json, Value};
+
+use crate::{
+    Backend, BackendError, BitcoinCoreRpc, Block, FeeHistogram, MempoolAcceptance, OutPoint,
+    PackageSubmission, TransactionOutput, Utxo, Wallet, WalletError, COINBASE_MATURITY,
+};
+
+/// the wallet created on the node to mine and pay from
+pub const HARNESS_WALLET: &str = "waller-harness";
+
+/// satoshis in a bitcoin, the unit amounts are given in over RPC
+const SATOSHIS_PER_BITCOIN: f64 = 100_000_000.0;
+
+/// Drives a local regtest node for integration tests: mines blocks, funds
+/// addresses of a [crate::Network::Regtest] wallet from the node's own
+/// wallet and confirms transactions
+#[derive(Debug, Clone)]
+pub struct RegtestHarness {
+    rpc: BitcoinCoreRpc,
+    /// the node wallet address block rewards are paid to
+    mining_address: String,
+}
+
+impl RegtestHarness {
+    /// Connect to a node, refusing any chain but regtest, and create or
+    /// load [HARNESS_WALLET] to mine to
+    pub fn new(rpc: BitcoinCoreRpc) -> Result<Self, BackendError> {
+        let info = rpc.call("getblockchaininfo", json!([]))?;
+        match info["chain"].as_str() {
+            Some("regtest") => {}
+            chain => {
+                return Err(BackendError::Unsupported(format!(
+                    "devtools on the {} chain",
+                    chain.unwrap_or("unknown")
+                )))
+            }
+        }
+
+        match rpc.call("createwallet", json!([HARNESS_WALLET])) {
+            // the wallet was made by an earlier run
+            Err(BackendError::Rpc { code: -4, .. }) => {
+                match rpc.call("loadwallet", json!([HARNESS_WALLET])) {
+                    // and is still loaded
+                    Err(BackendError::Rpc { code: -35, .. }) => {}
+                    result => {
+                        result?;
+                    }
+                }
+            }
+            result => {
+                result?;
+            }
+        }
+
+        let rpc = rpc.with_wallet(HARNESS_WALLET);
+        let mining_address = as_string(rpc.call("getnewaddress", json!([]))?)?;
+
+        Ok(Self {
+            rpc,
+            mining_address,
+        })
+    }
+
+    /// Connect to the node given by `WALLER_REGTEST_RPC` (`127.0.0.1:18443`
+    /// by default), `WALLER_REGTEST_USER` and `WALLER_REGTEST_PASSWORD`
+    pub fn from_env() -> Result<Self, BackendError> {
+        let address =
+            env::var("WALLER_REGTEST_RPC").unwrap_or_else(|_| "127.0.0.1:18443".to_string());
+        let user = env::var("WALLER_REGTEST_USER").unwrap_or_default();
+        let password = env::var("WALLER_REGTEST_PASSWORD").unwrap_or_default();
+
+        Self::new(BitcoinCoreRpc::new(address, user, password))
+    }
+
+    /// the node, with wallet calls made to [HARNESS_WALLET]
+    pub fn rpc(&self) -> &BitcoinCoreRpc {
+        &self.rpc
+    }
+
+    /// the height of the chain tip
+    pub fn height(&self) -> Result<u32, BackendError> {
+        self.rpc.tip_height()
+    }
+
+    /// mine blocks paying the node wallet, returns their hashes
+    pub fn generate(&self, blocks: u32) -> Result<Vec<String>, BackendError> {
+        let hashes = self.rpc.call(
+            "generatetoaddress",
+            json!([blocks, self.mining_address.as_str()]),
+        )?;
+
+        match hashes.as_array() {
+            Some(hashes) => hashes.iter().cloned().map(as_string).collect(),
+            None => Err(BackendError::InvalidResponse(hashes.to_string())),
+        }
+    }
+
+    /// Invalidate the block at a height and those after it, as
+    /// `invalidateblock` does, to reorganize the chain once more blocks are
+    /// mined. Returns the hash of the block invalidated
+    pub fn invalidate_block(&self, height: u32) -> Result<String, BackendError> {
+        let hash = self.rpc.block_hash(height)?;
+        self.rpc.call("invalidateblock", json!([hash]))?;
+        Ok(hash)
+    }
+
+    /// mine until the node wallet has coins to fund with, coinbase
+    /// outputs only become spendable after [COINBASE_MATURITY] blocks
+    pub fn mature(&self) -> Result<(), BackendError> {
+        let balance = self.rpc.call("getbalance", json!([]))?;
+        if balance.as_f64().unwrap_or_default() < 1.0 {
+            self.generate(COINBASE_MATURITY + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Pay an amount in satoshis to an address of the wallet from the node
+    /// wallet and track the new output in the wallet, unconfirmed until
+    /// [RegtestHarness::confirm]
+    pub fn fund(
+        &self,
+        wallet: &mut Wallet,
+        address: &str,
+        amount: i64,
+    ) -> Result<Utxo, WalletError> {
+        self.mature().map_err(WalletError::Backend)?;
+
+        let amount = amount as f64 / SATOSHIS_PER_BITCOIN;
+        let tx_id = self
+            .rpc
+            .call("sendtoaddress", json!([address, amount]))
+            .and_then(as_string)
+            .map_err(WalletError::Backend)?;
+
+        let tx = self
+            .rpc
+            .call("getrawtransaction", json!([tx_id.as_str(), true]))
+            .map_err(WalletError::Backend)?;
+        let output = tx["vout"]
+            .as_array()
+            .and_then(|outputs| {
+                outputs
+                    .iter()
+                    .find(|output| output["scriptPubKey"]["address"].as_str() == Some(address))
+            })
+            .ok_or_else(|| {
+                WalletError::Backend(BackendError::InvalidResponse(format!(
+                    "no output of {} pays to {}",
+                    tx_id, address
+                )))
+            })?;
+
+        let utxo = Utxo::new(
+            OutPoint::new(
+                tx_id.clone(),
+                output["n"].as_i64().unwrap_or_default() as i32,
+            ),
+            TransactionOutput::from_script(
+                (output["value"].as_f64().unwrap_or_default() * SATOSHIS_PER_BITCOIN).round()
+                    as i64,
+                output["scriptPubKey"]["hex"]
+                    .as_str()
+                    .and_then(|script| hex::decode(script).ok())
+                    .unwrap_or_default(),
+            ),
+            address.to_string(),
+        );
+        wallet.add_utxo(utxo.clone())?;
+
+        Ok(utxo)
+    }
+
+    /// Mine blocks and mark the wallet outputs they confirm with the height
+    /// of their block. Returns the height of the new tip
+    pub fn confirm(&self, wallet: &mut Wallet, blocks: u32) -> Result<u32, WalletError> {
+        let hashes = self.generate(blocks).map_err(WalletError::Backend)?;
+
+        let mut tip = 0;
+        for hash in hashes {
+            let block = self
+                .rpc
+                .call("getblock", json!([hash, 1]))
+                .map_err(WalletError::Backend)?;
+            let height = block["height"].as_u64().unwrap_or_default() as u32;
+
+            let mut confirmed: Vec<String> = wallet
+                .utxos()
+                .iter()
+                .filter(|utxo| !utxo.is_confirmed())
+                .map(|utxo| utxo.outpoint().hash())
+                .filter(|tx_id| {
+                    block["tx"]
+                        .as_array()
+                        .map(|txs| txs.iter().any(|tx| tx.as_str() == Some(tx_id)))
+                        .unwrap_or(false)
+                })
+                .collect();
+            confirmed.sort();
+            confirmed.dedup();
+            for tx_id in confirmed {
+                wallet.confirm_transaction(tx_id, height);
+            }
+
+            tip = height;
+        }
+
+        Ok(tip)
+    }
+}
+
+impl Backend for RegtestHarness {
+    fn broadcast(&self, raw_tx: &str) -> Result<String, BackendError> {
+        self.rpc.broadcast(raw_tx)
+    }
+
+    fn submit_package(&self, raw_txs: &[String]) -> Result<PackageSubmission, BackendError> {
+        self.rpc.submit_package(raw_txs)
+    }
+
+    fn test_accept(&self, raw_tx: &str) -> Result<MempoolAcceptance, BackendError> {
+        self.rpc.test_accept(raw_tx)
+    }
+
+    fn mempool_histogram(&self) -> Result<FeeHistogram, BackendError> {
+        self.rpc.mempool_histogram()
+    }
+
+    fn tip_height(&self) -> Result<u32, BackendError> {
+        self.rpc.tip_height()
+    }
+
+    fn block(&self, height: u32) -> Result<Block, BackendError> {
+        self.rpc.block(height)
+    }
+
+    fn silent_payment_tweaks(&self, height: u32) -> Result<HashMap<String, Vec<u8>>, BackendError> {
+        self.rpc.silent_payment_tweaks(height)
+    }
+
+    fn block_hash(&self, height: u32) -> Result<String, BackendError> {
+        self.rpc.block_hash(height)
+    }
+}
+
+/// the string of an RPC result
+fn as_string(value: Value) -> Result<String, BackendError> {
+    match value.as_str() {
+        Some(string) => Ok(string.to_string()),
+        None => Err(BackendError::InvalidResponse(value.to_string())),
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    Key as CipherKey, XChaCha20Poly1305, XNonce,
+};
+use scrypt::Params;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::{EntropySource, WalletError};
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const MAC_KEY_LENGTH: usize = 32;
+
+/// a known plaintext sealed with the wallet key, used to check
+/// a passphrase before touching any private keys
+const PASSPHRASE_CHECK: &[u8] = b"waller";
+
+/// the smallest scrypt cost [calibrate_kdf] picks, 1 MiB of memory with r = 8
+pub const MIN_KDF_LOG_N: u8 = 10;
+
+/// the largest scrypt cost [calibrate_kdf] picks, 1 GiB of memory with r = 8
+pub const MAX_KDF_LOG_N: u8 = 20;
+
+/// How hard turning a passphrase into a wallet key is, the scrypt cost
+/// `N = 2^log_n`, block size `r` and parallelism `p`. Higher costs slow
+/// down guessing the passphrase as much as unlocking the wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KdfParams {
+    log_n: u8,
+    r: u32,
+    p: u32,
+}
+
+impl KdfParams {
+    /// fails for parameters scrypt doesn't accept, eg `log_n` of 64 or more
+    pub fn new(log_n: u8, r: u32, p: u32) -> Result<Self, WalletError> {
+        Params::new(log_n, r, p).map_err(|e| WalletError::Encryption(e.to_string()))?;
+        Ok(Self { log_n, r, p })
+    }
+
+    pub fn log_n(&self) -> u8 {
+        self.log_n
+    }
+
+    pub fn r(&self) -> u32 {
+        self.r
+    }
+
+    pub fn p(&self) -> u32 {
+        self.p
+    }
+}
+
+/// the parameters recommended by scrypt, `log_n` 15, `r` 8 and `p` 1
+impl Default for KdfParams {
+    fn default() -> Self {
+        let recommended = Params::recommended();
+        Self {
+            log_n: recommended.log_n(),
+            r: recommended.r(),
+            p: recommended.p(),
+        }
+    }
+}
+
+/// Pick the scrypt cost deriving a key in about `target_millis` on this
+/// host, with the `r` and `p` of [KdfParams::default]. A key is derived at
+/// [MIN_KDF_LOG_N] and the cost doubled while the time it would take, twice
+/// as long each time, stays under the target. The cost stays within
+/// [MIN_KDF_LOG_N] and [MAX_KDF_LOG_N] whatever the target. The salt of
+/// the key derived is read from a source, eg [crate::OsEntropy]
+pub fn calibrate_kdf(
+    target_millis: u64,
+    source: &dyn EntropySource,
+) -> Result<KdfParams, WalletError> {
+    let default = KdfParams::default();
+    let mut kdf = KdfParams::new(MIN_KDF_LOG_N, default.r, default.p)?;
+
+    let params = EncryptionParams {
+        salt: source.random_bytes(SALT_LENGTH),
+        log_n: kdf.log_n,
+        r: kdf.r,
+        p: kdf.p,
+        check: vec![],
+        mac_key: vec![],
+    };
+    let started = Instant::now();
+    params.derive_key("calibrate")?;
+    let mut estimate = started.elapsed();
+
+    let target = Duration::from_millis(target_millis);
+    while kdf.log_n < MAX_KDF_LOG_N && estimate * 2 <= target {
+        kdf.log_n += 1;
+        estimate *= 2;
+    }
+    Ok(kdf)
+}
+
+/// The data needed to turn a passphrase back into the key
+/// sealing the private keys of a wallet. None of this is secret
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EncryptionParams {
+    salt: Vec<u8>,
+    log_n: u8,
+    r: u32,
+    p: u32,
+    check: Vec<u8>,
+    /// the key of the mac of the wallet file sealed with the wallet key, empty
+    /// in params written before wallet files were authenticated with their own key
+    #[serde(default)]
+    mac_key: Vec<u8>,
+}
+
+impl EncryptionParams {
+    /// Create params with a fresh salt for a passphrase, the salt, mac key
+    /// and nonces read from a source.
+    /// returns the params and the key derived from the passphrase
+    pub(crate) fn new(
+        passphrase: &str,
+        kdf: KdfParams,
+        source: &dyn EntropySource,
+    ) -> Result<(Self, [u8; 32]), WalletError> {
+        let mut params = Self {
+            salt: source.random_bytes(SALT_LENGTH),
+            log_n: kdf.log_n,
+            r: kdf.r,
+            p: kdf.p,
+            check: vec![],
+            mac_key: vec![],
+        };
+
+        let key = params.derive_key(passphrase)?;
+        params.check = encrypt(&key, PASSPHRASE_CHECK, source)?;
+        params.add_mac_key(&key, source)?;
+
+        Ok((params, key))
+    }
+
+    /// Seal a new random key for the mac of the wallet file with the wallet
+    /// key, returning it. Unsealed once, it's kept while the wallet is locked
+    /// so a locked wallet still authenticates the files it writes
+    pub(crate) fn add_mac_key(
+        &mut self,
+        key: &[u8; 32],
+        source: &dyn EntropySource,
+    ) -> Result<[u8; 32], WalletError> {
+        let mut mac_key = [0u8; MAC_KEY_LENGTH];
+        source.fill_bytes(&mut mac_key);
+        self.mac_key = encrypt(key, &mac_key, source)?;
+        Ok(mac_key)
+    }
+
+    /// unseal the key of the mac of the wallet file, none for params without one
+    pub(crate) fn mac_key(&self, key: &[u8; 32]) -> Result<Option<[u8; 32]>, WalletError> {
+        if self.mac_key.is_empty() {
+            return Ok(None);
+        }
+        let mut unsealed = decrypt(key, &self.mac_key)?;
+        let mac_key = <[u8; 32]>::try_from(unsealed.as_slice())
+            .map_err(|_| WalletError::Encryption("the mac key is not 32 bytes".to_string()));
+        unsealed.zeroize();
+        mac_key.map(Some)
+    }
+
+    /// derive the wallet key from a passphrase, fails if the passphrase is wrong
+    pub(crate) fn unlock(&self, passphrase: &str) -> Result<[u8; 32], WalletError> {
+        let key = self.derive_key(passphrase)?;
+
+        match decrypt(&key, &self.check) {
+            Ok(check) if check == PASSPHRASE_CHECK => Ok(key),
+            _ => Err(WalletError::IncorrectPassphrase),
+        }
+    }
+
+    /// the cost of deriving the key, which may differ from the config of the wallet
+    /// until the passphrase is changed
+    pub fn kdf(&self) -> KdfParams {
+        KdfParams {
+            log_n: self.log_n,
+            r: self.r,
+            p: self.p,
+        }
+    }
+
+    fn derive_key(&self, passphrase: &str) -> Result<[u8; 32], WalletError> {
+        let params = Params::new(self.log_n, self.r, self.p)
+            .map_err(|e| WalletError::Encryption(e.to_string()))?;
+
+        let mut key = [0u8; 32];
+        scrypt::scrypt(passphrase.as_bytes(), &self.salt, &params, &mut key)
+            .map_err(|e| WalletError::Encryption(e.to_string()))?;
+
+        Ok(key)
+    }
+}
+
+/// seal data with a wallet key, the nonce read from a source is prepended to the output
+pub(crate) fn encrypt(
+    key: &[u8; 32],
+    plaintext: &[u8],
+    source: &dyn EntropySource,
+) -> Result<Vec<u8>, WalletError> {
+    let cipher = XChaCha20Poly1305::new(CipherKey::from_slice(key));
+    let mut nonce = source.random_bytes(NONCE_LENGTH);
+
+    let mut ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .map_err(|e| WalletError::Encryption(e.to_string()))?;
+
+    nonce.append(&mut ciphertext);
+    Ok(nonce)
+}
+
+/// open data sealed by [encrypt]
+pub(crate) fn decrypt(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, WalletError> {
+    if data.len() < NONCE_LENGTH {
+        return Err(WalletError::Encryption(
+            "encrypted data is too short".to_string(),
+        ));
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+    let cipher = XChaCha20Poly1305::new(CipherKey::from_slice(key));
+
+    cipher
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|e| WalletError::Encryption(e.to_string()))
+}
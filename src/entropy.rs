@@ -0,0 +1,79 @@
+use std::{fmt::Debug, sync::Mutex};
+
+use rand::{rngs::StdRng, thread_rng, RngCore, SeedableRng};
+
+/// A source of the randomness of mnemonics, shuffled transactions and the
+/// extra data of nonces, given to what needs it so tests can be reproduced
+pub trait EntropySource: Debug + Send + Sync {
+    /// fill a buffer with random bytes
+    fn fill_bytes(&self, dest: &mut [u8]);
+
+    /// some random bytes
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        self.fill_bytes(&mut bytes);
+        bytes
+    }
+}
+
+/// The randomness of the operating system, through the thread rng it seeds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsEntropy;
+
+impl EntropySource for OsEntropy {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        thread_rng().fill_bytes(dest);
+    }
+}
+
+/// An rng giving the same bytes for the same seed, for tests only, its
+/// output is predictable
+#[derive(Debug)]
+pub struct SeededEntropy(Mutex<StdRng>);
+
+impl SeededEntropy {
+    pub fn new(seed: u64) -> Self {
+        Self(Mutex::new(StdRng::seed_from_u64(seed)))
+    }
+}
+
+impl EntropySource for SeededEntropy {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .fill_bytes(dest);
+    }
+}
+
+/// An [EntropySource] as an rng, eg for [crate::TransactionBuilder::build_with_rng]
+pub struct EntropyRng<'a>(&'a dyn EntropySource);
+
+impl<'a> EntropyRng<'a> {
+    pub fn new(source: &'a dyn EntropySource) -> Self {
+        Self(source)
+    }
+}
+
+impl RngCore for EntropyRng<'_> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.0.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.0.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.fill_bytes(dest);
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::{fmt::Debug, path::PathBuf, sync::Arc};
+
+use crate::{OutPoint, Utxo};
+
+/// Something that changed in a wallet, delivered to the callbacks
+/// registered with [crate::Wallet::on_event]
+#[derive(Debug, Clone)]
+pub enum WalletEvent {
+    /// a receive address was handed out by an account
+    NewAddressIssued { account: u32, address: String },
+    /// an output paying to the wallet is now tracked
+    UtxoReceived(Utxo),
+    /// a tracked output was spent and is no longer part of the balance
+    UtxoSpent(OutPoint),
+    /// a transaction paying to the wallet was included in a block
+    TxConfirmed { tx_id: String, height: u32 },
+    /// a transaction was replaced by one paying a higher fee
+    FeeBumped {
+        tx_id: String,
+        replacement_tx_id: String,
+    },
+    /// A transaction the wallet didn't build, found by a sync, spent coins
+    /// the pending transactions of the wallet spend. They can't confirm
+    /// anymore and are marked conflicted, eg after a double spend
+    Conflict {
+        tx_id: String,
+        conflicted: Vec<String>,
+    },
+    /// The chain reorganized past blocks the wallet scanned, the blocks
+    /// after `fork_height`, `depth` of them, were scanned again by
+    /// [crate::Wallet::sync]
+    Reorg { fork_height: u32, depth: u32 },
+    /// the wallet was written to disk
+    WalletFlushed(PathBuf),
+}
+
+/// Receives wallet events, implemented for any `Fn(&WalletEvent)`
+/// sinks are called synchronously, in registration order, by the thread
+/// changing the wallet so they should return quickly
+pub trait EventSink: Send + Sync {
+    fn on_event(&self, event: &WalletEvent);
+}
+
+impl<F> EventSink for F
+where
+    F: Fn(&WalletEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &WalletEvent) {
+        self(event)
+    }
+}
+
+/// The sinks registered on a wallet, never persisted
+#[derive(Clone, Default)]
+pub(crate) struct EventSinks(Vec<Arc<dyn EventSink>>);
+
+impl EventSinks {
+    pub(crate) fn push(&mut self, sink: Arc<dyn EventSink>) {
+        self.0.push(sink);
+    }
+
+    pub(crate) fn emit(&self, event: WalletEvent) {
+        for sink in self.0.iter() {
+            sink.on_event(&event);
+        }
+    }
+}
+
+impl Debug for EventSinks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EventSinks({})", self.0.len())
+    }
+}
+
+/// called by [crate::Wallet::watch_tx] with the txid and the height of the block confirming it
+pub(crate) type TxWatchCallback = Arc<dyn Fn(&str, u32) + Send + Sync>;
+
+/// A transaction waited on to reach a number of confirmations
+#[derive(Clone)]
+pub(crate) struct TxWatch {
+    pub(crate) tx_id: String,
+    pub(crate) confirmations: u32,
+    pub(crate) callback: TxWatchCallback,
+}
+
+/// The transactions watched on a wallet, never persisted
+#[derive(Clone, Default)]
+pub(crate) struct TxWatches(pub(crate) Vec<TxWatch>);
+
+impl Debug for TxWatches {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(
+                self.0
+                    .iter()
+                    .map(|watch| (&watch.tx_id, watch.confirmations)),
+            )
+            .finish()
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::WalletError;
+
+/// The file formats of [crate::Wallet::export_history] and [crate::Wallet::export_utxos]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// comma separated values under a header row, as read by spreadsheets
+    Csv,
+    /// an array holding an object per row
+    Json,
+}
+
+/// A transaction of the history, as exported by [crate::Wallet::export_history]
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryRow {
+    pub txid: String,
+    /// the height of the block confirming the transaction, none while in the mempool
+    pub height: Option<u32>,
+    /// satoshis the transaction changed the wallet's balance by, negative when spending
+    pub amount: i64,
+    /// satoshis paid in fees, only known when the wallet funded every input
+    pub fee: Option<i64>,
+    /// the address paid outside the wallet when spending
+    pub counterparty: Option<String>,
+    /// the label of the first labelled wallet address the transaction paid to
+    pub label: Option<String>,
+}
+
+/// A transaction of the history with its amount in a fiat currency, as
+/// exported by [crate::Wallet::export_history_with_rates]
+#[derive(Debug, Clone, Serialize)]
+pub struct FiatHistoryRow {
+    #[serde(flatten)]
+    pub row: HistoryRow,
+    /// the code of the fiat currency, eg `USD`
+    pub currency: String,
+    /// the amount at the price of the time of the block confirming the
+    /// transaction, none in the mempool or when the price isn't known
+    pub value_at_time: Option<String>,
+    /// the amount at the current price, none when it isn't known
+    pub value_now: Option<String>,
+}
+
+/// An unspent output, as exported by [crate::Wallet::export_utxos]
+#[derive(Debug, Clone, Serialize)]
+pub struct UtxoRow {
+    pub txid: String,
+    pub vout: i32,
+    /// the height of the block confirming the output, none while in the mempool
+    pub height: Option<u32>,
+    /// satoshis held by the output
+    pub amount: i64,
+    pub address: String,
+    pub label: Option<String>,
+}
+
+/// A row of an export, with its CSV columns
+pub(crate) trait ExportRow: Serialize {
+    const COLUMNS: &'static [&'static str];
+
+    /// the fields of the row in the order of [ExportRow::COLUMNS], empty when unknown
+    fn fields(&self) -> Vec<String>;
+}
+
+impl ExportRow for HistoryRow {
+    const COLUMNS: &'static [&'static str] =
+        &["txid", "height", "amount", "fee", "counterparty", "label"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.txid.clone(),
+            optional(self.height),
+            self.amount.to_string(),
+            optional(self.fee),
+            self.counterparty.clone().unwrap_or_default(),
+            self.label.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl ExportRow for FiatHistoryRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "txid",
+        "height",
+        "amount",
+        "fee",
+        "counterparty",
+        "label",
+        "currency",
+        "value_at_time",
+        "value_now",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        let mut fields = self.row.fields();
+        fields.extend([
+            self.currency.clone(),
+            self.value_at_time.clone().unwrap_or_default(),
+            self.value_now.clone().unwrap_or_default(),
+        ]);
+        fields
+    }
+}
+
+impl ExportRow for UtxoRow {
+    const COLUMNS: &'static [&'static str] =
+        &["txid", "vout", "height", "amount", "address", "label"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.txid.clone(),
+            self.vout.to_string(),
+            optional(self.height),
+            self.amount.to_string(),
+            self.address.clone(),
+            self.label.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// write rows in a format
+pub(crate) fn write_rows<R: ExportRow, W: Write>(
+    format: ExportFormat,
+    rows: &[R],
+    mut writer: W,
+) -> Result<(), WalletError> {
+    let error = |e: std::io::Error| WalletError::Write(format!("Failed to export: {}", e));
+
+    match format {
+        ExportFormat::Csv => {
+            writeln!(writer, "{}", R::COLUMNS.join(",")).map_err(error)?;
+            for row in rows {
+                let fields: Vec<String> =
+                    row.fields().iter().map(|field| csv_field(field)).collect();
+                writeln!(writer, "{}", fields.join(",")).map_err(error)?;
+            }
+        }
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, rows)
+                .map_err(|e| WalletError::Write(format!("Failed to export: {}", e)))?;
+            writeln!(writer).map_err(error)?;
+        }
+    }
+
+    writer.flush().map_err(error)
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// quote a field holding a separator, quote or line break (RFC 4180)
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
@@ -0,0 +1,167 @@
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+use serde::{Serialize, Serializer};
+
+/// satoshis in a bitcoin
+const SATS_PER_BTC: i128 = 100_000_000;
+
+/// A fiat currency, by its ISO 4217 code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub const USD: Currency = Currency(*b"USD");
+    pub const EUR: Currency = Currency(*b"EUR");
+    pub const GBP: Currency = Currency(*b"GBP");
+    pub const JPY: Currency = Currency(*b"JPY");
+    pub const CHF: Currency = Currency(*b"CHF");
+    pub const CAD: Currency = Currency(*b"CAD");
+    pub const AUD: Currency = Currency(*b"AUD");
+
+    /// a currency from its code of three letters, eg `NZD`
+    pub fn new(code: &str) -> Option<Self> {
+        let code = code.to_ascii_uppercase();
+        match <[u8; 3]>::try_from(code.as_bytes()) {
+            Ok(code) if code.iter().all(u8::is_ascii_alphabetic) => Some(Currency(code)),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &str {
+        // only ascii letters are kept
+        std::str::from_utf8(&self.0).unwrap_or_default()
+    }
+
+    /// the digits after the decimal point of amounts in the currency, eg
+    /// 2 for cents
+    pub fn decimals(&self) -> u32 {
+        match &self.0 {
+            b"JPY" | b"KRW" | b"ISK" | b"CLP" | b"VND" => 0,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// An exact decimal number, eg a price or an amount of a fiat currency,
+/// worth `mantissa / 10^scale`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    /// `mantissa / 10^scale`, eg 6543210 and 2 for 65432.10
+    pub fn new(mantissa: i128, scale: u32) -> Self {
+        // trailing zeros are dropped so equal numbers compare equal
+        let (mut mantissa, mut scale) = (mantissa, scale);
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        Self { mantissa, scale }
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    /// the digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// The value of an amount of satoshis at a price of a bitcoin, rounded
+    /// half away from zero to a number of decimals. None when it overflows
+    pub fn value_of(sats: i64, price: Decimal, decimals: u32) -> Option<Decimal> {
+        // sats * price / 10^8, at the scale of the decimals
+        let numerator = (sats as i128)
+            .checked_mul(price.mantissa)?
+            .checked_mul(10i128.checked_pow(decimals)?)?;
+        let denominator = SATS_PER_BTC.checked_mul(10i128.checked_pow(price.scale)?)?;
+
+        let quotient = numerator / denominator;
+        let remainder = numerator % denominator;
+        let rounded = match remainder.abs() * 2 >= denominator {
+            true => quotient + numerator.signum(),
+            false => quotient,
+        };
+        Some(Decimal::new(rounded, decimals))
+    }
+
+    /// the number written with at least a number of decimals, eg `12.50`
+    pub fn to_string_with(&self, decimals: u32) -> String {
+        let value = match decimals >= self.scale {
+            true => Decimal {
+                mantissa: self.mantissa * 10i128.pow(decimals - self.scale),
+                scale: decimals,
+            },
+            false => *self,
+        };
+        value.to_string()
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, integer, fraction)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = String;
+
+    /// read a number like `-65432.10`, without an exponent
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid decimal {}", s);
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, s),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let digits = format!("{}{}", integer, fraction);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let mantissa: i128 = digits.parse().map_err(|_| invalid())?;
+        let mantissa = if negative { -mantissa } else { mantissa };
+        Ok(Decimal::new(mantissa, fraction.len() as u32))
+    }
+}
+
+/// written as a string, so no precision is lost to floats
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Prices of bitcoin in fiat currencies, supplied by the application from
+/// the price source of its choice. See [crate::Wallet::export_history_with_rates]
+pub trait RateProvider {
+    /// the current price of a bitcoin, none when the currency isn't known
+    fn rate(&self, fiat: Currency) -> Option<Decimal>;
+
+    /// The price of a bitcoin at a time, unix seconds. Providers without
+    /// past prices return none
+    fn rate_at(&self, fiat: Currency, time: u64) -> Option<Decimal> {
+        let _ = (fiat, time);
+        None
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    ffi::OsString,
+    fmt,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Clock, SystemClock, WalletError};
+
+/// how long a flush waits for another flush of the same wallet file to finish
+const FLUSH_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The process holding the lock of a wallet file, written in the lock file
+/// while it's held
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    /// when the lock was taken, unix seconds
+    pub since: u64,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            since: SystemClock.now(),
+        }
+    }
+
+    /// Whether the process could still be running. Only known on Linux,
+    /// elsewhere every owner is taken to be running
+    fn is_running(&self) -> bool {
+        match cfg!(target_os = "linux") {
+            true => Path::new("/proc").join(self.pid.to_string()).exists(),
+            false => true,
+        }
+    }
+}
+
+impl fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "process {} since {}", self.pid, self.since)
+    }
+}
+
+/// An advisory lock on a wallet file, held by one process at a time until
+/// it's dropped, see [crate::Wallet::open_exclusive]. The lock is taken on
+/// a `.lock` file next to the wallet file, as the wallet file is replaced
+/// on every flush. On filesystems without advisory locks the lock file alone marks
+/// the wallet as held, by a process that may have died since
+#[derive(Debug)]
+pub struct WalletLock {
+    path: PathBuf,
+    file: File,
+    /// the owner left in the lock file by a process that died holding it
+    stale: Option<LockOwner>,
+}
+
+impl WalletLock {
+    /// Lock a wallet file for this process. A wallet held by another process
+    /// fails with [WalletError::FileInUse] unless forced, which replaces its
+    /// lock file, the other process can then still write the wallet
+    pub fn acquire(wallet_file: &Path, force: bool) -> Result<Self, WalletError> {
+        let path = lock_path(wallet_file);
+        let mut file = open(&path)?;
+
+        let held = match lock_waiting(&mut file)? {
+            LockState::Locked => None,
+            LockState::HeldBy(owner) => Some(owner),
+            // the owner left in the file holds it while it runs
+            LockState::Unsupported => read_owner(&mut file)
+                .filter(|owner| owner.is_running())
+                .map(Some),
+        };
+        if let Some(owner) = held {
+            if !force {
+                return Err(WalletError::FileInUse(owner));
+            }
+            warn!(path = %path.display(), "wallet lock taken from another process");
+            let _ = fs::remove_file(&path);
+            file = open(&path)?;
+            match file.try_lock() {
+                Err(TryLockError::Error(e)) if e.kind() != ErrorKind::Unsupported => {
+                    return Err(write_error(e))
+                }
+                _ => {}
+            }
+        }
+
+        // an owner still written in a lock file nobody holds died holding it
+        let stale = read_owner(&mut file);
+        if stale.is_some() {
+            warn!(pid = ?stale.as_ref().map(|owner| owner.pid), path = %path.display(), "stale wallet lock replaced");
+        }
+        write_owner(&mut file, Some(&LockOwner::current()))?;
+
+        Ok(Self { path, file, stale })
+    }
+
+    /// the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// the owner of a lock left behind by a process that died holding it,
+    /// found when this lock was taken
+    pub fn stale_owner(&self) -> Option<&LockOwner> {
+        self.stale.as_ref()
+    }
+
+    /// whether this is the lock of a wallet file
+    pub(crate) fn holds(&self, wallet_file: &Path) -> bool {
+        self.path == lock_path(wallet_file)
+    }
+}
+
+impl Drop for WalletLock {
+    fn drop(&mut self) {
+        // the file is kept, another process may be waiting on it already
+        let _ = write_owner(&mut self.file, None);
+    }
+}
+
+/// Lock a wallet file for the time of a flush, waiting for another flush
+/// to finish. Fails with [WalletError::FileInUse] while another process
+/// holds the wallet with a [WalletLock]. None on filesystems without locks
+pub(crate) fn lock_for_flush(wallet_file: &Path) -> Result<Option<File>, WalletError> {
+    let mut file = open(&lock_path(wallet_file))?;
+
+    match lock_waiting(&mut file)? {
+        LockState::Locked => Ok(Some(file)),
+        LockState::HeldBy(owner) => Err(WalletError::FileInUse(owner)),
+        LockState::Unsupported => match read_owner(&mut file)
+            .filter(|owner| owner.pid != std::process::id() && owner.is_running())
+        {
+            Some(owner) => Err(WalletError::FileInUse(Some(owner))),
+            None => Ok(None),
+        },
+    }
+}
+
+enum LockState {
+    Locked,
+    /// by the owner written in the lock file, none for a flush that timed out
+    HeldBy(Option<LockOwner>),
+    /// the filesystem has no advisory locks
+    Unsupported,
+}
+
+/// Try to lock a lock file, waiting while a flush holds it. A wallet held
+/// open writes its owner in the file, a flush doesn't
+fn lock_waiting(file: &mut File) -> Result<LockState, WalletError> {
+    let started = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(LockState::Locked),
+            Err(TryLockError::WouldBlock) => {
+                if let Some(owner) = read_owner(file) {
+                    return Ok(LockState::HeldBy(Some(owner)));
+                }
+                if started.elapsed() > FLUSH_LOCK_TIMEOUT {
+                    return Ok(LockState::HeldBy(None));
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(TryLockError::Error(e)) if e.kind() == ErrorKind::Unsupported => {
+                return Ok(LockState::Unsupported)
+            }
+            Err(TryLockError::Error(e)) => return Err(write_error(e)),
+        }
+    }
+}
+
+/// the lock file of a wallet file, its name with `.lock` appended
+fn lock_path(wallet_file: &Path) -> PathBuf {
+    let mut path = OsString::from(wallet_file.as_os_str());
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+fn open(path: &Path) -> Result<File, WalletError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(write_error)?;
+    }
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(write_error)
+}
+
+/// the owner written in a lock file, none when it's empty or unreadable
+fn read_owner(file: &mut File) -> Option<LockOwner> {
+    let mut data = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut data).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+/// replace the owner written in a lock file, emptying it for none
+fn write_owner(file: &mut File, owner: Option<&LockOwner>) -> Result<(), WalletError> {
+    file.set_len(0).map_err(write_error)?;
+    file.seek(SeekFrom::Start(0)).map_err(write_error)?;
+    if let Some(owner) = owner {
+        serde_json::to_writer(&mut *file, owner)
+            .map_err(|e| WalletError::Write(format!("Failed to write lock file: {}", e)))?;
+    }
+    file.flush().map_err(write_error)
+}
+
+fn write_error(e: std::io::Error) -> WalletError {
+    WalletError::Write(format!("Failed to lock wallet file: {}", e))
+}
@@ -0,0 +1,168 @@
+use libarena::Arena;
+
+use crate::{Account, Chain, ChildNumber, KeyPair, KeyType, Network};
+
+/// An account of a wallet with the chains derived from it, see
+/// [crate::Wallet::account_views]
+#[derive(Debug, Clone, Copy)]
+pub struct AccountView<'a> {
+    arena: &'a Arena<KeyPair, String>,
+    network: Network,
+    number: u32,
+    account: &'a Account,
+}
+
+/// A chain of an account and the keys derived on it
+#[derive(Debug, Clone, Copy)]
+pub struct ChainView<'a> {
+    arena: &'a Arena<KeyPair, String>,
+    account: AccountView<'a>,
+    chain: Chain,
+}
+
+/// A key of a wallet, derived on a chain of an account or imported
+#[derive(Debug, Clone, Copy)]
+pub struct KeyView<'a> {
+    address: &'a str,
+    keypair: &'a KeyPair,
+    /// the account, chain and index the key is derived at, none for imported keys
+    derivation: Option<(u32, Chain, u32)>,
+}
+
+impl<'a> AccountView<'a> {
+    pub(crate) fn new(
+        arena: &'a Arena<KeyPair, String>,
+        network: Network,
+        number: u32,
+        account: &'a Account,
+    ) -> Self {
+        Self {
+            arena,
+            network,
+            number,
+            account,
+        }
+    }
+
+    /// the account number, as given to the account methods of [crate::Wallet]
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn account(&self) -> &'a Account {
+        self.account
+    }
+
+    /// the derivation path of the account key
+    pub fn path(&self) -> String {
+        self.account.path(self.network)
+    }
+
+    /// the chain of the receive addresses handed out to payers
+    pub fn receive_chain(&self) -> ChainView<'a> {
+        self.chain(Chain::External)
+    }
+
+    /// the chain of the change addresses the wallet pays itself with
+    pub fn change_chain(&self) -> ChainView<'a> {
+        self.chain(Chain::Internal)
+    }
+
+    pub fn chain(&self, chain: Chain) -> ChainView<'a> {
+        ChainView {
+            arena: self.arena,
+            account: *self,
+            chain,
+        }
+    }
+
+    /// the receive and change chains
+    pub fn chains(&self) -> [ChainView<'a>; 2] {
+        [self.receive_chain(), self.change_chain()]
+    }
+}
+
+impl<'a> ChainView<'a> {
+    pub fn chain(&self) -> Chain {
+        self.chain
+    }
+
+    pub fn account(&self) -> AccountView<'a> {
+        self.account
+    }
+
+    /// the derivation path of the chain, eg `m/84'/0'/0'/1`
+    pub fn path(&self) -> String {
+        format!("{}/{}", self.account.path(), self.chain.index())
+    }
+
+    /// how many addresses of the chain were handed out
+    pub fn issued(&self) -> u32 {
+        self.account.account.next_index(self.chain)
+    }
+
+    /// Every key derived on the chain in index order, the addresses handed
+    /// out and those derived ahead of them while scanning
+    pub fn keys(&self) -> Vec<KeyView<'a>> {
+        let nodes = self.arena.nodes();
+        let chain_node = nodes.iter().position(|node| {
+            node.parent() == Some(self.account.account.node())
+                && node.data.index == Some(ChildNumber::Normal(self.chain.index()))
+        });
+        let chain_node = match chain_node {
+            Some(chain_node) => chain_node,
+            None => return vec![],
+        };
+
+        let mut keys: Vec<KeyView<'a>> = nodes
+            .iter()
+            .filter(|node| node.parent() == Some(chain_node))
+            .filter_map(|node| match node.data.index {
+                Some(ChildNumber::Normal(index)) => Some(KeyView {
+                    address: &node.key,
+                    keypair: &node.data,
+                    derivation: Some((self.account.number, self.chain, index)),
+                }),
+                _ => None,
+            })
+            .collect();
+        keys.sort_by_key(|key| key.index());
+        keys
+    }
+}
+
+impl<'a> KeyView<'a> {
+    pub(crate) fn imported(address: &'a str, keypair: &'a KeyPair) -> Self {
+        Self {
+            address,
+            keypair,
+            derivation: None,
+        }
+    }
+
+    pub fn address(&self) -> &'a str {
+        self.address
+    }
+
+    pub fn public_key(&self) -> &'a [u8] {
+        &self.keypair.public_key
+    }
+
+    pub fn key_type(&self) -> &'a KeyType {
+        &self.keypair.key_type
+    }
+
+    /// the index of the key on its chain, none for imported keys
+    pub fn index(&self) -> Option<u32> {
+        self.derivation.map(|(_, _, index)| index)
+    }
+
+    /// the account, chain and index the key is derived at, none for imported keys
+    pub fn derivation(&self) -> Option<(u32, Chain, u32)> {
+        self.derivation
+    }
+
+    pub fn is_imported(&self) -> bool {
+        self.derivation.is_none()
+    }
+}
@@ -1,24 +1,136 @@
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt,
+};
+
+use bech32::{FromBase32, ToBase32, Variant};
 use bip0039::Mnemonic;
-use ecdsa::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
-use k256::{EncodedPoint, ProjectivePoint};
-use num_bigint::BigInt;
-use secp256k1::{constants::CURVE_ORDER, Message, PublicKey, Secp256k1, SecretKey};
+use secp256k1::{schnorrsig, All, Message, PublicKey, Secp256k1, SecretKey};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    hmac_sha512_hash, ripemd160_hash, sha256_hash, sha256_hash_twice, sha512_hash, ChildKeyType,
-    KeyError, Network,
+    base58check_encode, constant_time_eq, hash160, hmac_sha512_hash, ripemd160_hash, sha256_hash,
+    sha256_hash_twice, sha512_hash, tagged_hash, trace::REDACTED, ChildNumber, KeyError, Network,
 };
 
+/// A 32 byte digest, what keys sign. Data is hashed into one first so a
+/// key never signs unhashed bytes or a hex string by mistake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Digest32([u8; 32]);
+
+impl Digest32 {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// the BIP340 tagged hash of data, separating its domain from that of
+    /// data hashed under other tags
+    pub fn tagged(tag: &str, data: &[u8]) -> Self {
+        Self::from_hash(tagged_hash(tag, data))
+    }
+
+    /// the double SHA256 of data, as transactions are hashed
+    pub fn sha256d(data: &[u8]) -> Self {
+        Self::from_hash(sha256_hash_twice(&data.to_vec()))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    fn from_hash(hash: Vec<u8>) -> Self {
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&hash);
+        Self(bytes)
+    }
+}
+
+impl From<[u8; 32]> for Digest32 {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+/// fails with [KeyError::InvalidFormat] unless the bytes are 32 long
+impl TryFrom<&[u8]> for Digest32 {
+    type Error = KeyError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        bytes
+            .try_into()
+            .map(Self)
+            .map_err(|_| KeyError::InvalidFormat)
+    }
+}
+
+impl AsRef<[u8]> for Digest32 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// the key of the HMAC-SHA512 of a seed giving the master key, from BIP32
+const BIP32_SEED_KEY: &[u8] = b"Bitcoin seed";
+
+/// How the master key of a wallet is derived from its seed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MasterKeyDerivation {
+    /// the HMAC-SHA512 of the seed keyed with "Bitcoin seed", as BIP32
+    /// specifies and every other wallet derives it
+    Bip32,
+    /// the SHA512 of the seed, as wallets written before version 1 derived it.
+    /// Their addresses can only be recovered from the mnemonic by waller
+    Legacy,
+}
+
 /// a bitcoin private key
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Key {
     bytes: Vec<u8>,
     network: Network,
     compress_public_keys: bool,
     chain_code: Vec<u8>,
+    /// how many derivations away from the master key, 0 for master and imported keys
+    #[serde(default)]
+    depth: u8,
+    #[serde(default)]
+    parent_fingerprint: [u8; 4],
+    /// the child number the key was derived at, none for master and imported keys
+    #[serde(default)]
+    child_number: Option<ChildNumber>,
+}
+
+/// the private key and chain code are redacted
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("bytes", &REDACTED)
+            .field("network", &self.network)
+            .field("compress_public_keys", &self.compress_public_keys)
+            .field("chain_code", &REDACTED)
+            .field("depth", &self.depth)
+            .field("parent_fingerprint", &hex::encode(self.parent_fingerprint))
+            .field("child_number", &self.child_number)
+            .finish()
+    }
+}
+
+/// the private key and chain code are compared in constant time
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        let secrets = constant_time_eq(&self.bytes, &other.bytes)
+            & constant_time_eq(&self.chain_code, &other.chain_code);
+        secrets
+            && self.network == other.network
+            && self.compress_public_keys == other.compress_public_keys
+            && self.depth == other.depth
+            && self.parent_fingerprint == other.parent_fingerprint
+            && self.child_number == other.child_number
+    }
 }
 
+impl Eq for Key {}
+
 impl Key {
     /// Create a new recoverable key from a BIP39 conforming mnemonic phrase
     pub fn new(
@@ -26,9 +138,56 @@ impl Key {
         network: Network,
         compress_public_keys: bool,
     ) -> Result<Self, KeyError> {
-        let mnemonic = Mnemonic::from_phrase(mnemonic).unwrap();
+        Self::new_with(
+            mnemonic,
+            MasterKeyDerivation::Bip32,
+            network,
+            compress_public_keys,
+        )
+    }
+
+    /// Create a master key from a BIP39 mnemonic phrase, its seed turned
+    /// into a key as [MasterKeyDerivation] says
+    pub fn new_with(
+        mnemonic: String,
+        derivation: MasterKeyDerivation,
+        network: Network,
+        compress_public_keys: bool,
+    ) -> Result<Self, KeyError> {
+        let mnemonic = Mnemonic::from_phrase(mnemonic)
+            .map_err(|e| KeyError::BadMnemonicPhrase(e.to_string()))?;
         let seed = mnemonic.to_seed("");
-        let mut hash = sha512_hash(&seed.to_vec());
+
+        Self::from_seed_with(&seed, derivation, network, compress_public_keys)
+    }
+
+    /// Create a new master key from a seed, as BIP32 does
+    pub fn from_seed(
+        seed: &[u8],
+        network: Network,
+        compress_public_keys: bool,
+    ) -> Result<Self, KeyError> {
+        Self::from_seed_with(
+            seed,
+            MasterKeyDerivation::Bip32,
+            network,
+            compress_public_keys,
+        )
+    }
+
+    /// create a master key from a seed as [MasterKeyDerivation] says
+    pub fn from_seed_with(
+        seed: &[u8],
+        derivation: MasterKeyDerivation,
+        network: Network,
+        compress_public_keys: bool,
+    ) -> Result<Self, KeyError> {
+        let mut hash = match derivation {
+            MasterKeyDerivation::Bip32 => {
+                hmac_sha512_hash(&seed.to_vec(), &BIP32_SEED_KEY.to_vec())
+            }
+            MasterKeyDerivation::Legacy => sha512_hash(&seed.to_vec()),
+        };
 
         let chain_code = hash.split_off(32);
 
@@ -40,6 +199,34 @@ impl Key {
             network,
             compress_public_keys,
             chain_code,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: None,
+        })
+    }
+
+    /// Create a key from an extended private key, the private key
+    /// followed by its chain code as given by [Key::extended_private_key]
+    pub fn from_extended_private_key(
+        bytes: &[u8],
+        network: Network,
+        compress_public_keys: bool,
+    ) -> Result<Self, KeyError> {
+        if bytes.len() != 64 {
+            return Err(KeyError::InvalidFormat);
+        }
+
+        let (private_key, chain_code) = bytes.split_at(32);
+        SecretKey::from_slice(private_key).map_err(|e| KeyError::Other(e.to_string()))?;
+
+        Ok(Self {
+            bytes: private_key.to_vec(),
+            network,
+            compress_public_keys,
+            chain_code: chain_code.to_vec(),
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: None,
         })
     }
 
@@ -55,11 +242,12 @@ impl Key {
             return Err(KeyError::ChecksumMismatch);
         }
 
-        let network = match decoded.remove(0) {
-            0x80 => Network::Mainnet,
-            0xef => Network::Testnet,
-            _ => return Err(KeyError::InvalidNetworkByte),
-        };
+        let prefix = decoded.remove(0);
+        let network = [Network::Mainnet, Network::Testnet]
+            .iter()
+            .find(|network| network.wif_prefix() == prefix)
+            .copied()
+            .ok_or(KeyError::InvalidNetworkByte)?;
 
         let last_byte = match decoded.last() {
             Some(byte) => byte,
@@ -84,17 +272,16 @@ impl Key {
             network,
             compress_public_keys,
             chain_code,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: None,
         })
     }
 
     /// return the wif representation of the key
     pub fn to_wif(&self) -> String {
         let mut key = self.bytes.clone();
-
-        match self.network {
-            Network::Mainnet => key.insert(0, 0x80),
-            Network::Testnet => key.insert(0, 0xef),
-        }
+        key.insert(0, self.network.wif_prefix());
 
         if self.compress_public_keys {
             key.push(0x01);
@@ -124,22 +311,38 @@ impl Key {
         Ok(key)
     }
 
-    /// generate a base58 encoded address from this key
+    /// Generate a base58 encoded P2PKH address from this key. The address
+    /// commits to the compressed or uncompressed public key, so the same
+    /// private key has two addresses, see [Key::to_compressed]
     pub fn address(&self) -> Result<String, KeyError> {
-        let pubkey = self.new_public_key()?;
-        let f_hash = sha256_hash(&pubkey);
-        let mut encrypted_pubkey = ripemd160_hash(&f_hash);
+        Ok(p2pkh_address(&self.new_public_key()?, self.network))
+    }
 
-        match self.network {
-            Network::Mainnet => encrypted_pubkey.insert(0, 0x00),
-            Network::Testnet => encrypted_pubkey.insert(0, 0x6f),
-        }
+    /// the redeem script of a P2SH-P2WPKH output, a version 0 witness program
+    /// committing to the compressed public key
+    pub fn nested_segwit_redeem_script(&self) -> Result<Vec<u8>, KeyError> {
+        nested_segwit_redeem_script(&self.new_public_key()?)
+    }
 
-        let mut checksum = sha256_hash_twice(&encrypted_pubkey);
+    /// generate a base58 encoded P2SH-P2WPKH address from this key, as used by BIP49
+    pub fn nested_segwit_address(&self) -> Result<String, KeyError> {
+        nested_segwit_address(&self.new_public_key()?, self.network)
+    }
 
-        encrypted_pubkey.append(&mut checksum);
+    /// generate a bech32 encoded P2WPKH address from this key, as used by BIP84
+    pub fn native_segwit_address(&self) -> Result<String, KeyError> {
+        native_segwit_address(&self.new_public_key()?, self.network)
+    }
+
+    /// The BIP86 taproot output key of this key, the x-only public key
+    /// tweaked with its own hash so it commits to no script path
+    pub fn taproot_output_key(&self) -> Result<Vec<u8>, KeyError> {
+        taproot_output_key(&self.new_public_key()?)
+    }
 
-        Ok(bs58::encode(&encrypted_pubkey).into_string())
+    /// generate a bech32m encoded P2TR address from this key, as used by BIP86
+    pub fn taproot_address(&self) -> Result<String, KeyError> {
+        taproot_address(&self.new_public_key()?, self.network)
     }
 
     /// return a reference to the underlying key
@@ -157,6 +360,105 @@ impl Key {
         self.compress_public_keys
     }
 
+    /// The same key using compressed public keys. Its P2PKH address
+    /// and WIF change, segwit addresses always use compressed keys
+    pub fn to_compressed(&self) -> Key {
+        Key {
+            compress_public_keys: true,
+            ..self.clone()
+        }
+    }
+
+    /// The same key using uncompressed public keys, as legacy wallets
+    /// did. Its P2PKH address and WIF change
+    pub fn to_uncompressed(&self) -> Key {
+        Key {
+            compress_public_keys: false,
+            ..self.clone()
+        }
+    }
+
+    /// check if the private key has been wiped, this is the case
+    /// for keys held by a locked wallet
+    pub fn is_wiped(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// zero and drop the private key, keeping the chain code and metadata
+    pub(crate) fn wipe(&mut self) {
+        for byte in self.bytes.iter_mut() {
+            *byte = 0;
+        }
+        self.bytes.clear();
+    }
+
+    /// put back a private key previously removed with [Key::wipe]
+    pub(crate) fn restore(&mut self, bytes: Vec<u8>) {
+        self.bytes = bytes;
+    }
+
+    /// the chain code used to derive children of this key
+    pub fn chain_code(&self) -> &[u8] {
+        &self.chain_code
+    }
+
+    /// Check the private key is a valid scalar, not zero and below the curve
+    /// order, and the chain code is 32 bytes, eg for an extended key imported
+    /// from outside waller
+    pub fn validate(&self) -> Result<(), KeyError> {
+        if self.chain_code.len() != 32 {
+            return Err(KeyError::InvalidFormat);
+        }
+        SecretKey::from_slice(&self.bytes)
+            .map(|_| ())
+            .map_err(|_| KeyError::InvalidPrivateKey)
+    }
+
+    /// how many derivations away from the master key, 0 for master and imported keys
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// the fingerprint of the key this one was derived from, zeros for master and imported keys
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    /// the child number this key was derived at, none for master and imported keys
+    pub fn child_number(&self) -> Option<ChildNumber> {
+        self.child_number
+    }
+
+    /// the fingerprint of this key, identifying it as the parent of its children
+    pub fn fingerprint(&self) -> Result<[u8; 4], KeyError> {
+        key_fingerprint(&self.new_public_key()?)
+    }
+
+    /// record where the key was derived from, for keys of wallets
+    /// written before the keys held it
+    pub(crate) fn set_origin(
+        &mut self,
+        depth: u8,
+        parent_fingerprint: [u8; 4],
+        child_number: ChildNumber,
+    ) {
+        self.depth = depth;
+        self.parent_fingerprint = parent_fingerprint;
+        self.child_number = Some(child_number);
+    }
+
+    /// the BIP32 extended public key of this key, with its depth, parent and child number
+    pub fn to_xpub(&self) -> Result<String, KeyError> {
+        serialize_xpub(
+            self.network,
+            self.depth,
+            self.parent_fingerprint,
+            self.child_number.map(u32::from).unwrap_or_default(),
+            &self.chain_code,
+            &self.new_public_key()?,
+        )
+    }
+
     /// get a hex encoded string of the underlying key
     pub fn hex(&self) -> String {
         hex::encode(&self.bytes)
@@ -183,96 +485,315 @@ impl Key {
 
     /// Create a child private key
     /// can be either normal or hardened
-    pub fn derive_child_private_key(
-        &self,
-        index: usize,
-        key_type: ChildKeyType,
-    ) -> Result<Key, KeyError> {
-        match key_type {
-            ChildKeyType::Normal if index > 2147483647 => return Err(KeyError::IndexOutOfRange),
-            ChildKeyType::Hardened if index < 2147483647 || index > 4294967295 => {
-                return Err(KeyError::IndexOutOfRange)
-            }
-            _ => {}
-        }
-
-        let mut hash = match key_type {
-            ChildKeyType::Normal => {
-                let mut pubkey = self.new_public_key()?;
-                pubkey.append(&mut index.to_le_bytes().to_vec());
-                hmac_sha512_hash(&pubkey, &self.chain_code)
-            }
-            ChildKeyType::Hardened => {
-                let mut bytes = self.bytes().to_vec();
-                bytes.append(&mut index.to_le_bytes().to_vec());
-                hmac_sha512_hash(&self.bytes().to_vec(), &self.chain_code)
-            }
+    pub fn derive_child_private_key(&self, child: ChildNumber) -> Result<Key, KeyError> {
+        let number = child.to_u32()?;
+        let depth = self.depth.checked_add(1).ok_or(KeyError::IndexOutOfRange)?;
+        let public_key = compress_public_key(&self.new_public_key()?)?;
+
+        // the compressed public key of a normal child, 0x00 and the private key
+        // of a hardened one, followed by the big endian child number
+        let mut data = match child {
+            ChildNumber::Normal(_) => public_key.clone(),
+            ChildNumber::Hardened(_) => [&[0x00], self.bytes()].concat(),
         };
+        data.extend_from_slice(&number.to_be_bytes());
+        let mut hash = hmac_sha512_hash(&data, &self.chain_code);
 
         let chain_code = hash.split_off(32);
 
-        let curve_order = BigInt::from_signed_bytes_le(&CURVE_ORDER);
-        let hash_int = BigInt::from_signed_bytes_le(&hash);
-        let prev_key = BigInt::from_signed_bytes_le(&self.bytes());
-
-        let key = (hash_int + prev_key) % curve_order;
-        let private_key = key.to_signed_bytes_le();
+        // the tweak is checked against the curve order in constant time, a
+        // tweak past it or a zero child key is invalid and BIP32 moves on
+        // to the next index
+        let mut secret_key =
+            SecretKey::from_slice(self.bytes()).map_err(|e| KeyError::Other(e.to_string()))?;
+        secret_key
+            .add_assign(&hash)
+            .map_err(|_| KeyError::InvalidChild(child))?;
 
         Ok(Key {
-            bytes: private_key,
+            bytes: secret_key[..].to_vec(),
             network: self.network,
             chain_code,
             compress_public_keys: self.compress_public_keys,
+            depth,
+            parent_fingerprint: key_fingerprint(&public_key)?,
+            child_number: Some(child),
         })
     }
 
-    /// Create normal, compressed child extended public key
-    pub fn derive_child_public_key(&self, index: u32) -> Result<Vec<u8>, KeyError> {
-        if index > 2147483647 {
-            return Err(KeyError::IndexOutOfRange);
+    /// Create normal, compressed child extended public key,
+    /// hardened children can't be derived from a public key
+    pub fn derive_child_public_key(&self, child: ChildNumber) -> Result<Vec<u8>, KeyError> {
+        let public_key = compress_public_key(&self.new_public_key()?)?;
+        let (point, mut chain_code) = derive_public_child(&public_key, &self.chain_code, child)?;
+
+        // append the chain to the compressed public key to create the extended public key
+        let mut bytes = point.serialize().to_vec();
+        bytes.append(&mut chain_code);
+        Ok(bytes)
+    }
+
+    /// Derive a normal child of a wiped key from its public key, eg while
+    /// the wallet holding it is locked. Returns the child wiped, with its
+    /// chain code and metadata, and its public key in the compression of
+    /// this key. Its private key can be derived once this one is restored
+    pub(crate) fn derive_wiped_child(
+        &self,
+        public_key: &[u8],
+        child: ChildNumber,
+    ) -> Result<(Key, Vec<u8>), KeyError> {
+        let depth = self.depth.checked_add(1).ok_or(KeyError::IndexOutOfRange)?;
+        let public_key = compress_public_key(public_key)?;
+        let (point, chain_code) = derive_public_child(&public_key, &self.chain_code, child)?;
+
+        let key = Key {
+            bytes: vec![],
+            network: self.network,
+            chain_code,
+            compress_public_keys: self.compress_public_keys,
+            depth,
+            parent_fingerprint: key_fingerprint(&public_key)?,
+            child_number: Some(child),
+        };
+        let child_public_key = match self.compress_public_keys {
+            true => point.serialize().to_vec(),
+            false => point.serialize_uncompressed().to_vec(),
+        };
+        Ok((key, child_public_key))
+    }
+
+    /// Sign a 32 byte digest with this key, returning the hex of the DER
+    /// encoded signature like [crate::Wallet::sign_data]. Hash the data first,
+    /// eg with [Digest32::tagged]. Fails with [KeyError::Wiped] for a key
+    /// wiped by a locked wallet
+    pub fn sign_data(&self, digest: Digest32) -> Result<Vec<u8>, KeyError> {
+        let der = self.sign_der(digest.as_bytes())?;
+        Ok(hex::encode(der).into_bytes())
+    }
+
+    /// Sign the tagged hash of data, see [Digest32::tagged], so the signature
+    /// can't be taken for one of other data, eg of a transaction
+    pub fn sign_raw_digest_with_tag(&self, tag: &str, data: &[u8]) -> Result<Vec<u8>, KeyError> {
+        self.sign_data(Digest32::tagged(tag, data))
+    }
+
+    /// Sign a 32 byte digest, returning the DER encoded signature used in scripts
+    pub(crate) fn sign_der(&self, digest: &[u8]) -> Result<Vec<u8>, KeyError> {
+        self.sign_der_with(&Secp256k1::new(), digest)
+    }
+
+    /// [Key::sign_der] with a context kept between signatures
+    pub(crate) fn sign_der_with(
+        &self,
+        secp: &Secp256k1<All>,
+        digest: &[u8],
+    ) -> Result<Vec<u8>, KeyError> {
+        if self.is_wiped() {
+            return Err(KeyError::Wiped);
         }
+        let message = Message::from_slice(digest).map_err(|e| KeyError::Other(e.to_string()))?;
+        let secret =
+            SecretKey::from_slice(self.bytes()).map_err(|e| KeyError::Other(e.to_string()))?;
+
+        Ok(secp.sign(&message, &secret).serialize_der().to_vec())
+    }
 
-        // create the inputs for hmac-sha512 (original public key || index)
-        let original_pubkey = self.new_public_key()?;
-        let mut pubkey = original_pubkey.clone();
-        pubkey.append(&mut index.to_le_bytes().to_vec());
+    /// Sign a 32 byte digest with BIP340 Schnorr for a BIP341 key path spend,
+    /// with this key tweaked as BIP86 does so it matches [Key::taproot_output_key].
+    /// `aux` is the auxiliary random data of BIP340
+    pub(crate) fn sign_taproot_with(
+        &self,
+        secp: &Secp256k1<All>,
+        digest: &[u8],
+        aux: &[u8; 32],
+    ) -> Result<Vec<u8>, KeyError> {
+        let message = Message::from_slice(digest).map_err(|e| KeyError::Other(e.to_string()))?;
+        let mut keypair = schnorrsig::KeyPair::from_seckey_slice(secp, self.bytes())
+            .map_err(|e| KeyError::Other(e.to_string()))?;
+
+        // the tweak commits to the x coordinate, the key is negated for an odd y
+        let internal_key = schnorrsig::PublicKey::from_keypair(secp, &keypair);
+        let tweak = tagged_hash("TapTweak", &internal_key.serialize());
+        keypair
+            .tweak_add_assign(secp, &tweak)
+            .map_err(|e| KeyError::Other(e.to_string()))?;
+
+        Ok(secp
+            .schnorrsig_sign_with_aux_rand(&message, &keypair, aux)
+            .as_ref()
+            .to_vec())
+    }
+}
 
-        // hash the inputs and split off the chain code right half
-        let mut hash = hmac_sha512_hash(&pubkey, &self.chain_code);
-        let mut chain_code = hash.split_off(32);
+/// The public key and chain code of a normal child of an extended public
+/// key (CKDpub of BIP32), from its compressed public key and chain code.
+/// Hardened children can't be derived from a public key
+fn derive_public_child(
+    public_key: &[u8],
+    chain_code: &[u8],
+    child: ChildNumber,
+) -> Result<(PublicKey, Vec<u8>), KeyError> {
+    let number = match child {
+        ChildNumber::Normal(_) => child.to_u32()?,
+        ChildNumber::Hardened(_) => return Err(KeyError::IndexOutOfRange),
+    };
+
+    // create the inputs for hmac-sha512 (compressed public key || index)
+    let mut data = public_key.to_vec();
+    data.extend_from_slice(&number.to_be_bytes());
+
+    // hash the inputs and split off the chain code right half
+    let mut hash = hmac_sha512_hash(&data, &chain_code.to_vec());
+    let chain_code = hash.split_off(32);
+
+    // add the point of the left half to the public key, failing like
+    // private derivation for a tweak past the curve order or infinity
+    let mut point =
+        PublicKey::from_slice(public_key).map_err(|e| KeyError::Other(e.to_string()))?;
+    point
+        .add_exp_assign(&Secp256k1::verification_only(), &hash)
+        .map_err(|_| KeyError::InvalidChild(child))?;
+    Ok((point, chain_code))
+}
 
-        // use hash as secret key to derive point
-        let hash = SecretKey::from_slice(hash.as_slice()).unwrap();
-        let point_hmac = PublicKey::from_secret_key(&Secp256k1::new(), &hash).serialize();
+/// The base58 encoded P2PKH address of a public key. It commits to the key
+/// as given, compressed or not
+pub fn p2pkh_address(public_key: &[u8], network: Network) -> String {
+    let mut pubkey_hash = hash160(&public_key.to_vec());
+    pubkey_hash.insert(0, network.p2pkh_prefix());
 
-        // convert slice from point to create another point type from this bullshit
-        let point_hmac = EncodedPoint::from_bytes(&point_hmac).unwrap();
-        let point_hmac = ProjectivePoint::from_encoded_point(&point_hmac).unwrap();
+    base58check_encode(&pubkey_hash)
+}
 
-        // create a point from the original public key
-        let point_public = EncodedPoint::from_bytes(&original_pubkey).unwrap();
-        let point_public = ProjectivePoint::from_encoded_point(&point_public).unwrap();
+/// the redeem script of a P2SH-P2WPKH output, a version 0 witness program
+/// committing to the compressed public key
+pub fn nested_segwit_redeem_script(public_key: &[u8]) -> Result<Vec<u8>, KeyError> {
+    let pubkey = compress_public_key(public_key)?;
 
-        // add the two points together
-        let point = point_hmac + point_public;
+    let mut script = vec![0x00, 0x14];
+    script.append(&mut hash160(&pubkey));
+    Ok(script)
+}
 
-        // compress the point
-        let point = point.to_encoded_point(true);
+/// the base58 encoded P2SH-P2WPKH address of a public key, as used by BIP49
+pub fn nested_segwit_address(public_key: &[u8], network: Network) -> Result<String, KeyError> {
+    let mut script_hash = hash160(&nested_segwit_redeem_script(public_key)?);
+    script_hash.insert(0, network.p2sh_prefix());
 
-        // append the chain to the public key to create the extended public key
-        let mut bytes = point.to_bytes().to_vec();
-        bytes.append(&mut chain_code);
-        Ok(bytes)
+    Ok(base58check_encode(&script_hash))
+}
+
+/// the bech32 encoded P2WPKH address of a public key, as used by BIP84
+pub fn native_segwit_address(public_key: &[u8], network: Network) -> Result<String, KeyError> {
+    let pubkey = compress_public_key(public_key)?;
+    encode_segwit_address(network, 0, &hash160(&pubkey))
+}
+
+/// The BIP86 taproot output key of a public key, its x coordinate tweaked
+/// with its own hash so it commits to no script path
+pub fn taproot_output_key(public_key: &[u8]) -> Result<Vec<u8>, KeyError> {
+    let pubkey = compress_public_key(public_key)?;
+
+    // the internal key is the x coordinate, implicitly with an even y
+    let mut output_key = schnorrsig::PublicKey::from_slice(&pubkey[1..])
+        .map_err(|e| KeyError::Other(e.to_string()))?;
+    let tweak = tagged_hash("TapTweak", &pubkey[1..]);
+    output_key
+        .tweak_add_assign(&Secp256k1::verification_only(), &tweak)
+        .map_err(|e| KeyError::Other(e.to_string()))?;
+
+    Ok(output_key.serialize().to_vec())
+}
+
+/// the bech32m encoded P2TR address of a public key, as used by BIP86
+pub fn taproot_address(public_key: &[u8], network: Network) -> Result<String, KeyError> {
+    encode_segwit_address(network, 1, &taproot_output_key(public_key)?)
+}
+
+/// Encode a witness program as a segwit address, bech32
+/// for version 0 and bech32m for later versions (BIP350)
+pub fn encode_segwit_address(
+    network: Network,
+    version: u8,
+    program: &[u8],
+) -> Result<String, KeyError> {
+    let hrp = network.bech32_hrp();
+    let variant = match version {
+        0 => Variant::Bech32,
+        _ => Variant::Bech32m,
+    };
+
+    let mut data = vec![bech32::u5::try_from_u8(version).map_err(|_| KeyError::InvalidFormat)?];
+    data.append(&mut program.to_base32());
+
+    bech32::encode(hrp, data, variant).map_err(|e| KeyError::Other(e.to_string()))
+}
+
+/// Decode a segwit address of a network into its witness version and
+/// program, checking the bech32 variant matches the version (BIP350)
+pub fn decode_segwit_address(network: Network, address: &str) -> Result<(u8, Vec<u8>), KeyError> {
+    let (hrp, data, variant) = bech32::decode(address).map_err(|_| KeyError::Decode)?;
+    if hrp != network.bech32_hrp() {
+        return Err(KeyError::InvalidNetworkByte);
     }
 
-    /// Sign a vector of bytes using this key
-    pub fn sign_data(&self, signing_bytes: Vec<u8>) -> Vec<u8> {
-        let secp = Secp256k1::new();
-        let transaction = Message::from_slice(&signing_bytes.as_slice()).unwrap();
-        let secret = SecretKey::from_slice(self.bytes()).unwrap();
-        secp.sign(&transaction, &secret)
-            .to_string()
-            .as_bytes()
-            .to_vec()
+    let (version, program) = data.split_first().ok_or(KeyError::InvalidFormat)?;
+    let version = version.to_u8();
+    let program = Vec::<u8>::from_base32(program).map_err(|_| KeyError::Decode)?;
+    let valid = match version {
+        0 => variant == Variant::Bech32 && matches!(program.len(), 20 | 32),
+        1..=16 => variant == Variant::Bech32m && (2..=40).contains(&program.len()),
+        _ => false,
+    };
+
+    match valid {
+        true => Ok((version, program)),
+        false => Err(KeyError::InvalidFormat),
     }
 }
+
+/// the first four bytes of the hash160 of a compressed public key,
+/// used by BIP32 to identify parent keys
+pub fn key_fingerprint(public_key: &[u8]) -> Result<[u8; 4], KeyError> {
+    let compressed = compress_public_key(public_key)?;
+    let hash = ripemd160_hash(&sha256_hash(&compressed));
+
+    let mut fingerprint = [0; 4];
+    fingerprint.copy_from_slice(&hash[..4]);
+    Ok(fingerprint)
+}
+
+/// convert a public key in either format to its 33 byte compressed form
+pub fn compress_public_key(public_key: &[u8]) -> Result<Vec<u8>, KeyError> {
+    let key = PublicKey::from_slice(public_key).map_err(|e| KeyError::Other(e.to_string()))?;
+    Ok(key.serialize().to_vec())
+}
+
+/// convert a public key in either format to its 65 byte uncompressed form
+pub fn decompress_public_key(public_key: &[u8]) -> Result<Vec<u8>, KeyError> {
+    let key = PublicKey::from_slice(public_key).map_err(|e| KeyError::Other(e.to_string()))?;
+    Ok(key.serialize_uncompressed().to_vec())
+}
+
+/// Serialize an extended public key in the BIP32 format
+pub fn serialize_xpub(
+    network: Network,
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: &[u8],
+    public_key: &[u8],
+) -> Result<String, KeyError> {
+    let mut bytes = network.xpub_version().to_vec();
+
+    bytes.push(depth);
+    bytes.extend_from_slice(&parent_fingerprint);
+    bytes.extend_from_slice(&child_number.to_be_bytes());
+    bytes.extend_from_slice(chain_code);
+    bytes.append(&mut compress_public_key(public_key)?);
+
+    let checksum = sha256_hash_twice(&bytes);
+    bytes.extend_from_slice(&checksum[..4]);
+
+    Ok(bs58::encode(bytes).into_string())
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use zeroize::Zeroize;
+
+/// the length of the private keys held by a [KeyCache]
+const SECRET_LEN: usize = 32;
+
+/// How a [KeyCache] is doing, see [crate::Wallet::key_cache_status]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCacheStatus {
+    /// the most keys held at once
+    pub capacity: usize,
+    /// the keys held now
+    pub len: usize,
+    /// whether the keys are in memory pages locked against being swapped to disk
+    pub memory_locked: bool,
+}
+
+/// Private keys unsealed while an encrypted wallet is unlocked, see
+/// [crate::Wallet::set_key_cache]. The keys share a single buffer, zeroed
+/// when a key is evicted, purged or dropped. With the `secure-memory`
+/// feature the buffer is locked in memory on unix, it's never swapped to disk
+pub struct KeyCache {
+    /// the public key of each cached key and its slot in the buffer, least recently used first
+    entries: Vec<(Vec<u8>, usize)>,
+    secrets: Box<[u8]>,
+    memory_locked: bool,
+}
+
+impl KeyCache {
+    /// a cache holding at most `capacity` keys, the least recently used is evicted first
+    pub fn new(capacity: usize) -> Self {
+        let secrets = vec![0u8; capacity * SECRET_LEN].into_boxed_slice();
+        let memory_locked = lock_memory(&secrets);
+
+        Self {
+            entries: Vec::with_capacity(capacity),
+            secrets,
+            memory_locked,
+        }
+    }
+
+    /// the private key of a public key, when cached
+    pub fn get(&mut self, public_key: &[u8]) -> Option<&[u8]> {
+        let position = self
+            .entries
+            .iter()
+            .position(|(cached, _)| cached == public_key)?;
+        let entry = self.entries.remove(position);
+        let slot = entry.1;
+        self.entries.push(entry);
+
+        Some(&self.secrets[slot * SECRET_LEN..(slot + 1) * SECRET_LEN])
+    }
+
+    /// Cache the private key of a public key, evicting the least recently
+    /// used key when full. Keys of another length aren't cached
+    pub fn insert(&mut self, public_key: &[u8], secret: &[u8]) {
+        if secret.len() != SECRET_LEN || self.capacity() == 0 {
+            return;
+        }
+
+        let slot = match self
+            .entries
+            .iter()
+            .position(|(cached, _)| cached == public_key)
+        {
+            Some(position) => self.entries.remove(position).1,
+            None if self.entries.len() < self.capacity() => self.entries.len(),
+            None => self.entries.remove(0).1,
+        };
+        self.secrets[slot * SECRET_LEN..(slot + 1) * SECRET_LEN].copy_from_slice(secret);
+        self.entries.push((public_key.to_vec(), slot));
+    }
+
+    /// zero and forget every cached key
+    pub fn purge(&mut self) {
+        self.secrets.zeroize();
+        self.entries.clear();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.secrets.len() / SECRET_LEN
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn status(&self) -> KeyCacheStatus {
+        KeyCacheStatus {
+            capacity: self.capacity(),
+            len: self.len(),
+            memory_locked: self.memory_locked,
+        }
+    }
+}
+
+impl Drop for KeyCache {
+    fn drop(&mut self) {
+        self.purge();
+        if self.memory_locked {
+            unlock_memory(&self.secrets);
+        }
+    }
+}
+
+/// the keys are left out
+impl fmt::Debug for KeyCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyCache")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len())
+            .field("memory_locked", &self.memory_locked)
+            .finish()
+    }
+}
+
+/// keep pages in memory, returns false when the OS refused, eg over `RLIMIT_MEMLOCK`
+#[cfg(all(feature = "secure-memory", unix))]
+fn lock_memory(bytes: &[u8]) -> bool {
+    if bytes.is_empty() {
+        return false;
+    }
+    // SAFETY: the range is a live allocation, mlock doesn't touch its content
+    unsafe { libc::mlock(bytes.as_ptr() as *const libc::c_void, bytes.len()) == 0 }
+}
+
+#[cfg(not(all(feature = "secure-memory", unix)))]
+fn lock_memory(_bytes: &[u8]) -> bool {
+    false
+}
+
+#[cfg(all(feature = "secure-memory", unix))]
+fn unlock_memory(bytes: &[u8]) {
+    // SAFETY: the range was locked by lock_memory and is still allocated
+    unsafe {
+        libc::munlock(bytes.as_ptr() as *const libc::c_void, bytes.len());
+    }
+}
+
+#[cfg(not(all(feature = "secure-memory", unix)))]
+fn unlock_memory(_bytes: &[u8]) {}
@@ -0,0 +1,69 @@
+use std::fmt::Debug;
+
+use crate::SignerError;
+
+/// A credential store holding secrets outside of the wallet file,
+/// eg the master key moved out with [crate::Wallet::store_master_key]
+pub trait KeystoreBackend: Debug + Send + Sync {
+    /// store a secret under an id, replacing any secret stored under it
+    fn store(&self, id: &str, secret: &[u8]) -> Result<(), SignerError>;
+
+    /// the secret stored under an id, if any
+    fn load(&self, id: &str) -> Result<Option<Vec<u8>>, SignerError>;
+
+    /// remove the secret stored under an id, removing a missing secret is not an error
+    fn remove(&self, id: &str) -> Result<(), SignerError>;
+}
+
+/// The credential store of the operating system, the macOS Keychain,
+/// Windows Credential Manager or the Linux kernel keyring.
+/// Secrets are stored as passwords of the service name, by id
+#[cfg(feature = "keychain")]
+#[derive(Debug, Clone)]
+pub struct OsKeychain {
+    service: String,
+}
+
+#[cfg(feature = "keychain")]
+impl OsKeychain {
+    pub fn new(service: &str) -> Self {
+        Self {
+            service: service.to_string(),
+        }
+    }
+
+    fn entry(&self, id: &str) -> Result<keyring::Entry, SignerError> {
+        keyring::Entry::new(&self.service, id).map_err(|e| SignerError::Backend(e.to_string()))
+    }
+}
+
+#[cfg(feature = "keychain")]
+impl Default for OsKeychain {
+    fn default() -> Self {
+        Self::new("waller")
+    }
+}
+
+#[cfg(feature = "keychain")]
+impl KeystoreBackend for OsKeychain {
+    fn store(&self, id: &str, secret: &[u8]) -> Result<(), SignerError> {
+        self.entry(id)?
+            .set_secret(secret)
+            .map_err(|e| SignerError::Backend(e.to_string()))
+    }
+
+    fn load(&self, id: &str) -> Result<Option<Vec<u8>>, SignerError> {
+        match self.entry(id)?.get_secret() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(SignerError::Backend(e.to_string())),
+        }
+    }
+
+    fn remove(&self, id: &str) -> Result<(), SignerError> {
+        match self.entry(id)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(SignerError::Backend(e.to_string())),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use libarena::Arena;
+
+use crate::{compress_public_key, key_fingerprint, ChildNumber, KeyPair, Network, Script};
+
+/// Maps from the address, output script, compressed public key,
+/// fingerprint and parent and child number of every key of a wallet to its
+/// node in the key arena, so syncs, signing and derivation don't walk the
+/// arena for each lookup. Nodes are indexed in arena order, the index is
+/// behind while it holds fewer nodes than the arena
+#[derive(Debug, Clone, Default)]
+pub(crate) struct KeyIndex {
+    indexed: usize,
+    by_address: HashMap<String, usize>,
+    by_script: HashMap<Vec<u8>, usize>,
+    by_pubkey: HashMap<Vec<u8>, usize>,
+    /// fingerprints are 4 bytes, several keys can share one
+    by_fingerprint: HashMap<[u8; 4], Vec<usize>>,
+    by_child: HashMap<(usize, ChildNumber), usize>,
+}
+
+impl KeyIndex {
+    /// index the nodes added to the arena since the last update, or every
+    /// node when the index holds more than the arena, eg after it was rebuilt
+    pub(crate) fn update(&mut self, arena: &Arena<KeyPair, String>, network: Network) {
+        if self.indexed > arena.count() {
+            *self = Self::default();
+        }
+        for (node_id, node) in arena.nodes().iter().enumerate().skip(self.indexed) {
+            self.by_address.entry(node.key.clone()).or_insert(node_id);
+            if let Ok(script) = Script::from_address(&node.key, network) {
+                self.by_script.entry(script.into_bytes()).or_insert(node_id);
+            }
+            if let Ok(pubkey) = compress_public_key(&node.data.public_key) {
+                self.by_pubkey.entry(pubkey).or_insert(node_id);
+            }
+            if let Ok(fingerprint) = key_fingerprint(&node.data.public_key) {
+                self.by_fingerprint
+                    .entry(fingerprint)
+                    .or_default()
+                    .push(node_id);
+            }
+            if let (Some(parent), Some(index)) = (node.parent(), node.data.index) {
+                self.by_child.entry((parent, index)).or_insert(node_id);
+            }
+        }
+        self.indexed = arena.count();
+    }
+
+    /// whether every node of the arena is indexed
+    pub(crate) fn is_current(&self, arena: &Arena<KeyPair, String>) -> bool {
+        self.indexed == arena.count()
+    }
+
+    pub(crate) fn by_address(&self, address: &str) -> Option<usize> {
+        self.by_address.get(address).copied()
+    }
+
+    pub(crate) fn by_script(&self, script: &[u8]) -> Option<usize> {
+        self.by_script.get(script).copied()
+    }
+
+    /// the node of a public key, compressed or not
+    pub(crate) fn by_pubkey(&self, public_key: &[u8]) -> Option<usize> {
+        let compressed = compress_public_key(public_key).ok()?;
+        self.by_pubkey.get(&compressed).copied()
+    }
+
+    pub(crate) fn by_fingerprint(&self, fingerprint: [u8; 4]) -> &[usize] {
+        self.by_fingerprint
+            .get(&fingerprint)
+            .map_or(&[], |nodes| nodes.as_slice())
+    }
+
+    /// the node of the child of a node at an index
+    pub(crate) fn by_child(&self, parent: usize, index: ChildNumber) -> Option<usize> {
+        self.by_child.get(&(parent, index)).copied()
+    }
+}
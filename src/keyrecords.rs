@@ -0,0 +1,107 @@
+use libarena::Arena;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::KeyPair;
+
+/// A key of a wallet file, with the address it's found by and the
+/// position of the key it was derived from among the records
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyRecord {
+    pub address: String,
+    /// none for the master key and imported keys
+    pub parent: Option<usize>,
+    pub keypair: KeyPair,
+}
+
+/// The key graph of a wallet as written to its file, a list of keys with
+/// parents before their children. It's kept apart from the arena holding
+/// the keys in memory so the file doesn't change with the arena crate
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct KeyRecords {
+    /// the position of the master key
+    pub root: Option<usize>,
+    pub records: Vec<KeyRecord>,
+}
+
+impl KeyRecords {
+    /// the keys of an arena, in the order it holds them
+    pub(crate) fn from_arena(arena: &Arena<KeyPair, String>) -> Self {
+        Self {
+            root: arena.root(),
+            records: arena
+                .nodes()
+                .iter()
+                .map(|node| KeyRecord {
+                    address: node.key.clone(),
+                    parent: node.parent(),
+                    keypair: node.data.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// an arena holding the keys at their positions
+    pub(crate) fn into_arena(self) -> Arena<KeyPair, String> {
+        let mut arena = Arena::new();
+        for record in self.records {
+            arena.insert(record.keypair, record.address, record.parent);
+        }
+        arena.set_root(self.root);
+        arena
+    }
+}
+
+/// a key node as wallet files written before [KeyRecords] hold it, the
+/// serialized node of the arena crate
+#[derive(Deserialize)]
+pub(crate) struct ArenaNode {
+    parent: Option<usize>,
+    key: String,
+    data: KeyPair,
+}
+
+impl From<ArenaNode> for KeyRecord {
+    fn from(node: ArenaNode) -> Self {
+        Self {
+            address: node.key,
+            parent: node.parent,
+            keypair: node.data,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ArenaFile {
+    root: Option<usize>,
+    nodes: Vec<ArenaNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredKeys {
+    Records(KeyRecords),
+    Arena(ArenaFile),
+}
+
+/// write the key arena of a wallet as its [KeyRecords]
+pub(crate) fn serialize_arena<S: Serializer>(
+    arena: &Arena<KeyPair, String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    KeyRecords::from_arena(arena).serialize(serializer)
+}
+
+/// read the key arena of a wallet from its [KeyRecords], or from the arena
+/// of older wallet files
+pub(crate) fn deserialize_arena<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Arena<KeyPair, String>, D::Error> {
+    let records = match StoredKeys::deserialize(deserializer)? {
+        StoredKeys::Records(records) => records,
+        StoredKeys::Arena(arena) => KeyRecords {
+            root: arena.root,
+            records: arena.nodes.into_iter().map(KeyRecord::from).collect(),
+        },
+    };
+    Ok(records.into_arena())
+}
@@ -4,22 +4,154 @@
 
 mod test;
 
+#[macro_use]
+mod trace;
+
+mod account;
+mod address;
+mod annotate;
+mod appkey;
+mod backend;
+mod broadcast;
+mod builder;
+mod config;
+mod coredump;
+#[cfg(feature = "cross-check")]
+mod cross_check;
+mod decode;
+mod descriptor;
+#[cfg(feature = "devtools")]
+mod devtools;
+mod encryption;
+mod entropy;
+mod events;
+mod export;
+mod fiat;
+mod filelock;
+mod hierarchy;
 mod key;
+mod keycache;
+mod keychain;
+mod keyindex;
+mod keyrecords;
+mod locktime;
+mod manager;
+mod mempool;
+mod mockbackend;
+mod multisig;
+#[cfg(feature = "musig2")]
+mod musig;
+mod network;
+mod offline;
+mod ownership;
+mod package;
+mod paper;
+#[cfg(feature = "payjoin")]
+mod payjoin;
+#[cfg(any(test, feature = "policy"))]
+mod policy;
+mod preview;
+mod proxy;
+mod psbt;
+mod readonly;
+mod reserves;
+mod retry;
+mod rpc;
+mod script;
+mod shamir;
+mod shared;
+mod sighash;
+mod signer;
+mod silentpayment;
+mod snapshot;
+mod stats;
 mod transaction;
 mod types;
 mod utils;
+mod utxo;
 mod wallet;
 
+#[cfg(any(test, feature = "test-vectors"))]
+pub mod test_vectors;
+
+pub use account::*;
+pub use address::*;
+pub use annotate::*;
+pub use appkey::*;
+pub use backend::*;
 use bip0039::Count;
 use bip0039::Mnemonic;
+pub use broadcast::*;
+pub use builder::*;
+pub use config::*;
+pub use coredump::*;
+pub use decode::*;
+pub use descriptor::*;
+#[cfg(feature = "devtools")]
+pub use devtools::*;
+pub use encryption::*;
+pub use entropy::*;
+pub use events::*;
+pub use export::*;
+pub use fiat::*;
+pub use filelock::*;
+pub use hierarchy::*;
 pub use key::*;
+pub use keycache::*;
+pub use keychain::*;
+pub use keyrecords::*;
+pub use locktime::*;
+pub use manager::*;
+pub use mempool::*;
+pub use mockbackend::*;
+pub use multisig::*;
+#[cfg(feature = "musig2")]
+pub use musig::*;
+pub use network::*;
+pub use offline::*;
+pub use ownership::*;
+pub use package::*;
+pub use paper::*;
+#[cfg(feature = "payjoin")]
+pub use payjoin::*;
+#[cfg(any(test, feature = "policy"))]
+pub use policy::*;
+pub use preview::*;
+pub use proxy::*;
+pub(crate) use psbt::*;
+pub use readonly::*;
+pub use reserves::*;
+pub use retry::*;
+pub use rpc::*;
+pub use script::*;
+pub use shamir::*;
+pub use shared::*;
+pub use sighash::*;
+pub use signer::*;
+pub use silentpayment::*;
+pub use snapshot::*;
+pub use stats::*;
 pub use transaction::*;
 pub use types::*;
 pub use utils::*;
+pub use utxo::*;
 pub use wallet::*;
 
 /// Generate a mnemonic for use with HDWs
 pub fn generate_mnemonic() -> String {
-    let mnemonic = Mnemonic::generate(Count::Words12);
+    generate_mnemonic_with(&OsEntropy)
+}
+
+/// Generate a 12 word mnemonic from the entropy of a source
+pub fn generate_mnemonic_with(source: &dyn EntropySource) -> String {
+    let entropy = source.random_bytes(Count::Words12.entropy_bits() / 8);
+    let mnemonic = Mnemonic::from_entropy(entropy).expect("16 bytes of entropy");
     mnemonic.phrase().to_string()
 }
+
+/// Derive the BIP39 seed of a mnemonic phrase protected by a passphrase
+pub fn mnemonic_to_seed(mnemonic: String, passphrase: &str) -> Result<Vec<u8>, KeyError> {
+    let mnemonic =
+        Mnemonic::from_phrase(mnemonic).map_err(|e| KeyError::BadMnemonicPhrase(e.to_string()))?;
+    Ok(mnemonic.to_seed(passphrase).to_vec())
+}
@@ -0,0 +1,107 @@
+use std::{
+    fmt::{self, Debug},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// lock times below this are block heights, from it on unix timestamps
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// The lock time of a transaction, the block height or time before which
+/// it can't be mined. Serialized as a single `u32` told apart by [LOCKTIME_THRESHOLD]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockTime {
+    /// the height of the last block the transaction can't be mined in
+    Blocks(u32),
+    /// a unix timestamp in seconds, compared against the median time past of the chain
+    Seconds(u32),
+}
+
+impl LockTime {
+    /// the lock time of a serialized transaction
+    pub fn from_consensus(lock_time: u32) -> Self {
+        match lock_time < LOCKTIME_THRESHOLD {
+            true => LockTime::Blocks(lock_time),
+            false => LockTime::Seconds(lock_time),
+        }
+    }
+
+    /// the lock time as it is serialized
+    pub fn to_consensus_u32(self) -> u32 {
+        match self {
+            LockTime::Blocks(height) => height,
+            LockTime::Seconds(time) => time,
+        }
+    }
+
+    /// whether a transaction with this lock time can be mined in a block
+    /// at a height, with the chain's median time past at a time
+    pub fn is_satisfied_by(self, height: u32, time: u64) -> bool {
+        match self {
+            LockTime::Blocks(lock) => lock < height,
+            LockTime::Seconds(lock) => (lock as u64) < time,
+        }
+    }
+}
+
+/// no lock, the transaction can be mined in any block
+impl Default for LockTime {
+    fn default() -> Self {
+        LockTime::Blocks(0)
+    }
+}
+
+impl fmt::Display for LockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockTime::Blocks(height) => write!(f, "block {}", height),
+            LockTime::Seconds(time) => write!(f, "time {}", time),
+        }
+    }
+}
+
+/// A source of the current time, given to what builds transactions
+/// so the result doesn't depend on the wall clock under test
+pub trait Clock: Debug + Send + Sync {
+    /// the current unix time in seconds
+    fn now(&self) -> u64;
+}
+
+/// The wall clock of the system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    /// the epoch when the system clock is set before it
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+/// A clock standing still until it's set or advanced, for tests
+#[derive(Debug, Default)]
+pub struct MockClock(AtomicU64);
+
+impl MockClock {
+    pub fn new(now: u64) -> Self {
+        Self(AtomicU64::new(now))
+    }
+
+    pub fn set(&self, now: u64) {
+        self.0.store(now, Ordering::SeqCst);
+    }
+
+    /// move the clock forward by some seconds
+    pub fn advance(&self, seconds: u64) {
+        self.0.fetch_add(seconds, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
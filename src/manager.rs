@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{SharedWallet, Wallet, WalletError, WALLET_FILE_NAME};
+
+/// Named wallets kept in a directory, each in its own data directory
+/// `<path>/<name>/` holding its [WALLET_FILE_NAME].
+///
+/// Opened wallets are handed out as [SharedWallet]s, locked one by one:
+/// calls on different wallets never wait on each other, and the manager
+/// only locks its list of opened wallets while looking a wallet up.
+#[derive(Debug)]
+pub struct WalletManager {
+    path: PathBuf,
+    wallets: RwLock<HashMap<String, Arc<SharedWallet>>>,
+}
+
+impl WalletManager {
+    /// Manage the wallets in a directory, creating it if needed
+    pub fn new(path: PathBuf) -> Result<Self, WalletError> {
+        fs::create_dir_all(&path)
+            .map_err(|e| WalletError::Write(format!("Failed to create directory: {}", e)))?;
+
+        Ok(Self {
+            path,
+            wallets: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// the directory holding the wallets
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// the names of every wallet in the directory, opened or not, sorted
+    pub fn list(&self) -> Result<Vec<String>, WalletError> {
+        let entries = fs::read_dir(&self.path)
+            .map_err(|e| WalletError::Read(format!("Failed to read directory: {}", e)))?;
+
+        let mut names = vec![];
+        for entry in entries {
+            let entry =
+                entry.map_err(|e| WalletError::Read(format!("Failed to read directory: {}", e)))?;
+            if entry.path().join(WALLET_FILE_NAME).is_file() {
+                if let Some(name) = entry.file_name().to_str() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
+    /// the names of the wallets currently opened, sorted
+    pub fn opened(&self) -> Result<Vec<String>, WalletError> {
+        let mut names: Vec<String> = self.read_wallets()?.keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Add a wallet under a new name, eg one just created or restored. The
+    /// wallet is moved to the data directory of the name, written and opened
+    pub fn create(&self, name: &str, mut wallet: Wallet) -> Result<Arc<SharedWallet>, WalletError> {
+        let path = self.wallet_path(name)?;
+
+        let mut wallets = self.write_wallets()?;
+        if wallets.contains_key(name) || path.join(WALLET_FILE_NAME).exists() {
+            return Err(WalletError::WalletExists(name.to_string()));
+        }
+
+        fs::create_dir_all(&path)
+            .map_err(|e| WalletError::Write(format!("Failed to create directory: {}", e)))?;
+        wallet.set_path(path);
+        wallet.flush()?;
+
+        let wallet = Arc::new(SharedWallet::new(wallet));
+        wallets.insert(name.to_string(), wallet.clone());
+        Ok(wallet)
+    }
+
+    /// Open a wallet by name, reading it from its file the first time.
+    /// Every call returns the same [SharedWallet] until the wallet is closed
+    pub fn open(&self, name: &str) -> Result<Arc<SharedWallet>, WalletError> {
+        let path = self.wallet_path(name)?;
+        if let Some(wallet) = self.read_wallets()?.get(name) {
+            return Ok(wallet.clone());
+        }
+
+        let mut wallets = self.write_wallets()?;
+        // opened by another thread while the lock was released
+        if let Some(wallet) = wallets.get(name) {
+            return Ok(wallet.clone());
+        }
+
+        let file = path.join(WALLET_FILE_NAME);
+        if !file.is_file() {
+            return Err(WalletError::WalletNotFound(name.to_string()));
+        }
+        let mut wallet = Wallet::from_wallet_file(file)?;
+        // the directory may have moved since the wallet was written
+        wallet.set_path(path);
+
+        let wallet = Arc::new(SharedWallet::new(wallet));
+        wallets.insert(name.to_string(), wallet.clone());
+        Ok(wallet)
+    }
+
+    /// Run a closure on a wallet by name, opening it if needed
+    pub fn with_wallet<R, F: FnOnce(&SharedWallet) -> R>(
+        &self,
+        name: &str,
+        f: F,
+    ) -> Result<R, WalletError> {
+        Ok(f(&*self.open(name)?))
+    }
+
+    /// Write a wallet to its file and stop handing it out. Clones of the
+    /// [SharedWallet] held elsewhere keep working but are no longer written
+    pub fn close(&self, name: &str) -> Result<(), WalletError> {
+        let wallet = self
+            .write_wallets()?
+            .remove(name)
+            .ok_or_else(|| WalletError::WalletNotFound(name.to_string()))?;
+
+        wallet.flush()?;
+        Ok(())
+    }
+
+    /// write every opened wallet to its file
+    pub fn flush_all(&self) -> Result<(), WalletError> {
+        let wallets: Vec<Arc<SharedWallet>> = self.read_wallets()?.values().cloned().collect();
+
+        for wallet in wallets {
+            wallet.flush()?;
+        }
+        Ok(())
+    }
+
+    /// the data directory of a wallet, names can't leave the managed directory
+    fn wallet_path(&self, name: &str) -> Result<PathBuf, WalletError> {
+        let plain = !name.is_empty()
+            && name != "."
+            && name != ".."
+            && !name.contains(|c: char| c == '/' || c == '\\' || c.is_control());
+        if !plain {
+            return Err(WalletError::InvalidWalletName(name.to_string()));
+        }
+
+        Ok(self.path.join(name))
+    }
+
+    fn read_wallets(
+        &self,
+    ) -> Result<RwLockReadGuard<'_, HashMap<String, Arc<SharedWallet>>>, WalletError> {
+        self.wallets.read().map_err(|_| WalletError::Poisoned)
+    }
+
+    fn write_wallets(
+        &self,
+    ) -> Result<RwLockWriteGuard<'_, HashMap<String, Arc<SharedWallet>>>, WalletError> {
+        self.wallets.write().map_err(|_| WalletError::Poisoned)
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+
+/// the virtual size of the transactions a block holds at most
+pub const BLOCK_VSIZE: u64 = 1_000_000;
+
+/// The fee rates the transactions of a mempool are grouped by, in satoshis
+/// per vbyte, as in the fee histogram of Electrum servers
+const BUCKET_FEE_RATES: [f64; 34] = [
+    2000.0, 1700.0, 1400.0, 1200.0, 1000.0, 800.0, 700.0, 600.0, 500.0, 400.0, 300.0, 250.0, 200.0,
+    170.0, 140.0, 120.0, 100.0, 80.0, 70.0, 60.0, 50.0, 40.0, 30.0, 20.0, 15.0, 12.0, 10.0, 8.0,
+    6.0, 5.0, 4.0, 3.0, 2.0, 1.0,
+];
+
+/// The transactions of a mempool paying at least a fee rate and less than
+/// the rate of the bucket before it, see [FeeHistogram]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeBucket {
+    /// satoshis per vbyte
+    pub fee_rate: f64,
+    /// the virtual size of the transactions of the bucket
+    pub vsize: u64,
+}
+
+/// The virtual size of the transactions waiting in a mempool by the fee
+/// rate they pay, highest first. Made by [crate::Backend::mempool_histogram]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeeHistogram {
+    pub buckets: Vec<FeeBucket>,
+}
+
+/// How busy a mempool is, counted in blocks of transactions waiting. See
+/// [FeeHistogram::congestion]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CongestionLevel {
+    /// less than a block waiting, the minimum fee confirms in the next block
+    Low,
+    /// up to 6 blocks waiting, about an hour
+    Normal,
+    /// up to 36 blocks waiting, about 6 hours, fees are higher than usual
+    High,
+    /// more waiting, fees are unusually high
+    Extreme,
+}
+
+impl fmt::Display for CongestionLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CongestionLevel::Low => write!(f, "low"),
+            CongestionLevel::Normal => write!(f, "normal"),
+            CongestionLevel::High => write!(f, "high"),
+            CongestionLevel::Extreme => write!(f, "extreme"),
+        }
+    }
+}
+
+impl CongestionLevel {
+    /// whether fees are higher than usual, to warn before spending
+    pub fn is_congested(&self) -> bool {
+        *self >= CongestionLevel::High
+    }
+}
+
+impl FeeHistogram {
+    /// Group transactions given by their fee rate, in satoshis per vbyte,
+    /// and virtual size. Transactions paying under 1 sat/vB are left out
+    pub fn from_transactions<I: IntoIterator<Item = (f64, u64)>>(transactions: I) -> Self {
+        let mut sizes = [0; BUCKET_FEE_RATES.len()];
+        for (fee_rate, vsize) in transactions {
+            if let Some(bucket) = BUCKET_FEE_RATES.iter().position(|rate| fee_rate >= *rate) {
+                sizes[bucket] += vsize;
+            }
+        }
+
+        let buckets = BUCKET_FEE_RATES
+            .iter()
+            .zip(sizes.iter())
+            .filter(|(_, vsize)| **vsize > 0)
+            .map(|(fee_rate, vsize)| FeeBucket {
+                fee_rate: *fee_rate,
+                vsize: *vsize,
+            })
+            .collect();
+        Self { buckets }
+    }
+
+    /// the virtual size of every transaction waiting
+    pub fn total_vsize(&self) -> u64 {
+        self.buckets.iter().map(|bucket| bucket.vsize).sum()
+    }
+
+    /// how many blocks the transactions waiting fill
+    pub fn backlog_blocks(&self) -> f64 {
+        self.total_vsize() as f64 / BLOCK_VSIZE as f64
+    }
+
+    /// Estimate the fee rate, in satoshis per vbyte, confirming within a
+    /// number of blocks when no other transaction comes in: just above the
+    /// lowest bucket the blocks would take, at least 1
+    pub fn estimate_fee_rate(&self, blocks: u32) -> u64 {
+        let room = BLOCK_VSIZE * blocks.max(1) as u64;
+        let mut filled = 0;
+        for bucket in self.buckets.iter() {
+            filled += bucket.vsize;
+            if filled >= room {
+                // outbid the transactions of the bucket left out of the blocks
+                return bucket.fee_rate.ceil() as u64 + 1;
+            }
+        }
+        1
+    }
+
+    pub fn congestion(&self) -> CongestionLevel {
+        match self.backlog_blocks() {
+            blocks if blocks < 1.0 => CongestionLevel::Low,
+            blocks if blocks <= 6.0 => CongestionLevel::Normal,
+            blocks if blocks <= 36.0 => CongestionLevel::High,
+            _ => CongestionLevel::Extreme,
+        }
+    }
+}
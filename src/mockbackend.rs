@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::{
+    decode_transaction, sha256_hash_twice, Backend, BackendError, Block, BlockTransaction,
+    MempoolAcceptance, Network, OutPoint, Script, Transaction, TransactionInput, TransactionOutput,
+    TransactionType,
+};
+
+/// the timestamp of the genesis block of a [MockBackend], blocks follow every ten minutes
+pub const MOCK_GENESIS_TIME: u64 = 1_600_000_000;
+
+/// seconds between the blocks of a [MockBackend]
+const MOCK_BLOCK_INTERVAL: u64 = 600;
+
+#[derive(Debug)]
+struct MockChain {
+    blocks: Vec<Block>,
+    mempool: Vec<BlockTransaction>,
+    /// the hex encoded transactions funded and relayed, by txid
+    raw: HashMap<String, String>,
+    /// blocks and funding transactions made so far, hashed into their ids
+    /// so a block mined again after a reorg gets another hash
+    nonce: u64,
+}
+
+impl MockChain {
+    fn next_hash(&mut self, tag: &str) -> String {
+        self.nonce += 1;
+        let mut preimage = tag.as_bytes().to_vec();
+        preimage.extend_from_slice(&self.nonce.to_le_bytes());
+        hex::encode(sha256_hash_twice(&preimage))
+    }
+
+    fn mine(&mut self) -> String {
+        let height = self.blocks.len() as u32;
+        let hash = self.next_hash("block");
+        let transactions = std::mem::take(&mut self.mempool);
+        self.blocks.push(Block {
+            height,
+            hash: hash.clone(),
+            time: MOCK_GENESIS_TIME + height as u64 * MOCK_BLOCK_INTERVAL,
+            transactions,
+        });
+        hash
+    }
+
+    /// the transactions of the chain and mempool
+    fn transactions(&self) -> impl Iterator<Item = &BlockTransaction> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .chain(self.mempool.iter())
+    }
+
+    /// why the mempool refuses a transaction, as Bitcoin Core tells it
+    fn check(&self, tx: &BlockTransaction) -> Result<(), BackendError> {
+        let reject = |code: i64, message: &str| {
+            Err(BackendError::Rpc {
+                code,
+                message: message.to_string(),
+            })
+        };
+
+        if self.mempool.iter().any(|pending| pending.tx_id == tx.tx_id) {
+            return reject(-27, "txn-already-in-mempool");
+        }
+        if self.blocks.iter().any(|block| {
+            block
+                .transactions
+                .iter()
+                .any(|confirmed| confirmed.tx_id == tx.tx_id)
+        }) {
+            return reject(-27, "txn-already-known");
+        }
+        for input in tx.inputs.iter() {
+            if self
+                .mempool
+                .iter()
+                .any(|pending| pending.inputs.contains(input))
+            {
+                return reject(-26, "txn-mempool-conflict");
+            }
+            if self.blocks.iter().any(|block| {
+                block
+                    .transactions
+                    .iter()
+                    .any(|confirmed| confirmed.inputs.contains(input))
+            }) {
+                return reject(-25, "bad-txns-inputs-missingorspent");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An in-memory chain to test wallet flows against without a node: outputs
+/// are funded from nowhere, relayed transactions wait in a mempool until
+/// blocks are mined and the tip can be reorganized at will. The hashes of
+/// its blocks and of the outputs funding transactions spend are made up
+#[derive(Debug)]
+pub struct MockBackend {
+    network: Network,
+    chain: Mutex<MockChain>,
+}
+
+impl MockBackend {
+    /// a chain of the genesis block alone, decoding the addresses of a network
+    pub fn new(network: Network) -> Self {
+        let mut chain = MockChain {
+            blocks: vec![],
+            mempool: vec![],
+            raw: HashMap::new(),
+            nonce: 0,
+        };
+        chain.mine();
+        Self {
+            network,
+            chain: Mutex::new(chain),
+        }
+    }
+
+    fn chain(&self) -> MutexGuard<'_, MockChain> {
+        self.chain
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// the height of the tip
+    pub fn height(&self) -> u32 {
+        self.chain().blocks.len() as u32 - 1
+    }
+
+    /// Pay an amount in satoshis to an address with a transaction spending
+    /// an output of no known transaction, left in the mempool until
+    /// [MockBackend::mine]. Returns the output paying the address
+    pub fn fund(&self, address: &str, amount: i64) -> Result<OutPoint, BackendError> {
+        let script = Script::from_address(address, self.network)
+            .map_err(|e| BackendError::InvalidResponse(e.to_string()))?;
+
+        let mut chain = self.chain();
+        let source = chain.next_hash("source");
+        let tx = Transaction::new(
+            TransactionType::Pay2PubKeyHash,
+            vec![TransactionInput::new(
+                TransactionOutput::from_script(0, vec![]),
+                source.clone(),
+                0,
+            )],
+            vec![TransactionOutput::from_script(amount, script.into_bytes())],
+            None,
+        );
+        let tx_id = tx.tx_id();
+        chain.raw.insert(tx_id.clone(), hex::encode(tx.serialize()));
+        chain.mempool.push(BlockTransaction {
+            tx_id: tx_id.clone(),
+            inputs: vec![OutPoint::new(source, 0)],
+            outputs: tx.outputs(),
+        });
+        Ok(OutPoint::new(tx_id, 0))
+    }
+
+    /// mine blocks confirming the transactions of the mempool in the first
+    /// of them, returns their hashes
+    pub fn mine(&self, blocks: u32) -> Vec<String> {
+        let mut chain = self.chain();
+        (0..blocks).map(|_| chain.mine()).collect()
+    }
+
+    /// Disconnect the blocks at the tip, as `invalidateblock` does, moving
+    /// their transactions back to the mempool. The genesis block is kept.
+    /// Returns the height of the new tip
+    pub fn disconnect(&self, blocks: u32) -> u32 {
+        let mut chain = self.chain();
+        let keep = chain.blocks.len().saturating_sub(blocks as usize).max(1);
+        let mut transactions: Vec<BlockTransaction> = chain
+            .blocks
+            .drain(keep..)
+            .flat_map(|block| block.transactions)
+            .collect();
+        transactions.append(&mut chain.mempool);
+        chain.mempool = transactions;
+        chain.blocks.len() as u32 - 1
+    }
+
+    /// Replace the blocks at the tip with as many others, confirming the
+    /// transactions they held again in the first of them. Returns the
+    /// hashes of the new blocks
+    pub fn reorg(&self, depth: u32) -> Vec<String> {
+        let before = self.height();
+        let tip = self.disconnect(depth);
+        self.mine(before - tip)
+    }
+
+    /// drop a transaction from the mempool, eg to reorganize it out of the
+    /// chain after [MockBackend::disconnect]. Returns whether it was there
+    pub fn evict(&self, tx_id: &str) -> bool {
+        let mut chain = self.chain();
+        let count = chain.mempool.len();
+        chain.mempool.retain(|tx| tx.tx_id != tx_id);
+        if chain.mempool.len() == count {
+            return false;
+        }
+        chain.raw.remove(tx_id);
+        true
+    }
+
+    /// the transactions waiting for a block
+    pub fn mempool(&self) -> Vec<BlockTransaction> {
+        self.chain().mempool.clone()
+    }
+
+    /// the blocks confirming a transaction, none while it's not in the chain
+    pub fn confirmations(&self, tx_id: &str) -> Option<u32> {
+        let chain = self.chain();
+        let height = chain
+            .blocks
+            .iter()
+            .position(|block| block.transactions.iter().any(|tx| tx.tx_id == tx_id))?;
+        Some((chain.blocks.len() - height) as u32)
+    }
+
+    /// the output of a transaction of the chain or mempool
+    pub fn output(&self, outpoint: &OutPoint) -> Option<TransactionOutput> {
+        let chain = self.chain();
+        let tx = chain
+            .transactions()
+            .find(|tx| tx.tx_id == outpoint.hash())?;
+        tx.outputs.get(outpoint.index() as usize).cloned()
+    }
+
+    /// the outputs and spent outputs of a raw transaction
+    fn decode(&self, raw_tx: &str) -> Result<BlockTransaction, BackendError> {
+        let summary = decode_transaction(raw_tx, self.network).map_err(|e| BackendError::Rpc {
+            code: -22,
+            message: format!("TX decode failed: {:?}", e),
+        })?;
+        Ok(BlockTransaction {
+            tx_id: summary.tx_id,
+            inputs: summary
+                .inputs
+                .into_iter()
+                .map(|input| input.previous_output)
+                .collect(),
+            outputs: summary
+                .outputs
+                .into_iter()
+                .map(|output| TransactionOutput::from_script(output.value, output.pk_script))
+                .collect(),
+        })
+    }
+}
+
+impl Backend for MockBackend {
+    /// accept a transaction into the mempool, refusing those spending an
+    /// output another transaction already spends
+    fn broadcast(&self, raw_tx: &str) -> Result<String, BackendError> {
+        let tx = self.decode(raw_tx)?;
+        let mut chain = self.chain();
+        chain.check(&tx)?;
+        let tx_id = tx.tx_id.clone();
+        chain.mempool.push(tx);
+        chain.raw.insert(tx_id.clone(), raw_tx.trim().to_string());
+        Ok(tx_id)
+    }
+
+    fn test_accept(&self, raw_tx: &str) -> Result<MempoolAcceptance, BackendError> {
+        let tx = self.decode(raw_tx)?;
+        let reject_reason = match self.chain().check(&tx) {
+            Ok(()) => None,
+            Err(BackendError::Rpc { message, .. }) => Some(message),
+            Err(e) => return Err(e),
+        };
+        Ok(MempoolAcceptance {
+            txid: tx.tx_id,
+            allowed: reject_reason.is_none(),
+            vsize: None,
+            fee: None,
+            reject_reason,
+        })
+    }
+
+    fn tip_height(&self) -> Result<u32, BackendError> {
+        Ok(self.height())
+    }
+
+    fn transaction(&self, tx_id: &str) -> Result<String, BackendError> {
+        self.chain()
+            .raw
+            .get(tx_id)
+            .cloned()
+            .ok_or_else(|| BackendError::Rpc {
+                code: -5,
+                message: "No such mempool or blockchain transaction".to_string(),
+            })
+    }
+
+    fn block(&self, height: u32) -> Result<Block, BackendError> {
+        self.chain()
+            .blocks
+            .get(height as usize)
+            .cloned()
+            .ok_or_else(|| BackendError::Rpc {
+                code: -8,
+                message: "Block height out of range".to_string(),
+            })
+    }
+}
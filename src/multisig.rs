@@ -0,0 +1,120 @@
+use secp256k1::{Message, PublicKey, Secp256k1, Signature};
+
+use crate::{KeyError, Network, Script, TransactionError, OP_CHECKMULTISIG};
+
+/// the largest element a script may push, which bounds the redeem script
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// the most keys that can be given as a small integer
+const MAX_MULTISIG_KEYS: usize = 16;
+
+/// An m-of-n `OP_m <pubkey>... OP_n OP_CHECKMULTISIG` script, spent through
+/// P2SH or P2WSH. Signatures must follow the order of the public keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigScript {
+    threshold: usize,
+    pubkeys: Vec<Vec<u8>>,
+}
+
+impl MultisigScript {
+    /// the public keys are kept in the order given, compressed or not
+    pub fn new(threshold: usize, pubkeys: Vec<Vec<u8>>) -> Result<Self, TransactionError> {
+        if pubkeys.is_empty() || pubkeys.len() > MAX_MULTISIG_KEYS {
+            return Err(TransactionError::InvalidMultisig(format!(
+                "{} public keys, between 1 and {} are allowed",
+                pubkeys.len(),
+                MAX_MULTISIG_KEYS
+            )));
+        }
+
+        if threshold == 0 || threshold > pubkeys.len() {
+            return Err(TransactionError::InvalidMultisig(format!(
+                "threshold of {} with {} public keys",
+                threshold,
+                pubkeys.len()
+            )));
+        }
+
+        if let Some(pubkey) = pubkeys.iter().find(|pk| PublicKey::from_slice(pk).is_err()) {
+            return Err(TransactionError::InvalidMultisig(format!(
+                "invalid public key {}",
+                hex::encode(pubkey)
+            )));
+        }
+
+        let script = Self { threshold, pubkeys };
+        if script.redeem_script().len() > MAX_SCRIPT_ELEMENT_SIZE {
+            return Err(TransactionError::InvalidMultisig(format!(
+                "redeem script larger than {} bytes",
+                MAX_SCRIPT_ELEMENT_SIZE
+            )));
+        }
+
+        Ok(script)
+    }
+
+    /// the number of signatures needed to spend
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn pubkeys(&self) -> &[Vec<u8>] {
+        &self.pubkeys
+    }
+
+    /// the script itself, the redeem script or witness script of a spend
+    pub fn to_script(&self) -> Script {
+        let mut builder = Script::builder();
+        builder.push_int(self.threshold as i64);
+        for pubkey in self.pubkeys.iter() {
+            builder.push_slice(pubkey);
+        }
+        builder
+            .push_int(self.pubkeys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .build()
+    }
+
+    /// the serialized script, pushed last by the signature script
+    pub fn redeem_script(&self) -> Vec<u8> {
+        self.to_script().into_bytes()
+    }
+
+    /// the P2SH pk script of outputs paying to this script
+    pub fn script_pubkey(&self) -> Vec<u8> {
+        self.to_script().to_p2sh().into_bytes()
+    }
+
+    /// generate a base58 encoded P2SH address paying to this script
+    pub fn address(&self, network: Network) -> String {
+        self.to_script().p2sh_address(network)
+    }
+
+    /// the P2WSH pk script of outputs paying to this script
+    pub fn p2wsh_script_pubkey(&self) -> Vec<u8> {
+        self.to_script().to_p2wsh().into_bytes()
+    }
+
+    /// generate a bech32 encoded P2WSH address paying to this script
+    pub fn p2wsh_address(&self, network: Network) -> Result<String, KeyError> {
+        self.to_script().p2wsh_address(network)
+    }
+
+    /// the position of a public key in the script
+    pub(crate) fn position(&self, pubkey: &[u8]) -> Option<usize> {
+        self.pubkeys.iter().position(|pk| pk == pubkey)
+    }
+
+    /// the position of the key that made a DER signature of a digest
+    pub(crate) fn signer_position(&self, digest: &[u8], signature: &[u8]) -> Option<usize> {
+        let message = Message::from_slice(digest).ok()?;
+        let mut signature = Signature::from_der(signature).ok()?;
+        signature.normalize_s();
+
+        let secp = Secp256k1::verification_only();
+        self.pubkeys.iter().position(|pubkey| {
+            PublicKey::from_slice(pubkey)
+                .map(|pubkey| secp.verify(&message, &signature, &pubkey).is_ok())
+                .unwrap_or(false)
+        })
+    }
+}
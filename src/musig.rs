@@ -0,0 +1,521 @@
+use std::convert::TryInto;
+
+use secp256k1::{constants::CURVE_ORDER, schnorrsig, Message, PublicKey, Secp256k1, SecretKey};
+use zeroize::Zeroize;
+
+use crate::{compress_public_key, tagged_hash, EntropySource, Key, MusigError, OsEntropy};
+
+/// a scalar mod the curve order, none for zero
+type Scalar = Option<SecretKey>;
+
+/// The keys of an n-of-n MuSig2 signing group aggregated to a single key
+/// (BIP327). The order of the keys changes the aggregate, every signer has
+/// to list them in the same order
+#[derive(Debug, Clone)]
+pub struct KeyAggContext {
+    pubkeys: Vec<PublicKey>,
+    /// the hash of every key, committed to by the coefficient of each
+    list_hash: Vec<u8>,
+    /// the first key other than the first, its coefficient is 1
+    second_key: Option<PublicKey>,
+    aggregate: PublicKey,
+    /// the accumulated negation of the aggregate by tweaks, 1 or -1
+    gacc: Scalar,
+    /// the accumulated tweak
+    tacc: Scalar,
+}
+
+impl KeyAggContext {
+    /// aggregate the compressed public keys of a signing group
+    pub fn new(pubkeys: &[Vec<u8>]) -> Result<Self, MusigError> {
+        if pubkeys.is_empty() {
+            return Err(MusigError::InvalidKey("no keys to aggregate".to_string()));
+        }
+        let pubkeys = pubkeys
+            .iter()
+            .map(|pubkey| match pubkey.len() {
+                33 => PublicKey::from_slice(pubkey)
+                    .map_err(|_| MusigError::InvalidKey(hex::encode(pubkey))),
+                _ => Err(MusigError::InvalidKey(hex::encode(pubkey))),
+            })
+            .collect::<Result<Vec<PublicKey>, MusigError>>()?;
+
+        let list: Vec<u8> = pubkeys
+            .iter()
+            .flat_map(|pubkey| pubkey.serialize())
+            .collect();
+        let mut context = Self {
+            list_hash: tagged_hash("KeyAgg list", &list),
+            second_key: pubkeys
+                .iter()
+                .find(|pubkey| **pubkey != pubkeys[0])
+                .copied(),
+            aggregate: pubkeys[0],
+            gacc: one(),
+            tacc: None,
+            pubkeys,
+        };
+
+        let points = context
+            .pubkeys
+            .iter()
+            .map(|pubkey| mul_point(pubkey, context.coefficient(pubkey)))
+            .collect::<Vec<Option<PublicKey>>>();
+        context.aggregate = add_points(&points)
+            .ok_or_else(|| MusigError::InvalidKey("the keys aggregate to infinity".to_string()))?;
+        Ok(context)
+    }
+
+    /// The aggregate tweaked to a BIP86 taproot output key committing to no
+    /// script path, the key paid to and signed for on a key path spend
+    pub fn with_taproot_tweak(&self) -> Result<Self, MusigError> {
+        let tweak = scalar(&tagged_hash("TapTweak", &self.x_only_public_key()));
+        let tweak = tweak.ok_or_else(|| MusigError::InvalidKey("a zero tweak".to_string()))?;
+
+        // x-only keys are taken with an even y, the aggregate is negated when odd
+        let g = match has_even_y(&self.aggregate) {
+            true => one(),
+            false => negate(one()),
+        };
+        let tweaked = add_points(&[
+            mul_point(&self.aggregate, g),
+            mul_point(&generator(), Some(tweak)),
+        ])
+        .ok_or_else(|| MusigError::InvalidKey("the tweak cancels the key".to_string()))?;
+
+        Ok(Self {
+            aggregate: tweaked,
+            gacc: mul(g, self.gacc),
+            tacc: add(Some(tweak), mul(g, self.tacc)),
+            ..self.clone()
+        })
+    }
+
+    /// the aggregate key, compressed
+    pub fn public_key(&self) -> Vec<u8> {
+        self.aggregate.serialize().to_vec()
+    }
+
+    /// the aggregate key as signatures are verified against, without its y
+    pub fn x_only_public_key(&self) -> Vec<u8> {
+        self.aggregate.serialize()[1..].to_vec()
+    }
+
+    /// the compressed public keys of the signers, in order
+    pub fn pubkeys(&self) -> Vec<Vec<u8>> {
+        self.pubkeys
+            .iter()
+            .map(|pubkey| pubkey.serialize().to_vec())
+            .collect()
+    }
+
+    /// the factor a key is multiplied by in the aggregate
+    fn coefficient(&self, pubkey: &PublicKey) -> Scalar {
+        match self.second_key.as_ref() == Some(pubkey) {
+            true => one(),
+            false => scalar(&tagged_hash(
+                "KeyAgg coefficient",
+                &[&self.list_hash[..], &pubkey.serialize()].concat(),
+            )),
+        }
+    }
+}
+
+/// The secret half of a nonce, used by a single [SigningSession::sign]. It
+/// can't be copied, signing twice with the same nonce leaks the key
+pub struct SecretNonce {
+    nonces: [u8; 64],
+    /// the public key of the signer it was made for
+    pubkey: PublicKey,
+}
+
+impl Drop for SecretNonce {
+    fn drop(&mut self) {
+        self.nonces.zeroize();
+    }
+}
+
+/// the nonces are left out
+impl std::fmt::Debug for SecretNonce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretNonce")
+            .field("pubkey", &self.pubkey)
+            .finish()
+    }
+}
+
+/// The public half of a nonce, sent to every other signer in the first round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicNonce([u8; 66]);
+
+impl PublicNonce {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MusigError> {
+        let nonce: [u8; 66] = bytes
+            .try_into()
+            .map_err(|_| MusigError::InvalidNonce(hex::encode(bytes)))?;
+        for point in nonce.chunks(33) {
+            PublicKey::from_slice(point)
+                .map_err(|_| MusigError::InvalidNonce(hex::encode(bytes)))?;
+        }
+        Ok(Self(nonce))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn points(&self) -> (PublicKey, PublicKey) {
+        // checked when made
+        (
+            PublicKey::from_slice(&self.0[..33]).unwrap(),
+            PublicKey::from_slice(&self.0[33..]).unwrap(),
+        )
+    }
+}
+
+/// The signature of a signer, sent in the second round to be aggregated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialSignature([u8; 32]);
+
+impl PartialSignature {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MusigError> {
+        let signature: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| MusigError::InvalidSignature(hex::encode(bytes)))?;
+        match signature == [0; 32] || SecretKey::from_slice(&signature).is_ok() {
+            true => Ok(Self(signature)),
+            false => Err(MusigError::InvalidSignature(hex::encode(bytes))),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn scalar(&self) -> Scalar {
+        SecretKey::from_slice(&self.0).ok()
+    }
+}
+
+/// the compressed public key of a key, as listed in a [KeyAggContext]
+pub fn musig_public_key(key: &Key) -> Result<Vec<u8>, MusigError> {
+    key.new_public_key()
+        .and_then(|pubkey| compress_public_key(&pubkey))
+        .map_err(|e| MusigError::InvalidKey(e.to_string()))
+}
+
+/// Make the nonces of a signer for signing a message with the key of a group,
+/// the first round. Fresh randomness is mixed with the key, the group and the
+/// message, a weak rng alone doesn't repeat a nonce across messages
+pub fn generate_nonce(
+    key: &Key,
+    context: &KeyAggContext,
+    message: &[u8],
+) -> Result<(SecretNonce, PublicNonce), MusigError> {
+    generate_nonce_with(key, context, message, &OsEntropy)
+}
+
+/// Make the nonces of a signer like [generate_nonce], the fresh randomness
+/// read from a source
+pub fn generate_nonce_with(
+    key: &Key,
+    context: &KeyAggContext,
+    message: &[u8],
+    source: &dyn EntropySource,
+) -> Result<(SecretNonce, PublicNonce), MusigError> {
+    let secret = secret_key(key)?;
+    let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &secret);
+
+    let mut random = [0u8; 32];
+    source.fill_bytes(&mut random);
+    let aux = tagged_hash("MuSig/aux", &random);
+    let mut rand: Vec<u8> = secret[..].iter().zip(aux).map(|(a, b)| a ^ b).collect();
+    random.zeroize();
+
+    let aggregate = context.x_only_public_key();
+    let mut nonces = [0u8; 64];
+    let mut points = vec![];
+    for i in 0..2u8 {
+        let input = [
+            &rand[..],
+            &[33],
+            &pubkey.serialize(),
+            &[32],
+            &aggregate,
+            &[1],
+            &(message.len() as u64).to_be_bytes(),
+            message,
+            &0u32.to_be_bytes(),
+            &[i],
+        ]
+        .concat();
+        let nonce = scalar(&tagged_hash("MuSig/nonce", &input))
+            .ok_or_else(|| MusigError::InvalidNonce("a zero nonce".to_string()))?;
+        nonces[i as usize * 32..(i as usize + 1) * 32].copy_from_slice(&nonce[..]);
+        points.push(PublicKey::from_secret_key(&Secp256k1::new(), &nonce));
+    }
+    rand.zeroize();
+
+    let mut public = [0u8; 66];
+    public[..33].copy_from_slice(&points[0].serialize());
+    public[33..].copy_from_slice(&points[1].serialize());
+    Ok((SecretNonce { nonces, pubkey }, PublicNonce(public)))
+}
+
+/// The values every signer derives from the nonces of the group and the message
+#[derive(Debug, Clone)]
+pub struct SigningSession {
+    context: KeyAggContext,
+    nonces: Vec<PublicNonce>,
+    message: Vec<u8>,
+    /// the factor of the second nonces
+    b: Scalar,
+    /// the nonce of the final signature
+    r: PublicKey,
+    /// the challenge of the final signature
+    e: Scalar,
+}
+
+impl SigningSession {
+    /// Start the second round once the public nonces of every signer are
+    /// known, given in the order of the keys of the group
+    pub fn new(
+        context: &KeyAggContext,
+        nonces: &[PublicNonce],
+        message: &[u8],
+    ) -> Result<Self, MusigError> {
+        if nonces.len() != context.pubkeys.len() {
+            return Err(MusigError::InvalidNonce(format!(
+                "{} nonces for {} keys",
+                nonces.len(),
+                context.pubkeys.len()
+            )));
+        }
+        let infinity = || MusigError::InvalidNonce("the nonces cancel out".to_string());
+
+        let (first, second): (Vec<_>, Vec<_>) = nonces
+            .iter()
+            .map(|nonce| {
+                let (first, second) = nonce.points();
+                (Some(first), Some(second))
+            })
+            .unzip();
+        let first = add_points(&first).ok_or_else(infinity)?;
+        let second = add_points(&second).ok_or_else(infinity)?;
+
+        let aggregate = context.x_only_public_key();
+        let b = scalar(&tagged_hash(
+            "MuSig/noncecoef",
+            &[
+                &first.serialize()[..],
+                &second.serialize(),
+                &aggregate,
+                message,
+            ]
+            .concat(),
+        ));
+        let r = add_points(&[Some(first), mul_point(&second, b)]).ok_or_else(infinity)?;
+        let e = scalar(&tagged_hash(
+            "BIP0340/challenge",
+            &[&r.serialize()[1..], &aggregate, message].concat(),
+        ));
+
+        Ok(Self {
+            context: context.clone(),
+            nonces: nonces.to_vec(),
+            message: message.to_vec(),
+            b,
+            r,
+            e,
+        })
+    }
+
+    /// Sign with a key of the group, using up the nonce it made for the session
+    pub fn sign(&self, nonce: SecretNonce, key: &Key) -> Result<PartialSignature, MusigError> {
+        let secret = secret_key(key)?;
+        let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &secret);
+        if pubkey != nonce.pubkey || !self.context.pubkeys.contains(&pubkey) {
+            return Err(MusigError::UnknownSigner(hex::encode(pubkey.serialize())));
+        }
+
+        // the final nonce and key are taken with an even y
+        let flip = |scalar: Scalar, point: &PublicKey| match has_even_y(point) {
+            true => scalar,
+            false => negate(scalar),
+        };
+        let first = flip(SecretKey::from_slice(&nonce.nonces[..32]).ok(), &self.r);
+        let second = flip(SecretKey::from_slice(&nonce.nonces[32..]).ok(), &self.r);
+        let d = mul(
+            flip(self.context.gacc, &self.context.aggregate),
+            Some(secret),
+        );
+
+        let s = add(
+            add(first, mul(self.b, second)),
+            mul(mul(self.e, self.context.coefficient(&pubkey)), d),
+        );
+        Ok(PartialSignature(to_bytes(s)))
+    }
+
+    /// check the partial signature of the signer at an index of the group
+    pub fn verify(&self, index: usize, signature: &PartialSignature) -> bool {
+        let (pubkey, nonce) = match (self.context.pubkeys.get(index), self.nonces.get(index)) {
+            (Some(pubkey), Some(nonce)) => (pubkey, nonce),
+            _ => return false,
+        };
+
+        let (first, second) = nonce.points();
+        let mut nonce = match add_points(&[Some(first), mul_point(&second, self.b)]) {
+            Some(nonce) => nonce,
+            None => return false,
+        };
+        if !has_even_y(&self.r) {
+            nonce.negate_assign(&Secp256k1::verification_only());
+        }
+        let g = match has_even_y(&self.context.aggregate) {
+            true => self.context.gacc,
+            false => negate(self.context.gacc),
+        };
+
+        let expected = add_points(&[
+            Some(nonce),
+            mul_point(
+                pubkey,
+                mul(mul(self.e, self.context.coefficient(pubkey)), g),
+            ),
+        ]);
+        mul_point(&generator(), signature.scalar()) == expected
+    }
+
+    /// Aggregate the partial signatures of every signer, in the order of the
+    /// keys of the group, to a BIP340 signature of the aggregate key
+    pub fn aggregate(&self, signatures: &[PartialSignature]) -> Result<Vec<u8>, MusigError> {
+        if signatures.len() != self.context.pubkeys.len() {
+            return Err(MusigError::InvalidSignature(format!(
+                "{} signatures for {} keys",
+                signatures.len(),
+                self.context.pubkeys.len()
+            )));
+        }
+        if let Some(index) = (0..signatures.len()).find(|i| !self.verify(*i, &signatures[*i])) {
+            return Err(MusigError::InvalidPartialSignature(index));
+        }
+
+        let g = match has_even_y(&self.context.aggregate) {
+            true => one(),
+            false => negate(one()),
+        };
+        let s = signatures
+            .iter()
+            .fold(mul(mul(self.e, g), self.context.tacc), |s, signature| {
+                add(s, signature.scalar())
+            });
+        let signature = [&self.r.serialize()[1..], &to_bytes(s)].concat();
+
+        // a signature failing here is a bug, not a dishonest signer
+        let secp = Secp256k1::new();
+        let verified = match (
+            schnorrsig::Signature::from_slice(&signature),
+            schnorrsig::PublicKey::from_slice(&self.context.x_only_public_key()),
+            Message::from_slice(&self.message),
+        ) {
+            (Ok(signature), Ok(pubkey), Ok(message)) => secp
+                .schnorrsig_verify(&signature, &message, &pubkey)
+                .is_ok(),
+            // only 32 byte messages are checked
+            (Ok(_), Ok(_), Err(_)) => true,
+            _ => false,
+        };
+        match verified {
+            true => Ok(signature),
+            false => Err(MusigError::InvalidSignature(hex::encode(&signature))),
+        }
+    }
+}
+
+fn secret_key(key: &Key) -> Result<SecretKey, MusigError> {
+    SecretKey::from_slice(key.bytes()).map_err(|e| MusigError::InvalidKey(e.to_string()))
+}
+
+fn generator() -> PublicKey {
+    PublicKey::from_secret_key(&Secp256k1::new(), &one().unwrap())
+}
+
+fn has_even_y(point: &PublicKey) -> bool {
+    point.serialize()[0] == 0x02
+}
+
+fn one() -> Scalar {
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    SecretKey::from_slice(&one).ok()
+}
+
+/// a hash as a scalar, reduced mod the curve order
+fn scalar(bytes: &[u8]) -> Scalar {
+    if let Ok(scalar) = SecretKey::from_slice(bytes) {
+        return Some(scalar);
+    }
+
+    // zero or at least the curve order, which is less than twice it
+    let mut reduced = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let difference = bytes[i] as i16 - CURVE_ORDER[i] as i16 - borrow;
+        borrow = (difference < 0) as i16;
+        reduced[i] = difference.rem_euclid(256) as u8;
+    }
+    match borrow {
+        0 => SecretKey::from_slice(&reduced).ok(),
+        _ => None,
+    }
+}
+
+fn to_bytes(scalar: Scalar) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    if let Some(scalar) = scalar {
+        bytes.copy_from_slice(&scalar[..]);
+    }
+    bytes
+}
+
+fn add(a: Scalar, b: Scalar) -> Scalar {
+    match (a, b) {
+        (Some(mut a), Some(b)) => a.add_assign(&b[..]).ok().map(|_| a),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+fn mul(a: Scalar, b: Scalar) -> Scalar {
+    match (a, b) {
+        (Some(mut a), Some(b)) => a.mul_assign(&b[..]).ok().map(|_| a),
+        _ => None,
+    }
+}
+
+fn negate(a: Scalar) -> Scalar {
+    a.map(|mut a| {
+        a.negate_assign();
+        a
+    })
+}
+
+/// a point times a scalar, none for infinity
+fn mul_point(point: &PublicKey, scalar: Scalar) -> Option<PublicKey> {
+    let mut point = *point;
+    point
+        .mul_assign(&Secp256k1::verification_only(), &scalar?[..])
+        .ok()?;
+    Some(point)
+}
+
+/// the sum of points, none for infinity
+fn add_points(points: &[Option<PublicKey>]) -> Option<PublicKey> {
+    let points: Vec<&PublicKey> = points.iter().flatten().collect();
+    match points.len() {
+        0 => None,
+        1 => Some(*points[0]),
+        _ => PublicKey::combine_keys(&points).ok(),
+    }
+}
@@ -0,0 +1,129 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{base58check_decode, KeyError};
+
+/// bitcoin networks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    /// a local test chain, sharing the WIF and base58 prefixes of testnet
+    /// so keys read from a WIF are always [Network::Testnet]
+    Regtest,
+}
+
+impl Network {
+    /// the version byte of a private key in the wallet import format
+    pub const fn wif_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x80,
+            Network::Testnet | Network::Regtest => 0xef,
+        }
+    }
+
+    /// the version byte of a base58 P2PKH address
+    pub const fn p2pkh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet | Network::Regtest => 0x6f,
+        }
+    }
+
+    /// the version byte of a base58 P2SH address
+    pub const fn p2sh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            Network::Testnet | Network::Regtest => 0xc4,
+        }
+    }
+
+    /// the human readable part of a segwit address
+    pub const fn bech32_hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+
+    /// the human readable part of a BIP352 silent payment address
+    pub const fn silent_payment_hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "sp",
+            Network::Testnet => "tsp",
+            Network::Regtest => "sprt",
+        }
+    }
+
+    /// the BIP44 coin type, the second level of an account's derivation path
+    pub const fn coin_type(&self) -> u32 {
+        match self {
+            Network::Mainnet => 0,
+            Network::Testnet | Network::Regtest => 1,
+        }
+    }
+
+    /// the version bytes of a serialized extended public key, `xpub` or `tpub`
+    pub const fn xpub_version(&self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0x04, 0x88, 0xb2, 0x1e],
+            Network::Testnet | Network::Regtest => [0x04, 0x35, 0x87, 0xcf],
+        }
+    }
+
+    /// The network of a P2PKH, P2SH or segwit address. Base58 addresses
+    /// of regtest can't be told apart from testnet ones and are [Network::Testnet]
+    pub fn from_address(address: &str) -> Result<Self, KeyError> {
+        let networks = [Network::Mainnet, Network::Testnet, Network::Regtest];
+
+        // a bech32 address fails the base58 checksum, when it decodes at all
+        match base58check_decode(address) {
+            Ok(decoded) => {
+                let prefix = *decoded.first().ok_or(KeyError::InvalidFormat)?;
+                networks
+                    .iter()
+                    .find(|network| {
+                        network.p2pkh_prefix() == prefix || network.p2sh_prefix() == prefix
+                    })
+                    .copied()
+                    .ok_or(KeyError::InvalidNetworkByte)
+            }
+            Err(_) => {
+                let (hrp, _, _) = bech32::decode(address).map_err(|_| KeyError::Decode)?;
+                networks
+                    .iter()
+                    .find(|network| network.bech32_hrp() == hrp)
+                    .copied()
+                    .ok_or(KeyError::InvalidNetworkByte)
+            }
+        }
+    }
+}
+
+/// written as bitcoin core names the chain, `main`, `test` or `regtest`
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Network::Mainnet => "main",
+            Network::Testnet => "test",
+            Network::Regtest => "regtest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// read the names of [Network]'s Display, or the variant names, ignoring case
+impl FromStr for Network {
+    type Err = KeyError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "main" | "mainnet" | "bitcoin" => Ok(Network::Mainnet),
+            "test" | "testnet" => Ok(Network::Testnet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(KeyError::UnknownNetwork(name.to_string())),
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ChildNumber, LockTime, OutPoint, Script, ScriptType, Transaction, TransactionError,
+    TransactionInput, TransactionOutput, TransactionType, TransactionVersion,
+};
+
+/// the identifier of the proprietary fields waller writes, integrators
+/// use one of their own
+pub const WALLER_PROPRIETARY_PREFIX: &[u8] = b"waller";
+
+/// subtype of the waller field marking an output paying the change chain
+const PROPRIETARY_CHANGE: u64 = 0;
+
+/// An unsigned transaction for an air-gapped wallet to sign, made by
+/// [crate::Wallet::export_signing_request]. It holds what signing needs
+/// besides the private keys: the outputs spent and where their keys are
+/// derived. Serializes to json, eg to carry it over on a QR code or an SD card
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SigningRequest {
+    /// the id of the unsigned transaction, checked by the signer
+    pub tx_id: String,
+    pub version: u32,
+    pub lock_time: u32,
+    /// the fingerprint of the master key of the wallet the paths are derived
+    /// from, none for a wallet without one
+    pub fingerprint: Option<[u8; 4]>,
+    pub inputs: Vec<SigningInput>,
+    pub outputs: Vec<SigningOutput>,
+    /// fields of applications about the whole transaction
+    #[serde(default, skip_serializing_if = "ProprietaryFields::is_empty")]
+    pub proprietary: ProprietaryFields,
+}
+
+/// An input of a [SigningRequest]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SigningInput {
+    pub previous_output: OutPoint,
+    pub sequence: u32,
+    /// the value of the output spent, committed to by segwit signatures
+    pub value: i64,
+    /// the hex encoded script of the output spent
+    pub script_pubkey: String,
+    /// the path of the key of the output from the master key, none for
+    /// imported keys and outputs of other wallets
+    pub path: Option<Vec<ChildNumber>>,
+    #[serde(default, skip_serializing_if = "ProprietaryFields::is_empty")]
+    pub proprietary: ProprietaryFields,
+}
+
+/// An output of a [SigningRequest]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SigningOutput {
+    pub value: i64,
+    /// the hex encoded script paid to
+    pub script_pubkey: String,
+    #[serde(default, skip_serializing_if = "ProprietaryFields::is_empty")]
+    pub proprietary: ProprietaryFields,
+}
+
+/// The signatures an air-gapped wallet made for a [SigningRequest], see
+/// [crate::Wallet::sign_signing_request]. Holds no private key
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SigningResponse {
+    pub tx_id: String,
+    pub signatures: Vec<InputSignature>,
+    /// the fields of the request about the whole transaction, handed back
+    /// to the online wallet with any the signer added
+    #[serde(default, skip_serializing_if = "ProprietaryFields::is_empty")]
+    pub proprietary: ProprietaryFields,
+}
+
+/// The signature of an input of a [SigningResponse]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InputSignature {
+    pub input: usize,
+    /// the hex encoded DER signature followed by its sighash type
+    pub signature: String,
+    /// the hex encoded public key the signature verifies with
+    pub public_key: String,
+}
+
+/// A field of an application, as the proprietary fields of PSBTs in BIP 174:
+/// keyed by the identifier of the application, a subtype it defines and key
+/// data. Waller ignores fields of other applications and carries them through
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ProprietaryField {
+    /// the hex encoded identifier of the application
+    pub prefix: String,
+    pub subtype: u64,
+    /// the hex encoded key data, empty for a field keyed by its subtype only
+    pub key: String,
+    /// the hex encoded value
+    pub value: String,
+}
+
+/// The proprietary fields of a [SigningRequest], one of its inputs or outputs,
+/// or a [SigningResponse]. A field is replaced by one with the same key
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ProprietaryFields(Vec<ProprietaryField>);
+
+impl ProprietaryFields {
+    /// add a field, returning the value it replaced
+    pub fn insert(
+        &mut self,
+        prefix: &[u8],
+        subtype: u64,
+        key: &[u8],
+        value: &[u8],
+    ) -> Option<Vec<u8>> {
+        let replaced = self.remove(prefix, subtype, key);
+        self.0.push(ProprietaryField {
+            prefix: hex::encode(prefix),
+            subtype,
+            key: hex::encode(key),
+            value: hex::encode(value),
+        });
+        replaced
+    }
+
+    /// the value of a field, none when it isn't set or isn't hex
+    pub fn get(&self, prefix: &[u8], subtype: u64, key: &[u8]) -> Option<Vec<u8>> {
+        let field = self.position(prefix, subtype, key)?;
+        hex::decode(&self.0[field].value).ok()
+    }
+
+    pub fn remove(&mut self, prefix: &[u8], subtype: u64, key: &[u8]) -> Option<Vec<u8>> {
+        let field = self.position(prefix, subtype, key)?;
+        hex::decode(self.0.remove(field).value).ok()
+    }
+
+    /// the fields of an application
+    pub fn with_prefix<'a>(&'a self, prefix: &[u8]) -> impl Iterator<Item = &'a ProprietaryField> {
+        let prefix = hex::encode(prefix);
+        self.0
+            .iter()
+            .filter(move |field| field.prefix.eq_ignore_ascii_case(&prefix))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ProprietaryField> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn position(&self, prefix: &[u8], subtype: u64, key: &[u8]) -> Option<usize> {
+        let (prefix, key) = (hex::encode(prefix), hex::encode(key));
+        self.0.iter().position(|field| {
+            field.prefix.eq_ignore_ascii_case(&prefix)
+                && field.subtype == subtype
+                && field.key.eq_ignore_ascii_case(&key)
+        })
+    }
+}
+
+impl SigningOutput {
+    /// whether the online wallet marked the output as paying its change chain,
+    /// for the signer to tell the payments apart
+    pub fn is_change(&self) -> bool {
+        self.proprietary
+            .get(WALLER_PROPRIETARY_PREFIX, PROPRIETARY_CHANGE, &[])
+            .is_some()
+    }
+
+    pub(crate) fn set_change(&mut self) {
+        self.proprietary
+            .insert(WALLER_PROPRIETARY_PREFIX, PROPRIETARY_CHANGE, &[], &[1]);
+    }
+}
+
+impl SigningRequest {
+    /// describe an unsigned transaction, with the path of the key of each input
+    pub(crate) fn new(
+        tx: &Transaction,
+        fingerprint: Option<[u8; 4]>,
+        paths: Vec<Option<Vec<ChildNumber>>>,
+    ) -> Self {
+        let inputs = tx
+            .inputs()
+            .iter()
+            .zip(paths)
+            .map(|(input, path)| SigningInput {
+                previous_output: input.previous_output().clone(),
+                sequence: input.sequence(),
+                value: input.utxo_value(),
+                script_pubkey: hex::encode(input.utxo_pk_script()),
+                path,
+                proprietary: ProprietaryFields::default(),
+            })
+            .collect();
+        let outputs = tx
+            .outputs()
+            .iter()
+            .map(|output| SigningOutput {
+                value: output.value(),
+                script_pubkey: hex::encode(output.pk_script()),
+                proprietary: ProprietaryFields::default(),
+            })
+            .collect();
+
+        Self {
+            tx_id: tx.tx_id(),
+            version: tx.version().as_u32(),
+            lock_time: tx.lock_time().to_consensus_u32(),
+            fingerprint,
+            inputs,
+            outputs,
+            proprietary: ProprietaryFields::default(),
+        }
+    }
+
+    /// The unsigned transaction of the request, failing with
+    /// [TransactionError::TxIdMismatch] when it isn't the one the request names
+    pub fn transaction(&self) -> Result<Transaction, TransactionError> {
+        let script = |script: &str| {
+            hex::decode(script).map_err(|e| TransactionError::Decode(format!("{}: {}", script, e)))
+        };
+
+        let mut inputs = vec![];
+        for input in self.inputs.iter() {
+            let utxo = TransactionOutput::from_script(input.value, script(&input.script_pubkey)?);
+            let outpoint = &input.previous_output;
+            let mut tx_in = TransactionInput::new(utxo, outpoint.hash(), outpoint.index());
+            tx_in.set_sequence(input.sequence);
+            inputs.push(tx_in);
+        }
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|output| {
+                Ok(TransactionOutput::from_script(
+                    output.value,
+                    script(&output.script_pubkey)?,
+                ))
+            })
+            .collect::<Result<Vec<TransactionOutput>, TransactionError>>()?;
+
+        // the type only sizes the transaction, taken from the first coin spent
+        let tx_type = match inputs
+            .first()
+            .map(|input| Script::new(input.utxo_pk_script().to_vec()).classify())
+        {
+            Some(ScriptType::P2sh) => TransactionType::NestedPay2WitnessPubKeyHash,
+            Some(ScriptType::P2wpkh) => TransactionType::Pay2WitnessPubKeyHash,
+            Some(ScriptType::P2tr) => TransactionType::Pay2Taproot,
+            _ => TransactionType::Pay2PubKeyHash,
+        };
+        let mut tx = Transaction::new(
+            tx_type,
+            inputs,
+            outputs,
+            Some(LockTime::from_consensus(self.lock_time)),
+        );
+        tx.set_version(match self.version {
+            1 => TransactionVersion::One,
+            2 => TransactionVersion::Two,
+            version => TransactionVersion::Nonstandard(version),
+        });
+
+        match tx.tx_id() == self.tx_id {
+            true => Ok(tx),
+            false => Err(TransactionError::TxIdMismatch(self.tx_id.clone())),
+        }
+    }
+}
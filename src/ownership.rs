@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    decode_transaction, push_data, read_varint, tagged_hash, write_varint, LockTime, Network,
+    OutPoint, ProofError, Script, ScriptType, Transaction, TransactionInput, TransactionOutput,
+    TransactionType, TransactionVersion,
+};
+
+/// the tag of the hash of a message signed with BIP322
+const BIP322_TAG: &str = "BIP0322-signed-message";
+
+/// BIP322 transactions are version 0, which no node relays
+const BIP322_VERSION: u32 = 0;
+
+/// A BIP322 proof that the owner of an address signed a message, eg the
+/// challenge of an exchange, made by [crate::Wallet::prove_ownership].
+/// Native segwit addresses give a "simple" signature, the base64 witness
+/// stack, others the "full" base64 transaction
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OwnershipProof {
+    pub address: String,
+    pub message: String,
+    pub signature: String,
+}
+
+impl OwnershipProof {
+    /// check the proof, see [verify_ownership]
+    pub fn verify(&self, network: Network) -> Result<(), ProofError> {
+        verify_ownership(&self.address, &self.message, &self.signature, network)
+    }
+}
+
+/// Check a BIP322 signature of a message by an address, simple or full.
+/// P2PKH, P2WPKH and P2SH-P2WPKH addresses and taproot key path spends are
+/// checked, see [Transaction::verify]
+pub fn verify_ownership(
+    address: &str,
+    message: &str,
+    signature: &str,
+    network: Network,
+) -> Result<(), ProofError> {
+    let to_spend = bip322_to_spend(address, message, network)?;
+    let mut to_sign = bip322_to_sign(&to_spend);
+
+    let signature = base64::decode(signature.trim())
+        .map_err(|e| ProofError::Malformed(format!("not base64: {}", e)))?;
+    match decode_witness(&signature) {
+        Some(witness) => to_sign
+            .set_witness(0, witness)
+            .map_err(|e| ProofError::Malformed(format!("{:?}", e)))?,
+        None => {
+            let summary = decode_transaction(&hex::encode(&signature), network)
+                .map_err(|e| ProofError::Malformed(format!("{:?}", e)))?;
+            // the full transaction may set its own version, lock time and
+            // sequence, but must spend the challenge alone to an OP_RETURN
+            let spends_challenge = matches!(
+                summary.inputs.as_slice(),
+                [input] if input.previous_output == OutPoint::new(to_spend.tx_id(), 0)
+            );
+            let burns = matches!(
+                summary.outputs.as_slice(),
+                [output] if output.value == 0 && output.pk_script == [0x6a]
+            );
+            if !spends_challenge || !burns {
+                return Err(ProofError::ChallengeMismatch);
+            }
+
+            let signed = &summary.inputs[0];
+            let mut input = to_sign.inputs().remove(0);
+            input.set_sequence(signed.sequence);
+            input.set_signature_script(signed.signature_script.clone());
+            to_sign = Transaction::new(
+                to_spend.tx_type(),
+                vec![input],
+                to_sign.outputs(),
+                Some(summary.lock_time),
+            );
+            to_sign.set_version(match summary.version {
+                1 => TransactionVersion::One,
+                2 => TransactionVersion::Two,
+                version => TransactionVersion::Nonstandard(version),
+            });
+            to_sign
+                .set_witness(0, signed.witness.clone())
+                .map_err(|e| ProofError::Malformed(format!("{:?}", e)))?;
+        }
+    }
+
+    to_sign
+        .verify(&to_spend.outputs())
+        .map_err(ProofError::Invalid)
+}
+
+/// the hash of a message committed to by its BIP322 signature
+pub(crate) fn bip322_message_hash(message: &[u8]) -> Vec<u8> {
+    tagged_hash(BIP322_TAG, message)
+}
+
+/// The virtual transaction paying the challenge of a message to an
+/// address, spending the hash of the message from no output
+pub(crate) fn bip322_to_spend(
+    address: &str,
+    message: &str,
+    network: Network,
+) -> Result<Transaction, ProofError> {
+    let script = Script::from_address(address, network)
+        .map_err(|_| ProofError::InvalidAddress(address.to_string()))?
+        .into_bytes();
+    let tx_type = match Script::new(script.clone()).classify() {
+        ScriptType::P2wpkh => TransactionType::Pay2WitnessPubKeyHash,
+        ScriptType::P2sh => TransactionType::NestedPay2WitnessPubKeyHash,
+        ScriptType::P2tr => TransactionType::Pay2Taproot,
+        _ => TransactionType::Pay2PubKeyHash,
+    };
+
+    let mut signature_script = vec![0x00];
+    signature_script.append(&mut push_data(&bip322_message_hash(message.as_bytes())));
+    let mut input = TransactionInput::new(
+        TransactionOutput::from_script(0, vec![]),
+        "00".repeat(32),
+        -1,
+    );
+    input.set_sequence(0);
+    input.set_signature_script(signature_script);
+
+    let mut tx = Transaction::new(
+        tx_type,
+        vec![input],
+        vec![TransactionOutput::from_script(0, script)],
+        Some(LockTime::default()),
+    );
+    tx.set_version(TransactionVersion::Nonstandard(BIP322_VERSION));
+    Ok(tx)
+}
+
+/// the unsigned virtual transaction spending the challenge of [bip322_to_spend]
+/// to an OP_RETURN, its signature is the proof
+pub(crate) fn bip322_to_sign(to_spend: &Transaction) -> Transaction {
+    let challenge = to_spend.outputs().remove(0);
+    let mut input = TransactionInput::new(challenge, to_spend.tx_id(), 0);
+    input.set_sequence(0);
+
+    let mut tx = Transaction::new(
+        to_spend.tx_type(),
+        vec![input],
+        vec![TransactionOutput::from_script(0, vec![0x6a])],
+        Some(LockTime::default()),
+    );
+    tx.set_version(TransactionVersion::Nonstandard(BIP322_VERSION));
+    tx
+}
+
+/// the signature of a signed [bip322_to_sign], simple when only a witness was set
+pub(crate) fn encode_bip322_signature(to_sign: &Transaction) -> String {
+    let input = &to_sign.inputs()[0];
+    match input.signature_script().is_empty() {
+        true => base64::encode(encode_witness(input.witness())),
+        false => base64::encode(to_sign.serialize()),
+    }
+}
+
+fn encode_witness(witness: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_varint(&mut bytes, witness.len() as u64);
+    for item in witness {
+        write_varint(&mut bytes, item.len() as u64);
+        bytes.extend_from_slice(item);
+    }
+    bytes
+}
+
+/// a simple signature, none when the bytes aren't exactly one witness stack
+fn decode_witness(mut bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let (count, read) = read_varint(bytes).ok()?;
+    bytes = &bytes[read..];
+    let mut witness = vec![];
+    for _ in 0..count {
+        let (length, read) = read_varint(bytes).ok()?;
+        let item = bytes.get(read..read.checked_add(length as usize)?)?;
+        witness.push(item.to_vec());
+        bytes = &bytes[read + item.len()..];
+    }
+    match bytes.is_empty() && !witness.is_empty() {
+        true => Some(witness),
+        false => None,
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{Backend, BackendError, Transaction, TransactionError};
+
+/// A parent transaction and the children spending its outputs,
+/// relayed together so a high fee child can pay for a low fee parent (CPFP)
+#[derive(Debug, Clone)]
+pub struct TxPackage {
+    parent: Transaction,
+    children: Vec<Transaction>,
+}
+
+/// The outcome of submitting a package to a backend
+#[derive(Debug, Clone)]
+pub struct PackageSubmission {
+    /// the backend's summary, `success` when every transaction was accepted
+    pub message: String,
+    pub results: Vec<PackageTxResult>,
+}
+
+/// The outcome of a single transaction within a package submission
+#[derive(Debug, Clone)]
+pub struct PackageTxResult {
+    pub txid: String,
+    pub error: Option<String>,
+}
+
+impl TxPackage {
+    pub fn new(parent: Transaction) -> Self {
+        Self {
+            parent,
+            children: vec![],
+        }
+    }
+
+    /// add a child, it must spend at least one output of the parent
+    pub fn add_child(&mut self, child: Transaction) -> Result<(), TransactionError> {
+        let parent_id = self.parent.tx_id();
+
+        let spends_parent = child
+            .inputs()
+            .iter()
+            .any(|input| input.previous_output().hash() == parent_id);
+
+        if !spends_parent {
+            return Err(TransactionError::NotAChild(child.tx_id()));
+        }
+
+        self.children.push(child);
+        Ok(())
+    }
+
+    pub fn parent(&self) -> &Transaction {
+        &self.parent
+    }
+
+    pub fn children(&self) -> &Vec<Transaction> {
+        &self.children
+    }
+
+    /// every transaction of the package in submission order, parent first
+    pub fn transactions(&self) -> Vec<&Transaction> {
+        let mut transactions = vec![&self.parent];
+        transactions.extend(self.children.iter());
+        transactions
+    }
+
+    /// the fee paid by all transactions of the package
+    pub fn fee(&self) -> i64 {
+        self.transactions().iter().map(|tx| tx.fee()).sum()
+    }
+
+    /// the size of all transactions of the package
+    pub fn size(&self) -> u64 {
+        self.transactions().iter().map(|tx| tx.size()).sum()
+    }
+
+    /// the fee rate of the package as a whole in satoshis per byte,
+    /// this is the rate miners consider when the parent alone pays too little
+    pub fn fee_rate(&self) -> f64 {
+        self.fee() as f64 / self.size() as f64
+    }
+
+    /// the hex encoded transactions in submission order
+    pub fn serialize(&self) -> Vec<String> {
+        self.transactions().iter().map(|tx| tx.to_hex()).collect()
+    }
+
+    /// relay the package through a backend supporting package relay
+    pub fn submit<B: Backend>(&self, backend: &B) -> Result<PackageSubmission, BackendError> {
+        backend.submit_package(&self.serialize())
+    }
+}
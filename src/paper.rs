@@ -0,0 +1,168 @@
+use aes::{
+    cipher::generic_array::GenericArray, Aes256, BlockDecrypt, BlockEncrypt, NewBlockCipher,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{base58check_encode, hash160, sha256_hash_twice, AccountType, Key, KeyError, Network};
+
+/// prefix of a BIP38 key encrypted without EC multiplication
+const BIP38_PREFIX: [u8; 2] = [0x01, 0x42];
+/// flag byte of a BIP38 key without EC multiplication, with
+/// [BIP38_COMPRESSED] set when the address uses a compressed public key
+const BIP38_FLAG: u8 = 0xc0;
+const BIP38_COMPRESSED: u8 = 0x20;
+/// log2 of the scrypt cost parameter, r and p, fixed by BIP38
+const BIP38_SCRYPT: (u8, u32, u32) = (14, 8, 8);
+
+/// A key laid out for printing on a paper wallet, serializable so
+/// it can be rendered by any template. The public key is included so
+/// the address can be checked offline without the private key
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PaperWallet {
+    pub network: Network,
+    pub account_type: AccountType,
+    pub address: String,
+    /// hex public key the address pays to
+    pub public_key: String,
+    /// the private key in WIF, or BIP38 encrypted when `encrypted` is set
+    pub private_key: String,
+    pub encrypted: bool,
+    /// BIP21 URI of the address for its QR code, eg `bitcoin:1BvBM...`
+    pub address_qr: String,
+    /// the payload of the private key QR code
+    pub private_key_qr: String,
+}
+
+impl PaperWallet {
+    /// Lay out a key and its address of an account type,
+    /// the private key is BIP38 encrypted when a passphrase is given
+    pub fn new(
+        key: &Key,
+        account_type: AccountType,
+        passphrase: Option<&str>,
+    ) -> Result<Self, KeyError> {
+        let address = account_type.address(key)?;
+        let private_key = match passphrase {
+            Some(passphrase) => bip38_encrypt(key, passphrase)?,
+            None => key.to_wif(),
+        };
+
+        Ok(Self {
+            network: *key.network(),
+            account_type,
+            address_qr: format!("bitcoin:{}", address),
+            address,
+            public_key: hex::encode(key.new_public_key()?),
+            private_key_qr: private_key.clone(),
+            private_key,
+            encrypted: passphrase.is_some(),
+        })
+    }
+
+    /// Recover the key of the paper wallet, checking it owns the public key
+    /// and address printed. The passphrase is only used for an encrypted key
+    pub fn verify(&self, passphrase: Option<&str>) -> Result<Key, KeyError> {
+        let key = match (self.encrypted, passphrase) {
+            (true, Some(passphrase)) => bip38_decrypt(&self.private_key, passphrase)?,
+            (true, None) => return Err(KeyError::IncorrectPassphrase),
+            (false, _) => Key::from_wif(self.private_key.clone())?,
+        };
+
+        if hex::encode(key.new_public_key()?) != self.public_key
+            || self.account_type.address(&key)? != self.address
+        {
+            return Err(KeyError::Other(
+                "the address does not belong to the private key".to_string(),
+            ));
+        }
+
+        Ok(key)
+    }
+}
+
+/// Encrypt a private key with a passphrase as a BIP38 `6P...` string
+pub fn bip38_encrypt(key: &Key, passphrase: &str) -> Result<String, KeyError> {
+    let address_hash = bip38_address_hash(key)?;
+    let derived = bip38_derive(passphrase, &address_hash)?;
+    let (half1, half2) = derived.split_at(32);
+
+    let cipher = Aes256::new(GenericArray::from_slice(half2));
+    let mut encrypted = vec![];
+    for (block, mask) in key.bytes().chunks(16).zip(half1.chunks(16)) {
+        let mut block: Vec<u8> = block.iter().zip(mask).map(|(a, b)| a ^ b).collect();
+        cipher.encrypt_block(GenericArray::from_mut_slice(&mut block));
+        encrypted.extend(block);
+    }
+
+    let flag = match key.compress_public_keys() {
+        true => BIP38_FLAG | BIP38_COMPRESSED,
+        false => BIP38_FLAG,
+    };
+
+    Ok(base58check_encode(
+        &[&BIP38_PREFIX[..], &[flag], &address_hash, &encrypted].concat(),
+    ))
+}
+
+/// Decrypt a BIP38 encrypted private key, keys made with EC multiplication
+/// aren't supported. Decrypted keys are for mainnet as BIP38 has no network
+pub fn bip38_decrypt(encrypted: &str, passphrase: &str) -> Result<Key, KeyError> {
+    let mut decoded = bs58::decode(encrypted)
+        .into_vec()
+        .map_err(|_| KeyError::Decode)?;
+    if decoded.len() != 43 {
+        return Err(KeyError::InvalidFormat);
+    }
+
+    let checksum = decoded.split_off(39);
+    if sha256_hash_twice(&decoded)[..4] != checksum[..] {
+        return Err(KeyError::ChecksumMismatch);
+    }
+    if decoded[..2] != BIP38_PREFIX || decoded[2] & !BIP38_COMPRESSED != BIP38_FLAG {
+        return Err(KeyError::InvalidFormat);
+    }
+
+    let compressed = decoded[2] & BIP38_COMPRESSED != 0;
+    let address_hash = &decoded[3..7];
+    let derived = bip38_derive(passphrase, address_hash)?;
+    let (half1, half2) = derived.split_at(32);
+
+    let cipher = Aes256::new(GenericArray::from_slice(half2));
+    let mut private_key = vec![];
+    for (block, mask) in decoded[7..].chunks(16).zip(half1.chunks(16)) {
+        let mut block = block.to_vec();
+        cipher.decrypt_block(GenericArray::from_mut_slice(&mut block));
+        private_key.extend(block.iter().zip(mask).map(|(a, b)| a ^ b));
+    }
+
+    let mut wif = vec![Network::Mainnet.wif_prefix()];
+    wif.extend(private_key);
+    if compressed {
+        wif.push(0x01);
+    }
+    let key = Key::from_wif(base58check_encode(&wif))?;
+
+    match bip38_address_hash(&key)? == address_hash {
+        true => Ok(key),
+        false => Err(KeyError::IncorrectPassphrase),
+    }
+}
+
+/// the first 4 bytes of the double sha256 of the key's mainnet P2PKH address
+fn bip38_address_hash(key: &Key) -> Result<Vec<u8>, KeyError> {
+    let mut payload = vec![Network::Mainnet.p2pkh_prefix()];
+    payload.extend(hash160(&key.new_public_key()?));
+    let address = base58check_encode(&payload);
+
+    Ok(sha256_hash_twice(&address.as_bytes().to_vec())[..4].to_vec())
+}
+
+fn bip38_derive(passphrase: &str, address_hash: &[u8]) -> Result<Vec<u8>, KeyError> {
+    let (log_n, r, p) = BIP38_SCRYPT;
+    let params = scrypt::Params::new(log_n, r, p).map_err(|e| KeyError::Other(e.to_string()))?;
+
+    let mut derived = vec![0; 64];
+    scrypt::scrypt(passphrase.as_bytes(), address_hash, &params, &mut derived)
+        .map_err(|e| KeyError::Other(e.to_string()))?;
+    Ok(derived)
+}
@@ -0,0 +1,431 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use serde_json::Value;
+
+use crate::{
+    encode_psbt, parse_http_response, BackendConfig, BackendError, Network, PayjoinError, Psbt,
+    Script, ScriptType, Transaction, TransactionInput, TransactionOutput,
+};
+
+/// What a sender asks of the receiver of a payjoin, as the parameters of
+/// the `pj` URI of BIP21 and the query of BIP78
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayjoinParams {
+    /// the address paid, whose output the receiver may substitute
+    address: String,
+    endpoint: String,
+    /// satoshis the receiver may take from an output of the sender to pay
+    /// for its inputs, and the index of that output
+    fee_contribution: Option<(i64, usize)>,
+    /// satoshis per vbyte the payjoin pays at least
+    min_fee_rate: Option<f64>,
+    output_substitution: bool,
+}
+
+impl PayjoinParams {
+    /// Pay an address through the payjoin endpoint of its `pj` parameter.
+    /// The endpoint is https, or http to an onion service reached over Tor
+    pub fn new(address: &str, endpoint: &str) -> Result<Self, PayjoinError> {
+        let invalid = || PayjoinError::InvalidEndpoint(endpoint.to_string());
+        let (scheme, rest) = endpoint.split_once("://").ok_or_else(invalid)?;
+        let (host, _) = split_host(rest);
+        let host = host.rsplit_once(':').map_or(host, |(host, _)| host);
+        match scheme.to_ascii_lowercase().as_str() {
+            _ if host.is_empty() => return Err(invalid()),
+            "https" => {}
+            "http" if host.ends_with(".onion") => {}
+            _ => return Err(invalid()),
+        }
+
+        Ok(Self {
+            address: address.to_string(),
+            endpoint: endpoint.to_string(),
+            fee_contribution: None,
+            min_fee_rate: None,
+            output_substitution: true,
+        })
+    }
+
+    /// let the receiver take up to an amount from an output of the original,
+    /// usually its change, to pay the fee of the inputs it adds
+    pub fn with_fee_contribution(self, max_amount: i64, output_index: usize) -> Self {
+        Self {
+            fee_contribution: Some((max_amount, output_index)),
+            ..self
+        }
+    }
+
+    /// refuse proposals paying less than a fee rate, in satoshis per vbyte
+    pub fn with_min_fee_rate(self, min_fee_rate: f64) -> Self {
+        Self {
+            min_fee_rate: Some(min_fee_rate),
+            ..self
+        }
+    }
+
+    /// keep the receiver from changing the output paying it, as the `pjos=0`
+    /// parameter of the URI asks
+    pub fn without_output_substitution(self) -> Self {
+        Self {
+            output_substitution: false,
+            ..self
+        }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// the most the receiver may take, and the index of the output it takes it from
+    pub fn fee_contribution(&self) -> Option<(i64, usize)> {
+        self.fee_contribution
+    }
+
+    pub fn min_fee_rate(&self) -> Option<f64> {
+        self.min_fee_rate
+    }
+
+    pub fn output_substitution(&self) -> bool {
+        self.output_substitution
+    }
+}
+
+/// The original transaction of a payjoin, as posted to the receiver. Made by
+/// [crate::Wallet::payjoin_request]
+#[derive(Debug, Clone)]
+pub struct PayjoinRequest {
+    original: Transaction,
+    params: PayjoinParams,
+    /// the output script of the address paid
+    payee: Vec<u8>,
+    psbt: String,
+}
+
+impl PayjoinRequest {
+    pub(crate) fn new(
+        original: &Transaction,
+        params: PayjoinParams,
+        network: Network,
+    ) -> Result<Self, PayjoinError> {
+        let invalid = |error: String| PayjoinError::InvalidOriginal(error);
+        let payee = Script::from_address(&params.address, network)
+            .map_err(|_| invalid(format!("{} isn't an address", params.address)))?
+            .as_bytes()
+            .to_vec();
+        let outputs = original.outputs();
+        let payee_index = outputs
+            .iter()
+            .position(|output| output.pk_script() == payee.as_slice())
+            .ok_or_else(|| invalid(format!("no output pays {}", params.address)))?;
+        if let Some((max_amount, index)) = params.fee_contribution {
+            match outputs.get(index) {
+                _ if max_amount < 0 => {
+                    return Err(invalid(format!("a contribution of {}", max_amount)))
+                }
+                Some(_) if index != payee_index => {}
+                _ => return Err(invalid(format!("output {} can't pay fees", index))),
+            }
+        }
+
+        for (index, input) in original.inputs().iter().enumerate() {
+            // the receiver reads the values of the coins from their witness utxo
+            if !is_segwit(input.utxo_pk_script()) {
+                return Err(invalid(format!("input {} isn't segwit", index)));
+            }
+            if input.signature_script().is_empty() && input.witness().is_empty() {
+                return Err(invalid(format!("input {} isn't signed", index)));
+            }
+        }
+
+        Ok(Self {
+            psbt: encode_psbt(original, &HashMap::new()),
+            original: original.clone(),
+            params,
+            payee,
+        })
+    }
+
+    /// the transaction to broadcast when the payjoin fails
+    pub fn original(&self) -> &Transaction {
+        &self.original
+    }
+
+    pub fn params(&self) -> &PayjoinParams {
+        &self.params
+    }
+
+    /// the original as a base64 PSBT with its inputs finalized, the body posted
+    pub fn psbt(&self) -> &str {
+        &self.psbt
+    }
+
+    /// the endpoint with the parameters of BIP78 added to its query
+    pub fn url(&self) -> String {
+        let mut query = vec!["v=1".to_string()];
+        if let Some((max_amount, index)) = self.params.fee_contribution {
+            query.push(format!("additionalfeeoutputindex={}", index));
+            query.push(format!("maxadditionalfeecontribution={}", max_amount));
+        }
+        if let Some(min_fee_rate) = self.params.min_fee_rate {
+            query.push(format!("minfeerate={}", min_fee_rate));
+        }
+        if !self.params.output_substitution {
+            query.push("disableoutputsubstitution=true".to_string());
+        }
+
+        let separator = match self.params.endpoint.contains('?') {
+            true => '&',
+            false => '?',
+        };
+        format!("{}{}{}", self.params.endpoint, separator, query.join("&"))
+    }
+
+    /// Check a proposal of the receiver as BIP78 asks of senders, returning
+    /// its transaction with the inputs of the receiver signed and the inputs
+    /// of the sender left to sign
+    pub(crate) fn check_proposal(
+        &self,
+        proposal: &str,
+        network: Network,
+    ) -> Result<Transaction, PayjoinError> {
+        let invalid = |error: &str| PayjoinError::InvalidProposal(error.to_string());
+        let psbt = Psbt::decode(proposal, network).map_err(PayjoinError::InvalidPsbt)?;
+        let original = &self.original;
+        if psbt.tx.version != original.version().as_u32()
+            || psbt.tx.lock_time != original.lock_time()
+        {
+            return Err(invalid("the version or lock time changed"));
+        }
+
+        let original_inputs = original.inputs();
+        let sender_types: Vec<ScriptType> = original_inputs
+            .iter()
+            .map(|input| Script::new(input.utxo_pk_script().to_vec()).classify())
+            .collect();
+        let sender_sequence = original_inputs[0].sequence();
+
+        let mut inputs = vec![];
+        let mut witnesses = vec![];
+        let mut receiver_inputs = 0;
+        for (input, fields) in psbt.tx.inputs.iter().zip(psbt.inputs) {
+            let outpoint = &input.previous_output;
+            match original_inputs
+                .iter()
+                .find(|original| original.previous_output() == outpoint)
+            {
+                Some(original) => {
+                    if input.sequence != original.sequence() {
+                        return Err(invalid("the sequence of an input of the sender changed"));
+                    }
+                    if fields.is_finalized() || fields.partial_sigs {
+                        return Err(invalid("an input of the sender is signed"));
+                    }
+                    let mut unsigned = TransactionInput::new(
+                        TransactionOutput::from_script(
+                            original.utxo_value(),
+                            original.utxo_pk_script().to_vec(),
+                        ),
+                        outpoint.hash(),
+                        outpoint.index(),
+                    );
+                    unsigned.set_sequence(input.sequence);
+                    inputs.push(unsigned);
+                    witnesses.push(vec![]);
+                }
+                None => {
+                    if !fields.is_finalized() {
+                        return Err(invalid("an input of the receiver isn't signed"));
+                    }
+                    let utxo = fields
+                        .utxo
+                        .ok_or_else(|| invalid("an input of the receiver has no utxo"))?;
+                    if input.sequence != sender_sequence {
+                        return Err(invalid("an input of the receiver has another sequence"));
+                    }
+                    // only the same types keep the inputs of each party apart
+                    let script_type = Script::new(utxo.pk_script().to_vec()).classify();
+                    if sender_types.iter().all(|sender| *sender == sender_types[0])
+                        && script_type != sender_types[0]
+                    {
+                        return Err(invalid("an input of the receiver is of another type"));
+                    }
+
+                    let mut signed = TransactionInput::new(utxo, outpoint.hash(), outpoint.index());
+                    signed.set_sequence(input.sequence);
+                    signed.set_signature_script(fields.final_script_sig.unwrap_or_default());
+                    inputs.push(signed);
+                    witnesses.push(fields.final_witness.unwrap_or_default());
+                    receiver_inputs += 1;
+                }
+            }
+        }
+        if inputs.len() - receiver_inputs != original_inputs.len() {
+            return Err(invalid("an input of the sender is missing"));
+        }
+
+        if psbt.outputs.iter().any(|output| output.key_paths) {
+            return Err(invalid("an output has key paths"));
+        }
+        let outputs: Vec<TransactionOutput> = psbt
+            .tx
+            .outputs
+            .iter()
+            .map(|output| TransactionOutput::from_script(output.value, output.pk_script.clone()))
+            .collect();
+        let mut matched = vec![false; outputs.len()];
+        let mut contribution = 0;
+        for (index, original_output) in original.outputs().iter().enumerate() {
+            let found = outputs.iter().enumerate().position(|(i, output)| {
+                !matched[i] && output.pk_script() == original_output.pk_script()
+            });
+            if let Some(found) = found {
+                matched[found] = true;
+            }
+            let value = found.map(|found| outputs[found].value());
+
+            match self.params.fee_contribution {
+                Some((max_amount, fee_index)) if fee_index == index => {
+                    let value = value.ok_or_else(|| invalid("the fee output was removed"))?;
+                    contribution = (original_output.value() - value).max(0);
+                    if contribution > max_amount {
+                        return Err(invalid("the fee output pays more than the contribution"));
+                    }
+                }
+                _ if original_output.pk_script() == self.payee.as_slice() => {
+                    if !self.params.output_substitution
+                        && value.is_none_or(|value| value < original_output.value())
+                    {
+                        return Err(invalid("the output of the receiver changed"));
+                    }
+                }
+                _ => {
+                    if value != Some(original_output.value()) {
+                        return Err(invalid("an output of the sender changed"));
+                    }
+                }
+            }
+        }
+
+        let mut tx = Transaction::new(original.tx_type(), inputs, outputs, Some(psbt.tx.lock_time));
+        tx.set_version(original.version());
+        for (index, witness) in witnesses.into_iter().enumerate() {
+            tx.set_witness(index, witness)
+                .map_err(|e| PayjoinError::InvalidPsbt(format!("{:?}", e)))?;
+        }
+
+        let original_fee = original.fee();
+        if tx.fee() < original_fee {
+            return Err(invalid("the proposal pays less fee than the original"));
+        }
+        if contribution > tx.fee() - original_fee {
+            return Err(invalid("the contribution pays more than the fee added"));
+        }
+        // the contribution pays for the inputs added at the rate of the original
+        let original_fee_rate = original_fee as f64 / original.size() as f64;
+        let input_vsize = sender_types[0]
+            .tx_type()
+            .map_or(0, |tx_type| tx_type.input_vsize());
+        if contribution as f64 > original_fee_rate * (input_vsize * receiver_inputs as u64) as f64 {
+            return Err(invalid(
+                "the contribution pays for more than the inputs added",
+            ));
+        }
+
+        Ok(tx)
+    }
+
+    /// check a signed proposal pays the min fee rate of the params
+    pub(crate) fn check_fee_rate(&self, tx: &Transaction) -> Result<(), PayjoinError> {
+        match self.params.min_fee_rate {
+            Some(min_fee_rate) if (tx.fee() as f64) < min_fee_rate * tx.size() as f64 => Err(
+                PayjoinError::InvalidProposal("the fee rate is under the minimum".to_string()),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Posts the original of a payjoin to the endpoint of its receiver. Apps
+/// posting to https endpoints bring a client with TLS, [BackendConfig]
+/// posts to onion services
+pub trait PayjoinClient {
+    /// post a body to a url as `text/plain`, returning the body of the response
+    fn post(&self, url: &str, body: &str) -> Result<String, PayjoinError>;
+}
+
+/// Posts over plain http, through the proxy of the config, eg Tor for onion
+/// services. Fails with [PayjoinError::InvalidEndpoint] for https
+impl PayjoinClient for BackendConfig {
+    fn post(&self, url: &str, body: &str) -> Result<String, PayjoinError> {
+        let invalid = || PayjoinError::InvalidEndpoint(url.to_string());
+        let rest = match url.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => rest,
+            _ => return Err(invalid()),
+        };
+        let (host, path) = split_host(rest);
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let address = match host.rsplit_once(':') {
+            Some((_, port)) if !port.contains(']') => host.to_string(),
+            _ => format!("{}:80", host),
+        };
+
+        let connection_error =
+            |e: std::io::Error| PayjoinError::Connection(BackendError::Connection(e.to_string()));
+        let mut stream = self.connect(&address).map_err(PayjoinError::Connection)?;
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            path,
+            host,
+            body.len(),
+            body
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(connection_error)?;
+        let mut response = vec![];
+        stream
+            .read_to_end(&mut response)
+            .map_err(connection_error)?;
+
+        let (status, body) = parse_http_response(&response).map_err(PayjoinError::Connection)?;
+        if status == 200 {
+            return Ok(body);
+        }
+        // receivers answer errors with the JSON of BIP78
+        match serde_json::from_str::<Value>(&body) {
+            Ok(error) if error["errorCode"].is_string() => Err(PayjoinError::Receiver {
+                code: error["errorCode"].as_str().unwrap_or_default().to_string(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+            }),
+            _ => Err(PayjoinError::Connection(BackendError::InvalidResponse(
+                format!("HTTP {} from the payjoin receiver", status),
+            ))),
+        }
+    }
+}
+
+/// split `host[:port]/path?query` into the host and the path, `/` when none
+fn split_host(rest: &str) -> (&str, &str) {
+    match rest.find(['/', '?']) {
+        Some(split) if rest[split..].starts_with('/') => (&rest[..split], &rest[split..]),
+        Some(split) => (&rest[..split], "/"),
+        None => (rest, "/"),
+    }
+}
+
+/// whether an output is spent with a witness, so its value is signed for
+fn is_segwit(pk_script: &[u8]) -> bool {
+    matches!(
+        Script::new(pk_script.to_vec()).classify(),
+        ScriptType::P2wpkh | ScriptType::P2sh | ScriptType::P2tr
+    )
+}
@@ -0,0 +1,473 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use secp256k1::PublicKey;
+
+use crate::{
+    PolicyError, Script, ScriptBuilder, LOCKTIME_THRESHOLD, OP_ADD, OP_CHECKLOCKTIMEVERIFY,
+    OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY, OP_CHECKSEQUENCEVERIFY, OP_CHECKSIG,
+    OP_CHECKSIGVERIFY, OP_ELSE, OP_ENDIF, OP_EQUAL, OP_EQUALVERIFY, OP_FROMALTSTACK, OP_IF,
+    OP_SHA256, OP_SIZE, OP_TOALTSTACK, OP_VERIFY,
+};
+
+/// the most keys a threshold of keys is compiled to a single CHECKMULTISIG for
+const MAX_MULTISIG_KEYS: usize = 20;
+/// set on a sequence number to disable its relative lock time (BIP68)
+const SEQUENCE_DISABLE_FLAG: u32 = 1 << 31;
+/// set on a sequence number when its relative lock time is in units of 512 seconds
+const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+/// the bits of a sequence number holding its relative lock time
+const SEQUENCE_LOCKTIME_MASK: u32 = 0xffff;
+
+/// A spending policy, written like `and(pk(<hex pubkey>),older(1000))`,
+/// compiled to a P2WSH witness script with [Policy::compile] and satisfied
+/// with [Policy::satisfy]. The compiled scripts are correct but not the
+/// smallest possible, an `or` always costs a branch selector in the witness
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Policy {
+    /// a signature by a compressed public key
+    Pk(Vec<u8>),
+    /// the input's sequence is a relative lock time of at least this (BIP112)
+    Older(u32),
+    /// the transaction's lock time is at least this (BIP65)
+    After(u32),
+    /// the preimage of a sha256 hash
+    Sha256(Vec<u8>),
+    /// both policies
+    And(Box<Policy>, Box<Policy>),
+    /// either policy
+    Or(Box<Policy>, Box<Policy>),
+    /// at least this many of the policies
+    Thresh(usize, Vec<Policy>),
+}
+
+/// What is available to satisfy a [Policy] for a spend
+#[derive(Debug, Clone, Default)]
+pub struct Satisfier {
+    /// signatures with their sighash type appended, by public key
+    pub signatures: HashMap<Vec<u8>, Vec<u8>>,
+    /// sha256 preimages, by hash
+    pub preimages: HashMap<Vec<u8>, Vec<u8>>,
+    /// the sequence of the spending input
+    pub sequence: u32,
+    /// the lock time of the spending transaction
+    pub lock_time: u32,
+}
+
+impl Policy {
+    /// The witness script enforcing the policy
+    pub fn compile(&self) -> Script {
+        let mut builder = Script::builder();
+        self.compile_into(&mut builder);
+        builder.build()
+    }
+
+    /// The witness items satisfying the policy, bottom of the stack first,
+    /// to be given with the witness script to [crate::Transaction::set_p2wsh_witness].
+    /// `None` when the policy can't be satisfied with what is available.
+    /// Timelocks are only checked against the satisfier, the transaction
+    /// must also be version 2 for `older` and have a non final input for `after`
+    pub fn satisfy(&self, satisfier: &Satisfier) -> Option<Vec<Vec<u8>>> {
+        match self {
+            Policy::Pk(key) => satisfier.signatures.get(key).map(|sig| vec![sig.clone()]),
+            Policy::Older(blocks) => match relative_lock_satisfied(*blocks, satisfier.sequence) {
+                true => Some(vec![]),
+                false => None,
+            },
+            Policy::After(lock_time) => {
+                match absolute_lock_satisfied(*lock_time, satisfier.lock_time) {
+                    true => Some(vec![]),
+                    false => None,
+                }
+            }
+            Policy::Sha256(hash) => satisfier
+                .preimages
+                .get(hash)
+                .filter(|preimage| preimage.len() == 32)
+                .map(|preimage| vec![preimage.clone()]),
+            Policy::And(first, second) => {
+                // the first policy is checked first so its items go on top
+                let mut items = second.satisfy(satisfier)?;
+                items.append(&mut first.satisfy(satisfier)?);
+                Some(items)
+            }
+            Policy::Or(first, second) => {
+                let first = first.satisfy(satisfier).map(|mut items| {
+                    items.push(vec![1]);
+                    items
+                });
+                let second = second.satisfy(satisfier).map(|mut items| {
+                    items.push(vec![]);
+                    items
+                });
+
+                match (first, second) {
+                    (Some(first), Some(second)) => {
+                        match witness_size(&first) <= witness_size(&second) {
+                            true => Some(first),
+                            false => Some(second),
+                        }
+                    }
+                    (first, second) => first.or(second),
+                }
+            }
+            Policy::Thresh(threshold, policies) => match multisig_keys(policies) {
+                Some(keys) => {
+                    // the extra element CHECKMULTISIG pops, then signatures in key order
+                    let mut items = vec![vec![]];
+                    items.extend(
+                        keys.iter()
+                            .filter_map(|key| satisfier.signatures.get(*key).cloned())
+                            .take(*threshold),
+                    );
+                    match items.len() > *threshold {
+                        true => Some(items),
+                        false => None,
+                    }
+                }
+                None => {
+                    let mut satisfied = 0;
+                    let mut selected = vec![];
+                    for policy in policies.iter() {
+                        let items = match satisfied < *threshold {
+                            true => policy.satisfy(satisfier),
+                            false => None,
+                        };
+                        if items.is_some() {
+                            satisfied += 1;
+                        }
+                        selected.push(items);
+                    }
+
+                    if satisfied < *threshold {
+                        return None;
+                    }
+
+                    // the first policy is checked first so its items go on top
+                    let mut items = vec![];
+                    for selection in selected.into_iter().rev() {
+                        match selection {
+                            Some(mut satisfaction) => {
+                                items.append(&mut satisfaction);
+                                items.push(vec![1]);
+                            }
+                            None => items.push(vec![]),
+                        }
+                    }
+                    Some(items)
+                }
+            },
+        }
+    }
+
+    /// Whether every way of satisfying the policy needs a signature. A policy
+    /// that doesn't can be spent by anyone once its timelocks or hashes are met
+    pub fn requires_signature(&self) -> bool {
+        match self {
+            Policy::Pk(_) => true,
+            Policy::Older(_) | Policy::After(_) | Policy::Sha256(_) => false,
+            Policy::And(first, second) => first.requires_signature() || second.requires_signature(),
+            Policy::Or(first, second) => first.requires_signature() && second.requires_signature(),
+            Policy::Thresh(threshold, policies) => {
+                policies
+                    .iter()
+                    .filter(|policy| !policy.requires_signature())
+                    .count()
+                    < *threshold
+            }
+        }
+    }
+
+    /// every public key of the policy, in the order they appear
+    pub fn keys(&self) -> Vec<&[u8]> {
+        match self {
+            Policy::Pk(key) => vec![key.as_slice()],
+            Policy::Older(_) | Policy::After(_) | Policy::Sha256(_) => vec![],
+            Policy::And(first, second) | Policy::Or(first, second) => {
+                let mut keys = first.keys();
+                keys.append(&mut second.keys());
+                keys
+            }
+            Policy::Thresh(_, policies) => {
+                policies.iter().flat_map(|policy| policy.keys()).collect()
+            }
+        }
+    }
+
+    /// push the policy, leaving true on the stack when satisfied
+    fn compile_into(&self, builder: &mut ScriptBuilder) {
+        match self {
+            Policy::Pk(key) => {
+                builder.push_slice(key).push_opcode(OP_CHECKSIG);
+            }
+            Policy::Older(blocks) => {
+                builder
+                    .push_int(*blocks as i64)
+                    .push_opcode(OP_CHECKSEQUENCEVERIFY);
+            }
+            Policy::After(lock_time) => {
+                builder
+                    .push_int(*lock_time as i64)
+                    .push_opcode(OP_CHECKLOCKTIMEVERIFY);
+            }
+            Policy::Sha256(hash) => {
+                compile_sha256(builder, hash);
+                builder.push_opcode(OP_EQUAL);
+            }
+            Policy::And(first, second) => {
+                first.compile_verify(builder);
+                second.compile_into(builder);
+            }
+            Policy::Or(first, second) => {
+                builder.push_opcode(OP_IF);
+                first.compile_into(builder);
+                builder.push_opcode(OP_ELSE);
+                second.compile_into(builder);
+                builder.push_opcode(OP_ENDIF);
+            }
+            Policy::Thresh(threshold, policies) => {
+                compile_thresh(builder, *threshold, policies);
+                match multisig_keys(policies) {
+                    Some(_) => builder.push_opcode(OP_CHECKMULTISIG),
+                    None => builder.push_opcode(OP_EQUAL),
+                };
+            }
+        }
+    }
+
+    /// push the policy, failing the script unless satisfied
+    fn compile_verify(&self, builder: &mut ScriptBuilder) {
+        match self {
+            Policy::Pk(key) => {
+                builder.push_slice(key).push_opcode(OP_CHECKSIGVERIFY);
+            }
+            Policy::Sha256(hash) => {
+                compile_sha256(builder, hash);
+                builder.push_opcode(OP_EQUALVERIFY);
+            }
+            Policy::Thresh(threshold, policies) => {
+                compile_thresh(builder, *threshold, policies);
+                match multisig_keys(policies) {
+                    Some(_) => builder.push_opcode(OP_CHECKMULTISIGVERIFY),
+                    None => builder.push_opcode(OP_EQUALVERIFY),
+                };
+            }
+            _ => {
+                self.compile_into(builder);
+                builder.push_opcode(OP_VERIFY);
+            }
+        }
+    }
+}
+
+/// check the preimage is 32 bytes and push its hash, comparing is left to the caller
+fn compile_sha256(builder: &mut ScriptBuilder, hash: &[u8]) {
+    builder
+        .push_opcode(OP_SIZE)
+        .push_int(32)
+        .push_opcode(OP_EQUALVERIFY)
+        .push_opcode(OP_SHA256)
+        .push_slice(hash);
+}
+
+/// push a threshold up to its final comparison, a CHECKMULTISIG for
+/// a threshold of keys, otherwise the count of satisfied policies and the threshold
+fn compile_thresh(builder: &mut ScriptBuilder, threshold: usize, policies: &[Policy]) {
+    if let Some(keys) = multisig_keys(policies) {
+        builder.push_int(threshold as i64);
+        for key in keys.iter() {
+            builder.push_slice(key);
+        }
+        builder.push_int(keys.len() as i64);
+        return;
+    }
+
+    // each policy adds 1 when selected and satisfied, 0 when not selected.
+    // The count is kept on the alt stack while a policy reads its selector
+    for (index, policy) in policies.iter().enumerate() {
+        if index > 0 {
+            builder.push_opcode(OP_TOALTSTACK);
+        }
+        builder.push_opcode(OP_IF);
+        policy.compile_verify(builder);
+        builder
+            .push_int(1)
+            .push_opcode(OP_ELSE)
+            .push_int(0)
+            .push_opcode(OP_ENDIF);
+        if index > 0 {
+            builder.push_opcode(OP_FROMALTSTACK).push_opcode(OP_ADD);
+        }
+    }
+    builder.push_int(threshold as i64);
+}
+
+/// the keys of a threshold made only of keys, compiled to a CHECKMULTISIG
+fn multisig_keys(policies: &[Policy]) -> Option<Vec<&Vec<u8>>> {
+    if policies.len() > MAX_MULTISIG_KEYS {
+        return None;
+    }
+
+    policies
+        .iter()
+        .map(|policy| match policy {
+            Policy::Pk(key) => Some(key),
+            _ => None,
+        })
+        .collect()
+}
+
+/// whether an input sequence satisfies a CHECKSEQUENCEVERIFY of a relative lock time
+fn relative_lock_satisfied(required: u32, sequence: u32) -> bool {
+    sequence & SEQUENCE_DISABLE_FLAG == 0
+        && required & SEQUENCE_TYPE_FLAG == sequence & SEQUENCE_TYPE_FLAG
+        && required & SEQUENCE_LOCKTIME_MASK <= sequence & SEQUENCE_LOCKTIME_MASK
+}
+
+/// whether a transaction lock time satisfies a CHECKLOCKTIMEVERIFY
+fn absolute_lock_satisfied(required: u32, lock_time: u32) -> bool {
+    (required < LOCKTIME_THRESHOLD) == (lock_time < LOCKTIME_THRESHOLD) && required <= lock_time
+}
+
+/// the serialized size of witness items
+fn witness_size(items: &[Vec<u8>]) -> usize {
+    items.iter().map(|item| item.len() + 1).sum()
+}
+
+impl FromStr for Policy {
+    type Err = PolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (policy, rest) = parse_policy(s)?;
+
+        match rest.trim().is_empty() {
+            true => Ok(policy),
+            false => Err(PolicyError::Parse(format!("unexpected `{}`", rest.trim()))),
+        }
+    }
+}
+
+/// parse a policy at the start of the input, returning it and what follows it
+fn parse_policy(input: &str) -> Result<(Policy, &str), PolicyError> {
+    let input = input.trim_start();
+    let open = input
+        .find('(')
+        .ok_or_else(|| PolicyError::Parse(format!("expected a policy at `{}`", input)))?;
+    let name = input[..open].trim();
+    let rest = &input[open + 1..];
+
+    match name {
+        "pk" | "older" | "after" | "sha256" => {
+            let close = rest
+                .find(')')
+                .ok_or_else(|| PolicyError::Parse(format!("unclosed `{}(`", name)))?;
+            let argument = rest[..close].trim();
+            let policy = match name {
+                "pk" => Policy::Pk(parse_key(argument)?),
+                "older" => Policy::Older(parse_lock(argument)?),
+                "after" => Policy::After(parse_lock(argument)?),
+                _ => Policy::Sha256(parse_hash(argument)?),
+            };
+            Ok((policy, &rest[close + 1..]))
+        }
+        "and" | "or" | "thresh" => {
+            let mut rest = rest;
+            let mut threshold = 0;
+
+            if name == "thresh" {
+                let comma = rest
+                    .find(',')
+                    .ok_or_else(|| PolicyError::Parse("thresh without policies".to_string()))?;
+                threshold = rest[..comma].trim().parse().map_err(|_| {
+                    PolicyError::Parse(format!("invalid threshold `{}`", rest[..comma].trim()))
+                })?;
+                rest = &rest[comma + 1..];
+            }
+
+            let mut policies = vec![];
+            loop {
+                let (policy, after) = parse_policy(rest)?;
+                policies.push(policy);
+
+                let after = after.trim_start();
+                match after.chars().next() {
+                    Some(',') => rest = &after[1..],
+                    Some(')') => {
+                        rest = &after[1..];
+                        break;
+                    }
+                    _ => return Err(PolicyError::Parse(format!("unclosed `{}(`", name))),
+                }
+            }
+
+            let policy = match (name, policies.len()) {
+                ("thresh", count) if threshold == 0 || threshold > count => {
+                    return Err(PolicyError::InvalidThreshold(threshold, count))
+                }
+                ("thresh", _) => Policy::Thresh(threshold, policies),
+                (_, 2) => {
+                    let second = Box::new(policies.pop().unwrap());
+                    let first = Box::new(policies.pop().unwrap());
+                    match name {
+                        "and" => Policy::And(first, second),
+                        _ => Policy::Or(first, second),
+                    }
+                }
+                (_, count) => {
+                    return Err(PolicyError::Parse(format!(
+                        "`{}` takes 2 policies, got {}",
+                        name, count
+                    )))
+                }
+            };
+            Ok((policy, rest))
+        }
+        _ => Err(PolicyError::Parse(format!("unknown policy `{}`", name))),
+    }
+}
+
+fn parse_key(argument: &str) -> Result<Vec<u8>, PolicyError> {
+    let key = hex::decode(argument).map_err(|_| PolicyError::InvalidKey(argument.to_string()))?;
+
+    // segwit scripts only accept compressed keys
+    match key.len() == 33 && PublicKey::from_slice(&key).is_ok() {
+        true => Ok(key),
+        false => Err(PolicyError::InvalidKey(argument.to_string())),
+    }
+}
+
+fn parse_lock(argument: &str) -> Result<u32, PolicyError> {
+    match argument.parse::<u32>() {
+        Ok(lock) if lock > 0 && lock < SEQUENCE_DISABLE_FLAG => Ok(lock),
+        _ => Err(PolicyError::InvalidTimelock(argument.to_string())),
+    }
+}
+
+fn parse_hash(argument: &str) -> Result<Vec<u8>, PolicyError> {
+    match hex::decode(argument) {
+        Ok(hash) if hash.len() == 32 => Ok(hash),
+        _ => Err(PolicyError::Parse(format!(
+            "invalid sha256 hash `{}`",
+            argument
+        ))),
+    }
+}
+
+impl Display for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Policy::Pk(key) => write!(f, "pk({})", hex::encode(key)),
+            Policy::Older(blocks) => write!(f, "older({})", blocks),
+            Policy::After(lock_time) => write!(f, "after({})", lock_time),
+            Policy::Sha256(hash) => write!(f, "sha256({})", hex::encode(hash)),
+            Policy::And(first, second) => write!(f, "and({},{})", first, second),
+            Policy::Or(first, second) => write!(f, "or({},{})", first, second),
+            Policy::Thresh(threshold, policies) => {
+                write!(f, "thresh({}", threshold)?;
+                for policy in policies.iter() {
+                    write!(f, ",{}", policy)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+
+use crate::{OutPoint, ScriptType};
+
+/// payments in multiples of this many satoshis look picked by a person,
+/// 0.0001 BTC, while change rarely is
+pub const ROUND_AMOUNT: i64 = 10_000;
+
+/// An input of a [SpendPreview]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewInput {
+    pub previous_output: OutPoint,
+    pub value: i64,
+    /// none for scripts without an address
+    pub address: Option<String>,
+    pub script_type: ScriptType,
+    /// the account of the wallet the coin was received on, none for imported keys
+    pub account: Option<u32>,
+    /// whether the address of the coin received other coins too
+    pub reused: bool,
+}
+
+/// An output of a [SpendPreview]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreviewOutput {
+    pub value: i64,
+    /// none for scripts without an address, eg `OP_RETURN`
+    pub address: Option<String>,
+    pub script_type: ScriptType,
+    /// whether it pays to an address of the wallet
+    pub is_mine: bool,
+    /// whether it pays to the change chain of an account, see [crate::Wallet::is_change]
+    pub is_change: bool,
+    /// whether the address already received coins of the wallet, or was
+    /// paid by it before
+    pub reused: bool,
+}
+
+/// A transaction drafted by the wallet, with what it knows of its inputs and
+/// outputs, for reviewing it before it's signed and broadcast. Made by
+/// [crate::Wallet::preview_spend]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendPreview {
+    pub tx_id: String,
+    pub inputs: Vec<PreviewInput>,
+    pub outputs: Vec<PreviewOutput>,
+    /// satoshis paid in fees
+    pub fee: i64,
+}
+
+/// What a [SpendPreview] gives away about the wallet, see [SpendPreview::privacy_report]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrivacyWarning {
+    /// an output pays to an address used before, tying this payment to the earlier ones
+    AddressReuse { output: usize, address: String },
+    /// a coin spent was received on an address that received others,
+    /// which are now known to belong to the same owner
+    SpendsReusedAddress { input: usize, address: String },
+    /// the payments are round amounts and the change isn't, telling the change apart
+    RoundNumberChange { change: usize },
+    /// inputs spending different script types, a fingerprint of the wallet
+    /// that also tells its change apart when it matches one of them
+    MixedInputTypes(Vec<ScriptType>),
+    /// coins of several addresses spent together, linking them to one owner,
+    /// with the accounts they were received on
+    CommonInputOwnership {
+        addresses: Vec<String>,
+        accounts: Vec<u32>,
+    },
+}
+
+impl fmt::Display for PrivacyWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrivacyWarning::AddressReuse { output, address } => {
+                write!(
+                    f,
+                    "output {} pays to {}, an address used before",
+                    output, address
+                )
+            }
+            PrivacyWarning::SpendsReusedAddress { input, address } => write!(
+                f,
+                "input {} spends from {}, which received other coins",
+                input, address
+            ),
+            PrivacyWarning::RoundNumberChange { change } => write!(
+                f,
+                "the payments are round amounts, output {} stands out as change",
+                change
+            ),
+            PrivacyWarning::MixedInputTypes(types) => {
+                write!(f, "inputs of mixed script types {:?}", types)
+            }
+            PrivacyWarning::CommonInputOwnership {
+                addresses,
+                accounts,
+            } => write!(
+                f,
+                "spending together links {} addresses of {} accounts",
+                addresses.len(),
+                accounts.len()
+            ),
+        }
+    }
+}
+
+impl SpendPreview {
+    /// Flag what the transaction tells observers about the wallet, to pick
+    /// other coins or outputs before broadcasting it. An empty report doesn't
+    /// make the transaction private, only free of these giveaways
+    pub fn privacy_report(&self) -> Vec<PrivacyWarning> {
+        let mut warnings = vec![];
+
+        for (output, preview) in self.outputs.iter().enumerate() {
+            if let (true, Some(address)) = (preview.reused, &preview.address) {
+                warnings.push(PrivacyWarning::AddressReuse {
+                    output,
+                    address: address.clone(),
+                });
+            }
+        }
+        for (input, preview) in self.inputs.iter().enumerate() {
+            if let (true, Some(address)) = (preview.reused, &preview.address) {
+                warnings.push(PrivacyWarning::SpendsReusedAddress {
+                    input,
+                    address: address.clone(),
+                });
+            }
+        }
+
+        let round = |value: i64| value % ROUND_AMOUNT == 0;
+        let mut payments = self
+            .outputs
+            .iter()
+            .filter(|output| !output.is_mine)
+            .peekable();
+        let round_payments =
+            payments.peek().is_some() && payments.all(|output| round(output.value));
+        if round_payments {
+            for (change, output) in self.outputs.iter().enumerate() {
+                if output.is_change && !round(output.value) {
+                    warnings.push(PrivacyWarning::RoundNumberChange { change });
+                }
+            }
+        }
+
+        let mut types = vec![];
+        for input in self.inputs.iter() {
+            if !types.contains(&input.script_type) {
+                types.push(input.script_type);
+            }
+        }
+        if types.len() > 1 {
+            warnings.push(PrivacyWarning::MixedInputTypes(types));
+        }
+
+        let mut addresses = vec![];
+        let mut accounts = vec![];
+        for input in self.inputs.iter() {
+            if let Some(address) = &input.address {
+                if !addresses.contains(address) {
+                    addresses.push(address.clone());
+                }
+            }
+            if let Some(account) = input.account {
+                if !accounts.contains(&account) {
+                    accounts.push(account);
+                }
+            }
+        }
+        if addresses.len() > 1 {
+            warnings.push(PrivacyWarning::CommonInputOwnership {
+                addresses,
+                accounts,
+            });
+        }
+
+        warnings
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+    net::{IpAddr, TcpStream},
+    time::Duration,
+};
+
+use crate::BackendError;
+
+/// the port Tor listens for SOCKS5 connections on
+pub const TOR_SOCKS_PORT: u16 = 9050;
+
+/// How a backend connects to its server: directly, or through a SOCKS5
+/// proxy such as Tor so the server doesn't learn the IP address of the
+/// wallet, and with it which addresses the wallet watches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendConfig {
+    /// how long to wait on the server before giving up
+    timeout: Duration,
+    /// host and port of the SOCKS5 proxy, eg `127.0.0.1:9050`
+    proxy: Option<String>,
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        Self::direct()
+    }
+}
+
+impl BackendConfig {
+    /// connect to servers directly
+    pub fn direct() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            proxy: None,
+        }
+    }
+
+    /// Connect to servers through a SOCKS5 proxy. Host names are resolved by
+    /// the proxy, which reaches `.onion` servers when it is Tor
+    pub fn socks5(proxy: &str) -> Self {
+        Self {
+            proxy: Some(proxy.to_string()),
+            ..Self::direct()
+        }
+    }
+
+    /// connect through the Tor daemon running on this machine
+    pub fn tor() -> Self {
+        Self::socks5(&format!("127.0.0.1:{}", TOR_SOCKS_PORT))
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    /// open a connection to a server at `host:port`
+    pub(crate) fn connect(&self, address: &str) -> Result<TcpStream, BackendError> {
+        let connection_error = |e: std::io::Error| BackendError::Connection(e.to_string());
+
+        let proxy = match &self.proxy {
+            Some(proxy) => proxy,
+            // resolving it would fail anyway, and leak the lookup to the DNS server
+            None if is_onion(address) => {
+                return Err(BackendError::Connection(format!(
+                    "{} is an onion service, it is reached through a Tor proxy",
+                    address
+                )))
+            }
+            None => address,
+        };
+        let mut stream = TcpStream::connect(proxy).map_err(connection_error)?;
+        stream
+            .set_read_timeout(Some(self.timeout))
+            .map_err(connection_error)?;
+        stream
+            .set_write_timeout(Some(self.timeout))
+            .map_err(connection_error)?;
+
+        if self.proxy.is_some() {
+            socks5_connect(&mut stream, address)?;
+        }
+        Ok(stream)
+    }
+}
+
+/// whether a `host:port` is a Tor onion service
+fn is_onion(address: &str) -> bool {
+    address
+        .rsplit_once(':')
+        .map_or(address, |(host, _)| host)
+        .ends_with(".onion")
+}
+
+/// Ask a SOCKS5 proxy to connect to `host:port`, without authentication,
+/// as in RFC 1928. Host names are sent to the proxy unresolved
+fn socks5_connect(stream: &mut TcpStream, address: &str) -> Result<(), BackendError> {
+    let connection_error = |e: std::io::Error| BackendError::Connection(e.to_string());
+    let invalid_address = || BackendError::Connection(format!("invalid address {}", address));
+
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid_address)?;
+    let port: u16 = port.parse().map_err(|_| invalid_address())?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    // version 5, one method, no authentication
+    stream.write_all(&[5, 1, 0]).map_err(connection_error)?;
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply).map_err(connection_error)?;
+    if reply != [5, 0] {
+        return Err(BackendError::Connection(
+            "the proxy requires authentication".to_string(),
+        ));
+    }
+
+    let mut request = vec![5, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            let length = u8::try_from(host.len()).map_err(|_| invalid_address())?;
+            request.push(3);
+            request.push(length);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).map_err(connection_error)?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).map_err(connection_error)?;
+    if reply[1] != 0 {
+        return Err(BackendError::Connection(format!(
+            "the proxy failed to connect to {}: {}",
+            address,
+            socks5_error(reply[1])
+        )));
+    }
+
+    // the address the proxy bound, and its port
+    let bound = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => {
+            let mut length = [0; 1];
+            stream.read_exact(&mut length).map_err(connection_error)?;
+            length[0] as usize
+        }
+        _ => {
+            return Err(BackendError::Connection(
+                "invalid reply from the proxy".to_string(),
+            ))
+        }
+    };
+    let mut bound = vec![0; bound + 2];
+    stream.read_exact(&mut bound).map_err(connection_error)?;
+
+    Ok(())
+}
+
+/// the reply codes of RFC 1928
+fn socks5_error(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
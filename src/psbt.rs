@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use crate::{
+    compact_size, decode_transaction, Network, Reader, Script, ScriptType, Transaction,
+    TransactionError, TransactionOutput, TxSummary,
+};
+
+/// the magic bytes starting a PSBT (BIP174)
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const PSBT_IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+const PSBT_OUT_TAP_BIP32_DERIVATION: u8 = 0x07;
+
+/// The fields of an input of a [Psbt] the wallet reads
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PsbtInput {
+    /// The output spent, from the transaction spent or the witness utxo.
+    /// Only an output spent with a witness can be read from its witness
+    /// utxo, the signatures of others don't commit to its value
+    pub(crate) utxo: Option<TransactionOutput>,
+    pub(crate) partial_sigs: bool,
+    pub(crate) final_script_sig: Option<Vec<u8>>,
+    pub(crate) final_witness: Option<Vec<Vec<u8>>>,
+}
+
+impl PsbtInput {
+    pub(crate) fn is_finalized(&self) -> bool {
+        self.final_script_sig.is_some() || self.final_witness.is_some()
+    }
+}
+
+/// The fields of an output of a [Psbt] a payjoin sender reads
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(feature = "payjoin"), allow(dead_code))]
+pub(crate) struct PsbtOutput {
+    /// whether the output carries the key paths of a wallet
+    pub(crate) key_paths: bool,
+}
+
+/// The parts of a PSBT (BIP174) the wallet reads, eg the proposal of a
+/// payjoin receiver, other fields are skipped
+#[derive(Debug, Clone)]
+pub(crate) struct Psbt {
+    pub(crate) tx: TxSummary,
+    pub(crate) inputs: Vec<PsbtInput>,
+    #[cfg_attr(not(feature = "payjoin"), allow(dead_code))]
+    pub(crate) outputs: Vec<PsbtOutput>,
+}
+
+impl Psbt {
+    /// read a base64 encoded PSBT, failing with what is wrong with it
+    pub(crate) fn decode(psbt: &str, network: Network) -> Result<Self, String> {
+        let invalid = |error: &str| error.to_string();
+        let decode_error = |e: TransactionError| format!("{:?}", e);
+        let bytes = base64::decode(psbt.trim()).map_err(|e| e.to_string())?;
+        if !bytes.starts_with(PSBT_MAGIC) {
+            return Err(invalid("no PSBT magic bytes"));
+        }
+        let mut reader = Reader::new(&bytes[PSBT_MAGIC.len()..]);
+
+        let mut tx = None;
+        for (key, value) in read_map(&mut reader).map_err(decode_error)? {
+            if key == [PSBT_GLOBAL_UNSIGNED_TX] {
+                tx = Some(decode_transaction(&hex::encode(value), network).map_err(decode_error)?);
+            }
+        }
+        let tx = tx.ok_or_else(|| invalid("no unsigned transaction"))?;
+        if tx
+            .inputs
+            .iter()
+            .any(|input| !input.signature_script.is_empty() || !input.witness.is_empty())
+        {
+            return Err(invalid("the unsigned transaction is signed"));
+        }
+
+        let mut inputs = vec![];
+        for (index, input) in tx.inputs.iter().enumerate() {
+            let mut fields = PsbtInput::default();
+            let mut witness_utxo = None;
+            for (key, value) in read_map(&mut reader).map_err(decode_error)? {
+                match key.first() {
+                    Some(&PSBT_IN_NON_WITNESS_UTXO) if key.len() == 1 => {
+                        let spent = decode_transaction(&hex::encode(value), network)
+                            .map_err(decode_error)?;
+                        let outpoint = &input.previous_output;
+                        if spent.tx_id != outpoint.hash() {
+                            return Err(format!("a utxo isn't from {}", outpoint.hash()));
+                        }
+                        let output = spent
+                            .outputs
+                            .get(outpoint.index() as usize)
+                            .ok_or_else(|| format!("no output {}", outpoint.index()))?;
+                        fields.utxo = Some(TransactionOutput::from_script(
+                            output.value,
+                            output.pk_script.clone(),
+                        ));
+                    }
+                    Some(&PSBT_IN_WITNESS_UTXO) if key.len() == 1 => {
+                        let mut value = Reader::new(value);
+                        let amount = value.i64().map_err(decode_error)?;
+                        let length = value.compact_size().map_err(decode_error)?;
+                        let pk_script = value.take(length).map_err(decode_error)?.to_vec();
+                        witness_utxo = Some(TransactionOutput::from_script(amount, pk_script));
+                    }
+                    Some(&PSBT_IN_PARTIAL_SIG) => fields.partial_sigs = true,
+                    Some(&PSBT_IN_FINAL_SCRIPTSIG) if key.len() == 1 => {
+                        fields.final_script_sig = Some(value.to_vec())
+                    }
+                    Some(&PSBT_IN_FINAL_SCRIPTWITNESS) if key.len() == 1 => {
+                        let mut value = Reader::new(value);
+                        let mut witness = vec![];
+                        for _ in 0..value.compact_size().map_err(decode_error)? {
+                            let length = value.compact_size().map_err(decode_error)?;
+                            witness.push(value.take(length).map_err(decode_error)?.to_vec());
+                        }
+                        fields.final_witness = Some(witness);
+                    }
+                    _ => {}
+                }
+            }
+            match (&fields.utxo, witness_utxo) {
+                (Some(spent), Some(witness_utxo))
+                    if spent.value() != witness_utxo.value()
+                        || spent.pk_script() != witness_utxo.pk_script() =>
+                {
+                    return Err(format!(
+                        "the witness utxo of input {} isn't the output spent",
+                        index
+                    ));
+                }
+                (Some(_), _) | (None, None) => {}
+                (None, Some(witness_utxo)) => {
+                    let script_sig = fields.final_script_sig.as_deref().unwrap_or_default();
+                    if !spends_witness(witness_utxo.pk_script(), script_sig) {
+                        return Err(format!(
+                            "input {} spends a non-segwit output without its transaction",
+                            index
+                        ));
+                    }
+                    fields.utxo = Some(witness_utxo);
+                }
+            }
+            inputs.push(fields);
+        }
+
+        let mut outputs = vec![];
+        for _ in tx.outputs.iter() {
+            let key_paths = read_map(&mut reader)
+                .map_err(decode_error)?
+                .iter()
+                .any(|(key, _)| {
+                    matches!(
+                        key.first(),
+                        Some(&PSBT_OUT_BIP32_DERIVATION | &PSBT_OUT_TAP_BIP32_DERIVATION)
+                    )
+                });
+            outputs.push(PsbtOutput { key_paths });
+        }
+        if reader.position() != bytes.len() - PSBT_MAGIC.len() {
+            return Err(invalid("bytes after the last output"));
+        }
+
+        Ok(Self {
+            tx,
+            inputs,
+            outputs,
+        })
+    }
+}
+
+/// a key of a map of a PSBT and its value
+type Field<'a> = (&'a [u8], &'a [u8]);
+
+/// the keys and values of a map of a PSBT, up to its separator
+fn read_map<'a>(reader: &mut Reader<'a>) -> Result<Vec<Field<'a>>, TransactionError> {
+    let mut map = vec![];
+    loop {
+        let key_length = reader.compact_size()?;
+        if key_length == 0 {
+            return Ok(map);
+        }
+        let key = reader.take(key_length)?;
+        let value_length = reader.compact_size()?;
+        map.push((key, reader.take(value_length)?));
+    }
+}
+
+/// write a key of a single type byte and its value to a map of a PSBT
+fn write_field(bytes: &mut Vec<u8>, key_type: u8, value: &[u8]) {
+    bytes.extend_from_slice(&[1, key_type]);
+    bytes.append(&mut compact_size(value.len()));
+    bytes.extend_from_slice(value);
+}
+
+/// Whether an output is spent with a witness, so the signatures of its input
+/// commit to its value (BIP143, BIP341): a witness program, or a P2SH output
+/// unless the script sig spending it shows it doesn't nest one
+pub(crate) fn spends_witness(pk_script: &[u8], script_sig: &[u8]) -> bool {
+    let is_witness_program = |script: &[u8]| {
+        matches!(
+            Script::new(script.to_vec()).classify(),
+            ScriptType::P2wpkh
+                | ScriptType::P2wsh
+                | ScriptType::P2tr
+                | ScriptType::UnknownWitness { .. }
+        )
+    };
+    match Script::new(pk_script.to_vec()).classify() {
+        ScriptType::P2sh => match script_sig {
+            [] => true,
+            [length, program @ ..] => {
+                *length as usize == program.len() && is_witness_program(program)
+            }
+        },
+        _ => is_witness_program(pk_script),
+    }
+}
+
+/// Encode a transaction as a base64 PSBT, each input finalized when it is
+/// signed. An input spent with a witness carries its witness utxo, any other
+/// the transaction it spends, taken from the serialized transactions given
+/// by txid, see [spends_witness]. Inputs spending no script, eg the commitment
+/// of a [crate::ReserveProof], or whose transaction isn't given carry no utxo
+pub(crate) fn encode_psbt(tx: &Transaction, spent: &HashMap<String, Vec<u8>>) -> String {
+    let mut bytes = PSBT_MAGIC.to_vec();
+    write_field(
+        &mut bytes,
+        PSBT_GLOBAL_UNSIGNED_TX,
+        &tx.serialize_unsigned(),
+    );
+    bytes.push(0);
+
+    for input in tx.inputs().iter() {
+        let utxo =
+            TransactionOutput::from_script(input.utxo_value(), input.utxo_pk_script().to_vec());
+        if spends_witness(utxo.pk_script(), input.signature_script()) {
+            write_field(&mut bytes, PSBT_IN_WITNESS_UTXO, &utxo.serialize());
+        } else if let Some(raw) = spent.get(&input.previous_output().hash()) {
+            write_field(&mut bytes, PSBT_IN_NON_WITNESS_UTXO, raw);
+        }
+        if !input.signature_script().is_empty() {
+            write_field(
+                &mut bytes,
+                PSBT_IN_FINAL_SCRIPTSIG,
+                input.signature_script(),
+            );
+        }
+        if !input.witness().is_empty() {
+            let mut witness = compact_size(input.witness().len());
+            for item in input.witness().iter() {
+                witness.append(&mut compact_size(item.len()));
+                witness.extend_from_slice(item);
+            }
+            write_field(&mut bytes, PSBT_IN_FINAL_SCRIPTWITNESS, &witness);
+        }
+        bytes.push(0);
+    }
+    bytes.extend(std::iter::repeat_n(0, tx.tx_out_count()));
+
+    base64::encode(bytes)
+}
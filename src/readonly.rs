@@ -0,0 +1,287 @@
+use std::{io::Write, path::PathBuf};
+
+use crate::{
+    Account, AccountView, AccountXpub, AddressValidation, AnnotatedTransaction, Backend, Balance,
+    Birthday, Chain, ExportFormat, FeeLimits, KeyError, KeyView, Network, OwnershipProof,
+    ReserveProof, SignedTx, SilentPaymentOutput, Spend, SpendPreview, StatsInterval, Transaction,
+    TxRecord, TxSummary, Utxo, Wallet, WalletConfig, WalletError, WalletSnapshot, WalletStats,
+};
+
+/// A [Wallet] loaded for reporting and monitoring, see [Wallet::open_read_only].
+///
+/// Only what reads the wallet is exposed: it derives no addresses, tracks
+/// no outputs and is never flushed, so it can't overwrite the file of the
+/// process keeping the wallet. An encrypted wallet can't be unlocked and
+/// doesn't sign, an unencrypted one signs with its keys as [Wallet] does
+#[derive(Debug, Clone)]
+pub struct ReadOnlyWallet(Wallet);
+
+impl From<Wallet> for ReadOnlyWallet {
+    fn from(wallet: Wallet) -> Self {
+        Self(wallet)
+    }
+}
+
+impl ReadOnlyWallet {
+    /// see [Wallet::network]
+    pub fn network(&self) -> &Network {
+        self.0.network()
+    }
+
+    /// see [Wallet::compress_public_keys]
+    pub fn compress_public_keys(&self) -> bool {
+        self.0.compress_public_keys()
+    }
+
+    /// the data directory the wallet was loaded from, see [Wallet::path]
+    pub fn path(&self) -> &PathBuf {
+        self.0.path()
+    }
+
+    pub fn config(&self) -> &WalletConfig {
+        self.0.config()
+    }
+
+    /// see [Wallet::addresses]
+    pub fn addresses(&self) -> Result<Vec<String>, KeyError> {
+        self.0.addresses()
+    }
+
+    /// whether the wallet is encrypted, it then can't sign
+    pub fn is_encrypted(&self) -> bool {
+        self.0.kdf().is_some()
+    }
+
+    /// see [Wallet::is_watch_only]
+    pub fn is_watch_only(&self) -> bool {
+        self.0.is_watch_only()
+    }
+
+    /// see [Wallet::birthday]
+    pub fn birthday(&self) -> Birthday {
+        self.0.birthday()
+    }
+
+    /// see [Wallet::fee_limits]
+    pub fn fee_limits(&self) -> FeeLimits {
+        self.0.fee_limits()
+    }
+
+    /// Sign a 32 byte digest with the key owning an address, see
+    /// [Wallet::sign_data]. Fails with [WalletError::Locked] when encrypted
+    pub fn sign_data(&self, address: String, data: Vec<u8>) -> Result<Vec<u8>, WalletError> {
+        self.ensure_unencrypted()?;
+        self.0.sign_data(address, data)
+    }
+
+    /// Sign the tagged hash of data with the key owning an address, see
+    /// [Wallet::sign_raw_digest_with_tag]. Fails with [WalletError::Locked] when encrypted
+    pub fn sign_raw_digest_with_tag(
+        &self,
+        address: String,
+        tag: &str,
+        data: &[u8],
+    ) -> Result<Vec<u8>, WalletError> {
+        self.ensure_unencrypted()?;
+        self.0.sign_raw_digest_with_tag(address, tag, data)
+    }
+
+    /// Sign the inputs of a transaction spending coins of the wallet, see
+    /// [Wallet::sign_transaction]. Fails with [WalletError::Locked] when encrypted
+    pub fn sign_transaction(&self, tx: &mut Transaction) -> Result<usize, WalletError> {
+        self.ensure_unencrypted()?;
+        self.0.sign_transaction(tx)
+    }
+
+    /// Sign a challenge with the key owning an address, see
+    /// [Wallet::prove_ownership]. Fails with [WalletError::Locked] when encrypted
+    pub fn prove_ownership(
+        &self,
+        address: &str,
+        message: &str,
+    ) -> Result<OwnershipProof, WalletError> {
+        self.ensure_unencrypted()?;
+        self.0.prove_ownership(address, message)
+    }
+
+    /// Sign a proof of the reserves of the wallet, see
+    /// [Wallet::prove_reserves]. Fails with [WalletError::Locked] when encrypted
+    pub fn prove_reserves(
+        &self,
+        message: &str,
+        backend: &dyn Backend,
+    ) -> Result<ReserveProof, WalletError> {
+        self.ensure_unencrypted()?;
+        self.0.prove_reserves(message, backend)
+    }
+
+    /// see [Wallet::validate_addresses]
+    pub fn validate_addresses<I, S>(&self, addresses: I) -> Vec<AddressValidation>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.0.validate_addresses(addresses)
+    }
+
+    /// see [Wallet::accounts]
+    pub fn accounts(&self) -> &[Account] {
+        self.0.accounts()
+    }
+
+    /// see [Wallet::account]
+    pub fn account(&self, number: u32) -> Option<&Account> {
+        self.0.account(number)
+    }
+
+    /// see [Wallet::account_views]
+    pub fn account_views(&self) -> Vec<AccountView<'_>> {
+        self.0.account_views()
+    }
+
+    /// see [Wallet::account_view]
+    pub fn account_view(&self, number: u32) -> Option<AccountView<'_>> {
+        self.0.account_view(number)
+    }
+
+    /// see [Wallet::imported_keys]
+    pub fn imported_keys(&self) -> Vec<KeyView<'_>> {
+        self.0.imported_keys()
+    }
+
+    /// see [Wallet::account_xpub]
+    pub fn account_xpub(&self, account: u32) -> Result<AccountXpub, WalletError> {
+        self.0.account_xpub(account)
+    }
+
+    /// see [Wallet::account_descriptors]
+    pub fn account_descriptors(&self, account: u32) -> Result<Vec<String>, WalletError> {
+        self.0.account_descriptors(account)
+    }
+
+    /// see [Wallet::verify_address]
+    pub fn verify_address(
+        &self,
+        account: u32,
+        chain: Chain,
+        index: u32,
+    ) -> Result<String, WalletError> {
+        self.0.verify_address(account, chain, index)
+    }
+
+    /// see [Wallet::account_of]
+    pub fn account_of(&self, address: &str) -> Option<u32> {
+        self.0.account_of(address)
+    }
+
+    /// see [Wallet::is_mine]
+    pub fn is_mine(&self, script: &[u8]) -> bool {
+        self.0.is_mine(script)
+    }
+
+    /// see [Wallet::is_change]
+    pub fn is_change(&self, script: &[u8]) -> bool {
+        self.0.is_change(script)
+    }
+
+    /// see [Wallet::label]
+    pub fn label(&self, address: &str) -> Option<&str> {
+        self.0.label(address)
+    }
+
+    /// see [Wallet::utxos]
+    pub fn utxos(&self) -> &Vec<Utxo> {
+        self.0.utxos()
+    }
+
+    /// see [Wallet::balance]
+    pub fn balance(&self) -> Balance {
+        self.0.balance()
+    }
+
+    /// see [Wallet::account_balance]
+    pub fn account_balance(&self, account: u32) -> i64 {
+        self.0.account_balance(account)
+    }
+
+    /// see [Wallet::history]
+    pub fn history(&self) -> &Vec<TxRecord> {
+        self.0.history()
+    }
+
+    /// see [Wallet::spends]
+    pub fn spends(&self) -> &Vec<Spend> {
+        self.0.spends()
+    }
+
+    /// see [Wallet::conflicts_of]
+    pub fn conflicts_of(&self, tx_id: &str) -> Vec<String> {
+        self.0.conflicts_of(tx_id)
+    }
+
+    /// see [Wallet::archived_transactions]
+    pub fn archived_transactions(&self) -> &[SignedTx] {
+        self.0.archived_transactions()
+    }
+
+    /// see [Wallet::confirmations]
+    pub fn confirmations(&self, tx_id: &str) -> Option<u32> {
+        self.0.confirmations(tx_id)
+    }
+
+    /// see [Wallet::silent_payment_outputs]
+    pub fn silent_payment_outputs(&self) -> Vec<&SilentPaymentOutput> {
+        self.0.silent_payment_outputs()
+    }
+
+    /// see [Wallet::snapshot]
+    pub fn snapshot(&self) -> WalletSnapshot {
+        self.0.snapshot()
+    }
+
+    /// see [Wallet::stats]
+    pub fn stats(&self, interval: StatsInterval) -> WalletStats {
+        self.0.stats(interval)
+    }
+
+    /// see [Wallet::preview_spend]
+    pub fn preview_spend(&self, tx: &Transaction) -> SpendPreview {
+        self.0.preview_spend(tx)
+    }
+
+    /// see [Wallet::annotate]
+    pub fn annotate(&self, tx: &Transaction) -> AnnotatedTransaction {
+        self.0.annotate(tx)
+    }
+
+    /// see [Wallet::decode_transaction]
+    pub fn decode_transaction(&self, hex: &str) -> Result<TxSummary, WalletError> {
+        self.0.decode_transaction(hex)
+    }
+
+    /// see [Wallet::export_history]
+    pub fn export_history<W: Write>(
+        &self,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<(), WalletError> {
+        self.0.export_history(format, writer)
+    }
+
+    /// see [Wallet::export_utxos]
+    pub fn export_utxos<W: Write>(
+        &self,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<(), WalletError> {
+        self.0.export_utxos(format, writer)
+    }
+
+    /// the keys of an encrypted wallet stay sealed, it's never unlocked
+    fn ensure_unencrypted(&self) -> Result<(), WalletError> {
+        match self.is_encrypted() {
+            true => Err(WalletError::Locked),
+            false => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    reverse_bytes, sha256_hash_twice, LockTime, Network, OutPoint, Psbt, ReservesError,
+    Transaction, TransactionInput, TransactionOutput, TransactionType, TransactionVersion, Utxo,
+    VerifyError,
+};
+
+/// what the message of a proof of reserves is prefixed with before hashing
+const RESERVES_PREFIX: &str = "Proof-of-Reserves: ";
+
+/// the script of the output of a proof of reserves, OP_TRUE
+const RESERVES_SCRIPT: [u8; 1] = [0x51];
+
+/// A proof of reserves in the style of BIP127, made by
+/// [crate::Wallet::prove_reserves]: a transaction spending every coin of the
+/// wallet, signed, along with a first input spending an output that doesn't
+/// exist and commits to the message, eg the challenge of an auditor. The
+/// transaction is invalid, so the proof moves no funds, but its signatures
+/// show the keys of the coins are held. Exported as a base64 PSBT with
+/// finalized inputs, auditors verify it with [verify_reserves]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ReserveProof {
+    pub message: String,
+    pub psbt: String,
+}
+
+impl ReserveProof {
+    /// check the proof, see [verify_reserves]
+    pub fn verify(&self, network: Network) -> Result<Reserves, ReservesError> {
+        verify_reserves(&self.message, &self.psbt, network)
+    }
+}
+
+/// The coins a [ReserveProof] shows the keys of. Whether they are still
+/// unspent is up to the auditor, eg with `gettxout` on their own node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reserves {
+    pub outpoints: Vec<OutPoint>,
+    /// satoshis
+    pub total: i64,
+}
+
+/// Check a proof of reserves of a message: the PSBT must spend the
+/// commitment of the message first, then coins signed for, each once, to a
+/// single OP_TRUE output of their total. The signatures are checked as by
+/// [Transaction::verify] and must sign every input and output with
+/// SIGHASH_ALL, or the default type for taproot, so they commit to the message.
+/// The values of coins not spent with a witness, which their signatures don't
+/// commit to, are read from the transactions paying them the PSBT carries
+pub fn verify_reserves(
+    message: &str,
+    psbt: &str,
+    network: Network,
+) -> Result<Reserves, ReservesError> {
+    let psbt = Psbt::decode(psbt, network).map_err(ReservesError::InvalidPsbt)?;
+    let summary = &psbt.tx;
+    match summary.inputs.first() {
+        Some(input) if input.previous_output == reserves_commitment(message) => {}
+        _ => return Err(ReservesError::ChallengeMismatch),
+    }
+    if summary.inputs.len() < 2 {
+        return Err(ReservesError::NoReserves);
+    }
+
+    let mut inputs = vec![];
+    let mut prevouts = vec![];
+    let mut spent = HashSet::new();
+    for (index, (input, fields)) in summary.inputs.iter().zip(psbt.inputs.iter()).enumerate() {
+        if !spent.insert(&input.previous_output) {
+            return Err(ReservesError::DuplicateInput(index));
+        }
+        let utxo = match (index, &fields.utxo) {
+            (0, _) => TransactionOutput::from_script(0, vec![]),
+            (_, Some(utxo)) => utxo.clone(),
+            (_, None) => return Err(ReservesError::MissingUtxo(index)),
+        };
+        if index > 0 && !fields.is_finalized() {
+            return Err(ReservesError::Invalid(VerifyError::UnsignedInput(index)));
+        }
+        let outpoint = &input.previous_output;
+        let mut tx_in = TransactionInput::new(utxo.clone(), outpoint.hash(), outpoint.index());
+        tx_in.set_sequence(input.sequence);
+        tx_in.set_signature_script(fields.final_script_sig.clone().unwrap_or_default());
+        inputs.push(tx_in);
+        prevouts.push(utxo);
+    }
+
+    let total: i64 = prevouts.iter().map(|utxo| utxo.value()).sum();
+    match summary.outputs.as_slice() {
+        [output] if output.value == total && output.pk_script == RESERVES_SCRIPT => {}
+        _ => return Err(ReservesError::InvalidOutput),
+    }
+
+    let mut tx = Transaction::new(
+        TransactionType::Pay2PubKeyHash,
+        inputs,
+        vec![TransactionOutput::from_script(
+            total,
+            RESERVES_SCRIPT.to_vec(),
+        )],
+        Some(summary.lock_time),
+    );
+    tx.set_version(match summary.version {
+        1 => TransactionVersion::One,
+        2 => TransactionVersion::Two,
+        version => TransactionVersion::Nonstandard(version),
+    });
+    for (index, fields) in psbt.inputs.iter().enumerate() {
+        if let Some(witness) = &fields.final_witness {
+            tx.set_witness(index, witness.clone())
+                .map_err(|e| ReservesError::InvalidPsbt(format!("{:?}", e)))?;
+        }
+    }
+    tx.verify_from(&prevouts, 1)
+        .map_err(ReservesError::Invalid)?;
+    if let Some(index) = (1..tx.tx_in_count()).find(|index| !tx.signs_all(*index)) {
+        return Err(ReservesError::SighashType(index));
+    }
+
+    Ok(Reserves {
+        outpoints: summary.inputs[1..]
+            .iter()
+            .map(|input| input.previous_output.clone())
+            .collect(),
+        total,
+    })
+}
+
+/// the output a proof of reserves spends first, whose txid is the double
+/// SHA256 of the prefixed message so the signatures commit to it
+pub(crate) fn reserves_commitment(message: &str) -> OutPoint {
+    let hash = sha256_hash_twice(&format!("{}{}", RESERVES_PREFIX, message).into_bytes());
+    OutPoint::new(hex::encode(reverse_bytes(&hash)), 0)
+}
+
+/// The unsigned transaction of a proof of reserves spending coins, see
+/// [ReserveProof]
+pub(crate) fn reserves_transaction(message: &str, utxos: &[Utxo]) -> Transaction {
+    let commitment = reserves_commitment(message);
+    let mut inputs = vec![TransactionInput::new(
+        TransactionOutput::from_script(0, vec![]),
+        commitment.hash(),
+        commitment.index(),
+    )];
+    inputs.extend(utxos.iter().map(|utxo| utxo.to_input()));
+    let total = utxos.iter().map(|utxo| utxo.value()).sum();
+
+    // the type only sizes the transaction, taken from the first coin spent
+    let tx_type = utxos
+        .first()
+        .and_then(|utxo| utxo.script_type().tx_type())
+        .unwrap_or(TransactionType::Pay2PubKeyHash);
+    let mut tx = Transaction::new(
+        tx_type,
+        inputs,
+        vec![TransactionOutput::from_script(
+            total,
+            RESERVES_SCRIPT.to_vec(),
+        )],
+        Some(LockTime::default()),
+    );
+    tx.set_version(TransactionVersion::One);
+    tx
+}
@@ -0,0 +1,195 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Backend, BackendError, Block, FeeHistogram, MempoolAcceptance, PackageSubmission};
+
+/// How a [RetryingBackend] retries calls that failed on every server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// how many times every server is tried, at least once
+    pub max_attempts: u32,
+    /// the wait before the second round of attempts
+    pub initial_backoff: Duration,
+    /// the longest wait between two rounds, the wait doubles up to it
+    pub max_backoff: Duration,
+    /// Give up retrying once a call has taken this long. Each backend sets
+    /// how long a single request waits, eg [crate::BitcoinCoreRpc::set_timeout]
+    pub timeout: Duration,
+    /// the shortest time between two requests to a server, to stay under
+    /// the rate limits of public servers
+    pub min_interval: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+            timeout: Duration::from_secs(60),
+            min_interval: Duration::ZERO,
+        }
+    }
+}
+
+/// A [Backend] over several servers of the same chain, eg public Electrum or
+/// Esplora endpoints. A call that can't reach a server fails over to the
+/// next one, and rounds over the servers are retried with exponential
+/// backoff. Calls start on the last server that answered.
+/// Errors from a server that was reached, eg a node rejecting a
+/// transaction, are returned as they are
+pub struct RetryingBackend {
+    servers: Vec<Box<dyn Backend + Send + Sync>>,
+    policy: RetryPolicy,
+    /// the server calls start on
+    current: AtomicUsize,
+    /// when each server was last sent a request
+    last_request: Mutex<Vec<Option<Instant>>>,
+}
+
+impl fmt::Debug for RetryingBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryingBackend")
+            .field("servers", &self.servers.len())
+            .field("policy", &self.policy)
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+impl RetryingBackend {
+    /// retry over servers, tried in order until one answers
+    pub fn new(servers: Vec<Box<dyn Backend + Send + Sync>>, policy: RetryPolicy) -> Self {
+        Self {
+            last_request: Mutex::new(vec![None; servers.len()]),
+            servers,
+            policy,
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// the index of the server calls start on, the last one that answered
+    pub fn current_server(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Make a call on the servers, starting on the current one. Servers
+    /// that can't be reached or answer garbage are failed over, and
+    /// unsupported calls try the other servers without a retry
+    fn call<T, F>(&self, operation: &str, call: F) -> Result<T, BackendError>
+    where
+        F: Fn(&dyn Backend) -> Result<T, BackendError>,
+    {
+        let start = Instant::now();
+        let mut backoff = self.policy.initial_backoff;
+        let mut error = None;
+
+        for attempt in 0..self.policy.max_attempts.max(1) {
+            if attempt > 0 {
+                if start.elapsed() + backoff > self.policy.timeout {
+                    break;
+                }
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(self.policy.max_backoff);
+            }
+
+            let first = self.current_server();
+            let mut retry = false;
+            for offset in 0..self.servers.len() {
+                let server = (first + offset) % self.servers.len();
+                self.throttle(server);
+                match call(self.servers[server].as_ref()) {
+                    Ok(result) => {
+                        self.current.store(server, Ordering::Relaxed);
+                        return Ok(result);
+                    }
+                    Err(e @ BackendError::Rpc { .. }) => return Err(e),
+                    Err(e) => {
+                        debug!(server = server, attempt = attempt, error = %e, "{} failed", operation);
+                        retry |= !matches!(e, BackendError::Unsupported(_));
+                        error = Some(e);
+                    }
+                }
+            }
+            if !retry {
+                break;
+            }
+        }
+
+        Err(error.unwrap_or_else(|| BackendError::Unsupported(operation.to_string())))
+    }
+
+    /// wait until a server can be sent another request
+    fn throttle(&self, server: usize) {
+        if self.policy.min_interval.is_zero() {
+            return;
+        }
+        // the times are valid whatever a panicking thread left them at
+        let mut last_request = self
+            .last_request
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // the request is booked before waiting, so other threads wait behind it
+        let now = Instant::now();
+        let at = match last_request[server] {
+            Some(last) => (last + self.policy.min_interval).max(now),
+            None => now,
+        };
+        last_request[server] = Some(at);
+        drop(last_request);
+
+        thread::sleep(at - now);
+    }
+}
+
+impl Backend for RetryingBackend {
+    fn broadcast(&self, raw_tx: &str) -> Result<String, BackendError> {
+        self.call("broadcast", |server| server.broadcast(raw_tx))
+    }
+
+    fn submit_package(&self, raw_txs: &[String]) -> Result<PackageSubmission, BackendError> {
+        self.call("submitpackage", |server| server.submit_package(raw_txs))
+    }
+
+    fn test_accept(&self, raw_tx: &str) -> Result<MempoolAcceptance, BackendError> {
+        self.call("testmempoolaccept", |server| server.test_accept(raw_tx))
+    }
+
+    fn mempool_histogram(&self) -> Result<FeeHistogram, BackendError> {
+        self.call("mempool_histogram", |server| server.mempool_histogram())
+    }
+
+    fn tip_height(&self) -> Result<u32, BackendError> {
+        self.call("tip_height", |server| server.tip_height())
+    }
+
+    fn block(&self, height: u32) -> Result<Block, BackendError> {
+        self.call("block", |server| server.block(height))
+    }
+
+    fn transaction(&self, tx_id: &str) -> Result<String, BackendError> {
+        self.call("getrawtransaction", |server| server.transaction(tx_id))
+    }
+
+    fn silent_payment_tweaks(&self, height: u32) -> Result<HashMap<String, Vec<u8>>, BackendError> {
+        self.call("silent_payment_tweaks", |server| {
+            server.silent_payment_tweaks(height)
+        })
+    }
+
+    fn block_hash(&self, height: u32) -> Result<String, BackendError> {
+        self.call("block_hash", |server| server.block_hash(height))
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{Read, Write},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    silent_payment_tweak, trace::REDACTED, Backend, BackendConfig, BackendError, Block,
+    BlockTransaction, FeeHistogram, MempoolAcceptance, OutPoint, PackageSubmission,
+    PackageTxResult, SilentPaymentInput, TransactionOutput,
+};
+
+/// A Bitcoin Core node reached over its JSON-RPC interface
+#[derive(Clone)]
+pub struct BitcoinCoreRpc {
+    /// host and port of the node, eg `127.0.0.1:8332`
+    address: String,
+    user: String,
+    password: String,
+    /// the timeout and proxy of the connections to the node
+    config: BackendConfig,
+    /// the node wallet calls are made to, when the node has several loaded
+    wallet: Option<String>,
+}
+
+/// the password is redacted
+impl fmt::Debug for BitcoinCoreRpc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BitcoinCoreRpc")
+            .field("address", &self.address)
+            .field("user", &self.user)
+            .field("password", &REDACTED)
+            .field("config", &self.config)
+            .field("wallet", &self.wallet)
+            .finish()
+    }
+}
+
+impl BitcoinCoreRpc {
+    pub fn new(address: String, user: String, password: String) -> Self {
+        Self {
+            address,
+            user,
+            password,
+            config: BackendConfig::direct(),
+            wallet: None,
+        }
+    }
+
+    /// the same node, with wallet calls made to one of its loaded wallets
+    pub fn with_wallet(&self, wallet: &str) -> Self {
+        Self {
+            wallet: Some(wallet.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// change how long to wait on the node before giving up
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.config = self.config.clone().with_timeout(timeout);
+    }
+
+    /// change how to connect to the node, eg over Tor with [BackendConfig::tor]
+    pub fn set_config(&mut self, config: BackendConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &BackendConfig {
+        &self.config
+    }
+
+    /// call an RPC method and return its result
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(method = method), err(Debug))
+    )]
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, BackendError> {
+        let request = json!({
+            "jsonrpc": "1.0",
+            "id": "waller",
+            "method": method,
+            "params": params,
+        });
+
+        let (status, body) = self.post(&request.to_string())?;
+
+        let response: Value = match serde_json::from_str(&body) {
+            Ok(response) => response,
+            Err(_) => {
+                return Err(BackendError::InvalidResponse(format!(
+                    "HTTP {} from {}",
+                    status, method
+                )))
+            }
+        };
+
+        match response.get("error") {
+            Some(error) if !error.is_null() => Err(BackendError::Rpc {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+            }),
+            _ => Ok(response["result"].clone()),
+        }
+    }
+
+    /// send a JSON body to the node, returning the HTTP status and response body
+    fn post(&self, body: &str) -> Result<(u16, String), BackendError> {
+        let connection_error = |e: std::io::Error| BackendError::Connection(e.to_string());
+
+        let mut stream = self.config.connect(&self.address)?;
+
+        let credentials = base64::encode(format!("{}:{}", self.user, self.password));
+        let path = match &self.wallet {
+            Some(wallet) => format!("/wallet/{}", wallet),
+            None => "/".to_string(),
+        };
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nAuthorization: Basic {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            path,
+            self.address,
+            credentials,
+            body.len(),
+            body
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(connection_error)?;
+
+        let mut response = vec![];
+        stream
+            .read_to_end(&mut response)
+            .map_err(connection_error)?;
+
+        parse_http_response(&response)
+    }
+}
+
+impl Backend for BitcoinCoreRpc {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, err(Debug))
+    )]
+    fn broadcast(&self, raw_tx: &str) -> Result<String, BackendError> {
+        let result = self.call("sendrawtransaction", json!([raw_tx]))?;
+
+        match result.as_str() {
+            Some(txid) => Ok(txid.to_string()),
+            None => Err(BackendError::InvalidResponse(result.to_string())),
+        }
+    }
+
+    fn submit_package(&self, raw_txs: &[String]) -> Result<PackageSubmission, BackendError> {
+        let result = match self.call("submitpackage", json!([raw_txs])) {
+            // nodes older than v26 don't know the method
+            Err(BackendError::Rpc { code: -32601, .. }) => {
+                return Err(BackendError::Unsupported("submitpackage".to_string()))
+            }
+            result => result?,
+        };
+
+        let results = match result["tx-results"].as_object() {
+            Some(tx_results) => tx_results
+                .values()
+                .map(|tx| PackageTxResult {
+                    txid: tx["txid"].as_str().unwrap_or_default().to_string(),
+                    error: tx["error"].as_str().map(|e| e.to_string()),
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        Ok(PackageSubmission {
+            message: result["package_msg"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            results,
+        })
+    }
+
+    fn test_accept(&self, raw_tx: &str) -> Result<MempoolAcceptance, BackendError> {
+        let result = self.call("testmempoolaccept", json!([[raw_tx]]))?;
+        let tx = &result[0];
+        let allowed = tx["allowed"]
+            .as_bool()
+            .ok_or_else(|| BackendError::InvalidResponse(result.to_string()))?;
+
+        Ok(MempoolAcceptance {
+            txid: tx["txid"].as_str().unwrap_or_default().to_string(),
+            allowed,
+            vsize: tx["vsize"].as_u64(),
+            // fees are in bitcoin
+            fee: tx["fees"]["base"]
+                .as_f64()
+                .map(|fee| (fee * 100_000_000.0).round() as i64),
+            reject_reason: tx["reject-reason"].as_str().map(str::to_string),
+        })
+    }
+
+    /// built from every transaction of the mempool, the node has no histogram
+    fn mempool_histogram(&self) -> Result<FeeHistogram, BackendError> {
+        let result = self.call("getrawmempool", json!([true]))?;
+        let mempool = result
+            .as_object()
+            .ok_or_else(|| BackendError::InvalidResponse(result.to_string()))?;
+
+        let transactions = mempool
+            .values()
+            .map(|tx| {
+                // fees are in bitcoin
+                let fee = tx["fees"]["base"].as_f64()? * 100_000_000.0;
+                let vsize = tx["vsize"].as_u64().filter(|vsize| *vsize > 0)?;
+                Some((fee / vsize as f64, vsize))
+            })
+            .collect::<Option<Vec<(f64, u64)>>>()
+            .ok_or_else(|| BackendError::InvalidResponse(result.to_string()))?;
+        Ok(FeeHistogram::from_transactions(transactions))
+    }
+
+    fn tip_height(&self) -> Result<u32, BackendError> {
+        let height = self.call("getblockcount", json!([]))?;
+        height
+            .as_u64()
+            .map(|height| height as u32)
+            .ok_or_else(|| BackendError::InvalidResponse(height.to_string()))
+    }
+
+    fn block(&self, height: u32) -> Result<Block, BackendError> {
+        let hash = self.call("getblockhash", json!([height]))?;
+        parse_block(&self.call("getblock", json!([hash, 2]))?)
+    }
+
+    /// read from the block with the outputs its inputs spend, on nodes from v23
+    fn silent_payment_tweaks(&self, height: u32) -> Result<HashMap<String, Vec<u8>>, BackendError> {
+        let hash = self.call("getblockhash", json!([height]))?;
+        let block = self.call("getblock", json!([hash, 3]))?;
+        let invalid = || BackendError::InvalidResponse(block.to_string());
+        let script = |script: &Value| {
+            script
+                .as_str()
+                .and_then(|script| hex::decode(script).ok())
+                .ok_or_else(invalid)
+        };
+
+        let mut tweaks = HashMap::new();
+        for tx in block["tx"].as_array().ok_or_else(invalid)? {
+            let vin = tx["vin"].as_array().ok_or_else(invalid)?;
+            if vin.iter().any(|input| input.get("coinbase").is_some()) {
+                continue;
+            }
+
+            let mut inputs = vec![];
+            for input in vin {
+                let prevout = match input.get("prevout") {
+                    Some(prevout) => prevout,
+                    // older nodes don't know verbosity 3 and answer as with 2
+                    None => {
+                        return Err(BackendError::Unsupported(
+                            "getblock verbosity 3".to_string(),
+                        ))
+                    }
+                };
+                let witness = match input["txinwitness"].as_array() {
+                    Some(witness) => witness.iter().map(script).collect::<Result<_, _>>()?,
+                    None => vec![],
+                };
+                inputs.push(SilentPaymentInput {
+                    previous_output: OutPoint::new(
+                        input["txid"].as_str().ok_or_else(invalid)?.to_string(),
+                        input["vout"].as_i64().ok_or_else(invalid)? as i32,
+                    ),
+                    script_pubkey: script(&prevout["scriptPubKey"]["hex"])?,
+                    script_sig: match input["scriptSig"]["hex"].as_str() {
+                        Some(_) => script(&input["scriptSig"]["hex"])?,
+                        None => vec![],
+                    },
+                    witness,
+                });
+            }
+            // only the scripts of the outputs count
+            let outputs = tx["vout"]
+                .as_array()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|output| {
+                    Ok(TransactionOutput::from_script(
+                        0,
+                        script(&output["scriptPubKey"]["hex"])?,
+                    ))
+                })
+                .collect::<Result<Vec<TransactionOutput>, BackendError>>()?;
+
+            if let Some(tweak) = silent_payment_tweak(&inputs, &outputs) {
+                let tx_id = tx["txid"].as_str().ok_or_else(invalid)?;
+                tweaks.insert(tx_id.to_string(), tweak);
+            }
+        }
+        Ok(tweaks)
+    }
+
+    fn block_hash(&self, height: u32) -> Result<String, BackendError> {
+        let hash = self.call("getblockhash", json!([height]))?;
+        hash.as_str()
+            .map(str::to_string)
+            .ok_or_else(|| BackendError::InvalidResponse(hash.to_string()))
+    }
+}
+
+/// a block as returned by `getblock` with verbosity 2
+fn parse_block(block: &Value) -> Result<Block, BackendError> {
+    let invalid = || BackendError::InvalidResponse(block.to_string());
+
+    let transactions = block["tx"]
+        .as_array()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|tx| {
+            let inputs = tx["vin"]
+                .as_array()
+                .ok_or_else(invalid)?
+                .iter()
+                .filter(|input| input.get("coinbase").is_none())
+                .map(|input| {
+                    Ok(OutPoint::new(
+                        input["txid"].as_str().ok_or_else(invalid)?.to_string(),
+                        input["vout"].as_i64().ok_or_else(invalid)? as i32,
+                    ))
+                })
+                .collect::<Result<Vec<OutPoint>, BackendError>>()?;
+
+            let outputs = tx["vout"]
+                .as_array()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|output| {
+                    let script = output["scriptPubKey"]["hex"]
+                        .as_str()
+                        .and_then(|script| hex::decode(script).ok())
+                        .ok_or_else(invalid)?;
+                    // values are in bitcoin
+                    let value = output["value"].as_f64().ok_or_else(invalid)?;
+                    Ok(TransactionOutput::from_script(
+                        (value * 100_000_000.0).round() as i64,
+                        script,
+                    ))
+                })
+                .collect::<Result<Vec<TransactionOutput>, BackendError>>()?;
+
+            Ok(BlockTransaction {
+                tx_id: tx["txid"].as_str().ok_or_else(invalid)?.to_string(),
+                inputs,
+                outputs,
+            })
+        })
+        .collect::<Result<Vec<BlockTransaction>, BackendError>>()?;
+
+    Ok(Block {
+        height: block["height"].as_u64().ok_or_else(invalid)? as u32,
+        hash: block["hash"].as_str().ok_or_else(invalid)?.to_string(),
+        time: block["time"].as_u64().ok_or_else(invalid)?,
+        transactions,
+    })
+}
+
+/// split a raw HTTP response into its status code and body
+pub(crate) fn parse_http_response(response: &[u8]) -> Result<(u16, String), BackendError> {
+    let response = String::from_utf8_lossy(response);
+
+    let (head, body) = match response.find("\r\n\r\n") {
+        Some(split) => (&response[..split], &response[split + 4..]),
+        None => return Err(BackendError::InvalidResponse(response.to_string())),
+    };
+
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| BackendError::InvalidResponse(head.to_string()))?;
+
+    let chunked = head.lines().any(|line| {
+        line.to_lowercase()
+            .starts_with("transfer-encoding: chunked")
+    });
+
+    match chunked {
+        true => Ok((status, decode_chunked(body)?)),
+        false => Ok((status, body.to_string())),
+    }
+}
+
+/// join the chunks of a body sent with chunked transfer encoding
+fn decode_chunked(mut body: &str) -> Result<String, BackendError> {
+    let mut output = String::new();
+
+    loop {
+        let line_end = body
+            .find("\r\n")
+            .ok_or_else(|| BackendError::InvalidResponse("truncated chunk".to_string()))?;
+        let size = usize::from_str_radix(body[..line_end].trim(), 16)
+            .map_err(|e| BackendError::InvalidResponse(e.to_string()))?;
+
+        if size == 0 {
+            return Ok(output);
+        }
+
+        let start = line_end + 2;
+        let chunk = body
+            .get(start..start + size)
+            .ok_or_else(|| BackendError::InvalidResponse("truncated chunk".to_string()))?;
+        output.push_str(chunk);
+        body = body.get(start + size + 2..).unwrap_or_default();
+    }
+}
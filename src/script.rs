@@ -0,0 +1,454 @@
+use crate::{
+    base58check_decode, base58check_encode, compact_size, decode_segwit_address,
+    encode_segwit_address, hash160, sha256_hash, KeyError, Network, TransactionType,
+};
+
+/// push an empty element, also false
+pub const OP_0: u8 = 0x00;
+/// the next byte is the length of the data pushed
+pub const OP_PUSHDATA1: u8 = 0x4c;
+/// the next two bytes are the length of the data pushed
+pub const OP_PUSHDATA2: u8 = 0x4d;
+/// the next four bytes are the length of the data pushed
+pub const OP_PUSHDATA4: u8 = 0x4e;
+/// push -1
+pub const OP_1NEGATE: u8 = 0x4f;
+/// push 1, the small integers 1 to 16 are encoded from it upwards
+pub const OP_1: u8 = 0x51;
+pub const OP_IF: u8 = 0x63;
+pub const OP_NOTIF: u8 = 0x64;
+pub const OP_ELSE: u8 = 0x67;
+pub const OP_ENDIF: u8 = 0x68;
+pub const OP_VERIFY: u8 = 0x69;
+/// marks an output unspendable, the rest of the script carries data
+pub const OP_RETURN: u8 = 0x6a;
+pub const OP_TOALTSTACK: u8 = 0x6b;
+pub const OP_FROMALTSTACK: u8 = 0x6c;
+pub const OP_DROP: u8 = 0x75;
+pub const OP_DUP: u8 = 0x76;
+pub const OP_SIZE: u8 = 0x82;
+pub const OP_EQUAL: u8 = 0x87;
+pub const OP_EQUALVERIFY: u8 = 0x88;
+pub const OP_ADD: u8 = 0x93;
+pub const OP_SHA256: u8 = 0xa8;
+pub const OP_HASH160: u8 = 0xa9;
+pub const OP_CHECKSIG: u8 = 0xac;
+pub const OP_CHECKSIGVERIFY: u8 = 0xad;
+pub const OP_CHECKMULTISIG: u8 = 0xae;
+pub const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+/// fails unless the transaction's lock time is past the top stack item (BIP65)
+pub const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+/// fails unless the input's sequence is past the top stack item (BIP112)
+pub const OP_CHECKSEQUENCEVERIFY: u8 = 0xb2;
+
+/// the largest DER signature with its sighash type
+const MAX_ECDSA_SIGNATURE_LEN: u64 = 73;
+/// a Schnorr signature with a sighash type other than SIGHASH_DEFAULT
+const MAX_SCHNORR_SIGNATURE_LEN: u64 = 65;
+/// the length of a compressed public key
+const COMPRESSED_KEY_LEN: u64 = 33;
+
+/// The template an output script follows, see [Script::classify]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptType {
+    /// a bare public key, `<key> OP_CHECKSIG`
+    P2pk,
+    P2pkh,
+    P2sh,
+    /// segwit v0 paying to a 20 byte key hash
+    P2wpkh,
+    /// segwit v0 paying to a 32 byte script hash
+    P2wsh,
+    /// segwit v1 paying to a 32 byte taproot output key
+    P2tr,
+    /// a witness version or program length without a known spending rule
+    UnknownWitness {
+        version: u8,
+    },
+    /// `OP_RETURN` followed by data, unspendable
+    NullData,
+    /// bare multisig, `OP_m <key>... OP_n OP_CHECKMULTISIG`
+    Multisig {
+        required: u8,
+        keys: u8,
+    },
+    /// any other script
+    NonStandard,
+}
+
+impl ScriptType {
+    /// How the wallet spends an output of this type, none for the types it
+    /// holds no keys for. The only P2SH outputs of the wallet are nested segwit
+    pub fn tx_type(&self) -> Option<TransactionType> {
+        match self {
+            ScriptType::P2pkh => Some(TransactionType::Pay2PubKeyHash),
+            ScriptType::P2sh => Some(TransactionType::NestedPay2WitnessPubKeyHash),
+            ScriptType::P2wpkh => Some(TransactionType::Pay2WitnessPubKeyHash),
+            ScriptType::P2tr => Some(TransactionType::Pay2Taproot),
+            ScriptType::P2pk => Some(TransactionType::Pay2PubKey),
+            _ => None,
+        }
+    }
+
+    /// The most weight the script sig and witness spending an output of this
+    /// type add to an input, length prefixes included, for sizing fees before
+    /// the signatures exist. Keys are taken as compressed. None for the types
+    /// whose satisfaction depends on a script not known from the output, eg P2WSH
+    pub fn max_satisfaction_weight(&self) -> Option<u64> {
+        // a script sig counts four times, a witness once
+        let script_sig = |len: u64| (compact_size(len as usize).len() as u64 + len) * 4;
+        let witness = |items: &[u64]| {
+            compact_size(items.len()).len() as u64
+                + items
+                    .iter()
+                    .map(|len| compact_size(*len as usize).len() as u64 + len)
+                    .sum::<u64>()
+        };
+        let signature = 1 + MAX_ECDSA_SIGNATURE_LEN;
+        let key = 1 + COMPRESSED_KEY_LEN;
+
+        match self {
+            ScriptType::P2pk => Some(script_sig(signature)),
+            ScriptType::P2pkh => Some(script_sig(signature + key)),
+            // the push of the P2WPKH redeem script then its witness
+            ScriptType::P2sh => {
+                Some(script_sig(1 + 22) + witness(&[MAX_ECDSA_SIGNATURE_LEN, COMPRESSED_KEY_LEN]))
+            }
+            ScriptType::P2wpkh => {
+                Some(script_sig(0) + witness(&[MAX_ECDSA_SIGNATURE_LEN, COMPRESSED_KEY_LEN]))
+            }
+            // a key path spend
+            ScriptType::P2tr => Some(script_sig(0) + witness(&[MAX_SCHNORR_SIGNATURE_LEN])),
+            // the dummy element popped by OP_CHECKMULTISIG then the signatures
+            ScriptType::Multisig { required, .. } => {
+                Some(script_sig(1 + signature * *required as u64))
+            }
+            _ => None,
+        }
+    }
+
+    /// whether spending an output of this type takes a witness, P2SH
+    /// outputs being taken as nested segwit
+    pub fn is_witness(&self) -> bool {
+        matches!(
+            self,
+            ScriptType::P2sh
+                | ScriptType::P2wpkh
+                | ScriptType::P2wsh
+                | ScriptType::P2tr
+                | ScriptType::UnknownWitness { .. }
+        )
+    }
+}
+
+/// A serialized bitcoin script, eg a witness script or redeem script
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script(Vec<u8>);
+
+impl Script {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// start building a script from opcodes and pushes
+    pub fn builder() -> ScriptBuilder {
+        ScriptBuilder::default()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// the P2WSH pk script paying to this witness script, `OP_0 <sha256(script)>`
+    pub fn to_p2wsh(&self) -> Script {
+        let mut script = vec![OP_0, 0x20];
+        script.append(&mut sha256_hash(&self.0));
+        Script(script)
+    }
+
+    /// the P2SH pk script paying to this redeem script, `OP_HASH160 <hash160(script)> OP_EQUAL`
+    pub fn to_p2sh(&self) -> Script {
+        let mut script = vec![OP_HASH160, 0x14];
+        script.append(&mut hash160(&self.0));
+        script.push(OP_EQUAL);
+        Script(script)
+    }
+
+    /// generate a bech32 encoded P2WSH address paying to this witness script
+    pub fn p2wsh_address(&self, network: Network) -> Result<String, KeyError> {
+        encode_segwit_address(network, 0, &sha256_hash(&self.0))
+    }
+
+    /// generate a base58 encoded P2SH address paying to this redeem script
+    pub fn p2sh_address(&self, network: Network) -> String {
+        let mut script_hash = hash160(&self.0);
+        script_hash.insert(0, network.p2sh_prefix());
+
+        base58check_encode(&script_hash)
+    }
+
+    /// the public key a P2PK output pays to, none for any other script
+    pub fn p2pk_public_key(&self) -> Option<&[u8]> {
+        match self.classify() {
+            ScriptType::P2pk => Some(&self.0[1..self.0.len() - 1]),
+            _ => None,
+        }
+    }
+
+    /// Tell the template of an output script, eg to know how to spend it
+    pub fn classify(&self) -> ScriptType {
+        let script = &self.0;
+        let is_pubkey = |key: &[u8]| match key.first() {
+            Some(0x02 | 0x03) => key.len() == 33,
+            Some(0x04) => key.len() == 65,
+            _ => false,
+        };
+
+        if let Some((version, program)) = self.witness_program() {
+            return match (version, program.len()) {
+                (0, 20) => ScriptType::P2wpkh,
+                (0, 32) => ScriptType::P2wsh,
+                // v0 programs of other lengths fail to spend (BIP141)
+                (0, _) => ScriptType::NonStandard,
+                (1, 32) => ScriptType::P2tr,
+                (version, _) => ScriptType::UnknownWitness { version },
+            };
+        }
+
+        match script.as_slice() {
+            [OP_DUP, OP_HASH160, 0x14, hash @ .., OP_EQUALVERIFY, OP_CHECKSIG]
+                if hash.len() == 20 =>
+            {
+                ScriptType::P2pkh
+            }
+            [OP_HASH160, 0x14, hash @ .., OP_EQUAL] if hash.len() == 20 => ScriptType::P2sh,
+            [length, key @ .., OP_CHECKSIG] if *length as usize == key.len() && is_pubkey(key) => {
+                ScriptType::P2pk
+            }
+            [OP_RETURN, ..] => ScriptType::NullData,
+            [required, pushes @ .., keys, OP_CHECKMULTISIG]
+                if (OP_1..OP_1 + 16).contains(required) && (OP_1..OP_1 + 16).contains(keys) =>
+            {
+                let (required, keys) = (required - OP_1 + 1, keys - OP_1 + 1);
+                match parse_pushes(pushes) {
+                    Some(pubkeys)
+                        if pubkeys.len() == keys as usize
+                            && required <= keys
+                            && pubkeys.iter().all(|key| is_pubkey(key)) =>
+                    {
+                        ScriptType::Multisig { required, keys }
+                    }
+                    _ => ScriptType::NonStandard,
+                }
+            }
+            _ => ScriptType::NonStandard,
+        }
+    }
+
+    /// The address an output script pays to, for P2PKH, P2SH and segwit
+    /// outputs. `None` for any other script, eg bare multisig or `OP_RETURN`
+    pub fn to_address(&self, network: Network) -> Option<String> {
+        let base58 = |prefix: u8, hash: &[u8]| base58check_encode(&[&[prefix], hash].concat());
+
+        match self.classify() {
+            ScriptType::P2pkh => Some(base58(network.p2pkh_prefix(), &self.0[3..23])),
+            ScriptType::P2sh => Some(base58(network.p2sh_prefix(), &self.0[2..22])),
+            ScriptType::P2wpkh
+            | ScriptType::P2wsh
+            | ScriptType::P2tr
+            | ScriptType::UnknownWitness { .. } => {
+                let (version, program) = self.witness_program()?;
+                encode_segwit_address(network, version, program).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// the version and program of a witness output, a version
+    /// opcode then a single push of 2 to 40 bytes (BIP141)
+    fn witness_program(&self) -> Option<(u8, &[u8])> {
+        match self.0.as_slice() {
+            [OP_0, length, program @ ..]
+                if *length as usize == program.len() && (2..=40).contains(&program.len()) =>
+            {
+                Some((0, program))
+            }
+            [version, length, program @ ..]
+                if (OP_1..OP_1 + 16).contains(version)
+                    && *length as usize == program.len()
+                    && (2..=40).contains(&program.len()) =>
+            {
+                Some((version - OP_1 + 1, program))
+            }
+            _ => None,
+        }
+    }
+
+    /// The output script paying to a P2PKH, P2SH or segwit address of a
+    /// network, the inverse of [Script::to_address]
+    pub fn from_address(address: &str, network: Network) -> Result<Script, KeyError> {
+        // a bech32 address fails the base58 checksum, when it decodes at all
+        let decoded = match base58check_decode(address) {
+            Ok(decoded) => decoded,
+            Err(_) => {
+                let (version, program) = decode_segwit_address(network, address)?;
+                let version = match version {
+                    0 => OP_0,
+                    version => OP_1 + version - 1,
+                };
+                return Ok(Script(
+                    [&[version, program.len() as u8][..], &program].concat(),
+                ));
+            }
+        };
+
+        match decoded.as_slice() {
+            [prefix, hash @ ..] if hash.len() == 20 => match *prefix {
+                prefix if prefix == network.p2pkh_prefix() => Ok(Script::builder()
+                    .push_opcode(OP_DUP)
+                    .push_opcode(OP_HASH160)
+                    .push_slice(hash)
+                    .push_opcode(OP_EQUALVERIFY)
+                    .push_opcode(OP_CHECKSIG)
+                    .build()),
+                prefix if prefix == network.p2sh_prefix() => Ok(Script::builder()
+                    .push_opcode(OP_HASH160)
+                    .push_slice(hash)
+                    .push_opcode(OP_EQUAL)
+                    .build()),
+                _ => Err(KeyError::InvalidNetworkByte),
+            },
+            _ => Err(KeyError::InvalidFormat),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Script {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+/// Builds a [Script] one opcode or push at a time
+#[derive(Debug, Clone, Default)]
+pub struct ScriptBuilder {
+    bytes: Vec<u8>,
+}
+
+impl ScriptBuilder {
+    pub fn push_opcode(&mut self, opcode: u8) -> &mut Self {
+        self.bytes.push(opcode);
+        self
+    }
+
+    /// push data with the smallest opcode able to push it
+    pub fn push_slice(&mut self, data: &[u8]) -> &mut Self {
+        self.bytes.append(&mut push_data(data));
+        self
+    }
+
+    /// push a number, as a small integer opcode when possible
+    pub fn push_int(&mut self, number: i64) -> &mut Self {
+        match number {
+            0 => self.push_opcode(OP_0),
+            -1 => self.push_opcode(OP_1NEGATE),
+            1..=16 => self.push_opcode(OP_1 + number as u8 - 1),
+            _ => self.push_slice(&script_number(number)),
+        }
+    }
+
+    pub fn build(&self) -> Script {
+        Script(self.bytes.clone())
+    }
+}
+
+/// encode a number the way the script interpreter reads it,
+/// little endian with the sign in the top bit of the last byte
+pub(crate) fn script_number(number: i64) -> Vec<u8> {
+    let negative = number < 0;
+    let mut absolute = number.unsigned_abs();
+    let mut bytes = vec![];
+
+    while absolute > 0 {
+        bytes.push((absolute & 0xff) as u8);
+        absolute >>= 8;
+    }
+
+    // the top bit is the sign, add a byte if the value already uses it
+    match bytes.last() {
+        Some(last) if last & 0x80 != 0 => bytes.push(if negative { 0x80 } else { 0x00 }),
+        Some(_) if negative => *bytes.last_mut().unwrap() |= 0x80,
+        _ => {}
+    }
+
+    bytes
+}
+
+/// encode a data push with the smallest opcode able to push it
+pub(crate) fn push_data(data: &[u8]) -> Vec<u8> {
+    let mut script = match data.len() {
+        0..=0x4b => vec![data.len() as u8],
+        0x4c..=0xff => vec![OP_PUSHDATA1, data.len() as u8],
+        0x100..=0xffff => {
+            let mut script = vec![OP_PUSHDATA2];
+            script.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            script
+        }
+        _ => {
+            let mut script = vec![OP_PUSHDATA4];
+            script.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            script
+        }
+    };
+    script.extend_from_slice(data);
+    script
+}
+
+/// split a push only script into the elements it pushes,
+/// `None` if the script holds anything but data pushes
+pub(crate) fn parse_pushes(script: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut pushes = vec![];
+    let mut cursor = 0;
+
+    while cursor < script.len() {
+        let opcode = script[cursor];
+        cursor += 1;
+
+        let length = match opcode {
+            0x00..=0x4b => opcode as usize,
+            OP_PUSHDATA1 => {
+                let length = *script.get(cursor)? as usize;
+                cursor += 1;
+                length
+            }
+            OP_PUSHDATA2 => {
+                let bytes = script.get(cursor..cursor + 2)?;
+                cursor += 2;
+                u16::from_le_bytes([bytes[0], bytes[1]]) as usize
+            }
+            OP_PUSHDATA4 => {
+                let bytes = script.get(cursor..cursor + 4)?;
+                cursor += 4;
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+            }
+            _ => return None,
+        };
+
+        pushes.push(script.get(cursor..cursor + length)?.to_vec());
+        cursor += length;
+    }
+
+    Some(pushes)
+}
@@ -0,0 +1,448 @@
+use std::{collections::BTreeMap, iter};
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+
+use crate::{get_random_bytes, ShamirError};
+
+/// the 1024 words of SLIP-39 mnemonics, one per line
+const WORDLIST: &str = include_str!("slip39_wordlist.txt");
+
+const RADIX_BITS: usize = 10;
+/// words holding the identifier, group and member parameters and checksum
+const METADATA_WORDS: usize = 7;
+const CHECKSUM_WORDS: usize = 3;
+const MIN_SECRET_LENGTH: usize = 16;
+const MIN_MNEMONIC_WORDS: usize = METADATA_WORDS + (MIN_SECRET_LENGTH * 8).div_ceil(RADIX_BITS);
+const MAX_SHARES: u8 = 16;
+const DIGEST_LENGTH: usize = 4;
+/// the x coordinates the shared secret and its digest are stored at
+const SECRET_INDEX: u8 = 255;
+const DIGEST_INDEX: u8 = 254;
+const BASE_ITERATION_COUNT: u32 = 10000;
+const ROUND_COUNT: u8 = 4;
+/// the exponent of the PBKDF2 iteration count used for new shares
+const ITERATION_EXPONENT: u8 = 1;
+const CUSTOMIZATION: &[u8] = b"shamir";
+const EXTENDABLE_CUSTOMIZATION: &[u8] = b"shamir_extendable";
+
+/// A decoded SLIP-39 mnemonic
+#[derive(Debug, Clone)]
+struct Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Vec<u8>,
+}
+
+impl Share {
+    fn from_mnemonic(mnemonic: &str) -> Result<Self, ShamirError> {
+        let words = mnemonic
+            .split_whitespace()
+            .map(|word| {
+                let lowercase = word.to_lowercase();
+                WORDLIST
+                    .lines()
+                    .position(|known| known == lowercase)
+                    .map(|index| index as u32)
+                    .ok_or_else(|| ShamirError::InvalidWord(word.to_string()))
+            })
+            .collect::<Result<Vec<u32>, ShamirError>>()?;
+
+        if words.len() < MIN_MNEMONIC_WORDS {
+            return Err(ShamirError::InvalidLength(words.len()));
+        }
+
+        let id_exp = words[0] << RADIX_BITS | words[1];
+        let extendable = id_exp >> 4 & 1 == 1;
+        if rs1024_polymod(customization(extendable), &words) != 1 {
+            return Err(ShamirError::InvalidChecksum);
+        }
+
+        let params = words[2] << RADIX_BITS | words[3];
+        let share = Self {
+            identifier: (id_exp >> 5) as u16,
+            extendable,
+            iteration_exponent: (id_exp & 15) as u8,
+            group_index: (params >> 16 & 15) as u8,
+            group_threshold: (params >> 12 & 15) as u8 + 1,
+            group_count: (params >> 8 & 15) as u8 + 1,
+            member_index: (params >> 4 & 15) as u8,
+            member_threshold: (params & 15) as u8 + 1,
+            value: from_words(&words[4..words.len() - CHECKSUM_WORDS])?,
+        };
+
+        if share.value.len() < MIN_SECRET_LENGTH || !share.value.len().is_multiple_of(2) {
+            return Err(ShamirError::InvalidLength(words.len()));
+        }
+        if share.group_threshold > share.group_count {
+            return Err(ShamirError::InvalidThreshold(
+                share.group_threshold,
+                share.group_count,
+            ));
+        }
+
+        Ok(share)
+    }
+
+    fn to_mnemonic(&self) -> String {
+        let id_exp = (self.identifier as u32) << 5
+            | (self.extendable as u32) << 4
+            | self.iteration_exponent as u32;
+        let params = (self.group_index as u32) << 16
+            | (self.group_threshold as u32 - 1) << 12
+            | (self.group_count as u32 - 1) << 8
+            | (self.member_index as u32) << 4
+            | (self.member_threshold as u32 - 1);
+
+        let mut words = vec![
+            id_exp >> RADIX_BITS,
+            id_exp & 1023,
+            params >> RADIX_BITS,
+            params & 1023,
+        ];
+        words.extend(to_words(&self.value));
+
+        let checksum = rs1024_polymod(
+            customization(self.extendable),
+            &[words.as_slice(), &[0; CHECKSUM_WORDS]].concat(),
+        ) ^ 1;
+        words.extend((0..CHECKSUM_WORDS).map(|i| checksum >> (RADIX_BITS * (2 - i)) & 1023));
+
+        let wordlist: Vec<&str> = WORDLIST.lines().collect();
+        words
+            .iter()
+            .map(|word| wordlist[*word as usize])
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// whether two shares were split from the same secret at once
+    fn same_set(&self, other: &Share) -> bool {
+        self.identifier == other.identifier
+            && self.extendable == other.extendable
+            && self.iteration_exponent == other.iteration_exponent
+            && self.group_threshold == other.group_threshold
+            && self.group_count == other.group_count
+            && self.value.len() == other.value.len()
+    }
+}
+
+/// Split a secret into SLIP-39 mnemonic shares, any `threshold` of the
+/// `count` shares recover it with [combine_shares]. The secret is encrypted
+/// with the passphrase first, recovering with another passphrase gives a
+/// different secret rather than an error
+pub fn split_secret(
+    secret: &[u8],
+    threshold: u8,
+    count: u8,
+    passphrase: &str,
+) -> Result<Vec<String>, ShamirError> {
+    if secret.len() < MIN_SECRET_LENGTH || !secret.len().is_multiple_of(2) {
+        return Err(ShamirError::InvalidSecret(secret.len()));
+    }
+    if threshold == 0 || threshold > count || count > MAX_SHARES || (threshold == 1 && count > 1) {
+        return Err(ShamirError::InvalidThreshold(threshold, count));
+    }
+
+    let random = get_random_bytes(2);
+    let identifier = u16::from_be_bytes([random[0], random[1]]) & 0x7fff;
+    let encrypted = feistel(
+        secret,
+        passphrase,
+        ITERATION_EXPONENT,
+        &salt(identifier, false),
+        (0..ROUND_COUNT).collect(),
+    );
+
+    Ok(split(threshold, count, &encrypted)
+        .into_iter()
+        .map(|(member_index, value)| Share {
+            identifier,
+            extendable: false,
+            iteration_exponent: ITERATION_EXPONENT,
+            group_index: 0,
+            group_threshold: 1,
+            group_count: 1,
+            member_index,
+            member_threshold: threshold,
+            value,
+        })
+        .map(|share| share.to_mnemonic())
+        .collect())
+}
+
+/// Recover a secret from SLIP-39 mnemonic shares made with [split_secret]
+/// or any other SLIP-39 implementation, including shares split in groups
+pub fn combine_shares<S: AsRef<str>>(
+    mnemonics: &[S],
+    passphrase: &str,
+) -> Result<Vec<u8>, ShamirError> {
+    let shares = mnemonics
+        .iter()
+        .map(|mnemonic| Share::from_mnemonic(mnemonic.as_ref()))
+        .collect::<Result<Vec<Share>, ShamirError>>()?;
+    let first = shares
+        .first()
+        .ok_or_else(|| ShamirError::InsufficientShares("no shares given".to_string()))?;
+
+    let mut groups: BTreeMap<u8, Vec<&Share>> = BTreeMap::new();
+    for share in shares.iter() {
+        if !share.same_set(first) {
+            return Err(ShamirError::MismatchedShares(
+                "shares are from different secrets or sets".to_string(),
+            ));
+        }
+
+        let group = groups.entry(share.group_index).or_default();
+        if group
+            .iter()
+            .any(|member| member.member_threshold != share.member_threshold)
+        {
+            return Err(ShamirError::MismatchedShares(format!(
+                "member thresholds differ in group {}",
+                share.group_index
+            )));
+        }
+        if group
+            .iter()
+            .any(|member| member.member_index == share.member_index)
+        {
+            return Err(ShamirError::MismatchedShares(format!(
+                "member {} of group {} is given twice",
+                share.member_index, share.group_index
+            )));
+        }
+        group.push(share);
+    }
+
+    let mut group_secrets = vec![];
+    for (group_index, members) in groups.iter() {
+        let threshold = members[0].member_threshold;
+        if members.len() < threshold as usize {
+            continue;
+        }
+
+        let members: Vec<(u8, Vec<u8>)> = members
+            .iter()
+            .take(threshold as usize)
+            .map(|member| (member.member_index, member.value.clone()))
+            .collect();
+        group_secrets.push((*group_index, recover(threshold, &members)?));
+    }
+
+    if group_secrets.len() < first.group_threshold as usize {
+        return Err(ShamirError::InsufficientShares(format!(
+            "{} of {} groups complete",
+            group_secrets.len(),
+            first.group_threshold
+        )));
+    }
+    group_secrets.truncate(first.group_threshold as usize);
+
+    let encrypted = recover(first.group_threshold, &group_secrets)?;
+    Ok(feistel(
+        &encrypted,
+        passphrase,
+        first.iteration_exponent,
+        &salt(first.identifier, first.extendable),
+        (0..ROUND_COUNT).rev().collect(),
+    ))
+}
+
+/// split a secret into `count` shares at x coordinates from 0, the shares
+/// together with the secret and its digest lie on one polynomial
+fn split(threshold: u8, count: u8, secret: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    if threshold == 1 {
+        return (0..count).map(|index| (index, secret.to_vec())).collect();
+    }
+
+    let random_count = threshold - 2;
+    let mut shares: Vec<(u8, Vec<u8>)> = (0..random_count)
+        .map(|index| (index, get_random_bytes(secret.len())))
+        .collect();
+
+    let random_part = get_random_bytes(secret.len() - DIGEST_LENGTH);
+    let mut digest = hmac_sha256(&random_part, secret)[..DIGEST_LENGTH].to_vec();
+    digest.extend(random_part);
+
+    let mut base = shares.clone();
+    base.push((DIGEST_INDEX, digest));
+    base.push((SECRET_INDEX, secret.to_vec()));
+
+    for index in random_count..count {
+        shares.push((index, interpolate(&base, index)));
+    }
+    shares
+}
+
+/// recover the secret of [split] from a threshold of shares, checking its digest
+fn recover(threshold: u8, shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, ShamirError> {
+    if threshold == 1 {
+        return Ok(shares[0].1.clone());
+    }
+
+    let secret = interpolate(shares, SECRET_INDEX);
+    let digest = interpolate(shares, DIGEST_INDEX);
+    let (digest, random_part) = digest.split_at(DIGEST_LENGTH);
+
+    match hmac_sha256(random_part, &secret)[..DIGEST_LENGTH] == *digest {
+        true => Ok(secret),
+        false => Err(ShamirError::InvalidDigest),
+    }
+}
+
+/// the value at `x` of the polynomials over GF(256) through the shares, byte by byte
+fn interpolate(shares: &[(u8, Vec<u8>)], x: u8) -> Vec<u8> {
+    if let Some((_, value)) = shares.iter().find(|(index, _)| *index == x) {
+        return value.clone();
+    }
+
+    let (exp, log) = gf256_tables();
+    let log_product: usize = shares
+        .iter()
+        .map(|(index, _)| log[(index ^ x) as usize])
+        .sum();
+
+    let mut result = vec![0; shares[0].1.len()];
+    for (index, value) in shares.iter() {
+        let log_basis = (log_product + 255 * (shares.len() + 1)
+            - log[(index ^ x) as usize]
+            - shares
+                .iter()
+                .map(|(other, _)| log[(index ^ other) as usize])
+                .sum::<usize>())
+            % 255;
+
+        for (byte, share_byte) in result.iter_mut().zip(value.iter()) {
+            if *share_byte != 0 {
+                *byte ^= exp[(log[*share_byte as usize] + log_basis) % 255];
+            }
+        }
+    }
+    result
+}
+
+/// exponent and logarithm tables of GF(256) with the Rijndael polynomial
+fn gf256_tables() -> ([u8; 255], [usize; 256]) {
+    let mut exp = [0; 255];
+    let mut log = [0; 256];
+
+    let mut poly: u16 = 1;
+    for (i, value) in exp.iter_mut().enumerate() {
+        *value = poly as u8;
+        log[poly as usize] = i;
+
+        poly = (poly << 1) ^ poly;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11b;
+        }
+    }
+    (exp, log)
+}
+
+/// the four round Feistel cipher encrypting the secret, decrypting with the rounds reversed
+fn feistel(value: &[u8], passphrase: &str, exponent: u8, salt: &[u8], rounds: Vec<u8>) -> Vec<u8> {
+    let (left, right) = value.split_at(value.len() / 2);
+    let (mut left, mut right) = (left.to_vec(), right.to_vec());
+
+    let iterations = (BASE_ITERATION_COUNT << exponent) / ROUND_COUNT as u32;
+    for round in rounds {
+        let password: Vec<u8> = iter::once(round).chain(passphrase.bytes()).collect();
+        let mut key = vec![0; right.len()];
+        pbkdf2::pbkdf2::<Hmac<Sha256>>(&password, &[salt, &right].concat(), iterations, &mut key);
+
+        let next = left.iter().zip(key).map(|(a, b)| a ^ b).collect();
+        left = std::mem::replace(&mut right, next);
+    }
+
+    [right, left].concat()
+}
+
+/// the PBKDF2 salt prefix, bound to the identifier unless the shares are extendable
+fn salt(identifier: u16, extendable: bool) -> Vec<u8> {
+    match extendable {
+        true => vec![],
+        false => [CUSTOMIZATION, &identifier.to_be_bytes()].concat(),
+    }
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+    match extendable {
+        true => EXTENDABLE_CUSTOMIZATION,
+        false => CUSTOMIZATION,
+    }
+}
+
+/// the Reed-Solomon checksum over GF(1024) of SLIP-39
+fn rs1024_polymod(customization: &[u8], words: &[u32]) -> u32 {
+    const GENERATOR: [u32; 10] = [
+        0xe0e040, 0x1c1c080, 0x3838100, 0x7070200, 0xe0e0009, 0x1c0c2412, 0x38086c24, 0x3090fc48,
+        0x21b1f890, 0x3f3f120,
+    ];
+
+    let values = customization
+        .iter()
+        .map(|byte| *byte as u32)
+        .chain(words.iter().copied());
+    let mut checksum = 1;
+    for value in values {
+        let top = checksum >> 20;
+        checksum = (checksum & 0xfffff) << 10 ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if top >> i & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// split bytes into 10 bit words, zero padded at the front
+fn to_words(bytes: &[u8]) -> Vec<u32> {
+    let count = (bytes.len() * 8).div_ceil(RADIX_BITS);
+    let padding = count * RADIX_BITS - bytes.len() * 8;
+
+    let bits: Vec<u32> = iter::repeat_n(0, padding)
+        .chain(
+            bytes
+                .iter()
+                .flat_map(|byte| (0..8).rev().map(move |i| (*byte >> i & 1) as u32)),
+        )
+        .collect();
+    bits.chunks(RADIX_BITS)
+        .map(|chunk| chunk.iter().fold(0, |word, bit| word << 1 | bit))
+        .collect()
+}
+
+/// join 10 bit words back into bytes, the front padding must be at most a byte of zeros
+fn from_words(words: &[u32]) -> Result<Vec<u8>, ShamirError> {
+    let padding = words.len() * RADIX_BITS % 16;
+    if padding > 8 {
+        return Err(ShamirError::InvalidPadding);
+    }
+
+    let bits: Vec<u8> = words
+        .iter()
+        .flat_map(|word| (0..RADIX_BITS).rev().map(move |i| (word >> i & 1) as u8))
+        .collect();
+    if bits[..padding].iter().any(|bit| *bit != 0) {
+        return Err(ShamirError::InvalidPadding);
+    }
+
+    Ok(bits[padding..]
+        .chunks(8)
+        .map(|chunk| chunk.iter().fold(0, |byte, bit| byte << 1 | bit))
+        .collect())
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
@@ -0,0 +1,182 @@
+use std::{
+    path::PathBuf,
+    sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{OutPoint, Utxo, Wallet, WalletError, WalletEvent, WalletSnapshot};
+
+/// A [Wallet] that can be shared between threads, eg behind an `Arc`.
+///
+/// The key tree and the UTXO set sit behind separate locks so listing
+/// coins never waits on key derivation. Signing only takes a read lock,
+/// any number of threads can sign at once while addresses are issued
+/// one at a time.
+///
+/// Locks are always taken key tree first, then UTXO set, so operations
+/// needing both can't deadlock. Event callbacks run while locks are held
+/// and must not call back into the shared wallet. A panic while a lock
+/// is held poisons it and every following call fails with [WalletError::Poisoned].
+///
+/// [SharedWallet::flush] holds read locks on both halves while
+/// serializing, so the file is a consistent snapshot: it contains either
+/// all or none of the effects of any concurrent call. Flushes are
+/// serialized with each other and the file is replaced atomically.
+#[derive(Debug)]
+pub struct SharedWallet {
+    /// the wallet without its UTXOs
+    keys: RwLock<Wallet>,
+    utxos: RwLock<Vec<Utxo>>,
+    flush: Mutex<()>,
+}
+
+impl SharedWallet {
+    pub fn new(mut wallet: Wallet) -> Self {
+        let utxos = wallet.take_utxos();
+
+        Self {
+            keys: RwLock::new(wallet),
+            utxos: RwLock::new(utxos),
+            flush: Mutex::new(()),
+        }
+    }
+
+    /// take the wallet back, with its UTXOs
+    pub fn into_inner(self) -> Result<Wallet, WalletError> {
+        let mut wallet = self.keys.into_inner().map_err(|_| WalletError::Poisoned)?;
+        let utxos = self.utxos.into_inner().map_err(|_| WalletError::Poisoned)?;
+
+        wallet.set_utxos(utxos);
+        Ok(wallet)
+    }
+
+    /// run a closure with read access to the key tree.
+    /// The wallet given has no UTXOs, they are held by the [SharedWallet]
+    pub fn read<R, F: FnOnce(&Wallet) -> R>(&self, f: F) -> Result<R, WalletError> {
+        Ok(f(&*self.read_keys()?))
+    }
+
+    /// run a closure with write access to the key tree, eg to create accounts.
+    /// The wallet given has no UTXOs, they are held by the [SharedWallet]
+    pub fn write<R, F: FnOnce(&mut Wallet) -> R>(&self, f: F) -> Result<R, WalletError> {
+        Ok(f(&mut *self.write_keys()?))
+    }
+
+    /// derive a new address on the external chain of an account
+    pub fn new_receive_address(&self, account: u32) -> Result<String, WalletError> {
+        self.write_keys()?.new_receive_address(account)
+    }
+
+    /// derive a new address on the internal chain of an account, for change outputs
+    pub fn new_change_address(&self, account: u32) -> Result<String, WalletError> {
+        self.write_keys()?.new_change_address(account)
+    }
+
+    /// Sign a 32 byte digest with the key owning an address
+    pub fn sign_data(&self, address: String, data: Vec<u8>) -> Result<Vec<u8>, WalletError> {
+        self.read_keys()?.sign_data(address, data)
+    }
+
+    /// Sign the tagged hash of data with the key owning an address, see
+    /// [Wallet::sign_raw_digest_with_tag]
+    pub fn sign_raw_digest_with_tag(
+        &self,
+        address: String,
+        tag: &str,
+        data: &[u8],
+    ) -> Result<Vec<u8>, WalletError> {
+        self.read_keys()?
+            .sign_raw_digest_with_tag(address, tag, data)
+    }
+
+    /// a copy of the unspent outputs owned by the wallet
+    pub fn utxos(&self) -> Result<Vec<Utxo>, WalletError> {
+        Ok(self.read_utxos()?.clone())
+    }
+
+    /// track an unspent output paying to one of the wallet's addresses,
+    /// an output already tracked is left as it is
+    pub fn add_utxo(&self, utxo: Utxo) -> Result<(), WalletError> {
+        let keys = self.read_keys()?;
+        if !keys.owns_address(utxo.address()) {
+            return Err(WalletError::UnknownAddress(utxo.address().to_string()));
+        }
+
+        let mut utxos = self.write_utxos()?;
+        if utxos.iter().any(|held| held.outpoint() == utxo.outpoint()) {
+            return Ok(());
+        }
+        utxos.push(utxo.clone());
+        drop(utxos);
+        keys.emit(WalletEvent::UtxoReceived(utxo));
+        Ok(())
+    }
+
+    /// stop tracking an output once a transaction spending it is seen,
+    /// returns the output if it was tracked
+    pub fn spend_utxo(&self, outpoint: &OutPoint) -> Result<Option<Utxo>, WalletError> {
+        let keys = self.read_keys()?;
+        let mut utxos = self.write_utxos()?;
+
+        let utxo = match utxos.iter().position(|utxo| utxo.outpoint() == outpoint) {
+            Some(position) => utxos.remove(position),
+            None => return Ok(None),
+        };
+
+        keys.emit(WalletEvent::UtxoSpent(outpoint.clone()));
+        Ok(Some(utxo))
+    }
+
+    /// the sum of the unspent outputs an account can spend, see [Wallet::account_balance]
+    pub fn account_balance(&self, account: u32) -> Result<i64, WalletError> {
+        let keys = self.read_keys()?;
+        let tip_height = keys.tip_height();
+
+        Ok(self
+            .read_utxos()?
+            .iter()
+            .filter(|utxo| keys.account_of(utxo.address()) == Some(account))
+            .filter(|utxo| utxo.is_mature(tip_height))
+            .map(|utxo| utxo.value())
+            .sum())
+    }
+
+    /// a read-only report of the wallet, see [Wallet::snapshot]
+    pub fn snapshot(&self) -> Result<WalletSnapshot, WalletError> {
+        let keys = self.read_keys()?;
+        let utxos = self.read_utxos()?;
+
+        Ok(keys.snapshot_with(&utxos))
+    }
+
+    /// Write a consistent snapshot of the wallet to disk, see [Wallet::flush]
+    pub fn flush(&self) -> Result<PathBuf, WalletError> {
+        let _flushing = self.flush.lock().map_err(|_| WalletError::Poisoned)?;
+
+        let snapshot = {
+            let keys = self.read_keys()?;
+            let utxos = self.read_utxos()?;
+
+            let mut snapshot = keys.clone();
+            snapshot.set_utxos(utxos.clone());
+            snapshot
+        };
+
+        snapshot.flush()
+    }
+
+    fn read_keys(&self) -> Result<RwLockReadGuard<'_, Wallet>, WalletError> {
+        self.keys.read().map_err(|_| WalletError::Poisoned)
+    }
+
+    fn write_keys(&self) -> Result<RwLockWriteGuard<'_, Wallet>, WalletError> {
+        self.keys.write().map_err(|_| WalletError::Poisoned)
+    }
+
+    fn read_utxos(&self) -> Result<RwLockReadGuard<'_, Vec<Utxo>>, WalletError> {
+        self.utxos.read().map_err(|_| WalletError::Poisoned)
+    }
+
+    fn write_utxos(&self) -> Result<RwLockWriteGuard<'_, Vec<Utxo>>, WalletError> {
+        self.utxos.write().map_err(|_| WalletError::Poisoned)
+    }
+}
@@ -0,0 +1,384 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    compact_size, sha256_hash, sha256_hash_twice, tagged_hash, Transaction, TransactionError,
+    TransactionInput, SIGHASH_ALL, SIGHASH_DEFAULT,
+};
+
+/// sign every input and no output
+const SIGHASH_NONE: u32 = 0x02;
+/// sign every input and the output at the same index as the signed input
+const SIGHASH_SINGLE: u32 = 0x03;
+/// combined with another type, only the signed input is committed to
+const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+/// The signature hash algorithm an input is signed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SighashMode {
+    /// the original algorithm of non segwit inputs, signed with ECDSA
+    Legacy,
+    /// BIP143, for segwit v0 inputs native or nested in P2SH, signed with ECDSA
+    SegwitV0,
+    /// BIP341, for taproot key path spends, signed with Schnorr
+    Taproot,
+}
+
+/// The parts of a transaction a signature commits to. The flag is
+/// appended to the preimage of the signature hash as 4 little endian bytes
+/// and to the signature as a single byte
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SigHashType {
+    /// every input and output
+    #[default]
+    All,
+    /// every input and no output
+    None,
+    /// every input and the output at the index of the signed input
+    Single,
+    /// only the signed input and every output
+    AllPlusAnyoneCanPay,
+    /// only the signed input and no output
+    NonePlusAnyoneCanPay,
+    /// only the signed input and the output at its index
+    SinglePlusAnyoneCanPay,
+    /// SIGHASH_DEFAULT of taproot key path spends, every input and output
+    /// with no flag appended to the signature. ECDSA signatures can't use it
+    TaprootDefault,
+}
+
+impl SigHashType {
+    /// Read the flag of an ECDSA signature, failing with
+    /// [TransactionError::InvalidSighashType] for an undefined one
+    pub fn from_u32(flag: u32) -> Result<Self, TransactionError> {
+        match flag {
+            SIGHASH_ALL => Ok(SigHashType::All),
+            SIGHASH_NONE => Ok(SigHashType::None),
+            SIGHASH_SINGLE => Ok(SigHashType::Single),
+            _ if flag == SIGHASH_ALL | SIGHASH_ANYONECANPAY => Ok(SigHashType::AllPlusAnyoneCanPay),
+            _ if flag == SIGHASH_NONE | SIGHASH_ANYONECANPAY => {
+                Ok(SigHashType::NonePlusAnyoneCanPay)
+            }
+            _ if flag == SIGHASH_SINGLE | SIGHASH_ANYONECANPAY => {
+                Ok(SigHashType::SinglePlusAnyoneCanPay)
+            }
+            _ => Err(TransactionError::InvalidSighashType(flag)),
+        }
+    }
+
+    pub fn to_u32(self) -> u32 {
+        match self {
+            SigHashType::All => SIGHASH_ALL,
+            SigHashType::None => SIGHASH_NONE,
+            SigHashType::Single => SIGHASH_SINGLE,
+            SigHashType::AllPlusAnyoneCanPay => SIGHASH_ALL | SIGHASH_ANYONECANPAY,
+            SigHashType::NonePlusAnyoneCanPay => SIGHASH_NONE | SIGHASH_ANYONECANPAY,
+            SigHashType::SinglePlusAnyoneCanPay => SIGHASH_SINGLE | SIGHASH_ANYONECANPAY,
+            SigHashType::TaprootDefault => SIGHASH_DEFAULT as u32,
+        }
+    }
+
+    /// the flag of an ECDSA signature, any type but [SigHashType::TaprootDefault]
+    pub(crate) fn ecdsa_flag(self) -> Result<u32, TransactionError> {
+        match self {
+            SigHashType::TaprootDefault => Err(TransactionError::InvalidSighashType(self.to_u32())),
+            _ => Ok(self.to_u32()),
+        }
+    }
+
+    /// the flag as committed to by the preimage of the signature hash
+    pub fn to_le_bytes(self) -> [u8; 4] {
+        self.to_u32().to_le_bytes()
+    }
+
+    /// the flag as appended to a signature
+    pub fn to_byte(self) -> u8 {
+        self.to_u32() as u8
+    }
+}
+
+/// The hashes of a transaction's prevouts, sequences and outputs shared by
+/// the BIP143 and BIP341 signature hashes of all of its inputs.
+/// They are computed on first use and reused for every following input,
+/// so signing n inputs hashes the transaction once instead of n times.
+/// A cache must only be used with the transaction it was first used with,
+/// and must be dropped if inputs or outputs are added or changed.
+/// Signature scripts and witnesses are not committed to, setting them is fine
+#[derive(Debug, Clone, Default)]
+pub struct SighashCache {
+    common: Option<CommonHashes>,
+    taproot: Option<TaprootHashes>,
+}
+
+/// single sha256 hashes used as is by BIP341 and hashed again by BIP143
+#[derive(Debug, Clone)]
+struct CommonHashes {
+    prevouts: Vec<u8>,
+    sequences: Vec<u8>,
+    outputs: Vec<u8>,
+}
+
+/// the hashes of the spent outputs, only committed to by BIP341
+#[derive(Debug, Clone)]
+struct TaprootHashes {
+    amounts: Vec<u8>,
+    script_pubkeys: Vec<u8>,
+}
+
+impl SighashCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The BIP143 signature hash of a segwit v0 input
+    /// the script code is given without its length prefix
+    pub fn segwit_v0_sighash(
+        &mut self,
+        tx: &Transaction,
+        input_index: usize,
+        script_code: &[u8],
+        amount: i64,
+        sighash_type: u32,
+    ) -> Result<Vec<u8>, TransactionError> {
+        let input = tx
+            .get_input(input_index)
+            .ok_or(TransactionError::InputOutOfRange(input_index))?;
+
+        let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+        let base_type = sighash_type & 0x1f;
+        let common = self.common(tx);
+
+        let hash_prevouts = match anyone_can_pay {
+            true => vec![0; 32],
+            false => sha256_hash(&common.prevouts),
+        };
+
+        let hash_sequence =
+            match anyone_can_pay || base_type == SIGHASH_SINGLE || base_type == SIGHASH_NONE {
+                true => vec![0; 32],
+                false => sha256_hash(&common.sequences),
+            };
+
+        let hash_outputs = match base_type {
+            SIGHASH_SINGLE | SIGHASH_NONE => match tx.get_output(input_index) {
+                Some(output) if base_type == SIGHASH_SINGLE => {
+                    sha256_hash_twice(&output.serialize())
+                }
+                _ => vec![0; 32],
+            },
+            _ => sha256_hash(&common.outputs),
+        };
+
+        let mut preimage = tx.version().as_u32().to_le_bytes().to_vec();
+        preimage.extend_from_slice(&hash_prevouts);
+        preimage.extend_from_slice(&hash_sequence);
+        preimage.append(&mut input.previous_output().serialize());
+        preimage.append(&mut compact_size(script_code.len()));
+        preimage.extend_from_slice(script_code);
+        preimage.extend_from_slice(&amount.to_le_bytes());
+        preimage.extend_from_slice(&input.sequence().to_le_bytes());
+        preimage.extend_from_slice(&hash_outputs);
+        preimage.extend_from_slice(&tx.lock_time().to_consensus_u32().to_le_bytes());
+        preimage.extend_from_slice(&sighash_type.to_le_bytes());
+
+        Ok(sha256_hash_twice(&preimage))
+    }
+
+    /// The BIP341 signature hash of a taproot key path spend. The spent
+    /// outputs are read from the transaction's inputs. The annex, if any,
+    /// is given with its leading `0x50` byte
+    pub fn taproot_key_spend_sighash(
+        &mut self,
+        tx: &Transaction,
+        input_index: usize,
+        sighash_type: u8,
+        annex: Option<&[u8]>,
+    ) -> Result<Vec<u8>, TransactionError> {
+        let input = tx
+            .get_input(input_index)
+            .ok_or(TransactionError::InputOutOfRange(input_index))?;
+
+        let sighash_type_u32 = sighash_type as u32;
+        if !matches!(sighash_type, 0x00..=0x03 | 0x81..=0x83) {
+            return Err(TransactionError::InvalidSighashType(sighash_type_u32));
+        }
+
+        let anyone_can_pay = sighash_type_u32 & SIGHASH_ANYONECANPAY != 0;
+        let base_type = sighash_type_u32 & 0x03;
+
+        // the epoch, always 0
+        let mut message = vec![0x00, sighash_type];
+        message.extend_from_slice(&tx.version().as_u32().to_le_bytes());
+        message.extend_from_slice(&tx.lock_time().to_consensus_u32().to_le_bytes());
+
+        if !anyone_can_pay {
+            let common = self.common(tx).clone();
+            let taproot = self.taproot(tx);
+
+            message.extend_from_slice(&common.prevouts);
+            message.extend_from_slice(&taproot.amounts);
+            message.extend_from_slice(&taproot.script_pubkeys);
+            message.extend_from_slice(&common.sequences);
+        }
+
+        if base_type != SIGHASH_NONE && base_type != SIGHASH_SINGLE {
+            message.extend_from_slice(&self.common(tx).outputs);
+        }
+
+        // no extension for key path spends, only the annex flag
+        message.push(annex.is_some() as u8);
+
+        match anyone_can_pay {
+            true => {
+                message.append(&mut input.previous_output().serialize());
+                message.extend_from_slice(&input.utxo_value().to_le_bytes());
+                message.append(&mut compact_size(input.utxo_pk_script().len()));
+                message.extend_from_slice(input.utxo_pk_script());
+                message.extend_from_slice(&input.sequence().to_le_bytes());
+            }
+            false => message.extend_from_slice(&(input_index as u32).to_le_bytes()),
+        }
+
+        if let Some(annex) = annex {
+            let mut serialized = compact_size(annex.len());
+            serialized.extend_from_slice(annex);
+            message.append(&mut sha256_hash(&serialized));
+        }
+
+        if base_type == SIGHASH_SINGLE {
+            let output = tx
+                .get_output(input_index)
+                .ok_or(TransactionError::MissingSingleOutput(input_index))?;
+            message.append(&mut sha256_hash(&output.serialize()));
+        }
+
+        Ok(tagged_hash("TapSighash", &message))
+    }
+
+    fn common(&mut self, tx: &Transaction) -> &CommonHashes {
+        self.common.get_or_insert_with(|| CommonHashes {
+            prevouts: hash_inputs(tx, |input| input.previous_output().serialize()),
+            sequences: hash_inputs(tx, |input| input.sequence().to_le_bytes().to_vec()),
+            outputs: hash_outputs(tx),
+        })
+    }
+
+    fn taproot(&mut self, tx: &Transaction) -> &TaprootHashes {
+        self.taproot.get_or_insert_with(|| TaprootHashes {
+            amounts: hash_inputs(tx, |input| input.utxo_value().to_le_bytes().to_vec()),
+            script_pubkeys: hash_inputs(tx, |input| {
+                let mut script = compact_size(input.utxo_pk_script().len());
+                script.extend_from_slice(input.utxo_pk_script());
+                script
+            }),
+        })
+    }
+}
+
+/// The original signature hash of a legacy (non segwit) input, including
+/// P2SH inputs whose script code is the redeem script. Nothing is shared
+/// between inputs so it isn't cached, hashing n inputs is quadratic
+pub fn legacy_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &[u8],
+    sighash_type: u32,
+) -> Result<Vec<u8>, TransactionError> {
+    if input_index >= tx.tx_in_count() {
+        return Err(TransactionError::InputOutOfRange(input_index));
+    }
+
+    let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+    let base_type = sighash_type & 0x1f;
+
+    // SIGHASH_SINGLE without a matching output signs the number one,
+    // a consensus bug kept for compatibility
+    if base_type == SIGHASH_SINGLE && input_index >= tx.tx_out_count() {
+        let mut one = vec![0; 32];
+        one[0] = 1;
+        return Ok(one);
+    }
+
+    let inputs: Vec<usize> = match anyone_can_pay {
+        true => vec![input_index],
+        false => (0..tx.tx_in_count()).collect(),
+    };
+
+    let mut preimage = tx.version().as_u32().to_le_bytes().to_vec();
+
+    preimage.append(&mut compact_size(inputs.len()));
+    for index in inputs {
+        let input = tx
+            .get_input(index)
+            .ok_or(TransactionError::InputOutOfRange(index))?;
+        preimage.append(&mut input.previous_output().serialize());
+
+        // only the signed input carries a script, the others are emptied
+        match index == input_index {
+            true => {
+                preimage.append(&mut compact_size(script_code.len()));
+                preimage.extend_from_slice(script_code);
+            }
+            false => preimage.push(0x00),
+        }
+
+        // other inputs may be updated when their outputs aren't signed
+        let sequence = match index != input_index
+            && (base_type == SIGHASH_NONE || base_type == SIGHASH_SINGLE)
+        {
+            true => 0,
+            false => input.sequence(),
+        };
+        preimage.extend_from_slice(&sequence.to_le_bytes());
+    }
+
+    match base_type {
+        SIGHASH_NONE => preimage.push(0x00),
+        SIGHASH_SINGLE => {
+            preimage.append(&mut compact_size(input_index + 1));
+            // outputs before the signed one are blanked, value -1 and no script
+            for _ in 0..input_index {
+                preimage.extend_from_slice(&(-1i64).to_le_bytes());
+                preimage.push(0x00);
+            }
+            if let Some(output) = tx.get_output(input_index) {
+                preimage.append(&mut output.serialize());
+            }
+        }
+        _ => {
+            preimage.append(&mut compact_size(tx.tx_out_count()));
+            for index in 0..tx.tx_out_count() {
+                if let Some(output) = tx.get_output(index) {
+                    preimage.append(&mut output.serialize());
+                }
+            }
+        }
+    }
+
+    preimage.extend_from_slice(&tx.lock_time().to_consensus_u32().to_le_bytes());
+    preimage.extend_from_slice(&sighash_type.to_le_bytes());
+
+    Ok(sha256_hash_twice(&preimage))
+}
+
+/// sha256 of a field serialized for every input
+fn hash_inputs<F: Fn(&TransactionInput) -> Vec<u8>>(tx: &Transaction, field: F) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for index in 0..tx.tx_in_count() {
+        if let Some(input) = tx.get_input(index) {
+            hasher.update(field(input));
+        }
+    }
+    hasher.finalize().to_vec()
+}
+
+/// sha256 of every serialized output
+fn hash_outputs(tx: &Transaction) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for index in 0..tx.tx_out_count() {
+        if let Some(output) = tx.get_output(index) {
+            hasher.update(output.serialize());
+        }
+    }
+    hasher.finalize().to_vec()
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    fs,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compress_public_key, decrypt, encrypt, trace::REDACTED, EncryptionParams, KdfParams, Key,
+    OsEntropy, SignerError, WalletError,
+};
+
+/// Holds private keys and signs with them on behalf of a [crate::Wallet].
+/// Keys are looked up by public key, so a wallet only needs public data
+/// to use a signer. Signatures are DER encoded without a sighash type
+pub trait KeystoreSigner: Debug + Send + Sync {
+    /// whether the signer holds the private key of a public key
+    fn has_key(&self, public_key: &[u8]) -> bool;
+
+    /// sign a 32 byte digest with the private key of a public key
+    fn sign(&self, public_key: &[u8], digest: &[u8]) -> Result<Vec<u8>, SignerError>;
+}
+
+/// A set of keys held in memory, what a [crate::Wallet] signs with
+/// until its keys are moved out with [crate::Wallet::split_keystore]
+#[derive(Debug, Clone, Default)]
+pub struct MemorySigner {
+    keys: HashMap<Vec<u8>, Key>,
+}
+
+impl MemorySigner {
+    pub fn new(keys: Vec<Key>) -> Result<Self, SignerError> {
+        let mut signer = Self::default();
+        for key in keys {
+            signer.insert(key)?;
+        }
+        Ok(signer)
+    }
+
+    /// add a key, found by its public key in both compressed and uncompressed form
+    pub fn insert(&mut self, key: Key) -> Result<(), SignerError> {
+        let signing_error = |e: crate::KeyError| SignerError::Signing(e.to_string());
+
+        let public_key = key.new_public_key().map_err(signing_error)?;
+        let compressed = compress_public_key(&public_key).map_err(signing_error)?;
+
+        self.keys.insert(compressed, key.clone());
+        self.keys.insert(public_key, key);
+        Ok(())
+    }
+
+    /// every key held, once each
+    pub fn keys(&self) -> Vec<&Key> {
+        let mut keys: Vec<&Key> = vec![];
+        for key in self.keys.values() {
+            if !keys.iter().any(|held| held.bytes() == key.bytes()) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+}
+
+impl KeystoreSigner for MemorySigner {
+    fn has_key(&self, public_key: &[u8]) -> bool {
+        self.keys.contains_key(public_key)
+    }
+
+    fn sign(&self, public_key: &[u8], digest: &[u8]) -> Result<Vec<u8>, SignerError> {
+        self.keys
+            .get(public_key)
+            .ok_or_else(|| SignerError::UnknownKey(hex::encode(public_key)))?
+            .sign_der(digest)
+            .map_err(|e| SignerError::Signing(e.to_string()))
+    }
+}
+
+/// The contents of an [EncryptedFileSigner] file
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct KeystoreFile {
+    encryption: EncryptionParams,
+    /// each sealed key, by hex public key
+    keys: HashMap<String, SealedKey>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct SealedKey {
+    /// the key with its private key wiped
+    key: Key,
+    sealed: Vec<u8>,
+}
+
+/// Keys sealed with a passphrase in their own file, apart from the wallet.
+/// A key is only decrypted for the time it takes to sign with it
+pub struct EncryptedFileSigner {
+    path: PathBuf,
+    file: KeystoreFile,
+    /// the key sealing private keys, only held while unlocked
+    unlock_key: RwLock<Option<[u8; 32]>>,
+}
+
+/// the unlock key is redacted
+impl Debug for EncryptedFileSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedFileSigner")
+            .field("path", &self.path)
+            .field("file", &self.file)
+            .field("unlock_key", &REDACTED)
+            .finish()
+    }
+}
+
+impl EncryptedFileSigner {
+    /// Seal keys with a passphrase and write them to a new keystore file.
+    /// The signer is returned locked
+    pub fn create(path: PathBuf, passphrase: &str, keys: &[&Key]) -> Result<Self, SignerError> {
+        let (encryption, unlock_key) =
+            EncryptionParams::new(passphrase, KdfParams::default(), &OsEntropy)?;
+
+        let mut sealed_keys = HashMap::new();
+        for key in keys.iter() {
+            let public_key = key
+                .new_public_key()
+                .map_err(|e| SignerError::Signing(e.to_string()))?;
+            let sealed = encrypt(&unlock_key, key.bytes(), &OsEntropy)?;
+
+            let mut wiped = (*key).clone();
+            wiped.wipe();
+            let sealed_key = SealedKey { key: wiped, sealed };
+
+            let compressed = compress_public_key(&public_key)
+                .map_err(|e| SignerError::Signing(e.to_string()))?;
+            sealed_keys.insert(hex::encode(&compressed), sealed_key.clone());
+            sealed_keys.insert(hex::encode(&public_key), sealed_key);
+        }
+
+        let signer = Self {
+            path,
+            file: KeystoreFile {
+                encryption,
+                keys: sealed_keys,
+            },
+            unlock_key: RwLock::new(None),
+        };
+        signer.write()?;
+
+        Ok(signer)
+    }
+
+    /// open an existing keystore file, locked
+    pub fn open(path: PathBuf) -> Result<Self, SignerError> {
+        let data = fs::read_to_string(&path).map_err(|e| SignerError::Io(e.to_string()))?;
+        let file = serde_json::from_str(&data).map_err(|e| SignerError::Io(e.to_string()))?;
+
+        Ok(Self {
+            path,
+            file,
+            unlock_key: RwLock::new(None),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// allow signing until [EncryptedFileSigner::lock]
+    pub fn unlock(&self, passphrase: &str) -> Result<(), SignerError> {
+        let key = self.file.encryption.unlock(passphrase)?;
+        *self.unlock_key.write().map_err(|_| SignerError::Poisoned)? = Some(key);
+        Ok(())
+    }
+
+    /// forget the key sealing the private keys
+    pub fn lock(&self) -> Result<(), SignerError> {
+        *self.unlock_key.write().map_err(|_| SignerError::Poisoned)? = None;
+        Ok(())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.unlock_key
+            .read()
+            .map(|key| key.is_none())
+            .unwrap_or(true)
+    }
+
+    fn write(&self) -> Result<(), SignerError> {
+        let data = serde_json::to_string(&self.file).map_err(|e| SignerError::Io(e.to_string()))?;
+        fs::write(&self.path, data).map_err(|e| SignerError::Io(e.to_string()))
+    }
+}
+
+impl KeystoreSigner for EncryptedFileSigner {
+    fn has_key(&self, public_key: &[u8]) -> bool {
+        self.file.keys.contains_key(&hex::encode(public_key))
+    }
+
+    fn sign(&self, public_key: &[u8], digest: &[u8]) -> Result<Vec<u8>, SignerError> {
+        let sealed_key = self
+            .file
+            .keys
+            .get(&hex::encode(public_key))
+            .ok_or_else(|| SignerError::UnknownKey(hex::encode(public_key)))?;
+
+        let unlock_key = self
+            .unlock_key
+            .read()
+            .map_err(|_| SignerError::Poisoned)?
+            .ok_or(SignerError::Locked)?;
+
+        let mut key = sealed_key.key.clone();
+        key.restore(decrypt(&unlock_key, &sealed_key.sealed)?);
+        let signature = key
+            .sign_der(digest)
+            .map_err(|e| SignerError::Signing(e.to_string()));
+        key.wipe();
+
+        signature
+    }
+}
+
+/// The callback an [ExternalSigner] signs through
+type SignFn = dyn Fn(&[u8], &[u8]) -> Result<Vec<u8>, SignerError> + Send + Sync;
+
+/// A signer outside of the process, eg a hardware wallet, reached through
+/// a callback given the public key and digest to sign
+pub struct ExternalSigner {
+    public_keys: Vec<Vec<u8>>,
+    sign: Box<SignFn>,
+}
+
+impl ExternalSigner {
+    /// a signer for the public keys given, signing with the callback
+    pub fn new<F>(public_keys: Vec<Vec<u8>>, sign: F) -> Self
+    where
+        F: Fn(&[u8], &[u8]) -> Result<Vec<u8>, SignerError> + Send + Sync + 'static,
+    {
+        Self {
+            public_keys,
+            sign: Box::new(sign),
+        }
+    }
+}
+
+impl KeystoreSigner for ExternalSigner {
+    fn has_key(&self, public_key: &[u8]) -> bool {
+        self.public_keys.iter().any(|key| key == public_key)
+    }
+
+    fn sign(&self, public_key: &[u8], digest: &[u8]) -> Result<Vec<u8>, SignerError> {
+        if !self.has_key(public_key) {
+            return Err(SignerError::UnknownKey(hex::encode(public_key)));
+        }
+
+        (self.sign)(public_key, digest)
+    }
+}
+
+impl Debug for ExternalSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ExternalSigner({} keys)", self.public_keys.len())
+    }
+}
+
+impl From<WalletError> for SignerError {
+    fn from(error: WalletError) -> Self {
+        match error {
+            WalletError::IncorrectPassphrase => SignerError::IncorrectPassphrase,
+            error => SignerError::Encryption(format!("{:?}", error)),
+        }
+    }
+}
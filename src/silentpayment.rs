@@ -0,0 +1,350 @@
+use std::{fmt, str::FromStr};
+
+use bech32::{FromBase32, ToBase32, Variant};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hash160, tagged_hash, ChildNumber, KeyError, Network, OutPoint, Script, ScriptType,
+    TransactionOutput,
+};
+
+/// the BIP352 purpose, the first level of the paths of the scan and spend keys
+pub const SILENT_PAYMENTS_PURPOSE: u32 = 352;
+
+/// The x coordinate of the point of BIP341 nobody knows the private key of,
+/// taproot inputs spent by a script path with it as internal key have no key
+const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// A BIP352 silent payment address, eg `sp1q...`. Senders derive a new
+/// taproot output from it and the keys of the coins they spend, so payments
+/// to it can't be linked on chain. Made by [crate::Wallet::silent_payment_address]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SilentPaymentAddress {
+    network: Network,
+    /// the compressed public key scanning for payments
+    scan_key: Vec<u8>,
+    /// the compressed public key the payments are spent with
+    spend_key: Vec<u8>,
+}
+
+impl SilentPaymentAddress {
+    pub fn new(network: Network, scan_key: &[u8], spend_key: &[u8]) -> Result<Self, KeyError> {
+        for key in [scan_key, spend_key] {
+            if key.len() != 33 || PublicKey::from_slice(key).is_err() {
+                return Err(KeyError::InvalidFormat);
+            }
+        }
+        Ok(Self {
+            network,
+            scan_key: scan_key.to_vec(),
+            spend_key: spend_key.to_vec(),
+        })
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn scan_key(&self) -> &[u8] {
+        &self.scan_key
+    }
+
+    pub fn spend_key(&self) -> &[u8] {
+        &self.spend_key
+    }
+}
+
+/// bech32m encoded, version 0 followed by the scan and spend keys
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut data = vec![bech32::u5::try_from_u8(0).map_err(|_| fmt::Error)?];
+        data.extend(
+            [self.scan_key.as_slice(), &self.spend_key]
+                .concat()
+                .to_base32(),
+        );
+        let address = bech32::encode(self.network.silent_payment_hrp(), data, Variant::Bech32m)
+            .map_err(|_| fmt::Error)?;
+        write!(f, "{}", address)
+    }
+}
+
+impl FromStr for SilentPaymentAddress {
+    type Err = KeyError;
+
+    /// Read an address of version 0. Addresses of later versions start with
+    /// the same keys and are read as version 0, except version 31 which breaks it
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hrp, data, variant) = bech32::decode(s).map_err(|_| KeyError::Decode)?;
+        let network = [Network::Mainnet, Network::Testnet, Network::Regtest]
+            .iter()
+            .copied()
+            .find(|network| network.silent_payment_hrp() == hrp)
+            .ok_or(KeyError::InvalidFormat)?;
+        let (version, data) = data.split_first().ok_or(KeyError::InvalidFormat)?;
+        let keys = Vec::<u8>::from_base32(data).map_err(|_| KeyError::Decode)?;
+
+        let keys = match (version.to_u8(), keys.len()) {
+            (0, 66) => &keys[..],
+            (1..=30, length) if length >= 66 => &keys[..66],
+            _ => return Err(KeyError::InvalidFormat),
+        };
+        if variant != Variant::Bech32m {
+            return Err(KeyError::InvalidFormat);
+        }
+        Self::new(network, &keys[..33], &keys[33..])
+    }
+}
+
+/// What a receiver needs of an input of a transaction to find silent
+/// payments: the output spent, and the script and witness spending it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SilentPaymentInput {
+    pub previous_output: OutPoint,
+    /// the script of the output spent
+    pub script_pubkey: Vec<u8>,
+    pub script_sig: Vec<u8>,
+    pub witness: Vec<Vec<u8>>,
+}
+
+/// An output paying to the [SilentPaymentAddress] of the wallet, found by
+/// [crate::Wallet::sync]. Spending it takes the spend key tweaked by `tweak`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SilentPaymentOutput {
+    outpoint: OutPoint,
+    output: TransactionOutput,
+    /// the hex encoded scalar the spend key is tweaked by
+    tweak: String,
+    /// the height of the block confirming the output
+    height: Option<u32>,
+    /// the height of the block confirming the transaction spending it
+    #[serde(default)]
+    spent: Option<u32>,
+}
+
+impl SilentPaymentOutput {
+    pub(crate) fn new(
+        outpoint: OutPoint,
+        output: TransactionOutput,
+        tweak: &[u8],
+        height: u32,
+    ) -> Self {
+        Self {
+            outpoint,
+            output,
+            tweak: hex::encode(tweak),
+            height: Some(height),
+            spent: None,
+        }
+    }
+
+    pub fn outpoint(&self) -> &OutPoint {
+        &self.outpoint
+    }
+
+    pub fn output(&self) -> &TransactionOutput {
+        &self.output
+    }
+
+    pub fn value(&self) -> i64 {
+        self.output.value()
+    }
+
+    /// the scalar added to the spend key to spend the output
+    pub fn tweak(&self) -> Vec<u8> {
+        hex::decode(&self.tweak).unwrap_or_default()
+    }
+
+    pub fn height(&self) -> Option<u32> {
+        self.height
+    }
+
+    /// the height of the block spending the output, none while unspent
+    pub fn spent_height(&self) -> Option<u32> {
+        self.spent
+    }
+
+    pub(crate) fn set_spent(&mut self, height: Option<u32>) {
+        self.spent = height;
+    }
+}
+
+/// The sum of the public keys of the inputs of a transaction, multiplied by
+/// the hash of the inputs, as in BIP352. It's all a receiver needs of the
+/// inputs to find the outputs paying to it, so backends and indexes serve
+/// it in place of the inputs, see [crate::Backend::silent_payment_tweaks].
+/// None when the transaction can't hold silent payments: it has no taproot
+/// output, no input with a key or spends a segwit version above 1
+pub fn silent_payment_tweak(
+    inputs: &[SilentPaymentInput],
+    outputs: &[TransactionOutput],
+) -> Option<Vec<u8>> {
+    let taproot = |script: &[u8]| Script::new(script.to_vec()).classify() == ScriptType::P2tr;
+    if !outputs.iter().any(|output| taproot(output.pk_script()))
+        || inputs
+            .iter()
+            .any(|input| is_future_segwit(&input.script_pubkey))
+    {
+        return None;
+    }
+
+    let keys: Vec<PublicKey> = inputs.iter().filter_map(input_public_key).collect();
+    let sum = keys
+        .iter()
+        .skip(1)
+        .try_fold(*keys.first()?, |sum, key| sum.combine(key))
+        // the keys cancel out
+        .ok()?;
+
+    let smallest = inputs
+        .iter()
+        .map(|input| input.previous_output.serialize())
+        .min()?;
+    let input_hash = tagged_hash(
+        "BIP0352/Inputs",
+        &[smallest.as_slice(), &sum.serialize()].concat(),
+    );
+    let mut tweak = sum;
+    tweak
+        .mul_assign(&Secp256k1::verification_only(), &input_hash)
+        .ok()?;
+    Some(tweak.serialize().to_vec())
+}
+
+/// whether a script pays to a witness program of a version above 1, whose
+/// inputs make a transaction ineligible
+fn is_future_segwit(script: &[u8]) -> bool {
+    match script {
+        // OP_2 to OP_16 followed by a push of the whole program
+        [version, length, program @ ..] => {
+            (0x52..=0x60).contains(version)
+                && *length as usize == program.len()
+                && (2..=40).contains(&program.len())
+        }
+        _ => false,
+    }
+}
+
+/// The public key of an input counted by BIP352, only inputs spending
+/// P2TR, P2WPKH, P2SH-P2WPKH and P2PKH outputs with compressed keys have one
+fn input_public_key(input: &SilentPaymentInput) -> Option<PublicKey> {
+    let compressed = |key: &[u8]| match key.len() {
+        33 => PublicKey::from_slice(key).ok(),
+        _ => None,
+    };
+    let script = &input.script_pubkey;
+
+    match Script::new(script.clone()).classify() {
+        ScriptType::P2tr => {
+            let mut witness = input.witness.as_slice();
+            // the annex
+            if witness.len() > 1 && witness.last()?.first() == Some(&0x50) {
+                witness = &witness[..witness.len() - 1];
+            }
+            // the control block of a script path spend names the internal key
+            if witness.len() > 1 && witness.last()?.get(1..33) == Some(&NUMS_H[..]) {
+                return None;
+            }
+            // the output key, implicitly with an even y
+            PublicKey::from_slice(&[&[0x02], &script[2..34]].concat()).ok()
+        }
+        ScriptType::P2wpkh => compressed(input.witness.last()?),
+        ScriptType::P2sh => {
+            // a single push of a version 0 key hash program
+            match input.script_sig.as_slice() {
+                [22, 0x00, 0x14, ..] if input.script_sig.len() == 23 => {
+                    compressed(input.witness.last()?)
+                }
+                _ => None,
+            }
+        }
+        ScriptType::P2pkh => {
+            // the last key the script signature holds hashing to the output,
+            // wherever it is, as the script signature may be malleated
+            let hash = &script[3..23];
+            let script_sig = &input.script_sig;
+            (33..=script_sig.len())
+                .rev()
+                .map(|end| &script_sig[end - 33..end])
+                .find(|key| hash160(&key.to_vec()) == hash)
+                .and_then(compressed)
+        }
+        _ => None,
+    }
+}
+
+/// The private scan key and public spend key of a [SilentPaymentAddress],
+/// derived at `m/352'/coin_type'/0'/1'/0` and `m/352'/coin_type'/0'/0'/0`
+pub(crate) struct SilentPaymentKeys {
+    scan: SecretKey,
+    spend: PublicKey,
+}
+
+impl SilentPaymentKeys {
+    pub(crate) fn new(scan: &[u8], spend: &[u8]) -> Result<Self, KeyError> {
+        let error = |e: secp256k1::Error| KeyError::Other(e.to_string());
+        Ok(Self {
+            scan: SecretKey::from_slice(scan).map_err(error)?,
+            spend: PublicKey::from_slice(spend).map_err(error)?,
+        })
+    }
+
+    /// the path of the scan key, or of the spend key, from the master key
+    pub(crate) fn path(network: Network, scan: bool) -> Vec<ChildNumber> {
+        vec![
+            ChildNumber::Hardened(SILENT_PAYMENTS_PURPOSE),
+            ChildNumber::Hardened(network.coin_type()),
+            ChildNumber::Hardened(0),
+            ChildNumber::Hardened(scan as u32),
+            ChildNumber::Normal(0),
+        ]
+    }
+
+    pub(crate) fn address(&self, network: Network) -> Result<SilentPaymentAddress, KeyError> {
+        let scan = PublicKey::from_secret_key(&Secp256k1::new(), &self.scan);
+        SilentPaymentAddress::new(network, &scan.serialize(), &self.spend.serialize())
+    }
+
+    /// The outputs of a transaction paying to the address, by their index,
+    /// with the tweak of their key. `tweak` is the [silent_payment_tweak] of the
+    /// transaction. A sender numbers the outputs it pays to the address, so
+    /// they're looked for in order until one is missing
+    pub(crate) fn scan(
+        &self,
+        tweak: &[u8],
+        outputs: &[TransactionOutput],
+    ) -> Result<Vec<(usize, Vec<u8>)>, KeyError> {
+        let secp = Secp256k1::verification_only();
+        let error = |e: secp256k1::Error| KeyError::Other(e.to_string());
+        let mut shared_secret = PublicKey::from_slice(tweak).map_err(error)?;
+        shared_secret
+            .mul_assign(&secp, &self.scan[..])
+            .map_err(error)?;
+
+        let mut found = vec![];
+        for k in 0..outputs.len() as u32 {
+            let t_k = tagged_hash(
+                "BIP0352/SharedSecret",
+                &[&shared_secret.serialize()[..], &k.to_be_bytes()].concat(),
+            );
+            let mut key = self.spend;
+            key.add_exp_assign(&secp, &t_k).map_err(error)?;
+            let x_only = &key.serialize()[1..];
+
+            let paid = outputs.iter().position(|output| {
+                let script = output.pk_script();
+                Script::new(script.to_vec()).classify() == ScriptType::P2tr
+                    && &script[2..34] == x_only
+            });
+            match paid {
+                Some(vout) => found.push((vout, t_k)),
+                None => break,
+            }
+        }
+        Ok(found)
+    }
+}
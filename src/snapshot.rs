@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{AccountType, BackendError, Chain, Network, TxRecord, Utxo};
+
+/// A read-only report of a wallet taken by [crate::Wallet::snapshot], for
+/// dashboards and audits. It holds no private key material and changing it
+/// doesn't change the wallet
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WalletSnapshot {
+    pub network: Network,
+    pub locked: bool,
+    pub watch_only: bool,
+    pub accounts: Vec<AccountReport>,
+    /// the addresses handed out by every account, then the imported ones
+    pub addresses: Vec<AddressReport>,
+    pub utxos: Vec<Utxo>,
+    /// transactions of the history not confirmed yet
+    pub pending: Vec<TxRecord>,
+}
+
+/// What a [crate::Wallet::sync] changed, to update a view of the wallet
+/// without reading all of it again
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SyncDiff {
+    /// the height of the tip scanned to
+    pub tip: u32,
+    /// outputs tracked since the sync started
+    pub new_utxos: Vec<Utxo>,
+    /// outputs no longer tracked, spent or rolled back by a reorg
+    pub spent_utxos: Vec<Utxo>,
+    /// transactions of the history that confirmed, or confirmed at another
+    /// height after a reorg, with the height of their block
+    pub confirmations: Vec<(String, u32)>,
+    /// pending transactions of the wallet replaced by another of its
+    /// transactions spending the same coins, that confirmed
+    pub fee_bumps: Vec<FeeBump>,
+    /// how the sum of the outputs tracked changed, in satoshis
+    pub balance_delta: i64,
+    /// the archived transactions relayed again, see [crate::Wallet::rebroadcast_pending]
+    #[serde(default)]
+    pub rebroadcast: Vec<String>,
+}
+
+/// What [crate::Wallet::rebroadcast_pending] did with the archived transactions
+#[derive(Debug, Clone, Default)]
+pub struct Rebroadcast {
+    /// transactions the backend relayed or already knew
+    pub relayed: Vec<String>,
+    /// transactions the backend refused, kept to try again
+    pub failed: Vec<(String, BackendError)>,
+    /// transactions dropped from the archive, they or a transaction
+    /// spending the same coins confirmed
+    pub dropped: Vec<String>,
+}
+
+/// A transaction replaced by one paying a higher fee, see [SyncDiff::fee_bumps]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FeeBump {
+    pub tx_id: String,
+    pub replacement_tx_id: String,
+}
+
+/// The balance and state of an account in a [WalletSnapshot]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AccountReport {
+    pub number: u32,
+    pub account_type: AccountType,
+    /// the derivation path of the account, eg `m/84'/0'/0'`
+    pub path: String,
+    pub archived: bool,
+    /// satoshis held by confirmed outputs
+    pub confirmed: i64,
+    /// satoshis held by outputs still in the mempool
+    pub unconfirmed: i64,
+    pub next_external_index: u32,
+    pub next_internal_index: u32,
+}
+
+/// An address of the wallet and how it's used, in a [WalletSnapshot]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AddressReport {
+    pub address: String,
+    /// the account, chain and index the address is derived at, none for imported keys
+    pub derivation: Option<(u32, Chain, u32)>,
+    pub label: Option<String>,
+    /// the number of unspent outputs paying to the address
+    pub utxos: usize,
+    /// satoshis held by the address
+    pub balance: i64,
+}
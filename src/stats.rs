@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Utxo;
+
+/// how many of the largest unspent outputs [crate::Wallet::stats] lists
+pub const LARGEST_UTXOS: usize = 10;
+
+/// The confirmations bounding the buckets of [WalletStats::utxo_ages]:
+/// unconfirmed, then up to an hour, a day, a week, a month and a year of
+/// blocks, the last bucket holds the older outputs
+pub const UTXO_AGE_BUCKETS: [u32; 6] = [0, 6, 144, 1_008, 4_320, 52_560];
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// The periods the flows of [crate::Wallet::stats] are summed over, in
+/// UTC. Weeks start on mondays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum StatsInterval {
+    Day,
+    Week,
+    Month,
+}
+
+impl StatsInterval {
+    /// the start of the period holding a time, both unix seconds
+    pub fn period_start(&self, time: u64) -> u64 {
+        let days = time / SECONDS_PER_DAY;
+        let first_day = match self {
+            StatsInterval::Day => days,
+            // the epoch was a thursday
+            StatsInterval::Week => days - (days + 3) % 7,
+            StatsInterval::Month => days + 1 - day_of_month(days),
+        };
+        first_day * SECONDS_PER_DAY
+    }
+}
+
+/// the day of the month of a day since the epoch, as in `civil_from_days`
+fn day_of_month(days: u64) -> u64 {
+    let days = days + 719_468;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    day_of_year - (153 * month + 2) / 5 + 1
+}
+
+/// Totals of the history and coins of a wallet taken by [crate::Wallet::stats],
+/// for dashboards. Amounts are in satoshis
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WalletStats {
+    pub interval: StatsInterval,
+    /// the accounts in account number order, then the imported keys when
+    /// they have a history
+    pub accounts: Vec<AccountStats>,
+    /// the fees of the transactions the wallet funded every input of
+    pub fees_paid: i64,
+    /// satoshis per virtual byte over the transactions of known size, see
+    /// [crate::TxRecord::vsize], weighted by their size
+    pub average_fee_rate: Option<f64>,
+    pub utxo_count: usize,
+    /// the unspent outputs by age, a bucket for each of [UTXO_AGE_BUCKETS]
+    /// then one for the older outputs
+    pub utxo_ages: Vec<AgeBucket>,
+    /// the [LARGEST_UTXOS] largest unspent outputs, largest first
+    pub largest_utxos: Vec<Utxo>,
+}
+
+/// What went in and out of an account, or of the imported keys. Each
+/// transaction counts as received or sent by what it changed the balance
+/// of the account by, so change isn't counted and sent amounts include fees
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AccountStats {
+    /// none for the imported keys
+    pub account: Option<u32>,
+    pub received: i64,
+    pub sent: i64,
+    pub transactions: usize,
+    /// the totals of each period with transactions, oldest first
+    pub periods: Vec<PeriodStats>,
+}
+
+/// The flows of an account over one [StatsInterval]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PeriodStats {
+    /// the start of the period, unix seconds. None for the transactions
+    /// without a block time, eg those still in the mempool
+    pub start: Option<u64>,
+    pub received: i64,
+    pub sent: i64,
+    pub transactions: usize,
+}
+
+impl AccountStats {
+    /// count a transaction changing the balance of the account by `net`
+    pub(crate) fn add(&mut self, start: Option<u64>, net: i64) {
+        let position = match self.periods.iter().position(|period| period.start == start) {
+            Some(position) => position,
+            None => {
+                self.periods.push(PeriodStats {
+                    start,
+                    ..PeriodStats::default()
+                });
+                self.periods.len() - 1
+            }
+        };
+        let period = &mut self.periods[position];
+        period.received += net.max(0);
+        period.sent += (-net).max(0);
+        period.transactions += 1;
+
+        self.received += net.max(0);
+        self.sent += (-net).max(0);
+        self.transactions += 1;
+    }
+}
+
+/// The unspent outputs of an age in [WalletStats::utxo_ages]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AgeBucket {
+    /// the most confirmations of the outputs of the bucket, none for the oldest
+    pub max_confirmations: Option<u32>,
+    pub count: usize,
+    pub value: i64,
+}
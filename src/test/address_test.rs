@@ -0,0 +1,147 @@
+use bech32::{ToBase32, Variant};
+
+use crate::{
+    validate_address, validate_addresses, AddressProblem, AddressType, Network, Script, Wallet,
+};
+
+/// the address of a pk script of a network
+fn address(script: Vec<u8>, network: Network) -> String {
+    Script::from(script).to_address(network).unwrap()
+}
+
+#[test]
+pub fn test_validate_address() {
+    let p2pkh = "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2";
+    let valid = validate_address(p2pkh, Network::Mainnet);
+    assert!(valid.is_valid());
+    assert_eq!(Some(Network::Mainnet), valid.network);
+    assert_eq!(Some(AddressType::P2pkh), valid.address_type);
+    assert_eq!(
+        Some(p2pkh.to_string()),
+        valid.script.unwrap().to_address(Network::Mainnet)
+    );
+
+    let cases = [
+        (vec![0xa9, 0x14], 20, AddressType::P2sh),
+        (vec![0x00, 0x14], 20, AddressType::P2wpkh),
+        (vec![0x00, 0x20], 32, AddressType::P2wsh),
+        (vec![0x51, 0x20], 32, AddressType::P2tr),
+        (
+            vec![0x52, 0x10],
+            16,
+            AddressType::UnknownWitness { version: 2 },
+        ),
+    ];
+    for (prefix, length, address_type) in cases {
+        let mut script = [prefix, vec![7; length]].concat();
+        if address_type == AddressType::P2sh {
+            script.push(0x87);
+        }
+        let found = validate_address(&address(script.clone(), Network::Testnet), Network::Testnet);
+        assert!(found.is_valid(), "{:?}", found);
+        assert_eq!(Some(address_type), found.address_type);
+        assert_eq!(Some(Script::from(script)), found.script);
+    }
+
+    // segwit addresses may be written in upper case
+    let segwit = address([vec![0x00, 0x14], vec![7; 20]].concat(), Network::Mainnet);
+    assert!(validate_address(&segwit.to_uppercase(), Network::Mainnet).is_valid());
+}
+
+#[test]
+pub fn test_address_problems() {
+    let segwit = address([vec![0x00, 0x14], vec![7; 20]].concat(), Network::Mainnet);
+
+    // a typo breaks the checksum
+    let typo = |address: &str, at: usize| {
+        let mut chars: Vec<char> = address.chars().collect();
+        chars[at] = match chars[at] {
+            'q' => 'p',
+            _ => 'q',
+        };
+        chars.into_iter().collect::<String>()
+    };
+    let problem = |address: &str, network| validate_address(address, network).problem;
+    assert_eq!(
+        Some(AddressProblem::Checksum),
+        problem(&typo(&segwit, 10), Network::Mainnet)
+    );
+    assert_eq!(
+        Some(AddressProblem::Checksum),
+        problem(
+            &typo("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", 5),
+            Network::Mainnet
+        )
+    );
+    assert_eq!(
+        Some(AddressProblem::Encoding),
+        problem("not an address", Network::Mainnet)
+    );
+    assert_eq!(
+        Some(AddressProblem::Encoding),
+        problem(&segwit.replacen("bc1q", "bc1Q", 1), Network::Mainnet)
+    );
+
+    // the network is reported along with the mismatch, without a script to pay to
+    let found = validate_address(&segwit, Network::Testnet);
+    assert_eq!(
+        Some(AddressProblem::WrongNetwork(Network::Mainnet)),
+        found.problem
+    );
+    assert_eq!(Some(Network::Mainnet), found.network);
+    assert!(found.script.is_none());
+    assert_eq!(
+        Some(AddressProblem::WrongNetwork(Network::Testnet)),
+        problem(
+            &address([vec![0x00, 0x14], vec![7; 20]].concat(), Network::Testnet),
+            Network::Regtest
+        )
+    );
+
+    // base58 addresses of regtest are those of testnet
+    let testnet = address(
+        [vec![0x76, 0xa9, 0x14], vec![7; 20], vec![0x88, 0xac]].concat(),
+        Network::Testnet,
+    );
+    let regtest = validate_address(&testnet, Network::Regtest);
+    assert!(regtest.is_valid());
+    assert_eq!(Some(Network::Regtest), regtest.network);
+
+    // a v0 program must be bech32, not bech32m
+    let mut data = vec![bech32::u5::try_from_u8(0).unwrap()];
+    data.extend(vec![7u8; 20].to_base32());
+    let bech32m = bech32::encode("bc", data, Variant::Bech32m).unwrap();
+    let found = validate_address(&bech32m, Network::Mainnet);
+    assert!(matches!(found.problem, Some(AddressProblem::Payload(_))));
+    assert_eq!(Some(Network::Mainnet), found.network);
+
+    // a base58 payload of an unknown version
+    let unknown = crate::base58check_encode(&[vec![0x30], vec![7; 20]].concat());
+    assert!(matches!(
+        problem(&unknown, Network::Mainnet),
+        Some(AddressProblem::Payload(_))
+    ));
+}
+
+#[test]
+pub fn test_validate_addresses() {
+    let valid = address([vec![0x00, 0x14], vec![7; 20]].concat(), Network::Mainnet);
+    let found = validate_addresses(vec![valid.as_str(), "", "1111"], Network::Mainnet);
+
+    assert_eq!(3, found.len());
+    assert_eq!(valid, found[0].address);
+    assert!(found[0].is_valid());
+    assert!(!found[1].is_valid());
+    assert!(!found[2].is_valid());
+    assert_eq!(
+        "the checksum doesn't match, check for typos",
+        AddressProblem::Checksum.to_string()
+    );
+
+    // a wallet checks against its own network
+    let wallet = Wallet::new(Network::Testnet, std::env::temp_dir(), true, false);
+    assert_eq!(
+        Some(AddressProblem::WrongNetwork(Network::Mainnet)),
+        wallet.validate_addresses([valid]).remove(0).problem
+    );
+}
@@ -0,0 +1,83 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Backend, BackendError, MultiBroadcaster};
+
+/// a backend answering broadcasts after a delay, keeping what it was sent
+struct Relay {
+    answer: Result<String, BackendError>,
+    delay: Duration,
+    received: Arc<Mutex<Vec<String>>>,
+}
+
+impl Backend for Relay {
+    fn broadcast(&self, raw_tx: &str) -> Result<String, BackendError> {
+        thread::sleep(self.delay);
+        self.received.lock().unwrap().push(raw_tx.to_string());
+        self.answer.clone()
+    }
+}
+
+fn relay(
+    answer: Result<String, BackendError>,
+    delay: Duration,
+    received: &Arc<Mutex<Vec<String>>>,
+) -> Box<dyn Backend + Send + Sync> {
+    Box::new(Relay {
+        answer,
+        delay,
+        received: received.clone(),
+    })
+}
+
+#[test]
+fn test_multi_broadcast() {
+    let received = Arc::new(Mutex::new(vec![]));
+    let delay = Duration::from_millis(200);
+    let mut broadcaster = MultiBroadcaster::new();
+    broadcaster
+        .add_backend(
+            "censoring",
+            relay(
+                Err(BackendError::Rpc {
+                    code: -26,
+                    message: "non-mandatory-script-verify-flag".to_string(),
+                }),
+                delay,
+                &received,
+            ),
+        )
+        .add_backend(
+            "offline",
+            relay(
+                Err(BackendError::Connection("connection refused".to_string())),
+                delay,
+                &received,
+            ),
+        )
+        .add_backend("node", relay(Ok("aa".repeat(32)), delay, &received));
+    assert_eq!(3, broadcaster.len());
+
+    // the backends are sent the transaction at once
+    let start = Instant::now();
+    let report = broadcaster.broadcast_raw("00");
+    assert!(start.elapsed() < delay * 3);
+    assert_eq!(vec!["00"; 3], *received.lock().unwrap());
+
+    assert!(report.is_success());
+    assert_eq!(Some("aa".repeat(32).as_str()), report.txid());
+    assert_eq!(vec!["node"], report.accepted());
+    let failed: Vec<&str> = report.failed().iter().map(|(name, _)| *name).collect();
+    assert_eq!(vec!["censoring", "offline"], failed);
+    assert!(matches!(
+        report.failed()[0].1,
+        BackendError::Rpc { code: -26, .. }
+    ));
+
+    let report = MultiBroadcaster::new().broadcast_raw("00");
+    assert!(!report.is_success());
+    assert!(report.txid().is_none());
+}
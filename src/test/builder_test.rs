@@ -0,0 +1,451 @@
+#![allow(unused_imports)]
+
+use std::{sync::Arc, time::Duration};
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    AddressProblem, FeeLimits, LockTime, MockClock, Network, Recipient, RecipientProblem, Script,
+    SeededEntropy, Transaction, TransactionBuilder, TransactionError, TransactionInput,
+    TransactionOutput, TransactionType, TxOrdering, LOCKTIME_THRESHOLD, MAX_STANDARD_TX_WEIGHT,
+};
+
+fn input(tx_id: &str, index: i32) -> TransactionInput {
+    let utxo = TransactionOutput::from_script(1000, vec![]);
+    TransactionInput::new(utxo, tx_id.repeat(32), index)
+}
+
+fn output(value: i64, script: &str) -> TransactionOutput {
+    TransactionOutput::from_script(value, hex::decode(script).unwrap())
+}
+
+fn builder() -> TransactionBuilder {
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2PubKeyHash);
+    builder
+        .add_input(input("ff", 0))
+        .add_input(input("0a", 1))
+        .add_input(input("0a", 0))
+        .add_input(input("10", 3))
+        .add_output(output(500, "76"))
+        .add_output(output(100, "a9"))
+        .add_output(output(100, "00"))
+        .add_output(output(400, "51"));
+    builder
+}
+
+fn outpoints(tx: &Transaction) -> Vec<(String, i32)> {
+    tx.inputs()
+        .iter()
+        .map(|input| {
+            let outpoint = input.previous_output();
+            (outpoint.hash()[..2].to_string(), outpoint.index())
+        })
+        .collect()
+}
+
+fn values(tx: &Transaction) -> Vec<i64> {
+    tx.outputs().iter().map(|output| output.value()).collect()
+}
+
+#[test]
+pub fn test_insertion_ordering() {
+    let tx = builder().ordering(TxOrdering::Insertion).build().unwrap();
+
+    assert_eq!(
+        vec![
+            ("ff".to_string(), 0),
+            ("0a".to_string(), 1),
+            ("0a".to_string(), 0),
+            ("10".to_string(), 3)
+        ],
+        outpoints(&tx)
+    );
+    assert_eq!(vec![500, 100, 100, 400], values(&tx));
+}
+
+#[test]
+pub fn test_bip69_ordering() {
+    let tx = builder().ordering(TxOrdering::Bip69).build().unwrap();
+
+    assert_eq!(
+        vec![
+            ("0a".to_string(), 0),
+            ("0a".to_string(), 1),
+            ("10".to_string(), 3),
+            ("ff".to_string(), 0)
+        ],
+        outpoints(&tx)
+    );
+    assert_eq!(vec![100, 100, 400, 500], values(&tx));
+    // equal amounts are ordered by their pk script
+    assert_eq!(&[0x00], tx.get_output(0).unwrap().pk_script());
+}
+
+#[test]
+pub fn test_shuffle_ordering() {
+    let builder = builder();
+
+    let tx = builder
+        .build_with_rng(&mut StdRng::seed_from_u64(7))
+        .unwrap();
+    let same_seed = builder
+        .build_with_rng(&mut StdRng::seed_from_u64(7))
+        .unwrap();
+    assert_eq!(outpoints(&tx), outpoints(&same_seed));
+    assert_eq!(values(&tx), values(&same_seed));
+
+    // shuffling only reorders
+    let mut sorted = values(&tx);
+    sorted.sort_unstable();
+    assert_eq!(vec![100, 100, 400, 500], sorted);
+    assert_eq!(4, outpoints(&tx).len());
+}
+
+#[test]
+pub fn test_shuffle_with_entropy() {
+    // a seeded source gives the same order on every build
+    let build = |seed: u64| {
+        builder()
+            .entropy(Arc::new(SeededEntropy::new(seed)))
+            .build()
+            .unwrap()
+    };
+    let tx = build(7);
+    assert_eq!(outpoints(&tx), outpoints(&build(7)));
+    assert_eq!(values(&tx), values(&build(7)));
+    assert!((8..40).any(|seed| outpoints(&build(seed)) != outpoints(&tx)));
+}
+
+#[test]
+pub fn test_lock_time_consensus() {
+    assert_eq!(LockTime::Blocks(0), LockTime::default());
+    assert_eq!(
+        LockTime::Blocks(LOCKTIME_THRESHOLD - 1),
+        LockTime::from_consensus(LOCKTIME_THRESHOLD - 1)
+    );
+    assert_eq!(
+        LockTime::Seconds(LOCKTIME_THRESHOLD),
+        LockTime::from_consensus(LOCKTIME_THRESHOLD)
+    );
+    for lock_time in [0, 800_000, LOCKTIME_THRESHOLD, u32::MAX] {
+        assert_eq!(
+            lock_time,
+            LockTime::from_consensus(lock_time).to_consensus_u32()
+        );
+    }
+
+    // serialized little endian as the last four bytes
+    let tx = builder()
+        .ordering(TxOrdering::Insertion)
+        .lock_time(LockTime::Blocks(0x01020304))
+        .build()
+        .unwrap();
+    assert_eq!(
+        &[0x04, 0x03, 0x02, 0x01],
+        &tx.serialize()[tx.serialize().len() - 4..]
+    );
+    assert!(tx.to_hex().ends_with("04030201"));
+}
+
+#[test]
+pub fn test_lock_time_after() {
+    let clock = Arc::new(MockClock::new(1_700_000_000));
+
+    let mut builder = builder();
+    builder
+        .clock(clock.clone())
+        .lock_time_after(Duration::from_secs(3600));
+    let tx = builder.build().unwrap();
+    assert_eq!(LockTime::Seconds(1_700_003_600), tx.lock_time());
+    // the lock time only applies once an input isn't final
+    assert!(tx.is_final(1, clock.as_ref()));
+
+    let mut locked = input("aa", 0);
+    locked.set_sequence(0xfffffffe);
+    let mut tx = builder.add_input(locked).build().unwrap();
+    assert!(!tx.is_final(1, clock.as_ref()));
+
+    clock.advance(3600);
+    assert!(!tx.is_final(1, clock.as_ref()));
+    clock.advance(1);
+    assert!(tx.is_final(1, clock.as_ref()));
+
+    // heights are compared against the block the transaction would be mined in
+    tx.set_lock_time(LockTime::Blocks(800_000));
+    assert!(!tx.is_final(800_000, clock.as_ref()));
+    assert!(tx.is_final(800_001, clock.as_ref()));
+}
+
+#[test]
+pub fn test_anti_fee_snipe() {
+    let mut builder = builder();
+    builder.anti_fee_snipe(true);
+    assert!(matches!(
+        builder.build(),
+        Err(TransactionError::UnknownTipHeight)
+    ));
+
+    builder.tip_height(800_000);
+    let mut heights = vec![];
+    for seed in 0..50 {
+        let tx = builder
+            .build_with_rng(&mut StdRng::seed_from_u64(seed))
+            .unwrap();
+        let height = match tx.lock_time() {
+            LockTime::Blocks(height) => height,
+            lock_time => panic!("locked to {:?}", lock_time),
+        };
+        assert!((800_000 - 99..=800_000).contains(&height));
+        heights.push(height);
+        // final inputs are made to enforce the lock time
+        assert!(tx
+            .inputs()
+            .iter()
+            .all(|input| input.sequence() == 0xfffffffe));
+    }
+    // mostly the tip, now and then further back
+    assert!(heights.iter().filter(|&&height| height == 800_000).count() > 30);
+    assert!(heights.iter().any(|&height| height < 800_000));
+
+    // a lock time set explicitly is kept
+    let tx = builder.lock_time(LockTime::Blocks(1)).build().unwrap();
+    assert_eq!(LockTime::Blocks(1), tx.lock_time());
+    assert!(tx
+        .inputs()
+        .iter()
+        .all(|input| input.sequence() == 0xffffffff));
+}
+
+#[test]
+pub fn test_fee_limits() {
+    // 2900 satoshis of fee for a 642 vbyte transaction, 4.5 satoshis per vbyte
+    let mut builder = builder();
+    assert_eq!(2900, builder.build().unwrap().fee());
+
+    builder.fee_limits(FeeLimits {
+        max_fee_rate: None,
+        max_absolute_fee: Some(2000),
+    });
+    assert!(matches!(
+        builder.build(),
+        Err(TransactionError::AbsurdFee {
+            fee: 2900,
+            limit: 2000
+        })
+    ));
+
+    builder.fee_limits(FeeLimits {
+        max_fee_rate: Some(4),
+        max_absolute_fee: None,
+    });
+    assert!(matches!(
+        builder.build(),
+        Err(TransactionError::AbsurdFeeRate { rate: 4, limit: 4 })
+    ));
+    builder.fee_limits(FeeLimits {
+        max_fee_rate: Some(5),
+        max_absolute_fee: None,
+    });
+    assert!(builder.build().is_ok());
+
+    // an amount in bitcoins read as satoshis
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2PubKeyHash);
+    builder
+        .add_input(TransactionInput::new(
+            TransactionOutput::from_script(50_000_000, vec![]),
+            "ff".repeat(32),
+            0,
+        ))
+        .add_output(output(1, "76"));
+    assert!(matches!(
+        builder.build(),
+        Err(TransactionError::AbsurdFee { .. })
+    ));
+    assert!(builder.fee_limits(FeeLimits::none()).build().is_ok());
+}
+
+/// a P2WPKH output script of a key hash of one repeated byte
+fn p2wpkh(byte: u8) -> Vec<u8> {
+    [vec![0x00, 0x14], vec![byte; 20]].concat()
+}
+
+fn coin(value: i64, byte: u8) -> TransactionInput {
+    let utxo = TransactionOutput::from_script(value, p2wpkh(byte));
+    TransactionInput::new(utxo, format!("{:02x}", byte).repeat(32), 0)
+}
+
+#[test]
+pub fn test_add_recipients() {
+    let address =
+        |network: Network, byte: u8| Script::new(p2wpkh(byte)).to_address(network).unwrap();
+    let mut typo = address(Network::Mainnet, 2);
+    let last = typo.pop().unwrap();
+    typo.push(if last == 'q' { 'p' } else { 'q' });
+
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder.ordering(TxOrdering::Insertion);
+    let rejected = builder.add_recipients(
+        vec![
+            (address(Network::Mainnet, 1), 10_000),
+            (address(Network::Testnet, 3), 10_000),
+            (typo.clone(), 10_000),
+            (address(Network::Mainnet, 1), 0),
+            (address(Network::Mainnet, 1), 545),
+            (address(Network::Mainnet, 4), 546),
+        ],
+        Network::Mainnet,
+    );
+
+    // every recipient is checked, the valid ones are paid in order
+    assert_eq!(
+        vec![
+            (
+                1,
+                RecipientProblem::Address(AddressProblem::WrongNetwork(Network::Testnet))
+            ),
+            (2, RecipientProblem::Address(AddressProblem::Checksum)),
+            (3, RecipientProblem::InvalidAmount(0)),
+            (4, RecipientProblem::Dust(545)),
+        ],
+        rejected
+            .iter()
+            .map(|recipient| (recipient.index, recipient.problem.clone()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(typo, rejected[1].address);
+    assert_eq!(10_000, rejected[1].amount);
+
+    let tx = builder.add_input(coin(20_000, 9)).build().unwrap();
+    assert_eq!(vec![10_000, 546], values(&tx));
+    assert_eq!(p2wpkh(1), tx.get_output(0).unwrap().pk_script());
+    assert_eq!(p2wpkh(4), tx.get_output(1).unwrap().pk_script());
+}
+
+#[test]
+pub fn test_subtract_fee() {
+    let address = |byte: u8| {
+        Script::new(p2wpkh(byte))
+            .to_address(Network::Mainnet)
+            .unwrap()
+    };
+    let recipients = || {
+        vec![
+            Recipient::new(&address(1), 10_000).subtracting_fee(),
+            Recipient::new(&address(2), 20_000),
+            Recipient::new(&address(3), 1_000).subtracting_fee(),
+        ]
+    };
+
+    // the fee is split between the recipients paying it, the first taking what's left over
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder.ordering(TxOrdering::Insertion);
+    assert!(builder
+        .add_recipients(recipients(), Network::Mainnet)
+        .is_empty());
+    assert!(builder.subtracts_fee());
+    builder.subtract_fee(401).unwrap();
+    let tx = builder.add_input(coin(40_000, 9)).build().unwrap();
+    assert_eq!(vec![9_799, 20_000, 800], values(&tx));
+
+    // none can be left under the dust limit
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder.add_recipients(recipients(), Network::Mainnet);
+    assert!(matches!(
+        builder.subtract_fee(1_000),
+        Err(TransactionError::FeeExceedsRecipients(1_000))
+    ));
+    let tx = builder.add_input(coin(40_000, 9)).build().unwrap();
+    assert_eq!(31_000, tx.outputs().iter().map(|o| o.value()).sum::<i64>());
+
+    // nor the fee taken from no one
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder.add_recipients(vec![(address(1), 10_000)], Network::Mainnet);
+    assert!(!builder.subtracts_fee());
+    assert!(matches!(
+        builder.subtract_fee(100),
+        Err(TransactionError::FeeExceedsRecipients(100))
+    ));
+}
+
+#[test]
+pub fn test_build_batches() {
+    let change = p2wpkh(0xcc);
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder
+        .ordering(TxOrdering::Insertion)
+        .add_input(coin(100_000, 1))
+        .add_input(coin(100_000, 2))
+        .add_input(coin(100_000, 3));
+    for byte in 0..3 {
+        builder.add_output(TransactionOutput::from_script(40_000, p2wpkh(byte)));
+    }
+
+    // the inputs needed are spent, in order, and the rest goes to the change
+    let batches = builder.build_batches(2, &change).unwrap();
+    assert_eq!(1, batches.len());
+    let batch = &batches[0];
+    assert_eq!((3, 120_000), (batch.recipients, batch.amount));
+    assert_eq!(
+        vec![("01".to_string(), 0), ("02".to_string(), 0)],
+        outpoints(&batch.tx)
+    );
+    assert_eq!(4, batch.tx.tx_out_count());
+    assert_eq!(change, batch.tx.get_output(3).unwrap().pk_script());
+    assert_eq!(batch.vsize as i64 * 2, batch.fee);
+    assert_eq!(batch.fee, batch.tx.fee());
+
+    // change that would be dust goes to the fee
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder
+        .add_input(coin(40_600, 1))
+        .add_output(TransactionOutput::from_script(40_000, p2wpkh(0)));
+    let batches = builder.build_batches(2, &change).unwrap();
+    assert_eq!(1, batches[0].tx.tx_out_count());
+    assert_eq!(600, batches[0].fee);
+
+    assert!(matches!(
+        builder.add_output(TransactionOutput::from_script(1_000, p2wpkh(1))).build_batches(2, &change),
+        Err(TransactionError::InsufficientFunds(missing)) if missing > 0
+    ));
+    assert!(
+        TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash)
+            .build_batches(2, &change)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+pub fn test_build_batches_split() {
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder.ordering(TxOrdering::Insertion);
+    for byte in 1..=5 {
+        builder.add_input(coin(20_000_000, byte));
+    }
+    let amounts: Vec<i64> = (0..7000).map(|index| 1_000 + index).collect();
+    for amount in amounts.iter() {
+        builder.add_output(TransactionOutput::from_script(*amount, p2wpkh(0)));
+    }
+
+    // too many outputs for one standard transaction
+    let batches = builder.build_batches(3, &p2wpkh(0xcc)).unwrap();
+    assert_eq!(3, batches.len());
+    for (batch, byte) in batches.iter().zip(["01", "02", "03"]) {
+        assert!(batch.tx.weight() <= MAX_STANDARD_TX_WEIGHT);
+        assert!(batch.vsize * 4 <= MAX_STANDARD_TX_WEIGHT);
+        assert_eq!(vec![(byte.to_string(), 0)], outpoints(&batch.tx));
+        assert_eq!(batch.vsize as i64 * 3, batch.fee);
+        assert_eq!(batch.recipients + 1, batch.tx.tx_out_count());
+    }
+
+    // every output is paid once, in order
+    let paid: Vec<i64> = batches
+        .iter()
+        .flat_map(|batch| values(&batch.tx)[..batch.recipients].to_vec())
+        .collect();
+    assert_eq!(amounts, paid);
+    assert_eq!(
+        amounts.iter().sum::<i64>(),
+        batches.iter().map(|batch| batch.amount).sum::<i64>()
+    );
+}
@@ -0,0 +1,298 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    calibrate_kdf, AccountType, CacheFormat, Chain, KdfParams, Network, OsEntropy, OutPoint,
+    TransactionOutput, Utxo, Wallet, WalletConfig, CACHE_FILE_NAME, COMPACT_CACHE_FILE_NAME,
+    MAX_KDF_LOG_N, MIN_KDF_LOG_N, WALLET_FILE_NAME,
+};
+
+fn platform(os: &str, vars: &[(&str, &str)]) -> Option<WalletConfig> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    WalletConfig::platform_with(os, |name| vars.get(name).cloned()).ok()
+}
+
+#[test]
+pub fn test_platform_dirs() {
+    let linux = platform("linux", &[("HOME", "/home/satoshi")]).unwrap();
+    assert_eq!(
+        &PathBuf::from("/home/satoshi/.local/share/waller"),
+        linux.data_dir()
+    );
+    assert_eq!(
+        &PathBuf::from("/home/satoshi/.cache/waller"),
+        linux.cache_dir()
+    );
+    assert_eq!(
+        PathBuf::from("/home/satoshi/.local/share/waller").join(WALLET_FILE_NAME),
+        linux.wallet_file()
+    );
+
+    // XDG directories win over the home directory, unless they're relative
+    let xdg = platform(
+        "linux",
+        &[
+            ("HOME", "/home/satoshi"),
+            ("XDG_DATA_HOME", "/data"),
+            ("XDG_CACHE_HOME", "cache"),
+        ],
+    )
+    .unwrap();
+    assert_eq!(&PathBuf::from("/data/waller"), xdg.data_dir());
+    assert_eq!(
+        &PathBuf::from("/home/satoshi/.cache/waller"),
+        xdg.cache_dir()
+    );
+
+    let macos = platform("macos", &[("HOME", "/Users/satoshi")]).unwrap();
+    assert_eq!(
+        &PathBuf::from("/Users/satoshi/Library/Application Support/waller"),
+        macos.data_dir()
+    );
+    assert_eq!(
+        &PathBuf::from("/Users/satoshi/Library/Caches/waller"),
+        macos.cache_dir()
+    );
+
+    assert!(platform("linux", &[]).is_none());
+    assert!(platform("macos", &[("HOME", "relative")]).is_none());
+
+    // overriding keeps the other directory
+    let config = linux.with_cache_dir(PathBuf::from("/var/cache/waller"));
+    assert_eq!(linux.data_dir(), config.data_dir());
+    assert_eq!(
+        PathBuf::from("/var/cache/waller").join(CACHE_FILE_NAME),
+        config.cache_file()
+    );
+}
+
+#[test]
+pub fn test_cache_dir() {
+    let root = std::env::temp_dir().join("waller_test_cache_dir");
+    let _ = std::fs::remove_dir_all(&root);
+    let config = WalletConfig::new(root.join("data")).with_cache_dir(root.join("cache"));
+
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        config.clone(),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    let output = TransactionOutput::from_script(5_000, vec![0x00, 0x14]);
+    wallet
+        .add_utxo(Utxo::new(
+            OutPoint::new("11".repeat(32), 0),
+            output,
+            address.clone(),
+        ))
+        .unwrap();
+
+    // the directories are made on flush, the utxos only go to the cache
+    let file = wallet.flush().unwrap();
+    assert_eq!(config.wallet_file(), file);
+    assert!(config.cache_file().is_file());
+    let data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert!(data.get("utxos").is_none());
+
+    let loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert_eq!(&config, loaded.config());
+    assert_eq!(5_000, loaded.account_balance(account));
+
+    // a lost cache only loses what a rescan finds again
+    std::fs::remove_file(config.cache_file()).unwrap();
+    let loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert!(loaded.utxos().is_empty());
+    assert_eq!(
+        address,
+        loaded.verify_address(account, Chain::External, 0).unwrap()
+    );
+
+    // moving the data directory leaves a cache kept apart where it is
+    let mut moved = loaded.clone();
+    moved.set_path(root.join("moved"));
+    assert_eq!(&root.join("moved"), moved.path());
+    assert_eq!(config.cache_dir(), moved.config().cache_dir());
+}
+
+#[test]
+pub fn test_legacy_wallet_file() {
+    let path = std::env::temp_dir().join("waller_test_legacy_wallet_file");
+    let _ = std::fs::remove_dir_all(&path);
+
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path.clone(),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    wallet
+        .add_utxo(Utxo::new(
+            OutPoint::new("22".repeat(32), 1),
+            TransactionOutput::from_script(7_000, vec![0x00, 0x14]),
+            address,
+        ))
+        .unwrap();
+    let file = wallet.flush().unwrap();
+
+    // older versions wrote a single path and kept the utxos in the wallet file
+    let mut data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    let cache: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(wallet.config().cache_file()).unwrap())
+            .unwrap();
+    let legacy = data.as_object_mut().unwrap();
+    legacy.remove("checksum");
+    legacy.remove("config");
+    legacy.insert("path".to_string(), path.to_str().unwrap().into());
+    legacy.insert("utxos".to_string(), cache["utxos"].clone());
+    std::fs::write(&file, data.to_string()).unwrap();
+    std::fs::remove_file(wallet.config().cache_file()).unwrap();
+
+    let loaded = Wallet::from_wallet_file(file).unwrap();
+    assert_eq!(&WalletConfig::new(path), loaded.config());
+    assert_eq!(7_000, loaded.account_balance(account));
+}
+
+#[test]
+pub fn test_cache_format() {
+    let path = std::env::temp_dir().join("waller_test_cache_format");
+    let _ = std::fs::remove_dir_all(&path);
+
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path.clone(),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    wallet
+        .add_utxo(Utxo::new(
+            OutPoint::new("33".repeat(32), 0),
+            TransactionOutput::from_script(9_000, vec![0x00, 0x14]),
+            address,
+        ))
+        .unwrap();
+    let file = wallet.flush().unwrap();
+    assert_eq!(CacheFormat::Json, wallet.config().cache_format());
+    assert!(path.join(CACHE_FILE_NAME).is_file());
+
+    // older files don't name a format
+    let config: WalletConfig = serde_json::from_value(serde_json::json!({
+        "data_dir": path,
+        "cache_dir": path,
+    }))
+    .unwrap();
+    assert_eq!(CacheFormat::Json, config.cache_format());
+
+    let config = wallet.config().with_cache_format(CacheFormat::Cbor);
+    assert_eq!(path.join(COMPACT_CACHE_FILE_NAME), config.cache_file());
+    wallet.set_config(config);
+
+    #[cfg(feature = "compact-cache")]
+    {
+        // the json cache is replaced by a smaller one
+        let json = std::fs::metadata(path.join(CACHE_FILE_NAME)).unwrap().len();
+        wallet.flush().unwrap();
+        assert!(!path.join(CACHE_FILE_NAME).exists());
+        assert!(
+            std::fs::metadata(path.join(COMPACT_CACHE_FILE_NAME))
+                .unwrap()
+                .len()
+                < json
+        );
+
+        let loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+        assert_eq!(CacheFormat::Cbor, loaded.config().cache_format());
+        assert_eq!(9_000, loaded.account_balance(account));
+        let (recovered, report) = Wallet::from_wallet_file_lenient(file).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(9_000, recovered.account_balance(account));
+    }
+
+    #[cfg(not(feature = "compact-cache"))]
+    {
+        assert!(matches!(wallet.flush(), Err(crate::WalletError::Write(_))));
+        // nothing was written, the json cache is kept
+        assert!(path.join(CACHE_FILE_NAME).is_file());
+        assert_eq!(
+            9_000,
+            Wallet::from_wallet_file(file)
+                .unwrap()
+                .account_balance(account)
+        );
+    }
+}
+
+#[test]
+pub fn test_kdf_params() {
+    let path = std::env::temp_dir().join("waller_test_kdf_params");
+    let _ = std::fs::remove_dir_all(&path);
+
+    assert!(KdfParams::new(64, 8, 1).is_err());
+    let cheap = KdfParams::new(MIN_KDF_LOG_N, 8, 1).unwrap();
+    assert_eq!(15, WalletConfig::new(path.clone()).kdf().log_n());
+
+    // older files don't name parameters
+    let config: WalletConfig = serde_json::from_value(serde_json::json!({
+        "data_dir": path,
+        "cache_dir": path,
+    }))
+    .unwrap();
+    assert_eq!(KdfParams::default(), config.kdf());
+
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path.clone(),
+        false,
+    )
+    .unwrap();
+    assert_eq!(None, wallet.kdf());
+    wallet.set_config(wallet.config().with_kdf(cheap));
+    wallet.encrypt("passphrase").unwrap();
+    assert_eq!(Some(cheap), wallet.kdf());
+
+    let file = wallet.flush().unwrap();
+    let mut loaded = Wallet::from_wallet_file(file).unwrap();
+    assert_eq!(cheap, loaded.config().kdf());
+    assert_eq!(Some(cheap), loaded.kdf());
+    loaded.unlock("passphrase").unwrap();
+    loaded.lock().unwrap();
+
+    // parameters set later apply once the passphrase is changed
+    let costlier = KdfParams::new(MIN_KDF_LOG_N + 1, 8, 1).unwrap();
+    loaded.set_config(loaded.config().with_kdf(costlier));
+    assert_eq!(Some(cheap), loaded.kdf());
+    loaded.change_passphrase("passphrase", "new").unwrap();
+    assert_eq!(Some(costlier), loaded.kdf());
+    loaded.unlock("new").unwrap();
+}
+
+#[test]
+pub fn test_calibrate_kdf() {
+    assert_eq!(MIN_KDF_LOG_N, calibrate_kdf(0, &OsEntropy).unwrap().log_n());
+    assert_eq!(
+        MAX_KDF_LOG_N,
+        calibrate_kdf(u64::MAX / 4, &OsEntropy).unwrap().log_n()
+    );
+
+    let calibrated = calibrate_kdf(50, &OsEntropy).unwrap();
+    assert!((MIN_KDF_LOG_N..=MAX_KDF_LOG_N).contains(&calibrated.log_n()));
+    assert_eq!(KdfParams::default().r(), calibrated.r());
+    assert_eq!(KdfParams::default().p(), calibrated.p());
+}
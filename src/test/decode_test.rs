@@ -0,0 +1,262 @@
+use std::path::PathBuf;
+
+use crate::{
+    decode_transaction, decode_transaction_with, AccountType, LockTime, Network, OutPoint,
+    ScriptType, Transaction, TransactionBuilder, TransactionError, TransactionInput,
+    TransactionOutput, TransactionType, TxOrdering, Utxo, VerifyError, Wallet,
+};
+
+/// the first transaction between people, from block 170, paying to bare public keys
+const BLOCK_170_TX: &str = "0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd3704000000004847304402204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de4860a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac00000000";
+
+#[test]
+pub fn test_decode_legacy_transaction() {
+    let summary = decode_transaction(BLOCK_170_TX, Network::Mainnet).unwrap();
+
+    assert_eq!(
+        "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16",
+        summary.tx_id
+    );
+    assert_eq!(summary.tx_id, summary.wtx_id);
+    assert_eq!(1, summary.version);
+    assert_eq!(LockTime::Blocks(0), summary.lock_time);
+    assert_eq!(275, summary.size);
+    assert_eq!(275 * 4, summary.weight);
+    assert_eq!(275, summary.vsize);
+    assert!(!summary.rbf);
+    assert!(!summary.is_coinbase());
+
+    let input = &summary.inputs[0];
+    assert_eq!(
+        "0437cd7f8525ceed2324359c2d0ba26006d92d856a9c20fa0241106ee5a597c9",
+        input.previous_output.hash()
+    );
+    assert_eq!(0, input.previous_output.index());
+    // a signature alone unlocks a bare public key
+    assert_eq!(Some(TransactionType::Pay2PubKey), input.script_type);
+    assert!(input.value.is_none());
+    assert!(summary.fee.is_none());
+
+    assert_eq!(
+        vec![1_000_000_000, 4_000_000_000],
+        summary
+            .outputs
+            .iter()
+            .map(|output| output.value)
+            .collect::<Vec<i64>>()
+    );
+    assert!(summary
+        .outputs
+        .iter()
+        .all(|output| output.address.is_none()));
+    assert!(summary
+        .outputs
+        .iter()
+        .all(|output| output.script_type == ScriptType::P2pk));
+
+    // the coinbase output spent held 50 bitcoin
+    let prevout = TransactionOutput::from_script(5_000_000_000, vec![]);
+    let summary =
+        decode_transaction_with(BLOCK_170_TX, Network::Mainnet, |_| Some(prevout.clone())).unwrap();
+    assert_eq!(Some(0), summary.fee);
+    assert_eq!(Some(5_000_000_000), summary.inputs[0].value);
+}
+
+#[test]
+pub fn test_sign_p2pk_input() {
+    let summary = decode_transaction(BLOCK_170_TX, Network::Mainnet).unwrap();
+    let input = &summary.inputs[0];
+    let signature = &input.signature_script[1..];
+
+    // the coinbase of block 9 paid its 50 bitcoin to a bare public key
+    let pk_script = hex::decode(
+        "410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac",
+    )
+    .unwrap();
+    let pubkey = pk_script[1..66].to_vec();
+    let prevout = TransactionOutput::from_script(5_000_000_000, pk_script);
+    let outputs = summary
+        .outputs
+        .iter()
+        .map(|output| TransactionOutput::from_script(output.value, output.pk_script.clone()))
+        .collect();
+    let mut tx = Transaction::new(
+        TransactionType::Pay2PubKey,
+        vec![TransactionInput::new(
+            prevout.clone(),
+            input.previous_output.hash().to_string(),
+            0,
+        )],
+        outputs,
+        None,
+    );
+
+    let mut other = pubkey.clone();
+    other[64] ^= 1;
+    assert!(matches!(
+        tx.set_signature(0, signature, &other),
+        Err(TransactionError::KeyNotInScript(0))
+    ));
+
+    // the signature alone makes the signature script
+    tx.set_signature(0, signature, &pubkey).unwrap();
+    assert_eq!(BLOCK_170_TX, tx.to_hex());
+
+    tx.verify(std::slice::from_ref(&prevout)).unwrap();
+    let mut other_script = prevout.pk_script().to_vec();
+    other_script[65] ^= 1;
+    assert_eq!(
+        Err(VerifyError::InvalidSignature(0)),
+        tx.verify(&[TransactionOutput::from_script(5_000_000_000, other_script)])
+    );
+    assert_eq!(
+        Err(VerifyError::PrevoutCount {
+            inputs: 1,
+            prevouts: 0
+        }),
+        tx.verify(&[])
+    );
+}
+
+#[test]
+pub fn test_decode_wallet_transaction() {
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    let mut outputs = vec![];
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder.ordering(TxOrdering::Insertion);
+    for (account_type, tx_id) in [
+        (AccountType::Legacy, "11"),
+        (AccountType::NestedSegwit, "22"),
+        (AccountType::NativeSegwit, "33"),
+    ] {
+        let account = wallet.new_account(account_type).unwrap();
+        let address = wallet.new_receive_address(account).unwrap();
+        let output = TransactionOutput::new(
+            account_type.tx_type(),
+            wallet.get_address(address.clone()).unwrap(),
+            10_000,
+        );
+        builder.add_input(TransactionInput::new(output.clone(), tx_id.repeat(32), 0));
+        outputs.push(Utxo::new(
+            OutPoint::new(tx_id.repeat(32), 0),
+            output,
+            address,
+        ));
+    }
+    let paid = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    builder.add_output(TransactionOutput::from_script(
+        29_000,
+        hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+    ));
+    let mut tx = builder.build().unwrap();
+    let mut second = tx.inputs()[1].clone();
+    second.set_sequence(0xfffffffd);
+    tx = {
+        let mut inputs = tx.inputs();
+        inputs[1] = second;
+        let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+        builder.ordering(TxOrdering::Insertion);
+        for input in inputs {
+            builder.add_input(input);
+        }
+        for output in tx.outputs() {
+            builder.add_output(output);
+        }
+        builder.build().unwrap()
+    };
+    wallet.sign_transaction(&mut tx).unwrap();
+
+    // without the coins spent, the types are read from the signatures
+    let summary = decode_transaction(&tx.to_hex(), Network::Mainnet).unwrap();
+    assert_eq!(tx.tx_id(), summary.tx_id);
+    assert_ne!(summary.tx_id, summary.wtx_id);
+    assert_eq!(tx.weight(), summary.weight);
+    assert_eq!(tx.size(), summary.vsize);
+    assert!(summary.rbf);
+    assert_eq!(
+        vec![
+            Some(TransactionType::Pay2PubKeyHash),
+            Some(TransactionType::NestedPay2WitnessPubKeyHash),
+            Some(TransactionType::Pay2WitnessPubKeyHash),
+        ],
+        summary
+            .inputs
+            .iter()
+            .map(|input| input.script_type.clone())
+            .collect::<Vec<Option<TransactionType>>>()
+    );
+    assert_eq!(2, summary.inputs[2].witness.len());
+    assert_eq!(Some(paid.to_string()), summary.outputs[0].address);
+    assert_eq!(ScriptType::P2wpkh, summary.outputs[0].script_type);
+    assert!(summary.fee.is_none());
+    assert!(summary.to_string().contains(paid));
+
+    // the wallet knows the coins it spends
+    for utxo in outputs.iter() {
+        wallet.add_utxo(utxo.clone()).unwrap();
+    }
+    let summary = wallet.decode_transaction(&tx.to_hex()).unwrap();
+    assert_eq!(Some(tx.fee()), summary.fee);
+    assert_eq!(Some(1_000), summary.fee);
+    assert_eq!(
+        Some(outputs[0].address()),
+        summary.inputs[0].address.as_deref()
+    );
+    assert!(summary.to_string().contains("fee 1000 sat"));
+}
+
+#[test]
+pub fn test_decode_errors() {
+    let decode = |hex: &str| decode_transaction(hex, Network::Mainnet);
+
+    assert!(matches!(decode("zz"), Err(TransactionError::Decode(_))));
+    assert!(matches!(
+        decode(&BLOCK_170_TX[..100]),
+        Err(TransactionError::Decode(_))
+    ));
+    assert!(matches!(
+        decode(&format!("{}00", BLOCK_170_TX)),
+        Err(TransactionError::Decode(_))
+    ));
+    // a count longer than the transaction
+    assert!(matches!(
+        decode("01000000ff0000000000000000"),
+        Err(TransactionError::Decode(_))
+    ));
+}
+
+#[test]
+pub fn test_serialize_many_outputs() {
+    let pk_script = hex::decode("76a914000000000000000000000000000000000000000088ac").unwrap();
+    let input = TransactionInput::new(
+        TransactionOutput::from_script(300_000_000, pk_script.clone()),
+        "11".repeat(32),
+        300,
+    );
+    // past 252 the counts take three bytes
+    let outputs = (0..300)
+        .map(|value| TransactionOutput::from_script(1_000 + value, pk_script.clone()))
+        .collect();
+    let tx = Transaction::new(TransactionType::Pay2PubKeyHash, vec![input], outputs, None);
+
+    for hex in [hex::encode(tx.serialize()), tx.pre_sign()] {
+        let summary = decode_transaction(&hex, Network::Mainnet).unwrap();
+        assert_eq!(300, summary.outputs.len());
+        assert_eq!(1_299, summary.outputs[299].value);
+        assert_eq!(300, summary.inputs[0].previous_output.index());
+        assert_eq!("11".repeat(32), summary.inputs[0].previous_output.hash());
+    }
+    let summary = decode_transaction(&hex::encode(tx.serialize()), Network::Mainnet).unwrap();
+    assert_eq!(tx.tx_id(), summary.tx_id);
+    // the pre-signed inputs hold the pk script they spend
+    let summary = decode_transaction(&tx.pre_sign(), Network::Mainnet).unwrap();
+    assert_eq!(pk_script, summary.inputs[0].signature_script);
+}
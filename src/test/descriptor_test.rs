@@ -0,0 +1,45 @@
+use crate::{
+    descriptor_checksum, verify_descriptor_checksum, with_descriptor_checksum, DescriptorError,
+};
+
+#[test]
+pub fn test_descriptor_checksum() {
+    // from the descriptor tests of Bitcoin Core
+    let private = "sh(multi(2,[00000000/111'/222]xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc,xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L/0))";
+    let public = "sh(multi(2,[00000000/111'/222]xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL,xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y/0))";
+    assert_eq!("ggrsrxfy", descriptor_checksum(private).unwrap());
+    assert_eq!("tjg09x5t", descriptor_checksum(public).unwrap());
+    assert_eq!(
+        format!("{}#tjg09x5t", public),
+        with_descriptor_checksum(public).unwrap()
+    );
+
+    assert_eq!(
+        public,
+        verify_descriptor_checksum(&format!("{}#tjg09x5t", public)).unwrap()
+    );
+    assert_eq!(
+        Err(DescriptorError::MissingChecksum),
+        verify_descriptor_checksum(public)
+    );
+    assert_eq!(
+        Err(DescriptorError::InvalidChecksumLength(7)),
+        verify_descriptor_checksum(&format!("{}#tjg09x5", public))
+    );
+    assert_eq!(
+        Err(DescriptorError::ChecksumMismatch {
+            expected: "tjg09x5t".to_string(),
+            found: "tjg09x5q".to_string()
+        }),
+        verify_descriptor_checksum(&format!("{}#tjg09x5q", public))
+    );
+    // a typo in the descriptor
+    assert!(matches!(
+        verify_descriptor_checksum(&format!("{}#tjg09x5t", public.replace("111'", "112'"))),
+        Err(DescriptorError::ChecksumMismatch { .. })
+    ));
+    assert_eq!(
+        Err(DescriptorError::InvalidCharacter('é')),
+        descriptor_checksum("pkh(é)")
+    );
+}
@@ -0,0 +1,128 @@
+#![allow(unused_imports)]
+
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    path::PathBuf,
+    thread,
+};
+
+use crate::{
+    AccountType, Backend, BackendError, BitcoinCoreRpc, Network, RegtestHarness, SigHashType,
+    Transaction, TransactionOutput, TransactionType, Wallet,
+};
+
+/// answer a single HTTP request with a canned JSON body
+fn serve_once(body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 4096];
+        let _ = stream.read(&mut buf).unwrap();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+    });
+
+    address
+}
+
+#[test]
+pub fn test_harness_requires_regtest() {
+    let address = serve_once(r#"{"result":{"chain":"main","blocks":800000},"error":null}"#);
+    let rpc = BitcoinCoreRpc::new(address, "user".to_string(), "pass".to_string());
+
+    assert!(matches!(
+        RegtestHarness::new(rpc),
+        Err(BackendError::Unsupported(_))
+    ));
+}
+
+#[test]
+#[ignore = "needs a regtest node, configured as in RegtestHarness::from_env"]
+pub fn test_regtest_send_receive() {
+    let harness = RegtestHarness::from_env().unwrap();
+
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Regtest,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NestedSegwit).unwrap();
+
+    let address = wallet.new_receive_address(account).unwrap();
+    let utxo = harness.fund(&mut wallet, &address, 100_000).unwrap();
+    assert_eq!(100_000, utxo.value());
+    assert!(!wallet.utxos()[0].is_confirmed());
+
+    let height = harness.confirm(&mut wallet, 1).unwrap();
+    assert_eq!(Some(height), wallet.utxos()[0].height());
+    assert_eq!(100_000, wallet.account_balance(account));
+
+    // spend the output back to the wallet's change chain
+    let change = wallet.new_change_address(account).unwrap();
+    let change_key = wallet.get_address(change).unwrap();
+    let key = wallet.get_address(address).unwrap();
+    let mut tx = Transaction::new(
+        TransactionType::NestedPay2WitnessPubKeyHash,
+        vec![utxo.to_input()],
+        vec![TransactionOutput::new(
+            TransactionType::NestedPay2WitnessPubKeyHash,
+            change_key,
+            99_000,
+        )],
+        None,
+    );
+    tx.sign_nested_segwit_input(0, &key, SigHashType::All)
+        .unwrap();
+
+    assert_eq!(tx.tx_id(), harness.broadcast(&tx.to_hex()).unwrap());
+    harness.generate(1).unwrap();
+    assert!(harness.height().unwrap() > height);
+}
+
+#[test]
+#[ignore = "needs a regtest node, configured as in RegtestHarness::from_env"]
+pub fn test_regtest_reorg() {
+    let harness = RegtestHarness::from_env().unwrap();
+
+    let data_dir = std::env::temp_dir().join("waller_test_regtest_reorg");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Regtest,
+        true,
+        data_dir,
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    let utxo = harness.fund(&mut wallet, &address, 100_000).unwrap();
+    harness.generate(1).unwrap();
+    let height = wallet.sync(&harness).unwrap().tip;
+    assert_eq!(Some(height), wallet.utxos()[0].height());
+
+    // the block confirming the payment is replaced, the payment is mined again after it
+    harness.invalidate_block(height).unwrap();
+    harness.generate(2).unwrap();
+    assert_eq!(height + 1, wallet.sync(&harness).unwrap().tip);
+    let refound = wallet
+        .utxos()
+        .iter()
+        .find(|found| found.outpoint() == utxo.outpoint())
+        .unwrap();
+    assert!(refound.is_confirmed());
+    assert_eq!(100_000, wallet.account_balance(account));
+}
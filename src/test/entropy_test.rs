@@ -0,0 +1,60 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+    generate_mnemonic_with, mnemonic_to_seed, EntropySource, KdfParams, Network, OsEntropy,
+    SeededEntropy, Wallet, MIN_KDF_LOG_N,
+};
+
+#[test]
+pub fn test_seeded_entropy() {
+    let source = SeededEntropy::new(42);
+    let first = source.random_bytes(32);
+    assert_eq!(32, first.len());
+    // the next bytes are new, and the same for the same seed
+    assert_ne!(first, source.random_bytes(32));
+    assert_eq!(first, SeededEntropy::new(42).random_bytes(32));
+    assert_ne!(first, SeededEntropy::new(43).random_bytes(32));
+
+    assert_ne!(OsEntropy.random_bytes(32), OsEntropy.random_bytes(32));
+}
+
+#[test]
+pub fn test_deterministic_mnemonic() {
+    let mnemonic = generate_mnemonic_with(&SeededEntropy::new(1));
+    assert_eq!(12, mnemonic.split_whitespace().count());
+    assert!(mnemonic_to_seed(mnemonic.clone(), "").is_ok());
+    assert_eq!(mnemonic, generate_mnemonic_with(&SeededEntropy::new(1)));
+    assert_ne!(mnemonic, generate_mnemonic_with(&SeededEntropy::new(2)));
+
+    // a wallet given the source generates the same master key
+    let mut wallet = Wallet::new(Network::Mainnet, PathBuf::from("/tmp"), false, true);
+    wallet.set_entropy(Arc::new(SeededEntropy::new(1)));
+    let created = wallet.generate_master_key(true).unwrap();
+    assert_eq!(mnemonic, created.mnemonic);
+}
+
+#[test]
+pub fn test_deterministic_encryption() {
+    // the salt, mac key and nonces of an encrypted wallet come from its source
+    let encrypted = |seed: u64| {
+        let mut wallet = Wallet::restore(
+            "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset"
+                .to_string(),
+            Network::Mainnet,
+            true,
+            PathBuf::from("/tmp"),
+            false,
+        )
+        .unwrap();
+        wallet.set_entropy(Arc::new(SeededEntropy::new(seed)));
+        let kdf = KdfParams::new(MIN_KDF_LOG_N, 8, 1).unwrap();
+        wallet.set_config(wallet.config().with_kdf(kdf));
+        wallet.encrypt("passphrase").unwrap();
+        serde_json::to_value(&wallet).unwrap()
+    };
+
+    let wallet = encrypted(1);
+    assert_eq!(wallet["encryption"], encrypted(1)["encryption"]);
+    assert_eq!(wallet["keys"], encrypted(1)["keys"]);
+    assert_ne!(wallet["encryption"], encrypted(2)["encryption"]);
+}
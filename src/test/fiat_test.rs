@@ -0,0 +1,39 @@
+use crate::{Currency, Decimal};
+
+#[test]
+fn test_decimal() {
+    let price: Decimal = "65432.10".parse().unwrap();
+    assert_eq!(Decimal::new(654321, 1), price);
+    assert_eq!("65432.1", price.to_string());
+    assert_eq!("65432.10", price.to_string_with(2));
+    assert_eq!("-0.05", "-0.050".parse::<Decimal>().unwrap().to_string());
+    assert_eq!("7", "7".parse::<Decimal>().unwrap().to_string());
+    assert!("1e5".parse::<Decimal>().is_err());
+    assert!("".parse::<Decimal>().is_err());
+    assert!("-".parse::<Decimal>().is_err());
+
+    // rounded half away from zero
+    let price: Decimal = "30000".parse().unwrap();
+    assert_eq!(Some(Decimal::new(15, 2)), Decimal::value_of(500, price, 2));
+    assert_eq!(Some(Decimal::new(2, 2)), Decimal::value_of(50, price, 2));
+    assert_eq!(Some(Decimal::new(-2, 2)), Decimal::value_of(-50, price, 2));
+    assert_eq!(Some(Decimal::new(0, 0)), Decimal::value_of(16, price, 2));
+    assert_eq!(
+        Some(Decimal::new(6_300_000, 0)),
+        Decimal::value_of(21_000_000, "30000000".parse().unwrap(), 0)
+    );
+    assert_eq!(
+        None,
+        Decimal::value_of(i64::MAX, Decimal::new(i128::MAX, 0), 2)
+    );
+}
+
+#[test]
+fn test_currency() {
+    assert_eq!(Some(Currency::USD), Currency::new("usd"));
+    assert_eq!("NZD", Currency::new("NZD").unwrap().to_string());
+    assert_eq!(None, Currency::new("US"));
+    assert_eq!(None, Currency::new("U$D"));
+    assert_eq!(2, Currency::EUR.decimals());
+    assert_eq!(0, Currency::JPY.decimals());
+}
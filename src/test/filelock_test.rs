@@ -0,0 +1,92 @@
+use crate::{AccountType, LockOwner, Network, Wallet, WalletError};
+
+fn locked_wallet(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&path);
+
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path,
+        false,
+    )
+    .unwrap();
+    wallet.new_account(AccountType::NativeSegwit).unwrap();
+    wallet.flush().unwrap()
+}
+
+#[test]
+pub fn test_open_exclusive() {
+    let file = locked_wallet("waller_test_open_exclusive");
+
+    let mut wallet = Wallet::open_exclusive(file.clone()).unwrap();
+    let lock = wallet.file_lock().unwrap();
+    assert!(lock.path().is_file());
+    assert_eq!(None, lock.stale_owner());
+
+    // another handle can neither lock nor flush the file
+    let owner = match Wallet::open_exclusive(file.clone()) {
+        Err(WalletError::FileInUse(Some(owner))) => owner,
+        other => panic!(
+            "expected the wallet to be in use, got {:?}",
+            other.map(|_| ())
+        ),
+    };
+    assert_eq!(std::process::id(), owner.pid);
+    let other = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert!(matches!(
+        other.flush(),
+        Err(WalletError::FileInUse(Some(_)))
+    ));
+
+    // the holder flushes as usual
+    wallet.new_account(AccountType::Legacy).unwrap();
+    assert_eq!(file, wallet.flush().unwrap());
+
+    drop(wallet);
+    let reopened = Wallet::open_exclusive(file.clone()).unwrap();
+    assert_eq!(None, reopened.file_lock().unwrap().stale_owner());
+    assert_eq!(2, reopened.accounts().len());
+    reopened.flush().unwrap();
+    drop(reopened);
+    other.flush().unwrap();
+}
+
+#[test]
+pub fn test_open_exclusive_forced() {
+    let file = locked_wallet("waller_test_open_exclusive_forced");
+
+    let held = Wallet::open_exclusive(file.clone()).unwrap();
+    let forced = Wallet::open_exclusive_with(file.clone(), true).unwrap();
+    assert_eq!(
+        held.file_lock().unwrap().path(),
+        forced.file_lock().unwrap().path()
+    );
+    // the forced lock is the one held now
+    assert!(matches!(
+        Wallet::open_exclusive(file),
+        Err(WalletError::FileInUse(Some(_)))
+    ));
+}
+
+#[test]
+pub fn test_stale_lock() {
+    let file = locked_wallet("waller_test_stale_lock");
+
+    // a process that died holding the wallet left its owner behind
+    let dead = LockOwner {
+        pid: u32::MAX,
+        since: 1_600_000_000,
+    };
+    let lock_file = Wallet::open_exclusive(file.clone())
+        .unwrap()
+        .file_lock()
+        .unwrap()
+        .path()
+        .to_path_buf();
+    std::fs::write(&lock_file, serde_json::to_string(&dead).unwrap()).unwrap();
+
+    let wallet = Wallet::open_exclusive(file).unwrap();
+    assert_eq!(Some(&dead), wallet.file_lock().unwrap().stale_owner());
+}
@@ -1,7 +1,15 @@
 #![allow(unused_imports)]
-use secp256k1::constants::CURVE_ORDER;
+use std::convert::TryFrom;
 
-use crate::{generate_mnemonic, ChildKeyType, Key, Network};
+use secp256k1::{constants::CURVE_ORDER, schnorrsig, Message, PublicKey, Secp256k1, Signature};
+
+use crate::{
+    base58check_decode, bip85_entropy, compress_public_key, decode_transaction,
+    encode_segwit_address, generate_mnemonic, sha256_hash, tagged_hash, AccountType,
+    BitcoinCoreRpc, ChildNumber, Digest32, Key, KeyCreationOutput, KeyError, Network, Script,
+    SigHashType, Transaction, TransactionError, TransactionInput, TransactionOutput,
+    TransactionType, VerifyError, BIP85_PURPOSE, HARDENED_OFFSET,
+};
 
 #[test]
 pub fn test_new_key() {
@@ -15,7 +23,16 @@ pub fn test_new_key() {
     let wif = key.to_wif();
     let key_from_wif = Key::from_wif(wif).unwrap();
 
-    assert_eq!(key_from_wif.bytes(), key.bytes())
+    assert_eq!(key_from_wif.bytes(), key.bytes());
+
+    // a word changed breaks the checksum of the phrase
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling abandon",
+    );
+    assert!(matches!(
+        Key::new(mnemonic, network, true),
+        Err(KeyError::BadMnemonicPhrase(_))
+    ));
 }
 
 #[test]
@@ -36,7 +53,7 @@ pub fn test_public_key() {
     let pubkey = key.new_public_key().unwrap();
 
     assert_eq!(
-        String::from("03dafb7df037fd4623009af2d4231fc015f0f194da5b5b197dd1d893bea2bae509"),
+        String::from("02fe9d83f29810c28834c117d787c6f2e7ed1afcea6542e06c08f51535e6745336"),
         hex::encode(pubkey)
     );
 }
@@ -51,11 +68,11 @@ pub fn test_derive_child_normal_private_key() {
     let key = Key::new(mnemonic, network, true).unwrap();
 
     let child_private_key = key
-        .derive_child_private_key(1, crate::ChildKeyType::Normal)
+        .derive_child_private_key(ChildNumber::Normal(1))
         .unwrap();
 
     assert_eq!(
-        "8c5c15f7f71c58f98bd0c64d77d982a210dd62d049806daef8affb06e29d7a32".to_string(),
+        "b7658d19ee95485c16e6fa1df25cb5901aa154c82732bbf77a38492249d8f7c8".to_string(),
         child_private_key.hex()
     );
 }
@@ -70,11 +87,11 @@ pub fn test_derive_child_hardened_private_key() {
     let key = Key::new(mnemonic, network, true).unwrap();
 
     let child_private_key = key
-        .derive_child_private_key(2147483648, ChildKeyType::Hardened)
+        .derive_child_private_key(ChildNumber::Hardened(0))
         .unwrap();
 
     assert_eq!(
-        "cbecb80118ebcce68e9d38b11b52beb29be4d5beea4a80230e6f7899fff0a715".to_string(),
+        "ef90f3089153ce47308382b16524699985fdff409e6ac11f0b8481b8ec91a443".to_string(),
         child_private_key.hex()
     );
 }
@@ -88,9 +105,116 @@ pub fn test_derive_child_public_key() {
 
     let key = Key::new(mnemonic, network, true).unwrap();
 
-    let pubkey = key.derive_child_public_key(1).unwrap();
+    let pubkey = key.derive_child_public_key(ChildNumber::Normal(1)).unwrap();
+
+    assert_eq!("03a0227336c437940a346b5bded6312f65f38130133eb20f20e5f3d47c61051e2d05ea1beae7dba3c9706cbdb4cf0a672fe4f156cd6474e2aec4755034c290c94f", hex::encode(&pubkey));
+
+    // the public key of the private child, with the same chain code
+    let child = key
+        .derive_child_private_key(ChildNumber::Normal(1))
+        .unwrap();
+    assert_eq!(child.extended_public_key().unwrap(), pubkey);
+
+    assert!(matches!(
+        key.derive_child_public_key(ChildNumber::Hardened(1)),
+        Err(KeyError::IndexOutOfRange)
+    ));
+}
+
+#[test]
+pub fn test_derive_wiped_child() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let network = Network::Mainnet;
+
+    for key in [
+        Key::new(mnemonic.clone(), network, true).unwrap(),
+        Key::new(mnemonic, network, true).unwrap().to_uncompressed(),
+    ] {
+        let mut wiped = key.clone();
+        wiped.wipe();
+        let (child, public_key) = wiped
+            .derive_wiped_child(&key.new_public_key().unwrap(), ChildNumber::Normal(3))
+            .unwrap();
 
-    assert_eq!("028be92ede5feab623905b30d1b1d87d477c1524ddb6f8f98ca122fbcf7e59870c5a7832455a67d351cf99fd030bb1d9a558f6a0cadb9bf9144c7010636f4224c4", hex::encode(pubkey));
+        // the private child once wiped, its public key in the compression of its parent
+        let mut private = key
+            .derive_child_private_key(ChildNumber::Normal(3))
+            .unwrap();
+        assert_eq!(private.new_public_key().unwrap(), public_key);
+        for account_type in [
+            AccountType::Legacy,
+            AccountType::NestedSegwit,
+            AccountType::NativeSegwit,
+            AccountType::Taproot,
+        ] {
+            assert_eq!(
+                account_type.address(&private).unwrap(),
+                account_type
+                    .public_key_address(&public_key, network)
+                    .unwrap()
+            );
+        }
+        private.wipe();
+        assert_eq!(private, child);
+
+        assert!(matches!(
+            wiped.derive_wiped_child(&public_key, ChildNumber::Hardened(3)),
+            Err(KeyError::IndexOutOfRange)
+        ));
+    }
+}
+
+#[test]
+pub fn test_child_number() {
+    assert_eq!(ChildNumber::Normal(5), ChildNumber::from(5));
+    assert_eq!(ChildNumber::Hardened(0), ChildNumber::from(HARDENED_OFFSET));
+    assert_eq!(
+        u32::MAX,
+        u32::from(ChildNumber::Hardened(HARDENED_OFFSET - 1))
+    );
+    assert_eq!("44'", ChildNumber::Hardened(44).to_string());
+    assert_eq!("0", ChildNumber::Normal(0).to_string());
+
+    // the last index of each range, the first past it
+    assert!(ChildNumber::normal(HARDENED_OFFSET - 1).is_ok());
+    assert!(ChildNumber::hardened(HARDENED_OFFSET - 1).is_ok());
+    assert!(matches!(
+        ChildNumber::normal(HARDENED_OFFSET),
+        Err(KeyError::IndexOutOfRange)
+    ));
+    assert!(matches!(
+        ChildNumber::hardened(HARDENED_OFFSET),
+        Err(KeyError::IndexOutOfRange)
+    ));
+
+    let key = Key::new(
+        String::from(
+            "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+        ),
+        Network::Mainnet,
+        true,
+    )
+    .unwrap();
+    assert!(matches!(
+        key.derive_child_private_key(ChildNumber::Normal(HARDENED_OFFSET)),
+        Err(KeyError::IndexOutOfRange)
+    ));
+    assert!(matches!(
+        key.derive_child_private_key(ChildNumber::Hardened(HARDENED_OFFSET)),
+        Err(KeyError::IndexOutOfRange)
+    ));
+
+    // serialized as the number BIP32 uses
+    assert_eq!(
+        "2147483692",
+        serde_json::to_string(&ChildNumber::Hardened(44)).unwrap()
+    );
+    assert_eq!(
+        ChildNumber::Normal(7),
+        serde_json::from_str::<ChildNumber>("7").unwrap()
+    );
 }
 
 #[test]
@@ -104,8 +228,405 @@ pub fn test_address() {
 
     let address = key.address().unwrap();
 
+    assert_eq!("1GXmQmC8XX9tY6ezqANXAqC6pJSDRX7Cbh".to_string(), address);
+}
+
+#[test]
+pub fn test_key_compression() {
+    let uncompressed =
+        Key::from_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ".to_string()).unwrap();
+    assert!(!uncompressed.compress_public_keys());
+    assert_eq!(
+        "1GAehh7TsJAHuUAeKZcXf5CnwuGuGgyX2S",
+        uncompressed.address().unwrap()
+    );
+
+    // the same private key has another address and WIF once compressed
+    let compressed = uncompressed.to_compressed();
+    assert_eq!(uncompressed.bytes(), compressed.bytes());
+    assert_eq!(
+        "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617",
+        compressed.to_wif()
+    );
+    assert_eq!(
+        "1LoVGDgRs9hTfTNJNuXKSpywcbdvwRXpmK",
+        compressed.address().unwrap()
+    );
+
+    let restored = compressed.to_uncompressed();
+    assert_eq!(
+        "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ",
+        restored.to_wif()
+    );
+    // segwit addresses always commit to the compressed key
+    assert_eq!(
+        compressed.native_segwit_address().unwrap(),
+        restored.native_segwit_address().unwrap()
+    );
+}
+
+#[test]
+pub fn test_native_segwit_address() {
+    // the key at m/84'/0'/0'/0/0 of the BIP84 test mnemonic
+    let key =
+        Key::from_wif("KyZpNDKnfs94vbrwhJneDi77V6jF64PWPF8x5cdJb8ifgg2DUc9d".to_string()).unwrap();
+
+    assert_eq!(
+        "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu".to_string(),
+        key.native_segwit_address().unwrap()
+    );
+
+    // regtest has its own human readable part
+    let program = hex::decode("c0cebcd6c3d3ca8c75dc5ec62ebe55330ef910e2").unwrap();
+    assert_eq!(
+        "bcrt1qcr8te4kr609gcawutmrza0j4xv80jy8zeqchgx".to_string(),
+        encode_segwit_address(Network::Regtest, 0, &program).unwrap()
+    );
+}
+
+#[test]
+pub fn test_taproot_address() {
+    // the key at m/86'/0'/0'/0/0 of the BIP86 test mnemonic
+    let key =
+        Key::from_wif("KyRv5iFPHG7iB5E4CqvMzH3WFJVhbfYK4VY7XAedd9Ys69mEsPLQ".to_string()).unwrap();
+
+    assert_eq!(
+        "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+        hex::encode(key.taproot_output_key().unwrap())
+    );
+    assert_eq!(
+        "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr".to_string(),
+        key.taproot_address().unwrap()
+    );
+}
+
+#[test]
+pub fn test_debug_redacts_secrets() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let key = Key::new(mnemonic.clone(), Network::Mainnet, true).unwrap();
+
+    let debug = format!("{:?}", key);
+    assert!(!debug.contains(&hex::encode(key.bytes())));
+    assert!(!debug.contains(&format!("{:?}", key.bytes())));
+    assert!(!debug.contains(&format!("{:?}", key.chain_code())));
+    assert!(debug.contains("Mainnet"));
+
+    let output = KeyCreationOutput {
+        mnemonic: mnemonic.clone(),
+        key,
+    };
+    assert!(!format!("{:?}", output).contains(&mnemonic));
+
+    let rpc = BitcoinCoreRpc::new(
+        "127.0.0.1:8332".to_string(),
+        "user".to_string(),
+        "hunter2".to_string(),
+    );
+    assert!(!format!("{:?}", rpc).contains("hunter2"));
+}
+
+#[test]
+pub fn test_bip85_entropy() {
+    // BIP85 test case 1, the key at m/83696968'/0'/0'
+    let xprv = base58check_decode("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+    let master = Key::from_extended_private_key(
+        &[&xprv[46..78], &xprv[13..45]].concat(),
+        Network::Mainnet,
+        true,
+    )
+    .unwrap();
+    let mut key = master;
+    for index in [BIP85_PURPOSE, 0, 0] {
+        key = key
+            .derive_child_private_key(ChildNumber::Hardened(index))
+            .unwrap();
+    }
+
+    assert_eq!(
+        "cca20ccb0e9a90feb0912870c3323b24874b0ca3d8018c4b96d0b97c0e82ded0",
+        hex::encode(key.bytes())
+    );
+    assert_eq!(
+        "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f00b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7",
+        hex::encode(bip85_entropy(&key))
+    );
+}
+
+#[test]
+pub fn test_derive_app_key() {
+    let key = Key::new(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+    )
+    .unwrap();
+
+    let nostr = key.derive_app_key("nostr").unwrap();
+    assert_eq!("nostr", nostr.tag());
+    assert_eq!(
+        nostr.public_key(),
+        key.derive_app_key("nostr").unwrap().public_key()
+    );
+    assert_ne!(
+        nostr.public_key(),
+        key.derive_app_key("ssh").unwrap().public_key()
+    );
+    assert_ne!(
+        nostr.public_key(),
+        compress_public_key(&key.new_public_key().unwrap()).unwrap()
+    );
+    assert_eq!(32, nostr.x_only_public_key().len());
+    assert!(matches!(key.derive_app_key(""), Err(KeyError::Other(_))));
+
+    let digest = sha256_hash(&b"an event".to_vec());
+    let signature = nostr.sign_schnorr(&digest).unwrap();
+    assert!(Secp256k1::new()
+        .schnorrsig_verify(
+            &schnorrsig::Signature::from_slice(&signature).unwrap(),
+            &Message::from_slice(&digest).unwrap(),
+            &schnorrsig::PublicKey::from_slice(&nostr.x_only_public_key()).unwrap(),
+        )
+        .is_ok());
+    let signature = nostr.sign_ecdsa(&digest).unwrap();
+    assert!(Secp256k1::new()
+        .verify(
+            &Message::from_slice(&digest).unwrap(),
+            &Signature::from_der(&signature).unwrap(),
+            &PublicKey::from_slice(&nostr.public_key()).unwrap(),
+        )
+        .is_ok());
+
+    assert!(nostr.to_npub().unwrap().starts_with("npub1"));
+    let nsec = nostr.export_nsec().unwrap();
+    assert!(nsec.starts_with("nsec1"));
+    assert!(!format!("{:?}", nostr).contains(&nsec));
+    assert!(format!("{:?}", nostr).contains("nostr"));
+}
+
+#[test]
+pub fn test_key_equality_and_validation() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let key = Key::new(mnemonic.clone(), Network::Mainnet, true).unwrap();
+    assert_eq!(key, Key::new(mnemonic, Network::Mainnet, true).unwrap());
+    assert_eq!(&key.extended_private_key()[32..], key.chain_code());
+    assert!(key.validate().is_ok());
+
+    // any part telling the keys apart makes them unequal
+    let child = key
+        .derive_child_private_key(ChildNumber::Normal(0))
+        .unwrap();
+    assert_ne!(key, child);
+    assert_ne!(key, key.to_uncompressed());
+    let restored =
+        Key::from_extended_private_key(&key.extended_private_key(), Network::Mainnet, true)
+            .unwrap();
+    assert_eq!(key, restored);
+    let mut other_chain = key.extended_private_key();
+    other_chain[63] ^= 1;
+    assert_ne!(
+        key,
+        Key::from_extended_private_key(&other_chain, Network::Mainnet, true).unwrap()
+    );
+
+    // keys read from elsewhere are checked against the curve order
+    let with = |field: &str, value: Vec<u8>| {
+        let mut json = serde_json::to_value(&key).unwrap();
+        json[field] = serde_json::json!(value);
+        serde_json::from_value::<Key>(json).unwrap()
+    };
+    for bytes in [
+        vec![0; 32],
+        CURVE_ORDER.to_vec(),
+        vec![0xff; 32],
+        vec![1; 31],
+    ] {
+        assert!(matches!(
+            with("bytes", bytes).validate(),
+            Err(KeyError::InvalidPrivateKey)
+        ));
+    }
+    assert!(matches!(
+        with("chain_code", vec![0; 31]).validate(),
+        Err(KeyError::InvalidFormat)
+    ));
+    let mut wiped = key.clone();
+    wiped.wipe();
+    assert!(wiped.validate().is_err());
+}
+
+#[test]
+pub fn test_sign_digest() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let key = Key::new(mnemonic, Network::Mainnet, true).unwrap();
+    let public_key = PublicKey::from_slice(&key.new_public_key().unwrap()).unwrap();
+    let secp = Secp256k1::new();
+    let verify = |digest: &Digest32, signature: Vec<u8>| {
+        let signature: Signature = String::from_utf8(signature).unwrap().parse().unwrap();
+        let message = Message::from_slice(digest.as_bytes()).unwrap();
+        secp.verify(&message, &signature, &public_key).is_ok()
+    };
+
+    let digest = Digest32::sha256d(b"invoice 1042");
+    assert!(verify(&digest, key.sign_data(digest).unwrap()));
+
+    // tagged signatures only hold for the data hashed under their tag
+    let tagged = Digest32::tagged("waller/invoice", b"invoice 1042");
+    assert_eq!(
+        tagged_hash("waller/invoice", b"invoice 1042"),
+        tagged.as_bytes()
+    );
+    let signature = key
+        .sign_raw_digest_with_tag("waller/invoice", b"invoice 1042")
+        .unwrap();
+    assert!(verify(&tagged, signature.clone()));
+    assert!(!verify(
+        &Digest32::tagged("waller/receipt", b"invoice 1042"),
+        signature.clone()
+    ));
+    assert!(!verify(&Digest32::new([0; 32]), signature));
+
+    // a wiped key, eg of a locked wallet, fails to sign
+    let mut wiped = key.clone();
+    wiped.wipe();
+    assert!(matches!(wiped.sign_data(digest), Err(KeyError::Wiped)));
+    assert!(matches!(
+        wiped.sign_raw_digest_with_tag("waller/invoice", b"invoice 1042"),
+        Err(KeyError::Wiped)
+    ));
+
+    assert_eq!(
+        Digest32::from([7; 32]),
+        Digest32::try_from(&[7; 32][..]).unwrap()
+    );
+    assert!(matches!(
+        Digest32::try_from(&b"not a digest"[..]),
+        Err(KeyError::InvalidFormat)
+    ));
+}
+
+/// an unsigned transaction spending two P2PKH coins of a key, and the coins
+fn p2pkh_spend(key: &Key) -> (Transaction, Vec<TransactionOutput>) {
+    let script = Script::from_address(&key.address().unwrap(), Network::Mainnet)
+        .unwrap()
+        .into_bytes();
+    let prevouts = vec![
+        TransactionOutput::from_script(30_000, script.clone()),
+        TransactionOutput::from_script(20_000, script),
+    ];
+    let inputs = prevouts
+        .iter()
+        .enumerate()
+        .map(|(index, prevout)| {
+            TransactionInput::new(prevout.clone(), "ab".repeat(32), index as i32)
+        })
+        .collect();
+    let tx = Transaction::new(
+        TransactionType::Pay2PubKeyHash,
+        inputs,
+        vec![
+            TransactionOutput::from_script(29_000, vec![0x6a]),
+            TransactionOutput::from_script(20_000, vec![0x6a]),
+        ],
+        None,
+    );
+    (tx, prevouts)
+}
+
+/// the transaction with the signature scripts of its signed hex
+fn with_signatures(tx: &Transaction, signed: &str) -> Transaction {
+    let summary = decode_transaction(signed, Network::Mainnet).unwrap();
+    let mut inputs = tx.inputs();
+    for (input, decoded) in inputs.iter_mut().zip(summary.inputs.iter()) {
+        input.set_signature_script(decoded.signature_script.clone());
+    }
+    Transaction::new(tx.tx_type(), inputs, tx.outputs(), None)
+}
+
+#[test]
+pub fn test_sign_transaction_inputs() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let key = Key::new(mnemonic, Network::Mainnet, true).unwrap();
+    let (tx, prevouts) = p2pkh_spend(&key);
+
+    // each input is signed over its own sighash
+    let signed = with_signatures(&tx, &tx.sign(key.clone()).unwrap());
+    assert_eq!(Ok(()), signed.verify(&prevouts));
+
+    // a wiped key fails instead of panicking
+    let mut wiped = key;
+    wiped.wipe();
+    assert!(matches!(tx.sign(wiped), Err(TransactionError::Key(_))));
+}
+
+#[test]
+pub fn test_sighash_types() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let key = Key::new(mnemonic, Network::Mainnet, true).unwrap();
+    let (tx, prevouts) = p2pkh_spend(&key);
+
+    for (sighash_type, flag) in [
+        (SigHashType::All, 0x01),
+        (SigHashType::None, 0x02),
+        (SigHashType::Single, 0x03),
+        (SigHashType::AllPlusAnyoneCanPay, 0x81),
+        (SigHashType::NonePlusAnyoneCanPay, 0x82),
+        (SigHashType::SinglePlusAnyoneCanPay, 0x83),
+    ] {
+        assert_eq!(flag, sighash_type.to_byte());
+        assert_eq!([flag, 0, 0, 0], sighash_type.to_le_bytes());
+        assert_eq!(sighash_type, SigHashType::from_u32(flag as u32).unwrap());
+
+        let signed = with_signatures(&tx, &tx.sign_with(key.clone(), sighash_type).unwrap());
+        for input in signed.inputs() {
+            // the signature is pushed first, its flag last
+            let signature_length = input.signature_script()[0] as usize;
+            assert_eq!(flag, input.signature_script()[signature_length]);
+        }
+        assert_eq!(Ok(()), signed.verify(&prevouts));
+    }
+    assert_eq!(SigHashType::All, SigHashType::default());
+    for flag in [0x00, 0x04, 0x80, 0x101] {
+        assert!(matches!(
+            SigHashType::from_u32(flag),
+            Err(TransactionError::InvalidSighashType(invalid)) if invalid == flag
+        ));
+    }
+
+    // without ANYONECANPAY the signatures commit to every input, with it
+    // others may add theirs
+    let mut prevouts = prevouts;
+    prevouts.push(TransactionOutput::from_script(5_000, vec![0x51]));
+    let mut inputs = tx.inputs();
+    inputs.push(TransactionInput::new(
+        prevouts[2].clone(),
+        "cd".repeat(32),
+        0,
+    ));
+    let joined = Transaction::new(tx.tx_type(), inputs, tx.outputs(), None);
+    assert_eq!(
+        Err(VerifyError::InvalidSignature(0)),
+        with_signatures(
+            &joined,
+            &tx.sign_with(key.clone(), SigHashType::All).unwrap()
+        )
+        .verify(&prevouts)
+    );
     assert_eq!(
-        "1YeLh5cLf94yHkF7q9F9zXAhKdtyCFejHD1GKRYnvUpngaFSs1xY8q8ko8cUJ18VQAUC1JWV".to_string(),
-        address
+        Err(VerifyError::UnsignedInput(2)),
+        with_signatures(
+            &joined,
+            &tx.sign_with(key, SigHashType::AllPlusAnyoneCanPay).unwrap()
+        )
+        .verify(&prevouts)
     );
 }
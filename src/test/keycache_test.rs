@@ -0,0 +1,39 @@
+use crate::KeyCache;
+
+#[test]
+pub fn test_key_cache() {
+    let mut cache = KeyCache::new(2);
+    assert_eq!(2, cache.capacity());
+    assert!(cache.is_empty());
+    assert!(cache.get(b"a").is_none());
+
+    cache.insert(b"a", &[1; 32]);
+    cache.insert(b"b", &[2; 32]);
+    assert_eq!(Some(&[1u8; 32][..]), cache.get(b"a"));
+
+    // b is the least recently used, a was just read
+    cache.insert(b"c", &[3; 32]);
+    assert_eq!(2, cache.len());
+    assert!(cache.get(b"b").is_none());
+    assert_eq!(Some(&[1u8; 32][..]), cache.get(b"a"));
+    assert_eq!(Some(&[3u8; 32][..]), cache.get(b"c"));
+
+    // a key cached again replaces its secret in place
+    cache.insert(b"c", &[4; 32]);
+    assert_eq!(2, cache.len());
+    assert_eq!(Some(&[4u8; 32][..]), cache.get(b"c"));
+
+    // only 32 byte keys are held
+    cache.insert(b"d", &[5; 16]);
+    assert!(cache.get(b"d").is_none());
+
+    cache.purge();
+    assert!(cache.is_empty());
+    assert!(cache.get(b"a").is_none());
+    assert!(!format!("{:?}", cache).contains("secrets"));
+
+    let mut empty = KeyCache::new(0);
+    empty.insert(b"a", &[1; 32]);
+    assert!(empty.is_empty());
+    assert!(!empty.status().memory_locked);
+}
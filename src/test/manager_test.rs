@@ -0,0 +1,104 @@
+use std::{path::PathBuf, sync::Arc, thread};
+
+use crate::{AccountType, Chain, Network, Wallet, WalletError, WalletManager};
+
+fn restore(mnemonic: &str) -> Wallet {
+    let mut wallet = Wallet::restore(
+        mnemonic.to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    wallet.new_account(AccountType::NativeSegwit).unwrap();
+    wallet
+}
+
+#[test]
+pub fn test_wallet_manager() {
+    let path = std::env::temp_dir().join("waller_test_wallet_manager");
+    let _ = std::fs::remove_dir_all(&path);
+    let manager = Arc::new(WalletManager::new(path.clone()).unwrap());
+    assert!(manager.list().unwrap().is_empty());
+
+    let alice = manager
+        .create(
+            "alice",
+            restore(
+                "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+            ),
+        )
+        .unwrap();
+    manager
+        .create(
+            "bob",
+            restore("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"),
+        )
+        .unwrap();
+    assert!(path.join("alice").join("wallet.json").is_file());
+    assert_eq!(vec!["alice", "bob"], manager.list().unwrap());
+
+    assert!(matches!(
+        manager.create(
+            "alice",
+            restore(
+                "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset"
+            )
+        ),
+        Err(WalletError::WalletExists(_))
+    ));
+    for name in ["", "..", "a/b"].iter() {
+        assert!(matches!(
+            manager.open(name),
+            Err(WalletError::InvalidWalletName(_))
+        ));
+    }
+    assert!(matches!(
+        manager.open("carol"),
+        Err(WalletError::WalletNotFound(_))
+    ));
+
+    // both wallets are used at once from several threads
+    let handles: Vec<_> = ["alice", "bob", "alice", "bob"]
+        .iter()
+        .map(|name| {
+            let manager = manager.clone();
+            thread::spawn(move || {
+                manager
+                    .with_wallet(name, |wallet| wallet.new_receive_address(0))
+                    .unwrap()
+                    .unwrap()
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert!(Arc::ptr_eq(&alice, &manager.open("alice").unwrap()));
+    let issued = alice.read(|wallet| wallet.addresses().unwrap()).unwrap();
+
+    // a closed wallet is written and read back from its file on the next open
+    manager.close("alice").unwrap();
+    assert_eq!(vec!["bob"], manager.opened().unwrap());
+    let reopened = manager.open("alice").unwrap();
+    assert!(!Arc::ptr_eq(&alice, &reopened));
+    assert_eq!(
+        issued,
+        reopened.read(|wallet| wallet.addresses().unwrap()).unwrap()
+    );
+    assert_eq!(
+        path.join("alice"),
+        reopened.read(|wallet| wallet.path().clone()).unwrap()
+    );
+
+    // a second manager finds what the first wrote
+    manager.flush_all().unwrap();
+    let other = WalletManager::new(path).unwrap();
+    let bob = other.open("bob").unwrap();
+    let next = bob
+        .read(|wallet| wallet.account(0).unwrap().next_index(Chain::External))
+        .unwrap();
+    assert_eq!(2, next);
+}
@@ -0,0 +1,54 @@
+use crate::{CongestionLevel, FeeBucket, FeeHistogram, BLOCK_VSIZE};
+
+#[test]
+fn test_fee_histogram() {
+    let histogram = FeeHistogram::from_transactions(vec![
+        (25.3, 400_000),
+        (21.0, 200_000),
+        (9.9, 900_000),
+        (1.0, 300_000),
+        // under the relay fee
+        (0.5, 1_000_000),
+    ]);
+    assert_eq!(
+        vec![
+            FeeBucket {
+                fee_rate: 20.0,
+                vsize: 600_000,
+            },
+            FeeBucket {
+                fee_rate: 8.0,
+                vsize: 900_000,
+            },
+            FeeBucket {
+                fee_rate: 1.0,
+                vsize: 300_000,
+            },
+        ],
+        histogram.buckets
+    );
+    assert_eq!(1_800_000, histogram.total_vsize());
+    assert_eq!(1.8, histogram.backlog_blocks());
+
+    // the next block takes part of the 8 sat/vB bucket, two blocks take everything
+    assert_eq!(9, histogram.estimate_fee_rate(1));
+    assert_eq!(9, histogram.estimate_fee_rate(0));
+    assert_eq!(1, histogram.estimate_fee_rate(2));
+    assert_eq!(1, FeeHistogram::default().estimate_fee_rate(1));
+}
+
+#[test]
+fn test_congestion_level() {
+    let backlog = |blocks: f64| {
+        FeeHistogram::from_transactions(vec![(10.0, (blocks * BLOCK_VSIZE as f64) as u64)])
+    };
+    assert_eq!(CongestionLevel::Low, FeeHistogram::default().congestion());
+    assert_eq!(CongestionLevel::Low, backlog(0.5).congestion());
+    assert_eq!(CongestionLevel::Normal, backlog(6.0).congestion());
+    assert_eq!(CongestionLevel::High, backlog(20.0).congestion());
+    assert_eq!(CongestionLevel::Extreme, backlog(100.0).congestion());
+
+    assert!(!CongestionLevel::Normal.is_congested());
+    assert!(CongestionLevel::High.is_congested());
+    assert_eq!("extreme", CongestionLevel::Extreme.to_string());
+}
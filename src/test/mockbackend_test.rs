@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use crate::{AccountType, Backend, BackendError, MockBackend, Network, Recipient, Wallet};
+
+#[test]
+pub fn test_mock_backend_chain() {
+    let backend = MockBackend::new(Network::Mainnet);
+    assert_eq!(0, backend.tip_height().unwrap());
+
+    let funded = backend
+        .fund("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", 50_000)
+        .unwrap();
+    assert_eq!(1, backend.mempool().len());
+    assert_eq!(None, backend.confirmations(&funded.hash()));
+    assert_eq!(50_000, backend.output(&funded).unwrap().value());
+    assert!(backend.fund("not an address", 1).is_err());
+
+    let hashes = backend.mine(3);
+    assert_eq!(3, hashes.len());
+    assert_eq!(3, backend.tip_height().unwrap());
+    assert_eq!(hashes[2], backend.block_hash(3).unwrap());
+    assert!(backend.mempool().is_empty());
+    assert_eq!(Some(3), backend.confirmations(&funded.hash()));
+    assert_eq!(1, backend.block(1).unwrap().transactions.len());
+    assert!(backend.block(4).is_err());
+
+    // the blocks are replaced, the transaction confirmed again in the first of them
+    let reorged = backend.reorg(2);
+    assert_eq!(2, reorged.len());
+    assert_eq!(3, backend.tip_height().unwrap());
+    assert_ne!(hashes[2], backend.block_hash(3).unwrap());
+    assert_eq!(hashes[0], backend.block_hash(1).unwrap());
+    assert_eq!(Some(3), backend.confirmations(&funded.hash()));
+
+    // reorganized out of the chain
+    assert_eq!(0, backend.disconnect(5));
+    assert_eq!(1, backend.mempool().len());
+    assert!(backend.evict(&funded.hash()));
+    assert!(!backend.evict(&funded.hash()));
+    backend.mine(1);
+    assert_eq!(None, backend.confirmations(&funded.hash()));
+    assert!(backend.output(&funded).is_none());
+}
+
+#[test]
+pub fn test_mock_backend_wallet() {
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+
+    let backend = MockBackend::new(Network::Mainnet);
+    let funded = backend.fund(&receive, 100_000).unwrap();
+    backend.mine(1);
+    let diff = wallet.sync(&backend).unwrap();
+    assert_eq!(1, diff.tip);
+    assert_eq!(100_000, diff.balance_delta);
+    assert_eq!(100_000, wallet.account_balance(account));
+
+    let tx = wallet
+        .send_many(
+            account,
+            &[Recipient::new(
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                40_000,
+            )],
+            5,
+        )
+        .unwrap();
+    assert_eq!(tx.tx_id(), backend.broadcast(&tx.to_hex()).unwrap());
+    assert_eq!(
+        Some("txn-already-in-mempool".to_string()),
+        backend.test_accept(&tx.to_hex()).unwrap().reject_reason
+    );
+    assert!(matches!(
+        backend.broadcast(&tx.to_hex()),
+        Err(BackendError::Rpc { code: -27, .. })
+    ));
+
+    backend.mine(1);
+    assert_eq!(Some(1), backend.confirmations(&tx.tx_id()));
+    wallet.sync(&backend).unwrap();
+    assert_eq!(60_000 - tx.fee(), wallet.account_balance(account));
+    assert!(matches!(
+        backend.broadcast(&tx.to_hex()),
+        Err(BackendError::Rpc { code: -27, .. })
+    ));
+
+    // both blocks reorganized out with the funding transaction
+    backend.disconnect(2);
+    backend.evict(&funded.hash());
+    backend.evict(&tx.tx_id());
+    backend.mine(3);
+    assert_eq!(3, wallet.sync(&backend).unwrap().tip);
+    assert_eq!(0, wallet.account_balance(account));
+}
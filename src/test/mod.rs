@@ -1,2 +1,71 @@
+#[cfg(test)]
+mod address_test;
+#[cfg(test)]
+mod broadcast_test;
+#[cfg(test)]
+mod builder_test;
+#[cfg(test)]
+mod config_test;
+#[cfg(test)]
+mod decode_test;
+#[cfg(test)]
+mod descriptor_test;
+#[cfg(all(test, feature = "devtools"))]
+mod devtools_test;
+#[cfg(test)]
+mod entropy_test;
+#[cfg(test)]
+mod fiat_test;
+#[cfg(test)]
+mod filelock_test;
+#[cfg(test)]
 mod key_test;
+#[cfg(test)]
+mod keycache_test;
+#[cfg(test)]
+mod manager_test;
+#[cfg(test)]
+mod mempool_test;
+#[cfg(test)]
+mod mockbackend_test;
+#[cfg(test)]
+mod multisig_test;
+#[cfg(all(test, feature = "musig2"))]
+mod musig_test;
+#[cfg(test)]
+mod network_test;
+#[cfg(test)]
+mod ownership_test;
+#[cfg(test)]
+mod package_test;
+#[cfg(test)]
+mod paper_test;
+#[cfg(all(test, feature = "payjoin"))]
+mod payjoin_test;
+#[cfg(test)]
+mod policy_test;
+#[cfg(test)]
+mod preview_test;
+#[cfg(test)]
+mod readonly_test;
+#[cfg(test)]
+mod reserves_test;
+#[cfg(test)]
+mod retry_test;
+#[cfg(test)]
+mod script_test;
+#[cfg(test)]
+mod shamir_test;
+#[cfg(test)]
+mod signer_test;
+#[cfg(test)]
+mod silentpayment_test;
+#[cfg(test)]
+mod stats_test;
+#[cfg(all(test, feature = "tracing"))]
+mod tracing_test;
+#[cfg(test)]
+mod utils_test;
+#[cfg(test)]
+mod vectors_test;
 mod wallet_test;
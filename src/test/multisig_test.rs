@@ -0,0 +1,249 @@
+use secp256k1::{Message, PublicKey, Secp256k1, Signature};
+
+use crate::{
+    compress_public_key, legacy_sighash, Key, MultisigScript, Network, SigHashType, Transaction,
+    TransactionError, TransactionInput, TransactionOutput, TransactionType, VerifyError,
+};
+
+const WIFS: [&str; 3] = [
+    "L57KYn5isHFThD4cohjJgLTZA2vaxnMMKWngnzbttF159yH9dARf",
+    "KyZpNDKnfs94vbrwhJneDi77V6jF64PWPF8x5cdJb8ifgg2DUc9d",
+    "KyRv5iFPHG7iB5E4CqvMzH3WFJVhbfYK4VY7XAedd9Ys69mEsPLQ",
+];
+
+fn keys() -> Vec<Key> {
+    WIFS.iter()
+        .map(|wif| Key::from_wif(wif.to_string()).unwrap())
+        .collect()
+}
+
+/// a 2-of-3 over the keys in order
+fn script(keys: &[Key]) -> MultisigScript {
+    let pubkeys = keys
+        .iter()
+        .map(|key| compress_public_key(&key.new_public_key().unwrap()).unwrap())
+        .collect();
+    MultisigScript::new(2, pubkeys).unwrap()
+}
+
+/// two inputs spending the script, paying the first key and back to the script
+fn unsigned_tx(keys: &[Key], script: &MultisigScript) -> Transaction {
+    let utxo = TransactionOutput::from_script(100_000, script.script_pubkey());
+    let first = TransactionInput::new(utxo.clone(), "11".repeat(32), 0);
+    let mut second = TransactionInput::new(utxo, "22".repeat(32), 1);
+    second.set_sequence(0xfffffffd);
+
+    let outputs = vec![
+        TransactionOutput::new(TransactionType::Pay2PubKeyHash, keys[0].clone(), 90_000),
+        TransactionOutput::from_script(5_000, script.script_pubkey()),
+    ];
+
+    Transaction::new(
+        TransactionType::Pay2PubKeyHash,
+        vec![first, second],
+        outputs,
+        None,
+    )
+}
+
+#[test]
+pub fn test_multisig_script() {
+    let script = script(&keys());
+
+    assert_eq!(
+        "522103ad1d8e89212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a26873210330d54fd0dd420a6e5f8d3624f5f3482cae350f79d5f0753bf5beef9c2d91af3c2103cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc11553ae",
+        hex::encode(script.redeem_script())
+    );
+    assert_eq!(
+        "3DE1SVxs3rcuP7cXZHw6VD8pQEnhacFAZT",
+        script.address(Network::Mainnet)
+    );
+
+    let pubkeys = script.pubkeys().to_vec();
+    assert!(matches!(
+        MultisigScript::new(0, pubkeys.clone()),
+        Err(TransactionError::InvalidMultisig(_))
+    ));
+    assert!(matches!(
+        MultisigScript::new(4, pubkeys),
+        Err(TransactionError::InvalidMultisig(_))
+    ));
+    assert!(matches!(
+        MultisigScript::new(1, vec![vec![4; 33]]),
+        Err(TransactionError::InvalidMultisig(_))
+    ));
+}
+
+#[test]
+pub fn test_legacy_sighash() {
+    let keys = keys();
+    let script = script(&keys);
+    let tx = unsigned_tx(&keys, &script);
+    let redeem_script = script.redeem_script();
+
+    let cases = [
+        (
+            0,
+            0x01,
+            "afe6fbc1324ee0ec3b9792c5ece14ecf550474ca51479bb397bc35269d87ee81",
+        ),
+        (
+            1,
+            0x01,
+            "40427fc990d81f31820663b6a9baddc719e9dd1ef2b35e6d32670105b085c7ad",
+        ),
+        (
+            1,
+            0x02,
+            "bf4821ddaf62b6cc9b5e0d5800a86d5bccb67235cc5db2acfe2d7a7324f1ecb0",
+        ),
+        (
+            1,
+            0x03,
+            "3bf52ac278012938c4be25b9e7f12de3ac9b3f9574e79e70bbadd979b197de1a",
+        ),
+        (
+            0,
+            0x81,
+            "23819cb335d10eebaa2fc09787c21dcc33c644438fc3a819426506476603f65d",
+        ),
+        (
+            1,
+            0x83,
+            "8b139c48985cd00b4a6eebad66f222bacd61f1229ffe966589788a9255c9ed2d",
+        ),
+    ];
+
+    for (index, sighash_type, expected) in cases.iter() {
+        assert_eq!(
+            *expected,
+            hex::encode(legacy_sighash(&tx, *index, &redeem_script, *sighash_type).unwrap())
+        );
+    }
+}
+
+#[test]
+pub fn test_multisig_signing() {
+    let keys = keys();
+    let script = script(&keys);
+    let mut tx = unsigned_tx(&keys, &script);
+
+    // signed out of order and one key at a time, as separate parties would
+    tx.sign_multisig_input(0, &script, &keys[2], SigHashType::All)
+        .unwrap();
+    tx.sign_multisig_input(0, &script, &keys[0], SigHashType::All)
+        .unwrap();
+    // signing again with a key that already signed changes nothing
+    let signed = tx.serialize();
+    tx.sign_multisig_input(0, &script, &keys[2], SigHashType::All)
+        .unwrap();
+    assert_eq!(signed, tx.serialize());
+
+    assert!(matches!(
+        tx.sign_multisig_input(0, &script, &keys[1], SigHashType::All),
+        Err(TransactionError::MultisigComplete(0))
+    ));
+
+    let outsider =
+        Key::from_wif("KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn".to_string()).unwrap();
+    assert!(matches!(
+        tx.sign_multisig_input(1, &script, &outsider, SigHashType::All),
+        Err(TransactionError::KeyNotInScript(1))
+    ));
+
+    let other = MultisigScript::new(1, script.pubkeys().to_vec()).unwrap();
+    assert!(matches!(
+        tx.sign_multisig_input(1, &other, &keys[0], SigHashType::All),
+        Err(TransactionError::ScriptMismatch(1))
+    ));
+
+    // OP_0 <sig by key 0> <sig by key 2> <redeem script>
+    let redeem_script = script.redeem_script();
+    let signature_script = tx.get_input(0).unwrap().signature_script();
+    assert_eq!(0x00, signature_script[0]);
+    assert!(signature_script.ends_with(&redeem_script));
+
+    let sighash = legacy_sighash(&tx, 0, &redeem_script, 0x01).unwrap();
+    let message = Message::from_slice(&sighash).unwrap();
+    let secp = Secp256k1::verification_only();
+
+    let mut cursor = 1;
+    for signer in [0, 2].iter() {
+        let length = signature_script[cursor] as usize;
+        let signature = &signature_script[cursor + 1..cursor + 1 + length];
+        cursor += 1 + length;
+
+        assert_eq!(Some(&0x01), signature.last());
+        let signature = Signature::from_der(&signature[..length - 1]).unwrap();
+        let pubkey = PublicKey::from_slice(&script.pubkeys()[*signer]).unwrap();
+        assert!(secp.verify(&message, &signature, &pubkey).is_ok());
+    }
+    // the redeem script is pushed with OP_PUSHDATA1
+    assert_eq!(
+        &[0x4c, redeem_script.len() as u8],
+        &signature_script[cursor..cursor + 2]
+    );
+
+    // the second input is still unsigned
+    tx.sign_multisig_input(1, &script, &keys[1], SigHashType::All)
+        .unwrap();
+    assert!(tx.get_input(1).unwrap().script_bytes() > 0);
+}
+
+#[test]
+pub fn test_verify_multisig() {
+    let keys = keys();
+    let script = script(&keys);
+    let prevouts =
+        |script_pubkey: Vec<u8>| vec![TransactionOutput::from_script(100_000, script_pubkey); 2];
+
+    let mut tx = unsigned_tx(&keys, &script);
+    assert_eq!(
+        Err(VerifyError::UnsignedInput(0)),
+        tx.verify(&prevouts(script.script_pubkey()))
+    );
+
+    tx.sign_multisig_input(0, &script, &keys[0], SigHashType::All)
+        .unwrap();
+    tx.sign_multisig_input(1, &script, &keys[1], SigHashType::All)
+        .unwrap();
+    // one signature short of the threshold
+    assert_eq!(
+        Err(VerifyError::InvalidSignature(0)),
+        tx.verify(&prevouts(script.script_pubkey()))
+    );
+
+    tx.sign_multisig_input(0, &script, &keys[2], SigHashType::All)
+        .unwrap();
+    tx.sign_multisig_input(1, &script, &keys[0], SigHashType::All)
+        .unwrap();
+    tx.verify(&prevouts(script.script_pubkey())).unwrap();
+    // the redeem script doesn't hash to another script
+    let other = MultisigScript::new(1, script.pubkeys().to_vec()).unwrap();
+    assert_eq!(
+        Err(VerifyError::ScriptMismatch(0)),
+        tx.verify(&prevouts(other.script_pubkey()))
+    );
+
+    // P2WSH signatures commit to the value spent
+    let utxo = TransactionOutput::from_script(100_000, script.p2wsh_script_pubkey());
+    let mut tx = Transaction::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        vec![
+            TransactionInput::new(utxo.clone(), "11".repeat(32), 0),
+            TransactionInput::new(utxo, "22".repeat(32), 1),
+        ],
+        unsigned_tx(&keys, &script).outputs(),
+        None,
+    );
+    for (input, signers) in [(0, [0, 1]), (1, [1, 2])] {
+        for signer in signers {
+            tx.sign_multisig_input(input, &script, &keys[signer], SigHashType::All)
+                .unwrap();
+        }
+    }
+    tx.verify(&prevouts(script.p2wsh_script_pubkey())).unwrap();
+    let mut underpaid = prevouts(script.p2wsh_script_pubkey());
+    underpaid[1] = TransactionOutput::from_script(99_999, script.p2wsh_script_pubkey());
+    assert_eq!(Err(VerifyError::InvalidSignature(1)), tx.verify(&underpaid));
+}
@@ -0,0 +1,213 @@
+use secp256k1::{schnorrsig, Message, Secp256k1};
+
+use crate::{
+    generate_nonce, generate_nonce_with, musig_public_key, Key, KeyAggContext, MusigError,
+    PartialSignature, PublicNonce, SeededEntropy, SigningSession,
+};
+
+const WIFS: [&str; 3] = [
+    "L57KYn5isHFThD4cohjJgLTZA2vaxnMMKWngnzbttF159yH9dARf",
+    "KyZpNDKnfs94vbrwhJneDi77V6jF64PWPF8x5cdJb8ifgg2DUc9d",
+    "KyRv5iFPHG7iB5E4CqvMzH3WFJVhbfYK4VY7XAedd9Ys69mEsPLQ",
+];
+
+fn keys() -> Vec<Key> {
+    WIFS.iter()
+        .map(|wif| Key::from_wif(wif.to_string()).unwrap())
+        .collect()
+}
+
+/// run both rounds with every key of a group, returning the signature
+fn sign(keys: &[Key], context: &KeyAggContext, message: &[u8]) -> Result<Vec<u8>, MusigError> {
+    let (secret_nonces, public_nonces): (Vec<_>, Vec<_>) = keys
+        .iter()
+        .map(|key| generate_nonce(key, context, message).unwrap())
+        .unzip();
+
+    let session = SigningSession::new(context, &public_nonces, message)?;
+    let signatures = secret_nonces
+        .into_iter()
+        .zip(keys)
+        .map(|(nonce, key)| session.sign(nonce, key))
+        .collect::<Result<Vec<PartialSignature>, MusigError>>()?;
+    session.aggregate(&signatures)
+}
+
+fn verify(signature: &[u8], message: &[u8], pubkey: &[u8]) -> bool {
+    Secp256k1::new()
+        .schnorrsig_verify(
+            &schnorrsig::Signature::from_slice(signature).unwrap(),
+            &Message::from_slice(message).unwrap(),
+            &schnorrsig::PublicKey::from_slice(pubkey).unwrap(),
+        )
+        .is_ok()
+}
+
+#[test]
+pub fn test_key_aggregation_vectors() {
+    // BIP327 key_agg_vectors.json
+    let pubkeys = [
+        "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        "03dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+        "023590a94e768f8e1815c2f24b4d80a8e3149316c3518ce7b7ad338368d038ca66",
+    ]
+    .map(|pubkey| hex::decode(pubkey).unwrap());
+    let vectors: [(&[usize], &str); 4] = [
+        (
+            &[0, 1, 2],
+            "90539eede565f5d054f32cc0c220126889ed1e5d193baf15aef344fe59d4610c",
+        ),
+        (
+            &[2, 1, 0],
+            "6204de8b083426dc6eaf9502d27024d53fc826bf7d2012148a0575435df54b2b",
+        ),
+        (
+            &[0, 0, 0],
+            "b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935",
+        ),
+        (
+            &[0, 0, 1, 1],
+            "69bc22bfa5d106306e48a20679de1d7389386124d07571d0d872686028c26a3e",
+        ),
+    ];
+
+    for (indices, expected) in vectors {
+        let keys: Vec<Vec<u8>> = indices.iter().map(|i| pubkeys[*i].clone()).collect();
+        let context = KeyAggContext::new(&keys).unwrap();
+        assert_eq!(expected, hex::encode(context.x_only_public_key()));
+        assert_eq!(keys, context.pubkeys());
+    }
+
+    assert!(matches!(
+        KeyAggContext::new(&[]),
+        Err(MusigError::InvalidKey(_))
+    ));
+    // x-only and uncompressed keys aren't listed
+    assert!(matches!(
+        KeyAggContext::new(&[pubkeys[0][1..].to_vec()]),
+        Err(MusigError::InvalidKey(_))
+    ));
+}
+
+#[test]
+pub fn test_musig_signing() {
+    let keys = keys();
+    let pubkeys: Vec<Vec<u8>> = keys
+        .iter()
+        .map(|key| musig_public_key(key).unwrap())
+        .collect();
+    let context = KeyAggContext::new(&pubkeys).unwrap();
+    let taproot = context.with_taproot_tweak().unwrap();
+    assert_ne!(context.x_only_public_key(), taproot.x_only_public_key());
+
+    // fresh nonces give other signatures, each valid for the aggregate key
+    for i in 0..4u8 {
+        let message = [i; 32];
+        let signature = sign(&keys, &taproot, &message).unwrap();
+        assert!(verify(&signature, &message, &taproot.x_only_public_key()));
+        assert!(!verify(&signature, &message, &context.x_only_public_key()));
+
+        let signature = sign(&keys, &context, &message).unwrap();
+        assert!(verify(&signature, &message, &context.x_only_public_key()));
+    }
+
+    // the same group with a single key missing signs for another key
+    let pair = KeyAggContext::new(&pubkeys[..2]).unwrap();
+    let message = [7; 32];
+    let signature = sign(&keys[..2], &pair, &message).unwrap();
+    assert!(verify(&signature, &message, &pair.x_only_public_key()));
+    assert!(!verify(&signature, &message, &context.x_only_public_key()));
+}
+
+#[test]
+pub fn test_musig_invalid_signers() {
+    let keys = keys();
+    let pubkeys: Vec<Vec<u8>> = keys
+        .iter()
+        .map(|key| musig_public_key(key).unwrap())
+        .collect();
+    let context = KeyAggContext::new(&pubkeys[..2])
+        .unwrap()
+        .with_taproot_tweak()
+        .unwrap();
+    let message = [1; 32];
+
+    let (first, first_public) = generate_nonce(&keys[0], &context, &message).unwrap();
+    let (second, second_public) = generate_nonce(&keys[1], &context, &message).unwrap();
+    let nonces = [first_public, second_public];
+    assert!(matches!(
+        SigningSession::new(&context, &nonces[..1], &message),
+        Err(MusigError::InvalidNonce(_))
+    ));
+    let session = SigningSession::new(&context, &nonces, &message).unwrap();
+
+    // a key outside the group, or signing with the nonce of another signer
+    let (outsider, _) = generate_nonce(&keys[2], &context, &message).unwrap();
+    assert!(matches!(
+        session.sign(outsider, &keys[2]),
+        Err(MusigError::UnknownSigner(_))
+    ));
+    let (stolen, _) = generate_nonce(&keys[0], &context, &message).unwrap();
+    assert!(matches!(
+        session.sign(stolen, &keys[1]),
+        Err(MusigError::UnknownSigner(_))
+    ));
+
+    let first = session.sign(first, &keys[0]).unwrap();
+    let second = session.sign(second, &keys[1]).unwrap();
+    assert!(session.verify(0, &first));
+    assert!(session.verify(1, &second));
+    assert!(!session.verify(1, &first));
+    assert!(!session.verify(2, &first));
+
+    // a signer sending a bad partial signature is caught before aggregation
+    assert!(matches!(
+        session.aggregate(&[first.clone(), first.clone()]),
+        Err(MusigError::InvalidPartialSignature(1))
+    ));
+    assert!(matches!(
+        session.aggregate(std::slice::from_ref(&first)),
+        Err(MusigError::InvalidSignature(_))
+    ));
+    let signature = session.aggregate(&[first, second.clone()]).unwrap();
+    assert!(verify(&signature, &message, &context.x_only_public_key()));
+
+    // nonces and partial signatures round trip through their bytes
+    assert_eq!(
+        nonces[0],
+        PublicNonce::from_bytes(&nonces[0].to_bytes()).unwrap()
+    );
+    assert_eq!(
+        second,
+        PartialSignature::from_bytes(&second.to_bytes()).unwrap()
+    );
+    assert!(matches!(
+        PublicNonce::from_bytes(&[5; 66]),
+        Err(MusigError::InvalidNonce(_))
+    ));
+    assert!(matches!(
+        PartialSignature::from_bytes(&[0xff; 32]),
+        Err(MusigError::InvalidSignature(_))
+    ));
+}
+
+#[test]
+pub fn test_nonce_with_entropy() {
+    let keys = keys();
+    let pubkeys: Vec<Vec<u8>> = keys
+        .iter()
+        .map(|key| musig_public_key(key).unwrap())
+        .collect();
+    let context = KeyAggContext::new(&pubkeys).unwrap();
+    let message = [1; 32];
+    let nonce = |seed: u64, message: &[u8]| {
+        generate_nonce_with(&keys[0], &context, message, &SeededEntropy::new(seed))
+            .unwrap()
+            .1
+    };
+
+    // the same randomness gives the same nonce for the same message only
+    assert_eq!(nonce(3, &message), nonce(3, &message));
+    assert_ne!(nonce(3, &message), nonce(4, &message));
+    assert_ne!(nonce(3, &message), nonce(3, &[2; 32]));
+}
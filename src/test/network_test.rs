@@ -0,0 +1,135 @@
+use crate::{base58check_decode, Key, KeyError, Network, Script};
+
+// the prefixes are const fns, these are checked when the tests compile
+const _: () = assert!(Network::Mainnet.wif_prefix() != Network::Testnet.wif_prefix());
+const _: () = assert!(Network::Mainnet.p2pkh_prefix() != Network::Testnet.p2pkh_prefix());
+const _: () = assert!(Network::Mainnet.p2sh_prefix() != Network::Testnet.p2sh_prefix());
+const _: () = assert!(Network::Testnet.p2pkh_prefix() != Network::Testnet.p2sh_prefix());
+const _: () = assert!(Network::Regtest.wif_prefix() == Network::Testnet.wif_prefix());
+const _: () = assert!(Network::Regtest.p2pkh_prefix() == Network::Testnet.p2pkh_prefix());
+
+#[test]
+pub fn test_network_names() {
+    for network in [Network::Mainnet, Network::Testnet, Network::Regtest] {
+        assert_eq!(network, network.to_string().parse().unwrap());
+    }
+    assert_eq!("main", Network::Mainnet.to_string());
+    assert_eq!(Network::Mainnet, "Mainnet".parse().unwrap());
+    assert_eq!(Network::Testnet, "TESTNET".parse().unwrap());
+    assert!(matches!(
+        "signet".parse::<Network>(),
+        Err(KeyError::UnknownNetwork(name)) if name == "signet"
+    ));
+}
+
+#[test]
+pub fn test_network_from_address() {
+    let addresses = [
+        ("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", Network::Mainnet),
+        ("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", Network::Mainnet),
+        (
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            Network::Mainnet,
+        ),
+        ("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", Network::Testnet),
+        ("2MzQwSSnBHWHqSAqtTVQ6v47XtaisrJa1Vc", Network::Testnet),
+        (
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            Network::Testnet,
+        ),
+        (
+            "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+            Network::Regtest,
+        ),
+    ];
+    for (address, network) in addresses {
+        assert_eq!(
+            network,
+            Network::from_address(address).unwrap(),
+            "{}",
+            address
+        );
+    }
+
+    assert!(Network::from_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3").is_err());
+    assert!(matches!(
+        Network::from_address("ltc1qw508d6qejxtdg4y5r3zarvary0c5xw7kgmn4n9"),
+        Err(KeyError::InvalidNetworkByte)
+    ));
+}
+
+#[test]
+pub fn test_network_prefixes() {
+    // the version bytes of addresses and keys decoded from their known encodings
+    assert_eq!(
+        Network::Mainnet.wif_prefix(),
+        bs58::decode("KyZpNDKnfs94vbrwhJneDi77V6jF64PWPF8x5cdJb8ifgg2DUc9d")
+            .into_vec()
+            .unwrap()[0]
+    );
+    assert_eq!(
+        Network::Mainnet.p2pkh_prefix(),
+        bs58::decode("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2")
+            .into_vec()
+            .unwrap()[0]
+    );
+    assert_eq!(
+        Network::Testnet.p2sh_prefix(),
+        bs58::decode("2MzQwSSnBHWHqSAqtTVQ6v47XtaisrJa1Vc")
+            .into_vec()
+            .unwrap()[0]
+    );
+    assert_eq!(
+        &Network::Testnet.xpub_version()[..],
+        &bs58::decode("tpubD6NzVbkrYhZ4XgiXtGrdW5XDAPFCL9h7we1vwNCpn8tGbBcgfVYjXyhWo4E1xkh56hjod1RhGjxbaTLV3X4FyWuejifB9jusQ46QzG87VKp")
+            .into_vec()
+            .unwrap()[..4]
+    );
+    assert_eq!(Network::Regtest.wif_prefix(), Network::Testnet.wif_prefix());
+    assert_eq!(1, Network::Regtest.coin_type());
+}
+
+#[test]
+pub fn test_network_prefixes_in_use() {
+    let seed = [7; 64];
+    let script = Script::from(vec![0x51]);
+
+    for network in [Network::Mainnet, Network::Testnet, Network::Regtest] {
+        let key = Key::from_seed(&seed, network, true).unwrap();
+        let version = |encoded: &str| base58check_decode(encoded).unwrap()[0];
+
+        assert_eq!(network.wif_prefix(), version(&key.to_wif()));
+        assert_eq!(network.p2pkh_prefix(), version(&key.address().unwrap()));
+        assert_eq!(
+            network.p2sh_prefix(),
+            version(&key.nested_segwit_address().unwrap())
+        );
+        assert_eq!(
+            network.p2sh_prefix(),
+            version(&script.p2sh_address(network))
+        );
+
+        let hrp = format!("{}1", network.bech32_hrp());
+        assert!(key.native_segwit_address().unwrap().starts_with(&hrp));
+        assert!(key.taproot_address().unwrap().starts_with(&hrp));
+        assert!(script.p2wsh_address(network).unwrap().starts_with(&hrp));
+
+        // read back through the same prefixes
+        let from_wif = Key::from_wif(key.to_wif()).unwrap();
+        assert_eq!(network.wif_prefix(), from_wif.network().wif_prefix());
+        let address = key.address().unwrap();
+        assert_eq!(
+            Some(address.clone()),
+            Script::from_address(&address, network)
+                .unwrap()
+                .to_address(network)
+        );
+        let address = key.native_segwit_address().unwrap();
+        assert_eq!(
+            Some(address.clone()),
+            Script::from_address(&address, network)
+                .unwrap()
+                .to_address(network)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    verify_ownership, AccountType, Network, OwnershipProof, ProofError, Wallet, WalletError,
+};
+
+fn restore(name: &str) -> Wallet {
+    let path = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&path);
+    Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path,
+        false,
+    )
+    .unwrap()
+}
+
+#[test]
+pub fn test_prove_ownership() {
+    let mut wallet = restore("waller_test_prove_ownership");
+    for account_type in [
+        AccountType::Legacy,
+        AccountType::NestedSegwit,
+        AccountType::NativeSegwit,
+        AccountType::Taproot,
+    ] {
+        let account = wallet.new_account(account_type).unwrap();
+        let address = wallet.new_receive_address(account).unwrap();
+
+        let proof = wallet
+            .prove_ownership(&address, "exchange challenge 8f2e")
+            .unwrap();
+        assert_eq!(address, proof.address);
+        assert_eq!("exchange challenge 8f2e", proof.message);
+        proof.verify(Network::Mainnet).unwrap();
+
+        // a proof only holds for its message and address
+        let other = wallet.new_receive_address(account).unwrap();
+        for (address, message) in [
+            (&address, "another challenge"),
+            (&other, &proof.message[..]),
+        ] {
+            assert!(
+                verify_ownership(address, message, &proof.signature, Network::Mainnet).is_err()
+            );
+        }
+    }
+
+    // native segwit gives the simple signature, a witness stack
+    let native = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(native).unwrap();
+    let simple = wallet.prove_ownership(&address, "").unwrap();
+    assert_eq!(
+        Some(&0x02),
+        base64::decode(&simple.signature).unwrap().first()
+    );
+}
+
+#[test]
+pub fn test_prove_ownership_errors() {
+    let mut wallet = restore("waller_test_prove_ownership_errors");
+    assert!(matches!(
+        wallet.prove_ownership("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l", "challenge"),
+        Err(WalletError::UnknownAddress(_))
+    ));
+
+    let account = wallet.new_account(AccountType::Legacy).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    let proof = wallet.prove_ownership(&address, "challenge").unwrap();
+    let forged = OwnershipProof {
+        signature: "not base64!".to_string(),
+        ..proof.clone()
+    };
+    assert!(matches!(
+        forged.verify(Network::Mainnet),
+        Err(ProofError::Malformed(_))
+    ));
+    assert!(matches!(
+        verify_ownership(
+            "not an address",
+            "challenge",
+            &proof.signature,
+            Network::Mainnet
+        ),
+        Err(ProofError::InvalidAddress(_))
+    ));
+    assert!(matches!(
+        proof.verify(Network::Testnet),
+        Err(ProofError::InvalidAddress(_))
+    ));
+}
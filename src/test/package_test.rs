@@ -0,0 +1,420 @@
+#![allow(unused_imports)]
+
+use std::{
+    cell::RefCell,
+    io::{Read, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+};
+
+use crate::{
+    silent_payment_tweak, Backend, BackendConfig, BackendError, BitcoinCoreRpc, FeeBucket, Key,
+    MempoolAcceptance, MempoolRejection, Network, OutPoint, PackageSubmission,
+    SilentPaymentAddress, SilentPaymentInput, Transaction, TransactionError, TransactionInput,
+    TransactionOutput, TransactionType, TxPackage, Wallet, WalletError,
+};
+
+fn test_key() -> Key {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    Key::new(mnemonic, Network::Mainnet, true).unwrap()
+}
+
+fn spend(utxo: TransactionOutput, tx_id: String, fee: i64) -> Transaction {
+    let value = utxo.value() - fee;
+    let input = TransactionInput::new(utxo, tx_id, 0);
+    let output = TransactionOutput::new(TransactionType::Pay2PubKeyHash, test_key(), value);
+    Transaction::new(
+        TransactionType::Pay2PubKeyHash,
+        vec![input],
+        vec![output],
+        None,
+    )
+}
+
+/// records what was submitted instead of relaying it
+#[derive(Default)]
+struct RecordingBackend {
+    submitted: RefCell<Vec<String>>,
+}
+
+impl Backend for RecordingBackend {
+    fn broadcast(&self, raw_tx: &str) -> Result<String, BackendError> {
+        self.submitted.borrow_mut().push(raw_tx.to_string());
+        Ok(String::new())
+    }
+
+    fn submit_package(&self, raw_txs: &[String]) -> Result<PackageSubmission, BackendError> {
+        self.submitted.borrow_mut().extend(raw_txs.iter().cloned());
+        Ok(PackageSubmission {
+            message: "success".to_string(),
+            results: vec![],
+        })
+    }
+}
+
+/// answer a single HTTP request with a canned JSON body
+fn serve_once(body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 4096];
+        let _ = stream.read(&mut buf).unwrap();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+    });
+
+    address
+}
+
+#[test]
+pub fn test_package_fee_rate_and_order() {
+    let funding = TransactionOutput::new(TransactionType::Pay2PubKeyHash, test_key(), 100_000);
+    let parent = spend(funding, "22".repeat(32), 100);
+
+    let child = spend(parent.get_output(0).unwrap().clone(), parent.tx_id(), 5_000);
+
+    let mut package = TxPackage::new(parent.clone());
+    package.add_child(child.clone()).unwrap();
+
+    assert_eq!(5_100, package.fee());
+    assert_eq!(parent.size() + child.size(), package.size());
+    assert!(package.fee_rate() > parent.fee() as f64 / parent.size() as f64);
+    assert_eq!(vec![parent.to_hex(), child.to_hex()], package.serialize());
+
+    let unrelated = spend(child.get_output(0).unwrap().clone(), child.tx_id(), 100);
+    assert!(matches!(
+        package.add_child(unrelated),
+        Err(TransactionError::NotAChild(_))
+    ));
+
+    let backend = RecordingBackend::default();
+    package.submit(&backend).unwrap();
+    assert_eq!(package.serialize(), *backend.submitted.borrow());
+}
+
+#[test]
+pub fn test_rpc_submit_package() {
+    let address = serve_once(
+        r#"{"result":{"package_msg":"success","tx-results":{"aa":{"txid":"bb","vsize":110}}},"error":null,"id":"waller"}"#,
+    );
+    let rpc = BitcoinCoreRpc::new(address, "user".to_string(), "pass".to_string());
+
+    let submission = rpc.submit_package(&["00".to_string()]).unwrap();
+    assert_eq!("success", submission.message);
+    assert_eq!("bb", submission.results[0].txid);
+    assert!(submission.results[0].error.is_none());
+}
+
+#[test]
+pub fn test_rpc_error() {
+    let address = serve_once(
+        r#"{"result":null,"error":{"code":-26,"message":"min relay fee not met"},"id":"waller"}"#,
+    );
+    let rpc = BitcoinCoreRpc::new(address, "user".to_string(), "pass".to_string());
+
+    match rpc.broadcast("00") {
+        Err(BackendError::Rpc { code, message }) => {
+            assert_eq!(-26, code);
+            assert_eq!("min relay fee not met", message);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+pub fn test_rpc_test_accept() {
+    let address = serve_once(
+        r#"{"result":[{"txid":"aa","wtxid":"bb","allowed":true,"vsize":141,"fees":{"base":0.00000282}}],"error":null,"id":"waller"}"#,
+    );
+    let rpc = BitcoinCoreRpc::new(address, "user".to_string(), "pass".to_string());
+
+    let acceptance = rpc.test_accept("00").unwrap();
+    assert_eq!(
+        MempoolAcceptance {
+            txid: "aa".to_string(),
+            allowed: true,
+            vsize: Some(141),
+            fee: Some(282),
+            reject_reason: None,
+        },
+        acceptance
+    );
+}
+
+/// answer each connection with the next of a list of bodies
+fn serve_each(bodies: Vec<String>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    thread::spawn(move || {
+        for body in bodies {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf).unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    address
+}
+
+#[test]
+pub fn test_rpc_silent_payment_tweaks() {
+    use super::silentpayment_test::{silent_payment_script, Spending};
+
+    let spending = vec![
+        (Spending::Legacy(3), OutPoint::new("11".repeat(32), 0)),
+        (Spending::Witness(4), OutPoint::new("22".repeat(32), 1)),
+    ];
+    let inputs: Vec<SilentPaymentInput> = spending
+        .iter()
+        .map(|(key, outpoint)| key.input(outpoint.clone()))
+        .collect();
+    let address = SilentPaymentAddress::new(
+        Network::Regtest,
+        &hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap(),
+        &hex::decode("02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5").unwrap(),
+    )
+    .unwrap();
+    let paid = silent_payment_script(&spending, &address, 0);
+    let expected =
+        silent_payment_tweak(&inputs, &[TransactionOutput::from_script(0, paid.clone())]).unwrap();
+
+    let vin: Vec<serde_json::Value> = inputs
+        .iter()
+        .map(|input| {
+            let mut vin = serde_json::json!({
+                "txid": input.previous_output.hash(),
+                "vout": input.previous_output.index(),
+                "scriptSig": {"hex": hex::encode(&input.script_sig)},
+                "prevout": {"scriptPubKey": {"hex": hex::encode(&input.script_pubkey)}},
+            });
+            if !input.witness.is_empty() {
+                vin["txinwitness"] = input.witness.iter().map(hex::encode).collect();
+            }
+            vin
+        })
+        .collect();
+    let block = |vin: Vec<serde_json::Value>| {
+        serde_json::json!({
+            "result": {"tx": [
+                {"txid": "cc".repeat(32), "vin": [{"coinbase": "03"}], "vout": [
+                    {"value": 50.0, "scriptPubKey": {"hex": hex::encode(&paid)}}
+                ]},
+                {"txid": "aa".repeat(32), "vin": vin, "vout": [
+                    {"value": 0.0003, "scriptPubKey": {"hex": hex::encode(&paid)}}
+                ]},
+            ]},
+            "error": null,
+            "id": "waller",
+        })
+        .to_string()
+    };
+    let hash = r#"{"result":"00","error":null,"id":"waller"}"#.to_string();
+
+    let rpc = BitcoinCoreRpc::new(
+        serve_each(vec![hash.clone(), block(vin.clone())]),
+        "user".to_string(),
+        "pass".to_string(),
+    );
+    let tweaks = rpc.silent_payment_tweaks(1).unwrap();
+    assert_eq!(1, tweaks.len());
+    assert_eq!(Some(&expected), tweaks.get(&"aa".repeat(32)));
+
+    // nodes before v23 answer without the outputs spent
+    let mut old = vin;
+    old.iter_mut().for_each(|vin| {
+        vin.as_object_mut().unwrap().remove("prevout");
+    });
+    let rpc = BitcoinCoreRpc::new(
+        serve_each(vec![hash, block(old)]),
+        "user".to_string(),
+        "pass".to_string(),
+    );
+    assert!(matches!(
+        rpc.silent_payment_tweaks(1),
+        Err(BackendError::Unsupported(_))
+    ));
+}
+
+#[test]
+pub fn test_rpc_mempool_histogram() {
+    let address = serve_once(
+        r#"{"result":{"aa":{"vsize":200,"fees":{"base":0.00005}},"bb":{"vsize":1000,"fees":{"base":0.00002}}},"error":null,"id":"waller"}"#,
+    );
+    let rpc = BitcoinCoreRpc::new(address, "user".to_string(), "pass".to_string());
+
+    let histogram = rpc.mempool_histogram().unwrap();
+    assert_eq!(
+        vec![
+            FeeBucket {
+                fee_rate: 20.0,
+                vsize: 200,
+            },
+            FeeBucket {
+                fee_rate: 2.0,
+                vsize: 1000,
+            },
+        ],
+        histogram.buckets
+    );
+    assert!(matches!(
+        RecordingBackend::default().mempool_histogram(),
+        Err(BackendError::Unsupported(_))
+    ));
+}
+
+#[test]
+pub fn test_mempool_rejection() {
+    assert_eq!(
+        MempoolRejection::InsufficientFee("min relay fee not met".to_string()),
+        MempoolRejection::from_reason("min relay fee not met")
+    );
+    assert_eq!(
+        MempoolRejection::Dust,
+        MempoolRejection::from_reason("dust")
+    );
+    assert_eq!(
+        MempoolRejection::NonStandard("tx-size".to_string()),
+        MempoolRejection::from_reason("tx-size")
+    );
+    assert!(matches!(
+        MempoolRejection::from_reason(
+            "mandatory-script-verify-flag-failed (Signature must be zero for failed CHECK(MULTI)SIG operation)"
+        ),
+        MempoolRejection::InvalidScript(_)
+    ));
+    assert_eq!(
+        MempoolRejection::Other("too-long-mempool-chain".to_string()),
+        MempoolRejection::from_reason("too-long-mempool-chain")
+    );
+
+    let wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        std::env::temp_dir().join("waller_test_mempool_rejection"),
+        false,
+    )
+    .unwrap();
+    let funding = TransactionOutput::new(TransactionType::Pay2PubKeyHash, test_key(), 100_000);
+    let tx = spend(funding, "22".repeat(32), 0);
+
+    let address = serve_once(
+        r#"{"result":[{"txid":"aa","wtxid":"bb","allowed":false,"reject-reason":"min relay fee not met"}],"error":null,"id":"waller"}"#,
+    );
+    let rpc = BitcoinCoreRpc::new(address, "user".to_string(), "pass".to_string());
+    assert!(matches!(
+        wallet.test_accept(&rpc, &tx),
+        Err(WalletError::Rejected(MempoolRejection::InsufficientFee(_)))
+    ));
+
+    // backends without a mempool
+    assert!(matches!(
+        wallet.test_accept(&RecordingBackend::default(), &tx),
+        Err(WalletError::Backend(BackendError::Unsupported(_)))
+    ));
+}
+
+/// A SOCKS5 proxy answering a single connection with a reply code, and
+/// standing in for the node it was asked to reach. Sends back the
+/// address type, host and port of the connect request
+fn socks5_once(reply: u8, body: &'static str) -> (String, mpsc::Receiver<(u8, Vec<u8>, u16)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut greeting = [0; 3];
+        stream.read_exact(&mut greeting).unwrap();
+        assert_eq!([5, 1, 0], greeting);
+        stream.write_all(&[5, 0]).unwrap();
+
+        let mut request = [0; 4];
+        stream.read_exact(&mut request).unwrap();
+        let mut host = match request[3] {
+            1 => vec![0; 4],
+            _ => {
+                let mut length = [0; 1];
+                stream.read_exact(&mut length).unwrap();
+                vec![0; length[0] as usize]
+            }
+        };
+        stream.read_exact(&mut host).unwrap();
+        let mut port = [0; 2];
+        stream.read_exact(&mut port).unwrap();
+        sender
+            .send((request[3], host, u16::from_be_bytes(port)))
+            .unwrap();
+
+        stream
+            .write_all(&[5, reply, 0, 1, 127, 0, 0, 1, 0, 0])
+            .unwrap();
+        if reply != 0 {
+            return;
+        }
+        let mut buf = [0; 4096];
+        let _ = stream.read(&mut buf).unwrap();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+    });
+
+    (address, receiver)
+}
+
+#[test]
+pub fn test_rpc_over_socks5() {
+    let onion = "waller2ixmnvqtnhbdp6lvzypkiyxtnu3rnqzpa7rpdpfyspdcx7yad.onion";
+    let (proxy, requested) = socks5_once(0, r#"{"result":812345,"error":null,"id":"waller"}"#);
+    let mut rpc = BitcoinCoreRpc::new(
+        format!("{}:8332", onion),
+        "user".to_string(),
+        "pass".to_string(),
+    );
+    rpc.set_config(BackendConfig::socks5(&proxy));
+
+    // the onion host is resolved by the proxy
+    assert_eq!(812345, rpc.tip_height().unwrap());
+    let (address_type, host, port) = requested.recv().unwrap();
+    assert_eq!(3, address_type);
+    assert_eq!(onion.as_bytes(), &host[..]);
+    assert_eq!(8332, port);
+
+    let (proxy, requested) = socks5_once(5, "");
+    let mut rpc = BitcoinCoreRpc::new(
+        "10.0.0.1:8332".to_string(),
+        "user".to_string(),
+        "pass".to_string(),
+    );
+    rpc.set_config(BackendConfig::socks5(&proxy));
+    assert!(matches!(rpc.tip_height(), Err(BackendError::Connection(_))));
+    assert_eq!((1, vec![10, 0, 0, 1], 8332), requested.recv().unwrap());
+
+    // onion services aren't looked up without a proxy
+    let rpc = BitcoinCoreRpc::new(
+        format!("{}:8332", onion),
+        "user".to_string(),
+        "pass".to_string(),
+    );
+    assert!(BackendConfig::default().proxy().is_none());
+    assert!(matches!(rpc.tip_height(), Err(BackendError::Connection(_))));
+}
@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use crate::{AccountType, Key, KeyError, Network, PaperWallet, Wallet, WalletError};
+
+const WIF: &str = "L44B5gGEpqEDRS9vVPz7QT35jcBG2r3CZwSwQ4fCewXAhAhqGVpP";
+
+#[test]
+pub fn test_paper_wallet() {
+    let key = Key::from_wif(WIF.to_string()).unwrap();
+
+    let paper = PaperWallet::new(&key, AccountType::NativeSegwit, None).unwrap();
+    assert!(!paper.encrypted);
+    assert_eq!(WIF, paper.private_key);
+    assert_eq!(WIF, paper.private_key_qr);
+    assert_eq!(key.native_segwit_address().unwrap(), paper.address);
+    assert_eq!(format!("bitcoin:{}", paper.address), paper.address_qr);
+    assert_eq!(WIF, paper.verify(None).unwrap().to_wif());
+
+    // the layout survives a round trip through a template's data format
+    let json = serde_json::to_string(&paper).unwrap();
+    let parsed: PaperWallet = serde_json::from_str(&json).unwrap();
+    assert_eq!(paper.address, parsed.address);
+    assert_eq!(paper.public_key, parsed.public_key);
+
+    let mut tampered = paper.clone();
+    tampered.address = key.nested_segwit_address().unwrap();
+    assert!(matches!(tampered.verify(None), Err(KeyError::Other(_))));
+}
+
+#[test]
+pub fn test_encrypted_paper_wallet() {
+    let key = Key::from_wif(WIF.to_string()).unwrap();
+
+    let paper = PaperWallet::new(&key, AccountType::Legacy, Some("TestingOneTwoThree")).unwrap();
+    assert!(paper.encrypted);
+    // BIP38
+    assert_eq!(
+        "6PYNKZ1EAgYgmQfmNVamxyXVWHzK5s6DGhwP4J5o44cvXdoY7sRzhtpUeo",
+        paper.private_key
+    );
+    assert_eq!(paper.private_key, paper.private_key_qr);
+
+    assert!(matches!(
+        paper.verify(None),
+        Err(KeyError::IncorrectPassphrase)
+    ));
+    assert!(matches!(
+        paper.verify(Some("Satoshi")),
+        Err(KeyError::IncorrectPassphrase)
+    ));
+    assert_eq!(
+        WIF,
+        paper.verify(Some("TestingOneTwoThree")).unwrap().to_wif()
+    );
+}
+
+#[test]
+pub fn test_wallet_paper_export() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+
+    let paper = wallet.export_paper_wallet(&address, None).unwrap();
+    assert_eq!(AccountType::NativeSegwit, paper.account_type);
+    assert_eq!(address, paper.address);
+    assert_eq!(
+        wallet.get_address(address.clone()).unwrap().to_wif(),
+        paper.verify(None).unwrap().to_wif()
+    );
+
+    assert!(matches!(
+        wallet.export_paper_wallet("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", None),
+        Err(WalletError::UnknownAddress(_))
+    ));
+}
@@ -0,0 +1,450 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Read, Write},
+    net::TcpListener,
+    path::PathBuf,
+    thread,
+};
+
+use crate::{
+    encode_psbt, AccountType, BackendConfig, Network, OutPoint, PayjoinClient, PayjoinError,
+    PayjoinParams, Psbt, Script, Transaction, TransactionBuilder, TransactionInput,
+    TransactionOutput, TransactionType, TxOrdering, Utxo, Wallet,
+};
+
+const SENDER: &str =
+    "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset";
+const RECEIVER: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+const ENDPOINT: &str = "https://example.com/pj";
+
+fn wallet(mnemonic: &str) -> Wallet {
+    let mut wallet = Wallet::restore(
+        mnemonic.to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    wallet.new_account(AccountType::NativeSegwit).unwrap();
+    wallet
+}
+
+/// a new receive address of the wallet, funded with a coin
+fn funded(wallet: &mut Wallet, tx_id: &str, value: i64) -> TransactionInput {
+    let address = wallet.new_receive_address(0).unwrap();
+    let output = pay_to(&address, value);
+    let utxo = Utxo::new(OutPoint::new(tx_id.repeat(32), 0), output, address);
+    wallet.add_utxo(utxo.clone()).unwrap();
+    utxo.to_input()
+}
+
+fn pay_to(address: &str, value: i64) -> TransactionOutput {
+    let script = Script::from_address(address, Network::Mainnet).unwrap();
+    TransactionOutput::from_script(value, script.into_bytes())
+}
+
+/// a sender paying 50_000 to the receiver from a coin of 100_000, its
+/// change is the second output, with a fee of 1_000
+struct Payjoin {
+    sender: Wallet,
+    receiver: Wallet,
+    payee: String,
+    change: String,
+    original: Transaction,
+    /// the coin the receiver adds, of 30_000
+    coin: TransactionInput,
+}
+
+impl Payjoin {
+    fn new() -> Self {
+        let mut sender = wallet(SENDER);
+        let mut receiver = wallet(RECEIVER);
+        let spent = funded(&mut sender, "11", 100_000);
+        let coin = funded(&mut receiver, "22", 30_000);
+        let payee = receiver.new_receive_address(0).unwrap();
+        let change = sender.new_change_address(0).unwrap();
+
+        let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+        builder
+            .ordering(TxOrdering::Insertion)
+            .add_input(spent)
+            .add_output(pay_to(&payee, 50_000))
+            .add_output(pay_to(&change, 49_000));
+        let mut original = builder.build().unwrap();
+        assert_eq!(1, sender.sign_transaction(&mut original).unwrap());
+
+        Self {
+            sender,
+            receiver,
+            payee,
+            change,
+            original,
+            coin,
+        }
+    }
+
+    fn params(&self) -> PayjoinParams {
+        PayjoinParams::new(&self.payee, ENDPOINT).unwrap()
+    }
+
+    /// The proposal of a receiver adding its coin to the inputs, of the
+    /// outputs given, with the inputs of the sender unsigned
+    fn proposal(&self, inputs: Vec<TransactionInput>, outputs: Vec<TransactionOutput>) -> String {
+        let mut unsigned: Vec<TransactionInput> = self
+            .original
+            .inputs()
+            .iter()
+            .map(|input| {
+                let utxo = TransactionOutput::from_script(
+                    input.utxo_value(),
+                    input.utxo_pk_script().to_vec(),
+                );
+                let outpoint = input.previous_output();
+                TransactionInput::new(utxo, outpoint.hash(), outpoint.index())
+            })
+            .collect();
+        unsigned.extend(inputs);
+        let mut tx = Transaction::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            unsigned,
+            outputs,
+            None,
+        );
+        self.receiver.sign_transaction(&mut tx).unwrap();
+        encode_psbt(&tx, &HashMap::new())
+    }
+
+    /// the proposal paying the coin to the receiver, taking a contribution from the change
+    fn paying(&self, contribution: i64) -> String {
+        self.proposal(
+            vec![self.coin.clone()],
+            vec![
+                pay_to(&self.payee, 80_000),
+                pay_to(&self.change, 49_000 - contribution),
+            ],
+        )
+    }
+}
+
+fn invalid_proposal(result: Result<Transaction, PayjoinError>) -> String {
+    match result {
+        Err(PayjoinError::InvalidProposal(error)) => error,
+        result => panic!("expected an invalid proposal, got {:?}", result),
+    }
+}
+
+#[test]
+fn test_payjoin_request() {
+    let payjoin = Payjoin::new();
+    let sender = &payjoin.sender;
+
+    let request = sender
+        .payjoin_request(&payjoin.original, payjoin.params())
+        .unwrap();
+    assert_eq!(format!("{}?v=1", ENDPOINT), request.url());
+    let params = payjoin
+        .params()
+        .with_fee_contribution(500, 1)
+        .with_min_fee_rate(2.5)
+        .without_output_substitution();
+    let request = sender.payjoin_request(&payjoin.original, params).unwrap();
+    assert_eq!(
+        format!(
+            "{}?v=1&additionalfeeoutputindex=1&maxadditionalfeecontribution=500&minfeerate=2.5&disableoutputsubstitution=true",
+            ENDPOINT
+        ),
+        request.url()
+    );
+    let with_query = PayjoinParams::new(&payjoin.payee, "https://example.com/pj?id=7").unwrap();
+    let request = sender
+        .payjoin_request(&payjoin.original, with_query)
+        .unwrap();
+    assert_eq!("https://example.com/pj?id=7&v=1", request.url());
+
+    // the original is posted finalized, with the coins it spends
+    let psbt = Psbt::decode(request.psbt(), Network::Mainnet).unwrap();
+    assert_eq!(payjoin.original.tx_id(), psbt.tx.tx_id);
+    let input = &psbt.inputs[0];
+    assert_eq!(Some(100_000), input.utxo.as_ref().map(|utxo| utxo.value()));
+    assert_eq!(
+        payjoin.original.inputs()[0].witness(),
+        input.final_witness.as_ref().unwrap()
+    );
+    assert!(input.final_script_sig.is_none() && !input.partial_sigs);
+    assert_eq!(2, psbt.outputs.len());
+    assert!(psbt.outputs.iter().all(|output| !output.key_paths));
+
+    // https, or http to an onion service
+    assert!(PayjoinParams::new(&payjoin.payee, "http://example.onion/pj").is_ok());
+    for endpoint in ["http://example.com/pj", "example.com/pj", "https:///pj"] {
+        assert!(matches!(
+            PayjoinParams::new(&payjoin.payee, endpoint),
+            Err(PayjoinError::InvalidEndpoint(_))
+        ));
+    }
+
+    let invalid_original = |original: &Transaction, params: PayjoinParams| {
+        matches!(
+            sender.payjoin_request(original, params),
+            Err(PayjoinError::InvalidOriginal(_))
+        )
+    };
+    // fees come out of an output of the sender
+    assert!(invalid_original(
+        &payjoin.original,
+        payjoin.params().with_fee_contribution(500, 0)
+    ));
+    assert!(invalid_original(
+        &payjoin.original,
+        payjoin.params().with_fee_contribution(500, 2)
+    ));
+    // no output pays the address
+    let other = PayjoinParams::new("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", ENDPOINT).unwrap();
+    assert!(invalid_original(&payjoin.original, other));
+    // the original is signed
+    let mut unsigned = payjoin.original.clone();
+    unsigned.set_witness(0, vec![]).unwrap();
+    assert!(invalid_original(&unsigned, payjoin.params()));
+}
+
+#[test]
+fn test_payjoin_proposal() {
+    let payjoin = Payjoin::new();
+    let sender = &payjoin.sender;
+    let params = payjoin.params().with_fee_contribution(450, 1);
+    let request = sender
+        .payjoin_request(&payjoin.original, params.clone())
+        .unwrap();
+
+    // the receiver takes 300 from the change for the fee of its input
+    let tx = sender
+        .process_payjoin_proposal(&request, &payjoin.paying(300))
+        .unwrap();
+    assert_eq!(2, tx.tx_in_count());
+    assert!(tx.inputs().iter().all(|input| input.witness().len() == 2));
+    assert_eq!(1_300, tx.fee());
+    assert_eq!(48_700, tx.outputs()[1].value());
+    assert!(tx.size() > payjoin.original.size());
+
+    // more than the most the sender lets it take
+    let error = invalid_proposal(sender.process_payjoin_proposal(&request, &payjoin.paying(460)));
+    assert_eq!("the fee output pays more than the contribution", error);
+    // more than the input added costs at the rate of the original, 1000/141 * 68
+    let generous = sender
+        .payjoin_request(
+            &payjoin.original,
+            payjoin.params().with_fee_contribution(1_000, 1),
+        )
+        .unwrap();
+    assert!(sender
+        .process_payjoin_proposal(&generous, &payjoin.paying(480))
+        .is_ok());
+    let error = invalid_proposal(sender.process_payjoin_proposal(&generous, &payjoin.paying(500)));
+    assert_eq!(
+        "the contribution pays for more than the inputs added",
+        error
+    );
+    // without a contribution the change is left as it is
+    let plain = sender
+        .payjoin_request(&payjoin.original, payjoin.params())
+        .unwrap();
+    assert!(sender
+        .process_payjoin_proposal(&plain, &payjoin.paying(0))
+        .is_ok());
+    let error = invalid_proposal(sender.process_payjoin_proposal(&plain, &payjoin.paying(100)));
+    assert_eq!("an output of the sender changed", error);
+
+    // the receiver keeps fees it pays from falling under the original
+    let error = invalid_proposal(sender.process_payjoin_proposal(
+        &request,
+        &payjoin.proposal(
+            vec![payjoin.coin.clone()],
+            vec![
+                pay_to(&payjoin.payee, 81_000),
+                pay_to(&payjoin.change, 49_000),
+            ],
+        ),
+    ));
+    assert_eq!("the proposal pays less fee than the original", error);
+    // an unsigned input of the receiver
+    let mut foreign = TransactionInput::new(
+        pay_to("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", 30_000),
+        "33".repeat(32),
+        0,
+    );
+    foreign.set_sequence(payjoin.original.inputs()[0].sequence());
+    let error = invalid_proposal(sender.process_payjoin_proposal(
+        &request,
+        &payjoin.proposal(
+            vec![foreign],
+            vec![
+                pay_to(&payjoin.payee, 80_000),
+                pay_to(&payjoin.change, 49_000),
+            ],
+        ),
+    ));
+    assert_eq!("an input of the receiver isn't signed", error);
+    // the input of the sender is dropped
+    let mut receiver_only = payjoin.proposal(vec![], vec![]);
+    let psbt = Psbt::decode(&receiver_only, Network::Mainnet).unwrap();
+    assert_eq!(1, psbt.tx.inputs.len());
+    let mut tx = Transaction::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        vec![payjoin.coin.clone()],
+        vec![pay_to(&payjoin.payee, 29_000)],
+        None,
+    );
+    payjoin.receiver.sign_transaction(&mut tx).unwrap();
+    receiver_only = encode_psbt(&tx, &HashMap::new());
+    let error = invalid_proposal(sender.process_payjoin_proposal(&request, &receiver_only));
+    assert_eq!("an input of the sender is missing", error);
+    // the original itself, signed by the sender
+    let error = invalid_proposal(
+        sender.process_payjoin_proposal(&request, &encode_psbt(&payjoin.original, &HashMap::new())),
+    );
+    assert_eq!("an input of the sender is signed", error);
+    // not a PSBT
+    assert!(matches!(
+        sender.process_payjoin_proposal(&request, "cHNidP8="),
+        Err(PayjoinError::InvalidPsbt(_))
+    ));
+    assert!(matches!(
+        sender.process_payjoin_proposal(&request, request.original().to_hex().as_str()),
+        Err(PayjoinError::InvalidPsbt(_))
+    ));
+}
+
+#[test]
+fn test_payjoin_output_substitution() {
+    let mut payjoin = Payjoin::new();
+    let substituted = payjoin.receiver.new_receive_address(0).unwrap();
+    let sender = &payjoin.sender;
+    let proposal = payjoin.proposal(
+        vec![payjoin.coin.clone()],
+        vec![
+            pay_to(&substituted, 80_000),
+            pay_to(&payjoin.change, 49_000),
+        ],
+    );
+
+    // the receiver may pay itself at another address, and add outputs
+    let request = sender
+        .payjoin_request(&payjoin.original, payjoin.params())
+        .unwrap();
+    assert!(sender.process_payjoin_proposal(&request, &proposal).is_ok());
+    let added = payjoin.proposal(
+        vec![payjoin.coin.clone()],
+        vec![
+            pay_to(&payjoin.payee, 50_000),
+            pay_to(&payjoin.change, 49_000),
+            pay_to(&substituted, 30_000),
+        ],
+    );
+    assert!(sender.process_payjoin_proposal(&request, &added).is_ok());
+
+    // unless the sender disables it, then the output can only grow
+    let request = sender
+        .payjoin_request(
+            &payjoin.original,
+            payjoin.params().without_output_substitution(),
+        )
+        .unwrap();
+    let error = invalid_proposal(sender.process_payjoin_proposal(&request, &proposal));
+    assert_eq!("the output of the receiver changed", error);
+    assert!(sender.process_payjoin_proposal(&request, &added).is_ok());
+    assert!(sender
+        .process_payjoin_proposal(&request, &payjoin.paying(0))
+        .is_ok());
+
+    // a min fee rate the payjoin pays under, 1000 over about 209 vbytes
+    let request = sender
+        .payjoin_request(&payjoin.original, payjoin.params().with_min_fee_rate(5.0))
+        .unwrap();
+    let error = invalid_proposal(sender.process_payjoin_proposal(&request, &payjoin.paying(0)));
+    assert_eq!("the fee rate is under the minimum", error);
+}
+
+/// answers every post with a body, recording the urls posted to
+struct Receiver {
+    response: String,
+    urls: RefCell<Vec<String>>,
+}
+
+impl PayjoinClient for Receiver {
+    fn post(&self, url: &str, _: &str) -> Result<String, PayjoinError> {
+        self.urls.borrow_mut().push(url.to_string());
+        Ok(self.response.clone())
+    }
+}
+
+/// an http server answering one request with a status and body, returning its address
+fn serve(status: &'static str, body: String) -> (String, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = vec![0; 4096];
+        let read = stream.read(&mut request).unwrap();
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+        String::from_utf8_lossy(&request[..read]).to_string()
+    });
+    (address, handle)
+}
+
+#[test]
+fn test_send_payjoin() {
+    let payjoin = Payjoin::new();
+    let receiver = Receiver {
+        response: payjoin.paying(0),
+        urls: RefCell::new(vec![]),
+    };
+    let tx = payjoin
+        .sender
+        .send_payjoin(&payjoin.original, payjoin.params(), &receiver)
+        .unwrap();
+    assert_eq!(2, tx.tx_in_count());
+    assert_eq!(vec![format!("{}?v=1", ENDPOINT)], *receiver.urls.borrow());
+
+    // posted as text over http, the proposal is the body
+    let config = BackendConfig::direct();
+    let (address, handle) = serve("200 OK", payjoin.paying(0));
+    let url = format!("http://{}/pj?v=1", address);
+    let response = config.post(&url, "cHNidP8=").unwrap();
+    assert_eq!(payjoin.paying(0), response);
+    let request = handle.join().unwrap();
+    assert!(request.starts_with("POST /pj?v=1 HTTP/1.1\r\n"));
+    assert!(request.contains("Content-Type: text/plain\r\n"));
+    assert!(request.ends_with("\r\n\r\ncHNidP8="));
+
+    // the errors of BIP78
+    let error = r#"{"errorCode": "not-enough-money", "message": "The receiver added no inputs"}"#;
+    let (address, handle) = serve("400 Bad Request", error.to_string());
+    match config.post(&format!("http://{}", address), "cHNidP8=") {
+        Err(PayjoinError::Receiver { code, message }) => {
+            assert_eq!("not-enough-money", code);
+            assert_eq!("The receiver added no inputs", message);
+        }
+        result => panic!("expected a receiver error, got {:?}", result),
+    }
+    assert!(handle.join().unwrap().starts_with("POST / HTTP/1.1\r\n"));
+    let (address, handle) = serve("500 Internal Server Error", String::new());
+    assert!(matches!(
+        config.post(&format!("http://{}", address), "cHNidP8="),
+        Err(PayjoinError::Connection(_))
+    ));
+    handle.join().unwrap();
+    // a TLS client posts to https
+    assert!(matches!(
+        config.post(ENDPOINT, "cHNidP8="),
+        Err(PayjoinError::InvalidEndpoint(_))
+    ));
+}
@@ -0,0 +1,230 @@
+use std::str::FromStr;
+
+use secp256k1::{Message, PublicKey, Secp256k1, Signature};
+
+use crate::{
+    compress_public_key, sha256_hash, Key, MultisigScript, Policy, PolicyError, Satisfier,
+    SigHashType, SighashCache, Transaction, TransactionInput, TransactionOutput, TransactionType,
+    TransactionVersion, SIGHASH_ALL,
+};
+
+const WIFS: [&str; 3] = [
+    "L57KYn5isHFThD4cohjJgLTZA2vaxnMMKWngnzbttF159yH9dARf",
+    "KyZpNDKnfs94vbrwhJneDi77V6jF64PWPF8x5cdJb8ifgg2DUc9d",
+    "KyRv5iFPHG7iB5E4CqvMzH3WFJVhbfYK4VY7XAedd9Ys69mEsPLQ",
+];
+
+fn key(index: usize) -> Key {
+    Key::from_wif(WIFS[index].to_string()).unwrap()
+}
+
+fn pubkey(index: usize) -> String {
+    hex::encode(compress_public_key(&key(index).new_public_key().unwrap()).unwrap())
+}
+
+/// a satisfier holding a placeholder signature for each key given
+fn signed_by(keys: &[usize]) -> Satisfier {
+    let mut satisfier = Satisfier::default();
+    for index in keys.iter() {
+        let pubkey = hex::decode(pubkey(*index)).unwrap();
+        satisfier.signatures.insert(pubkey, vec![*index as u8; 72]);
+    }
+    satisfier
+}
+
+#[test]
+pub fn test_policy_parsing() {
+    let text = format!("and(pk({}),older(1000))", pubkey(0));
+    let policy = Policy::from_str(&text).unwrap();
+    assert_eq!(text, policy.to_string());
+
+    let spaced = format!(
+        "thresh(2, pk({}), pk({}), or(pk({}), after(800000)))",
+        pubkey(0),
+        pubkey(1),
+        pubkey(2)
+    );
+    let policy = Policy::from_str(&spaced).unwrap();
+    assert_eq!(spaced.replace(' ', ""), policy.to_string());
+    assert_eq!(3, policy.keys().len());
+
+    assert!(matches!(
+        Policy::from_str("pk(02aa)"),
+        Err(PolicyError::InvalidKey(_))
+    ));
+    assert!(matches!(
+        Policy::from_str("older(0)"),
+        Err(PolicyError::InvalidTimelock(_))
+    ));
+    assert!(matches!(
+        Policy::from_str(&format!("thresh(3,pk({}),pk({}))", pubkey(0), pubkey(1))),
+        Err(PolicyError::InvalidThreshold(3, 2))
+    ));
+    assert!(matches!(
+        Policy::from_str(&format!("and(pk({}))", pubkey(0))),
+        Err(PolicyError::Parse(_))
+    ));
+    assert!(matches!(
+        Policy::from_str("older(10) older(20)"),
+        Err(PolicyError::Parse(_))
+    ));
+}
+
+#[test]
+pub fn test_policy_compilation() {
+    let policy = Policy::from_str(&format!("and(pk({}),older(1000))", pubkey(0))).unwrap();
+    // <key> CHECKSIGVERIFY <1000> CHECKSEQUENCEVERIFY
+    assert_eq!(
+        format!("21{}ad02e803b2", pubkey(0)),
+        hex::encode(policy.compile().as_bytes())
+    );
+
+    let policy = Policy::from_str(&format!("or(pk({}),pk({}))", pubkey(0), pubkey(1))).unwrap();
+    // IF <key> CHECKSIG ELSE <key> CHECKSIG ENDIF
+    assert_eq!(
+        format!("6321{}ac6721{}ac68", pubkey(0), pubkey(1)),
+        hex::encode(policy.compile().as_bytes())
+    );
+
+    // a threshold of keys is a plain multisig
+    let policy = Policy::from_str(&format!(
+        "thresh(2,pk({}),pk({}),pk({}))",
+        pubkey(0),
+        pubkey(1),
+        pubkey(2)
+    ))
+    .unwrap();
+    let multisig = MultisigScript::new(
+        2,
+        (0..3)
+            .map(|index| hex::decode(pubkey(index)).unwrap())
+            .collect(),
+    )
+    .unwrap();
+    assert_eq!(multisig.to_script(), policy.compile());
+}
+
+#[test]
+pub fn test_policy_satisfaction() {
+    let policy = Policy::from_str(&format!("and(pk({}),older(1000))", pubkey(0))).unwrap();
+    let mut satisfier = signed_by(&[0]);
+    satisfier.sequence = 999;
+    assert_eq!(None, policy.satisfy(&satisfier));
+    satisfier.sequence = 1000;
+    assert_eq!(Some(vec![vec![0; 72]]), policy.satisfy(&satisfier));
+    // a disabled relative lock time never satisfies
+    satisfier.sequence = 0xffffffff;
+    assert_eq!(None, policy.satisfy(&satisfier));
+
+    // the cheaper branch is picked, the selector is on top
+    let policy = Policy::from_str(&format!(
+        "or(and(pk({}),pk({})),pk({}))",
+        pubkey(0),
+        pubkey(1),
+        pubkey(2)
+    ))
+    .unwrap();
+    assert_eq!(
+        Some(vec![vec![1; 72], vec![0; 72], vec![1]]),
+        policy.satisfy(&signed_by(&[0, 1]))
+    );
+    assert_eq!(
+        Some(vec![vec![2; 72], vec![]]),
+        policy.satisfy(&signed_by(&[0, 1, 2]))
+    );
+    assert_eq!(None, policy.satisfy(&signed_by(&[0])));
+
+    // a mixed threshold selects policies with 1 and skips them with an empty element
+    let preimage = vec![7; 32];
+    let policy = Policy::from_str(&format!(
+        "thresh(2,pk({}),sha256({}),after(800000))",
+        pubkey(0),
+        hex::encode(sha256_hash(&preimage))
+    ))
+    .unwrap();
+    let mut satisfier = signed_by(&[0]);
+    satisfier.lock_time = 800_000;
+    assert_eq!(
+        Some(vec![vec![1], vec![], vec![0; 72], vec![1]]),
+        policy.satisfy(&satisfier)
+    );
+    satisfier.signatures.clear();
+    satisfier
+        .preimages
+        .insert(sha256_hash(&preimage), preimage.clone());
+    assert_eq!(
+        Some(vec![vec![1], preimage, vec![1], vec![]]),
+        policy.satisfy(&satisfier)
+    );
+    // lock times in blocks aren't satisfied by a timestamp
+    satisfier.lock_time = 1_700_000_000;
+    assert_eq!(None, policy.satisfy(&satisfier));
+}
+
+#[test]
+pub fn test_policy_analysis() {
+    let safe = format!("or(pk({}),and(pk({}),older(144)))", pubkey(0), pubkey(1));
+    assert!(Policy::from_str(&safe).unwrap().requires_signature());
+
+    let unsafe_policy = format!("or(pk({}),older(144))", pubkey(0));
+    assert!(!Policy::from_str(&unsafe_policy)
+        .unwrap()
+        .requires_signature());
+
+    let threshold = format!("thresh(2,pk({}),older(144),after(800000))", pubkey(0));
+    assert!(!Policy::from_str(&threshold).unwrap().requires_signature());
+}
+
+#[test]
+pub fn test_policy_spend() {
+    let policy = Policy::from_str(&format!(
+        "or(pk({}),and(pk({}),older(144)))",
+        pubkey(0),
+        pubkey(1)
+    ))
+    .unwrap();
+    let witness_script = policy.compile();
+
+    let utxo = TransactionOutput::from_script(100_000, witness_script.to_p2wsh().into_bytes());
+    let mut input = TransactionInput::new(utxo, "44".repeat(32), 0);
+    input.set_sequence(144);
+    let output = TransactionOutput::new(TransactionType::Pay2PubKeyHash, key(0), 90_000);
+    let mut tx = Transaction::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        vec![input],
+        vec![output],
+        None,
+    );
+    tx.set_version(TransactionVersion::Two);
+
+    // the second key alone can spend once the timelock has passed
+    let signature = tx
+        .sign_p2wsh_input(0, &witness_script, &key(1), SigHashType::All)
+        .unwrap();
+    let mut satisfier = Satisfier {
+        sequence: 144,
+        ..Satisfier::default()
+    };
+    satisfier
+        .signatures
+        .insert(hex::decode(pubkey(1)).unwrap(), signature.clone());
+
+    let items = policy.satisfy(&satisfier).unwrap();
+    assert_eq!(vec![signature.clone(), vec![]], items);
+    tx.set_p2wsh_witness(0, &witness_script, items).unwrap();
+
+    let witness = tx.get_input(0).unwrap().witness();
+    assert_eq!(witness_script.as_bytes(), witness[2].as_slice());
+
+    let sighash = SighashCache::new()
+        .segwit_v0_sighash(&tx, 0, witness_script.as_bytes(), 100_000, SIGHASH_ALL)
+        .unwrap();
+    let (_, der) = signature.split_last().unwrap();
+    assert!(Secp256k1::verification_only()
+        .verify(
+            &Message::from_slice(&sighash).unwrap(),
+            &Signature::from_der(der).unwrap(),
+            &PublicKey::from_slice(&hex::decode(pubkey(1)).unwrap()).unwrap(),
+        )
+        .is_ok());
+}
@@ -0,0 +1,184 @@
+use std::path::PathBuf;
+
+use crate::{
+    AccountType, Network, OutPoint, PrivacyWarning, Script, ScriptType, TransactionBuilder,
+    TransactionInput, TransactionOutput, TransactionType, TxOrdering, Utxo, Wallet,
+};
+
+/// a new receive address of an account, funded with a coin of each transaction
+fn funded(
+    wallet: &mut Wallet,
+    account: u32,
+    coins: &[(&str, i64)],
+) -> (String, Vec<TransactionInput>) {
+    let address = wallet.new_receive_address(account).unwrap();
+    let inputs = coins
+        .iter()
+        .map(|(tx_id, value)| {
+            let script = Script::from_address(&address, Network::Mainnet).unwrap();
+            let output = TransactionOutput::from_script(*value, script.into_bytes());
+            let utxo = Utxo::new(OutPoint::new(tx_id.repeat(32), 0), output, address.clone());
+            wallet.add_utxo(utxo.clone()).unwrap();
+            utxo.to_input()
+        })
+        .collect();
+    (address, inputs)
+}
+
+fn pay_to(address: &str, value: i64) -> TransactionOutput {
+    let script = Script::from_address(address, Network::Mainnet).unwrap();
+    TransactionOutput::from_script(value, script.into_bytes())
+}
+
+#[test]
+pub fn test_privacy_report() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let segwit = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let legacy = wallet.new_account(AccountType::Legacy).unwrap();
+    let recipient = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+    let (reused, reused_coins) = funded(&mut wallet, segwit, &[("11", 60_000), ("22", 40_000)]);
+    let (old, old_coins) = funded(&mut wallet, legacy, &[("33", 50_000)]);
+    let (fresh, fresh_coins) = funded(&mut wallet, segwit, &[("44", 80_000)]);
+    let change = wallet.new_change_address(segwit).unwrap();
+
+    // a fresh coin paying a recipient an uneven amount gives nothing away
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder
+        .ordering(TxOrdering::Insertion)
+        .add_input(fresh_coins[0].clone())
+        .add_output(pay_to(recipient, 12_345))
+        .add_output(pay_to(&change, 67_000));
+    let preview = wallet.preview_spend(&builder.build().unwrap());
+    assert_eq!(
+        vec![Some(segwit)],
+        preview
+            .inputs
+            .iter()
+            .map(|input| input.account)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some(fresh.clone()), preview.inputs[0].address);
+    assert_eq!(ScriptType::P2wpkh, preview.inputs[0].script_type);
+    assert!(!preview.outputs[0].is_mine);
+    assert!(preview.outputs[1].is_mine && preview.outputs[1].is_change);
+    assert_eq!(80_000 - 12_345 - 67_000, preview.fee);
+    assert!(preview.privacy_report().is_empty());
+
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder
+        .ordering(TxOrdering::Insertion)
+        .add_input(reused_coins[0].clone())
+        .add_input(old_coins[0].clone())
+        .add_output(pay_to(recipient, 50_000))
+        .add_output(pay_to(&change, 39_123))
+        .add_output(pay_to(&reused, 20_000));
+    let report = wallet
+        .preview_spend(&builder.build().unwrap())
+        .privacy_report();
+    assert_eq!(
+        vec![
+            PrivacyWarning::AddressReuse {
+                output: 2,
+                address: reused.clone()
+            },
+            PrivacyWarning::SpendsReusedAddress {
+                input: 0,
+                address: reused.clone()
+            },
+            PrivacyWarning::RoundNumberChange { change: 1 },
+            PrivacyWarning::MixedInputTypes(vec![ScriptType::P2wpkh, ScriptType::P2pkh]),
+            PrivacyWarning::CommonInputOwnership {
+                addresses: vec![reused, old],
+                accounts: vec![segwit, legacy],
+            },
+        ],
+        report
+    );
+    assert_eq!(
+        "the payments are round amounts, output 1 stands out as change",
+        report[2].to_string()
+    );
+}
+
+#[test]
+pub fn test_annotate() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let recipient = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    let (savings, coins) = funded(&mut wallet, account, &[("11", 60_000), ("22", 40_000)]);
+    wallet.set_label(&savings, "savings").unwrap();
+    let change = wallet.new_change_address(account).unwrap();
+
+    // the second coin spent without its output, as read from a raw transaction
+    let unknown = TransactionInput::new(
+        TransactionOutput::from_script(0, vec![]),
+        "22".repeat(32),
+        0,
+    );
+    let foreign = TransactionInput::new(pay_to(recipient, 5_000), "33".repeat(32), 1);
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder
+        .ordering(TxOrdering::Insertion)
+        .add_input(coins[0].clone())
+        .add_input(unknown)
+        .add_input(foreign)
+        .add_output(pay_to(recipient, 70_000))
+        .add_output(pay_to(&change, 34_000));
+    let tx = builder.build().unwrap();
+
+    let annotated = wallet.annotate(&tx);
+    assert_eq!(tx.tx_id(), annotated.tx_id());
+    let inputs = annotated.inputs();
+    assert_eq!(
+        (
+            60_000,
+            Some(savings.clone()),
+            true,
+            Some("savings".to_string())
+        ),
+        (
+            inputs[0].value,
+            inputs[0].address.clone(),
+            inputs[0].is_mine,
+            inputs[0].label.clone()
+        )
+    );
+    assert_eq!(40_000, inputs[1].value);
+    assert_eq!(Some(savings), inputs[1].address);
+    assert!(inputs[1].is_mine);
+    assert_eq!(OutPoint::new("33".repeat(32), 1), inputs[2].previous_output);
+    assert!(!inputs[2].is_mine);
+
+    let outputs = annotated.outputs();
+    assert_eq!(Some(recipient.to_string()), outputs[0].address);
+    assert_eq!(None, outputs[0].label);
+    assert!(!outputs[0].is_mine && !outputs[0].is_change);
+    assert_eq!(Some(change), outputs[1].address);
+    assert!(outputs[1].is_mine && outputs[1].is_change);
+    assert_eq!(None, outputs[1].label);
+
+    assert_eq!(34_000 - 100_000, annotated.net());
+    assert_eq!(vec![&outputs[0]], annotated.payments());
+    assert_eq!(tx.to_hex(), annotated.into_transaction().to_hex());
+}
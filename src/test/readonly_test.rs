@@ -0,0 +1,58 @@
+use crate::{AccountType, Network, OutPoint, TransactionOutput, Utxo, Wallet, WalletError};
+
+#[test]
+pub fn test_open_read_only() {
+    let path = std::env::temp_dir().join("waller_test_open_read_only");
+    let _ = std::fs::remove_dir_all(&path);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path,
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    wallet.set_label(&address, "savings").unwrap();
+    wallet
+        .add_utxo(Utxo::new(
+            OutPoint::new("44".repeat(32), 0),
+            TransactionOutput::from_script(12_000, vec![0x00, 0x14]),
+            address.clone(),
+        ))
+        .unwrap();
+    let file = wallet.flush().unwrap();
+    let written = std::fs::read(&file).unwrap();
+
+    // readable while the process keeping the wallet holds it
+    let held = Wallet::open_exclusive(file.clone()).unwrap();
+    let read_only = Wallet::open_read_only(file.clone()).unwrap();
+    assert_eq!(&Network::Mainnet, read_only.network());
+    assert_eq!(Some("savings"), read_only.label(&address));
+    assert_eq!(12_000, read_only.account_balance(account));
+    assert_eq!(Some(account), read_only.account_of(&address));
+    assert_eq!(
+        wallet.account_descriptors(account).unwrap(),
+        read_only.account_descriptors(account).unwrap()
+    );
+    assert_eq!(1, read_only.snapshot().utxos.len());
+    assert!(!read_only.is_encrypted());
+    assert_eq!(
+        wallet.sign_data(address.clone(), vec![9; 32]).unwrap(),
+        read_only.sign_data(address.clone(), vec![9; 32]).unwrap()
+    );
+    drop(held);
+    assert_eq!(written, std::fs::read(&file).unwrap());
+
+    // an encrypted wallet stays sealed
+    wallet.encrypt("passphrase").unwrap();
+    wallet.flush().unwrap();
+    let read_only = Wallet::open_read_only(file).unwrap();
+    assert!(read_only.is_encrypted());
+    assert_eq!(12_000, read_only.balance().spendable());
+    assert!(matches!(
+        read_only.sign_data(address, vec![9; 32]),
+        Err(WalletError::Locked)
+    ));
+}
@@ -0,0 +1,275 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    compact_size, decode_transaction, encode_psbt, reserves_commitment, reserves_transaction,
+    verify_reserves, AccountType, Backend, MockBackend, Network, OutPoint, ReservesError, Script,
+    SigHashType, Transaction, TransactionOutput, TransactionType, Utxo, VerifyError, Wallet,
+    WalletError,
+};
+
+fn restore(name: &str) -> Wallet {
+    let path = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&path);
+    Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path,
+        false,
+    )
+    .unwrap()
+}
+
+/// fund a new receive address of a new account of a type with a coin of a backend
+fn fund(
+    wallet: &mut Wallet,
+    backend: &MockBackend,
+    account_type: AccountType,
+    value: i64,
+) -> OutPoint {
+    let account = wallet.new_account(account_type).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    let script = Script::from_address(&address, Network::Mainnet).unwrap();
+    let outpoint = backend.fund(&address, value).unwrap();
+    wallet
+        .add_utxo(Utxo::new(
+            outpoint.clone(),
+            TransactionOutput::from_script(value, script.into_bytes()),
+            address,
+        ))
+        .unwrap();
+    outpoint
+}
+
+#[test]
+pub fn test_prove_reserves() {
+    let mut wallet = restore("waller_test_prove_reserves");
+    let backend = MockBackend::new(Network::Mainnet);
+    let outpoints = vec![
+        fund(&mut wallet, &backend, AccountType::Legacy, 50_000),
+        fund(&mut wallet, &backend, AccountType::NestedSegwit, 30_000),
+        fund(&mut wallet, &backend, AccountType::NativeSegwit, 20_000),
+        fund(&mut wallet, &backend, AccountType::Taproot, 10_000),
+    ];
+
+    let proof = wallet
+        .prove_reserves("audit 2026-09-30 f81c", &backend)
+        .unwrap();
+    let reserves = proof.verify(Network::Mainnet).unwrap();
+    assert_eq!(110_000, reserves.total);
+    assert_eq!(outpoints, reserves.outpoints);
+
+    // the signatures only hold for the message committed to
+    assert_eq!(
+        Err(ReservesError::ChallengeMismatch),
+        verify_reserves("audit 2026-12-31", &proof.psbt, Network::Mainnet)
+    );
+    assert!(matches!(
+        verify_reserves(&proof.message, "cHNidP8=", Network::Mainnet),
+        Err(ReservesError::InvalidPsbt(_))
+    ));
+}
+
+#[test]
+pub fn test_unsigned_reserves() {
+    let mut wallet = restore("waller_test_unsigned_reserves");
+    let backend = MockBackend::new(Network::Mainnet);
+    assert!(matches!(
+        wallet.prove_reserves("audit", &backend),
+        Err(WalletError::InsufficientFunds)
+    ));
+
+    fund(&mut wallet, &backend, AccountType::NativeSegwit, 10_000);
+    let unsigned = reserves_transaction("audit", wallet.utxos());
+    assert_eq!(
+        Err(ReservesError::Invalid(VerifyError::UnsignedInput(1))),
+        verify_reserves(
+            "audit",
+            &encode_psbt(&unsigned, &HashMap::new()),
+            Network::Mainnet
+        )
+    );
+    assert_eq!(
+        Err(ReservesError::NoReserves),
+        verify_reserves(
+            "audit",
+            &encode_psbt(&reserves_transaction("audit", &[]), &HashMap::new()),
+            Network::Mainnet
+        )
+    );
+
+    // a signer can't sign taproot coins, which only signs ECDSA
+    fund(&mut wallet, &backend, AccountType::Taproot, 10_000);
+    let signer = wallet.split_keystore().unwrap();
+    wallet.set_signer(Arc::new(signer));
+    assert!(matches!(
+        wallet.prove_reserves("audit", &backend),
+        Err(WalletError::UnsupportedInput(2))
+    ));
+}
+
+#[test]
+pub fn test_duplicate_reserves() {
+    let mut wallet = restore("waller_test_duplicate_reserves");
+    let backend = MockBackend::new(Network::Mainnet);
+    fund(&mut wallet, &backend, AccountType::NativeSegwit, 10_000);
+    // a coin already tracked isn't counted twice
+    let utxo = wallet.utxos()[0].clone();
+    wallet.add_utxo(utxo.clone()).unwrap();
+    assert_eq!(1, wallet.utxos().len());
+
+    let mut tx = reserves_transaction("audit", &[utxo.clone(), utxo.clone()]);
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert_eq!(
+        Err(ReservesError::DuplicateInput(2)),
+        verify_reserves(
+            "audit",
+            &encode_psbt(&tx, &HashMap::new()),
+            Network::Mainnet
+        )
+    );
+
+    // nor is the commitment spent again by a coin
+    let commitment = Utxo::new(
+        reserves_commitment("audit"),
+        utxo.output().clone(),
+        utxo.address().to_string(),
+    );
+    let mut tx = reserves_transaction("audit", &[commitment]);
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert_eq!(
+        Err(ReservesError::DuplicateInput(1)),
+        verify_reserves(
+            "audit",
+            &encode_psbt(&tx, &HashMap::new()),
+            Network::Mainnet
+        )
+    );
+}
+
+#[test]
+pub fn test_anyone_can_pay_reserves() {
+    let mut wallet = restore("waller_test_anyone_can_pay_reserves");
+    let backend = MockBackend::new(Network::Mainnet);
+    let outpoint = fund(&mut wallet, &backend, AccountType::Legacy, 10_000);
+    let spent = spent_transactions(&backend, &[outpoint]);
+    let utxo = wallet.utxos()[0].clone();
+    let key = wallet.get_address(utxo.address().to_string()).unwrap();
+
+    // a signature of its own input only holds for any other commitment
+    let tx = reserves_transaction("audit 2026-09-30", wallet.utxos());
+    let signed = decode_transaction(
+        &tx.sign_with(key, SigHashType::AllPlusAnyoneCanPay).unwrap(),
+        Network::Mainnet,
+    )
+    .unwrap();
+    let replay = reserves_transaction("audit 2026-12-31", wallet.utxos());
+    let mut inputs = replay.inputs();
+    inputs[1].set_signature_script(signed.inputs[1].signature_script.clone());
+    let forged = Transaction::new(
+        TransactionType::Pay2PubKeyHash,
+        inputs,
+        replay.outputs(),
+        None,
+    );
+
+    assert_eq!(
+        Err(ReservesError::SighashType(1)),
+        verify_reserves(
+            "audit 2026-12-31",
+            &encode_psbt(&forged, &spent),
+            Network::Mainnet
+        )
+    );
+}
+
+/// the serialized transactions paying coins, by txid
+fn spent_transactions(backend: &MockBackend, outpoints: &[OutPoint]) -> HashMap<String, Vec<u8>> {
+    outpoints
+        .iter()
+        .map(|outpoint| {
+            let raw = backend.transaction(&outpoint.hash()).unwrap();
+            (outpoint.hash(), hex::decode(raw).unwrap())
+        })
+        .collect()
+}
+
+/// A PSBT of a proof of reserves giving the outputs its coins spend as
+/// witness utxos, along with the transactions paying them when given
+fn witness_utxo_psbt(tx: &Transaction, spent: &HashMap<String, Vec<u8>>) -> String {
+    let field = |key_type: u8, value: &[u8]| {
+        [&[1, key_type][..], &compact_size(value.len()), value].concat()
+    };
+    let mut bytes = b"psbt\xff".to_vec();
+    bytes.extend(field(0x00, &tx.serialize_unsigned()));
+    bytes.push(0);
+    for input in tx.inputs().iter() {
+        if let Some(raw) = spent.get(&input.previous_output().hash()) {
+            bytes.extend(field(0x00, raw));
+        }
+        if !input.utxo_pk_script().is_empty() {
+            let utxo =
+                TransactionOutput::from_script(input.utxo_value(), input.utxo_pk_script().to_vec());
+            bytes.extend(field(0x01, &utxo.serialize()));
+            bytes.extend(field(0x07, input.signature_script()));
+        }
+        bytes.push(0);
+    }
+    bytes.push(0);
+    base64::encode(bytes)
+}
+
+#[test]
+pub fn test_legacy_reserves_values() {
+    let mut wallet = restore("waller_test_legacy_reserves_values");
+    let backend = MockBackend::new(Network::Mainnet);
+    let outpoint = fund(&mut wallet, &backend, AccountType::Legacy, 10_000);
+    let spent = spent_transactions(&backend, std::slice::from_ref(&outpoint));
+
+    // the value of a legacy coin is read from the transaction paying it
+    let proof = wallet.prove_reserves("audit", &backend).unwrap();
+    assert_eq!(10_000, proof.verify(Network::Mainnet).unwrap().total);
+    assert!(matches!(
+        wallet.prove_reserves("audit", &MockBackend::new(Network::Mainnet)),
+        Err(WalletError::Backend(_))
+    ));
+
+    // its signature doesn't commit to its value, which can't be inflated
+    let utxo = &wallet.utxos()[0];
+    let inflated = Utxo::new(
+        outpoint,
+        TransactionOutput::from_script(1_000_000, utxo.output().pk_script().to_vec()),
+        utxo.address().to_string(),
+    );
+    let mut tx = reserves_transaction("audit", &[inflated]);
+    wallet.sign_transaction(&mut tx).unwrap();
+    assert_eq!(
+        Err(ReservesError::InvalidOutput),
+        verify_reserves("audit", &encode_psbt(&tx, &spent), Network::Mainnet)
+    );
+    assert_eq!(
+        Err(ReservesError::MissingUtxo(1)),
+        verify_reserves(
+            "audit",
+            &encode_psbt(&tx, &HashMap::new()),
+            Network::Mainnet
+        )
+    );
+    // nor taken from a witness utxo, alone or disagreeing with the transaction
+    assert_eq!(
+        Err(ReservesError::InvalidPsbt(
+            "input 1 spends a non-segwit output without its transaction".to_string()
+        )),
+        verify_reserves(
+            "audit",
+            &witness_utxo_psbt(&tx, &HashMap::new()),
+            Network::Mainnet
+        )
+    );
+    assert_eq!(
+        Err(ReservesError::InvalidPsbt(
+            "the witness utxo of input 1 isn't the output spent".to_string()
+        )),
+        verify_reserves("audit", &witness_utxo_psbt(&tx, &spent), Network::Mainnet)
+    );
+}
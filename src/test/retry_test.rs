@@ -0,0 +1,170 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{Backend, BackendError, RetryPolicy, RetryingBackend};
+
+/// a server failing its first calls with an error, counting the calls it gets
+struct FlakyServer {
+    failures: AtomicUsize,
+    error: BackendError,
+    calls: Arc<AtomicUsize>,
+    tip: u32,
+}
+
+impl Backend for FlakyServer {
+    fn broadcast(&self, _raw_tx: &str) -> Result<String, BackendError> {
+        Err(BackendError::Unsupported("broadcast".to_string()))
+    }
+
+    fn tip_height(&self) -> Result<u32, BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let failing = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                failures.checked_sub(1)
+            })
+            .is_ok();
+        match failing {
+            true => Err(self.error.clone()),
+            false => Ok(self.tip),
+        }
+    }
+}
+
+fn server(
+    failures: usize,
+    error: BackendError,
+    tip: u32,
+) -> (Box<dyn Backend + Send + Sync>, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let server = FlakyServer {
+        failures: AtomicUsize::new(failures),
+        error,
+        calls: calls.clone(),
+        tip,
+    };
+    (Box::new(server), calls)
+}
+
+fn unreachable() -> BackendError {
+    BackendError::Connection("connection refused".to_string())
+}
+
+fn policy(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(4),
+        ..RetryPolicy::default()
+    }
+}
+
+#[test]
+fn test_failover() {
+    let (down, down_calls) = server(usize::MAX, unreachable(), 1);
+    let (up, up_calls) = server(0, unreachable(), 2);
+    let backend = RetryingBackend::new(vec![down, up], policy(3));
+
+    assert_eq!(2, backend.tip_height().unwrap());
+    assert_eq!(1, backend.current_server());
+    assert_eq!(1, down_calls.load(Ordering::SeqCst));
+
+    // calls start on the server that answered
+    assert_eq!(2, backend.tip_height().unwrap());
+    assert_eq!(1, down_calls.load(Ordering::SeqCst));
+    assert_eq!(2, up_calls.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_retry_with_backoff() {
+    let (flaky, calls) = server(2, unreachable(), 7);
+    let backend = RetryingBackend::new(vec![flaky], policy(3));
+    assert_eq!(7, backend.tip_height().unwrap());
+    assert_eq!(3, calls.load(Ordering::SeqCst));
+
+    // the last error is returned once the attempts run out
+    let (flaky, calls) = server(2, unreachable(), 7);
+    let backend = RetryingBackend::new(vec![flaky], policy(2));
+    assert!(matches!(
+        backend.tip_height(),
+        Err(BackendError::Connection(_))
+    ));
+    assert_eq!(2, calls.load(Ordering::SeqCst));
+
+    // nor retried past the timeout
+    let (flaky, calls) = server(2, unreachable(), 7);
+    let backend = RetryingBackend::new(
+        vec![flaky],
+        RetryPolicy {
+            initial_backoff: Duration::from_secs(10),
+            timeout: Duration::from_secs(1),
+            ..RetryPolicy::default()
+        },
+    );
+    let start = Instant::now();
+    assert!(backend.tip_height().is_err());
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(1, calls.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_errors_not_retried() {
+    // a server that was reached answered, the others would answer the same
+    let rejected = BackendError::Rpc {
+        code: -26,
+        message: "txn-mempool-conflict".to_string(),
+    };
+    let (node, node_calls) = server(1, rejected, 1);
+    let (other, other_calls) = server(0, unreachable(), 2);
+    let backend = RetryingBackend::new(vec![node, other], policy(3));
+    assert!(matches!(
+        backend.tip_height(),
+        Err(BackendError::Rpc { code: -26, .. })
+    ));
+    assert_eq!(1, node_calls.load(Ordering::SeqCst));
+    assert_eq!(0, other_calls.load(Ordering::SeqCst));
+
+    // unsupported calls try the other servers once
+    let unsupported = || BackendError::Unsupported("tip_height".to_string());
+    let (first, first_calls) = server(usize::MAX, unsupported(), 1);
+    let (second, _) = server(0, unreachable(), 2);
+    let backend = RetryingBackend::new(vec![first, second], policy(3));
+    assert_eq!(2, backend.tip_height().unwrap());
+    assert_eq!(1, first_calls.load(Ordering::SeqCst));
+
+    let (first, first_calls) = server(usize::MAX, unsupported(), 1);
+    let backend = RetryingBackend::new(vec![first], policy(3));
+    assert!(matches!(
+        backend.tip_height(),
+        Err(BackendError::Unsupported(_))
+    ));
+    assert_eq!(1, first_calls.load(Ordering::SeqCst));
+    assert!(matches!(
+        backend.broadcast("00"),
+        Err(BackendError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn test_rate_limit() {
+    let (node, calls) = server(0, unreachable(), 1);
+    let backend = RetryingBackend::new(
+        vec![node],
+        RetryPolicy {
+            min_interval: Duration::from_millis(50),
+            ..RetryPolicy::default()
+        },
+    );
+
+    let start = Instant::now();
+    for _ in 0..3 {
+        backend.tip_height().unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(3, calls.load(Ordering::SeqCst));
+}
@@ -0,0 +1,400 @@
+use secp256k1::{Message, PublicKey, Secp256k1, Signature};
+
+use crate::{
+    compress_public_key, estimate_mixed_vsize, Key, KeyError, MultisigScript, Network, Script,
+    ScriptType, SigHashType, Transaction, TransactionError, TransactionInput, TransactionOutput,
+    TransactionType, TransactionVersion, OP_CHECKSEQUENCEVERIFY, OP_CHECKSIG, OP_DROP, SIGHASH_ALL,
+};
+
+const WIFS: [&str; 3] = [
+    "L57KYn5isHFThD4cohjJgLTZA2vaxnMMKWngnzbttF159yH9dARf",
+    "KyZpNDKnfs94vbrwhJneDi77V6jF64PWPF8x5cdJb8ifgg2DUc9d",
+    "KyRv5iFPHG7iB5E4CqvMzH3WFJVhbfYK4VY7XAedd9Ys69mEsPLQ",
+];
+
+fn key(index: usize) -> Key {
+    Key::from_wif(WIFS[index].to_string()).unwrap()
+}
+
+fn pubkey(key: &Key) -> Vec<u8> {
+    compress_public_key(&key.new_public_key().unwrap()).unwrap()
+}
+
+/// a version 2 transaction spending a P2WSH output of the
+/// witness script to a P2PKH output of the first key
+fn spend(witness_script: &Script, sequence: u32) -> Transaction {
+    let utxo = TransactionOutput::from_script(100_000, witness_script.to_p2wsh().into_bytes());
+    let mut input = TransactionInput::new(utxo, "33".repeat(32), 0);
+    input.set_sequence(sequence);
+    let output = TransactionOutput::new(TransactionType::Pay2PubKeyHash, key(0), 90_000);
+
+    let mut tx = Transaction::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        vec![input],
+        vec![output],
+        None,
+    );
+    tx.set_version(TransactionVersion::Two);
+    tx
+}
+
+fn verify(sighash: &str, signature: &[u8], pubkey: &[u8]) -> bool {
+    let message = Message::from_slice(&hex::decode(sighash).unwrap()).unwrap();
+    let (sighash_type, der) = signature.split_last().unwrap();
+    assert_eq!(SIGHASH_ALL as u8, *sighash_type);
+
+    Secp256k1::verification_only()
+        .verify(
+            &message,
+            &Signature::from_der(der).unwrap(),
+            &PublicKey::from_slice(pubkey).unwrap(),
+        )
+        .is_ok()
+}
+
+#[test]
+pub fn test_p2wsh_address() {
+    // BIP173
+    let script = Script::new(
+        hex::decode("210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac")
+            .unwrap(),
+    );
+
+    assert_eq!(
+        "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+        script.p2wsh_address(Network::Mainnet).unwrap()
+    );
+    assert_eq!(
+        "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+        script.p2wsh_address(Network::Testnet).unwrap()
+    );
+    assert_eq!(
+        "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+        hex::encode(script.to_p2wsh().as_bytes())
+    );
+}
+
+#[test]
+pub fn test_script_numbers() {
+    let script = Script::builder()
+        .push_int(0)
+        .push_int(-1)
+        .push_int(16)
+        .push_int(17)
+        .push_int(144)
+        .push_int(-144)
+        .push_int(1000)
+        .build();
+
+    assert_eq!(
+        "004f60011102900002908002e803",
+        hex::encode(script.as_bytes())
+    );
+}
+
+#[test]
+pub fn test_p2wsh_timelock_spend() {
+    let key = key(0);
+    let witness_script = Script::builder()
+        .push_int(144)
+        .push_opcode(OP_CHECKSEQUENCEVERIFY)
+        .push_opcode(OP_DROP)
+        .push_slice(&pubkey(&key))
+        .push_opcode(OP_CHECKSIG)
+        .build();
+    assert_eq!(
+        "bc1quy2ha8j52pgtj52sje68p385mt877ktvehvs4etls3p0r0dju0jqtd5q3d",
+        witness_script.p2wsh_address(Network::Mainnet).unwrap()
+    );
+
+    let mut tx = spend(&witness_script, 144);
+    let signature = tx
+        .sign_p2wsh_input(0, &witness_script, &key, SigHashType::All)
+        .unwrap();
+    assert!(verify(
+        "65f7f7115278d1c7d4ecc51b4f965c3e9717ea4ff79897dac294069bd0345d62",
+        &signature,
+        &pubkey(&key)
+    ));
+
+    tx.set_p2wsh_witness(0, &witness_script, vec![signature.clone()])
+        .unwrap();
+    let input = tx.get_input(0).unwrap();
+    assert_eq!(0, input.script_bytes());
+    assert_eq!(
+        &vec![signature, witness_script.as_bytes().to_vec()],
+        input.witness()
+    );
+    // the segwit marker and flag follow the version
+    assert_eq!(&[0x02, 0, 0, 0, 0x00, 0x01], &tx.serialize()[..6]);
+
+    let other = Script::builder().push_opcode(OP_CHECKSIG).build();
+    assert!(matches!(
+        tx.sign_p2wsh_input(0, &other, &key, SigHashType::All),
+        Err(TransactionError::ScriptMismatch(0))
+    ));
+}
+
+#[test]
+pub fn test_p2wsh_multisig_signing() {
+    let keys = [key(0), key(1), key(2)];
+    let multisig = MultisigScript::new(2, keys.iter().map(pubkey).collect::<Vec<_>>()).unwrap();
+    let witness_script = multisig.to_script();
+    assert_eq!(
+        witness_script.to_p2wsh().into_bytes(),
+        multisig.p2wsh_script_pubkey()
+    );
+
+    let mut tx = spend(&witness_script, 0xffffffff);
+    tx.sign_multisig_input(0, &multisig, &keys[2], SigHashType::All)
+        .unwrap();
+    tx.sign_multisig_input(0, &multisig, &keys[0], SigHashType::All)
+        .unwrap();
+
+    let witness = tx.get_input(0).unwrap().witness().clone();
+    assert_eq!(0, tx.get_input(0).unwrap().script_bytes());
+    assert_eq!(4, witness.len());
+    assert!(witness[0].is_empty());
+    assert_eq!(witness_script.as_bytes(), witness[3].as_slice());
+
+    let sighash = "6615d5d6a81b22c05ac905453a697b07eda99f2891ddffb11fde0e44059b51c1";
+    assert!(verify(sighash, &witness[1], &multisig.pubkeys()[0]));
+    assert!(verify(sighash, &witness[2], &multisig.pubkeys()[2]));
+}
+
+#[test]
+pub fn test_output_script_address() {
+    let address = |script: &str, network: Network| {
+        Script::new(hex::decode(script).unwrap()).to_address(network)
+    };
+
+    assert_eq!(
+        Some("1BoatSLRHtKNngkdXEeobR76b53LETtpyT".to_string()),
+        address(
+            "76a9147680adec8eabcabac676be9e83854ade0bd22cdb88ac",
+            Network::Mainnet
+        )
+    );
+    assert_eq!(
+        Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string()),
+        address(
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            Network::Mainnet
+        )
+    );
+    assert_eq!(
+        Some("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string()),
+        address(
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            Network::Regtest
+        )
+    );
+    assert_eq!(
+        Some("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0".to_string()),
+        address(
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            Network::Mainnet
+        )
+    );
+
+    // a P2SH address round trips through its script
+    let redeem = Script::new(vec![OP_CHECKSIG]);
+    assert_eq!(
+        Some(redeem.p2sh_address(Network::Testnet)),
+        redeem.to_p2sh().to_address(Network::Testnet)
+    );
+
+    // OP_RETURN and bare public keys have no address
+    assert_eq!(None, address("6a0401020304", Network::Mainnet));
+    assert_eq!(
+        None,
+        address(
+            "2102f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9ac",
+            Network::Mainnet
+        )
+    );
+}
+
+#[test]
+pub fn test_script_from_address() {
+    let scripts = [
+        (
+            "76a9147680adec8eabcabac676be9e83854ade0bd22cdb88ac",
+            Network::Mainnet,
+        ),
+        (
+            "a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87",
+            Network::Testnet,
+        ),
+        (
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            Network::Regtest,
+        ),
+        (
+            "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            Network::Mainnet,
+        ),
+        (
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            Network::Mainnet,
+        ),
+    ];
+    for (script, network) in scripts {
+        let script = Script::new(hex::decode(script).unwrap());
+        let address = script.to_address(network).unwrap();
+        assert_eq!(script, Script::from_address(&address, network).unwrap());
+    }
+
+    // addresses of other networks
+    assert!(matches!(
+        Script::from_address("1BoatSLRHtKNngkdXEeobR76b53LETtpyT", Network::Testnet),
+        Err(KeyError::InvalidNetworkByte)
+    ));
+    assert!(matches!(
+        Script::from_address(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            Network::Regtest
+        ),
+        Err(KeyError::InvalidNetworkByte)
+    ));
+    // a typo, and a v1 program in bech32 rather than bech32m (BIP350)
+    assert!(Script::from_address("1BoatSLRHtKNngkdXEeobR76b53LETtpyU", Network::Mainnet).is_err());
+    assert!(Script::from_address(
+        "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx",
+        Network::Mainnet
+    )
+    .is_err());
+}
+
+#[test]
+pub fn test_classify_script() {
+    let classify = |script: &str| Script::new(hex::decode(script).unwrap()).classify();
+
+    assert_eq!(
+        ScriptType::P2pk,
+        classify("2102f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9ac")
+    );
+    assert_eq!(
+        ScriptType::P2pk,
+        classify("410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac")
+    );
+    assert_eq!(
+        ScriptType::P2pkh,
+        classify("76a9147680adec8eabcabac676be9e83854ade0bd22cdb88ac")
+    );
+    assert_eq!(
+        ScriptType::P2sh,
+        classify("a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87")
+    );
+    assert_eq!(
+        ScriptType::P2wpkh,
+        classify("0014751e76e8199196d454941c45d1b3a323f1433bd6")
+    );
+    assert_eq!(
+        ScriptType::P2wsh,
+        classify("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262")
+    );
+    assert_eq!(
+        ScriptType::P2tr,
+        classify("512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798")
+    );
+    assert_eq!(
+        ScriptType::UnknownWitness { version: 2 },
+        classify("5210751e76e8199196d454941c45d1b3a323")
+    );
+    assert_eq!(ScriptType::NullData, classify("6a0401020304"));
+    assert_eq!(ScriptType::NullData, classify("6a"));
+
+    let keys = [key(0), key(1), key(2)];
+    let multisig = MultisigScript::new(2, keys.iter().map(pubkey).collect::<Vec<_>>()).unwrap();
+    assert_eq!(
+        ScriptType::Multisig {
+            required: 2,
+            keys: 3
+        },
+        multisig.to_script().classify()
+    );
+
+    // a v0 program of the wrong length, more keys required than given,
+    // a key count not matching the keys and a script that isn't a template
+    for script in [
+        "0015751e76e8199196d454941c45d1b3a323f1433bd6ff",
+        "532102f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f952ae",
+        "522102f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f951ae",
+        "51",
+        "",
+    ] {
+        assert_eq!(ScriptType::NonStandard, classify(script), "{}", script);
+    }
+    assert_eq!(
+        None,
+        Script::new(hex::decode("0015751e76e8199196d454941c45d1b3a323f1433bd6ff").unwrap())
+            .to_address(Network::Mainnet)
+    );
+
+    assert_eq!(
+        Some(TransactionType::Pay2WitnessPubKeyHash),
+        ScriptType::P2wpkh.tx_type()
+    );
+    assert_eq!(None, ScriptType::P2wsh.tx_type());
+}
+
+#[test]
+pub fn test_max_satisfaction_weight() {
+    // a 73 byte signature and a compressed key pushed in the script sig
+    assert_eq!(Some(109 * 4), ScriptType::P2pkh.max_satisfaction_weight());
+    assert_eq!(Some(75 * 4), ScriptType::P2pk.max_satisfaction_weight());
+    // the same witness, with or without the push of the redeem script
+    assert_eq!(Some(4 + 109), ScriptType::P2wpkh.max_satisfaction_weight());
+    assert_eq!(
+        Some(24 * 4 + 109),
+        ScriptType::P2sh.max_satisfaction_weight()
+    );
+    assert_eq!(Some(4 + 67), ScriptType::P2tr.max_satisfaction_weight());
+    assert_eq!(
+        Some((1 + 1 + 2 * 74) * 4),
+        ScriptType::Multisig {
+            required: 2,
+            keys: 3
+        }
+        .max_satisfaction_weight()
+    );
+    assert_eq!(None, ScriptType::P2wsh.max_satisfaction_weight());
+    assert_eq!(None, ScriptType::NullData.max_satisfaction_weight());
+
+    // a P2WPKH input and output, matching the size of a signed transaction
+    let outputs = [TransactionOutput::from_script(
+        1_000,
+        hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+    )];
+    assert_eq!(
+        110,
+        estimate_mixed_vsize(
+            &TransactionType::Pay2WitnessPubKeyHash,
+            &[ScriptType::P2wpkh],
+            &outputs
+        )
+    );
+    // a legacy input in a segwit transaction takes an empty witness
+    assert_eq!(
+        estimate_mixed_vsize(
+            &TransactionType::Pay2PubKeyHash,
+            &[ScriptType::P2pkh, ScriptType::P2wpkh],
+            &outputs
+        ),
+        (4 * (4 + 1 + 1 + 31 + 4) + 2u64 + 1 + 2 * 160 + 109 * 4 + 113).div_ceil(4)
+    );
+    // unknown satisfactions are sized as the type of the transaction
+    assert_eq!(
+        estimate_mixed_vsize(
+            &TransactionType::Pay2WitnessPubKeyHash,
+            &[ScriptType::P2wpkh],
+            &outputs
+        ) + 68,
+        estimate_mixed_vsize(
+            &TransactionType::Pay2WitnessPubKeyHash,
+            &[ScriptType::P2wpkh, ScriptType::P2wsh],
+            &outputs
+        )
+    );
+}
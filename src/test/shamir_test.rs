@@ -0,0 +1,75 @@
+use crate::{combine_shares, split_secret, ShamirError};
+
+#[test]
+pub fn test_shamir_split_and_combine() {
+    let secret = hex::decode("bb54aac4b89dc868ba37d9cc21b2cece").unwrap();
+    let shares = split_secret(&secret, 2, 3, "TREZOR").unwrap();
+    assert_eq!(3, shares.len());
+    // 7 words of metadata and 13 words of a 128 bit secret
+    assert!(shares
+        .iter()
+        .all(|share| share.split_whitespace().count() == 20));
+
+    for (first, second) in [(0, 1), (0, 2), (2, 1)] {
+        let pair = [shares[first].clone(), shares[second].clone()];
+        assert_eq!(secret, combine_shares(&pair, "TREZOR").unwrap());
+    }
+
+    // a wrong passphrase gives another secret without an error
+    assert_ne!(secret, combine_shares(&shares[..2], "").unwrap());
+
+    assert!(matches!(
+        combine_shares(&shares[..1], "TREZOR"),
+        Err(ShamirError::InsufficientShares(_))
+    ));
+    assert!(matches!(
+        combine_shares(&[shares[0].clone(), shares[0].clone()], "TREZOR"),
+        Err(ShamirError::MismatchedShares(_))
+    ));
+
+    let other = split_secret(&secret, 2, 3, "TREZOR").unwrap();
+    assert!(matches!(
+        combine_shares(&[shares[0].clone(), other[1].clone()], "TREZOR"),
+        Err(ShamirError::MismatchedShares(_))
+    ));
+
+    let mut words: Vec<&str> = shares[0].split_whitespace().collect();
+    words[10] = if words[10] == "academic" {
+        "acid"
+    } else {
+        "academic"
+    };
+    assert!(matches!(
+        combine_shares(&[words.join(" "), shares[1].clone()], "TREZOR"),
+        Err(ShamirError::InvalidChecksum)
+    ));
+}
+
+#[test]
+pub fn test_shamir_parameters() {
+    let secret = vec![7; 32];
+
+    let shares = split_secret(&secret, 1, 1, "").unwrap();
+    assert_eq!(secret, combine_shares(&shares, "").unwrap());
+
+    let shares = split_secret(&secret, 16, 16, "").unwrap();
+    assert_eq!(secret, combine_shares(&shares, "").unwrap());
+    assert!(combine_shares(&shares[1..], "").is_err());
+
+    assert!(matches!(
+        split_secret(&secret, 3, 2, ""),
+        Err(ShamirError::InvalidThreshold(3, 2))
+    ));
+    assert!(matches!(
+        split_secret(&secret, 1, 3, ""),
+        Err(ShamirError::InvalidThreshold(1, 3))
+    ));
+    assert!(matches!(
+        split_secret(&secret, 2, 17, ""),
+        Err(ShamirError::InvalidThreshold(2, 17))
+    ));
+    assert!(matches!(
+        split_secret(&secret[..15], 2, 3, ""),
+        Err(ShamirError::InvalidSecret(15))
+    ));
+}
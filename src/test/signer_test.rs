@@ -0,0 +1,109 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    compress_public_key, EncryptedFileSigner, ExternalSigner, Key, KeystoreSigner, MemorySigner,
+    SignerError,
+};
+
+const WIFS: [&str; 2] = [
+    "L57KYn5isHFThD4cohjJgLTZA2vaxnMMKWngnzbttF159yH9dARf",
+    "KyZpNDKnfs94vbrwhJneDi77V6jF64PWPF8x5cdJb8ifgg2DUc9d",
+];
+
+fn key(index: usize) -> Key {
+    Key::from_wif(WIFS[index].to_string()).unwrap()
+}
+
+fn pubkey(index: usize) -> Vec<u8> {
+    compress_public_key(&key(index).new_public_key().unwrap()).unwrap()
+}
+
+#[test]
+pub fn test_memory_signer() {
+    let signer = MemorySigner::new(vec![key(0)]).unwrap();
+    let digest = [3; 32];
+
+    assert!(signer.has_key(&pubkey(0)));
+    assert!(!signer.has_key(&pubkey(1)));
+    assert_eq!(1, signer.keys().len());
+    assert_eq!(
+        key(0).sign_der(&digest).unwrap(),
+        signer.sign(&pubkey(0), &digest).unwrap()
+    );
+    assert!(matches!(
+        signer.sign(&pubkey(1), &digest),
+        Err(SignerError::UnknownKey(_))
+    ));
+}
+
+#[test]
+pub fn test_encrypted_file_signer() {
+    let path = std::env::temp_dir().join("waller_test_encrypted_file_signer.json");
+    let digest = [5; 32];
+    let keys = [key(0), key(1)];
+
+    let signer = EncryptedFileSigner::create(
+        path.clone(),
+        "correct horse",
+        &keys.iter().collect::<Vec<_>>(),
+    )
+    .unwrap();
+    assert!(signer.is_locked());
+    assert!(matches!(
+        signer.sign(&pubkey(0), &digest),
+        Err(SignerError::Locked)
+    ));
+
+    // the file holds no private key in the clear
+    let file = std::fs::read_to_string(&path).unwrap();
+    assert!(!file.contains(&key(0).hex()));
+
+    let signer = EncryptedFileSigner::open(path).unwrap();
+    assert!(signer.has_key(&pubkey(1)));
+    assert!(matches!(
+        signer.unlock("battery staple"),
+        Err(SignerError::IncorrectPassphrase)
+    ));
+    signer.unlock("correct horse").unwrap();
+    assert_eq!(
+        key(1).sign_der(&digest).unwrap(),
+        signer.sign(&pubkey(1), &digest).unwrap()
+    );
+
+    signer.lock().unwrap();
+    assert!(matches!(
+        signer.sign(&pubkey(1), &digest),
+        Err(SignerError::Locked)
+    ));
+}
+
+#[test]
+pub fn test_external_signer() {
+    let requests = Arc::new(Mutex::new(vec![]));
+    let seen = requests.clone();
+    let device = key(0);
+    let signer = ExternalSigner::new(vec![pubkey(0)], move |public_key, digest| {
+        seen.lock().unwrap().push(public_key.to_vec());
+        if digest == [0; 32] {
+            return Err(SignerError::Rejected("declined on device".to_string()));
+        }
+        device
+            .sign_der(digest)
+            .map_err(|e| SignerError::Signing(e.to_string()))
+    });
+
+    assert_eq!(
+        key(0).sign_der(&[9; 32]).unwrap(),
+        signer.sign(&pubkey(0), &[9; 32]).unwrap()
+    );
+    assert!(matches!(
+        signer.sign(&pubkey(0), &[0; 32]),
+        Err(SignerError::Rejected(_))
+    ));
+    assert!(matches!(
+        signer.sign(&pubkey(1), &[9; 32]),
+        Err(SignerError::UnknownKey(_))
+    ));
+    // keys the signer doesn't hold never reach the device
+    assert_eq!(2, requests.lock().unwrap().len());
+}
@@ -0,0 +1,278 @@
+use std::str::FromStr;
+
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+use crate::{
+    hash160, silent_payment_tweak, tagged_hash, KeyError, Network, OutPoint, SilentPaymentAddress,
+    SilentPaymentInput, SilentPaymentKeys, TransactionOutput,
+};
+
+/// A key spending an input, of a P2WPKH, P2TR or P2PKH output
+#[derive(Clone, Copy)]
+pub enum Spending {
+    Witness(u8),
+    Taproot(u8),
+    Legacy(u8),
+}
+
+impl Spending {
+    fn secret(&self) -> SecretKey {
+        let (Spending::Witness(seed) | Spending::Taproot(seed) | Spending::Legacy(seed)) = self;
+        SecretKey::from_slice(&[*seed; 32]).unwrap()
+    }
+
+    fn public_key(&self) -> [u8; 33] {
+        PublicKey::from_secret_key(&Secp256k1::new(), &self.secret()).serialize()
+    }
+
+    /// the input spending the output of the key, with a placeholder signature
+    pub fn input(&self, previous_output: OutPoint) -> SilentPaymentInput {
+        let public_key = self.public_key();
+        let signature = vec![0x30; 71];
+        let (script_pubkey, script_sig, witness) = match self {
+            Spending::Witness(_) => (
+                [&[0x00, 0x14][..], &hash160(&public_key.to_vec())].concat(),
+                vec![],
+                vec![signature, public_key.to_vec()],
+            ),
+            Spending::Taproot(_) => (
+                [&[0x51, 0x20][..], &public_key[1..]].concat(),
+                vec![],
+                vec![vec![0x01; 64]],
+            ),
+            Spending::Legacy(_) => (
+                [
+                    &[0x76, 0xa9, 0x14][..],
+                    &hash160(&public_key.to_vec()),
+                    &[0x88, 0xac],
+                ]
+                .concat(),
+                [&[71][..], &signature, &[33], &public_key].concat(),
+                vec![],
+            ),
+        };
+        SilentPaymentInput {
+            previous_output,
+            script_pubkey,
+            script_sig,
+            witness,
+        }
+    }
+}
+
+/// The script of the `k`th output a sender spending coins with keys pays to
+/// an address, computed from the private keys of the inputs as in BIP352
+pub fn silent_payment_script(
+    spending: &[(Spending, OutPoint)],
+    address: &SilentPaymentAddress,
+    k: u32,
+) -> Vec<u8> {
+    let secp = Secp256k1::new();
+    // taproot keys count with an even y
+    let mut sum: Option<SecretKey> = None;
+    for (key, _) in spending {
+        let mut secret = key.secret();
+        if matches!(key, Spending::Taproot(_)) && key.public_key()[0] == 0x03 {
+            secret.negate_assign();
+        }
+        sum = Some(match sum {
+            Some(mut sum) => {
+                sum.add_assign(&secret[..]).unwrap();
+                sum
+            }
+            None => secret,
+        });
+    }
+    let mut sum = sum.unwrap();
+
+    let smallest = spending
+        .iter()
+        .map(|(_, outpoint)| outpoint.serialize())
+        .min()
+        .unwrap();
+    let input_hash = tagged_hash(
+        "BIP0352/Inputs",
+        &[
+            smallest.as_slice(),
+            &PublicKey::from_secret_key(&secp, &sum).serialize(),
+        ]
+        .concat(),
+    );
+    sum.mul_assign(&input_hash).unwrap();
+    let mut shared_secret = PublicKey::from_slice(address.scan_key()).unwrap();
+    shared_secret.mul_assign(&secp, &sum[..]).unwrap();
+
+    let t_k = tagged_hash(
+        "BIP0352/SharedSecret",
+        &[&shared_secret.serialize()[..], &k.to_be_bytes()].concat(),
+    );
+    let mut output = PublicKey::from_slice(address.spend_key()).unwrap();
+    output.add_exp_assign(&secp, &t_k).unwrap();
+    [&[0x51, 0x20][..], &output.serialize()[1..]].concat()
+}
+
+fn outpoint(byte: &str, index: i32) -> OutPoint {
+    OutPoint::new(byte.repeat(32), index)
+}
+
+#[test]
+fn test_silent_payment_address() {
+    let scan = Spending::Witness(1).public_key();
+    let spend = Spending::Witness(2).public_key();
+
+    let address = SilentPaymentAddress::new(Network::Mainnet, &scan, &spend).unwrap();
+    let encoded = address.to_string();
+    assert!(encoded.starts_with("sp1q"));
+    assert_eq!(116, encoded.len());
+    assert_eq!(address, SilentPaymentAddress::from_str(&encoded).unwrap());
+    assert_eq!(address, encoded.to_uppercase().parse().unwrap());
+
+    let testnet = SilentPaymentAddress::new(Network::Testnet, &scan, &spend).unwrap();
+    assert!(testnet.to_string().starts_with("tsp1q"));
+    assert_eq!(
+        Network::Testnet,
+        SilentPaymentAddress::from_str(&testnet.to_string())
+            .unwrap()
+            .network()
+    );
+    let regtest = SilentPaymentAddress::new(Network::Regtest, &scan, &spend).unwrap();
+    assert!(regtest.to_string().starts_with("sprt1q"));
+
+    // only valid compressed keys
+    assert!(matches!(
+        SilentPaymentAddress::new(Network::Mainnet, &scan[1..], &spend),
+        Err(KeyError::InvalidFormat)
+    ));
+    assert!(SilentPaymentAddress::new(Network::Mainnet, &scan, &[0x05; 33]).is_err());
+
+    // a segwit address, and a changed character
+    assert!("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        .parse::<SilentPaymentAddress>()
+        .is_err());
+    let mut tampered = encoded.into_bytes();
+    tampered[10] = if tampered[10] == b'q' { b'p' } else { b'q' };
+    assert!(String::from_utf8(tampered)
+        .unwrap()
+        .parse::<SilentPaymentAddress>()
+        .is_err());
+}
+
+#[test]
+fn test_silent_payment_tweak() {
+    let scan = Spending::Witness(7).public_key();
+    let spend = Spending::Witness(8).public_key();
+    let address = SilentPaymentAddress::new(Network::Mainnet, &scan, &spend).unwrap();
+    let spending = vec![
+        (Spending::Witness(3), outpoint("bb", 1)),
+        (Spending::Taproot(4), outpoint("aa", 2)),
+        (Spending::Legacy(5), outpoint("cc", 0)),
+    ];
+    let inputs: Vec<SilentPaymentInput> = spending
+        .iter()
+        .map(|(key, outpoint)| key.input(outpoint.clone()))
+        .collect();
+    let paid = silent_payment_script(&spending, &address, 0);
+    let outputs = vec![
+        TransactionOutput::from_script(10_000, paid),
+        TransactionOutput::from_script(
+            5_000,
+            hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+        ),
+    ];
+
+    // the tweak is the sum of the input keys times the input hash, the
+    // receiver multiplies it by its scan key as the sender did its keys
+    let tweak = silent_payment_tweak(&inputs, &outputs).unwrap();
+    assert_eq!(33, tweak.len());
+    let mut reversed = inputs.clone();
+    reversed.reverse();
+    assert_eq!(
+        Some(&tweak),
+        silent_payment_tweak(&reversed, &outputs).as_ref()
+    );
+
+    // no taproot output to pay to
+    assert_eq!(None, silent_payment_tweak(&inputs, &outputs[1..]));
+    // an input spending a segwit version 2 output
+    let mut future = inputs.clone();
+    future[0].script_pubkey = [&[0x52, 0x20][..], &[0x01; 32]].concat();
+    assert_eq!(None, silent_payment_tweak(&future, &outputs));
+    // uncompressed keys and script path spends with no key don't count
+    let mut uncounted = inputs[..1].to_vec();
+    let mut uncompressed = Spending::Witness(6).input(outpoint("dd", 0));
+    uncompressed.witness[1] = vec![0x04; 65];
+    uncounted.push(uncompressed);
+    let mut script_path = Spending::Taproot(9).input(outpoint("ee", 0));
+    let nums = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+    script_path.witness = vec![
+        vec![0x51],
+        [&[0xc0][..], &hex::decode(nums).unwrap()].concat(),
+    ];
+    uncounted.push(script_path);
+    let keyless = |previous_output| SilentPaymentInput {
+        previous_output,
+        script_pubkey: vec![0x6a],
+        script_sig: vec![],
+        witness: vec![],
+    };
+    let mut expected = vec![
+        inputs[0].clone(),
+        keyless(outpoint("dd", 0)),
+        keyless(outpoint("ee", 0)),
+    ];
+    let tweak_uncounted = silent_payment_tweak(&uncounted, &outputs);
+    assert!(tweak_uncounted.is_some());
+    assert_eq!(silent_payment_tweak(&expected, &outputs), tweak_uncounted);
+    // the smallest outpoint is hashed, of an input with a key or not
+    expected.push(keyless(outpoint("00", 0)));
+    assert_ne!(silent_payment_tweak(&expected, &outputs), tweak_uncounted);
+    // no input with a key
+    assert_eq!(None, silent_payment_tweak(&uncounted[1..], &outputs));
+}
+
+#[test]
+fn test_silent_payment_scan() {
+    let keys = SilentPaymentKeys::new(&[7; 32], &Spending::Witness(8).public_key()).unwrap();
+    let address = keys.address(Network::Mainnet).unwrap();
+    assert_eq!(&Spending::Witness(7).public_key()[..], address.scan_key());
+    let spending = vec![
+        (Spending::Taproot(3), outpoint("11", 0)),
+        (Spending::Witness(4), outpoint("22", 5)),
+    ];
+    let inputs: Vec<SilentPaymentInput> = spending
+        .iter()
+        .map(|(key, outpoint)| key.input(outpoint.clone()))
+        .collect();
+    let taproot = |script: Vec<u8>| TransactionOutput::from_script(20_000, script);
+
+    // two outputs paying to the address, in any order
+    let outputs = vec![
+        taproot(silent_payment_script(&spending, &address, 1)),
+        taproot([&[0x51, 0x20][..], &[0x09; 32]].concat()),
+        taproot(silent_payment_script(&spending, &address, 0)),
+    ];
+    let tweak = silent_payment_tweak(&inputs, &outputs).unwrap();
+    let found = keys.scan(&tweak, &outputs).unwrap();
+    assert_eq!(
+        vec![2, 0],
+        found.iter().map(|(vout, _)| *vout).collect::<Vec<usize>>()
+    );
+
+    // the spend key tweaked by the tweak of an output holds it
+    let secp = Secp256k1::new();
+    let mut spend = Spending::Witness(8).secret();
+    spend.add_assign(&found[1].1).unwrap();
+    assert_eq!(
+        &PublicKey::from_secret_key(&secp, &spend).serialize()[1..],
+        &outputs[0].pk_script()[2..]
+    );
+
+    // the second output alone isn't found, the sender pays the first one first
+    let outputs = vec![taproot(silent_payment_script(&spending, &address, 1))];
+    assert!(keys.scan(&tweak, &outputs).unwrap().is_empty());
+
+    // another address finds nothing
+    let other = SilentPaymentKeys::new(&[6; 32], &Spending::Witness(8).public_key()).unwrap();
+    let outputs = vec![taproot(silent_payment_script(&spending, &address, 0))];
+    assert!(other.scan(&tweak, &outputs).unwrap().is_empty());
+}
@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use crate::{
+    AccountType, Backend, MockBackend, Network, Recipient, StatsInterval, Wallet, MOCK_GENESIS_TIME,
+};
+
+#[test]
+pub fn test_period_start() {
+    let noon = 1_709_164_800 + 12 * 3600;
+    assert_eq!(1_709_164_800, StatsInterval::Day.period_start(noon));
+    assert_eq!(1_708_905_600, StatsInterval::Week.period_start(noon));
+    assert_eq!(1_706_745_600, StatsInterval::Month.period_start(noon));
+
+    // the epoch is a thursday of the week starting on monday the 29th of december
+    assert_eq!(0, StatsInterval::Month.period_start(0));
+    assert_eq!(
+        1_599_436_800,
+        StatsInterval::Week.period_start(MOCK_GENESIS_TIME)
+    );
+    assert_eq!(
+        1_598_918_400,
+        StatsInterval::Month.period_start(MOCK_GENESIS_TIME)
+    );
+}
+
+#[test]
+pub fn test_wallet_stats() {
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let spending = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let saving = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(spending).unwrap();
+    let savings = wallet.new_receive_address(saving).unwrap();
+
+    let backend = MockBackend::new(Network::Mainnet);
+    backend.fund(&receive, 100_000).unwrap();
+    backend.mine(1);
+    wallet.sync(&backend).unwrap();
+
+    let mut tx = wallet
+        .send_many(spending, &[Recipient::new(&savings, 40_000)], 5)
+        .unwrap();
+    wallet.sign_transaction(&mut tx).unwrap();
+    wallet.archive_transaction(&tx).unwrap();
+    let vsize = wallet.decode_transaction(&tx.to_hex()).unwrap().vsize;
+    backend.broadcast(&tx.to_hex()).unwrap();
+    backend.mine(1);
+    wallet.sync(&backend).unwrap();
+
+    let stats = wallet.stats(StatsInterval::Day);
+    assert_eq!(2, stats.accounts.len());
+    let (spent, saved) = (&stats.accounts[0], &stats.accounts[1]);
+    assert_eq!(Some(spending), spent.account);
+    assert_eq!(100_000, spent.received);
+    assert_eq!(40_000 + tx.fee(), spent.sent);
+    assert_eq!(2, spent.transactions);
+    assert_eq!(Some(saving), saved.account);
+    assert_eq!(
+        (40_000, 0, 1),
+        (saved.received, saved.sent, saved.transactions)
+    );
+
+    // both blocks were mined on the day of the genesis block
+    assert_eq!(1, spent.periods.len());
+    assert_eq!(Some(1_599_955_200), spent.periods[0].start);
+    assert_eq!(2, spent.periods[0].transactions);
+
+    assert_eq!(tx.fee(), stats.fees_paid);
+    assert_eq!(Some(tx.fee() as f64 / vsize as f64), stats.average_fee_rate);
+
+    assert_eq!(2, stats.utxo_count);
+    assert_eq!(7, stats.utxo_ages.len());
+    assert_eq!(Some(6), stats.utxo_ages[1].max_confirmations);
+    assert_eq!(2, stats.utxo_ages[1].count);
+    assert_eq!(100_000 - tx.fee(), stats.utxo_ages[1].value);
+    assert_eq!(
+        vec![60_000 - tx.fee(), 40_000],
+        stats
+            .largest_utxos
+            .iter()
+            .map(|utxo| utxo.value())
+            .collect::<Vec<i64>>()
+    );
+}
@@ -0,0 +1,100 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+use crate::{AccountType, Network, Wallet};
+
+/// a subscriber keeping every span and event it sees as a line of text
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+struct Line(String);
+
+impl Visit for Line {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.push_str(&format!(" {}={:?}", field.name(), value));
+    }
+}
+
+impl Recorder {
+    fn push(&self, line: Line) {
+        self.0.lock().unwrap().push(line.0);
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut line = Line(span.metadata().name().to_string());
+        span.record(&mut line);
+        self.push(line);
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, values: &Record<'_>) {
+        let mut line = Line(String::new());
+        values.record(&mut line);
+        self.push(line);
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut line = Line(String::new());
+        event.record(&mut line);
+        self.push(line);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+pub fn test_tracing_events() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let lines = Arc::new(Mutex::new(vec![]));
+
+    let secret = tracing::subscriber::with_default(Recorder(lines.clone()), || {
+        let data_dir = std::env::temp_dir().join("waller_test_tracing_events");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let mut wallet =
+            Wallet::restore(mnemonic.clone(), Network::Mainnet, true, data_dir, false).unwrap();
+        let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+        let address = wallet.new_receive_address(account).unwrap();
+        wallet.sign_data(address.clone(), vec![1; 32]).unwrap();
+        assert!(wallet
+            .sign_data("unknown".to_string(), vec![1; 32])
+            .is_err());
+        wallet.flush().unwrap();
+        tracing::info!(wallet = ?wallet, "done");
+        wallet.get_address(address).unwrap()
+    });
+
+    let lines = lines.lock().unwrap();
+    let has = |text: &str| lines.iter().any(|line| line.contains(text));
+    assert!(has("address issued"));
+    assert!(has("insert_child"));
+    assert!(has("sign_data"));
+    assert!(has("address is not in this wallet"));
+    assert!(has("wallet written"));
+
+    // nothing recorded, not even the wallet itself, holds key material
+    for line in lines.iter() {
+        assert!(!line.contains(&hex::encode(secret.bytes())));
+        assert!(!line.contains(&format!("{:?}", secret.bytes())));
+        assert!(!line.contains(&secret.to_wif()));
+        assert!(!line.contains(&mnemonic));
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{
+    read_varint, reverse_byte_order, reverse_bytes, write_i64_le, write_u32_le, write_varint,
+    TransactionError,
+};
+
+#[test]
+pub fn test_varint() {
+    let cases: [(u64, &str); 7] = [
+        (0, "00"),
+        (0xfc, "fc"),
+        (0xfd, "fdfd00"),
+        (0xffff, "fdffff"),
+        (0x10000, "fe00000100"),
+        (0xffffffff, "feffffffff"),
+        (0x100000000, "ff0000000001000000"),
+    ];
+    for (value, encoded) in cases {
+        let mut bytes = vec![];
+        write_varint(&mut bytes, value);
+        assert_eq!(encoded, hex::encode(&bytes));
+
+        // trailing bytes are left unread
+        bytes.push(0xaa);
+        assert_eq!((value, bytes.len() - 1), read_varint(&bytes).unwrap());
+    }
+
+    // written with more bytes than needed
+    assert!(matches!(
+        read_varint(&hex::decode("fdfc00").unwrap()),
+        Err(TransactionError::Decode(_))
+    ));
+    assert!(read_varint(&hex::decode("feffff0000").unwrap()).is_err());
+    assert!(read_varint(&hex::decode("ffffffffff00000000").unwrap()).is_err());
+    // the bytes end first
+    assert!(read_varint(&[]).is_err());
+    assert!(read_varint(&hex::decode("fe0000").unwrap()).is_err());
+}
+
+#[test]
+pub fn test_byte_order() {
+    let mut bytes = vec![];
+    write_u32_le(&mut bytes, 1);
+    write_i64_le(&mut bytes, -2);
+    assert_eq!("01000000feffffffffffffff", hex::encode(&bytes));
+
+    assert_eq!(vec![3, 2, 1], reverse_bytes(&[1, 2, 3]));
+    assert!(reverse_bytes(&[]).is_empty());
+    assert_eq!("efbeadde", reverse_byte_order("deadbeef".to_string()));
+}
@@ -0,0 +1,612 @@
+#![allow(unused_imports)]
+
+use std::convert::TryInto;
+
+use bip0039::Mnemonic;
+
+use crate::{
+    bip322_message_hash, bip322_to_sign, bip322_to_spend, bip38_decrypt, bip38_encrypt,
+    combine_shares, compress_public_key, legacy_sighash, mnemonic_to_seed, serialize_xpub,
+    test_vectors::{
+        BIP143_VECTORS, BIP322_ADDRESS, BIP322_VECTORS, BIP32_VECTORS, BIP341_VECTORS,
+        BIP38_VECTORS, BIP39_PASSPHRASE, BIP39_VECTORS, BIP44_VECTORS, BIP49_VECTORS,
+        BIP84_VECTORS, BIP86_VECTORS, SLIP39_PASSPHRASE, SLIP39_VECTORS,
+    },
+    verify_ownership, ChildNumber, Key, LockTime, Network, SigHashType, SighashCache, SighashMode,
+    Transaction, TransactionError, TransactionInput, TransactionOutput, TransactionType,
+    TransactionVersion, SIGHASH_ALL,
+};
+
+/// walk a derivation path like `m/0h/1` or `m/44'/0'/0'/0/0` from a master key
+#[allow(dead_code)]
+fn derive_path(master: &Key, path: &str) -> Key {
+    let mut key = master.clone();
+    for step in path.split('/').skip(1) {
+        let hardened = step.ends_with('h') || step.ends_with('\'');
+        let index: u32 = step.trim_end_matches(['h', '\'']).parse().unwrap();
+        let child = match hardened {
+            true => ChildNumber::Hardened(index),
+            false => ChildNumber::Normal(index),
+        };
+        key = key.derive_child_private_key(child).unwrap();
+    }
+    key
+}
+
+/// split a serialized extended private key into its chain code and private key
+#[allow(dead_code)]
+fn decode_xprv(xprv: &str) -> (Vec<u8>, Vec<u8>) {
+    let bytes = bs58::decode(xprv).into_vec().unwrap();
+    (bytes[13..45].to_vec(), bytes[46..78].to_vec())
+}
+
+/// read a compact size integer, advancing the cursor
+#[allow(dead_code)]
+fn read_compact_size(bytes: &[u8], cursor: &mut usize) -> usize {
+    let (size, width) = match bytes[*cursor] {
+        0xfd => (
+            u16::from_le_bytes([bytes[*cursor + 1], bytes[*cursor + 2]]) as usize,
+            3,
+        ),
+        0xfe => (
+            u32::from_le_bytes(bytes[*cursor + 1..*cursor + 5].try_into().unwrap()) as usize,
+            5,
+        ),
+        size => (size as usize, 1),
+    };
+    *cursor += width;
+    size
+}
+
+/// parse a compact size prefixed vector of serialized outputs
+#[allow(dead_code)]
+fn parse_outputs(bytes: &[u8], cursor: &mut usize) -> Vec<TransactionOutput> {
+    let mut outputs = vec![];
+    for _ in 0..read_compact_size(bytes, cursor) {
+        let value = i64::from_le_bytes(bytes[*cursor..*cursor + 8].try_into().unwrap());
+        *cursor += 8;
+        let script_length = read_compact_size(bytes, cursor);
+        let script = bytes[*cursor..*cursor + script_length].to_vec();
+        *cursor += script_length;
+        outputs.push(TransactionOutput::from_script(value, script));
+    }
+    outputs
+}
+
+/// parse an unsigned legacy serialized transaction,
+/// the spent outputs are given in input order
+#[allow(dead_code)]
+fn parse_tx(raw: &str, prevouts: &[TransactionOutput]) -> Transaction {
+    let bytes = hex::decode(raw).unwrap();
+    let version = match u32::from_le_bytes(bytes[..4].try_into().unwrap()) {
+        1 => TransactionVersion::One,
+        2 => TransactionVersion::Two,
+        version => TransactionVersion::Nonstandard(version),
+    };
+    let mut cursor = 4;
+
+    let mut inputs = vec![];
+    for index in 0..read_compact_size(&bytes, &mut cursor) {
+        let mut tx_id = bytes[cursor..cursor + 32].to_vec();
+        tx_id.reverse();
+        let vout = i32::from_le_bytes(bytes[cursor + 32..cursor + 36].try_into().unwrap());
+        cursor += 36;
+
+        let script_length = read_compact_size(&bytes, &mut cursor);
+        cursor += script_length;
+
+        let utxo = prevouts
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| TransactionOutput::from_script(0, vec![]));
+        let mut input = TransactionInput::new(utxo, hex::encode(tx_id), vout);
+        input.set_sequence(u32::from_le_bytes(
+            bytes[cursor..cursor + 4].try_into().unwrap(),
+        ));
+        cursor += 4;
+        inputs.push(input);
+    }
+
+    let outputs = parse_outputs(&bytes, &mut cursor);
+
+    let lock_time = u32::from_le_bytes(bytes[cursor..cursor + 4].try_into().unwrap());
+    let mut tx = Transaction::new(
+        TransactionType::Pay2PubKeyHash,
+        inputs,
+        outputs,
+        Some(LockTime::from_consensus(lock_time)),
+    );
+    tx.set_version(version);
+    tx
+}
+
+#[test]
+pub fn test_bip39_vectors() {
+    for vector in BIP39_VECTORS {
+        let mnemonic = Mnemonic::from_entropy(hex::decode(vector.entropy).unwrap()).unwrap();
+        assert_eq!(vector.mnemonic, mnemonic.phrase());
+
+        let seed = mnemonic_to_seed(vector.mnemonic.to_string(), BIP39_PASSPHRASE).unwrap();
+        assert_eq!(vector.seed, hex::encode(seed), "{}", vector.mnemonic);
+    }
+}
+
+#[test]
+pub fn test_bip32_vectors() {
+    for vector in BIP32_VECTORS {
+        let seed = hex::decode(vector.seed).unwrap();
+        let master = Key::from_seed(&seed, Network::Mainnet, true).unwrap();
+
+        for chain in vector.chains {
+            let key = derive_path(&master, chain.path);
+            let (chain_code, private_key) = decode_xprv(chain.xprv);
+
+            assert_eq!(hex::encode(private_key), key.hex(), "{}", chain.path);
+            assert_eq!(
+                hex::encode(chain_code),
+                hex::encode(&key.extended_private_key()[32..]),
+                "{}",
+                chain.path
+            );
+        }
+    }
+}
+
+#[test]
+pub fn test_bip32_child_vectors() {
+    // every chain of a vector is a child of the one before it
+    for vector in BIP32_VECTORS {
+        for pair in vector.chains.windows(2) {
+            let (chain_code, private_key) = decode_xprv(pair[0].xprv);
+            let parent = Key::from_extended_private_key(
+                &[private_key, chain_code].concat(),
+                Network::Mainnet,
+                true,
+            )
+            .unwrap();
+            let step = pair[1].path.rsplit('/').next().unwrap();
+            let key = derive_path(&parent, &format!("m/{}", step));
+
+            let (chain_code, private_key) = decode_xprv(pair[1].xprv);
+            assert_eq!(hex::encode(private_key), key.hex(), "{}", pair[1].path);
+            assert_eq!(
+                hex::encode(chain_code),
+                hex::encode(&key.extended_private_key()[32..]),
+                "{}",
+                pair[1].path
+            );
+        }
+    }
+}
+
+#[test]
+pub fn test_bip44_vectors() {
+    for vector in BIP44_VECTORS {
+        let network = match vector.path.starts_with("m/44'/1'") {
+            true => Network::Testnet,
+            false => Network::Mainnet,
+        };
+        let master = Key::new(vector.mnemonic.to_string(), network, true).unwrap();
+        let key = derive_path(&master, vector.path);
+
+        assert_eq!(vector.address, key.address().unwrap(), "{}", vector.path);
+    }
+}
+
+#[test]
+pub fn test_bip32_key_origins() {
+    // derived from the master key of each vector, read from its xprv
+    for vector in BIP32_VECTORS {
+        let (chain_code, private_key) = decode_xprv(vector.chains[0].xprv);
+        let master = Key::from_extended_private_key(
+            &[private_key, chain_code].concat(),
+            Network::Mainnet,
+            true,
+        )
+        .unwrap();
+        assert_eq!(0, master.depth());
+        assert_eq!(None, master.child_number());
+
+        for chain in vector.chains {
+            let key = derive_path(&master, chain.path);
+            let bytes = bs58::decode(chain.xpub).into_vec().unwrap();
+
+            assert_eq!(bytes[4], key.depth(), "{}", chain.path);
+            assert_eq!(&bytes[5..9], &key.parent_fingerprint(), "{}", chain.path);
+            assert_eq!(chain.xpub, key.to_xpub().unwrap(), "{}", chain.path);
+        }
+    }
+}
+
+#[test]
+pub fn test_bip32_xpub_serialization() {
+    for vector in BIP32_VECTORS {
+        for chain in vector.chains {
+            let bytes = bs58::decode(chain.xpub).into_vec().unwrap();
+            let mut parent_fingerprint = [0; 4];
+            parent_fingerprint.copy_from_slice(&bytes[5..9]);
+            let child_number = u32::from_be_bytes([bytes[9], bytes[10], bytes[11], bytes[12]]);
+
+            let xpub = serialize_xpub(
+                Network::Mainnet,
+                bytes[4],
+                parent_fingerprint,
+                child_number,
+                &bytes[13..45],
+                &bytes[45..78],
+            )
+            .unwrap();
+
+            assert_eq!(chain.xpub, xpub, "{}", chain.path);
+        }
+    }
+}
+
+#[test]
+pub fn test_bip49_vectors() {
+    for vector in BIP49_VECTORS {
+        let network = match vector.path.starts_with("m/49'/1'") {
+            true => Network::Testnet,
+            false => Network::Mainnet,
+        };
+        let master = Key::new(vector.mnemonic.to_string(), network, true).unwrap();
+        let key = derive_path(&master, vector.path);
+
+        assert_eq!(
+            vector.address,
+            key.nested_segwit_address().unwrap(),
+            "{}",
+            vector.path
+        );
+    }
+}
+
+#[test]
+pub fn test_bip84_vectors() {
+    for vector in BIP84_VECTORS {
+        let master = Key::new(vector.mnemonic.to_string(), Network::Mainnet, true).unwrap();
+        let key = derive_path(&master, vector.path);
+
+        assert_eq!(
+            vector.address,
+            key.native_segwit_address().unwrap(),
+            "{}",
+            vector.path
+        );
+    }
+}
+
+#[test]
+pub fn test_bip86_vectors() {
+    for vector in BIP86_VECTORS {
+        let master = Key::new(vector.mnemonic.to_string(), Network::Mainnet, true).unwrap();
+        let key = derive_path(&master, vector.path);
+
+        assert_eq!(
+            vector.address,
+            key.taproot_address().unwrap(),
+            "{}",
+            vector.path
+        );
+    }
+}
+
+#[test]
+pub fn test_bip143_vectors() {
+    for vector in BIP143_VECTORS {
+        let tx = parse_tx(vector.unsigned_tx, &[]);
+        let sighash = SighashCache::new()
+            .segwit_v0_sighash(
+                &tx,
+                vector.input_index,
+                &hex::decode(vector.script_code).unwrap(),
+                vector.amount as i64,
+                vector.sighash_type,
+            )
+            .unwrap();
+
+        assert_eq!(vector.sighash, hex::encode(sighash));
+    }
+}
+
+#[test]
+pub fn test_bip143_nested_segwit_signing() {
+    // the P2SH-P2WPKH example from BIP143, signed with its published key
+    let vector = BIP143_VECTORS[1];
+    let key =
+        Key::from_wif("L57KYn5isHFThD4cohjJgLTZA2vaxnMMKWngnzbttF159yH9dARf".to_string()).unwrap();
+    assert_eq!(
+        "38BW8nqpHSWpkf5sXrQd2xYwvnPJwP59ic",
+        key.nested_segwit_address().unwrap()
+    );
+
+    let prevout = TransactionOutput::from_script(vector.amount as i64, vec![]);
+    let mut tx = parse_tx(vector.unsigned_tx, &[prevout]);
+    let unsigned_id = tx.tx_id();
+    tx.sign_nested_segwit_input(vector.input_index, &key, SigHashType::All)
+        .unwrap();
+
+    assert_eq!(
+        "01000000000101db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a5477010000001716001479091972186c449eb1ded22b78e40d009bdf0089feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac02473044022047ac8e878352d3ebbde1c94ce3a10d057c24175747116f8288e5d794d12d482f0220217f36a485cae903c713331d877c1f64677e3622ad4010726870540656fe9dcb012103ad1d8e89212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a2687392040000",
+        tx.to_hex()
+    );
+    assert_eq!(
+        SIGHASH_ALL as u8,
+        *tx.get_input(0).unwrap().witness()[0].last().unwrap()
+    );
+    // the witness is not part of the txid but the signature script is
+    assert_ne!(unsigned_id, tx.tx_id());
+    assert!(tx.weight() < tx.serialize().len() as u64 * 4);
+}
+
+#[test]
+pub fn test_bip341_vectors() {
+    for vector in BIP341_VECTORS {
+        let prevouts = parse_outputs(&hex::decode(vector.prevouts).unwrap(), &mut 0);
+        let tx = parse_tx(vector.tx, &prevouts);
+        let annex = vector.annex.map(|annex| hex::decode(annex).unwrap());
+
+        let sighash = SighashCache::new()
+            .taproot_key_spend_sighash(
+                &tx,
+                vector.input_index,
+                vector.sighash_type,
+                annex.as_deref(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            vector.sighash,
+            hex::encode(sighash),
+            "sighash type {:#x}",
+            vector.sighash_type
+        );
+    }
+}
+
+#[test]
+pub fn test_sighash_cache_reuse() {
+    let prevouts = parse_outputs(&hex::decode(BIP341_VECTORS[1].prevouts).unwrap(), &mut 0);
+    let tx = parse_tx(BIP341_VECTORS[1].tx, &prevouts);
+    let script_code = hex::decode(BIP143_VECTORS[0].script_code).unwrap();
+
+    let mut cache = SighashCache::new();
+    for index in 0..tx.tx_in_count() {
+        assert_eq!(
+            SighashCache::new()
+                .segwit_v0_sighash(&tx, index, &script_code, 1000, SIGHASH_ALL)
+                .unwrap(),
+            cache
+                .segwit_v0_sighash(&tx, index, &script_code, 1000, SIGHASH_ALL)
+                .unwrap()
+        );
+        assert_eq!(
+            SighashCache::new()
+                .taproot_key_spend_sighash(&tx, index, 0x01, None)
+                .unwrap(),
+            cache
+                .taproot_key_spend_sighash(&tx, index, 0x01, None)
+                .unwrap()
+        );
+    }
+}
+
+#[test]
+pub fn test_slip39_vectors() {
+    for vector in SLIP39_VECTORS {
+        let recovered = combine_shares(vector.mnemonics, SLIP39_PASSPHRASE);
+
+        match vector.master_secret {
+            "" => assert!(recovered.is_err(), "{}", vector.description),
+            secret => assert_eq!(
+                secret,
+                hex::encode(recovered.unwrap()),
+                "{}",
+                vector.description
+            ),
+        }
+    }
+}
+
+#[test]
+pub fn test_bip38_vectors() {
+    for vector in BIP38_VECTORS {
+        let key = Key::from_wif(vector.wif.to_string()).unwrap();
+        assert_eq!(
+            vector.encrypted,
+            bip38_encrypt(&key, vector.passphrase).unwrap()
+        );
+
+        let decrypted = bip38_decrypt(vector.encrypted, vector.passphrase).unwrap();
+        assert_eq!(vector.wif, decrypted.to_wif());
+    }
+}
+
+#[test]
+pub fn test_bip322_vectors() {
+    for vector in BIP322_VECTORS {
+        assert_eq!(
+            vector.message_hash,
+            hex::encode(bip322_message_hash(vector.message.as_bytes()))
+        );
+        let to_spend = bip322_to_spend(BIP322_ADDRESS, vector.message, Network::Mainnet).unwrap();
+        assert_eq!(vector.to_spend, to_spend.tx_id());
+        assert_eq!(vector.to_sign, bip322_to_sign(&to_spend).tx_id());
+
+        verify_ownership(
+            BIP322_ADDRESS,
+            vector.message,
+            vector.signature,
+            Network::Mainnet,
+        )
+        .unwrap();
+    }
+    // each signature only holds for its own message
+    assert!(verify_ownership(
+        BIP322_ADDRESS,
+        BIP322_VECTORS[1].message,
+        BIP322_VECTORS[0].signature,
+        Network::Mainnet,
+    )
+    .is_err());
+}
+
+#[test]
+pub fn test_transaction_sighash() {
+    // the outputs spent by the native P2WPKH example of BIP143, a P2PK and a P2WPKH output
+    let vector = BIP143_VECTORS[0];
+    let prevouts = [
+        TransactionOutput::from_script(
+            625_000_000,
+            hex::decode("2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac")
+                .unwrap(),
+        ),
+        TransactionOutput::from_script(
+            vector.amount as i64,
+            hex::decode("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap(),
+        ),
+    ];
+    let tx = parse_tx(vector.unsigned_tx, &prevouts);
+    let script_code = hex::decode(vector.script_code).unwrap();
+
+    assert_eq!(
+        SighashMode::SegwitV0,
+        tx.sighash_mode(1, &script_code).unwrap()
+    );
+    assert_eq!(
+        vector.sighash,
+        hex::encode(
+            tx.sighash(
+                1,
+                &script_code,
+                SigHashType::from_u32(vector.sighash_type).unwrap()
+            )
+            .unwrap()
+        )
+    );
+
+    let p2pk = prevouts[0].pk_script().to_vec();
+    assert_eq!(SighashMode::Legacy, tx.sighash_mode(0, &p2pk).unwrap());
+    assert_eq!(
+        legacy_sighash(&tx, 0, &p2pk, SIGHASH_ALL).unwrap(),
+        tx.sighash(0, &p2pk, SigHashType::All).unwrap()
+    );
+
+    // a P2SH input is nested segwit unless the script code is its redeem script
+    let vector = BIP143_VECTORS[1];
+    let prevout = TransactionOutput::from_script(
+        vector.amount as i64,
+        hex::decode("a9144733f37cf4db86fbc2efed2500b4f4e49f31202387").unwrap(),
+    );
+    let tx = parse_tx(vector.unsigned_tx, &[prevout]);
+    let script_code = hex::decode(vector.script_code).unwrap();
+    let redeem_script = hex::decode("001479091972186c449eb1ded22b78e40d009bdf0089").unwrap();
+
+    assert_eq!(
+        SighashMode::SegwitV0,
+        tx.sighash_mode(0, &script_code).unwrap()
+    );
+    assert_eq!(
+        vector.sighash,
+        hex::encode(
+            tx.sighash(
+                0,
+                &script_code,
+                SigHashType::from_u32(vector.sighash_type).unwrap()
+            )
+            .unwrap()
+        )
+    );
+    assert_eq!(
+        SighashMode::Legacy,
+        tx.sighash_mode(0, &redeem_script).unwrap()
+    );
+
+    // the vectors spending P2TR outputs, the others spend arbitrary scripts
+    for vector in BIP341_VECTORS
+        .iter()
+        .filter(|vector| vector.annex.is_none())
+    {
+        let prevouts = parse_outputs(&hex::decode(vector.prevouts).unwrap(), &mut 0);
+        if !prevouts[vector.input_index]
+            .pk_script()
+            .starts_with(&[0x51, 0x20])
+        {
+            continue;
+        }
+        let tx = parse_tx(vector.tx, &prevouts);
+
+        assert_eq!(
+            SighashMode::Taproot,
+            tx.sighash_mode(vector.input_index, &[]).unwrap()
+        );
+        let sighash_type = match vector.sighash_type {
+            0x00 => SigHashType::TaprootDefault,
+            flag => SigHashType::from_u32(flag as u32).unwrap(),
+        };
+        assert_eq!(
+            vector.sighash,
+            hex::encode(tx.sighash(vector.input_index, &[], sighash_type).unwrap())
+        );
+    }
+
+    // SIGHASH_DEFAULT is only defined for taproot
+    assert!(matches!(
+        tx.sighash(0, &script_code, SigHashType::TaprootDefault),
+        Err(TransactionError::InvalidSighashType(0x00))
+    ));
+
+    assert!(matches!(
+        tx.sighash(5, &script_code, SigHashType::All),
+        Err(TransactionError::InputOutOfRange(5))
+    ));
+}
+
+#[test]
+pub fn test_external_signature() {
+    // the P2SH-P2WPKH example from BIP143, signed outside of the transaction
+    let vector = BIP143_VECTORS[1];
+    let key =
+        Key::from_wif("L57KYn5isHFThD4cohjJgLTZA2vaxnMMKWngnzbttF159yH9dARf".to_string()).unwrap();
+    let pubkey = compress_public_key(&key.new_public_key().unwrap()).unwrap();
+
+    let prevout = TransactionOutput::from_script(
+        vector.amount as i64,
+        hex::decode("a9144733f37cf4db86fbc2efed2500b4f4e49f31202387").unwrap(),
+    );
+    let mut tx = parse_tx(vector.unsigned_tx, &[prevout]);
+    let script_code = hex::decode(vector.script_code).unwrap();
+
+    let sighash = tx.sighash(0, &script_code, SigHashType::All).unwrap();
+    let mut signature = key.sign_der(&sighash).unwrap();
+    signature.push(SIGHASH_ALL as u8);
+
+    // a signature over another digest is refused
+    let mut wrong = key.sign_der(&[1; 32]).unwrap();
+    wrong.push(SIGHASH_ALL as u8);
+    assert!(matches!(
+        tx.set_signature(0, &wrong, &pubkey),
+        Err(TransactionError::InvalidSignature(0))
+    ));
+    let other =
+        Key::from_wif("KyZpNDKnfs94vbrwhJneDi77V6jF64PWPF8x5cdJb8ifgg2DUc9d".to_string()).unwrap();
+    let other = compress_public_key(&other.new_public_key().unwrap()).unwrap();
+    assert!(matches!(
+        tx.set_signature(0, &signature, &other),
+        Err(TransactionError::KeyNotInScript(0))
+    ));
+
+    tx.set_signature(0, &signature, &pubkey).unwrap();
+    assert_eq!(
+        "01000000000101db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a5477010000001716001479091972186c449eb1ded22b78e40d009bdf0089feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac02473044022047ac8e878352d3ebbde1c94ce3a10d057c24175747116f8288e5d794d12d482f0220217f36a485cae903c713331d877c1f64677e3622ad4010726870540656fe9dcb012103ad1d8e89212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a2687392040000",
+        tx.to_hex()
+    );
+
+    // a witness built elsewhere is put in as it is
+    tx.set_witness(0, vec![signature.clone()]).unwrap();
+    assert_eq!(&vec![signature], tx.get_input(0).unwrap().witness());
+    assert!(matches!(
+        tx.set_witness(1, vec![]),
+        Err(TransactionError::InputOutOfRange(1))
+    ));
+}
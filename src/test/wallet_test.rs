@@ -1,8 +1,25 @@
 #![allow(unused_imports)]
 
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
 
-use crate::{Network, Wallet};
+use crate::{
+    base58check_encode, compress_public_key, key_fingerprint, seal_wallet_file,
+    silent_payment_tweak, verify_descriptor_checksum, wallet_file_mac, with_descriptor_checksum,
+    AccountType, Backend, BackendError, Birthday, Block, BlockTransaction, Chain, ChildNumber,
+    Compaction, Currency, Decimal, Digest32, EncryptionParams, ExportFormat, FeeBump, FeeLimits,
+    Key, KeyType, KeystoreBackend, LockTime, MasterKeyDerivation, MockBackend, MockClock, Network,
+    OutPoint, ProprietaryFields, RateProvider, Recipient, RetentionPolicy, Script, SharedWallet,
+    SigHashType, SignerError, SigningRequest, SigningResponse, SilentPaymentAddress,
+    SilentPaymentInput, Transaction, TransactionBuilder, TransactionError, TransactionInput,
+    TransactionOutput, TransactionType, TxOrdering, Utxo, VerifyError, Wallet, WalletError,
+    WalletEvent, WalletSection, DEFAULT_MAX_FEE_RATE, SIGHASH_ALL, WALLER_PROPRIETARY_PREFIX,
+    WALLET_VERSION,
+};
 
 #[test]
 pub fn test_wallet_init() {
@@ -13,3 +30,4612 @@ pub fn test_wallet_init() {
     println!("mnemonic :: {}", mnemonic);
     println!("addresses\n{:#?}", wallet.addresses().unwrap());
 }
+
+#[test]
+pub fn test_wallet_lock_unlock() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    let address = wallet.addresses().unwrap().pop().unwrap();
+    let digest = vec![7; 32];
+    let signature = wallet.sign_data(address.clone(), digest.clone()).unwrap();
+    assert_eq!(
+        wallet
+            .sign_data(
+                address.clone(),
+                Digest32::tagged("waller/test", b"data").as_bytes().to_vec()
+            )
+            .unwrap(),
+        wallet
+            .sign_raw_digest_with_tag(address.clone(), "waller/test", b"data")
+            .unwrap()
+    );
+
+    assert!(matches!(wallet.lock(), Err(WalletError::Unencrypted)));
+
+    wallet.encrypt("correct horse").unwrap();
+    assert!(wallet.is_locked());
+    assert!(wallet.get_address(address.clone()).unwrap().is_wiped());
+    assert!(matches!(
+        wallet.sign_data(address.clone(), digest.clone()),
+        Err(WalletError::Locked)
+    ));
+    assert!(matches!(
+        wallet.unlock("battery staple"),
+        Err(WalletError::IncorrectPassphrase)
+    ));
+
+    wallet.unlock("correct horse").unwrap();
+    assert!(!wallet.is_locked());
+    assert_eq!(
+        signature,
+        wallet.sign_data(address.clone(), digest).unwrap()
+    );
+
+    wallet.lock().unwrap();
+    assert!(wallet.is_locked());
+}
+
+#[test]
+pub fn test_change_passphrase() {
+    let data_dir = std::env::temp_dir().join("waller_test_change_passphrase");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        data_dir.clone(),
+        false,
+    )
+    .unwrap();
+    let address = wallet.addresses().unwrap().pop().unwrap();
+    let signature = wallet.sign_data(address.clone(), vec![7; 32]).unwrap();
+
+    assert!(matches!(
+        wallet.change_passphrase("", "new"),
+        Err(WalletError::Unencrypted)
+    ));
+    wallet.encrypt("old").unwrap();
+    wallet.unlock("old").unwrap();
+    wallet.lock().unwrap();
+    let file = wallet.flush().unwrap();
+    assert!(matches!(
+        wallet.change_passphrase("wrong", "new"),
+        Err(WalletError::IncorrectPassphrase)
+    ));
+
+    // a locked wallet stays locked, its keys are sealed with the new passphrase
+    wallet.change_passphrase("old", "new").unwrap();
+    assert!(wallet.is_locked());
+    assert!(matches!(
+        wallet.unlock("old"),
+        Err(WalletError::IncorrectPassphrase)
+    ));
+    wallet.unlock("new").unwrap();
+    assert_eq!(
+        signature,
+        wallet.sign_data(address.clone(), vec![7; 32]).unwrap()
+    );
+
+    // the change was flushed, the file is authenticated with the new key
+    let mut loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert!(loaded.is_locked());
+    loaded.unlock("new").unwrap();
+    assert_eq!(
+        signature,
+        loaded.sign_data(address.clone(), vec![7; 32]).unwrap()
+    );
+
+    // an unlocked wallet with a key cache stays unlocked and drops its cached keys
+    loaded.set_key_cache(Some(4)).unwrap();
+    loaded.sign_data(address.clone(), vec![7; 32]).unwrap();
+    assert_eq!(1, loaded.key_cache_status().unwrap().len);
+    loaded.change_passphrase("new", "newer").unwrap();
+    assert!(!loaded.is_locked());
+    assert_eq!(0, loaded.key_cache_status().unwrap().len);
+    assert_eq!(
+        signature,
+        loaded.sign_data(address.clone(), vec![7; 32]).unwrap()
+    );
+    loaded.lock().unwrap();
+    loaded.unlock("newer").unwrap();
+
+    let mut loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert!(matches!(
+        loaded.unlock("new"),
+        Err(WalletError::IncorrectPassphrase)
+    ));
+    loaded.unlock("newer").unwrap();
+    assert_eq!(
+        signature,
+        loaded.sign_data(address.clone(), vec![7; 32]).unwrap()
+    );
+
+    // an unlocked wallet holding its keys unsealed writes them sealed with the new passphrase
+    assert!(loaded.key_cache_status().is_none());
+    loaded.change_passphrase("newer", "newest").unwrap();
+    assert!(!loaded.is_locked());
+    let data = std::fs::read_to_string(&file).unwrap();
+    let mut loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert!(matches!(
+        loaded.unlock("newer"),
+        Err(WalletError::IncorrectPassphrase)
+    ));
+    loaded.unlock("newest").unwrap();
+    assert_eq!(signature, loaded.sign_data(address, vec![7; 32]).unwrap());
+
+    // a file changed without the passphrase isn't authenticated again with a new one
+    let mut tampered: serde_json::Value = serde_json::from_str(&data).unwrap();
+    tampered["compress_public_keys"] = serde_json::Value::Bool(false);
+    seal_wallet_file(&mut tampered, None);
+    std::fs::write(&file, tampered.to_string()).unwrap();
+    let mut forged = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert!(matches!(
+        forged.change_passphrase("newest", "forged"),
+        Err(WalletError::Corrupted { .. })
+    ));
+    assert_eq!(
+        tampered.to_string(),
+        std::fs::read_to_string(&file).unwrap()
+    );
+    assert!(matches!(
+        forged.unlock("newest"),
+        Err(WalletError::Corrupted { .. })
+    ));
+}
+
+#[test]
+pub fn test_unlocked_flush_seals_keys() {
+    let data_dir = std::env::temp_dir().join("waller_test_unlocked_flush_seals_keys");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        data_dir,
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    let signature = wallet.sign_data(address.clone(), vec![7; 32]).unwrap();
+    let private_keys: Vec<Vec<u8>> = wallet
+        .keys()
+        .iter()
+        .map(|node| node.data.private_key.bytes().to_vec())
+        .collect();
+
+    wallet.encrypt("passphrase").unwrap();
+    wallet.unlock("passphrase").unwrap();
+    let file = wallet.flush().unwrap();
+
+    // the wallet stays unlocked, its file holds no private key in the clear
+    assert!(!wallet.is_locked());
+    let data = std::fs::read_to_string(&file).unwrap();
+    for private_key in private_keys.iter() {
+        assert!(!data.contains(&serde_json::to_string(private_key).unwrap()));
+        assert!(!data.contains(&hex::encode(private_key)));
+    }
+    let value: serde_json::Value = serde_json::from_str(&data).unwrap();
+    assert_eq!(Some(true), value["locked"].as_bool());
+
+    let mut loaded = Wallet::from_wallet_file(file).unwrap();
+    assert!(loaded.is_locked());
+    loaded.unlock("passphrase").unwrap();
+    assert_eq!(signature, loaded.sign_data(address, vec![7; 32]).unwrap());
+}
+
+#[test]
+pub fn test_rotate_and_drain_account() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    let old = wallet.new_account(AccountType::Legacy).unwrap();
+    let address = wallet.new_receive_address(old).unwrap();
+    assert_eq!(Some(old), wallet.account_of(&address));
+
+    let key = wallet.get_address(address.clone()).unwrap();
+    let output = TransactionOutput::new(TransactionType::Pay2PubKeyHash, key, 100_000);
+    let outpoint = OutPoint::new("11".repeat(32), 0);
+    wallet
+        .add_utxo(Utxo::new(outpoint, output, address))
+        .unwrap();
+
+    let new = wallet.rotate_account().unwrap();
+    assert_ne!(old, new);
+    assert!(wallet.account(old).unwrap().is_archived());
+    assert!(!wallet.account(new).unwrap().is_archived());
+    assert!(matches!(
+        wallet.new_receive_address(old),
+        Err(WalletError::AccountArchived(_))
+    ));
+
+    let tx = wallet.drain_account(old, new, 10).unwrap();
+    // a P2PKH input of 596 weight units and a P2PKH output, 193 vbytes
+    let fee = 1930;
+
+    assert_eq!(1, tx.tx_in_count());
+    assert_eq!(1, tx.tx_out_count());
+    assert_eq!(100_000 - fee, tx.get_output(0).unwrap().value());
+    assert_eq!(100_000, wallet.account_balance(old));
+    assert!(matches!(
+        wallet.drain_account(new, old, 10),
+        Err(WalletError::InsufficientFunds)
+    ));
+}
+
+#[test]
+pub fn test_drain_segwit_account() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    let old = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(old).unwrap();
+    let key = wallet.get_address(address.clone()).unwrap();
+    let output = TransactionOutput::new(TransactionType::Pay2WitnessPubKeyHash, key, 100_000);
+    wallet
+        .add_utxo(Utxo::new(
+            OutPoint::new("22".repeat(32), 0),
+            output,
+            address,
+        ))
+        .unwrap();
+    let new = wallet.rotate_account().unwrap();
+
+    let tx = wallet.drain_account(old, new, 10).unwrap();
+    // a P2WPKH input and output with the marker and flag, 439 weight units
+    let fee = 1100;
+
+    assert_eq!(1, tx.tx_in_count());
+    assert_eq!(100_000 - fee, tx.get_output(0).unwrap().value());
+    assert_eq!(1, tx.tx_out_count());
+}
+
+#[test]
+pub fn test_account_xpub() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        false,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::Legacy).unwrap();
+
+    let exported = wallet.account_xpub(account).unwrap();
+    assert_eq!("m/44'/0'/0'", exported.path);
+    assert!(exported.xpub.starts_with("xpub"));
+    assert_eq!(
+        format!(
+            "[{}/44'/0'/0']{}",
+            hex::encode(exported.fingerprint),
+            exported.xpub
+        ),
+        exported.to_string()
+    );
+
+    let decoded = bs58::decode(&exported.xpub).into_vec().unwrap();
+    assert_eq!(82, decoded.len());
+    // depth, hardened child number and a compressed public key
+    assert_eq!(3, decoded[4]);
+    assert_eq!([0x80, 0, 0, 0], decoded[9..13]);
+    assert!(decoded[45] == 0x02 || decoded[45] == 0x03);
+
+    assert!(matches!(
+        wallet.account_xpub(7),
+        Err(WalletError::AccountNotFound(7))
+    ));
+}
+
+#[test]
+pub fn test_key_origins_of_older_files() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_key_origins");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let exported = wallet.account_xpub(account).unwrap();
+    let decoded = bs58::decode(&exported.xpub).into_vec().unwrap();
+    assert_eq!(3, decoded[4]);
+
+    // written before keys held their origin, without a checksum either
+    let file = wallet.flush().unwrap();
+    let mut value: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    let object = value.as_object_mut().unwrap();
+    object.remove("checksum");
+    for record in object["keys"]["records"].as_array_mut().unwrap() {
+        let key = record["keypair"]["private_key"].as_object_mut().unwrap();
+        for field in ["depth", "parent_fingerprint", "child_number"] {
+            assert!(key.remove(field).is_some());
+        }
+    }
+    std::fs::write(&file, value.to_string()).unwrap();
+
+    let loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert_eq!(exported.xpub, loaded.account_xpub(account).unwrap().xpub);
+    let (recovered, _) = Wallet::from_wallet_file_lenient(file).unwrap();
+    assert_eq!(exported.xpub, recovered.account_xpub(account).unwrap().xpub);
+}
+
+#[test]
+pub fn test_key_records_file() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_key_records");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    let imported = wallet
+        .import_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ", None)
+        .unwrap();
+    let xpub = wallet.account_xpub(account).unwrap().xpub;
+
+    // the keys are written as records, parents before their children
+    let file = wallet.flush().unwrap();
+    let mut data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert!(data.get("arena").is_none());
+    let records = data["keys"]["records"].as_array().unwrap().clone();
+    assert_eq!(wallet.keys().len(), records.len());
+    assert_eq!(0, data["keys"]["root"]);
+    for (index, record) in records.iter().enumerate() {
+        assert!(record["parent"]
+            .as_u64()
+            .is_none_or(|parent| (parent as usize) < index));
+    }
+    assert!(records
+        .iter()
+        .any(|record| record["address"] == imported.as_str()));
+
+    // files written with the arena itself still load
+    let nodes: Vec<serde_json::Value> = records
+        .iter()
+        .map(|record| {
+            serde_json::json!({
+                "parent": record["parent"],
+                "previous_sibling": null,
+                "next_sibling": null,
+                "first_child": null,
+                "last_child": null,
+                "key": record["address"],
+                "data": record["keypair"],
+            })
+        })
+        .collect();
+    let object = data.as_object_mut().unwrap();
+    object.remove("keys");
+    object.insert(
+        "arena".to_string(),
+        serde_json::json!({ "root": 0, "nodes": nodes }),
+    );
+    seal_wallet_file(&mut data, None);
+    std::fs::write(&file, data.to_string()).unwrap();
+
+    let loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert_eq!(wallet.keys().len(), loaded.keys().len());
+    assert_eq!(xpub, loaded.account_xpub(account).unwrap().xpub);
+    assert!(loaded.get_address(address.clone()).is_some());
+    assert!(loaded.get_address(imported.clone()).is_some());
+    let (recovered, report) = Wallet::from_wallet_file_lenient(file.clone()).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+    assert_eq!(wallet.keys().len(), recovered.keys().len());
+    assert_eq!(xpub, recovered.account_xpub(account).unwrap().xpub);
+
+    // and are written back as records
+    loaded.flush().unwrap();
+    let data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert!(data.get("arena").is_none());
+    assert_eq!(
+        records.len(),
+        data["keys"]["records"].as_array().unwrap().len()
+    );
+}
+
+#[test]
+pub fn test_nested_segwit_account() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    let legacy = wallet.new_account(AccountType::Legacy).unwrap();
+    let nested = wallet.new_account(AccountType::NestedSegwit).unwrap();
+    assert_ne!(legacy, nested);
+
+    // account indexes are counted per purpose
+    let account = wallet.account(nested).unwrap();
+    assert_eq!(0, account.index());
+    assert_eq!("m/49'/0'/0'", account.path(Network::Mainnet));
+
+    let address = wallet.new_receive_address(nested).unwrap();
+    assert!(address.starts_with('3'));
+    assert_eq!(Some(nested), wallet.account_of(&address));
+
+    let key = wallet.get_address(address.clone()).unwrap();
+    assert_eq!(address, key.nested_segwit_address().unwrap());
+}
+
+#[test]
+pub fn test_account_nodes_shared() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let mut added = vec![];
+    for expected in 1..4 {
+        let count = wallet.keys().len();
+        let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+        assert_eq!(expected, wallet.account(account).unwrap().index());
+        added.push(wallet.keys().len() - count);
+    }
+    // the purpose and coin type nodes are found again, not derived twice
+    assert!(added.iter().all(|count| *count == added[0]));
+
+    let mut children = std::collections::HashSet::new();
+    for node in wallet.keys() {
+        if let (Some(parent), Some(index)) = (node.parent(), node.data.index) {
+            assert!(children.insert((parent, index)));
+        }
+    }
+}
+
+#[test]
+pub fn test_segwit_account_presets() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    let native = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let taproot = wallet.new_account(AccountType::Taproot).unwrap();
+
+    assert_eq!(
+        "m/84'/0'/0'",
+        wallet.account(native).unwrap().path(Network::Mainnet)
+    );
+    assert_eq!(
+        "m/86'/0'/0'",
+        wallet.account(taproot).unwrap().path(Network::Mainnet)
+    );
+    assert!(wallet
+        .new_receive_address(native)
+        .unwrap()
+        .starts_with("bc1q"));
+    assert!(wallet
+        .new_receive_address(taproot)
+        .unwrap()
+        .starts_with("bc1p"));
+
+    let xpub = wallet.account_xpub(taproot).unwrap();
+    assert_eq!(
+        vec![
+            with_descriptor_checksum(&format!("tr({}/0/*)", xpub)).unwrap(),
+            with_descriptor_checksum(&format!("tr({}/1/*)", xpub)).unwrap()
+        ],
+        wallet.account_descriptors(taproot).unwrap()
+    );
+    assert!(wallet.account_descriptors(native).unwrap()[0].starts_with("wpkh(["));
+    for descriptor in wallet.account_descriptors(native).unwrap() {
+        assert!(verify_descriptor_checksum(&descriptor).is_ok());
+    }
+}
+
+#[test]
+pub fn test_wallet_events() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_wallet_events");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet =
+        Wallet::restore(mnemonic, Network::Mainnet, true, data_dir.clone(), false).unwrap();
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+    wallet.on_event(move |event: &WalletEvent| recorded.lock().unwrap().push(event.clone()));
+
+    let account = wallet.new_account(AccountType::Legacy).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    let key = wallet.get_address(address.clone()).unwrap();
+    let output = TransactionOutput::new(TransactionType::Pay2PubKeyHash, key, 100_000);
+    let outpoint = OutPoint::new("11".repeat(32), 0);
+    wallet
+        .add_utxo(Utxo::new(outpoint.clone(), output, address.clone()))
+        .unwrap();
+
+    wallet.confirm_transaction("11".repeat(32), 800_000);
+    assert_eq!(Some(800_000), wallet.utxos()[0].height());
+    // nothing of ours was confirmed
+    wallet.confirm_transaction("22".repeat(32), 800_001);
+
+    assert!(wallet.spend_utxo(&outpoint).is_some());
+    assert!(wallet.spend_utxo(&outpoint).is_none());
+
+    let replacement = Transaction::new(TransactionType::Pay2PubKeyHash, vec![], vec![], None);
+    wallet.replace_transaction("33".repeat(32), &replacement);
+
+    let file = wallet.flush().unwrap();
+    assert_eq!(data_dir.join("wallet.json"), file);
+    let loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert_eq!(wallet.addresses().unwrap(), loaded.addresses().unwrap());
+
+    let events = events.lock().unwrap();
+    assert_eq!(6, events.len());
+    assert!(matches!(
+        &events[0],
+        WalletEvent::NewAddressIssued { account: 0, address: issued } if issued == &address
+    ));
+    assert!(matches!(&events[1], WalletEvent::UtxoReceived(utxo) if utxo.value() == 100_000));
+    assert!(matches!(
+        &events[2],
+        WalletEvent::TxConfirmed {
+            height: 800_000,
+            ..
+        }
+    ));
+    assert!(matches!(&events[3], WalletEvent::UtxoSpent(spent) if spent == &outpoint));
+    assert!(matches!(
+        &events[4],
+        WalletEvent::FeeBumped { replacement_tx_id, .. } if replacement_tx_id == &replacement.tx_id()
+    ));
+    assert!(matches!(&events[5], WalletEvent::WalletFlushed(path) if path == &file));
+}
+
+#[test]
+pub fn test_shared_wallet() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedWallet>();
+
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_shared_wallet");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet =
+        Wallet::restore(mnemonic, Network::Mainnet, true, data_dir.clone(), false).unwrap();
+    let account = wallet.new_account(AccountType::Legacy).unwrap();
+
+    let shared = Arc::new(SharedWallet::new(wallet));
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                (0..5)
+                    .map(|_| {
+                        let address = shared.new_receive_address(account).unwrap();
+                        shared.sign_data(address.clone(), vec![1; 32]).unwrap();
+                        address
+                    })
+                    .collect::<Vec<String>>()
+            })
+        })
+        .collect();
+
+    let mut addresses: Vec<String> = handles
+        .into_iter()
+        .flat_map(|handle| handle.join().unwrap())
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+    assert_eq!(20, addresses.len());
+
+    let key = shared
+        .read(|wallet| wallet.get_address(addresses[0].clone()))
+        .unwrap()
+        .unwrap();
+    let output = TransactionOutput::new(TransactionType::Pay2PubKeyHash, key, 5_000);
+    let outpoint = OutPoint::new("11".repeat(32), 0);
+    shared
+        .add_utxo(Utxo::new(outpoint.clone(), output, addresses[0].clone()))
+        .unwrap();
+    assert_eq!(5_000, shared.account_balance(account).unwrap());
+
+    let file = shared.flush().unwrap();
+    let loaded = Wallet::from_wallet_file(file).unwrap();
+    assert_eq!(1, loaded.utxos().len());
+
+    assert!(shared.spend_utxo(&outpoint).unwrap().is_some());
+    let wallet = Arc::try_unwrap(shared).unwrap().into_inner().unwrap();
+    assert!(wallet.utxos().is_empty());
+    assert_eq!(loaded.addresses().unwrap(), wallet.addresses().unwrap());
+}
+
+#[test]
+pub fn test_address_counters() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_address_counters");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+
+    let first = wallet.new_account(AccountType::Legacy).unwrap();
+    let second = wallet.new_account(AccountType::NativeSegwit).unwrap();
+
+    let mut issued = vec![
+        wallet.new_receive_address(first).unwrap(),
+        wallet.new_receive_address(first).unwrap(),
+        wallet.new_change_address(first).unwrap(),
+        wallet.new_receive_address(second).unwrap(),
+    ];
+
+    let account = wallet.account(first).unwrap();
+    assert_eq!(2, account.next_index(Chain::External));
+    assert_eq!(1, account.next_index(Chain::Internal));
+    let account = wallet.account(second).unwrap();
+    assert_eq!(1, account.next_index(Chain::External));
+    assert_eq!(0, account.next_index(Chain::Internal));
+
+    let mut loaded = Wallet::from_wallet_file(wallet.flush().unwrap()).unwrap();
+    assert_eq!(
+        2,
+        loaded.account(first).unwrap().next_index(Chain::External)
+    );
+
+    issued.push(loaded.new_receive_address(first).unwrap());
+    issued.push(loaded.new_change_address(first).unwrap());
+    issued.push(loaded.new_change_address(second).unwrap());
+    assert_eq!(Some(first), loaded.account_of(&issued[4]));
+    assert_eq!(Some(second), loaded.account_of(&issued[6]));
+
+    let count = issued.len();
+    issued.sort();
+    issued.dedup();
+    assert_eq!(count, issued.len());
+}
+
+#[test]
+pub fn test_split_keystore() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_split_keystore");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+
+    let digest = vec![7; 32];
+    let signature = wallet.sign_data(address.clone(), digest.clone()).unwrap();
+
+    let signer = wallet.split_keystore().unwrap();
+    assert!(wallet.is_watch_only());
+    assert!(wallet.get_address(address.clone()).unwrap().is_wiped());
+    assert!(matches!(
+        wallet.sign_data(address.clone(), digest.clone()),
+        Err(WalletError::WatchOnly)
+    ));
+    assert!(matches!(
+        wallet.new_receive_address(account),
+        Err(WalletError::WatchOnly)
+    ));
+
+    // the watch-only wallet is saved without private keys
+    let mut loaded = Wallet::from_wallet_file(wallet.flush().unwrap()).unwrap();
+    assert!(loaded.is_watch_only());
+    assert!(loaded.signer().is_none());
+    assert_eq!(loaded.addresses().unwrap(), wallet.addresses().unwrap());
+
+    loaded.set_signer(Arc::new(signer));
+    assert_eq!(signature, loaded.sign_data(address, digest).unwrap());
+}
+
+/// a credential store in memory, standing in for the OS keychain
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct MemoryBackend(Mutex<HashMap<String, Vec<u8>>>);
+
+#[cfg(test)]
+impl KeystoreBackend for MemoryBackend {
+    fn store(&self, id: &str, secret: &[u8]) -> Result<(), SignerError> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), secret.to_vec());
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Option<Vec<u8>>, SignerError> {
+        Ok(self.0.lock().unwrap().get(id).cloned())
+    }
+
+    fn remove(&self, id: &str) -> Result<(), SignerError> {
+        self.0.lock().unwrap().remove(id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn master_key_hex(wallet: &Wallet) -> String {
+    wallet
+        .keys()
+        .iter()
+        .find(|node| matches!(node.data.key_type, KeyType::Master))
+        .unwrap()
+        .data
+        .private_key
+        .hex()
+}
+
+#[test]
+pub fn test_master_key_in_keystore() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_master_key_in_keystore");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+    let master = master_key_hex(&wallet);
+    let backend = MemoryBackend::default();
+
+    let id = wallet.store_master_key(&backend).unwrap();
+    assert_eq!(Some(id.as_str()), wallet.master_key_id());
+    assert!(master_key_hex(&wallet).is_empty());
+    assert!(matches!(
+        wallet.new_account(AccountType::Taproot),
+        Err(WalletError::MasterKeyNotLoaded)
+    ));
+
+    wallet.load_master_key(&backend).unwrap();
+    assert_eq!(master, master_key_hex(&wallet));
+    wallet.new_account(AccountType::Taproot).unwrap();
+
+    // the master key loaded in memory is never written to the wallet file
+    let file = wallet.flush().unwrap();
+    assert!(!std::fs::read_to_string(&file).unwrap().contains(&master));
+    let mut loaded = Wallet::from_wallet_file(file).unwrap();
+    assert_eq!(Some(id.as_str()), loaded.master_key_id());
+    loaded.load_master_key(&backend).unwrap();
+    assert_eq!(master, master_key_hex(&loaded));
+
+    backend.remove(&id).unwrap();
+    let mut loaded = Wallet::from_wallet_file(wallet.flush().unwrap()).unwrap();
+    assert!(matches!(
+        loaded.load_master_key(&backend),
+        Err(WalletError::Signer(SignerError::UnknownKey(_)))
+    ));
+}
+
+#[test]
+pub fn test_encrypted_master_key_in_keystore() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let master = master_key_hex(&wallet);
+    let backend = MemoryBackend::default();
+
+    wallet.encrypt("correct horse").unwrap();
+    assert!(matches!(
+        wallet.store_master_key(&backend),
+        Err(WalletError::Locked)
+    ));
+    wallet.unlock("correct horse").unwrap();
+    let id = wallet.store_master_key(&backend).unwrap();
+
+    // the key is sealed with the wallet passphrase in the store
+    let secret = backend.load(&id).unwrap().unwrap();
+    assert_ne!(master, hex::encode(&secret));
+
+    wallet.lock().unwrap();
+    wallet.load_master_key(&backend).unwrap();
+    assert!(master_key_hex(&wallet).is_empty());
+    wallet.unlock("correct horse").unwrap();
+    assert_eq!(master, master_key_hex(&wallet));
+}
+
+#[test]
+pub fn test_shamir_backup() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    let shares = wallet.export_shamir_shares(2, 3, "correct horse").unwrap();
+    assert_eq!(3, shares.len());
+
+    let restored = Wallet::restore_from_shamir_shares(
+        &shares[1..],
+        "correct horse",
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    assert_eq!(master_key_hex(&wallet), master_key_hex(&restored));
+    assert_eq!(wallet.addresses().unwrap(), restored.addresses().unwrap());
+
+    assert!(matches!(
+        Wallet::restore_from_shamir_shares(
+            &shares[..1],
+            "correct horse",
+            Network::Mainnet,
+            true,
+            PathBuf::from("/tmp"),
+            false,
+        ),
+        Err(WalletError::Shamir(_))
+    ));
+}
+
+#[test]
+pub fn test_verify_address() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_verify_address");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet =
+        Wallet::restore(mnemonic.clone(), Network::Mainnet, true, data_dir, false).unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+
+    let first = wallet.new_receive_address(account).unwrap();
+    let second = wallet.new_receive_address(account).unwrap();
+    let change = wallet.new_change_address(account).unwrap();
+
+    assert_eq!(
+        first,
+        wallet.verify_address(account, Chain::External, 0).unwrap()
+    );
+    assert_eq!(
+        second,
+        wallet.verify_address(account, Chain::External, 1).unwrap()
+    );
+    assert_eq!(
+        change,
+        wallet.verify_address(account, Chain::Internal, 0).unwrap()
+    );
+
+    assert!(matches!(
+        wallet.verify_address(account, Chain::Internal, 1),
+        Err(WalletError::UnknownAddress(_))
+    ));
+    assert!(matches!(
+        wallet.verify_address(5, Chain::External, 0),
+        Err(WalletError::AccountNotFound(5))
+    ));
+
+    // a wallet file holding the wrong address at a path is caught, even with a valid checksum
+    let file = wallet.flush().unwrap();
+    let data = std::fs::read_to_string(&file).unwrap();
+    std::fs::write(&file, data.replace(&first, &change)).unwrap();
+    assert!(matches!(
+        Wallet::from_wallet_file(file.clone()),
+        Err(WalletError::Corrupted { .. })
+    ));
+    let mut data = serde_json::from_str(&data.replace(&first, &change)).unwrap();
+    seal_wallet_file(&mut data, None);
+    std::fs::write(&file, data.to_string()).unwrap();
+    let corrupted = Wallet::from_wallet_file(file).unwrap();
+    match corrupted.verify_address(account, Chain::External, 0) {
+        Err(WalletError::AddressMismatch { stored, derived }) => {
+            assert_eq!(change, stored);
+            assert_eq!(first, derived);
+        }
+        other => panic!("expected a mismatch, got {:?}", other),
+    }
+
+    // derived from the mnemonic, the master key included, even while locked
+    wallet.encrypt("passphrase").unwrap();
+    assert_eq!(
+        change,
+        wallet
+            .verify_address_with_mnemonic(&mnemonic, account, Chain::Internal, 0)
+            .unwrap()
+    );
+    let other = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+    assert!(matches!(
+        wallet.verify_address_with_mnemonic(other, account, Chain::Internal, 0),
+        Err(WalletError::AddressMismatch { .. })
+    ));
+    assert!(matches!(
+        wallet.verify_address_with_mnemonic("not a mnemonic", account, Chain::Internal, 0),
+        Err(WalletError::Key(_))
+    ));
+}
+
+#[test]
+pub fn test_import_wif() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    assert!(wallet.compress_public_keys());
+
+    // a legacy uncompressed WIF keeps its own address in a compressed wallet
+    let wif = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    let address = wallet.import_wif(wif, None).unwrap();
+    assert_eq!("1GAehh7TsJAHuUAeKZcXf5CnwuGuGgyX2S", address);
+    assert!(!wallet
+        .get_address(address.clone())
+        .unwrap()
+        .compress_public_keys());
+    assert!(wallet.addresses().unwrap().contains(&address));
+    assert!(wallet.sign_data(address.clone(), vec![7; 32]).is_ok());
+
+    // importing again is a no-op, overriding the compression is another address
+    let count = wallet.keys().len();
+    assert_eq!(address, wallet.import_wif(wif, None).unwrap());
+    assert_eq!(count, wallet.keys().len());
+    assert_eq!(
+        "1LoVGDgRs9hTfTNJNuXKSpywcbdvwRXpmK",
+        wallet.import_wif(wif, Some(true)).unwrap()
+    );
+    assert!(matches!(
+        wallet.keys().last().unwrap().data.key_type,
+        KeyType::Imported
+    ));
+
+    let testnet = "92Pg46rUhgTT7romnV7iGW6W1gbGdeezqdbJCzShkCsYNzyyNcc";
+    assert!(matches!(
+        wallet.import_wif(testnet, None),
+        Err(WalletError::Key(_))
+    ));
+}
+
+#[test]
+pub fn test_import_core_dump() {
+    let dir = std::env::temp_dir().join("waller_test_import_core_dump");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        dir.clone(),
+        false,
+    )
+    .unwrap();
+
+    let key = |byte: u8, network: Network| Key::from_seed(&[byte; 64], network, true).unwrap();
+    let (legacy, segwit, testnet) = (
+        key(1, Network::Mainnet),
+        key(2, Network::Mainnet),
+        key(3, Network::Testnet),
+    );
+    let dump = format!(
+        "# Wallet dump created by Bitcoin v25.0.0
+# * Created on 2023-06-01T00:00:00Z
+
+{} 2010-05-22T00:00:00Z label=pizza%20money # addr={}
+{} 2009-01-03T18:15:05Z change=1 # addr={},{}
+{} 2015-01-01T00:00:00Z label= # addr={}
+0014751e76e8199196d454941c45d1b3a323f1433bd6 0 script=1 # addr=bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4
+
+# End of dump
+",
+        legacy.to_wif(),
+        legacy.address().unwrap(),
+        segwit.to_wif(),
+        segwit.native_segwit_address().unwrap(),
+        segwit.address().unwrap(),
+        testnet.to_wif(),
+        testnet.address().unwrap(),
+    );
+    let path = dir.join("dump.txt");
+    std::fs::write(&path, dump).unwrap();
+
+    let report = wallet.import_core_dump(&path).unwrap();
+    assert_eq!(
+        vec![
+            legacy.address().unwrap(),
+            segwit.native_segwit_address().unwrap()
+        ],
+        report.imported
+    );
+    assert_eq!(
+        vec![6, 7],
+        report
+            .skipped
+            .iter()
+            .map(|skipped| skipped.entry)
+            .collect::<Vec<_>>()
+    );
+    // the genesis block
+    assert_eq!(Some(1231006505), report.earliest_key_time);
+    assert_eq!(Some(1231006505), wallet.birthday().time);
+    assert_eq!(
+        Some("pizza money"),
+        wallet.label(&legacy.address().unwrap())
+    );
+    assert_eq!(None, wallet.label(&segwit.native_segwit_address().unwrap()));
+    assert!(wallet
+        .sign_data(segwit.native_segwit_address().unwrap(), vec![7; 32])
+        .is_ok());
+
+    // a key that can't be imported fails the dump before any key is added
+    let fresh = key(5, Network::Mainnet);
+    let zero = base58check_encode(&[vec![0x80], vec![0; 32], vec![0x01]].concat());
+    let dump = format!(
+        "{} 2000-01-01T00:00:00Z label=fresh # addr={}\n{} 2001-01-01T00:00:00Z # addr=\n",
+        fresh.to_wif(),
+        fresh.address().unwrap(),
+        zero,
+    );
+    let bad = dir.join("bad.txt");
+    std::fs::write(&bad, dump).unwrap();
+    wallet.flush().unwrap();
+    assert!(matches!(
+        wallet.import_core_dump(&bad),
+        Err(WalletError::Key(_))
+    ));
+    assert!(wallet.get_address(fresh.address().unwrap()).is_none());
+    assert_eq!(None, wallet.label(&fresh.address().unwrap()));
+    assert_eq!(Some(1231006505), wallet.birthday().time);
+    assert!(!wallet.is_dirty());
+
+    // the keys of a descriptor wallet, from `listdescriptors true`
+    let taproot = key(4, Network::Mainnet);
+    let descriptors = serde_json::json!({
+        "wallet_name": "descriptors",
+        "descriptors": [
+            {
+                "desc": with_descriptor_checksum(&format!("tr([d34db33f/86h/0h/0h]{})", taproot.to_wif())).unwrap(),
+                "timestamp": 1700000000,
+                "active": false,
+            },
+            {
+                "desc": with_descriptor_checksum(&format!("wpkh({})", legacy.to_wif())).unwrap(),
+                "timestamp": 1300000000,
+            },
+            {
+                "desc": with_descriptor_checksum("wpkh(xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi/84h/0h/0h/0/*)").unwrap(),
+                "timestamp": 1600000000,
+            },
+            {
+                "desc": format!("pkh({})#aaaaaaaa", taproot.to_wif()),
+                "timestamp": 1,
+            },
+        ]
+    });
+    let path = dir.join("descriptors.json");
+    std::fs::write(&path, descriptors.to_string()).unwrap();
+
+    let report = wallet.import_core_dump(&path).unwrap();
+    assert_eq!(
+        vec![
+            taproot.taproot_address().unwrap(),
+            legacy.native_segwit_address().unwrap()
+        ],
+        report.imported
+    );
+    assert_eq!(
+        vec![3, 4],
+        report
+            .skipped
+            .iter()
+            .map(|skipped| skipped.entry)
+            .collect::<Vec<_>>()
+    );
+    assert!(report.skipped[0]
+        .reason
+        .starts_with("not a single private key descriptor"));
+    assert_eq!(Some(1300000000), report.earliest_key_time);
+    // an older birthday is kept
+    assert_eq!(Some(1231006505), wallet.birthday().time);
+
+    std::fs::write(&path, "{\"wallet_name\": \"empty\"}").unwrap();
+    assert!(matches!(
+        wallet.import_core_dump(&path),
+        Err(WalletError::Read(_))
+    ));
+
+    // a rescan watches the address each key was imported with
+    let address = segwit.native_segwit_address().unwrap();
+    let script = Script::from_address(&address, Network::Mainnet).unwrap();
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(
+            1,
+            vec![BlockTransaction {
+                tx_id: "11".repeat(32),
+                inputs: vec![OutPoint::new("00".repeat(32), 0)],
+                outputs: vec![TransactionOutput::from_script(
+                    25_000,
+                    script.as_bytes().to_vec(),
+                )],
+            }],
+        ),
+    ]);
+    wallet.rescan(&chain, 0).unwrap();
+    assert_eq!(address, wallet.utxos()[0].address());
+}
+
+/// a chain held in memory, the block at a height is at its position
+#[cfg(test)]
+struct MemoryChain(Vec<Block>);
+
+#[cfg(test)]
+impl Backend for MemoryChain {
+    fn broadcast(&self, _raw_tx: &str) -> Result<String, BackendError> {
+        Err(BackendError::Unsupported("broadcast".to_string()))
+    }
+
+    fn tip_height(&self) -> Result<u32, BackendError> {
+        Ok(self.0.len() as u32 - 1)
+    }
+
+    fn block(&self, height: u32) -> Result<Block, BackendError> {
+        Ok(self.0[height as usize].clone())
+    }
+}
+
+/// the timestamp of the first block of a [MemoryChain], blocks follow every ten minutes
+#[cfg(test)]
+const BLOCK_TIME: u64 = 1_600_000_000;
+
+#[cfg(test)]
+fn block(height: u32, transactions: Vec<BlockTransaction>) -> Block {
+    Block {
+        height,
+        hash: format!("{:064x}", height),
+        time: BLOCK_TIME + height as u64 * 600,
+        transactions,
+    }
+}
+
+#[test]
+pub fn test_rescan() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let restore = || {
+        let mut wallet = Wallet::restore(
+            mnemonic.clone(),
+            Network::Mainnet,
+            true,
+            PathBuf::from("/tmp"),
+            false,
+        )
+        .unwrap();
+        wallet.new_account(AccountType::NativeSegwit).unwrap();
+        wallet
+    };
+
+    // the wallet that was used, its outputs paying to an address of the account
+    let mut old = restore();
+    let receive: Vec<String> = (0..27)
+        .map(|_| old.new_receive_address(0).unwrap())
+        .collect();
+    let change = old.new_change_address(0).unwrap();
+    let pay = |address: &String, value: i64| {
+        TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            old.get_address(address.clone()).unwrap(),
+            value,
+        )
+    };
+
+    let funding = BlockTransaction {
+        tx_id: "11".repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![pay(&receive[0], 50_000), pay(&receive[5], 20_000)],
+    };
+    let spend = BlockTransaction {
+        tx_id: "22".repeat(32),
+        inputs: vec![OutPoint::new("11".repeat(32), 0)],
+        outputs: vec![
+            TransactionOutput::from_script(15_000, vec![0x6a]),
+            pay(&change, 30_000),
+        ],
+    };
+    // past the lookahead of the last address used, never found
+    let too_far = BlockTransaction {
+        tx_id: "33".repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![pay(&receive[26], 10_000)],
+    };
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(1, vec![funding]),
+        block(2, vec![spend]),
+        block(3, vec![too_far]),
+    ]);
+
+    let mut wallet = restore();
+    assert_eq!(3, wallet.rescan(&chain, 0).unwrap());
+
+    assert_eq!(50_000, wallet.account_balance(0));
+    assert_eq!(2, wallet.utxos().len());
+    assert!(wallet.utxos().iter().all(|utxo| utxo.is_confirmed()));
+    assert_eq!(6, wallet.account(0).unwrap().next_index(Chain::External));
+    assert_eq!(1, wallet.account(0).unwrap().next_index(Chain::Internal));
+
+    let history = wallet.history();
+    assert_eq!(2, history.len());
+    assert_eq!((Some(1), 70_000), (history[0].height(), history[0].net()));
+    assert_eq!((Some(2), -20_000), (history[1].height(), history[1].net()));
+
+    // scanning again from a height finds the same outputs
+    wallet.rescan(&chain, 2).unwrap();
+    assert_eq!(50_000, wallet.account_balance(0));
+    assert_eq!(2, wallet.history().len());
+    assert_eq!(receive[6], wallet.new_receive_address(0).unwrap());
+}
+
+#[test]
+pub fn test_conflicts_of() {
+    let data_dir = std::env::temp_dir().join("waller_test_conflicts_of");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        data_dir,
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let change = wallet.new_change_address(account).unwrap();
+    let pay = |wallet: &Wallet, address: &String, value: i64| {
+        TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            wallet.get_address(address.clone()).unwrap(),
+            value,
+        )
+    };
+
+    let funding = BlockTransaction {
+        tx_id: "11".repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![pay(&wallet, &receive, 50_000)],
+    };
+    wallet
+        .rescan(
+            &MemoryChain(vec![block(0, vec![]), block(1, vec![funding.clone()])]),
+            0,
+        )
+        .unwrap();
+    let utxo = wallet.utxos()[0].clone();
+
+    // a payment bumped twice, each spending the same coin with less change
+    let spend = |wallet: &Wallet, change_value: i64| {
+        Transaction::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            vec![utxo.to_input()],
+            vec![
+                TransactionOutput::from_script(10_000, vec![0x6a]),
+                pay(wallet, &change, change_value),
+            ],
+            None,
+        )
+    };
+    let (a, b, c) = (
+        spend(&wallet, 39_000),
+        spend(&wallet, 38_000),
+        spend(&wallet, 37_000),
+    );
+    assert_eq!(1, wallet.record_spends(&a).len());
+    assert!(wallet.utxos().is_empty());
+    wallet
+        .add_utxo(Utxo::new(
+            OutPoint::new(a.tx_id(), 1),
+            pay(&wallet, &change, 39_000),
+            change.clone(),
+        ))
+        .unwrap();
+
+    wallet.replace_transaction(a.tx_id(), &b);
+    wallet.replace_transaction(b.tx_id(), &c);
+    assert!(wallet.utxos().is_empty());
+    assert_eq!(vec![a.tx_id(), c.tx_id()], wallet.conflicts_of(&b.tx_id()));
+    assert_eq!(vec![b.tx_id(), c.tx_id()], wallet.conflicts_of(&a.tx_id()));
+    assert!(wallet.conflicts_of(&"ff".repeat(32)).is_empty());
+
+    // a malleated copy of the last bump confirms under another txid
+    let malleated = BlockTransaction {
+        tx_id: "44".repeat(32),
+        inputs: vec![utxo.outpoint().clone()],
+        outputs: vec![
+            TransactionOutput::from_script(10_000, vec![0x6a]),
+            pay(&wallet, &change, 37_000),
+        ],
+    };
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(1, vec![funding]),
+        block(2, vec![malleated]),
+    ]);
+    wallet.rescan(&chain, 0).unwrap();
+    assert_eq!(
+        vec![OutPoint::new("44".repeat(32), 1)],
+        wallet
+            .utxos()
+            .iter()
+            .map(|utxo| utxo.outpoint().clone())
+            .collect::<Vec<_>>()
+    );
+    let spent = wallet.history().last().unwrap();
+    assert_eq!(
+        ("44".repeat(32).as_str(), 50_000),
+        (spent.tx_id(), spent.sent())
+    );
+    assert_eq!(Some(3_000), spent.fee());
+    assert_eq!(
+        vec![a.tx_id(), b.tx_id(), c.tx_id()],
+        wallet.conflicts_of(&"44".repeat(32))
+    );
+
+    // spends are kept in the cache
+    let loaded = Wallet::from_wallet_file(wallet.flush().unwrap()).unwrap();
+    assert_eq!(wallet.spends().len(), loaded.spends().len());
+    assert_eq!(
+        wallet.conflicts_of(&b.tx_id()),
+        loaded.conflicts_of(&b.tx_id())
+    );
+}
+
+#[test]
+pub fn test_sync_conflict() {
+    let data_dir = std::env::temp_dir().join("waller_test_sync_conflict");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        data_dir,
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let output = TransactionOutput::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        wallet.get_address(receive).unwrap(),
+        50_000,
+    );
+    let funding = BlockTransaction {
+        tx_id: "11".repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![output],
+    };
+    wallet
+        .rescan(
+            &MemoryChain(vec![block(0, vec![]), block(1, vec![funding.clone()])]),
+            0,
+        )
+        .unwrap();
+    let utxo = wallet.utxos()[0].clone();
+    let spend = |value: i64| {
+        Transaction::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            vec![utxo.to_input()],
+            vec![TransactionOutput::from_script(value, vec![0x6a])],
+            None,
+        )
+    };
+    let (payment, bump) = (spend(49_000), spend(48_000));
+    wallet.record_spends(&payment);
+    let mut raced = wallet.clone();
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+    wallet.on_event(move |event: &WalletEvent| recorded.lock().unwrap().push(event.clone()));
+
+    // the coin is spent by a transaction the wallet didn't build
+    let double_spend = BlockTransaction {
+        tx_id: "66".repeat(32),
+        inputs: vec![utxo.outpoint().clone()],
+        outputs: vec![TransactionOutput::from_script(49_500, vec![0x6a])],
+    };
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(1, vec![funding.clone()]),
+        block(2, vec![double_spend]),
+    ]);
+    wallet.rescan(&chain, 0).unwrap();
+    let conflicts: Vec<(String, Vec<String>)> = events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|event| match event {
+            WalletEvent::Conflict { tx_id, conflicted } => {
+                Some((tx_id.clone(), conflicted.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vec![("66".repeat(32), vec![payment.tx_id()])], conflicts);
+    let conflicted = |wallet: &Wallet, tx_id: String| {
+        wallet
+            .spends()
+            .iter()
+            .filter(|spend| spend.tx_id() == tx_id)
+            .all(|spend| spend.is_conflicted())
+    };
+    assert!(conflicted(&wallet, payment.tx_id()));
+    assert!(!conflicted(&wallet, "66".repeat(32)));
+    assert_eq!(-50_000, wallet.history().last().unwrap().net());
+
+    // a sync finding the conflict again doesn't report it twice
+    wallet.rescan(&chain, 0).unwrap();
+    assert_eq!(
+        1,
+        events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| matches!(event, WalletEvent::Conflict { .. }))
+            .count()
+    );
+
+    // a fee bump losing to the payment it replaced is a conflict the wallet made
+    raced.replace_transaction(payment.tx_id(), &bump);
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+    raced.on_event(move |event: &WalletEvent| recorded.lock().unwrap().push(event.clone()));
+    let confirmed = BlockTransaction {
+        tx_id: payment.tx_id(),
+        inputs: vec![utxo.outpoint().clone()],
+        outputs: vec![TransactionOutput::from_script(49_000, vec![0x6a])],
+    };
+    raced
+        .rescan(
+            &MemoryChain(vec![
+                block(0, vec![]),
+                block(1, vec![funding]),
+                block(2, vec![confirmed]),
+            ]),
+            0,
+        )
+        .unwrap();
+    assert!(conflicted(&raced, bump.tx_id()));
+    assert!(!conflicted(&raced, payment.tx_id()));
+    assert!(!events
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|event| matches!(event, WalletEvent::Conflict { .. })));
+}
+
+#[test]
+pub fn test_sync_reorg() {
+    let data_dir = std::env::temp_dir().join("waller_test_sync_reorg");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        data_dir,
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let key = wallet.get_address(receive).unwrap();
+    let payment = |tx_id: &str, value: i64| BlockTransaction {
+        tx_id: tx_id.repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            key.clone(),
+            value,
+        )],
+    };
+    // a block of another chain than the one [block] makes
+    let fork = |height: u32, transactions: Vec<BlockTransaction>| Block {
+        hash: format!("{:064x}", height + 1000),
+        ..block(height, transactions)
+    };
+    let confirmations = |wallet: &Wallet| {
+        let mut utxos: Vec<(String, Option<u32>)> = wallet
+            .utxos()
+            .iter()
+            .map(|utxo| (utxo.outpoint().hash()[..2].to_string(), utxo.height()))
+            .collect();
+        utxos.sort();
+        utxos
+    };
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+    wallet.on_event(move |event: &WalletEvent| recorded.lock().unwrap().push(event.clone()));
+    let reorgs = || -> Vec<(u32, u32)> {
+        events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                WalletEvent::Reorg { fork_height, depth } => Some((*fork_height, *depth)),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(1, vec![]),
+        block(2, vec![payment("22", 50_000)]),
+        block(3, vec![payment("33", 10_000)]),
+    ]);
+    assert_eq!(3, wallet.sync(&chain).unwrap().tip);
+    assert_eq!(3, wallet.sync(&chain).unwrap().tip);
+    assert_eq!(
+        vec![("22".to_string(), Some(2)), ("33".to_string(), Some(3))],
+        confirmations(&wallet)
+    );
+    assert!(reorgs().is_empty());
+
+    // `invalidateblock` of block 3 then two blocks mined, confirming its transaction later
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(1, vec![]),
+        block(2, vec![payment("22", 50_000)]),
+        fork(3, vec![payment("55", 5_000)]),
+        fork(4, vec![payment("33", 10_000)]),
+    ]);
+    assert_eq!(4, wallet.sync(&chain).unwrap().tip);
+    assert_eq!(vec![(2, 1)], reorgs());
+    assert_eq!(
+        vec![
+            ("22".to_string(), Some(2)),
+            ("33".to_string(), Some(4)),
+            ("55".to_string(), Some(3))
+        ],
+        confirmations(&wallet)
+    );
+    assert_eq!(65_000, wallet.account_balance(account));
+
+    // the scanned blocks are kept in the cache
+    let mut loaded = Wallet::from_wallet_file(wallet.flush().unwrap()).unwrap();
+    assert_eq!(4, loaded.sync(&chain).unwrap().tip);
+    assert_eq!(confirmations(&wallet), confirmations(&loaded));
+
+    // `invalidateblock` of block 2, the chain is shorter than what was scanned
+    let chain = MemoryChain(vec![block(0, vec![]), block(1, vec![])]);
+    assert_eq!(1, wallet.sync(&chain).unwrap().tip);
+    assert_eq!(vec![(2, 1), (1, 3)], reorgs());
+    assert!(wallet.utxos().is_empty());
+    assert!(wallet.history().is_empty());
+}
+
+#[test]
+pub fn test_sync_diff() {
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let key = wallet.get_address(receive).unwrap();
+    let payment = |tx_id: &str, value: i64| BlockTransaction {
+        tx_id: tx_id.repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            key.clone(),
+            value,
+        )],
+    };
+
+    let mut chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(1, vec![payment("11", 100_000)]),
+    ]);
+    let diff = wallet.sync(&chain).unwrap();
+    assert_eq!(1, diff.tip);
+    assert_eq!(1, diff.new_utxos.len());
+    assert!(diff.spent_utxos.is_empty());
+    assert_eq!(vec![("11".repeat(32), 1)], diff.confirmations);
+    assert_eq!(100_000, diff.balance_delta);
+
+    // nothing new
+    let diff = wallet.sync(&chain).unwrap();
+    assert!(diff.new_utxos.is_empty() && diff.confirmations.is_empty());
+    assert_eq!(0, diff.balance_delta);
+
+    // a spend bumped before it confirmed
+    let recipient = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string();
+    let original = wallet
+        .new_transaction(account, &[(recipient.clone(), 30_000)], 2)
+        .unwrap();
+    let bump = wallet
+        .new_transaction(account, &[(recipient, 30_000)], 10)
+        .unwrap();
+    wallet.record_spends(&original);
+    wallet.record_spends(&bump);
+    chain.0.push(block(
+        2,
+        vec![
+            BlockTransaction {
+                tx_id: bump.tx_id(),
+                inputs: vec![OutPoint::new("11".repeat(32), 0)],
+                outputs: bump.outputs().clone(),
+            },
+            payment("22", 5_000),
+        ],
+    ));
+
+    let diff = wallet.sync(&chain).unwrap();
+    assert_eq!(2, diff.tip);
+    assert_eq!(
+        vec![FeeBump {
+            tx_id: original.tx_id(),
+            replacement_tx_id: bump.tx_id(),
+        }],
+        diff.fee_bumps
+    );
+    let change = bump
+        .outputs()
+        .iter()
+        .find(|output| wallet.is_change(output.pk_script()))
+        .unwrap()
+        .value();
+    assert_eq!(2, diff.new_utxos.len());
+    assert_eq!(
+        vec![(bump.tx_id(), 2), ("22".repeat(32), 2)],
+        diff.confirmations
+    );
+    // the coin spent was no longer tracked once the spend was recorded
+    assert!(diff.spent_utxos.is_empty());
+    assert_eq!(change + 5_000, diff.balance_delta);
+}
+
+#[test]
+pub fn test_watch_tx() {
+    let data_dir = std::env::temp_dir().join("waller_test_watch_tx");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        data_dir,
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let key = wallet.get_address(receive).unwrap();
+    let payment = |tx_id: &str| BlockTransaction {
+        tx_id: tx_id.repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            key.clone(),
+            50_000,
+        )],
+    };
+    let fork = |height: u32, transactions: Vec<BlockTransaction>| Block {
+        hash: format!("{:064x}", height + 1000),
+        ..block(height, transactions)
+    };
+    let chain = |mut blocks: Vec<Block>, tip: u32| {
+        while blocks.len() as u32 <= tip {
+            let height = blocks.len() as u32;
+            blocks.push(fork(height, vec![]));
+        }
+        MemoryChain(blocks)
+    };
+
+    let fired = Arc::new(Mutex::new(vec![]));
+    let watch = |wallet: &mut Wallet, tx_id: &str, confirmations: u32| {
+        let recorded = fired.clone();
+        wallet.watch_tx(
+            &tx_id.repeat(32),
+            confirmations,
+            move |tx_id: &str, height| {
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push((tx_id[..2].to_string(), height))
+            },
+        );
+    };
+    let fired = || -> Vec<(String, u32)> { std::mem::take(&mut *fired.lock().unwrap()) };
+
+    watch(&mut wallet, "22", 3);
+    watch(&mut wallet, "33", 0);
+    assert_eq!(None, wallet.confirmations(&"22".repeat(32)));
+
+    let first = vec![
+        block(0, vec![]),
+        block(1, vec![]),
+        block(2, vec![payment("22")]),
+    ];
+    wallet.sync(&chain(first, 3)).unwrap();
+    assert_eq!(Some(2), wallet.confirmations(&"22".repeat(32)));
+    assert!(fired().is_empty());
+
+    // a reorg of the block confirming it waits for the confirmations of the new one
+    let second = vec![
+        block(0, vec![]),
+        block(1, vec![]),
+        fork(2, vec![]),
+        block(3, vec![payment("22")]),
+    ];
+    wallet.sync(&chain(second.clone(), 4)).unwrap();
+    assert_eq!(Some(2), wallet.confirmations(&"22".repeat(32)));
+    assert!(fired().is_empty());
+
+    wallet.sync(&chain(second.clone(), 5)).unwrap();
+    assert_eq!(vec![("22".to_string(), 3)], fired());
+
+    // fired once, and right away when already deep enough
+    wallet.sync(&chain(second.clone(), 6)).unwrap();
+    assert!(fired().is_empty());
+    watch(&mut wallet, "22", 4);
+    assert_eq!(vec![("22".to_string(), 3)], fired());
+    watch(&mut wallet, "22", 5);
+    assert!(fired().is_empty());
+    assert!(wallet.unwatch_tx(&"22".repeat(32)));
+    assert!(!wallet.unwatch_tx(&"22".repeat(32)));
+
+    // no confirmations waits for the first
+    let mut third = second;
+    third.push(block(4, vec![payment("33")]));
+    wallet.sync(&chain(third, 4)).unwrap();
+    assert_eq!(vec![("33".to_string(), 4)], fired());
+}
+
+#[test]
+pub fn test_is_change() {
+    let data_dir = std::env::temp_dir().join("waller_test_is_change");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        data_dir,
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let script = |address: &str| {
+        Script::from_address(address, Network::Mainnet)
+            .unwrap()
+            .into_bytes()
+    };
+    let receive = wallet.new_receive_address(account).unwrap();
+    let change = wallet.new_change_address(account).unwrap();
+    let imported = wallet
+        .import_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ", None)
+        .unwrap();
+    let recipient = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+    assert!(wallet.is_mine(&script(&receive)));
+    assert!(!wallet.is_change(&script(&receive)));
+    assert!(wallet.is_mine(&script(&change)));
+    assert!(wallet.is_change(&script(&change)));
+    assert!(wallet.is_mine(&script(&imported)));
+    assert!(!wallet.is_change(&script(&imported)));
+    assert!(!wallet.is_mine(&script(recipient)));
+    assert!(!wallet.is_change(&script(recipient)));
+    assert!(!wallet.is_mine(&[0x6a]));
+
+    // the change of a spend is recorded apart from the payment
+    let funding = BlockTransaction {
+        tx_id: "11".repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![TransactionOutput::from_script(100_000, script(&receive))],
+    };
+    let mut chain = MemoryChain(vec![block(0, vec![]), block(1, vec![funding])]);
+    wallet.sync(&chain).unwrap();
+    let tx = wallet
+        .new_transaction(account, &[(recipient.to_string(), 30_000)], 2)
+        .unwrap();
+    let outputs = tx.outputs();
+    let change_output = outputs
+        .iter()
+        .find(|output| wallet.is_change(output.pk_script()))
+        .unwrap();
+    assert_eq!(2, outputs.len());
+    assert!(wallet.is_mine(change_output.pk_script()));
+
+    chain.0.push(block(
+        2,
+        vec![BlockTransaction {
+            tx_id: tx.tx_id(),
+            inputs: vec![OutPoint::new("11".repeat(32), 0)],
+            outputs: outputs.clone(),
+        }],
+    ));
+    wallet.sync(&chain).unwrap();
+    let record = wallet
+        .history()
+        .iter()
+        .find(|record| record.tx_id() == tx.tx_id())
+        .unwrap();
+    assert_eq!(change_output.value(), record.change());
+    assert_eq!(change_output.value(), record.received());
+    assert_eq!(Some(tx.fee()), record.fee());
+    assert_eq!(30_000, record.paid());
+    assert_eq!(-30_000 - tx.fee(), record.net());
+
+    // a payment to the wallet has no change and paid nothing
+    let funding = &wallet.history()[0];
+    assert_eq!((0, 0), (funding.change(), funding.paid()));
+}
+
+#[test]
+pub fn test_compact() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_compact");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let first = wallet.new_receive_address(account).unwrap();
+    let second = wallet.new_receive_address(account).unwrap();
+    let imported = wallet
+        .import_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ", None)
+        .unwrap();
+    let pay = |address: &String, value: i64| {
+        TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            wallet.get_address(address.clone()).unwrap(),
+            value,
+        )
+    };
+
+    // the coins of the first block are spent in the second, only those of the third are held
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(
+            1,
+            vec![BlockTransaction {
+                tx_id: "11".repeat(32),
+                inputs: vec![OutPoint::new("00".repeat(32), 0)],
+                outputs: vec![pay(&first, 50_000)],
+            }],
+        ),
+        block(
+            2,
+            vec![BlockTransaction {
+                tx_id: "22".repeat(32),
+                inputs: vec![OutPoint::new("11".repeat(32), 0)],
+                outputs: vec![TransactionOutput::from_script(49_000, vec![0x6a])],
+            }],
+        ),
+        block(
+            3,
+            vec![BlockTransaction {
+                tx_id: "33".repeat(32),
+                inputs: vec![OutPoint::new("00".repeat(32), 0)],
+                outputs: vec![pay(&second, 10_000)],
+            }],
+        ),
+    ]);
+    wallet.rescan(&chain, 0).unwrap();
+    assert_eq!(3, wallet.history().len());
+
+    // a wallet file holding a key cut off from the master key, and a child of it
+    let file = wallet.flush().unwrap();
+    let mut data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    let records = data["keys"]["records"].as_array_mut().unwrap();
+    let mut orphan = records.last().unwrap().clone();
+    orphan["address"] = "orphan".into();
+    orphan["parent"] = serde_json::Value::Null;
+    orphan["keypair"]["key_type"] = "Normal".into();
+    let mut orphan_child = orphan.clone();
+    orphan_child["address"] = "orphan child".into();
+    orphan_child["parent"] = records.len().into();
+    records.push(orphan);
+    records.push(orphan_child);
+    seal_wallet_file(&mut data, None);
+    std::fs::write(&file, data.to_string()).unwrap();
+
+    let mut loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    let keys = loaded.keys().len();
+    let xpub = loaded.account_xpub(account).unwrap().xpub;
+
+    let compaction = loaded
+        .compact(
+            3,
+            RetentionPolicy {
+                history_confirmations: Some(2),
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        Compaction {
+            history_pruned: 2,
+            keys_dropped: 2,
+        },
+        compaction
+    );
+
+    // the file was rewritten with the graph still resolving every address
+    let mut compacted = Wallet::from_wallet_file(file).unwrap();
+    assert_eq!(keys - 2, compacted.keys().len());
+    assert_eq!(1, compacted.history().len());
+    assert_eq!("33".repeat(32), compacted.history()[0].tx_id());
+    assert!(compacted.get_address("orphan".to_string()).is_none());
+    assert!(compacted.get_address(imported).is_some());
+    assert_eq!(xpub, compacted.account_xpub(account).unwrap().xpub);
+    assert_eq!(
+        second,
+        compacted
+            .verify_address(account, Chain::External, 1)
+            .unwrap()
+    );
+    assert_eq!(
+        wallet.new_receive_address(account).unwrap(),
+        compacted.new_receive_address(account).unwrap()
+    );
+
+    // nothing is left to drop, and the default policy keeps recent history
+    assert_eq!(
+        Compaction::default(),
+        compacted.compact(3, RetentionPolicy::default()).unwrap()
+    );
+}
+
+#[test]
+pub fn test_account_views() {
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let other = wallet.new_account(AccountType::Legacy).unwrap();
+    let receive = vec![
+        wallet.new_receive_address(account).unwrap(),
+        wallet.new_receive_address(account).unwrap(),
+    ];
+    let change = wallet.new_change_address(account).unwrap();
+    let imported = wallet
+        .import_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ", None)
+        .unwrap();
+
+    let views = wallet.account_views();
+    assert_eq!(2, views.len());
+    let view = views[account as usize];
+    assert_eq!(account, view.number());
+    assert_eq!("m/84'/0'/0'", view.path());
+    assert_eq!(AccountType::NativeSegwit, view.account().account_type());
+
+    let chain = view.receive_chain();
+    assert_eq!(Chain::External, chain.chain());
+    assert_eq!("m/84'/0'/0'/0", chain.path());
+    assert_eq!(2, chain.issued());
+    let keys = chain.keys();
+    let addresses: Vec<&str> = keys.iter().map(|key| key.address()).collect();
+    assert_eq!(receive, addresses[..2]);
+    assert_eq!(Some((account, Chain::External, 1)), keys[1].derivation());
+    assert_eq!(
+        wallet
+            .get_address(receive[1].clone())
+            .unwrap()
+            .new_public_key()
+            .unwrap(),
+        keys[1].public_key()
+    );
+
+    let keys = view.change_chain().keys();
+    assert_eq!(change, keys[0].address());
+    assert_eq!(Some(0), keys[0].index());
+    assert!(!keys[0].is_imported());
+
+    let other = wallet.account_view(other).unwrap();
+    assert_eq!("m/44'/0'/0'", other.path());
+    assert!(other
+        .chains()
+        .iter()
+        .all(|chain| chain.issued() == 0 && chain.keys().is_empty()));
+    assert!(wallet.account_view(7).is_none());
+
+    let imported_keys = wallet.imported_keys();
+    assert_eq!(1, imported_keys.len());
+    assert_eq!(imported, imported_keys[0].address());
+    assert!(imported_keys[0].is_imported());
+    assert_eq!(None, imported_keys[0].derivation());
+}
+
+#[test]
+pub fn test_snapshot() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let first = wallet.new_receive_address(account).unwrap();
+    let second = wallet.new_receive_address(account).unwrap();
+    let change = wallet.new_change_address(account).unwrap();
+    let imported = wallet
+        .import_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ", None)
+        .unwrap();
+
+    wallet.set_label(&first, "alice").unwrap();
+    wallet.set_label(&second, "bob").unwrap();
+    wallet.set_label(&second, "").unwrap();
+    assert_eq!(Some("alice"), wallet.label(&first));
+    assert_eq!(None, wallet.label(&second));
+    assert!(matches!(
+        wallet.set_label("1BoatSLRHtKNngkdXEeobR76b53LETtpyT", "nobody"),
+        Err(WalletError::UnknownAddress(_))
+    ));
+
+    for (tx_id, address, value) in [("11", &first, 40_000), ("22", &change, 5_000)].iter() {
+        let key = wallet.get_address(address.to_string()).unwrap();
+        let output = TransactionOutput::new(TransactionType::Pay2WitnessPubKeyHash, key, *value);
+        wallet
+            .add_utxo(Utxo::new(
+                OutPoint::new(tx_id.repeat(32), 0),
+                output,
+                address.to_string(),
+            ))
+            .unwrap();
+    }
+    wallet.confirm_transaction("11".repeat(32), 100);
+
+    let snapshot = wallet.snapshot();
+    assert!(!snapshot.locked);
+    assert_eq!(1, snapshot.accounts.len());
+    assert_eq!("m/84'/0'/0'", snapshot.accounts[0].path);
+    assert_eq!(
+        (40_000, 5_000),
+        (
+            snapshot.accounts[0].confirmed,
+            snapshot.accounts[0].unconfirmed
+        )
+    );
+    assert_eq!(2, snapshot.utxos.len());
+    assert!(snapshot.pending.is_empty());
+
+    let addresses: Vec<_> = snapshot
+        .addresses
+        .iter()
+        .map(|report| (report.address.as_str(), report.derivation))
+        .collect();
+    assert_eq!(
+        vec![
+            (first.as_str(), Some((account, Chain::External, 0))),
+            (second.as_str(), Some((account, Chain::External, 1))),
+            (change.as_str(), Some((account, Chain::Internal, 0))),
+            (imported.as_str(), None),
+        ],
+        addresses
+    );
+    assert_eq!(Some("alice".to_string()), snapshot.addresses[0].label);
+    assert_eq!(
+        (1, 40_000),
+        (snapshot.addresses[0].utxos, snapshot.addresses[0].balance)
+    );
+    assert_eq!(
+        (0, 0),
+        (snapshot.addresses[1].utxos, snapshot.addresses[1].balance)
+    );
+
+    // the report carries no private keys
+    let json = serde_json::to_string(&snapshot).unwrap();
+    assert!(!json.contains("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ"));
+    assert!(!json.contains("private"));
+
+    let shared = SharedWallet::new(wallet);
+    let from_shared = serde_json::to_string(&shared.snapshot().unwrap()).unwrap();
+    assert_eq!(json, from_shared);
+}
+
+#[test]
+pub fn test_export_history() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let change = wallet.new_change_address(account).unwrap();
+    wallet.set_label(&receive, "salary, \"march\"").unwrap();
+    let pay = |address: &String, value: i64| {
+        TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            wallet.get_address(address.clone()).unwrap(),
+            value,
+        )
+    };
+
+    // paid by someone else, then spent to an outside address with change back
+    let outside = hex::decode("76a9147680adec8eabcabac676be9e83854ade0bd22cdb88ac").unwrap();
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(
+            1,
+            vec![BlockTransaction {
+                tx_id: "11".repeat(32),
+                inputs: vec![OutPoint::new("00".repeat(32), 0)],
+                outputs: vec![pay(&receive, 50_000)],
+            }],
+        ),
+        block(
+            2,
+            vec![BlockTransaction {
+                tx_id: "22".repeat(32),
+                inputs: vec![OutPoint::new("11".repeat(32), 0)],
+                outputs: vec![
+                    TransactionOutput::from_script(30_000, outside),
+                    pay(&change, 19_000),
+                ],
+            }],
+        ),
+    ]);
+    wallet.rescan(&chain, 0).unwrap();
+
+    let mut csv = vec![];
+    wallet.export_history(ExportFormat::Csv, &mut csv).unwrap();
+    assert_eq!(
+        format!(
+            "txid,height,amount,fee,counterparty,label\n\
+             {},1,50000,,,\"salary, \"\"march\"\"\"\n\
+             {},2,-31000,1000,1BoatSLRHtKNngkdXEeobR76b53LETtpyT,\n",
+            "11".repeat(32),
+            "22".repeat(32)
+        ),
+        String::from_utf8(csv).unwrap()
+    );
+
+    let mut json = vec![];
+    wallet
+        .export_history(ExportFormat::Json, &mut json)
+        .unwrap();
+    let rows: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(2, rows.as_array().unwrap().len());
+    assert_eq!(serde_json::Value::Null, rows[0]["fee"]);
+    assert_eq!(1000, rows[1]["fee"]);
+    assert_eq!("salary, \"march\"", rows[0]["label"]);
+
+    let mut csv = vec![];
+    wallet.export_utxos(ExportFormat::Csv, &mut csv).unwrap();
+    assert_eq!(
+        format!(
+            "txid,vout,height,amount,address,label\n{},1,2,19000,{},\n",
+            "22".repeat(32),
+            change
+        ),
+        String::from_utf8(csv).unwrap()
+    );
+
+    // valued at the price of the block of the first transaction, the price
+    // of the second isn't known
+    let mut csv = vec![];
+    wallet
+        .export_history_with_rates(ExportFormat::Csv, &Prices, Currency::USD, &mut csv)
+        .unwrap();
+    assert_eq!(
+        format!(
+            "txid,height,amount,fee,counterparty,label,currency,value_at_time,value_now\n\
+             {},1,50000,,,\"salary, \"\"march\"\"\",USD,10.00,32.50\n\
+             {},2,-31000,1000,1BoatSLRHtKNngkdXEeobR76b53LETtpyT,,USD,,-20.15\n",
+            "11".repeat(32),
+            "22".repeat(32)
+        ),
+        String::from_utf8(csv).unwrap()
+    );
+
+    let mut json = vec![];
+    wallet
+        .export_history_with_rates(ExportFormat::Json, &Prices, Currency::JPY, &mut json)
+        .unwrap();
+    let rows: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(50000, rows[0]["amount"]);
+    assert_eq!("JPY", rows[0]["currency"]);
+    assert_eq!(serde_json::Value::Null, rows[0]["value_now"]);
+}
+
+/// the prices of a bitcoin in dollars
+#[cfg(test)]
+struct Prices;
+
+#[cfg(test)]
+impl RateProvider for Prices {
+    fn rate(&self, fiat: Currency) -> Option<Decimal> {
+        match fiat {
+            Currency::USD => Some("65000.00".parse().unwrap()),
+            _ => None,
+        }
+    }
+
+    fn rate_at(&self, fiat: Currency, time: u64) -> Option<Decimal> {
+        match (fiat, time) {
+            (Currency::USD, time) if time == BLOCK_TIME + 600 => Some("20000.5".parse().unwrap()),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+pub fn test_recover_wallet_file() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_recover");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let first = wallet.new_receive_address(account).unwrap();
+    let second = wallet.new_receive_address(account).unwrap();
+    wallet.set_label(&first, "savings").unwrap();
+    wallet.set_label(&second, "rent").unwrap();
+    let pay = |address: &String, value: i64| {
+        TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            wallet.get_address(address.clone()).unwrap(),
+            value,
+        )
+    };
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(
+            1,
+            vec![BlockTransaction {
+                tx_id: "11".repeat(32),
+                inputs: vec![OutPoint::new("00".repeat(32), 0)],
+                outputs: vec![pay(&first, 50_000), pay(&second, 10_000)],
+            }],
+        ),
+    ]);
+    wallet.rescan(&chain, 0).unwrap();
+    assert_eq!(60_000, wallet.account_balance(account));
+    let xpub = wallet.account_xpub(account).unwrap().xpub;
+
+    // an unreadable key, utxo and label, and a flag of the wrong type
+    let file = wallet.flush().unwrap();
+    let mut data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    let records = data["keys"]["records"].as_array_mut().unwrap();
+    let lost_key = records
+        .iter()
+        .position(|record| record["address"] == second.as_str())
+        .unwrap();
+    records[lost_key]["keypair"] = "garbage".into();
+    data["labels"][second.as_str()] = serde_json::json!({ "rent": true });
+    data["compress_public_keys"] = "yes".into();
+    std::fs::write(&file, data.to_string()).unwrap();
+    let cache_file = wallet.config().cache_file();
+    let mut cache: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&cache_file).unwrap()).unwrap();
+    cache["utxos"][0]["outpoint"] = 7.into();
+    std::fs::write(&cache_file, cache.to_string()).unwrap();
+
+    assert!(matches!(
+        Wallet::from_wallet_file(file.clone()),
+        Err(WalletError::Corrupted { .. })
+    ));
+
+    let (mut recovered, report) = Wallet::from_wallet_file_lenient(file.clone()).unwrap();
+    assert!(!report.is_clean());
+    assert!(report.needs_rescan());
+    assert_eq!(5, report.issues.len());
+    assert_eq!(1, report.section(WalletSection::Checksum).len());
+    assert_eq!(
+        Some(lost_key.to_string()),
+        report.section(WalletSection::Keys)[0].entry
+    );
+    assert_eq!(
+        Some("0".to_string()),
+        report.section(WalletSection::Utxos)[0].entry
+    );
+    assert_eq!(
+        Some(second.clone()),
+        report.section(WalletSection::Labels)[0].entry
+    );
+    assert_eq!(1, report.section(WalletSection::Settings).len());
+
+    // the master key and account survive, the lost key's coins are found again by a rescan
+    assert_eq!(xpub, recovered.account_xpub(account).unwrap().xpub);
+    assert!(recovered.get_address(second.clone()).is_none());
+    assert_eq!(Some("savings"), recovered.label(&first));
+    assert_eq!(None, recovered.label(&second));
+    assert_eq!(1, recovered.utxos().len());
+    recovered.rescan(&chain, 0).unwrap();
+    assert_eq!(60_000, recovered.account_balance(account));
+
+    // an intact file recovers cleanly
+    recovered.flush().unwrap();
+    let (_, report) = Wallet::from_wallet_file_lenient(file.clone()).unwrap();
+    assert!(report.is_clean());
+
+    std::fs::write(&file, "{\"network\":").unwrap();
+    assert!(matches!(
+        Wallet::from_wallet_file_lenient(file),
+        Err(WalletError::Read(_))
+    ));
+}
+
+#[test]
+pub fn test_birthday() {
+    let data_dir = std::env::temp_dir().join("waller_test_birthday");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet = Wallet::new(Network::Mainnet, data_dir, true, false);
+    // created a little after block 3 was mined, block 2 is older than the margin allows
+    let created = BLOCK_TIME + 2 * 60 * 60 + 3 * 600;
+    wallet.set_clock(Arc::new(MockClock::new(created)));
+    let mnemonic = wallet.init().unwrap();
+    assert_eq!(
+        Birthday {
+            height: None,
+            time: Some(created),
+        },
+        wallet.birthday()
+    );
+
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    let pay = |tx_id: &str, value: i64| BlockTransaction {
+        tx_id: tx_id.repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            wallet.get_address(address.clone()).unwrap(),
+            value,
+        )],
+    };
+    let chain = MemoryChain(vec![
+        block(0, vec![]),
+        block(1, vec![pay("11", 1_000)]),
+        block(2, vec![]),
+        block(3, vec![]),
+        block(4, vec![pay("44", 2_000)]),
+        block(5, vec![pay("55", 4_000)]),
+    ]);
+
+    // the blocks before the birthday are skipped, the first one scanned is kept
+    wallet.rescan(&chain, 0).unwrap();
+    assert_eq!(6_000, wallet.account_balance(account));
+    assert_eq!(Some(3), wallet.birthday().height);
+    let file = wallet.flush().unwrap();
+    assert_eq!(
+        wallet.birthday(),
+        Wallet::from_wallet_file(file).unwrap().birthday()
+    );
+
+    // a restored wallet scans from genesis until its birthday is set
+    let restore = || {
+        let mut restored = Wallet::restore(
+            mnemonic.clone(),
+            Network::Mainnet,
+            true,
+            std::env::temp_dir(),
+            false,
+        )
+        .unwrap();
+        restored.new_account(AccountType::NativeSegwit).unwrap();
+        restored
+    };
+    let mut restored = restore();
+    assert_eq!(Birthday::default(), restored.birthday());
+    restored.rescan(&chain, 0).unwrap();
+    assert_eq!(7_000, restored.account_balance(account));
+
+    let mut restored = restore();
+    restored.set_birthday(5);
+    restored.rescan(&chain, 0).unwrap();
+    assert_eq!(4_000, restored.account_balance(account));
+    assert_eq!(1, restored.history().len());
+}
+
+#[test]
+pub fn test_new_transaction() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    for (tx_id, value) in [("11", 60_000), ("22", 30_000), ("33", 5_000)] {
+        let address = wallet.new_receive_address(account).unwrap();
+        let output = TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            wallet.get_address(address.clone()).unwrap(),
+            value,
+        );
+        wallet
+            .add_utxo(Utxo::new(
+                OutPoint::new(tx_id.repeat(32), 0),
+                output,
+                address,
+            ))
+            .unwrap();
+    }
+    let recipient = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string();
+    let recipient_script = hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+    let paid = |tx: &Transaction| {
+        tx.outputs()
+            .iter()
+            .filter(|output| output.pk_script() == recipient_script.as_slice())
+            .map(|output| output.value())
+            .sum::<i64>()
+    };
+
+    // the largest coin covers it, the change goes to the internal chain
+    let tx = wallet
+        .new_transaction(account, &[(recipient.clone(), 50_000)], 2)
+        .unwrap();
+    assert_eq!(1, tx.tx_in_count());
+    assert_eq!("11".repeat(32), tx.inputs()[0].previous_output().hash());
+    assert_eq!(50_000, paid(&tx));
+    // 11 bytes of overhead, a 68 vbyte input and two 31 byte outputs
+    assert_eq!(282, tx.fee());
+    let change = wallet.verify_address(account, Chain::Internal, 0).unwrap();
+    let change_script = TransactionOutput::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        wallet.get_address(change).unwrap(),
+        0,
+    );
+    assert!(tx
+        .outputs()
+        .iter()
+        .any(|output| output.pk_script() == change_script.pk_script()
+            && output.value() == 60_000 - 50_000 - 282));
+
+    // coins are added until the amount and the fee are covered
+    let tx = wallet
+        .new_transaction(account, &[(recipient.clone(), 85_000)], 2)
+        .unwrap();
+    assert_eq!(2, tx.tx_in_count());
+    assert_eq!(418, tx.fee());
+
+    // change worth less than the dust limit is left to the fee
+    let tx = wallet
+        .new_transaction(account, &[(recipient.clone(), 59_500)], 2)
+        .unwrap();
+    assert_eq!(1, tx.tx_out_count());
+    assert_eq!(500, tx.fee());
+
+    assert!(matches!(
+        wallet.new_transaction(account, &[(recipient.clone(), 95_000)], 2),
+        Err(WalletError::InsufficientFunds)
+    ));
+    assert!(matches!(
+        wallet.new_transaction(account, &[(recipient.clone(), 0)], 2),
+        Err(WalletError::InvalidAmount(0))
+    ));
+    assert!(matches!(
+        wallet.new_transaction(
+            account,
+            &[("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn".to_string(), 1_000)],
+            2
+        ),
+        Err(WalletError::InvalidAddress(_))
+    ));
+    assert!(matches!(
+        wallet.new_transaction(7, &[(recipient.clone(), 1_000)], 2),
+        Err(WalletError::AccountNotFound(7))
+    ));
+
+    // the limits of bitcoin core by default
+    assert_eq!(Some(DEFAULT_MAX_FEE_RATE), wallet.fee_limits().max_fee_rate);
+    wallet.set_fee_limits(FeeLimits {
+        max_fee_rate: Some(100),
+        max_absolute_fee: None,
+    });
+    assert!(matches!(
+        wallet.new_transaction(account, &[(recipient.clone(), 1_000)], 200),
+        Err(WalletError::Transaction(TransactionError::AbsurdFeeRate {
+            rate: 200,
+            limit: 100
+        }))
+    ));
+    wallet.set_fee_limits(FeeLimits {
+        max_fee_rate: None,
+        max_absolute_fee: Some(1_000),
+    });
+    assert!(matches!(
+        wallet.new_transaction(account, &[(recipient.clone(), 1_000)], 10),
+        Err(WalletError::Transaction(TransactionError::AbsurdFee {
+            fee: 1_410,
+            limit: 1_000
+        }))
+    ));
+    wallet.set_fee_limits(FeeLimits::none());
+    assert!(wallet
+        .new_transaction(account, &[(recipient, 1_000)], 200)
+        .is_ok());
+}
+
+#[test]
+pub fn test_estimate_mixed_inputs() {
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2PubKeyHash);
+    builder.add_output(TransactionOutput::from_script(
+        29_000,
+        hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+    ));
+    for (account_type, tx_id) in [
+        (AccountType::Legacy, "11"),
+        (AccountType::NestedSegwit, "22"),
+        (AccountType::NativeSegwit, "33"),
+    ] {
+        let account = wallet.new_account(account_type).unwrap();
+        let address = wallet.new_receive_address(account).unwrap();
+        let output = TransactionOutput::new(
+            account_type.tx_type(),
+            wallet.get_address(address).unwrap(),
+            10_000,
+        );
+        builder.add_input(TransactionInput::new(output, tx_id.repeat(32), 0));
+    }
+
+    // each input is sized by its own type, not the type of the transaction
+    let mut tx = builder.build().unwrap();
+    let estimate = tx.estimate_vsize();
+    assert_eq!(estimate, tx.size());
+    wallet.sign_transaction(&mut tx).unwrap();
+    // signatures are at most a byte shorter than estimated
+    assert!(tx.size() <= estimate);
+    assert!(estimate - tx.size() <= 3);
+}
+
+#[test]
+pub fn test_sign_transaction() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    // a coin of an account of each type, spent as an input
+    let input = |wallet: &mut Wallet, account_type: AccountType, tx_id: &str| {
+        let account = wallet.new_account(account_type).unwrap();
+        let address = wallet.new_receive_address(account).unwrap();
+        let output = TransactionOutput::new(
+            account_type.tx_type(),
+            wallet.get_address(address).unwrap(),
+            10_000,
+        );
+        TransactionInput::new(output, tx_id.repeat(32), 0)
+    };
+    let legacy = input(&mut wallet, AccountType::Legacy, "11");
+    let nested = input(&mut wallet, AccountType::NestedSegwit, "22");
+    let native = input(&mut wallet, AccountType::NativeSegwit, "33");
+    let taproot = input(&mut wallet, AccountType::Taproot, "44");
+    let foreign = TransactionInput::new(
+        TransactionOutput::from_script(
+            10_000,
+            hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+        ),
+        "55".repeat(32),
+        0,
+    );
+
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder
+        .ordering(TxOrdering::Insertion)
+        .add_input(legacy)
+        .add_input(foreign)
+        .add_input(nested)
+        .add_input(native)
+        .add_output(TransactionOutput::from_script(
+            39_000,
+            hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+        ));
+
+    // every input of the wallet is signed, with a valid signature of its key
+    let mut tx = builder.build().unwrap();
+    assert_eq!(3, wallet.sign_transaction(&mut tx).unwrap());
+    let inputs = tx.inputs();
+    assert!(!inputs[0].signature_script().is_empty());
+    assert!(inputs[0].witness().is_empty());
+    assert!(inputs[1].signature_script().is_empty());
+    assert!(inputs[1].witness().is_empty());
+    assert!(!inputs[2].signature_script().is_empty());
+    assert_eq!(2, inputs[2].witness().len());
+    assert!(inputs[3].signature_script().is_empty());
+    assert_eq!(2, inputs[3].witness().len());
+
+    // signing again gives the same transaction
+    let signed = tx.to_hex();
+    assert_eq!(3, wallet.sign_transaction(&mut tx).unwrap());
+    assert_eq!(signed, tx.to_hex());
+
+    // taproot coins are spent through their key path with SIGHASH_DEFAULT
+    let mut tx = builder.add_input(taproot).build().unwrap();
+    assert_eq!(4, wallet.sign_transaction(&mut tx).unwrap());
+    let witness = tx.inputs()[4].witness().clone();
+    assert_eq!(1, witness.len());
+    assert_eq!(64, witness[0].len());
+    let prevouts: Vec<TransactionOutput> = tx
+        .inputs()
+        .iter()
+        .map(|input| {
+            TransactionOutput::from_script(input.utxo_value(), input.utxo_pk_script().to_vec())
+        })
+        .collect();
+    assert_eq!(Err(VerifyError::UnsignedInput(1)), tx.verify(&prevouts));
+    tx.verify_from(&prevouts, 2).unwrap();
+
+    // other sighash types are appended to every signature, taproot ones included
+    let mut anyone = builder.build().unwrap();
+    assert_eq!(
+        4,
+        wallet
+            .sign_transaction_with(&mut anyone, SigHashType::AllPlusAnyoneCanPay)
+            .unwrap()
+    );
+    let inputs = anyone.inputs();
+    assert_eq!(Some(&0x81), inputs[3].witness()[0].last());
+    assert_eq!(65, inputs[4].witness()[0].len());
+    assert_eq!(0x81, inputs[4].witness()[0][64]);
+    anyone.verify_from(&prevouts, 2).unwrap();
+    assert!(matches!(
+        wallet.sign_transaction_with(&mut anyone, SigHashType::TaprootDefault),
+        Err(WalletError::Transaction(
+            TransactionError::InvalidSighashType(0)
+        ))
+    ));
+
+    // a signer only signs ECDSA
+    let mut signing = wallet.clone();
+    let signer = signing.split_keystore().unwrap();
+    signing.set_signer(Arc::new(signer));
+    let mut unsigned = builder.build().unwrap();
+    assert!(matches!(
+        signing.sign_transaction(&mut unsigned),
+        Err(WalletError::UnsupportedInput(4))
+    ));
+
+    // keys can't be used while the wallet is locked
+    let mut tx = builder.build().unwrap();
+    wallet.encrypt("passphrase").unwrap();
+    assert!(matches!(
+        wallet.sign_transaction(&mut tx),
+        Err(WalletError::Locked)
+    ));
+    wallet.unlock("passphrase").unwrap();
+    assert_eq!(4, wallet.sign_transaction(&mut tx).unwrap());
+}
+
+#[test]
+pub fn test_sign_all() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let legacy = wallet.new_account(AccountType::Legacy).unwrap();
+    let taproot = wallet.new_account(AccountType::Taproot).unwrap();
+    let coin = |wallet: &mut Wallet, account: u32| {
+        let account_type = wallet.accounts()[account as usize].account_type();
+        let address = wallet.new_receive_address(account).unwrap();
+        TransactionOutput::new(
+            account_type.tx_type(),
+            wallet.get_address(address).unwrap(),
+            10_000,
+        )
+    };
+    let input = |wallet: &mut Wallet, account: u32, tx_id: &str| {
+        TransactionInput::new(coin(wallet, account), tx_id.repeat(32), 0)
+    };
+    let withdrawal = |inputs: Vec<TransactionInput>| {
+        let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+        builder.ordering(TxOrdering::Insertion);
+        for input in inputs {
+            builder.add_input(input);
+        }
+        builder
+            .add_output(TransactionOutput::from_script(
+                9_000,
+                hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+            ))
+            .build()
+            .unwrap()
+    };
+
+    // the same key spends coins of several transactions of the batch
+    let shared = coin(&mut wallet, account);
+    let mut batch = vec![
+        withdrawal(vec![
+            TransactionInput::new(shared.clone(), "11".repeat(32), 0),
+            input(&mut wallet, account, "22"),
+        ]),
+        withdrawal(vec![input(&mut wallet, legacy, "33")]),
+        withdrawal(vec![TransactionInput::new(shared, "44".repeat(32), 1)]),
+    ];
+    let unsigned = batch.clone();
+
+    // signed as one at a time
+    let mut one_by_one = batch.clone();
+    for tx in one_by_one.iter_mut() {
+        wallet.sign_transaction(tx).unwrap();
+    }
+    assert_eq!(vec![2, 1, 1], wallet.sign_all(&mut batch).unwrap());
+    let hex = |txs: &[Transaction]| txs.iter().map(Transaction::to_hex).collect::<Vec<_>>();
+    assert_eq!(hex(&one_by_one), hex(&batch));
+    assert!(wallet.sign_all(&mut []).unwrap().is_empty());
+
+    // no transaction is signed when one of the batch can't be, eg a taproot
+    // coin through a signer, which only signs ECDSA
+    let mut batch = unsigned.clone();
+    batch.insert(1, withdrawal(vec![input(&mut wallet, taproot, "55")]));
+    let before = hex(&batch);
+    let mut signing = wallet.clone();
+    let signer = signing.split_keystore().unwrap();
+    signing.set_signer(Arc::new(signer));
+    assert!(matches!(
+        signing.sign_all(&mut batch),
+        Err(WalletError::UnsupportedInput(0))
+    ));
+    assert_eq!(before, hex(&batch));
+    assert_eq!(vec![2, 1, 1, 1], wallet.sign_all(&mut batch).unwrap());
+
+    // the lock is checked before signing
+    let mut batch = unsigned;
+    wallet.encrypt("passphrase").unwrap();
+    assert!(matches!(
+        wallet.sign_all(&mut batch),
+        Err(WalletError::Locked)
+    ));
+    wallet.unlock("passphrase").unwrap();
+    assert_eq!(vec![2, 1, 1], wallet.sign_all(&mut batch).unwrap());
+    assert_eq!(hex(&one_by_one), hex(&batch));
+}
+
+#[test]
+pub fn test_anti_fee_snipe() {
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let recipient = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string();
+    let funding = BlockTransaction {
+        tx_id: "11".repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            wallet.get_address(receive).unwrap(),
+            100_000,
+        )],
+    };
+    let mut blocks = vec![block(0, vec![]), block(1, vec![funding])];
+    blocks.extend((2..=150).map(|height| block(height, vec![])));
+    wallet.sync(&MemoryChain(blocks)).unwrap();
+    assert!(wallet.anti_fee_snipe());
+
+    // locked to the tip scanned, or now and then a little before it
+    let tx = wallet
+        .new_transaction(account, &[(recipient.clone(), 30_000)], 2)
+        .unwrap();
+    match tx.lock_time() {
+        LockTime::Blocks(height) => assert!((51..=150).contains(&height)),
+        lock_time => panic!("locked to {:?}", lock_time),
+    }
+    assert!(tx
+        .inputs()
+        .iter()
+        .all(|input| input.sequence() == 0xfffffffe));
+
+    wallet.set_anti_fee_snipe(false);
+    let tx = wallet
+        .new_transaction(account, &[(recipient, 30_000)], 2)
+        .unwrap();
+    assert_eq!(LockTime::Blocks(0), tx.lock_time());
+    assert!(tx
+        .inputs()
+        .iter()
+        .all(|input| input.sequence() == 0xffffffff));
+}
+
+#[test]
+pub fn test_consolidate() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let other = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let mut fund = |account: u32, tx_id: &str, value: i64| {
+        let address = wallet.new_receive_address(account).unwrap();
+        let output = TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            wallet.get_address(address.clone()).unwrap(),
+            value,
+        );
+        wallet
+            .add_utxo(Utxo::new(
+                OutPoint::new(tx_id.repeat(32), 0),
+                output,
+                address,
+            ))
+            .unwrap();
+    };
+    for (tx_id, value) in [
+        ("11", 8_000),
+        ("22", 100),
+        ("33", 500_000),
+        ("44", 5_000),
+        ("55", 7_000),
+        ("66", 6_000),
+    ] {
+        fund(account, tx_id, value);
+    }
+    fund(other, "77", 1_000);
+    let values = |utxos: &[Utxo]| utxos.iter().map(Utxo::value).collect::<Vec<i64>>();
+
+    // the smallest coins worth spending are merged, the dust is left alone
+    let consolidation = wallet.consolidate(account, 2, 3).unwrap();
+    assert_eq!(
+        vec![5_000, 6_000, 7_000, 8_000],
+        values(&consolidation.spent)
+    );
+    assert_eq!(vec![100, 500_000], values(&consolidation.kept));
+    assert_eq!(3, consolidation.utxo_count());
+    let tx = &consolidation.tx;
+    assert_eq!(4, tx.tx_in_count());
+    assert_eq!(1, tx.tx_out_count());
+    assert!(wallet.is_change(tx.get_output(0).unwrap().pk_script()));
+    assert_eq!(consolidation.merged, tx.get_output(0).unwrap().value());
+    assert_eq!(26_000 - consolidation.merged, consolidation.fee);
+    assert_eq!((tx.estimate_vsize() * 2) as i64, consolidation.fee);
+
+    // as few coins as possible
+    let consolidation = wallet.consolidate(account, 2, 0).unwrap();
+    assert_eq!(5, consolidation.spent.len());
+    assert_eq!(2, consolidation.utxo_count());
+
+    assert_eq!(2, wallet.consolidate(account, 2, 5).unwrap().spent.len());
+    assert!(matches!(
+        wallet.consolidate(account, 2, 6),
+        Err(WalletError::NothingToConsolidate)
+    ));
+    // at a high rate only the largest coin is worth spending
+    assert!(matches!(
+        wallet.consolidate(account, 500, 1),
+        Err(WalletError::NothingToConsolidate)
+    ));
+    assert!(matches!(
+        wallet.consolidate(7, 2, 1),
+        Err(WalletError::AccountNotFound(7))
+    ));
+}
+
+#[test]
+pub fn test_wallet_file_checksum() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_wallet_file_checksum");
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    wallet.set_label(&address, "rent").unwrap();
+
+    let file = wallet.flush().unwrap();
+    let data = std::fs::read_to_string(&file).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&data).unwrap();
+    assert_eq!(64, value["checksum"].as_str().unwrap().len());
+    assert!(value.get("mac").is_none());
+    assert!(Wallet::from_wallet_file(file.clone()).is_ok());
+
+    // a label changed outside the wallet
+    std::fs::write(&file, data.replace("rent", "food")).unwrap();
+    match Wallet::from_wallet_file(file.clone()) {
+        Err(WalletError::Corrupted { expected, actual }) => {
+            assert_eq!(value["checksum"].as_str().unwrap(), expected);
+            assert_ne!(expected, actual);
+        }
+        other => panic!("expected a corrupted file, got {:?}", other),
+    }
+
+    // an encrypted wallet flushed while unlocked is also authenticated with its key
+    wallet.encrypt("passphrase").unwrap();
+    wallet.unlock("passphrase").unwrap();
+    wallet.flush().unwrap();
+    let data = std::fs::read_to_string(&file).unwrap();
+    let mut loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    loaded.unlock("passphrase").unwrap();
+    assert_eq!(Some("rent"), loaded.label(&address));
+
+    // a change to the key fields whose checksum is written again can't forge
+    // the mac, eg chain codes redirecting the addresses derived while locked
+    let mut tampered: serde_json::Value = serde_json::from_str(&data).unwrap();
+    let account_node = tampered["accounts"][0]["node"].clone();
+    for record in tampered["keys"]["records"].as_array_mut().unwrap() {
+        if record["parent"] == account_node {
+            let chain_code = &mut record["keypair"]["private_key"]["chain_code"][0];
+            *chain_code = (chain_code.as_u64().unwrap() ^ 1).into();
+        }
+    }
+    seal_wallet_file(&mut tampered, None);
+    std::fs::write(&file, tampered.to_string()).unwrap();
+    let mut forged = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert!(matches!(
+        forged.new_receive_address(account),
+        Err(WalletError::Locked)
+    ));
+    assert!(matches!(
+        forged.unlock("passphrase"),
+        Err(WalletError::Corrupted { .. })
+    ));
+    assert!(matches!(
+        forged.unlock("wrong"),
+        Err(WalletError::IncorrectPassphrase)
+    ));
+
+    // a locked wallet still authenticates the files it writes
+    wallet.lock().unwrap();
+    wallet.set_label(&address, "bills").unwrap();
+    wallet.flush().unwrap();
+    let value: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert!(value.get("mac").is_some());
+    let mut loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    loaded.unlock("passphrase").unwrap();
+    assert_eq!(Some("bills"), loaded.label(&address));
+
+    // a wallet loaded locked writes the mac it read while its key fields are
+    // unchanged, and derives addresses once they're authenticated by unlocking it
+    let mut locked = Wallet::from_wallet_file(file.clone()).unwrap();
+    locked.set_label(&address, "rent").unwrap();
+    locked.flush().unwrap();
+    assert!(matches!(
+        locked.new_receive_address(account),
+        Err(WalletError::Locked)
+    ));
+    locked.unlock("passphrase").unwrap();
+    locked.lock().unwrap();
+    let issued = locked.new_receive_address(account).unwrap();
+    locked.flush().unwrap();
+    let mut loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    loaded.unlock("passphrase").unwrap();
+    assert_eq!(Some("rent"), loaded.label(&address));
+    assert!(loaded.get_address(issued).is_some());
+
+    // an encrypted file without a mac was changed without the passphrase
+    let mut stripped = value.clone();
+    stripped.as_object_mut().unwrap().remove("mac");
+    seal_wallet_file(&mut stripped, None);
+    std::fs::write(&file, stripped.to_string()).unwrap();
+    match Wallet::from_wallet_file(file.clone()) {
+        Err(WalletError::Corrupted { expected, .. }) => assert_eq!("mac", expected),
+        other => panic!("expected a corrupted file, got {:?}", other),
+    }
+
+    // a file written before the mac had its own key is authenticated with the
+    // wallet key, and given a mac key once unlocked
+    let mut legacy = value;
+    legacy["encryption"]
+        .as_object_mut()
+        .unwrap()
+        .remove("mac_key");
+    let params: EncryptionParams = serde_json::from_value(legacy["encryption"].clone()).unwrap();
+    let key = params.unlock("passphrase").unwrap();
+    let mac = wallet_file_mac(&legacy, &key);
+    seal_wallet_file(&mut legacy, Some(&mac));
+    std::fs::write(&file, legacy.to_string()).unwrap();
+    let mut loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    loaded.set_label(&address, "rent").unwrap();
+    assert!(matches!(loaded.flush(), Err(WalletError::Locked)));
+    loaded.unlock("passphrase").unwrap();
+    loaded.lock().unwrap();
+    loaded.flush().unwrap();
+    let value: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert!(!value["encryption"]["mac_key"]
+        .as_array()
+        .unwrap()
+        .is_empty());
+    let mut loaded = Wallet::from_wallet_file(file).unwrap();
+    loaded.unlock("passphrase").unwrap();
+    assert_eq!(Some("rent"), loaded.label(&address));
+}
+
+#[test]
+pub fn test_flush_skips_unchanged_files() {
+    let path = std::env::temp_dir().join("waller_test_flush_skips_unchanged_files");
+    let _ = std::fs::remove_dir_all(&path);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path,
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+
+    let modified = |file: &std::path::Path| std::fs::metadata(file).unwrap().modified().unwrap();
+    let file = wallet.flush().unwrap();
+    let cache = wallet.config().cache_file();
+    let (written, cached) = (modified(&file), modified(&cache));
+
+    // neither file changed, nor did a clone of the wallet
+    wallet.flush().unwrap();
+    wallet.clone().flush().unwrap();
+    assert_eq!(written, modified(&file));
+    assert_eq!(cached, modified(&cache));
+
+    // a label only changes the wallet file
+    wallet.set_label(&address, "rent").unwrap();
+    wallet.flush().unwrap();
+    assert_ne!(written, modified(&file));
+    assert_eq!(cached, modified(&cache));
+    let loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert_eq!(Some("rent"), loaded.label(&address));
+
+    // a file written by something else is written again
+    std::fs::write(&file, "{}").unwrap();
+    wallet.flush().unwrap();
+    let loaded = Wallet::from_wallet_file(file).unwrap();
+    assert_eq!(Some("rent"), loaded.label(&address));
+}
+
+#[test]
+pub fn test_flush_tracks_changes() {
+    let path = std::env::temp_dir().join("waller_test_flush_tracks_changes");
+    let _ = std::fs::remove_dir_all(&path);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path,
+        false,
+    )
+    .unwrap();
+    assert!(wallet.is_dirty());
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+
+    // flushing after every derived key
+    let mut addresses = vec![];
+    for _ in 0..20 {
+        addresses.push(wallet.new_receive_address(account).unwrap());
+        wallet.flush().unwrap();
+        assert!(!wallet.is_dirty());
+    }
+    let file = wallet.flush().unwrap();
+    let loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert!(!loaded.is_dirty());
+    assert_eq!(
+        wallet.keys().len(),
+        loaded.keys().len(),
+        "every derived key was written"
+    );
+
+    // an output only changes the cache file
+    let modified = |file: &std::path::Path| std::fs::metadata(file).unwrap().modified().unwrap();
+    let cache = wallet.config().cache_file();
+    let written = modified(&file);
+    wallet
+        .add_utxo(Utxo::new(
+            OutPoint::new("33".repeat(32), 0),
+            TransactionOutput::from_script(5_000, vec![0x00, 0x14]),
+            addresses[3].clone(),
+        ))
+        .unwrap();
+    assert!(wallet.is_dirty());
+    let cached = modified(&cache);
+    wallet.flush().unwrap();
+    assert_eq!(written, modified(&file));
+    assert_ne!(cached, modified(&cache));
+
+    // a clone flushing other changes doesn't leave the wallet thinking its
+    // unchanged sections are still on disk
+    let mut clone = wallet.clone();
+    clone.set_label(&addresses[0], "clone").unwrap();
+    clone.flush().unwrap();
+    assert!(!wallet.is_dirty());
+    wallet.flush().unwrap();
+    let loaded = Wallet::from_wallet_file(file).unwrap();
+    assert_eq!(None, loaded.label(&addresses[0]));
+    assert_eq!(5_000, loaded.account_balance(account));
+}
+
+#[test]
+pub fn test_failed_calls_leave_wallet_clean() {
+    let path = std::env::temp_dir().join("waller_test_failed_calls_leave_wallet_clean");
+    let _ = std::fs::remove_dir_all(&path);
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        path,
+        false,
+    )
+    .unwrap();
+    wallet.flush().unwrap();
+
+    assert!(matches!(wallet.lock(), Err(WalletError::Unencrypted)));
+    assert!(matches!(
+        wallet.unlock("pass"),
+        Err(WalletError::Unencrypted)
+    ));
+    assert!(matches!(
+        wallet.change_passphrase("pass", "new"),
+        Err(WalletError::Unencrypted)
+    ));
+    assert!(matches!(
+        wallet.set_key_cache(Some(4)),
+        Err(WalletError::Unencrypted)
+    ));
+    assert!(wallet
+        .set_label("1BoatSLRHtKNngkdXEeobR76b53LETtpyT", "x")
+        .is_err());
+    assert!(matches!(
+        wallet.new_receive_address(7),
+        Err(WalletError::AccountNotFound(7))
+    ));
+    assert!(wallet
+        .spend_utxo(&OutPoint::new("44".repeat(32), 0))
+        .is_none());
+    assert!(!wallet.is_dirty());
+
+    wallet.encrypt("pass").unwrap();
+    assert!(wallet.is_dirty());
+    wallet.flush().unwrap();
+    assert!(matches!(
+        wallet.encrypt("pass"),
+        Err(WalletError::AlreadyEncrypted)
+    ));
+    assert!(wallet.unlock("wrong").is_err());
+    assert!(wallet.change_passphrase("wrong", "new").is_err());
+    // locking a locked wallet changes nothing
+    wallet.lock().unwrap();
+    assert!(!wallet.is_dirty());
+}
+
+#[test]
+pub fn test_key_cache() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_key_cache");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let address = wallet.new_receive_address(account).unwrap();
+    let signature = wallet.sign_data(address.clone(), vec![7; 32]).unwrap();
+
+    assert!(matches!(
+        wallet.set_key_cache(Some(2)),
+        Err(WalletError::Unencrypted)
+    ));
+    wallet.encrypt("passphrase").unwrap();
+    wallet.set_key_cache(Some(2)).unwrap();
+    wallet.unlock("passphrase").unwrap();
+
+    // the keys stay sealed while unlocked, they're unsealed on use
+    assert!(wallet.get_address(address.clone()).unwrap().is_wiped());
+    assert_eq!(0, wallet.key_cache_status().unwrap().len);
+    assert_eq!(
+        signature,
+        wallet.sign_data(address.clone(), vec![7; 32]).unwrap()
+    );
+    assert_eq!(1, wallet.key_cache_status().unwrap().len);
+
+    // new keys are sealed as they're derived, the cache keeps the most recent
+    let second = wallet.new_receive_address(account).unwrap();
+    assert!(wallet.get_address(second.clone()).unwrap().is_wiped());
+    let status = wallet.key_cache_status().unwrap();
+    assert_eq!(2, status.capacity);
+    assert_eq!(2, status.len);
+    wallet.sign_data(second.clone(), vec![7; 32]).unwrap();
+
+    wallet.purge_key_cache();
+    assert_eq!(0, wallet.key_cache_status().unwrap().len);
+    assert_eq!(
+        signature,
+        wallet.sign_data(address.clone(), vec![7; 32]).unwrap()
+    );
+
+    // the keys on disk stay sealed, the file is locked until unlocked with the passphrase
+    let file = wallet.flush().unwrap();
+    let mut loaded = Wallet::from_wallet_file(file).unwrap();
+    assert!(loaded.is_locked());
+    assert!(matches!(
+        loaded.sign_data(address.clone(), vec![7; 32]),
+        Err(WalletError::Locked)
+    ));
+    loaded.unlock("passphrase").unwrap();
+    assert!(!loaded.get_address(second).unwrap().is_wiped());
+
+    // locking purges the cache, dropping it unseals the keys again
+    wallet.lock().unwrap();
+    assert_eq!(0, wallet.key_cache_status().unwrap().len);
+    assert!(matches!(
+        wallet.sign_data(address.clone(), vec![7; 32]),
+        Err(WalletError::Locked)
+    ));
+    wallet.unlock("passphrase").unwrap();
+    wallet.set_key_cache(None).unwrap();
+    assert!(wallet.key_cache_status().is_none());
+    assert!(!wallet.get_address(address.clone()).unwrap().is_wiped());
+    assert_eq!(signature, wallet.sign_data(address, vec![7; 32]).unwrap());
+}
+
+#[test]
+pub fn test_offline_signing() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let restore = |name: &str| {
+        let data_dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&data_dir).unwrap();
+        Wallet::restore(mnemonic.clone(), Network::Mainnet, true, data_dir, false).unwrap()
+    };
+
+    // the online wallet keeps no private key
+    let mut online = restore("waller_test_offline_signing_online");
+    let input = |wallet: &mut Wallet, account_type: AccountType, tx_id: &str| {
+        let account = wallet.new_account(account_type).unwrap();
+        let address = wallet.new_receive_address(account).unwrap();
+        let output = TransactionOutput::new(
+            account_type.tx_type(),
+            wallet.get_address(address).unwrap(),
+            10_000,
+        );
+        TransactionInput::new(output, tx_id.repeat(32), 0)
+    };
+    let legacy = input(&mut online, AccountType::Legacy, "11");
+    let native = input(&mut online, AccountType::NativeSegwit, "22");
+    let foreign = TransactionInput::new(
+        TransactionOutput::from_script(
+            10_000,
+            hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+        ),
+        "33".repeat(32),
+        0,
+    );
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder
+        .ordering(TxOrdering::Insertion)
+        .add_input(legacy)
+        .add_input(foreign)
+        .add_input(native)
+        .add_output(TransactionOutput::from_script(
+            29_000,
+            hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+        ));
+    let unsigned = builder.build().unwrap();
+    let mut expected = unsigned.clone();
+    assert_eq!(2, online.sign_transaction(&mut expected).unwrap());
+    online.split_keystore().unwrap();
+
+    let request = online.export_signing_request(&unsigned).unwrap();
+    assert_eq!(unsigned.tx_id(), request.tx_id);
+    assert!(request.inputs[0].path.is_some());
+    assert!(request.inputs[1].path.is_none());
+    assert!(request.inputs[2].path.is_some());
+    let request: SigningRequest =
+        serde_json::from_str(&serde_json::to_string(&request).unwrap()).unwrap();
+    assert_eq!(unsigned.tx_id(), request.transaction().unwrap().tx_id());
+
+    // the air-gapped wallet derives the keys from the paths, it has no account yet
+    let mut offline = restore("waller_test_offline_signing_offline");
+    let response = offline.sign_signing_request(&request).unwrap();
+    assert_eq!(
+        vec![0, 2],
+        response
+            .signatures
+            .iter()
+            .map(|signature| signature.input)
+            .collect::<Vec<usize>>()
+    );
+    let response: SigningResponse =
+        serde_json::from_str(&serde_json::to_string(&response).unwrap()).unwrap();
+
+    let mut tx = unsigned.clone();
+    assert_eq!(
+        2,
+        online.apply_signing_response(&mut tx, &response).unwrap()
+    );
+    assert_eq!(expected.to_hex(), tx.to_hex());
+
+    // a changed transaction isn't signed
+    let mut tampered = request.clone();
+    tampered.outputs[0].value = 9_000;
+    assert!(matches!(
+        offline.sign_signing_request(&tampered),
+        Err(WalletError::Transaction(TransactionError::TxIdMismatch(_)))
+    ));
+
+    // a bad signature adds none of them
+    let mut forged = response.clone();
+    forged.signatures[1].public_key = forged.signatures[0].public_key.clone();
+    let mut tx = unsigned.clone();
+    assert!(online.apply_signing_response(&mut tx, &forged).is_err());
+    assert_eq!(unsigned.to_hex(), tx.to_hex());
+    assert!(matches!(
+        online.apply_signing_response(
+            &mut expected,
+            &SigningResponse {
+                tx_id: "00".repeat(32),
+                signatures: vec![],
+                proprietary: ProprietaryFields::default(),
+            }
+        ),
+        Err(WalletError::Transaction(TransactionError::TxIdMismatch(_)))
+    ));
+}
+
+#[test]
+pub fn test_offline_taproot_signing() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let restore = |name: &str| {
+        let data_dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&data_dir).unwrap();
+        Wallet::restore(mnemonic.clone(), Network::Mainnet, true, data_dir, false).unwrap()
+    };
+
+    // a BIP86 account of a wallet keeping no private key
+    let mut online = restore("waller_test_offline_taproot_signing_online");
+    let account = online.new_account(AccountType::Taproot).unwrap();
+    let address = online.new_receive_address(account).unwrap();
+    let prevout = TransactionOutput::new(
+        AccountType::Taproot.tx_type(),
+        online.get_address(address).unwrap(),
+        10_000,
+    );
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2Taproot);
+    builder
+        .add_input(TransactionInput::new(prevout.clone(), "11".repeat(32), 0))
+        .add_output(TransactionOutput::from_script(
+            9_000,
+            hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+        ));
+    let unsigned = builder.build().unwrap();
+    online.split_keystore().unwrap();
+
+    let request = online.export_signing_request(&unsigned).unwrap();
+    assert!(request.inputs[0].path.is_some());
+    let mut offline = restore("waller_test_offline_taproot_signing_offline");
+    let response = offline.sign_signing_request(&request).unwrap();
+    assert_eq!(1, response.signatures.len());
+    assert_eq!(
+        hex::encode(&prevout.pk_script()[2..]),
+        response.signatures[0].public_key
+    );
+
+    // the key path signature is checked against the tweaked output key
+    let mut tx = unsigned.clone();
+    assert_eq!(
+        1,
+        online.apply_signing_response(&mut tx, &response).unwrap()
+    );
+    assert_eq!(1, tx.inputs()[0].witness().len());
+    assert_eq!(Ok(()), tx.verify(std::slice::from_ref(&prevout)));
+
+    let mut forged = response.clone();
+    forged.signatures[0].signature = hex::encode([1; 64]);
+    let mut tx = unsigned.clone();
+    assert!(matches!(
+        online.apply_signing_response(&mut tx, &forged),
+        Err(WalletError::Transaction(
+            TransactionError::InvalidSignature(0)
+        ))
+    ));
+    assert_eq!(unsigned.to_hex(), tx.to_hex());
+}
+
+#[test]
+pub fn test_signing_request_proprietary_fields() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let restore = |name: &str| {
+        let data_dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&data_dir).unwrap();
+        Wallet::restore(mnemonic.clone(), Network::Mainnet, true, data_dir, false).unwrap()
+    };
+
+    let mut online = restore("waller_test_signing_request_proprietary_online");
+    let account = online.new_account(AccountType::NativeSegwit).unwrap();
+    let address = online.new_receive_address(account).unwrap();
+    let coin = TransactionOutput::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        online.get_address(address).unwrap(),
+        50_000,
+    );
+    let change = online.new_change_address(account).unwrap();
+    let mut builder = TransactionBuilder::new(TransactionType::Pay2WitnessPubKeyHash);
+    builder
+        .ordering(TxOrdering::Insertion)
+        .add_input(TransactionInput::new(coin, "11".repeat(32), 0))
+        .add_output(TransactionOutput::from_script(
+            30_000,
+            hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+        ))
+        .add_output(TransactionOutput::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            online.get_address(change).unwrap(),
+            19_000,
+        ));
+    let unsigned = builder.build().unwrap();
+
+    let mut request = online.export_signing_request(&unsigned).unwrap();
+    assert!(!request.outputs[0].is_change());
+    assert!(request.outputs[1].is_change());
+
+    // the metadata of an application, at every level of the request
+    let app: &[u8] = b"acme";
+    request.proprietary.insert(app, 0, b"invoice", b"2026-17");
+    request.inputs[0].proprietary.insert(app, 1, &[], b"hot");
+    request.outputs[0].proprietary.insert(app, 2, &[], b"rent");
+    assert_eq!(
+        Some(b"2026-17".to_vec()),
+        request.proprietary.insert(app, 0, b"invoice", b"2026-18")
+    );
+    assert_eq!(1, request.proprietary.len());
+    assert_eq!(None, request.proprietary.get(app, 1, b"invoice"));
+    assert_eq!(None, request.proprietary.get(b"other", 0, b"invoice"));
+
+    // the fields survive the trip to the signer, and come back with the signatures
+    let request: SigningRequest =
+        serde_json::from_str(&serde_json::to_string(&request).unwrap()).unwrap();
+    assert_eq!(
+        Some(b"hot".to_vec()),
+        request.inputs[0].proprietary.get(app, 1, &[])
+    );
+    assert_eq!(
+        Some(b"rent".to_vec()),
+        request.outputs[0].proprietary.get(app, 2, &[])
+    );
+    assert!(request.outputs[1].is_change());
+    assert_eq!(
+        1,
+        request.outputs[1]
+            .proprietary
+            .with_prefix(WALLER_PROPRIETARY_PREFIX)
+            .count()
+    );
+    assert_eq!(0, request.outputs[1].proprietary.with_prefix(app).count());
+
+    let mut offline = restore("waller_test_signing_request_proprietary_offline");
+    let mut response = offline.sign_signing_request(&request).unwrap();
+    response.proprietary.insert(app, 3, &[], b"approved");
+    let response: SigningResponse =
+        serde_json::from_str(&serde_json::to_string(&response).unwrap()).unwrap();
+    assert_eq!(
+        Some(b"2026-18".to_vec()),
+        response.proprietary.get(app, 0, b"invoice")
+    );
+    assert_eq!(
+        Some(b"approved".to_vec()),
+        response.proprietary.get(app, 3, &[])
+    );
+
+    let mut tx = unsigned.clone();
+    assert_eq!(
+        1,
+        online.apply_signing_response(&mut tx, &response).unwrap()
+    );
+
+    // requests without fields read as before
+    let mut bare: serde_json::Value = serde_json::to_value(&request).unwrap();
+    assert!(bare["outputs"][0]["proprietary"].is_array());
+    bare["outputs"][0]
+        .as_object_mut()
+        .unwrap()
+        .remove("proprietary");
+    let bare: SigningRequest = serde_json::from_value(bare).unwrap();
+    assert!(bare.outputs[0].proprietary.is_empty());
+}
+
+/// a [MemoryChain] relaying transactions, refusing them when offline
+#[cfg(test)]
+struct RelayChain {
+    blocks: Vec<Block>,
+    relayed: Mutex<Vec<String>>,
+    offline: bool,
+}
+
+#[cfg(test)]
+impl Backend for RelayChain {
+    fn broadcast(&self, raw_tx: &str) -> Result<String, BackendError> {
+        if self.offline {
+            return Err(BackendError::Connection("connection refused".to_string()));
+        }
+        let mut relayed = self.relayed.lock().unwrap();
+        if relayed.iter().any(|relayed| relayed == raw_tx) {
+            return Err(BackendError::Rpc {
+                code: -26,
+                message: "txn-already-in-mempool".to_string(),
+            });
+        }
+        relayed.push(raw_tx.to_string());
+        Ok(String::new())
+    }
+
+    fn tip_height(&self) -> Result<u32, BackendError> {
+        Ok(self.blocks.len() as u32 - 1)
+    }
+
+    fn block(&self, height: u32) -> Result<Block, BackendError> {
+        Ok(self.blocks[height as usize].clone())
+    }
+}
+
+#[test]
+pub fn test_rebroadcast_pending() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_rebroadcast_pending");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let key = wallet.get_address(receive).unwrap();
+    let coin = |value: i64| {
+        TransactionOutput::new(TransactionType::Pay2WitnessPubKeyHash, key.clone(), value)
+    };
+    let outside = hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+    let funding = |tx_id: &str| BlockTransaction {
+        tx_id: tx_id.repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![coin(50_000)],
+    };
+    let mut blocks = vec![
+        block(0, vec![]),
+        block(1, vec![funding("11"), funding("22")]),
+    ];
+    wallet
+        .sync(&RelayChain {
+            blocks: blocks.clone(),
+            relayed: Mutex::new(vec![]),
+            offline: false,
+        })
+        .unwrap();
+
+    let spend = |wallet: &Wallet, tx_id: &str, value: i64| {
+        let input = TransactionInput::new(coin(50_000), tx_id.repeat(32), 0);
+        let output = TransactionOutput::from_script(value, outside.clone());
+        let mut tx = Transaction::new(
+            TransactionType::Pay2WitnessPubKeyHash,
+            vec![input],
+            vec![output],
+            None,
+        );
+        wallet.sign_transaction(&mut tx).unwrap();
+        tx
+    };
+    let first = spend(&wallet, "11", 49_000);
+    let second = spend(&wallet, "22", 49_000);
+    let bump = spend(&wallet, "22", 48_000);
+
+    let unsigned = Transaction::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        vec![TransactionInput::new(coin(50_000), "11".repeat(32), 0)],
+        vec![TransactionOutput::from_script(49_000, outside.clone())],
+        None,
+    );
+    assert!(matches!(
+        wallet.archive_transaction(&unsigned),
+        Err(WalletError::Transaction(TransactionError::UnsignedInput(0)))
+    ));
+    wallet.archive_transaction(&first).unwrap();
+    wallet.archive_transaction(&first).unwrap();
+    wallet.archive_transaction(&second).unwrap();
+    assert_eq!(2, wallet.archived_transactions().len());
+
+    // the archive is kept in the cache, and relayed on each sync
+    let mut wallet = Wallet::from_wallet_file(wallet.flush().unwrap()).unwrap();
+    assert_eq!(2, wallet.archived_transactions().len());
+    let offline = RelayChain {
+        blocks: blocks.clone(),
+        relayed: Mutex::new(vec![]),
+        offline: true,
+    };
+    let report = wallet.rebroadcast_pending(&offline);
+    assert_eq!(2, report.failed.len());
+    assert!(report.relayed.is_empty());
+
+    let backend = RelayChain {
+        blocks: blocks.clone(),
+        relayed: Mutex::new(vec![]),
+        offline: false,
+    };
+    let diff = wallet.sync(&backend).unwrap();
+    assert_eq!(vec![first.tx_id(), second.tx_id()], diff.rebroadcast);
+    assert_eq!(
+        vec![first.to_hex(), second.to_hex()],
+        *backend.relayed.lock().unwrap()
+    );
+    // known to the mempool already
+    let diff = wallet.sync(&backend).unwrap();
+    assert_eq!(2, diff.rebroadcast.len());
+
+    // the first confirms, a fee bump of the second confirms in its place
+    let spent = |tx: &Transaction| BlockTransaction {
+        tx_id: tx.tx_id(),
+        inputs: tx
+            .inputs()
+            .iter()
+            .map(|input| input.previous_output().clone())
+            .collect(),
+        outputs: tx.outputs().clone(),
+    };
+    wallet.record_spends(&second);
+    blocks.push(block(2, vec![spent(&first), spent(&bump)]));
+    let report = wallet.rebroadcast_pending(&RelayChain {
+        blocks: blocks.clone(),
+        relayed: Mutex::new(vec![]),
+        offline: false,
+    });
+    assert!(report.dropped.is_empty());
+    let diff = wallet
+        .sync(&RelayChain {
+            blocks,
+            relayed: Mutex::new(vec![]),
+            offline: false,
+        })
+        .unwrap();
+    assert!(diff.rebroadcast.is_empty());
+    assert!(wallet.archived_transactions().is_empty());
+}
+
+/// a [MemoryChain] serving the silent payment tweaks of its blocks
+#[cfg(test)]
+struct TweakChain {
+    blocks: Vec<Block>,
+    tweaks: HashMap<u32, HashMap<String, Vec<u8>>>,
+    requests: Mutex<u32>,
+}
+
+#[cfg(test)]
+impl Backend for TweakChain {
+    fn broadcast(&self, _raw_tx: &str) -> Result<String, BackendError> {
+        Err(BackendError::Unsupported("broadcast".to_string()))
+    }
+
+    fn tip_height(&self) -> Result<u32, BackendError> {
+        Ok(self.blocks.len() as u32 - 1)
+    }
+
+    fn block(&self, height: u32) -> Result<Block, BackendError> {
+        Ok(self.blocks[height as usize].clone())
+    }
+
+    fn silent_payment_tweaks(&self, height: u32) -> Result<HashMap<String, Vec<u8>>, BackendError> {
+        *self.requests.lock().unwrap() += 1;
+        Ok(self.tweaks.get(&height).cloned().unwrap_or_default())
+    }
+}
+
+#[test]
+pub fn test_silent_payments() {
+    use super::silentpayment_test::{silent_payment_script, Spending};
+
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let data_dir = std::env::temp_dir().join("waller_test_silent_payments");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let mut wallet = Wallet::restore(mnemonic, Network::Mainnet, true, data_dir, false).unwrap();
+
+    // a sender pays the address twice in a transaction, spending two coins
+    let spending = vec![
+        (Spending::Taproot(3), OutPoint::new("11".repeat(32), 0)),
+        (Spending::Witness(4), OutPoint::new("22".repeat(32), 1)),
+    ];
+    let inputs: Vec<SilentPaymentInput> = spending
+        .iter()
+        .map(|(key, outpoint)| key.input(outpoint.clone()))
+        .collect();
+    let chain = |address: &SilentPaymentAddress, spent: bool| {
+        let outputs = vec![
+            TransactionOutput::from_script(30_000, silent_payment_script(&spending, address, 0)),
+            TransactionOutput::from_script(
+                5_000,
+                hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+            ),
+            TransactionOutput::from_script(12_000, silent_payment_script(&spending, address, 1)),
+        ];
+        let tweak = silent_payment_tweak(&inputs, &outputs).unwrap();
+        let paying = BlockTransaction {
+            tx_id: "aa".repeat(32),
+            inputs: inputs
+                .iter()
+                .map(|input| input.previous_output.clone())
+                .collect(),
+            outputs,
+        };
+        let mut blocks = vec![block(0, vec![]), block(1, vec![paying])];
+        if spent {
+            blocks.push(block(
+                2,
+                vec![BlockTransaction {
+                    tx_id: "bb".repeat(32),
+                    inputs: vec![OutPoint::new("aa".repeat(32), 2)],
+                    outputs: vec![],
+                }],
+            ));
+        }
+        TweakChain {
+            blocks,
+            tweaks: HashMap::from([(1, HashMap::from([("aa".repeat(32), tweak)]))]),
+            requests: Mutex::new(0),
+        }
+    };
+
+    // payments aren't looked for until the address is handed out
+    let address = wallet.silent_payment_address().unwrap();
+    let mut fresh = Wallet::restore(
+        String::from(
+            "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+        ),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let backend = chain(&address, false);
+    fresh.sync(&backend).unwrap();
+    assert_eq!(0, *backend.requests.lock().unwrap());
+    assert!(fresh.silent_payment_outputs().is_empty());
+
+    assert!(address.to_string().starts_with("sp1q"));
+    assert_eq!(address, wallet.silent_payment_address().unwrap());
+    assert_eq!(
+        address.to_string().parse::<SilentPaymentAddress>().unwrap(),
+        address
+    );
+    assert_eq!(address, fresh.silent_payment_address().unwrap());
+
+    wallet.sync(&backend).unwrap();
+    let found = wallet.silent_payment_outputs();
+    assert_eq!(
+        vec![(0, 30_000), (2, 12_000)],
+        found
+            .iter()
+            .map(|output| (output.outpoint().index(), output.value()))
+            .collect::<Vec<(i32, i64)>>()
+    );
+    assert_eq!(Some(1), found[0].height());
+    assert_eq!(32, found[0].tweak().len());
+    // not part of the balance, the wallet can't spend them yet
+    assert!(wallet.utxos().is_empty());
+
+    // spent, and found again by a rescan
+    let backend = chain(&address, true);
+    wallet.sync(&backend).unwrap();
+    assert_eq!(1, wallet.silent_payment_outputs().len());
+    wallet.rescan(&backend, 0).unwrap();
+    assert_eq!(1, wallet.silent_payment_outputs().len());
+    assert_eq!(0, wallet.silent_payment_outputs()[0].outpoint().index());
+
+    // kept in the cache, with the address handed out
+    let wallet = Wallet::from_wallet_file(wallet.flush().unwrap()).unwrap();
+    assert_eq!(1, wallet.silent_payment_outputs().len());
+    assert!(format!("{:?}", wallet).contains("silent_payments: true"));
+}
+
+#[test]
+pub fn test_coinbase_maturity() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let script = Script::from_address(&receive, Network::Mainnet).unwrap();
+    let recipient = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string();
+
+    // a block reward at height 1, and a payment confirmed with it
+    let chain = |tip: u32| {
+        let mut blocks = vec![block(0, vec![])];
+        blocks.push(block(
+            1,
+            vec![
+                BlockTransaction {
+                    tx_id: "11".repeat(32),
+                    inputs: vec![],
+                    outputs: vec![TransactionOutput::from_script(
+                        312_500_000,
+                        script.as_bytes().to_vec(),
+                    )],
+                },
+                BlockTransaction {
+                    tx_id: "22".repeat(32),
+                    inputs: vec![OutPoint::new("00".repeat(32), 0)],
+                    outputs: vec![TransactionOutput::from_script(
+                        20_000,
+                        script.as_bytes().to_vec(),
+                    )],
+                },
+            ],
+        ));
+        blocks.extend((2..=tip).map(|height| block(height, vec![])));
+        MemoryChain(blocks)
+    };
+
+    // 99 confirmations at height 99
+    assert_eq!(99, wallet.rescan(&chain(99), 0).unwrap());
+    let coinbase = wallet
+        .utxos()
+        .iter()
+        .find(|utxo| utxo.outpoint().hash() == "11".repeat(32))
+        .unwrap();
+    assert!(coinbase.is_coinbase() && !coinbase.is_mature(99));
+    assert!(coinbase.is_mature(100));
+    assert!(wallet.utxos().iter().any(|utxo| !utxo.is_coinbase()));
+    let balance = wallet.balance();
+    assert_eq!(
+        (20_000, 0, 312_500_000),
+        (balance.confirmed, balance.unconfirmed, balance.immature)
+    );
+    assert_eq!(20_000, balance.spendable());
+    assert_eq!(20_000, wallet.account_balance(account));
+    // coin selection leaves the reward alone
+    assert!(matches!(
+        wallet.new_transaction(account, &[(recipient.clone(), 100_000)], 1),
+        Err(WalletError::InsufficientFunds)
+    ));
+
+    // spendable in the block after the 100th confirmation
+    wallet.sync(&chain(100)).unwrap();
+    let balance = wallet.balance();
+    assert_eq!((312_520_000, 0), (balance.confirmed, balance.immature));
+    assert_eq!(312_520_000, balance.total());
+    assert_eq!(312_520_000, wallet.account_balance(account));
+    let tx = wallet
+        .new_transaction(account, &[(recipient, 100_000)], 1)
+        .unwrap();
+    assert_eq!("11".repeat(32), tx.inputs()[0].previous_output().hash());
+
+    // still a coinbase once reloaded
+    let wallet = Wallet::from_wallet_file(wallet.flush().unwrap()).unwrap();
+    assert!(wallet
+        .utxos()
+        .iter()
+        .any(|utxo| utxo.is_coinbase() && utxo.value() == 312_500_000));
+}
+
+#[test]
+pub fn test_sign_p2pk() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::Legacy).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let wif = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    wallet.import_wif(wif, None).unwrap();
+    let uncompressed = Key::from_wif(wif.to_string())
+        .unwrap()
+        .new_public_key()
+        .unwrap();
+    let compressed = compress_public_key(&uncompressed).unwrap();
+    let p2pk = |key: &[u8]| {
+        let mut script = vec![key.len() as u8];
+        script.extend_from_slice(key);
+        script.push(0xac);
+        script
+    };
+
+    // old coins of the imported key paid to its bare public key, in both forms
+    let derived = TransactionOutput::new(
+        TransactionType::Pay2PubKey,
+        wallet.get_address(receive).unwrap(),
+        70_000,
+    );
+    let funding = BlockTransaction {
+        tx_id: "11".repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![
+            TransactionOutput::from_script(50_000, p2pk(&uncompressed)),
+            TransactionOutput::from_script(60_000, p2pk(&compressed)),
+            derived.clone(),
+        ],
+    };
+    let chain = MemoryChain(vec![block(0, vec![]), block(1, vec![funding])]);
+    wallet.rescan(&chain, 0).unwrap();
+    let mut utxos = wallet.utxos().clone();
+    utxos.sort_by_key(|utxo| utxo.outpoint().index());
+    assert_eq!(
+        vec![50_000, 60_000],
+        utxos.iter().map(|utxo| utxo.value()).collect::<Vec<i64>>()
+    );
+
+    // a derived key signs its bare public key too
+    let mut inputs: Vec<TransactionInput> = utxos.iter().map(|utxo| utxo.to_input()).collect();
+    inputs.push(TransactionInput::new(derived, "11".repeat(32), 2));
+    let recipient = Script::from_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", Network::Mainnet)
+        .unwrap()
+        .into_bytes();
+    let unsigned = Transaction::new(
+        TransactionType::Pay2PubKey,
+        inputs,
+        vec![TransactionOutput::from_script(170_000, recipient)],
+        None,
+    );
+    let mut tx = unsigned.clone();
+    assert_eq!(3, wallet.sign_transaction(&mut tx).unwrap());
+
+    // each signature script is the signature alone, valid for the key of its output
+    let mut checked = unsigned;
+    for (index, input) in tx.inputs().iter().enumerate() {
+        let script = input.signature_script();
+        assert_eq!(script[0] as usize, script.len() - 1);
+        assert_eq!(0x30, script[1]);
+        assert!(input.witness().is_empty());
+        let pk_script = checked.inputs()[index].utxo_pk_script().to_vec();
+        checked
+            .set_signature(index, &script[1..], &pk_script[1..pk_script.len() - 1])
+            .unwrap();
+    }
+    assert_eq!(tx.to_hex(), checked.to_hex());
+}
+
+#[test]
+pub fn test_key_chain_hardened_index() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let wallet = Wallet::restore(
+        mnemonic.clone(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+
+    // the key chain starts at the first index of the hardened range, m/0'/1
+    let master = Key::new(mnemonic, Network::Mainnet, true).unwrap();
+    let hardened = master
+        .derive_child_private_key(ChildNumber::Hardened(0))
+        .unwrap();
+    let keys = wallet.keys();
+    assert_eq!(Some(ChildNumber::Hardened(0)), keys[1].data.index);
+    assert_eq!(Some(0), keys[1].parent());
+    assert_eq!(hardened, keys[1].data.private_key);
+    assert_eq!(Some(ChildNumber::Normal(1)), keys[2].data.index);
+    assert_eq!(
+        hardened
+            .derive_child_private_key(ChildNumber::Normal(1))
+            .unwrap(),
+        keys[2].data.private_key
+    );
+}
+
+#[test]
+pub fn test_find_keys() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let addresses: Vec<String> = (0..20)
+        .map(|_| wallet.new_receive_address(account).unwrap())
+        .collect();
+    let wif = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    let imported = wallet.import_wif(wif, None).unwrap();
+    let uncompressed = Key::from_wif(wif.to_string())
+        .unwrap()
+        .new_public_key()
+        .unwrap();
+
+    let check = |wallet: &Wallet| {
+        for address in addresses.iter().chain([&imported]) {
+            let script = Script::from_address(address, Network::Mainnet).unwrap();
+            let node = wallet.find_by_script(script.as_bytes()).unwrap();
+            assert_eq!(address, &node.key);
+            let node = wallet.find_by_pubkey(&node.data.public_key).unwrap();
+            assert_eq!(address, &node.key);
+        }
+        // a key is found by its public key in either form
+        let compressed = compress_public_key(&uncompressed).unwrap();
+        for public_key in [&uncompressed, &compressed] {
+            assert_eq!(imported, wallet.find_by_pubkey(public_key).unwrap().key);
+        }
+        let fingerprint = key_fingerprint(&uncompressed).unwrap();
+        let found = wallet.find_by_fingerprint(fingerprint);
+        assert_eq!(1, found.len());
+        assert_eq!(imported, found[0].key);
+
+        assert!(wallet.find_by_script(&[0x6a]).is_none());
+        assert!(wallet.find_by_pubkey(&[2; 33]).is_none());
+        assert!(wallet.find_by_fingerprint([0; 4]).is_empty());
+    };
+    check(&wallet);
+
+    // a wallet deserialized on its own finds its keys too, and indexes
+    // them again once a key is added
+    let mut copy: Wallet = serde_json::from_str(&serde_json::to_string(&wallet).unwrap()).unwrap();
+    check(&copy);
+    let address = copy.new_receive_address(account).unwrap();
+    check(&copy);
+    let script = Script::from_address(&address, Network::Mainnet).unwrap();
+    assert_eq!(address, copy.find_by_script(script.as_bytes()).unwrap().key);
+}
+
+#[test]
+pub fn test_send_many_subtract_fee() {
+    let mnemonic = String::from(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset",
+    );
+    let mut wallet = Wallet::restore(
+        mnemonic,
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    let receive = wallet.new_receive_address(account).unwrap();
+    let script = Script::from_address(&receive, Network::Mainnet).unwrap();
+    let funding = BlockTransaction {
+        tx_id: "11".repeat(32),
+        inputs: vec![OutPoint::new("00".repeat(32), 0)],
+        outputs: vec![TransactionOutput::from_script(100_000, script.into_bytes())],
+    };
+    wallet
+        .rescan(
+            &MemoryChain(vec![block(0, vec![]), block(1, vec![funding])]),
+            0,
+        )
+        .unwrap();
+    let exchange = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    let other = "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2";
+    let paid = |tx: &Transaction, address: &str| {
+        let script = Script::from_address(address, Network::Mainnet).unwrap();
+        tx.outputs()
+            .iter()
+            .find(|output| output.pk_script() == script.as_bytes())
+            .map(|output| output.value())
+    };
+
+    // the whole balance withdrawn, the fee out of it and no change
+    let tx = wallet
+        .send_many(
+            account,
+            &[Recipient::new(exchange, 100_000).subtracting_fee()],
+            5,
+        )
+        .unwrap();
+    assert_eq!(1, tx.outputs().len());
+    assert!(tx.fee() > 0);
+    assert_eq!(Some(100_000 - tx.fee()), paid(&tx, exchange));
+
+    // one recipient pays the fee, the other gets its amount and the change what's left
+    let tx = wallet
+        .send_many(
+            account,
+            &[
+                Recipient::new(exchange, 30_000).subtracting_fee(),
+                Recipient::new(other, 20_000),
+            ],
+            5,
+        )
+        .unwrap();
+    assert_eq!(3, tx.outputs().len());
+    assert_eq!(Some(30_000 - tx.fee()), paid(&tx, exchange));
+    assert_eq!(Some(20_000), paid(&tx, other));
+    let outputs = tx.outputs();
+    let change = outputs
+        .iter()
+        .find(|output| wallet.is_change(output.pk_script()))
+        .unwrap();
+    assert_eq!(50_000, change.value());
+
+    // the same payment without subtracting takes the fee from the change
+    let tx = wallet
+        .new_transaction(
+            account,
+            &[(exchange.to_string(), 30_000), (other.to_string(), 20_000)],
+            5,
+        )
+        .unwrap();
+    assert_eq!(Some(30_000), paid(&tx, exchange));
+
+    // a fee larger than what leaves a recipient over the dust limit
+    assert!(matches!(
+        wallet.send_many(
+            account,
+            &[Recipient::new(exchange, 600).subtracting_fee()],
+            5
+        ),
+        Err(WalletError::Transaction(
+            TransactionError::FeeExceedsRecipients(_)
+        ))
+    ));
+}
+
+#[test]
+pub fn test_verify_signed_transactions() {
+    let mut wallet = Wallet::restore(
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string(),
+        Network::Mainnet,
+        true,
+        PathBuf::from("/tmp"),
+        false,
+    )
+    .unwrap();
+    let account_types = [
+        AccountType::Legacy,
+        AccountType::NestedSegwit,
+        AccountType::NativeSegwit,
+    ];
+    let accounts: Vec<u32> = account_types
+        .iter()
+        .map(|account_type| wallet.new_account(*account_type).unwrap())
+        .collect();
+    let funding = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, account)| {
+            let receive = wallet.new_receive_address(*account).unwrap();
+            let script = Script::from_address(&receive, Network::Mainnet).unwrap();
+            BlockTransaction {
+                tx_id: format!("{:02x}", i + 1).repeat(32),
+                inputs: vec![OutPoint::new("00".repeat(32), 0)],
+                outputs: vec![TransactionOutput::from_script(100_000, script.into_bytes())],
+            }
+        })
+        .collect();
+    wallet
+        .rescan(&MemoryChain(vec![block(0, vec![]), block(1, funding)]), 0)
+        .unwrap();
+    let prevouts = |tx: &Transaction, extra: i64| -> Vec<TransactionOutput> {
+        tx.inputs()
+            .iter()
+            .map(|input| {
+                TransactionOutput::from_script(
+                    input.utxo_value() + extra,
+                    input.utxo_pk_script().to_vec(),
+                )
+            })
+            .collect()
+    };
+
+    for (account_type, account) in account_types.iter().zip(accounts) {
+        let mut tx = wallet
+            .send_many(
+                account,
+                &[Recipient::new(
+                    "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                    40_000,
+                )],
+                5,
+            )
+            .unwrap();
+        assert_eq!(
+            Err(VerifyError::UnsignedInput(0)),
+            tx.verify(&prevouts(&tx, 0))
+        );
+        wallet.sign_transaction(&mut tx).unwrap();
+        tx.verify(&prevouts(&tx, 0)).unwrap();
+
+        // only segwit signatures commit to the value spent
+        match account_type {
+            AccountType::Legacy => tx.verify(&prevouts(&tx, 1)).unwrap(),
+            _ => assert_eq!(
+                Err(VerifyError::InvalidSignature(0)),
+                tx.verify(&prevouts(&tx, 1))
+            ),
+        }
+
+        // an output changed after signing
+        let mut outputs = tx.outputs();
+        outputs[0] =
+            TransactionOutput::from_script(outputs[0].value() + 1, outputs[0].pk_script().to_vec());
+        let tampered = Transaction::new(tx.tx_type(), tx.inputs(), outputs, Some(tx.lock_time()));
+        assert_eq!(
+            Err(VerifyError::InvalidSignature(0)),
+            tampered.verify(&prevouts(&tx, 0))
+        );
+
+        // an output of another key, the last byte of its hash flipped
+        let mut other = prevouts(&tx, 0);
+        let mut pk_script = other[0].pk_script().to_vec();
+        let hash_end = match account_type {
+            AccountType::Legacy => pk_script.len() - 3,
+            AccountType::NestedSegwit => pk_script.len() - 2,
+            _ => pk_script.len() - 1,
+        };
+        pk_script[hash_end] ^= 1;
+        other[0] = TransactionOutput::from_script(other[0].value(), pk_script);
+        assert_eq!(Err(VerifyError::ScriptMismatch(0)), tx.verify(&other));
+    }
+}
+
+#[test]
+pub fn test_verify_taproot_key_path() {
+    let key = Key::from_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ".to_string())
+        .unwrap()
+        .derive_app_key("taproot")
+        .unwrap();
+    let mut pk_script = vec![0x51, 0x20];
+    pk_script.extend_from_slice(&key.x_only_public_key());
+    let prevout = TransactionOutput::from_script(100_000, pk_script);
+    let prevouts = [prevout.clone()];
+
+    let mut tx = Transaction::new(
+        TransactionType::Pay2Taproot,
+        vec![TransactionInput::new(prevout.clone(), "11".repeat(32), 0)],
+        vec![TransactionOutput::from_script(
+            90_000,
+            Script::from_address(
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                Network::Mainnet,
+            )
+            .unwrap()
+            .into_bytes(),
+        )],
+        None,
+    );
+    let sighash = tx.sighash(0, &[], SigHashType::TaprootDefault).unwrap();
+    let signature = key.sign_schnorr(&sighash).unwrap();
+
+    // SIGHASH_DEFAULT is implied by a 64 byte signature
+    tx.set_witness(0, vec![signature.clone()]).unwrap();
+    tx.verify(&prevouts).unwrap();
+    tx.set_witness(0, vec![[signature.clone(), vec![0x00]].concat()])
+        .unwrap();
+    assert_eq!(Err(VerifyError::InvalidSignature(0)), tx.verify(&prevouts));
+
+    let sighash = tx.sighash(0, &[], SigHashType::All).unwrap();
+    let signature = [key.sign_schnorr(&sighash).unwrap(), vec![SIGHASH_ALL as u8]].concat();
+    tx.set_witness(0, vec![signature.clone()]).unwrap();
+    tx.verify(&prevouts).unwrap();
+    assert_eq!(
+        Err(VerifyError::InvalidSignature(0)),
+        tx.verify(&[TransactionOutput::from_script(
+            99_999,
+            prevout.pk_script().to_vec()
+        )])
+    );
+
+    // a script path spend, the control block after the script
+    tx.set_witness(0, vec![signature, vec![0x51], vec![0xc0; 33]])
+        .unwrap();
+    assert_eq!(Err(VerifyError::UnsupportedScript(0)), tx.verify(&prevouts));
+}
+
+#[test]
+pub fn test_locked_wallet_sync() {
+    let data_dir = std::env::temp_dir().join("waller_test_locked_wallet_sync");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let restore = |data_dir: PathBuf| {
+        Wallet::restore(
+            "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset"
+                .to_string(),
+            Network::Mainnet,
+            true,
+            data_dir,
+            false,
+        )
+        .unwrap()
+    };
+    let mut wallet = restore(data_dir);
+    let mut unlocked = restore(PathBuf::from("/tmp"));
+    let segwit = wallet.new_account(AccountType::NestedSegwit).unwrap();
+    let taproot = wallet.new_account(AccountType::Taproot).unwrap();
+    unlocked.new_account(AccountType::NestedSegwit).unwrap();
+    unlocked.new_account(AccountType::Taproot).unwrap();
+    let secrets: Vec<String> = wallet
+        .keys()
+        .iter()
+        .flat_map(|node| vec![node.data.private_key.hex(), node.data.private_key.to_wif()])
+        .collect();
+
+    // addresses are derived from public keys while locked, as they would be unlocked
+    wallet.encrypt("passphrase").unwrap();
+    let first = wallet.new_receive_address(segwit).unwrap();
+    assert_eq!(unlocked.new_receive_address(segwit).unwrap(), first);
+    assert!(wallet.get_address(first.clone()).unwrap().is_wiped());
+    wallet.set_label(&first, "savings").unwrap();
+    assert!(matches!(
+        wallet.new_account(AccountType::Legacy),
+        Err(WalletError::Locked)
+    ));
+
+    // the file holds the public data in the clear and only sealed private keys
+    let file = wallet.flush().unwrap();
+    let contents = std::fs::read_to_string(&file).unwrap();
+    assert!(contents.contains(&first));
+    assert!(contents.contains("savings"));
+    for secret in secrets {
+        assert!(!contents.contains(&secret));
+    }
+
+    // addresses are derived from the public keys loaded once they're authenticated
+    let mut loaded = Wallet::from_wallet_file(file).unwrap();
+    assert!(loaded.is_locked());
+    assert!(matches!(
+        loaded.new_change_address(taproot),
+        Err(WalletError::Locked)
+    ));
+    loaded.unlock("passphrase").unwrap();
+    loaded.lock().unwrap();
+    let change = loaded.new_change_address(taproot).unwrap();
+    assert_eq!(unlocked.new_change_address(taproot).unwrap(), change);
+    // an address past those issued is found in the lookahead
+    unlocked.new_receive_address(segwit).unwrap();
+    let ahead = unlocked.new_receive_address(segwit).unwrap();
+
+    let backend = MockBackend::new(Network::Mainnet);
+    backend.fund(&first, 50_000).unwrap();
+    backend.fund(&change, 30_000).unwrap();
+    backend.fund(&ahead, 20_000).unwrap();
+    backend.mine(1);
+    let diff = loaded.sync(&backend).unwrap();
+    assert_eq!(100_000, diff.balance_delta);
+    assert_eq!(70_000, loaded.account_balance(segwit));
+    assert_eq!(30_000, loaded.account_balance(taproot));
+    assert_eq!(Some("savings"), loaded.label(&first));
+
+    // spends are built while locked, with change to a new address, but not signed
+    let mut tx = loaded
+        .send_many(
+            segwit,
+            &[Recipient::new(
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                60_000,
+            )],
+            5,
+        )
+        .unwrap();
+    assert_eq!(2, tx.outputs().len());
+    assert!(matches!(
+        loaded.sign_transaction(&mut tx),
+        Err(WalletError::Locked)
+    ));
+
+    // once unlocked the keys of the addresses derived while locked sign
+    loaded.unlock("passphrase").unwrap();
+    assert!(!loaded.get_address(first.clone()).unwrap().is_wiped());
+    assert!(!loaded.get_address(change).unwrap().is_wiped());
+    assert_eq!(2, loaded.sign_transaction(&mut tx).unwrap());
+    let prevouts: Vec<TransactionOutput> = tx
+        .inputs()
+        .iter()
+        .map(|input| {
+            TransactionOutput::from_script(input.utxo_value(), input.utxo_pk_script().to_vec())
+        })
+        .collect();
+    tx.verify(&prevouts).unwrap();
+
+    // and stay sealed when locked again
+    loaded.lock().unwrap();
+    assert!(loaded.get_address(first).unwrap().is_wiped());
+}
+
+#[test]
+pub fn test_master_key_migration() {
+    let data_dir = std::env::temp_dir().join("waller_test_master_key_migration");
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let mnemonic =
+        "fancy lemon deliver stock castle eye answer palm nerve exchange sibling asset".to_string();
+
+    // new wallets derive their master key as BIP32 does
+    let mut wallet = Wallet::restore(
+        mnemonic.clone(),
+        Network::Mainnet,
+        true,
+        data_dir.clone(),
+        false,
+    )
+    .unwrap();
+    assert_eq!(MasterKeyDerivation::Bip32, wallet.master_key_derivation());
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+    // the first BIP84 address of the mnemonic, as other wallets derive it
+    assert_eq!(
+        "bc1q5lj7yksptgmljr3lzuytgg69gg75ellkwc754j",
+        wallet.new_receive_address(account).unwrap()
+    );
+    let file = wallet.flush().unwrap();
+    let data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert_eq!(WALLET_VERSION as u64, data["version"]);
+    assert_eq!(
+        MasterKeyDerivation::Bip32,
+        Wallet::from_wallet_file(file.clone())
+            .unwrap()
+            .master_key_derivation()
+    );
+
+    // files written before versions keep their keys, derived the legacy way
+    let mut legacy = Wallet::restore_with_derivation(
+        mnemonic.clone(),
+        MasterKeyDerivation::Legacy,
+        Network::Mainnet,
+        true,
+        data_dir,
+        false,
+    )
+    .unwrap();
+    let account = legacy.new_account(AccountType::NativeSegwit).unwrap();
+    let address = legacy.new_receive_address(account).unwrap();
+    assert_ne!("bc1q5lj7yksptgmljr3lzuytgg69gg75ellkwc754j", address);
+    assert_eq!(
+        address,
+        legacy
+            .verify_address_with_mnemonic(&mnemonic, account, Chain::External, 0)
+            .unwrap()
+    );
+    let file = legacy.flush().unwrap();
+    let mut data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    let object = data.as_object_mut().unwrap();
+    object.remove("version");
+    object.remove("master_key_derivation");
+    seal_wallet_file(&mut data, None);
+    std::fs::write(&file, data.to_string()).unwrap();
+
+    let mut loaded = Wallet::from_wallet_file(file.clone()).unwrap();
+    assert_eq!(MasterKeyDerivation::Legacy, loaded.master_key_derivation());
+    assert!(loaded.get_address(address.clone()).is_some());
+    let (recovered, report) = Wallet::from_wallet_file_lenient(file.clone()).unwrap();
+    assert!(report.is_clean(), "{:?}", report);
+    assert_eq!(
+        MasterKeyDerivation::Legacy,
+        recovered.master_key_derivation()
+    );
+
+    // the migrated wallet is written with the current version and stays legacy
+    loaded.set_label(&address, "old").unwrap();
+    loaded.flush().unwrap();
+    let data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert_eq!(WALLET_VERSION as u64, data["version"]);
+    assert_eq!(
+        MasterKeyDerivation::Legacy,
+        Wallet::from_wallet_file(file)
+            .unwrap()
+            .master_key_derivation()
+    );
+}
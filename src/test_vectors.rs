@@ -0,0 +1,857 @@
+//! Official test vectors from the BIPs waller implements
+//! These are compiled into the test build and behind the `test-vectors`
+//! feature so downstream crates can run the same conformance checks.
+//! The harness that runs waller against them lives in `test::vectors_test`
+
+/// The passphrase used by every BIP39 reference vector
+pub const BIP39_PASSPHRASE: &str = "TREZOR";
+
+/// A BIP39 vector. The seed is derived using [BIP39_PASSPHRASE]
+#[derive(Debug, Clone, Copy)]
+pub struct Bip39Vector {
+    pub entropy: &'static str,
+    pub mnemonic: &'static str,
+    pub seed: &'static str,
+}
+
+/// A single derivation path within a BIP32 vector
+#[derive(Debug, Clone, Copy)]
+pub struct Bip32Chain {
+    pub path: &'static str,
+    pub xprv: &'static str,
+    pub xpub: &'static str,
+}
+
+/// A BIP32 vector, every chain is derived from the seed
+#[derive(Debug, Clone, Copy)]
+pub struct Bip32Vector {
+    pub seed: &'static str,
+    pub chains: &'static [Bip32Chain],
+}
+
+/// An address derived from a mnemonic at a full derivation path
+#[derive(Debug, Clone, Copy)]
+pub struct AddressVector {
+    pub mnemonic: &'static str,
+    pub path: &'static str,
+    pub address: &'static str,
+}
+
+/// A BIP143 segwit v0 signature hash vector
+#[derive(Debug, Clone, Copy)]
+pub struct Bip143Vector {
+    pub unsigned_tx: &'static str,
+    pub input_index: usize,
+    /// the script code of the input being signed, without its length prefix
+    pub script_code: &'static str,
+    pub amount: u64,
+    pub sighash_type: u32,
+    pub hash_prevouts: &'static str,
+    pub hash_sequence: &'static str,
+    pub hash_outputs: &'static str,
+    pub sighash: &'static str,
+}
+
+/// A BIP341 taproot key path signature hash vector
+#[derive(Debug, Clone, Copy)]
+pub struct Bip341Vector {
+    pub tx: &'static str,
+    /// every output spent by the transaction, serialized as a vector of outputs
+    pub prevouts: &'static str,
+    pub input_index: usize,
+    pub sighash_type: u8,
+    pub annex: Option<&'static str>,
+    pub sighash: &'static str,
+}
+
+/// Vectors from the reference implementation (trezor/python-mnemonic)
+pub const BIP39_VECTORS: &[Bip39Vector] = &[
+    Bip39Vector {
+        entropy: "00000000000000000000000000000000",
+        mnemonic: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        seed: "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+    },
+    Bip39Vector {
+        entropy: "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+        mnemonic: "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        seed: "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+    },
+    Bip39Vector {
+        entropy: "80808080808080808080808080808080",
+        mnemonic: "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+        seed: "d71de856f81a8acc65e6fc851a38d4d7ec216fd0796d0a6827a3ad6ed5511a30fa280f12eb2e47ed2ac03b5c462a0358d18d69fe4f985ec81778c1b370b652a8",
+    },
+    Bip39Vector {
+        entropy: "ffffffffffffffffffffffffffffffff",
+        mnemonic: "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+        seed: "ac27495480225222079d7be181583751e86f571027b0497b5b5d11218e0a8a13332572917f0f8e5a589620c6f15b11c61dee327651a14c34e18231052e48c069",
+    },
+    Bip39Vector {
+        entropy: "000000000000000000000000000000000000000000000000",
+        mnemonic: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon agent",
+        seed: "035895f2f481b1b0f01fcf8c289c794660b289981a78f8106447707fdd9666ca06da5a9a565181599b79f53b844d8a71dd9f439c52a3d7b3e8a79c906ac845fa",
+    },
+    Bip39Vector {
+        entropy: "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+        mnemonic: "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will",
+        seed: "f2b94508732bcbacbcc020faefecfc89feafa6649a5491b8c952cede496c214a0c7b3c392d168748f2d4a612bada0753b52a1c7ac53c1e93abd5c6320b9e95dd",
+    },
+    Bip39Vector {
+        entropy: "808080808080808080808080808080808080808080808080",
+        mnemonic: "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter always",
+        seed: "107d7c02a5aa6f38c58083ff74f04c607c2d2c0ecc55501dadd72d025b751bc27fe913ffb796f841c49b1d33b610cf0e91d3aa239027f5e99fe4ce9e5088cd65",
+    },
+    Bip39Vector {
+        entropy: "ffffffffffffffffffffffffffffffffffffffffffffffff",
+        mnemonic: "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo when",
+        seed: "0cd6e5d827bb62eb8fc1e262254223817fd068a74b5b449cc2f667c3f1f985a76379b43348d952e2265b4cd129090758b3e3c2c49103b5051aac2eaeb890a528",
+    },
+    Bip39Vector {
+        entropy: "0000000000000000000000000000000000000000000000000000000000000000",
+        mnemonic: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+        seed: "bda85446c68413707090a52022edd26a1c9462295029f2e60cd7c4f2bbd3097170af7a4d73245cafa9c3cca8d561a7c3de6f5d4a10be8ed2a5e608d68f92fcc8",
+    },
+    Bip39Vector {
+        entropy: "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+        mnemonic: "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title",
+        seed: "bc09fca1804f7e69da93c2f2028eb238c227f2e9dda30cd63699232578480a4021b146ad717fbb7e451ce9eb835f43620bf5c514db0f8add49f5d121449d3e87",
+    },
+    Bip39Vector {
+        entropy: "8080808080808080808080808080808080808080808080808080808080808080",
+        mnemonic: "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+        seed: "c0c519bd0e91a2ed54357d9d1ebef6f5af218a153624cf4f2da911a0ed8f7a09e2ef61af0aca007096df430022f7a2b6fb91661a9589097069720d015e4e982f",
+    },
+    Bip39Vector {
+        entropy: "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        mnemonic: "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+        seed: "dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad",
+    },
+    Bip39Vector {
+        entropy: "9e885d952ad362caeb4efe34a8e91bd2",
+        mnemonic: "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+        seed: "274ddc525802f7c828d8ef7ddbcdc5304e87ac3535913611fbbfa986d0c9e5476c91689f9c8a54fd55bd38606aa6a8595ad213d4c9c9f9aca3fb217069a41028",
+    },
+    Bip39Vector {
+        entropy: "6610b25967cdcca9d59875f5cb50b0ea75433311869e930b",
+        mnemonic: "gravity machine north sort system female filter attitude volume fold club stay feature office ecology stable narrow fog",
+        seed: "628c3827a8823298ee685db84f55caa34b5cc195a778e52d45f59bcf75aba68e4d7590e101dc414bc1bbd5737666fbbef35d1f1903953b66624f910feef245ac",
+    },
+    Bip39Vector {
+        entropy: "68a79eaca2324873eacc50cb9c6eca8cc68ea5d936f98787c60c7ebc74e6ce7c",
+        mnemonic: "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+        seed: "64c87cde7e12ecf6704ab95bb1408bef047c22db4cc7491c4271d170a1b213d20b385bc1588d9c7b38f1b39d415665b8a9030c9ec653d75e65f847d8fc1fc440",
+    },
+    Bip39Vector {
+        entropy: "c0ba5a8e914111210f2bd131f3d5e08d",
+        mnemonic: "scheme spot photo card baby mountain device kick cradle pact join borrow",
+        seed: "ea725895aaae8d4c1cf682c1bfd2d358d52ed9f0f0591131b559e2724bb234fca05aa9c02c57407e04ee9dc3b454aa63fbff483a8b11de949624b9f1831a9612",
+    },
+    Bip39Vector {
+        entropy: "6d9be1ee6ebd27a258115aad99b7317b9c8d28b6d76431c3",
+        mnemonic: "horn tenant knee talent sponsor spell gate clip pulse soap slush warm silver nephew swap uncle crack brave",
+        seed: "fd579828af3da1d32544ce4db5c73d53fc8acc4ddb1e3b251a31179cdb71e853c56d2fcb11aed39898ce6c34b10b5382772db8796e52837b54468aeb312cfc3d",
+    },
+    Bip39Vector {
+        entropy: "9f6a2878b2520799a44ef18bc7df394e7061a224d2c33cd015b157d746869863",
+        mnemonic: "panda eyebrow bullet gorilla call smoke muffin taste mesh discover soft ostrich alcohol speed nation flash devote level hobby quick inner drive ghost inside",
+        seed: "72be8e052fc4919d2adf28d5306b5474b0069df35b02303de8c1729c9538dbb6fc2d731d5f832193cd9fb6aeecbc469594a70e3dd50811b5067f3b88b28c3e8d",
+    },
+    Bip39Vector {
+        entropy: "23db8160a31d3e0dca3688ed941adbf3",
+        mnemonic: "cat swing flag economy stadium alone churn speed unique patch report train",
+        seed: "deb5f45449e615feff5640f2e49f933ff51895de3b4381832b3139941c57b59205a42480c52175b6efcffaa58a2503887c1e8b363a707256bdd2b587b46541f5",
+    },
+    Bip39Vector {
+        entropy: "8197a4a47f0425faeaa69deebc05ca29c0a5b5cc76ceacc0",
+        mnemonic: "light rule cinnamon wrap drastic word pride squirrel upgrade then income fatal apart sustain crack supply proud access",
+        seed: "4cbdff1ca2db800fd61cae72a57475fdc6bab03e441fd63f96dabd1f183ef5b782925f00105f318309a7e9c3ea6967c7801e46c8a58082674c860a37b93eda02",
+    },
+    Bip39Vector {
+        entropy: "066dca1a2bb7e8a1db2832148ce9933eea0f3ac9548d793112d9a95c9407efad",
+        mnemonic: "all hour make first leader extend hole alien behind guard gospel lava path output census museum junior mass reopen famous sing advance salt reform",
+        seed: "26e975ec644423f4a4c4f4215ef09b4bd7ef924e85d1d17c4cf3f136c2863cf6df0a475045652c57eb5fb41513ca2a2d67722b77e954b4b3fc11f7590449191d",
+    },
+    Bip39Vector {
+        entropy: "f30f8c1da665478f49b001d94c5fc452",
+        mnemonic: "vessel ladder alter error federal sibling chat ability sun glass valve picture",
+        seed: "2aaa9242daafcee6aa9d7269f17d4efe271e1b9a529178d7dc139cd18747090bf9d60295d0ce74309a78852a9caadf0af48aae1c6253839624076224374bc63f",
+    },
+    Bip39Vector {
+        entropy: "c10ec20dc3cd9f652c7fac2f1230f7a3c828389a14392f05",
+        mnemonic: "scissors invite lock maple supreme raw rapid void congress muscle digital elegant little brisk hair mango congress clump",
+        seed: "7b4a10be9d98e6cba265566db7f136718e1398c71cb581e1b2f464cac1ceedf4f3e274dc270003c670ad8d02c4558b2f8e39edea2775c9e232c7cb798b069e88",
+    },
+    Bip39Vector {
+        entropy: "f585c11aec520db57dd353c69554b21a89b20fb0650966fa0a9d6f74fd989d8f",
+        mnemonic: "void come effort suffer camp survey warrior heavy shoot primary clutch crush open amazing screen patrol group space point ten exist slush involve unfold",
+        seed: "01f5bced59dec48e362f2c45b5de68b9fd6c92c6634f44d6d40aab69056506f0e35524a518034ddc1192e1dacd32c1ed3eaa3c3b131c88ed8e7e54c49a5d0998",
+    },
+];
+
+/// Test vectors 1, 2 and 3 from BIP32
+pub const BIP32_VECTORS: &[Bip32Vector] = &[
+    Bip32Vector {
+        seed: "000102030405060708090a0b0c0d0e0f",
+        chains: &[
+            Bip32Chain {
+                path: "m",
+                xprv: "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+                xpub: "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+            },
+            Bip32Chain {
+                path: "m/0h",
+                xprv: "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+                xpub: "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+            },
+            Bip32Chain {
+                path: "m/0h/1",
+                xprv: "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+                xpub: "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+            },
+            Bip32Chain {
+                path: "m/0h/1/2h",
+                xprv: "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM",
+                xpub: "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5",
+            },
+            Bip32Chain {
+                path: "m/0h/1/2h/2",
+                xprv: "xprvA2JDeKCSNNZky6uBCviVfJSKyQ1mDYahRjijr5idH2WwLsEd4Hsb2Tyh8RfQMuPh7f7RtyzTtdrbdqqsunu5Mm3wDvUAKRHSC34sJ7in334",
+                xpub: "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV",
+            },
+            Bip32Chain {
+                path: "m/0h/1/2h/2/1000000000",
+                xprv: "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+                xpub: "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+            },
+        ],
+    },
+    Bip32Vector {
+        seed: "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542",
+        chains: &[
+            Bip32Chain {
+                path: "m",
+                xprv: "xprv9s21ZrQH143K31xYSDQpPDxsXRTUcvj2iNHm5NUtrGiGG5e2DtALGdso3pGz6ssrdK4PFmM8NSpSBHNqPqm55Qn3LqFtT2emdEXVYsCzC2U",
+                xpub: "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB",
+            },
+            Bip32Chain {
+                path: "m/0",
+                xprv: "xprv9vHkqa6EV4sPZHYqZznhT2NPtPCjKuDKGY38FBWLvgaDx45zo9WQRUT3dKYnjwih2yJD9mkrocEZXo1ex8G81dwSM1fwqWpWkeS3v86pgKt",
+                xpub: "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH",
+            },
+            Bip32Chain {
+                path: "m/0/2147483647h",
+                xprv: "xprv9wSp6B7kry3Vj9m1zSnLvN3xH8RdsPP1Mh7fAaR7aRLcQMKTR2vidYEeEg2mUCTAwCd6vnxVrcjfy2kRgVsFawNzmjuHc2YmYRmagcEPdU9",
+                xpub: "xpub6ASAVgeehLbnwdqV6UKMHVzgqAG8Gr6riv3Fxxpj8ksbH9ebxaEyBLZ85ySDhKiLDBrQSARLq1uNRts8RuJiHjaDMBU4Zn9h8LZNnBC5y4a",
+            },
+            Bip32Chain {
+                path: "m/0/2147483647h/1",
+                xprv: "xprv9zFnWC6h2cLgpmSA46vutJzBcfJ8yaJGg8cX1e5StJh45BBciYTRXSd25UEPVuesF9yog62tGAQtHjXajPPdbRCHuWS6T8XA2ECKADdw4Ef",
+                xpub: "xpub6DF8uhdarytz3FWdA8TvFSvvAh8dP3283MY7p2V4SeE2wyWmG5mg5EwVvmdMVCQcoNJxGoWaU9DCWh89LojfZ537wTfunKau47EL2dhHKon",
+            },
+            Bip32Chain {
+                path: "m/0/2147483647h/1/2147483646h",
+                xprv: "xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc",
+                xpub: "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL",
+            },
+            Bip32Chain {
+                path: "m/0/2147483647h/1/2147483646h/2",
+                xprv: "xprvA2nrNbFZABcdryreWet9Ea4LvTJcGsqrMzxHx98MMrotbir7yrKCEXw7nadnHM8Dq38EGfSh6dqA9QWTyefMLEcBYJUuekgW4BYPJcr9E7j",
+                xpub: "xpub6FnCn6nSzZAw5Tw7cgR9bi15UV96gLZhjDstkXXxvCLsUXBGXPdSnLFbdpq8p9HmGsApME5hQTZ3emM2rnY5agb9rXpVGyy3bdW6EEgAtqt",
+            },
+        ],
+    },
+    Bip32Vector {
+        seed: "4b381541583be4423346c643850da4b320e46a87ae3d2a4e6da11eba819cd4acba45d239319ac14f863b8d5ab5a0d0c64d2e8a1e7d1457df2e5a3c51c73235be",
+        chains: &[
+            Bip32Chain {
+                path: "m",
+                xprv: "xprv9s21ZrQH143K25QhxbucbDDuQ4naNntJRi4KUfWT7xo4EKsHt2QJDu7KXp1A3u7Bi1j8ph3EGsZ9Xvz9dGuVrtHHs7pXeTzjuxBrCmmhgC6",
+                xpub: "xpub661MyMwAqRbcEZVB4dScxMAdx6d4nFc9nvyvH3v4gJL378CSRZiYmhRoP7mBy6gSPSCYk6SzXPTf3ND1cZAceL7SfJ1Z3GC8vBgp2epUt13",
+            },
+            Bip32Chain {
+                path: "m/0h",
+                xprv: "xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L",
+                xpub: "xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y",
+            },
+        ],
+    },
+];
+
+/// The mnemonic shared by the BIP44, 49, 84 and 86 address vectors
+pub const ADDRESS_VECTOR_MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+/// BIP44 P2PKH addresses
+pub const BIP44_VECTORS: &[AddressVector] = &[
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/44'/0'/0'/0/0",
+        address: "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA",
+    },
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/44'/0'/0'/0/1",
+        address: "1Ak8PffB2meyfYnbXZR9EGfLfFZVpzJvQP",
+    },
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/44'/0'/0'/1/0",
+        address: "1J3J6EvPrv8q6AC3VCjWV45Uf3nssNMRtH",
+    },
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/44'/1'/0'/0/0",
+        address: "mkpZhYtJu2r87Js3pDiWJDmPte2NRZ8bJV",
+    },
+];
+
+/// BIP49 P2SH-P2WPKH addresses, the testnet one is the example from BIP49
+pub const BIP49_VECTORS: &[AddressVector] = &[
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/49'/0'/0'/0/0",
+        address: "37VucYSaXLCAsxYyAPfbSi9eh4iEcbShgf",
+    },
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/49'/0'/0'/0/1",
+        address: "3LtMnn87fqUeHBUG414p9CWwnoV6E2pNKS",
+    },
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/49'/1'/0'/0/0",
+        address: "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2",
+    },
+];
+
+/// BIP84 P2WPKH addresses
+pub const BIP84_VECTORS: &[AddressVector] = &[
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/84'/0'/0'/0/0",
+        address: "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu",
+    },
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/84'/0'/0'/0/1",
+        address: "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g",
+    },
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/84'/0'/0'/1/0",
+        address: "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el",
+    },
+];
+
+/// BIP86 single key P2TR addresses
+pub const BIP86_VECTORS: &[AddressVector] = &[
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/86'/0'/0'/0/0",
+        address: "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+    },
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/86'/0'/0'/0/1",
+        address: "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh",
+    },
+    AddressVector {
+        mnemonic: ADDRESS_VECTOR_MNEMONIC,
+        path: "m/86'/0'/0'/1/0",
+        address: "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7",
+    },
+];
+
+/// The native P2WPKH and P2SH-P2WPKH examples from BIP143
+pub const BIP143_VECTORS: &[Bip143Vector] = &[
+    Bip143Vector {
+        unsigned_tx: "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+        input_index: 1,
+        script_code: "76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac",
+        amount: 600_000_000,
+        sighash_type: 0x01,
+        hash_prevouts: "96b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd37",
+        hash_sequence: "52b0a642eea2fb7ae638c36f6252b6750293dbe574a806984b8e4d8548339a3b",
+        hash_outputs: "863ef3e1a92afbfdb97f31ad0fc7683ee943e9abcf2501590ff8f6551f47e5e5",
+        sighash: "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670",
+    },
+    Bip143Vector {
+        unsigned_tx: "0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000",
+        input_index: 0,
+        script_code: "76a91479091972186c449eb1ded22b78e40d009bdf008988ac",
+        amount: 1_000_000_000,
+        sighash_type: 0x01,
+        hash_prevouts: "b0287b4a252ac05af83d2dcef00ba313af78a3e9c329afa216eb3aa2a7b4613a",
+        hash_sequence: "18606b350cd8bf565266bc352f0caddcf01e8fa789dd8a15386327cf8cabe198",
+        hash_outputs: "de984f44532e2173ca0d64314fcefe6d30da6f8cf27bafa706da61df8a226c83",
+        sighash: "64f3b0f4dd2bb3aa1ce8566d220cc74dda9df97d8490cc81d89d735c92e59fb6",
+    },
+];
+
+/// Taproot key path signature hashes, one per sighash type plus an annex case.
+/// These come from the Bitcoin Core functional test framework
+pub const BIP341_VECTORS: &[Bip341Vector] = &[
+    Bip341Vector {
+        tx: "020000000164eb050a5e3da0c2a65e4786f26d753b7bc69691fabccafb11f7acef36641f1846010000003101b2b404392a22000000000017a9147f2bde86fe78bf68a0544a4f290e12f0b7e0a08c87580200000000000017a91425d11723074ecfb96a0a83c3956bfaf362ae0c908758020000000000001600147e20f938993641de67bb0cdd71682aa34c4d29ad5802000000000000160014c64984dc8761acfa99418bd6bedc79b9287d652d72000000",
+        prevouts: "01365724000000000023542156b39dab4f8f3508e0432cfb41fab110170acaa2d4c42539cb90a4dc7c093bc500",
+        input_index: 0,
+        sighash_type: 0x00,
+        annex: None,
+        sighash: "33ca0ebfb4a945eeee9569fc0f5040221275f88690b7f8592ada88ce3bdf6703",
+    },
+    Bip341Vector {
+        tx: "0200000002fff49be59befe7566050737910f6ccdc5e749c7f8860ddc140386463d88c5ad0f3000000002cf68eb4a3d67f9d4c079249f7e4f27b8854815cb1ed13842d4fbf395f9e217fd605ee24090100000065235d9203f458520000000000160014b6d48333bb13b4c644e57c43a9a26df3a44b785e58020000000000001976a914eea9461a9e1e3f765d3af3e726162e0229fe3eb688ac58020000000000001976a9143a8869c9f2b5ea1d4ff3aeeb6a8fb2fffb1ad5fe88ac0ad7125c",
+        prevouts: "02591f220000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece48fb310000000000225120f25ad35583ea31998d968871d7de1abd2a52f6fe4178b54ea158274806ff4ece",
+        input_index: 1,
+        sighash_type: 0x01,
+        annex: None,
+        sighash: "626ab955d58c9a8a600a0c580549d06dc7da4e802eb2a531f62a588e430967a8",
+    },
+    Bip341Vector {
+        tx: "0200000001350005f65aa830ced2079df348e2d8c2bdb4f10e2dde6a161d8a07b40d1ad87dae000000001611d0d603d9dc0e000000000017a914459b6d7d6bbb4d8837b4bf7e9a4556f952da2f5c8758020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88ac58020000000000001976a9141dd70e1299ffc2d5b51f6f87de9dfe9398c33cbb88aca71c1f4f",
+        prevouts: "01c4811000000000002251201bf9297d0a2968ae6693aadd0fa514717afefd218087a239afb7418e2d22e65c",
+        input_index: 0,
+        sighash_type: 0x81,
+        annex: None,
+        sighash: "dfa9437f9c9a1d1f9af271f79f2f5482f287cdb0d2e03fa92c8a9b216cc6061c",
+    },
+    Bip341Vector {
+        tx: "020000000185bed1a6da2bffbd60ec681a1bfb71c5111d6395b99b3f8b2bf90167111bcb18f5010000007c83ace802ded24a00000000001600142c4698f9f7a773866879755aa78c516fb332af8e5802000000000000160014d38639dfbac4259323b98a472405db0c461b31fa61073747",
+        prevouts: "0144c84d0000000000225120e3f2107989c88e67296ab2faca930efa2e3a5bd3ff0904835a11c9e807458621",
+        input_index: 0,
+        sighash_type: 0x02,
+        annex: None,
+        sighash: "3129de36a5d05fff97ffca31eb75fcccbbbc27b3147a7a36a9e4b45d8b625067",
+    },
+    Bip341Vector {
+        tx: "eb93dbb901028c8515589dac980b6e7f8e4088b77ed866ca0d6d210a7218b6fd0f6b22dd6d7300000000eb4740a9047efc0e0000000000160014913da2128d8fcf292b3691db0e187414aa1783825802000000000000160014913da2128d8fcf292b3691db0e187414aa178382580200000000000017a9143dd27f01c6f7ef9bb9159937b17f17065ed01a0c875802000000000000160014d7630e19df70ada9905ede1722b800c0005f246641000000",
+        prevouts: "013fed110000000000225120eb536ae8c33580290630fc495046e998086a64f8f33b93b07967d9029b265c55",
+        input_index: 0,
+        sighash_type: 0x82,
+        annex: None,
+        sighash: "2441e8b0e063a2083ee790f14f2045022f07258ddde5ee01de543c9e789d80ae",
+    },
+    Bip341Vector {
+        tx: "02000000017836b409a5fed32211407e44b971591f2032053f14701fb5b3a30c0ff382f2cc9c0100000061ac55f60288fb5600000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ac58020000000000001976a9144ea02f6f182b082fb6ce47e36bbde390b6a41b5088ace4000000",
+        prevouts: "01efa558000000000022512007071ea3dc7e331b0687d0193d1e6d6ed10e645ef36f10ef8831d5e522ac9e80",
+        input_index: 0,
+        sighash_type: 0x03,
+        annex: None,
+        sighash: "30239345177cadd0e3ea413d49803580abb6cb27971b481b7788a78d35117a88",
+    },
+    Bip341Vector {
+        tx: "0100000001aa6deae89d5e0aaca58714fc76ef6f3c8284224888089232d4e663843ed3ab3eae010000008b6657a60450cb4c0000000000160014a3d42b5413ef0c0701c4702f3cd7d4df222c147058020000000000001976a91430b4ed8723a4ee8992aa2c8814cfe5c3ad0ab9d988ac5802000000000000160014365b1166a6ed0a5e8e9dff17a6d00bbb43454bc758020000000000001976a914bc98c51a84fe7fad5dc380eb8b39586eff47241688ac4f313247",
+        prevouts: "0107af4e00000000002251202c36d243dfc06cb56a248e62df27ecba7417307511a81ae61aa41c597a929c69",
+        input_index: 0,
+        sighash_type: 0x83,
+        annex: None,
+        sighash: "bf9c83f26c6dd16449e4921f813f551c4218e86f2ec906ca8611175b41b566df",
+    },
+    Bip341Vector {
+        tx: "0200000001df8123752e8f37d132c4e9f1ff7e4f9b986ade9211267e9ebd5fd22a5e718dec6d01000000ce4023b903cb7b23000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787580200000000000017a914afd0d512a2c5c2b40e25669e9cc460303c325b8b87580200000000000017a914a18b36ea7a094db2f4940fc09edf154e86de7bd787f6020000",
+        prevouts: "01ea49260000000000225120ab5e9800806bf18cb246edcf5fe63441208fe955a4b5a35bbff65f5db622a010",
+        input_index: 0,
+        sighash_type: 0x83,
+        annex: Some("507b979802e62d397acb29f56743a791894b99372872fc5af06a4f6e8d242d0615cda53062bb20e6ec79756fe39183f0c128adfe85559a8fa042b042c018aa8010143799e44f0893c40e1e"),
+        sighash: "3b003000add359a364a156e73e02846782a59d0d95ca8c4638aaad99f2ef915c",
+    },
+];
+
+/// A BIP38 key encrypted without EC multiplication
+#[derive(Debug, Clone, Copy)]
+pub struct Bip38Vector {
+    pub passphrase: &'static str,
+    pub encrypted: &'static str,
+    pub wif: &'static str,
+}
+
+/// The vectors without EC multiplication from BIP38
+pub const BIP38_VECTORS: &[Bip38Vector] = &[
+    Bip38Vector {
+        passphrase: "TestingOneTwoThree",
+        encrypted: "6PRVWUbkzzsbcVac2qwfssoUJAN1Xhrg6bNk8J7Nzm5H7kxEbn2Nh2ZoGg",
+        wif: "5KN7MzqK5wt2TP1fQCYyHBtDrXdJuXbUzm4A9rKAteGu3Qi5CVR",
+    },
+    Bip38Vector {
+        passphrase: "Satoshi",
+        encrypted: "6PRNFFkZc2NZ6dJqFfhRoFNMR9Lnyj7dYGrzdgXXVMXcxoKTePPX1dWByq",
+        wif: "5HtasZ6ofTHP6HCwTqTkLDuLQisYPah7aUnSKfC7h4hMUVw2gi5",
+    },
+    Bip38Vector {
+        passphrase: "TestingOneTwoThree",
+        encrypted: "6PYNKZ1EAgYgmQfmNVamxyXVWHzK5s6DGhwP4J5o44cvXdoY7sRzhtpUeo",
+        wif: "L44B5gGEpqEDRS9vVPz7QT35jcBG2r3CZwSwQ4fCewXAhAhqGVpP",
+    },
+    Bip38Vector {
+        passphrase: "Satoshi",
+        encrypted: "6PYLtMnXvfG3oJde97zRyLYFZCYizPU5T3LwgdYJz1fRhh16bU7u6PPmY7",
+        wif: "KwYgW8gcxj1JWJXhPSu4Fqwzfhp5Yfi42mdYmMa4XqK7NJxXUSK7",
+    },
+];
+
+/// A message signed by an address with BIP322, simple signatures
+#[derive(Debug, Clone, Copy)]
+pub struct Bip322Vector {
+    pub message: &'static str,
+    pub message_hash: &'static str,
+    pub to_spend: &'static str,
+    pub to_sign: &'static str,
+    pub signature: &'static str,
+}
+
+/// The address signing every BIP322 vector, of the key
+/// L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k
+pub const BIP322_ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+
+/// The P2WPKH vectors from BIP322
+pub const BIP322_VECTORS: &[Bip322Vector] = &[
+    Bip322Vector {
+        message: "",
+        message_hash: "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1",
+        to_spend: "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7",
+        to_sign: "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6",
+        signature: "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=",
+    },
+    Bip322Vector {
+        message: "Hello World",
+        message_hash: "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a",
+        to_spend: "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b",
+        to_sign: "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf",
+        signature: "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=",
+    },
+];
+
+/// The passphrase every SLIP-39 vector's master secret is encrypted with
+pub const SLIP39_PASSPHRASE: &str = "TREZOR";
+
+/// A set of SLIP-39 shares, the master secret is empty when the shares are invalid
+#[derive(Debug, Clone, Copy)]
+pub struct Slip39Vector {
+    pub description: &'static str,
+    pub mnemonics: &'static [&'static str],
+    pub master_secret: &'static str,
+}
+
+/// Vectors from the reference implementation (trezor/python-shamir-mnemonic)
+pub const SLIP39_VECTORS: &[Slip39Vector] = &[
+    Slip39Vector {
+        description: "Valid mnemonic without sharing (128 bits)",
+        mnemonics: &[
+            "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard",
+        ],
+        master_secret: "bb54aac4b89dc868ba37d9cc21b2cece",
+    },
+    Slip39Vector {
+        description: "Mnemonic with invalid checksum (128 bits)",
+        mnemonics: &[
+            "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonic with invalid padding (128 bits)",
+        mnemonics: &[
+            "duckling enlarge academic academic email result length solution fridge kidney coal piece deal husband erode duke ajar music cargo fitness",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Basic sharing 2-of-3 (128 bits)",
+        mnemonics: &[
+            "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+            "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+        ],
+        master_secret: "b43ceb7e57a0ea8766221624d01b0864",
+    },
+    Slip39Vector {
+        description: "Basic sharing 2-of-3 (128 bits)",
+        mnemonics: &[
+            "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with different identifiers (128 bits)",
+        mnemonics: &[
+            "adequate smoking academic acid debut wine petition glen cluster slow rhyme slow simple epidemic rumor junk tracks treat olympic tolerate",
+            "adequate stay academic agency agency formal party ting frequent learn upstairs remember smear leaf damage anatomy ladle market hush corner",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with different iteration exponents (128 bits)",
+        mnemonics: &[
+            "peasant leaves academic acid desert exact olympic math alive axle trial tackle drug deny decent smear dominant desert bucket remind",
+            "peasant leader academic agency cultural blessing percent network envelope medal junk primary human pumps jacket fragment payroll ticket evoke voice",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with mismatching group thresholds (128 bits)",
+        mnemonics: &[
+            "liberty category beard echo animal fawn temple briefing math username various wolf aviation fancy visual holy thunder yelp helpful payment",
+            "liberty category beard email beyond should fancy romp founder easel pink holy hairy romp loyalty material victim owner toxic custody",
+            "liberty category academic easy being hazard crush diminish oral lizard reaction cluster force dilemma deploy force club veteran expect photo",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with mismatching group counts (128 bits)",
+        mnemonics: &[
+            "average senior academic leaf broken teacher expect surface hour capture obesity desire negative dynamic dominant pistol mineral mailman iris aide",
+            "average senior academic agency curious pants blimp spew clothes slice script dress wrap firm shaft regular slavery negative theater roster",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with greater group threshold than group counts (128 bits)",
+        mnemonics: &[
+            "music husband acrobat acid artist finance center either graduate swimming object bike medical clothes station aspect spider maiden bulb welcome",
+            "music husband acrobat agency advance hunting bike corner density careful material civil evil tactics remind hawk discuss hobo voice rainbow",
+            "music husband beard academic black tricycle clock mayor estimate level photo episode exclude ecology papa source amazing salt verify divorce",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with duplicate member indices (128 bits)",
+        mnemonics: &[
+            "device stay academic always dive coal antenna adult black exceed stadium herald advance soldier busy dryer daughter evaluate minister laser",
+            "device stay academic always dwarf afraid robin gravity crunch adjust soul branch walnut coastal dream costume scholar mortgage mountain pumps",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with mismatching member thresholds (128 bits)",
+        mnemonics: &[
+            "hour painting academic academic device formal evoke guitar random modern justice filter withdraw trouble identify mailman insect general cover oven",
+            "hour painting academic agency artist again daisy capital beaver fiber much enjoy suitable symbolic identify photo editor romp float echo",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics giving an invalid digest (128 bits)",
+        mnemonics: &[
+            "guilt walnut academic acid deliver remove equip listen vampire tactics nylon rhythm failure husband fatigue alive blind enemy teaspoon rebound",
+            "guilt walnut academic agency brave hamster hobo declare herd taste alpha slim criminal mild arcade formal romp branch pink ambition",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Insufficient number of groups (128 bits, case 1)",
+        mnemonics: &[
+            "eraser senior beard romp adorn nuclear spill corner cradle style ancient family general leader ambition exchange unusual garlic promise voice",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Insufficient number of groups (128 bits, case 2)",
+        mnemonics: &[
+            "eraser senior decision scared cargo theory device idea deliver modify curly include pancake both news skin realize vitamins away join",
+            "eraser senior decision roster beard treat identify grumpy salt index fake aviation theater cubic bike cause research dragon emphasis counter",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Threshold number of groups, but insufficient number of members in one group (128 bits)",
+        mnemonics: &[
+            "eraser senior decision shadow artist work morning estate greatest pipeline plan ting petition forget hormone flexible general goat admit surface",
+            "eraser senior beard romp adorn nuclear spill corner cradle style ancient family general leader ambition exchange unusual garlic promise voice",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Threshold number of groups and members in each group (128 bits, case 1)",
+        mnemonics: &[
+            "eraser senior decision roster beard treat identify grumpy salt index fake aviation theater cubic bike cause research dragon emphasis counter",
+            "eraser senior ceramic snake clay various huge numb argue hesitate auction category timber browser greatest hanger petition script leaf pickup",
+            "eraser senior ceramic shaft dynamic become junior wrist silver peasant force math alto coal amazing segment yelp velvet image paces",
+            "eraser senior ceramic round column hawk trust auction smug shame alive greatest sheriff living perfect corner chest sled fumes adequate",
+            "eraser senior decision smug corner ruin rescue cubic angel tackle skin skunk program roster trash rumor slush angel flea amazing",
+        ],
+        master_secret: "7c3397a292a5941682d7a4ae2d898d11",
+    },
+    Slip39Vector {
+        description: "Threshold number of groups and members in each group (128 bits, case 2)",
+        mnemonics: &[
+            "eraser senior decision smug corner ruin rescue cubic angel tackle skin skunk program roster trash rumor slush angel flea amazing",
+            "eraser senior beard romp adorn nuclear spill corner cradle style ancient family general leader ambition exchange unusual garlic promise voice",
+            "eraser senior decision scared cargo theory device idea deliver modify curly include pancake both news skin realize vitamins away join",
+        ],
+        master_secret: "7c3397a292a5941682d7a4ae2d898d11",
+    },
+    Slip39Vector {
+        description: "Threshold number of groups and members in each group (128 bits, case 3)",
+        mnemonics: &[
+            "eraser senior beard romp adorn nuclear spill corner cradle style ancient family general leader ambition exchange unusual garlic promise voice",
+            "eraser senior acrobat romp bishop medical gesture pumps secret alive ultimate quarter priest subject class dictate spew material endless market",
+        ],
+        master_secret: "7c3397a292a5941682d7a4ae2d898d11",
+    },
+    Slip39Vector {
+        description: "Valid mnemonic without sharing (256 bits)",
+        mnemonics: &[
+            "theory painting academic academic armed sweater year military elder discuss acne wildlife boring employer fused large satoshi bundle carbon diagnose anatomy hamster leaves tracks paces beyond phantom capital marvel lips brave detect luck",
+        ],
+        master_secret: "989baf9dcaad5b10ca33dfd8cc75e42477025dce88ae83e75a230086a0e00e92",
+    },
+    Slip39Vector {
+        description: "Mnemonic with invalid checksum (256 bits)",
+        mnemonics: &[
+            "theory painting academic academic armed sweater year military elder discuss acne wildlife boring employer fused large satoshi bundle carbon diagnose anatomy hamster leaves tracks paces beyond phantom capital marvel lips brave detect lunar",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonic with invalid padding (256 bits)",
+        mnemonics: &[
+            "theory painting academic academic campus sweater year military elder discuss acne wildlife boring employer fused large satoshi bundle carbon diagnose anatomy hamster leaves tracks paces beyond phantom capital marvel lips facility obtain sister",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Basic sharing 2-of-3 (256 bits)",
+        mnemonics: &[
+            "humidity disease academic always aluminum jewelry energy woman receiver strategy amuse duckling lying evidence network walnut tactics forget hairy rebound impulse brother survive clothes stadium mailman rival ocean reward venture always armed unwrap",
+            "humidity disease academic agency actress jacket gross physics cylinder solution fake mortgage benefit public busy prepare sharp friar change work slow purchase ruler again tricycle involve viral wireless mixture anatomy desert cargo upgrade",
+        ],
+        master_secret: "c938b319067687e990e05e0da0ecce1278f75ff58d9853f19dcaeed5de104aae",
+    },
+    Slip39Vector {
+        description: "Basic sharing 2-of-3 (256 bits)",
+        mnemonics: &[
+            "humidity disease academic always aluminum jewelry energy woman receiver strategy amuse duckling lying evidence network walnut tactics forget hairy rebound impulse brother survive clothes stadium mailman rival ocean reward venture always armed unwrap",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with different identifiers (256 bits)",
+        mnemonics: &[
+            "smear husband academic acid deadline scene venture distance dive overall parking bracelet elevator justice echo burning oven chest duke nylon",
+            "smear isolate academic agency alpha mandate decorate burden recover guard exercise fatal force syndrome fumes thank guest drift dramatic mule",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with different iteration exponents (256 bits)",
+        mnemonics: &[
+            "finger trash academic acid average priority dish revenue academic hospital spirit western ocean fact calcium syndrome greatest plan losing dictate",
+            "finger traffic academic agency building lilac deny paces subject threaten diploma eclipse window unknown health slim piece dragon focus smirk",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with mismatching group thresholds (256 bits)",
+        mnemonics: &[
+            "flavor pink beard echo depart forbid retreat become frost helpful juice unwrap reunion credit math burning spine black capital lair",
+            "flavor pink beard email diet teaspoon freshman identify document rebound cricket prune headset loyalty smell emission skin often square rebound",
+            "flavor pink academic easy credit cage raisin crazy closet lobe mobile become drink human tactics valuable hand capture sympathy finger",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with mismatching group counts (256 bits)",
+        mnemonics: &[
+            "column flea academic leaf debut extra surface slow timber husky lawsuit game behavior husky swimming already paper episode tricycle scroll",
+            "column flea academic agency blessing garbage party software stadium verify silent umbrella therapy decorate chemical erode dramatic eclipse replace apart",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with greater group threshold than group counts (256 bits)",
+        mnemonics: &[
+            "smirk pink acrobat acid auction wireless impulse spine sprinkle fortune clogs elbow guest hush loyalty crush dictate tracks airport talent",
+            "smirk pink acrobat agency dwarf emperor ajar organize legs slice harvest plastic dynamic style mobile float bulb health coding credit",
+            "smirk pink beard academic alto strategy carve shame language rapids ruin smart location spray training acquire eraser endorse submit peaceful",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with duplicate member indices (256 bits)",
+        mnemonics: &[
+            "fishing recover academic always device craft trend snapshot gums skin downtown watch device sniff hour clock public maximum garlic born",
+            "fishing recover academic always aircraft view software cradle fangs amazing package plastic evaluate intend penalty epidemic anatomy quarter cage apart",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics with mismatching member thresholds (256 bits)",
+        mnemonics: &[
+            "evoke garden academic academic answer wolf scandal modern warmth station devote emerald market physics surface formal amazing aquatic gesture medical",
+            "evoke garden academic agency deal revenue knit reunion decrease magazine flexible company goat repair alarm military facility clogs aide mandate",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonics giving an invalid digest (256 bits)",
+        mnemonics: &[
+            "river deal academic acid average forbid pistol peanut custody bike class aunt hairy merit valid flexible learn ajar very easel",
+            "river deal academic agency camera amuse lungs numb isolate display smear piece traffic worthy year patrol crush fact fancy emission",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Insufficient number of groups (256 bits, case 1)",
+        mnemonics: &[
+            "wildlife deal beard romp alcohol space mild usual clothes union nuclear testify course research heat listen task location thank hospital slice smell failure fawn helpful priest ambition average recover lecture process dough stadium",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Insufficient number of groups (256 bits, case 2)",
+        mnemonics: &[
+            "wildlife deal decision scared acne fatal snake paces obtain election dryer dominant romp tactics railroad marvel trust helpful flip peanut theory theater photo luck install entrance taxi step oven network dictate intimate listen",
+            "wildlife deal decision smug ancestor genuine move huge cubic strategy smell game costume extend swimming false desire fake traffic vegan senior twice timber submit leader payroll fraction apart exact forward pulse tidy install",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Threshold number of groups, but insufficient number of members in one group (256 bits)",
+        mnemonics: &[
+            "wildlife deal decision shadow analysis adjust bulb skunk muscle mandate obesity total guitar coal gravity carve slim jacket ruin rebuild ancestor numerous hour mortgage require herd maiden public ceiling pecan pickup shadow club",
+            "wildlife deal beard romp alcohol space mild usual clothes union nuclear testify course research heat listen task location thank hospital slice smell failure fawn helpful priest ambition average recover lecture process dough stadium",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Threshold number of groups and members in each group (256 bits, case 1)",
+        mnemonics: &[
+            "wildlife deal ceramic round aluminum pitch goat racism employer miracle percent math decision episode dramatic editor lily prospect program scene rebuild display sympathy have single mustang junction relate often chemical society wits estate",
+            "wildlife deal decision scared acne fatal snake paces obtain election dryer dominant romp tactics railroad marvel trust helpful flip peanut theory theater photo luck install entrance taxi step oven network dictate intimate listen",
+            "wildlife deal ceramic scatter argue equip vampire together ruin reject literary rival distance aquatic agency teammate rebound false argue miracle stay again blessing peaceful unknown cover beard acid island language debris industry idle",
+            "wildlife deal ceramic snake agree voter main lecture axis kitchen physics arcade velvet spine idea scroll promise platform firm sharp patrol divorce ancestor fantasy forbid goat ajar believe swimming cowboy symbolic plastic spelling",
+            "wildlife deal decision shadow analysis adjust bulb skunk muscle mandate obesity total guitar coal gravity carve slim jacket ruin rebuild ancestor numerous hour mortgage require herd maiden public ceiling pecan pickup shadow club",
+        ],
+        master_secret: "5385577c8cfc6c1a8aa0f7f10ecde0a3318493262591e78b8c14c6686167123b",
+    },
+    Slip39Vector {
+        description: "Threshold number of groups and members in each group (256 bits, case 2)",
+        mnemonics: &[
+            "wildlife deal decision scared acne fatal snake paces obtain election dryer dominant romp tactics railroad marvel trust helpful flip peanut theory theater photo luck install entrance taxi step oven network dictate intimate listen",
+            "wildlife deal beard romp alcohol space mild usual clothes union nuclear testify course research heat listen task location thank hospital slice smell failure fawn helpful priest ambition average recover lecture process dough stadium",
+            "wildlife deal decision smug ancestor genuine move huge cubic strategy smell game costume extend swimming false desire fake traffic vegan senior twice timber submit leader payroll fraction apart exact forward pulse tidy install",
+        ],
+        master_secret: "5385577c8cfc6c1a8aa0f7f10ecde0a3318493262591e78b8c14c6686167123b",
+    },
+    Slip39Vector {
+        description: "Threshold number of groups and members in each group (256 bits, case 3)",
+        mnemonics: &[
+            "wildlife deal beard romp alcohol space mild usual clothes union nuclear testify course research heat listen task location thank hospital slice smell failure fawn helpful priest ambition average recover lecture process dough stadium",
+            "wildlife deal acrobat romp anxiety axis starting require metric flexible geology game drove editor edge screw helpful have huge holy making pitch unknown carve holiday numb glasses survive already tenant adapt goat fangs",
+        ],
+        master_secret: "5385577c8cfc6c1a8aa0f7f10ecde0a3318493262591e78b8c14c6686167123b",
+    },
+    Slip39Vector {
+        description: "Mnemonic with insufficient length",
+        mnemonics: &[
+            "junk necklace academic academic acne isolate join hesitate lunar roster dough calcium chemical ladybug amount mobile glasses verify cylinder",
+        ],
+        master_secret: "",
+    },
+    Slip39Vector {
+        description: "Mnemonic with invalid master secret length",
+        mnemonics: &[
+            "fraction necklace academic academic award teammate mouse regular testify coding building member verdict purchase blind camera duration email prepare spirit quarter",
+        ],
+        master_secret: "",
+    },
+];
@@ -0,0 +1,38 @@
+//! Events of the `tracing` feature. Without the feature the macros expand to
+//! nothing. Events never record key material, mnemonics or passphrases,
+//! the `Debug` output of the types holding them is redacted
+
+/// an event at the debug level, see `tracing::debug!`
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// an event at the info level, see `tracing::info!`
+macro_rules! info {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::info!($($arg)*);
+    };
+}
+
+/// an event at the warn level, see `tracing::warn!`
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
+
+/// an event at the trace level, see `tracing::trace!`
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+/// what the `Debug` output of a type shows in place of a secret
+pub(crate) const REDACTED: &str = "<redacted>";
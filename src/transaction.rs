@@ -1,21 +1,169 @@
-use crate::{reverse_byte_order, ripemd160_hash, sha256_hash, sha256_hash_twice, Key};
+use secp256k1::{schnorrsig, All, Message, PublicKey, Secp256k1, Signature, Verification};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compress_public_key, hash160, legacy_sighash, parse_pushes, push_data, reverse_bytes,
+    ripemd160_hash, sha256_hash, sha256_hash_twice, write_i32_le, write_i64_le, write_u32_le,
+    write_varint, Clock, Key, LockTime, MultisigScript, Script, ScriptType, SigHashType,
+    SighashCache, SighashMode, TransactionError, VerifyError, OP_1,
+};
+
+/// rough size in bytes of a transaction with no inputs or outputs
+const TX_OVERHEAD_SIZE: u64 = 10;
+/// rough size in bytes of a signed P2PKH input
+const P2PKH_INPUT_SIZE: u64 = 148;
+/// size in bytes of a P2PKH output
+const P2PKH_OUTPUT_SIZE: u64 = 34;
+/// the weight of an input without its script sig and witness, its outpoint and sequence
+pub(crate) const INPUT_BASE_WEIGHT: u64 = (32 + 4 + 4) * 4;
+
+/// the sequence number of an input that opts out of replacement and relative locktimes
+pub const FINAL_SEQUENCE: u32 = 0xffffffff;
+
+/// sign every input and output
+pub const SIGHASH_ALL: u32 = 0x01;
+/// the taproot sighash type of a 64 byte signature, signing every input and output
+pub(crate) const SIGHASH_DEFAULT: u8 = 0x00;
+
+/// estimate the size of a signed P2PKH transaction, used for fee calculation
+pub fn estimate_p2pkh_size(num_inputs: usize, num_outputs: usize) -> u64 {
+    TX_OVERHEAD_SIZE + P2PKH_INPUT_SIZE * num_inputs as u64 + P2PKH_OUTPUT_SIZE * num_outputs as u64
+}
+
+/// Estimate the virtual size of a signed transaction spending inputs of a
+/// type to some outputs, used for fee calculation
+pub fn estimate_vsize(
+    tx_type: &TransactionType,
+    num_inputs: usize,
+    outputs: &[TransactionOutput],
+) -> u64 {
+    // segwit transactions carry a marker and flag, a quarter of their size each
+    let overhead = match tx_type {
+        TransactionType::Pay2PubKeyHash => TX_OVERHEAD_SIZE,
+        _ => TX_OVERHEAD_SIZE + 1,
+    };
+    let outputs: u64 = outputs
+        .iter()
+        .map(|output| output.serialize().len() as u64)
+        .sum();
+
+    overhead + tx_type.input_vsize() * num_inputs as u64 + outputs
+}
+
+/// Estimate the virtual size of a signed transaction spending outputs of
+/// mixed types, each input weighed by [ScriptType::max_satisfaction_weight].
+/// Inputs of a type without one, eg P2WSH, are sized as spending `tx_type`
+pub fn estimate_mixed_vsize(
+    tx_type: &TransactionType,
+    inputs: &[ScriptType],
+    outputs: &[TransactionOutput],
+) -> u64 {
+    let mut weight = 0;
+    let mut segwit = false;
+    for input in inputs {
+        let (satisfaction, witness) = match input.max_satisfaction_weight() {
+            Some(satisfaction) => (satisfaction, input.is_witness()),
+            None => (
+                tx_type.input_vsize() * 4 - INPUT_BASE_WEIGHT,
+                *tx_type != TransactionType::Pay2PubKeyHash,
+            ),
+        };
+        weight += INPUT_BASE_WEIGHT + satisfaction;
+        segwit |= witness;
+    }
+
+    // the version, lock time, counts and outputs aren't witness data
+    let outputs_len: usize = outputs.iter().map(|output| output.serialize().len()).sum();
+    let counts = compact_size(inputs.len()).len() + compact_size(outputs.len()).len();
+    weight += (4 + counts + outputs_len + 4) as u64 * 4;
+
+    // the marker and flag, and an empty witness for every input without one
+    if segwit {
+        weight += 2 + inputs.iter().filter(|input| !input.is_witness()).count() as u64;
+    }
+    weight.div_ceil(4)
+}
+
+/// encode a length as a bitcoin compact size unsigned integer
+/// whether there are signatures and each has SIGHASH_ALL appended
+fn ecdsa_signs_all(signatures: &[Vec<u8>]) -> bool {
+    !signatures.is_empty()
+        && signatures
+            .iter()
+            .all(|signature| signature.last() == Some(&(SIGHASH_ALL as u8)))
+}
+
+pub(crate) fn compact_size(length: usize) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_varint(&mut bytes, length as u64);
+    bytes
+}
 
 #[derive(Debug, Clone)]
 pub enum TransactionVersion {
     One,
+    /// enables relative locktimes (BIP68)
+    Two,
+    /// any other version, valid by consensus but not relayed
+    Nonstandard(u32),
 }
 
 impl TransactionVersion {
     pub fn as_ver_string(&self) -> String {
         match self {
             TransactionVersion::One => "01000000".to_string(),
+            TransactionVersion::Two => "02000000".to_string(),
+            TransactionVersion::Nonstandard(version) => hex::encode(version.to_le_bytes()),
+        }
+    }
+
+    /// the version number as it is serialized
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            TransactionVersion::One => 1,
+            TransactionVersion::Two => 2,
+            TransactionVersion::Nonstandard(version) => *version,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TransactionType {
     Pay2PubKeyHash,
+    /// P2SH-P2WPKH, a segwit v0 key hash wrapped in a script hash
+    NestedPay2WitnessPubKeyHash,
+    /// P2WPKH, a native segwit v0 key hash
+    Pay2WitnessPubKeyHash,
+    /// P2TR, a segwit v1 taproot output key with no script path
+    Pay2Taproot,
+    /// P2PK, a bare public key spent with a signature alone, only seen in
+    /// old outputs, eg coinbases of the first blocks
+    Pay2PubKey,
+}
+
+impl TransactionType {
+    /// rough virtual size of a signed input spending an output of this type
+    pub fn input_vsize(&self) -> u64 {
+        match self {
+            TransactionType::Pay2PubKeyHash => P2PKH_INPUT_SIZE,
+            TransactionType::NestedPay2WitnessPubKeyHash => 91,
+            TransactionType::Pay2WitnessPubKeyHash => 68,
+            TransactionType::Pay2Taproot => 58,
+            // a signature push without the key
+            TransactionType::Pay2PubKey => 114,
+        }
+    }
+
+    /// the length of the pk script of an output of this type
+    pub fn pk_script_len(&self) -> usize {
+        match self {
+            TransactionType::Pay2PubKeyHash => 25,
+            TransactionType::NestedPay2WitnessPubKeyHash => 23,
+            TransactionType::Pay2WitnessPubKeyHash => 22,
+            TransactionType::Pay2Taproot => 34,
+            TransactionType::Pay2PubKey => 35,
+        }
+    }
 }
 
 /// A bitcoin Transaction
@@ -30,7 +178,7 @@ pub struct Transaction {
     /// vector of transaction outputs
     tx_out: Vec<TransactionOutput>,
     /// a timestamp or block number
-    lock_time: u128,
+    lock_time: LockTime,
 }
 
 impl Transaction {
@@ -38,165 +186,1003 @@ impl Transaction {
         tx_type: TransactionType,
         inputs: Vec<TransactionInput>,
         outputs: Vec<TransactionOutput>,
-        lock_time: Option<u128>,
+        lock_time: Option<LockTime>,
     ) -> Self {
-        match lock_time {
-            Some(time) => Self {
-                tx_type,
-                version: TransactionVersion::One,
-                tx_in: inputs,
-                tx_out: outputs,
-                lock_time: time,
-            },
-            None => Self {
-                tx_type,
-                version: TransactionVersion::One,
-                tx_in: inputs,
-                tx_out: outputs,
-                lock_time: 0,
-            },
+        Self {
+            tx_type,
+            version: TransactionVersion::One,
+            tx_in: inputs,
+            tx_out: outputs,
+            lock_time: lock_time.unwrap_or_default(),
         }
     }
 
-    /// create a presigned transaction
+    /// create a presigned transaction, every input holding the pk script
+    /// of the output it spends in place of its signature script
     pub fn pre_sign(&self) -> String {
-        let mut presigned_tx = String::new();
+        let mut presigned = self.clone();
+        for input in presigned.tx_in.iter_mut() {
+            input.signature_script = input.utxo_pk_script.clone();
+            input.witness.clear();
+        }
+        hex::encode(presigned.encode(false))
+    }
+
+    /// Get a signed copy of this transaction as hex, every input signed as
+    /// P2PKH by a key, each over its own legacy sighash with [SigHashType::All].
+    /// Fails with [TransactionError::Key] for a wiped key
+    pub fn sign(&self, key: Key) -> Result<String, TransactionError> {
+        self.sign_with(key, SigHashType::All)
+    }
+
+    /// [Transaction::sign] with a sighash type, eg [SigHashType::AllPlusAnyoneCanPay]
+    /// so others may add inputs after
+    pub fn sign_with(
+        &self,
+        key: Key,
+        sighash_type: SigHashType,
+    ) -> Result<String, TransactionError> {
+        let key_error = |e: crate::KeyError| TransactionError::Key(e.to_string());
+        let pk = key.new_public_key().map_err(key_error)?;
+        let flag = sighash_type.ecdsa_flag()?;
+
+        let mut signed = self.clone();
+        for (index, input) in signed.tx_in.iter_mut().enumerate() {
+            let sighash = legacy_sighash(self, index, &self.tx_in[index].utxo_pk_script, flag)?;
+            let mut signature = key.sign_der(&sighash).map_err(key_error)?;
+            signature.push(sighash_type.to_byte());
+
+            let mut sig_script = push_data(&signature);
+            sig_script.append(&mut push_data(&pk));
+            input.signature_script = sig_script;
+            input.witness.clear();
+        }
+        Ok(hex::encode(signed.encode(false)))
+    }
+
+    /// serialize the transaction as it is relayed to the network
+    /// inputs carry whatever signature scripts and witnesses have been set on them
+    pub fn serialize(&self) -> Vec<u8> {
+        let has_witness = self.tx_in.iter().any(|input| !input.witness.is_empty());
+        self.encode(has_witness)
+    }
+
+    /// serialize without signature scripts and witnesses, as the unsigned
+    /// transaction of a PSBT
+    pub(crate) fn serialize_unsigned(&self) -> Vec<u8> {
+        let mut unsigned = self.clone();
+        for input in unsigned.tx_in.iter_mut() {
+            input.signature_script.clear();
+            input.witness.clear();
+        }
+        unsigned.encode(false)
+    }
+
+    /// serialize with or without the segwit marker, flag and witnesses
+    fn encode(&self, include_witness: bool) -> Vec<u8> {
+        let mut bytes = vec![];
+        write_u32_le(&mut bytes, self.version.as_u32());
+
+        if include_witness {
+            bytes.extend_from_slice(&[0x00, 0x01]);
+        }
+
+        write_varint(&mut bytes, self.tx_in.len() as u64);
+        for input in self.tx_in.iter() {
+            bytes.append(&mut input.previous_output.serialize());
+            write_varint(&mut bytes, input.signature_script.len() as u64);
+            bytes.extend_from_slice(&input.signature_script);
+            write_u32_le(&mut bytes, input.sequence);
+        }
+
+        write_varint(&mut bytes, self.tx_out.len() as u64);
+        for output in self.tx_out.iter() {
+            bytes.append(&mut output.serialize());
+        }
+
+        if include_witness {
+            for input in self.tx_in.iter() {
+                write_varint(&mut bytes, input.witness.len() as u64);
+                for item in input.witness.iter() {
+                    write_varint(&mut bytes, item.len() as u64);
+                    bytes.extend_from_slice(item);
+                }
+            }
+        }
+
+        write_u32_le(&mut bytes, self.lock_time.to_consensus_u32());
+        bytes
+    }
+
+    /// Sign a P2SH-P2WPKH input, eg with [SigHashType::All]. The signature script
+    /// pushes the redeem script and the signature goes in the witness
+    pub fn sign_nested_segwit_input(
+        &mut self,
+        input_index: usize,
+        key: &Key,
+        sighash_type: SigHashType,
+    ) -> Result<(), TransactionError> {
+        self.sign_nested_segwit_inputs(&[(input_index, key)], sighash_type)
+    }
+
+    /// Sign many P2SH-P2WPKH inputs, each given with its key, sharing
+    /// the hashes of the transaction between all signatures
+    pub fn sign_nested_segwit_inputs(
+        &mut self,
+        inputs: &[(usize, &Key)],
+        sighash_type: SigHashType,
+    ) -> Result<(), TransactionError> {
+        let mut cache = SighashCache::new();
+
+        for (input_index, key) in inputs.iter() {
+            self.sign_nested_segwit_input_with(&mut cache, *input_index, key, sighash_type)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_index = input_index), err(Debug))
+    )]
+    fn sign_nested_segwit_input_with(
+        &mut self,
+        cache: &mut SighashCache,
+        input_index: usize,
+        key: &Key,
+        sighash_type: SigHashType,
+    ) -> Result<(), TransactionError> {
+        let key_error = |e: crate::KeyError| TransactionError::Key(e.to_string());
+
+        let pubkey =
+            compress_public_key(&key.new_public_key().map_err(key_error)?).map_err(key_error)?;
+        let redeem_script = key.nested_segwit_redeem_script().map_err(key_error)?;
+
+        // the script code of a P2WPKH program is the matching P2PKH script
+        let script_code = hex::decode(format!("76a914{}88ac", hex::encode(hash160(&pubkey))))
+            .map_err(|e| TransactionError::Key(e.to_string()))?;
+
+        let amount = self
+            .tx_in
+            .get(input_index)
+            .ok_or(TransactionError::InputOutOfRange(input_index))?
+            .utxo_value;
+        let sighash = cache.segwit_v0_sighash(
+            self,
+            input_index,
+            &script_code,
+            amount,
+            sighash_type.ecdsa_flag()?,
+        )?;
+
+        let mut signature = key.sign_der(&sighash).map_err(key_error)?;
+        signature.push(sighash_type.to_byte());
+
+        let input = &mut self.tx_in[input_index];
+        input.signature_script = compact_size(redeem_script.len());
+        input.signature_script.extend_from_slice(&redeem_script);
+        input.witness = vec![signature, pubkey];
+
+        Ok(())
+    }
+
+    /// Add a signature, eg with [SigHashType::All], to a P2SH or P2WSH multisig input. Inputs
+    /// can be signed by one key at a time, by different parties, until the
+    /// threshold is reached. The input is always left as `OP_0 <sig>... <script>`,
+    /// in the signature script for P2SH and the witness for P2WSH,
+    /// with signatures in the order of their public keys in the script
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_index = input_index), err(Debug))
+    )]
+    pub fn sign_multisig_input(
+        &mut self,
+        input_index: usize,
+        script: &MultisigScript,
+        key: &Key,
+        sighash_type: SigHashType,
+    ) -> Result<(), TransactionError> {
+        let key_error = |e: crate::KeyError| TransactionError::Key(e.to_string());
+
+        let input = self
+            .tx_in
+            .get(input_index)
+            .ok_or(TransactionError::InputOutOfRange(input_index))?;
+        let segwit = match &input.utxo_pk_script {
+            pk_script if *pk_script == script.script_pubkey() => false,
+            pk_script if *pk_script == script.p2wsh_script_pubkey() => true,
+            _ => return Err(TransactionError::ScriptMismatch(input_index)),
+        };
+
+        let pubkey = key.new_public_key().map_err(key_error)?;
+        let compressed = compress_public_key(&pubkey).map_err(key_error)?;
+        let position = script
+            .position(&pubkey)
+            .or_else(|| script.position(&compressed))
+            .ok_or(TransactionError::KeyNotInScript(input_index))?;
+
+        let redeem_script = script.redeem_script();
+        let mut signatures =
+            self.multisig_signatures(input_index, script, &redeem_script, segwit)?;
+
+        if signatures.iter().any(|(signer, _)| *signer == position) {
+            return Ok(());
+        }
+        if signatures.len() >= script.threshold() {
+            return Err(TransactionError::MultisigComplete(input_index));
+        }
+
+        let sighash = self.script_sighash(
+            input_index,
+            &redeem_script,
+            segwit,
+            sighash_type.ecdsa_flag()?,
+        )?;
+        let mut signature = key.sign_der(&sighash).map_err(key_error)?;
+        signature.push(sighash_type.to_byte());
+
+        signatures.push((position, signature));
+        signatures.sort_by_key(|(signer, _)| *signer);
+
+        // an empty element for the extra item CHECKMULTISIG pops
+        let mut stack = vec![vec![]];
+        stack.extend(signatures.into_iter().map(|(_, signature)| signature));
+        stack.push(redeem_script);
+
+        let input = &mut self.tx_in[input_index];
+        match segwit {
+            true => input.witness = stack,
+            false => {
+                input.signature_script = stack.iter().flat_map(|item| push_data(item)).collect()
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the signatures already in a multisig input's signature script
+    /// or witness, each with the position of the key that made it
+    fn multisig_signatures(
+        &self,
+        input_index: usize,
+        script: &MultisigScript,
+        redeem_script: &[u8],
+        segwit: bool,
+    ) -> Result<Vec<(usize, Vec<u8>)>, TransactionError> {
+        let malformed = TransactionError::MalformedSignatureScript(input_index);
+
+        let input = &self.tx_in[input_index];
+        let stack = match segwit {
+            true => input.witness.clone(),
+            false => parse_pushes(&input.signature_script).ok_or_else(|| malformed.clone())?,
+        };
+        if stack.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let signatures = match stack.as_slice() {
+            [dummy, signatures @ .., last] if dummy.is_empty() && last == redeem_script => {
+                signatures
+            }
+            _ => return Err(malformed),
+        };
+
+        signatures
+            .iter()
+            .map(|signature| {
+                let (flag, der) = signature.split_last().ok_or_else(|| malformed.clone())?;
+                let sighash_type = SigHashType::from_u32(*flag as u32)?;
+                let sighash =
+                    self.script_sighash(input_index, redeem_script, segwit, sighash_type.to_u32())?;
+                let signer = script
+                    .signer_position(&sighash, der)
+                    .ok_or_else(|| malformed.clone())?;
+                Ok((signer, signature.clone()))
+            })
+            .collect()
+    }
+
+    /// the signature hash of a script spend, BIP143 for P2WSH and legacy for P2SH
+    fn script_sighash(
+        &self,
+        input_index: usize,
+        script: &[u8],
+        segwit: bool,
+        sighash_type: u32,
+    ) -> Result<Vec<u8>, TransactionError> {
+        match segwit {
+            true => {
+                let amount = self.tx_in[input_index].utxo_value;
+                SighashCache::new().segwit_v0_sighash(
+                    self,
+                    input_index,
+                    script,
+                    amount,
+                    sighash_type,
+                )
+            }
+            false => legacy_sighash(self, input_index, script, sighash_type),
+        }
+    }
+
+    /// The signature hash algorithm of an input, found from the output it
+    /// spends. A P2SH input is legacy when the script code is its redeem
+    /// script and a nested segwit input otherwise
+    pub fn sighash_mode(
+        &self,
+        input_index: usize,
+        script_code: &[u8],
+    ) -> Result<SighashMode, TransactionError> {
+        let input = self
+            .tx_in
+            .get(input_index)
+            .ok_or(TransactionError::InputOutOfRange(input_index))?;
+
+        Ok(match input.utxo_pk_script.as_slice() {
+            [0x51, 0x20, program @ ..] if program.len() == 32 => SighashMode::Taproot,
+            [0x00, length, program @ ..]
+                if matches!(length, 20 | 32) && program.len() == *length as usize =>
+            {
+                SighashMode::SegwitV0
+            }
+            [0xa9, 0x14, hash @ .., 0x87] if hash.len() == 20 => {
+                match hash160(&script_code.to_vec()) == hash {
+                    true => SighashMode::Legacy,
+                    false => SighashMode::SegwitV0,
+                }
+            }
+            _ => SighashMode::Legacy,
+        })
+    }
+
+    /// The digest the signature of an input commits to, for signing outside of
+    /// waller, eg with an HSM. The algorithm is picked by [Transaction::sighash_mode].
+    /// The script code is given without its length prefix and is ignored for
+    /// taproot key path spends, whose digest is signed with Schnorr.
+    /// [SigHashType::TaprootDefault] fails with [TransactionError::InvalidSighashType]
+    /// for inputs signed with ECDSA
+    pub fn sighash(
+        &self,
+        input_index: usize,
+        script_code: &[u8],
+        sighash_type: SigHashType,
+    ) -> Result<[u8; 32], TransactionError> {
+        self.sighash_with(
+            &mut SighashCache::new(),
+            input_index,
+            script_code,
+            sighash_type,
+        )
+    }
 
-        // version
-        let version = self.version.as_ver_string();
-        presigned_tx.push_str(&version);
+    /// [Transaction::sighash] sharing the hashes of the transaction
+    /// between the inputs signed with one cache
+    pub(crate) fn sighash_with(
+        &self,
+        cache: &mut SighashCache,
+        input_index: usize,
+        script_code: &[u8],
+        sighash_type: SigHashType,
+    ) -> Result<[u8; 32], TransactionError> {
+        let sighash = match self.sighash_mode(input_index, script_code)? {
+            SighashMode::Legacy => {
+                legacy_sighash(self, input_index, script_code, sighash_type.ecdsa_flag()?)?
+            }
+            SighashMode::SegwitV0 => cache.segwit_v0_sighash(
+                self,
+                input_index,
+                script_code,
+                self.tx_in[input_index].utxo_value,
+                sighash_type.ecdsa_flag()?,
+            )?,
+            SighashMode::Taproot => {
+                cache.taproot_key_spend_sighash(self, input_index, sighash_type.to_byte(), None)?
+            }
+        };
 
-        // num inputs
-        let num_inputs = self.inputs().len();
-        let num_inputs_hex = format!("{:02x}", num_inputs);
-        presigned_tx.push_str(&num_inputs_hex);
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&sighash);
+        Ok(digest)
+    }
 
-        // UTXOs to be spent
-        for input in self.inputs().iter() {
-            // TXID
-            let tx_id = input.previous_output.hash();
-            presigned_tx.push_str(&tx_id);
+    /// Sign a P2WSH input spending through a witness script, returning the
+    /// signature with its sighash type appended. Where the signatures go in the
+    /// witness depends on the script, see [Transaction::set_p2wsh_witness]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_index = input_index), err(Debug))
+    )]
+    pub fn sign_p2wsh_input(
+        &self,
+        input_index: usize,
+        witness_script: &Script,
+        key: &Key,
+        sighash_type: SigHashType,
+    ) -> Result<Vec<u8>, TransactionError> {
+        let input = self
+            .tx_in
+            .get(input_index)
+            .ok_or(TransactionError::InputOutOfRange(input_index))?;
+        if input.utxo_pk_script != witness_script.to_p2wsh().as_bytes() {
+            return Err(TransactionError::ScriptMismatch(input_index));
+        }
 
-            // VOUTS
-            let vout = input.previous_output.index();
-            let vout_hex = reverse_byte_order(format!("{:08x}", vout));
-            presigned_tx.push_str(&vout_hex);
+        let sighash = self.script_sighash(
+            input_index,
+            witness_script.as_bytes(),
+            true,
+            sighash_type.ecdsa_flag()?,
+        )?;
+        let mut signature = key
+            .sign_der(&sighash)
+            .map_err(|e| TransactionError::Key(e.to_string()))?;
+        signature.push(sighash_type.to_byte());
 
-            // num bytes in script sig
-            let bytes_hex = format!("{:02x}", input.utxo_pk_script.len());
-            presigned_tx.push_str(&bytes_hex);
+        Ok(signature)
+    }
 
-            // placeholder script sig
-            presigned_tx.push_str(&hex::encode(input.utxo_pk_script.clone()));
+    /// Complete a P2WSH input, the witness is the items satisfying
+    /// the witness script, bottom of the stack first, then the script
+    pub fn set_p2wsh_witness(
+        &mut self,
+        input_index: usize,
+        witness_script: &Script,
+        items: Vec<Vec<u8>>,
+    ) -> Result<(), TransactionError> {
+        let input = self
+            .tx_in
+            .get_mut(input_index)
+            .ok_or(TransactionError::InputOutOfRange(input_index))?;
+        if input.utxo_pk_script != witness_script.to_p2wsh().as_bytes() {
+            return Err(TransactionError::ScriptMismatch(input_index));
         }
 
-        // sequence
-        presigned_tx.push_str("ffffffff");
+        input.signature_script = vec![];
+        input.witness = items;
+        input.witness.push(witness_script.as_bytes().to_vec());
+
+        Ok(())
+    }
+
+    /// Add a signature made outside of waller, eg by an HSM or co-signer, to a
+    /// P2PKH, P2WPKH, P2SH-P2WPKH or P2PK input. The signature is DER encoded with its
+    /// sighash type appended and is checked against the input's [Transaction::sighash]
+    /// before the signature script and witness are written. A P2TR key path spend
+    /// takes a Schnorr signature, with its sighash type appended unless SIGHASH_DEFAULT,
+    /// and the tweaked output key as the public key
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(input_index = input_index), err(Debug))
+    )]
+    pub fn set_signature(
+        &mut self,
+        input_index: usize,
+        signature: &[u8],
+        pubkey: &[u8],
+    ) -> Result<(), TransactionError> {
+        self.set_signature_with(
+            &Secp256k1::verification_only(),
+            input_index,
+            signature,
+            pubkey,
+        )
+    }
 
-        // num outputs
-        let num_outputs = self.outputs().len();
-        let num_outputs_hex = reverse_byte_order(format!("{:02x}", num_outputs));
-        presigned_tx.push_str(&num_outputs_hex);
+    /// [Transaction::set_signature] checking with a context kept between signatures
+    pub(crate) fn set_signature_with<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        input_index: usize,
+        signature: &[u8],
+        pubkey: &[u8],
+    ) -> Result<(), TransactionError> {
+        let input = self
+            .tx_in
+            .get(input_index)
+            .ok_or(TransactionError::InputOutOfRange(input_index))?;
+        if let [0x51, 0x20, output_key @ ..] = input.utxo_pk_script.as_slice() {
+            if output_key.len() == 32 && output_key == pubkey {
+                return self.set_taproot_signature(input_index, signature);
+            }
+        }
+        let pubkey_hash = hash160(&pubkey.to_vec());
 
-        // outputs
-        for out in self.outputs().iter() {
-            // value
-            let value = format!("{:016x}", out.value());
-            presigned_tx.push_str(&value);
+        let mut witness_program = vec![0x00, 0x14];
+        witness_program.extend_from_slice(&pubkey_hash);
+        let (signature_script, witness) = match input.utxo_pk_script.as_slice() {
+            [0x76, 0xa9, 0x14, hash @ .., 0x88, 0xac] if hash == pubkey_hash.as_slice() => {
+                let mut script = push_data(signature);
+                script.append(&mut push_data(pubkey));
+                (script, vec![])
+            }
+            // P2PK, the key is in the output already
+            [length, key @ .., 0xac] if *length as usize == key.len() && key == pubkey => {
+                (push_data(signature), vec![])
+            }
+            pk_script if *pk_script == witness_program => {
+                (vec![], vec![signature.to_vec(), pubkey.to_vec()])
+            }
+            [0xa9, 0x14, hash @ .., 0x87] if *hash == hash160(&witness_program) => (
+                push_data(&witness_program),
+                vec![signature.to_vec(), pubkey.to_vec()],
+            ),
+            _ => return Err(TransactionError::KeyNotInScript(input_index)),
+        };
 
-            // pk script bytes
-            let bytes_hex = format!("{:02x}", out.script_bytes());
-            presigned_tx.push_str(&bytes_hex);
+        // every input type here but P2PK signs with the P2PKH script of its key
+        let script_code = match Script::new(input.utxo_pk_script.clone()).classify() {
+            ScriptType::P2pk => input.utxo_pk_script.clone(),
+            _ => {
+                let mut script_code = vec![0x76, 0xa9, 0x14];
+                script_code.extend_from_slice(&pubkey_hash);
+                script_code.extend_from_slice(&[0x88, 0xac]);
+                script_code
+            }
+        };
+        let segwit = !witness.is_empty();
 
-            // pk script
-            presigned_tx.push_str(&hex::encode(out.pk_script.clone()));
+        let invalid = TransactionError::InvalidSignature(input_index);
+        let (flag, der) = signature.split_last().ok_or_else(|| invalid.clone())?;
+        let sighash_type = SigHashType::from_u32(*flag as u32)?;
+        let sighash =
+            self.script_sighash(input_index, &script_code, segwit, sighash_type.to_u32())?;
+        let message = Message::from_slice(&sighash).map_err(|_| invalid.clone())?;
+        let der = Signature::from_der(der).map_err(|_| invalid.clone())?;
+        let pubkey_point = PublicKey::from_slice(pubkey).map_err(|_| invalid.clone())?;
+        if secp.verify(&message, &der, &pubkey_point).is_err() {
+            return Err(invalid);
         }
 
-        // locktime
-        let lock_time = format!("{:08x}", self.lock_time);
-        presigned_tx.push_str(&lock_time);
+        let input = &mut self.tx_in[input_index];
+        input.signature_script = signature_script;
+        input.witness = witness;
 
-        presigned_tx
+        Ok(())
     }
 
-    /// get a signed copy of this transaction using a key
-    pub fn sign(&self, key: Key) -> String {
-        let mut presigned_tx = self.pre_sign();
-        presigned_tx.push_str(&format!("{:08x}", 1));
+    /// [Transaction::set_signature] for a taproot key path spend, the Schnorr
+    /// signature is checked against the output key of the input
+    fn set_taproot_signature(
+        &mut self,
+        input_index: usize,
+        signature: &[u8],
+    ) -> Result<(), TransactionError> {
+        let invalid = TransactionError::InvalidSignature(input_index);
+        let (schnorr, sighash_type) = match signature.len() {
+            64 => (signature, SigHashType::TaprootDefault),
+            // the default type is only ever implied
+            65 if signature[64] != SIGHASH_DEFAULT => (
+                &signature[..64],
+                SigHashType::from_u32(signature[64] as u32)?,
+            ),
+            _ => return Err(invalid),
+        };
 
-        // sign transaction
-        let hash = sha256_hash_twice(&presigned_tx.as_bytes().to_vec());
+        let sighash = self.sighash(input_index, &[], sighash_type)?;
+        let message = Message::from_slice(&sighash).map_err(|_| invalid.clone())?;
+        let schnorr = schnorrsig::Signature::from_slice(schnorr).map_err(|_| invalid.clone())?;
+        let output_key =
+            schnorrsig::PublicKey::from_slice(&self.tx_in[input_index].utxo_pk_script[2..])
+                .map_err(|_| invalid.clone())?;
+        // secp256k1 only checks Schnorr signatures with a context that can also sign
+        Secp256k1::new()
+            .schnorrsig_verify(&schnorr, &message, &output_key)
+            .map_err(|_| invalid)?;
 
-        let mut signature = key.sign_data(hash);
-        signature.push(0x01);
+        let input = &mut self.tx_in[input_index];
+        input.signature_script = vec![];
+        input.witness = vec![signature.to_vec()];
 
-        let pk = key.new_public_key().unwrap();
+        Ok(())
+    }
 
-        let sig_script = format!(
-            "{:02x}{}{:02x}{}",
-            signature.len(),
-            hex::encode(signature),
-            pk.len(),
-            hex::encode(pk)
-        );
+    /// Replace the witness of an input with a stack built outside of waller,
+    /// bottom of the stack first, eg `[<schnorr sig>]` for a taproot key path
+    /// spend. The signature script is left as it is
+    pub fn set_witness(
+        &mut self,
+        input_index: usize,
+        stack: Vec<Vec<u8>>,
+    ) -> Result<(), TransactionError> {
+        self.tx_in
+            .get_mut(input_index)
+            .ok_or(TransactionError::InputOutOfRange(input_index))?
+            .witness = stack;
 
-        let mut output = String::new();
+        Ok(())
+    }
 
-        // version
-        let version = self.version.as_ver_string();
-        output.push_str(&version);
+    /// Check the signatures of every input against the outputs spent, given in
+    /// the order of the inputs, eg before relaying a transaction signed
+    /// elsewhere. The values and pk scripts of the prevouts are used in place
+    /// of those the inputs were made with. P2PK, P2PKH, P2WPKH, P2SH-P2WPKH,
+    /// multisig (bare, P2SH and P2WSH) and taproot key path spends are checked,
+    /// inputs spending anything else fail with [VerifyError::UnsupportedScript]
+    pub fn verify(&self, prevouts: &[TransactionOutput]) -> Result<(), VerifyError> {
+        self.verify_from(prevouts, 0)
+    }
 
-        // num inputs
-        let num_inputs = self.inputs().len();
-        let num_inputs_hex = format!("{:02x}", num_inputs);
-        output.push_str(&num_inputs_hex);
+    /// [Transaction::verify] from an input on, eg past the commitment of a
+    /// proof of reserves, which spends nothing
+    pub(crate) fn verify_from(
+        &self,
+        prevouts: &[TransactionOutput],
+        first_input: usize,
+    ) -> Result<(), VerifyError> {
+        if prevouts.len() != self.tx_in.len() {
+            return Err(VerifyError::PrevoutCount {
+                inputs: self.tx_in.len(),
+                prevouts: prevouts.len(),
+            });
+        }
 
-        // UTXOs to be spent
-        for input in self.inputs().iter() {
-            // TXID
-            let tx_id = input.previous_output.hash();
-            output.push_str(&tx_id);
+        let mut tx = self.clone();
+        for (input, prevout) in tx.tx_in.iter_mut().zip(prevouts) {
+            input.utxo_pk_script = prevout.pk_script.clone();
+            input.utxo_value = prevout.value;
+        }
 
-            // VOUTS
-            let vout = input.previous_output.index();
-            let vout_hex = reverse_byte_order(format!("{:08x}", vout));
-            output.push_str(&vout_hex);
+        // Schnorr signatures are only verified by a context able to sign
+        let secp = Secp256k1::new();
+        let mut cache = SighashCache::new();
+        for input_index in first_input..tx.tx_in.len() {
+            tx.verify_input(&secp, &mut cache, input_index)?;
+        }
+        Ok(())
+    }
+
+    fn verify_input(
+        &self,
+        secp: &Secp256k1<All>,
+        cache: &mut SighashCache,
+        input_index: usize,
+    ) -> Result<(), VerifyError> {
+        let malformed = VerifyError::MalformedInput(input_index);
+        let mismatch = VerifyError::ScriptMismatch(input_index);
+        let unsupported = VerifyError::UnsupportedScript(input_index);
+
+        let input = &self.tx_in[input_index];
+        let pushes = parse_pushes(&input.signature_script).ok_or_else(|| malformed.clone())?;
+        if pushes.is_empty() && input.witness.is_empty() {
+            return Err(VerifyError::UnsignedInput(input_index));
+        }
+        let legacy = |stack: &[Vec<u8>]| match input.witness.is_empty() {
+            true => Ok(stack.to_vec()),
+            false => Err(malformed.clone()),
+        };
+        let witness = |stack: &[Vec<u8>]| match stack.is_empty() {
+            true => Ok(input.witness.clone()),
+            false => Err(malformed.clone()),
+        };
+
+        let pk_script = Script::new(input.utxo_pk_script.clone());
+        match pk_script.classify() {
+            ScriptType::P2pk => match legacy(&pushes)?.as_slice() {
+                [signature] => self.verify_ecdsa(
+                    secp,
+                    cache,
+                    input_index,
+                    signature,
+                    pk_script.p2pk_public_key().unwrap_or_default(),
+                    pk_script.as_bytes(),
+                    false,
+                ),
+                _ => Err(malformed),
+            },
+            ScriptType::P2pkh => match legacy(&pushes)?.as_slice() {
+                [signature, pubkey] if hash160(pubkey) == pk_script.as_bytes()[3..23] => self
+                    .verify_ecdsa(
+                        secp,
+                        cache,
+                        input_index,
+                        signature,
+                        pubkey,
+                        pk_script.as_bytes(),
+                        false,
+                    ),
+                [_, _] => Err(mismatch),
+                _ => Err(malformed),
+            },
+            ScriptType::Multisig { .. } => self.verify_multisig(
+                secp,
+                cache,
+                input_index,
+                &legacy(&pushes)?,
+                pk_script.as_bytes(),
+                false,
+            ),
+            ScriptType::P2wpkh => self.verify_witness_v0(
+                secp,
+                cache,
+                input_index,
+                &witness(&pushes)?,
+                pk_script.as_bytes(),
+            ),
+            ScriptType::P2wsh => self.verify_witness_v0(
+                secp,
+                cache,
+                input_index,
+                &witness(&pushes)?,
+                pk_script.as_bytes(),
+            ),
+            ScriptType::P2tr => {
+                self.verify_taproot_key_path(secp, cache, input_index, &witness(&pushes)?)
+            }
+            ScriptType::P2sh => {
+                // the redeem script is pushed last
+                let (redeem_script, stack) =
+                    pushes.split_last().ok_or_else(|| malformed.clone())?;
+                if hash160(redeem_script) != pk_script.as_bytes()[2..22] {
+                    return Err(mismatch);
+                }
+                match Script::new(redeem_script.clone()).classify() {
+                    ScriptType::P2wpkh | ScriptType::P2wsh => self.verify_witness_v0(
+                        secp,
+                        cache,
+                        input_index,
+                        &witness(stack)?,
+                        redeem_script,
+                    ),
+                    ScriptType::Multisig { .. } => self.verify_multisig(
+                        secp,
+                        cache,
+                        input_index,
+                        &legacy(stack)?,
+                        redeem_script,
+                        false,
+                    ),
+                    _ => Err(unsupported),
+                }
+            }
+            _ => Err(unsupported),
+        }
+    }
+
+    /// verify the witness of a segwit v0 program, native or nested in P2SH
+    fn verify_witness_v0(
+        &self,
+        secp: &Secp256k1<All>,
+        cache: &mut SighashCache,
+        input_index: usize,
+        witness: &[Vec<u8>],
+        program: &[u8],
+    ) -> Result<(), VerifyError> {
+        let malformed = VerifyError::MalformedInput(input_index);
+        let mismatch = VerifyError::ScriptMismatch(input_index);
+
+        match (&program[2..], witness) {
+            (hash, [signature, pubkey]) if hash.len() == 20 => {
+                if hash160(pubkey) != hash {
+                    return Err(mismatch);
+                }
+                // the script code of a P2WPKH program is the matching P2PKH script
+                let mut script_code = vec![0x76, 0xa9, 0x14];
+                script_code.extend_from_slice(hash);
+                script_code.extend_from_slice(&[0x88, 0xac]);
+                self.verify_ecdsa(
+                    secp,
+                    cache,
+                    input_index,
+                    signature,
+                    pubkey,
+                    &script_code,
+                    true,
+                )
+            }
+            (hash, [stack @ .., witness_script]) if hash.len() == 32 => {
+                if sha256_hash(witness_script) != hash {
+                    return Err(mismatch);
+                }
+                match Script::new(witness_script.clone()).classify() {
+                    ScriptType::Multisig { .. } => {
+                        self.verify_multisig(secp, cache, input_index, stack, witness_script, true)
+                    }
+                    _ => Err(VerifyError::UnsupportedScript(input_index)),
+                }
+            }
+            _ => Err(malformed),
+        }
+    }
+
+    /// Verify the stack of `OP_CHECKMULTISIG`, the extra empty element it
+    /// pops then a signature for every key required, each made by a key
+    /// after the key of the one before it
+    fn verify_multisig(
+        &self,
+        secp: &Secp256k1<All>,
+        cache: &mut SighashCache,
+        input_index: usize,
+        stack: &[Vec<u8>],
+        script: &[u8],
+        segwit: bool,
+    ) -> Result<(), VerifyError> {
+        let malformed = VerifyError::MalformedInput(input_index);
+
+        let required = (script[0] - OP_1 + 1) as usize;
+        let pubkeys =
+            parse_pushes(&script[1..script.len() - 2]).ok_or_else(|| malformed.clone())?;
+        let signatures = match stack {
+            [dummy, signatures @ ..] if dummy.is_empty() => signatures,
+            _ => return Err(malformed),
+        };
+        if signatures.len() != required {
+            return Err(VerifyError::InvalidSignature(input_index));
+        }
+
+        let mut pubkeys = pubkeys.iter();
+        for signature in signatures {
+            let signed = pubkeys.any(|pubkey| {
+                self.verify_ecdsa(secp, cache, input_index, signature, pubkey, script, segwit)
+                    .is_ok()
+            });
+            if !signed {
+                return Err(VerifyError::InvalidSignature(input_index));
+            }
+        }
+        Ok(())
+    }
 
-            // num bytes in script sig
-            let bytes_hex = format!("{:02x}", sig_script.len());
-            output.push_str(&bytes_hex);
+    /// verify a DER signature, its sighash type appended, of a script code
+    #[allow(clippy::too_many_arguments)]
+    fn verify_ecdsa(
+        &self,
+        secp: &Secp256k1<All>,
+        cache: &mut SighashCache,
+        input_index: usize,
+        signature: &[u8],
+        pubkey: &[u8],
+        script_code: &[u8],
+        segwit: bool,
+    ) -> Result<(), VerifyError> {
+        let invalid = VerifyError::InvalidSignature(input_index);
 
-            // actual sig script
-            output.push_str(&hex::encode(sig_script.clone()));
+        let (sighash_type, der) = signature.split_last().ok_or_else(|| invalid.clone())?;
+        let sighash = match segwit {
+            true => cache.segwit_v0_sighash(
+                self,
+                input_index,
+                script_code,
+                self.tx_in[input_index].utxo_value,
+                *sighash_type as u32,
+            ),
+            false => legacy_sighash(self, input_index, script_code, *sighash_type as u32),
         }
+        .map_err(|_| invalid.clone())?;
+
+        let message = Message::from_slice(&sighash).map_err(|_| invalid.clone())?;
+        let der = Signature::from_der(der).map_err(|_| invalid.clone())?;
+        let pubkey = PublicKey::from_slice(pubkey).map_err(|_| invalid.clone())?;
+        secp.verify(&message, &der, &pubkey).map_err(|_| invalid)
+    }
+
+    /// Verify the Schnorr signature of a taproot key path spend, 64 bytes
+    /// for [SIGHASH_DEFAULT] or 65 with the sighash type appended,
+    /// optionally followed by an annex
+    fn verify_taproot_key_path(
+        &self,
+        secp: &Secp256k1<All>,
+        cache: &mut SighashCache,
+        input_index: usize,
+        witness: &[Vec<u8>],
+    ) -> Result<(), VerifyError> {
+        let invalid = VerifyError::InvalidSignature(input_index);
+
+        let (signature, annex) = match witness {
+            [signature] => (signature, None),
+            [signature, annex] if annex.first() == Some(&0x50) => {
+                (signature, Some(annex.as_slice()))
+            }
+            _ => return Err(VerifyError::UnsupportedScript(input_index)),
+        };
+        let (signature, sighash_type) = match signature.len() {
+            64 => (signature.as_slice(), SIGHASH_DEFAULT),
+            // the default type is only ever implied
+            65 if signature[64] != SIGHASH_DEFAULT => (&signature[..64], signature[64]),
+            _ => return Err(invalid),
+        };
 
-        // sequence
-        output.push_str("ffffffff");
+        let sighash = cache
+            .taproot_key_spend_sighash(self, input_index, sighash_type, annex)
+            .map_err(|_| invalid.clone())?;
+        let message = Message::from_slice(&sighash).map_err(|_| invalid.clone())?;
+        let signature =
+            schnorrsig::Signature::from_slice(signature).map_err(|_| invalid.clone())?;
+        let output_key =
+            schnorrsig::PublicKey::from_slice(&self.tx_in[input_index].utxo_pk_script[2..])
+                .map_err(|_| invalid.clone())?;
+        secp.schnorrsig_verify(&signature, &message, &output_key)
+            .map_err(|_| invalid)
+    }
 
-        // num outputs
-        let num_outputs = self.outputs().len();
-        let num_outputs_hex = reverse_byte_order(format!("{:02x}", num_outputs));
-        output.push_str(&num_outputs_hex);
+    /// Whether every signature of an input commits to all the inputs and
+    /// outputs, with SIGHASH_ALL or the default type of a taproot key path
+    /// spend, eg so the signatures of a proof of reserves can't be reused
+    /// with another commitment. Only the sighash types are read, the
+    /// signatures are checked by [Transaction::verify]
+    pub(crate) fn signs_all(&self, input_index: usize) -> bool {
+        let input = match self.tx_in.get(input_index) {
+            Some(input) => input,
+            None => return false,
+        };
+        let pushes = match parse_pushes(&input.signature_script) {
+            Some(pushes) => pushes,
+            None => return false,
+        };
+        // the signatures of a witness script multisig, between the dummy and the script
+        let witness_multisig = |witness: &[Vec<u8>]| match witness {
+            [_, signatures @ .., _] => ecdsa_signs_all(signatures),
+            _ => false,
+        };
+
+        match Script::new(input.utxo_pk_script.clone()).classify() {
+            ScriptType::P2pk | ScriptType::P2pkh => ecdsa_signs_all(&pushes[..pushes.len().min(1)]),
+            ScriptType::Multisig { .. } => ecdsa_signs_all(pushes.get(1..).unwrap_or_default()),
+            ScriptType::P2wpkh => ecdsa_signs_all(&input.witness[..input.witness.len().min(1)]),
+            ScriptType::P2wsh => witness_multisig(&input.witness),
+            ScriptType::P2tr => match input.witness.first() {
+                Some(signature) if signature.len() == 64 => true,
+                Some(signature) => signature.len() == 65 && signature[64] == SIGHASH_ALL as u8,
+                None => false,
+            },
+            ScriptType::P2sh => match pushes.split_last() {
+                Some((redeem_script, stack)) => match Script::new(redeem_script.clone()).classify()
+                {
+                    ScriptType::P2wpkh => {
+                        ecdsa_signs_all(&input.witness[..input.witness.len().min(1)])
+                    }
+                    ScriptType::P2wsh => witness_multisig(&input.witness),
+                    ScriptType::Multisig { .. } => {
+                        ecdsa_signs_all(stack.get(1..).unwrap_or_default())
+                    }
+                    _ => false,
+                },
+                None => false,
+            },
+            _ => false,
+        }
+    }
 
-        // outputs
-        for out in self.outputs().iter() {
-            // value
-            let value = format!("{:016x}", out.value());
-            output.push_str(&value);
+    /// hex encoded [Transaction::serialize], the format expected by `sendrawtransaction`
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.serialize())
+    }
 
-            // pk script bytes
-            let bytes_hex = format!("{:02x}", out.script_bytes());
-            output.push_str(&bytes_hex);
+    /// The fee paid by the transaction, the value of the
+    /// outputs being spent minus the value of the new outputs
+    pub fn fee(&self) -> i64 {
+        let spent: i64 = self.tx_in.iter().map(|input| input.utxo_value()).sum();
+        let created: i64 = self.tx_out.iter().map(|output| output.value()).sum();
+        spent - created
+    }
 
-            // pk script
-            output.push_str(&hex::encode(out.pk_script.clone()));
+    /// The size used for fee calculation, in virtual bytes for segwit transactions.
+    /// Unsigned transactions are estimated with [Transaction::estimate_vsize]
+    pub fn size(&self) -> u64 {
+        match self
+            .tx_in
+            .iter()
+            .all(|input| input.script_bytes() > 0 || !input.witness.is_empty())
+        {
+            true => self.weight().div_ceil(4),
+            false => self.estimate_vsize(),
         }
+    }
 
-        // locktime
-        let lock_time = format!("{:08x}", self.lock_time);
-        output.push_str(&lock_time);
+    /// the virtual size of the transaction once signed, each input sized by
+    /// the type of the output it spends, see [estimate_mixed_vsize]
+    pub fn estimate_vsize(&self) -> u64 {
+        let inputs: Vec<ScriptType> = self
+            .tx_in
+            .iter()
+            .map(|input| Script::new(input.utxo_pk_script.clone()).classify())
+            .collect();
+        estimate_mixed_vsize(&self.tx_type, &inputs, &self.tx_out)
+    }
 
-        output
+    /// The BIP141 weight of the transaction as currently serialized,
+    /// witness bytes count once and every other byte four times
+    pub fn weight(&self) -> u64 {
+        (self.encode(false).len() * 3 + self.serialize().len()) as u64
     }
 
     pub fn get_input(&self, index: usize) -> Option<&TransactionInput> {
@@ -207,9 +1193,11 @@ impl Transaction {
         self.tx_out.get(index)
     }
 
+    /// the double sha256 of the serialized transaction, in the
+    /// reversed byte order used by RPC and block explorers
+    /// witnesses are not part of the txid
     pub fn tx_id(&self) -> String {
-        // hash all tx data with sha256 twice
-        todo!()
+        hex::encode(reverse_bytes(&sha256_hash_twice(&self.encode(false))))
     }
 
     pub fn tx_type(&self) -> TransactionType {
@@ -220,6 +1208,10 @@ impl Transaction {
         self.version.clone()
     }
 
+    pub fn set_version(&mut self, version: TransactionVersion) {
+        self.version = version;
+    }
+
     pub fn inputs(&self) -> Vec<TransactionInput> {
         self.tx_in.clone()
     }
@@ -238,9 +1230,25 @@ impl Transaction {
         self.tx_out.len()
     }
 
-    pub fn lock_time(&self) -> u128 {
+    pub fn lock_time(&self) -> LockTime {
         self.lock_time
     }
+
+    pub fn set_lock_time(&mut self, lock_time: LockTime) {
+        self.lock_time = lock_time;
+    }
+
+    /// Whether the transaction can be mined in the block at a height. The
+    /// clock stands in for the median time past of the chain. As in consensus,
+    /// the lock time is ignored when every input has a [FINAL_SEQUENCE]
+    pub fn is_final(&self, height: u32, clock: &dyn Clock) -> bool {
+        self.lock_time == LockTime::default()
+            || self.lock_time.is_satisfied_by(height, clock.now())
+            || self
+                .tx_in
+                .iter()
+                .all(|input| input.sequence == FINAL_SEQUENCE)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -255,6 +1263,12 @@ pub struct TransactionInput {
     signature_script: Vec<u8>,
     // the pk_script of the utxo to be redeemed
     utxo_pk_script: Vec<u8>,
+    /// the value of the utxo to be redeemed
+    utxo_value: i64,
+    /// sequence number, [FINAL_SEQUENCE] unless replacement or relative locktimes are used
+    sequence: u32,
+    /// the witness stack of a segwit input, empty for legacy inputs
+    witness: Vec<Vec<u8>>,
 }
 
 impl TransactionInput {
@@ -265,6 +1279,9 @@ impl TransactionInput {
             // left blank until signed
             signature_script: vec![],
             utxo_pk_script: utxo.pk_script,
+            utxo_value: utxo.value,
+            sequence: FINAL_SEQUENCE,
+            witness: vec![],
         }
     }
 
@@ -272,17 +1289,51 @@ impl TransactionInput {
         self.signature_script.len()
     }
 
+    /// the script unlocking the output being spent, empty until signed
+    pub fn signature_script(&self) -> &[u8] {
+        &self.signature_script
+    }
+
+    /// the value of the output being spent
+    pub fn utxo_value(&self) -> i64 {
+        self.utxo_value
+    }
+
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    pub fn set_sequence(&mut self, sequence: u32) {
+        self.sequence = sequence;
+    }
+
+    /// set a signature script made elsewhere, eg by the receiver of a payjoin
+    /// or in a BIP322 proof
+    pub(crate) fn set_signature_script(&mut self, script: Vec<u8>) {
+        self.signature_script = script;
+    }
+
+    pub fn witness(&self) -> &Vec<Vec<u8>> {
+        &self.witness
+    }
+
     pub fn previous_output(&self) -> &OutPoint {
         &self.previous_output
     }
+
+    /// the pk script of the output being spent
+    pub fn utxo_pk_script(&self) -> &[u8] {
+        &self.utxo_pk_script
+    }
 }
 
 /// a tx can have multiple outputs so the Outpoint
 /// includes a txid and an output index to refer
 /// to a specific output
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct OutPoint {
     /// the TXID of the tx holding the output to spend
+    /// hex encoded in the byte order shown by RPC and block explorers
     hash: String,
     /// output index number of the specific output
     /// to spend from the transaction
@@ -303,12 +1354,20 @@ impl OutPoint {
     pub fn index(&self) -> i32 {
         self.index
     }
+
+    /// the txid in internal byte order followed by the little endian output index
+    pub(crate) fn serialize(&self) -> Vec<u8> {
+        // an invalid txid serializes as empty so the transaction fails to relay
+        let mut bytes = reverse_bytes(&hex::decode(&self.hash).unwrap_or_default());
+        write_i32_le(&mut bytes, self.index);
+        bytes
+    }
 }
 
 /// each output spends a certain number of sats
 /// placing them under control of anyone who can
 /// satisfy the provided pubkey script
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TransactionOutput {
     /// number of satoshis to spend
     value: i64,
@@ -324,6 +1383,21 @@ impl TransactionOutput {
                 let pk_hash = ripemd160_hash(&sha_hash);
                 format!("76a914{}88ac", hex::encode(pk_hash))
             }
+            TransactionType::NestedPay2WitnessPubKeyHash => {
+                let script_hash = hash160(&key.nested_segwit_redeem_script().unwrap());
+                format!("a914{}87", hex::encode(script_hash))
+            }
+            TransactionType::Pay2WitnessPubKeyHash => {
+                let pubkey = compress_public_key(&key.new_public_key().unwrap()).unwrap();
+                format!("0014{}", hex::encode(hash160(&pubkey)))
+            }
+            TransactionType::Pay2Taproot => {
+                format!("5120{}", hex::encode(key.taproot_output_key().unwrap()))
+            }
+            TransactionType::Pay2PubKey => {
+                let pubkey = key.new_public_key().unwrap();
+                format!("{:02x}{}ac", pubkey.len(), hex::encode(pubkey))
+            }
         };
 
         Self {
@@ -332,6 +1406,11 @@ impl TransactionOutput {
         }
     }
 
+    /// create an output locked by an already built pk script
+    pub fn from_script(value: i64, pk_script: Vec<u8>) -> Self {
+        Self { value, pk_script }
+    }
+
     pub fn value(&self) -> i64 {
         self.value
     }
@@ -339,4 +1418,18 @@ impl TransactionOutput {
     pub fn script_bytes(&self) -> usize {
         self.pk_script.len()
     }
+
+    /// the conditions which must be satisfied to spend this output
+    pub fn pk_script(&self) -> &[u8] {
+        &self.pk_script
+    }
+
+    /// the little endian value followed by the length prefixed pk script
+    pub(crate) fn serialize(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        write_i64_le(&mut bytes, self.value);
+        write_varint(&mut bytes, self.pk_script.len() as u64);
+        bytes.extend_from_slice(&self.pk_script);
+        bytes
+    }
 }
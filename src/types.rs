@@ -1,22 +1,130 @@
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use std::fmt::{self, Display};
 
-use crate::Key;
-
-/// bitcoin networks
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
-pub enum Network {
-    Mainnet,
-    Testnet,
-}
+use crate::{trace::REDACTED, Key, LockOwner, HARDENED_OFFSET};
 
 /// The output when deriving/generating new keys
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct KeyCreationOutput {
     pub mnemonic: String,
     pub key: Key,
 }
 
+/// the mnemonic is redacted
+impl fmt::Debug for KeyCreationOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyCreationOutput")
+            .field("mnemonic", &REDACTED)
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+/// What [crate::Wallet::compact] keeps of a wallet's history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// confirmations after which a transaction the wallet holds no more
+    /// outputs of is dropped from the history, none keeps the whole history
+    pub history_confirmations: Option<u32>,
+}
+
+impl Default for RetentionPolicy {
+    /// keep about two weeks of blocks
+    fn default() -> Self {
+        Self {
+            history_confirmations: Some(2016),
+        }
+    }
+}
+
+/// When a wallet was created, the chain before it can't hold its transactions
+/// and isn't scanned. Unknown for wallets restored from a mnemonic until set
+/// with [crate::Wallet::set_birthday]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Birthday {
+    /// the first block [crate::Wallet::rescan] scans
+    pub height: Option<u32>,
+    /// the unix time in seconds the wallet was created at
+    pub time: Option<u64>,
+}
+
+/// What [crate::Wallet::compact] removed from a wallet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Compaction {
+    /// transactions dropped from the history
+    pub history_pruned: usize,
+    /// key nodes dropped from the key graph
+    pub keys_dropped: usize,
+}
+
+/// A part of a wallet file, as read on its own by [crate::Wallet::from_wallet_file_lenient]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletSection {
+    /// the data directory, birthday, key compression and lock and watch-only flags
+    Settings,
+    /// the key graph, master key included
+    Keys,
+    /// the parameters the unlock key is derived with
+    Encryption,
+    Accounts,
+    Utxos,
+    History,
+    Labels,
+    /// the checksum of the file, an issue means the file was changed outside the wallet
+    Checksum,
+}
+
+/// A part of a wallet file that couldn't be read and was dropped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryIssue {
+    pub section: WalletSection,
+    /// the index of a list entry or the key of a map entry, none when the whole section was lost
+    pub entry: Option<String>,
+    pub error: String,
+}
+
+/// What [crate::Wallet::from_wallet_file_lenient] couldn't recover from a wallet file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    pub issues: Vec<RecoveryIssue>,
+}
+
+impl RecoveryReport {
+    /// whether the whole file was read
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// whether utxos or history may be missing, they're found again with [crate::Wallet::rescan]
+    pub fn needs_rescan(&self) -> bool {
+        self.issues.iter().any(|issue| {
+            matches!(
+                issue.section,
+                WalletSection::Keys
+                    | WalletSection::Accounts
+                    | WalletSection::Utxos
+                    | WalletSection::History
+            )
+        })
+    }
+
+    /// the issues of a section
+    pub fn section(&self, section: WalletSection) -> Vec<&RecoveryIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.section == section)
+            .collect()
+    }
+
+    pub(crate) fn push(&mut self, section: WalletSection, entry: Option<String>, error: String) {
+        self.issues.push(RecoveryIssue {
+            section,
+            entry,
+            error,
+        });
+    }
+}
+
 /// Generic Error type for decoding/encoding
 /// from import formats and other errors
 #[derive(Debug, Clone)]
@@ -25,9 +133,20 @@ pub enum KeyError {
     InvalidFormat,
     ChecksumMismatch,
     InvalidNetworkByte,
+    /// not the name of a [crate::Network]
+    UnknownNetwork(String),
     IndexOutOfRange,
     TooLong(String),
     BadMnemonicPhrase(String),
+    /// the passphrase doesn't decrypt an encrypted key
+    IncorrectPassphrase,
+    /// the private key is zero or not below the curve order
+    InvalidPrivateKey,
+    /// the child key at the index is invalid, derivation
+    /// should continue with the next index
+    InvalidChild(ChildNumber),
+    /// the private key was wiped, eg by a locked wallet, and can't sign
+    Wiped,
     Other(String),
 }
 
@@ -38,14 +157,23 @@ impl Display for KeyError {
             KeyError::InvalidFormat => "Key was not in a valid format".to_string(),
             KeyError::ChecksumMismatch => "Checksum verification failed".to_string(),
             KeyError::InvalidNetworkByte => "Network byte was invalid".to_string(),
+            KeyError::UnknownNetwork(name) => format!("Unknown network: {}", name),
             KeyError::TooLong(error) => format!("Key was too long in length: {}", error),
             KeyError::BadMnemonicPhrase(error) => {
                 format!("Mnemonic prhase was incorrect: {}", error)
             }
+            KeyError::IncorrectPassphrase => "Passphrase was incorrect".to_string(),
+            KeyError::InvalidPrivateKey => {
+                "Private key was zero or out of the curve order".to_string()
+            }
             KeyError::Other(error) => format!("an error occured: {}", error),
             KeyError::IndexOutOfRange => {
                 "The index used for child key derivation was too large".to_string()
             }
+            KeyError::InvalidChild(index) => {
+                format!("The child key at index {} is invalid", index)
+            }
+            KeyError::Wiped => "The private key was wiped".to_string(),
         };
         write!(f, "{}", string)
     }
@@ -58,13 +186,608 @@ pub enum WalletError {
     Uninitialized,
     Write(String),
     Read(String),
+    Locked,
+    Unencrypted,
+    AlreadyEncrypted,
+    IncorrectPassphrase,
+    Encryption(String),
+    AccountNotFound(u32),
+    AccountArchived(u32),
+    UnknownAddress(String),
+    /// an address that can't be paid to on the wallet's network
+    InvalidAddress(String),
+    /// an amount paid that isn't positive
+    InvalidAmount(i64),
+    InsufficientFunds,
+    /// the account holds no more coins worth spending than the count aimed for
+    NothingToConsolidate,
+    Transaction(TransactionError),
+    /// an input spending a coin of the wallet that it can't sign, eg a taproot key path spend through a signer
+    UnsupportedInput(usize),
+    /// a thread panicked while holding a lock on a shared wallet
+    Poisoned,
+    /// the wallet's private keys were moved out with [crate::Wallet::split_keystore]
+    WatchOnly,
+    Signer(SignerError),
+    /// the master key was moved to a [crate::KeystoreBackend] and must
+    /// be loaded with [crate::Wallet::load_master_key] to derive accounts
+    MasterKeyNotLoaded,
+    Shamir(ShamirError),
+    /// the address stored by the wallet isn't the one derived at its path
+    AddressMismatch {
+        stored: String,
+        derived: String,
+    },
+    /// the child key at the index is invalid, the next index is used instead
+    InvalidChild(ChildNumber),
+    /// the wallet file doesn't match its checksum or mac, hex encoded, or the
+    /// name of a field missing from it
+    Corrupted {
+        expected: String,
+        actual: String,
+    },
+    Backend(BackendError),
+    /// the backend's mempool wouldn't accept the transaction, see [crate::Wallet::test_accept]
+    Rejected(MempoolRejection),
+    /// no wallet of the name in the directory of a [crate::WalletManager]
+    WalletNotFound(String),
+    /// a wallet of the name is already in the directory of a [crate::WalletManager]
+    WalletExists(String),
+    /// a wallet name that isn't a plain directory name
+    InvalidWalletName(String),
+    /// another process holds the wallet file, see [crate::Wallet::open_exclusive].
+    /// None when it's held by a flush that didn't finish in time
+    FileInUse(Option<LockOwner>),
 }
 
-/// Used to determine what type of key
-/// the child will be
-pub enum ChildKeyType {
-    Normal,
-    Hardened,
+/// Errors signing with a [crate::KeystoreSigner]
+#[derive(Debug, Clone)]
+pub enum SignerError {
+    /// the signer holds no private key for the public key, in hex
+    UnknownKey(String),
+    Locked,
+    IncorrectPassphrase,
+    Encryption(String),
+    Io(String),
+    Signing(String),
+    /// an external signer refused to sign
+    Rejected(String),
+    /// the credential store failed
+    Backend(String),
+    /// a thread panicked while holding the signer's unlock key
+    Poisoned,
+}
+
+impl Display for SignerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignerError::UnknownKey(key) => write!(f, "No private key for public key {}", key),
+            SignerError::Locked => write!(f, "The keystore is locked"),
+            SignerError::IncorrectPassphrase => write!(f, "Incorrect keystore passphrase"),
+            SignerError::Encryption(error) => write!(f, "Keystore encryption failed: {}", error),
+            SignerError::Io(error) => write!(f, "Keystore file error: {}", error),
+            SignerError::Signing(error) => write!(f, "Signing failed: {}", error),
+            SignerError::Rejected(reason) => write!(f, "Signer rejected the request: {}", reason),
+            SignerError::Backend(error) => write!(f, "Credential store error: {}", error),
+            SignerError::Poisoned => write!(f, "The keystore lock was poisoned"),
+        }
+    }
+}
+
+/// Errors building or combining transactions
+#[derive(Debug, Clone)]
+pub enum TransactionError {
+    /// a transaction added to a package doesn't spend the package parent
+    NotAChild(String),
+    /// there is no input at the index
+    InputOutOfRange(usize),
+    /// the signing key couldn't be used
+    Key(String),
+    /// the sighash type is not valid for the signature being made
+    InvalidSighashType(u32),
+    /// a SIGHASH_SINGLE signature was made for an input without a matching output
+    MissingSingleOutput(usize),
+    /// the threshold or public keys can't form a multisig script
+    InvalidMultisig(String),
+    /// the output spent by the input isn't locked by the script given
+    ScriptMismatch(usize),
+    /// the signing key isn't one of the keys of the input's script
+    KeyNotInScript(usize),
+    /// the input's signature script or witness can't be parsed, or holds an invalid signature
+    MalformedSignatureScript(usize),
+    /// the input already holds enough signatures
+    MultisigComplete(usize),
+    /// a signature given for the input isn't valid for it
+    InvalidSignature(usize),
+    /// the fee in satoshis is over the highest fee accepted
+    AbsurdFee { fee: i64, limit: i64 },
+    /// the fee rate in satoshis per vbyte is over the highest rate accepted
+    AbsurdFeeRate { rate: u64, limit: u64 },
+    /// a raw transaction can't be read
+    Decode(String),
+    /// the inputs are short of the outputs and the fee by an amount in satoshis
+    InsufficientFunds(i64),
+    /// the transaction would weigh over [crate::MAX_STANDARD_TX_WEIGHT]
+    NonStandardWeight(u64),
+    /// anti fee sniping was asked for without the height of the chain tip
+    UnknownTipHeight,
+    /// the transaction isn't the one with the id expected, eg of a [crate::SigningRequest]
+    TxIdMismatch(String),
+    /// the input holds no signature
+    UnsignedInput(usize),
+    /// the fee in satoshis can't be taken from the recipients subtracting it,
+    /// none does or one would be left under the dust limit
+    FeeExceedsRecipients(i64),
+}
+
+/// Why an input of a transaction fails [crate::Transaction::verify], with the index of the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// an output spent must be given for every input, in their order
+    PrevoutCount { inputs: usize, prevouts: usize },
+    /// the input holds no signature script or witness
+    UnsignedInput(usize),
+    /// the signature script isn't push only, or the stack isn't the one the output asks for
+    MalformedInput(usize),
+    /// the key or script of the input isn't the one the output commits to
+    ScriptMismatch(usize),
+    /// a signature doesn't verify, or too few for a multisig script
+    InvalidSignature(usize),
+    /// the output spent follows a template that isn't checked, eg a taproot script path
+    UnsupportedScript(usize),
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::PrevoutCount { inputs, prevouts } => {
+                write!(f, "{} outputs spent given for {} inputs", prevouts, inputs)
+            }
+            VerifyError::UnsignedInput(index) => write!(f, "Input {} is not signed", index),
+            VerifyError::MalformedInput(index) => write!(f, "Input {} is malformed", index),
+            VerifyError::ScriptMismatch(index) => {
+                write!(f, "Input {} does not match the output it spends", index)
+            }
+            VerifyError::InvalidSignature(index) => {
+                write!(f, "Input {} has an invalid signature", index)
+            }
+            VerifyError::UnsupportedScript(index) => {
+                write!(f, "Input {} spends a script that cannot be verified", index)
+            }
+        }
+    }
+}
+
+/// Why a BIP322 proof of ownership fails [crate::verify_ownership]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofError {
+    /// not an address of the network
+    InvalidAddress(String),
+    /// the signature isn't base64 of a witness stack or a transaction
+    Malformed(String),
+    /// a full signature that isn't a transaction spending the challenge of the message
+    ChallengeMismatch,
+    /// the signature doesn't verify against the address
+    Invalid(VerifyError),
+}
+
+impl Display for ProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofError::InvalidAddress(address) => write!(f, "Invalid address {}", address),
+            ProofError::Malformed(reason) => write!(f, "Malformed signature: {}", reason),
+            ProofError::ChallengeMismatch => {
+                write!(
+                    f,
+                    "The signature does not spend the challenge of the message"
+                )
+            }
+            ProofError::Invalid(e) => write!(f, "Invalid signature: {}", e),
+        }
+    }
+}
+
+/// Why a proof of reserves fails [crate::verify_reserves]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReservesError {
+    /// not a base64 PSBT
+    InvalidPsbt(String),
+    /// the first input isn't the commitment of the message
+    ChallengeMismatch,
+    /// no coin is spent past the commitment
+    NoReserves,
+    /// the PSBT doesn't give the output spent by an input
+    MissingUtxo(usize),
+    /// an input spends an output spent by an input before it
+    DuplicateInput(usize),
+    /// the transaction doesn't pay the total of its coins to a single OP_TRUE output
+    InvalidOutput,
+    /// a coin isn't signed for by its key
+    Invalid(VerifyError),
+    /// a signature of an input doesn't sign every input and output, eg with
+    /// SIGHASH_ANYONECANPAY, and holds for any commitment
+    SighashType(usize),
+}
+
+impl Display for ReservesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReservesError::InvalidPsbt(reason) => write!(f, "Invalid PSBT: {}", reason),
+            ReservesError::ChallengeMismatch => {
+                write!(f, "The proof does not commit to the message")
+            }
+            ReservesError::NoReserves => write!(f, "The proof spends no coins"),
+            ReservesError::MissingUtxo(index) => {
+                write!(f, "No output spent given for input {}", index)
+            }
+            ReservesError::DuplicateInput(index) => {
+                write!(
+                    f,
+                    "Input {} spends an output already spent by the proof",
+                    index
+                )
+            }
+            ReservesError::InvalidOutput => {
+                write!(f, "The proof must pay its total to a single OP_TRUE output")
+            }
+            ReservesError::Invalid(e) => write!(f, "Invalid signature: {}", e),
+            ReservesError::SighashType(index) => {
+                write!(f, "Input {} is not signed with SIGHASH_ALL", index)
+            }
+        }
+    }
+}
+
+/// Errors parsing a spending policy
+#[derive(Debug, Clone)]
+pub enum PolicyError {
+    Parse(String),
+    /// not a compressed public key in hex
+    InvalidKey(String),
+    /// a threshold of zero or above the number of policies, with that number
+    InvalidThreshold(usize, usize),
+    /// a lock time of zero or with the disable bit set
+    InvalidTimelock(String),
+}
+
+impl Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyError::Parse(error) => write!(f, "Invalid policy: {}", error),
+            PolicyError::InvalidKey(key) => write!(f, "Invalid public key in policy: {}", key),
+            PolicyError::InvalidThreshold(threshold, count) => {
+                write!(f, "Threshold of {} with {} policies", threshold, count)
+            }
+            PolicyError::InvalidTimelock(lock) => write!(f, "Invalid lock time: {}", lock),
+        }
+    }
+}
+
+/// Errors reading the checksum of an output descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DescriptorError {
+    /// a character descriptors can't hold, eg a non ascii one
+    InvalidCharacter(char),
+    /// no `#` followed by the checksum
+    MissingChecksum,
+    /// a checksum that isn't 8 characters long, with its length
+    InvalidChecksumLength(usize),
+    /// the checksum doesn't match, most likely a typo in the descriptor
+    ChecksumMismatch { expected: String, found: String },
+}
+
+impl Display for DescriptorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DescriptorError::InvalidCharacter(ch) => {
+                write!(f, "Invalid character in descriptor: {:?}", ch)
+            }
+            DescriptorError::MissingChecksum => write!(f, "Descriptor has no checksum"),
+            DescriptorError::InvalidChecksumLength(length) => {
+                write!(f, "Descriptor checksum of {} characters, not 8", length)
+            }
+            DescriptorError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "Descriptor checksum {} doesn't match, expected {}",
+                found, expected
+            ),
+        }
+    }
+}
+
+/// Errors aggregating keys or signing with MuSig2
+#[derive(Debug, Clone)]
+pub enum MusigError {
+    /// not a compressed public key or private key, or keys aggregating to infinity
+    InvalidKey(String),
+    /// a public nonce that can't be read, or nonces cancelling out
+    InvalidNonce(String),
+    InvalidSignature(String),
+    /// the partial signature of the signer at this index doesn't verify
+    InvalidPartialSignature(usize),
+    /// a key, or the key of a nonce, that isn't one of the group
+    UnknownSigner(String),
+}
+
+impl Display for MusigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MusigError::InvalidKey(error) => write!(f, "Invalid MuSig2 key: {}", error),
+            MusigError::InvalidNonce(error) => write!(f, "Invalid MuSig2 nonce: {}", error),
+            MusigError::InvalidSignature(error) => write!(f, "Invalid MuSig2 signature: {}", error),
+            MusigError::InvalidPartialSignature(index) => {
+                write!(f, "Partial signature of signer {} is invalid", index)
+            }
+            MusigError::UnknownSigner(key) => write!(f, "Key {} is not a signer", key),
+        }
+    }
+}
+
+/// Errors sending a payjoin (BIP78) with [crate::Wallet::send_payjoin]
+#[derive(Debug)]
+pub enum PayjoinError {
+    /// an endpoint that can't be read, or plain http to a host that isn't an onion service
+    InvalidEndpoint(String),
+    /// an original transaction that can't be sent as a payjoin, eg with an unsigned input
+    InvalidOriginal(String),
+    /// a PSBT that can't be read
+    InvalidPsbt(String),
+    /// the receiver refused the original, with the error code of BIP78, eg `not-enough-money`
+    Receiver {
+        code: String,
+        message: String,
+    },
+    /// a proposal the sender must not sign, the original is broadcast instead
+    InvalidProposal(String),
+    Connection(BackendError),
+    Wallet(WalletError),
+}
+
+impl Display for PayjoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayjoinError::InvalidEndpoint(endpoint) => {
+                write!(f, "Invalid payjoin endpoint: {}", endpoint)
+            }
+            PayjoinError::InvalidOriginal(error) => {
+                write!(f, "Cannot send as a payjoin: {}", error)
+            }
+            PayjoinError::InvalidPsbt(error) => write!(f, "Invalid PSBT: {}", error),
+            PayjoinError::Receiver { code, message } => {
+                write!(f, "Payjoin receiver error {}: {}", code, message)
+            }
+            PayjoinError::InvalidProposal(error) => {
+                write!(f, "Invalid payjoin proposal: {}", error)
+            }
+            PayjoinError::Connection(error) => write!(f, "{}", error),
+            PayjoinError::Wallet(error) => write!(f, "Wallet error: {:?}", error),
+        }
+    }
+}
+
+/// Errors splitting or recovering a secret with SLIP-39 shares
+#[derive(Debug, Clone)]
+pub enum ShamirError {
+    /// a word that isn't in the SLIP-39 wordlist
+    InvalidWord(String),
+    InvalidChecksum,
+    InvalidPadding,
+    /// a mnemonic too short or holding a secret of the wrong length, with its word count
+    InvalidLength(usize),
+    /// a secret shorter than 16 bytes or of odd length, with its length
+    InvalidSecret(usize),
+    /// a threshold of zero or above the share count, with that count
+    InvalidThreshold(u8, u8),
+    /// shares from different secrets, or one share given twice
+    MismatchedShares(String),
+    InsufficientShares(String),
+    /// the recovered secret doesn't match its digest, a share is corrupt
+    InvalidDigest,
+}
+
+impl Display for ShamirError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShamirError::InvalidWord(word) => write!(f, "Not a SLIP-39 word: {}", word),
+            ShamirError::InvalidChecksum => write!(f, "Share checksum verification failed"),
+            ShamirError::InvalidPadding => write!(f, "Share has invalid padding"),
+            ShamirError::InvalidLength(words) => write!(f, "Share of {} words is invalid", words),
+            ShamirError::InvalidSecret(length) => {
+                write!(f, "Cannot share a secret of {} bytes", length)
+            }
+            ShamirError::InvalidThreshold(threshold, count) => {
+                write!(f, "Threshold of {} with {} shares", threshold, count)
+            }
+            ShamirError::MismatchedShares(error) => write!(f, "Shares do not match: {}", error),
+            ShamirError::InsufficientShares(error) => write!(f, "Not enough shares: {}", error),
+            ShamirError::InvalidDigest => write!(f, "Share digest verification failed"),
+        }
+    }
+}
+
+/// Errors talking to a [crate::Backend]
+#[derive(Debug, Clone)]
+pub enum BackendError {
+    /// the backend can't perform the operation
+    Unsupported(String),
+    Connection(String),
+    InvalidResponse(String),
+    /// the node rejected the request
+    Rpc {
+        code: i64,
+        message: String,
+    },
+}
+
+impl Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendError::Unsupported(operation) => {
+                write!(f, "The backend does not support {}", operation)
+            }
+            BackendError::Connection(error) => write!(f, "Connection failed: {}", error),
+            BackendError::InvalidResponse(response) => {
+                write!(f, "Unexpected response from backend: {}", response)
+            }
+            BackendError::Rpc { code, message } => write!(f, "RPC error {}: {}", code, message),
+        }
+    }
+}
+
+/// Why a mempool refused a transaction, read from the reject reasons of
+/// Bitcoin Core, eg `min relay fee not met`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MempoolRejection {
+    /// the fee is under the relay fee, the mempool minimum, or the fee of
+    /// the transactions it would replace
+    InsufficientFee(String),
+    /// an output is worth less than it costs to spend
+    Dust,
+    /// outside the standardness rules of the node, with the rule broken, eg `tx-size`
+    NonStandard(String),
+    /// the lock time or sequences of the inputs aren't met yet
+    NonFinal,
+    /// an input spends an output the node doesn't know, or that's already spent
+    MissingInputs,
+    /// an input is spent by a transaction in the mempool it can't replace
+    Conflict,
+    /// the transaction is already in the mempool
+    AlreadyKnown,
+    /// a signature or script doesn't verify, with the reason of the node
+    InvalidScript(String),
+    /// the fee is over the maximum the node was asked to accept
+    FeeTooHigh,
+    Other(String),
+}
+
+impl MempoolRejection {
+    /// the rejection of a `reject-reason` or the message of a rejected `sendrawtransaction`
+    pub fn from_reason(reason: &str) -> Self {
+        let reason = reason.trim();
+        match reason {
+            "min relay fee not met" | "mempool min fee not met" | "insufficient fee" => {
+                MempoolRejection::InsufficientFee(reason.to_string())
+            }
+            "dust" => MempoolRejection::Dust,
+            "non-final" | "non-BIP68-final" => MempoolRejection::NonFinal,
+            "missing-inputs" | "bad-txns-inputs-missingorspent" => MempoolRejection::MissingInputs,
+            "txn-mempool-conflict" => MempoolRejection::Conflict,
+            "txn-already-in-mempool"
+            | "txn-already-known"
+            | "txn-same-nonwitness-data-in-mempool" => MempoolRejection::AlreadyKnown,
+            "absurdly-high-fee" | "max-fee-exceeded" => MempoolRejection::FeeTooHigh,
+            "version"
+            | "tx-size"
+            | "tx-size-small"
+            | "scriptsig-size"
+            | "scriptsig-not-pushonly"
+            | "scriptpubkey"
+            | "bare-multisig"
+            | "multi-op-return"
+            | "bad-txns-nonstandard-inputs"
+            | "bad-witness-nonstandard" => MempoolRejection::NonStandard(reason.to_string()),
+            // eg `mandatory-script-verify-flag-failed (Signature must be zero for failed CHECK(MULTI)SIG operation)`
+            _ if reason.contains("script-verify-flag-failed") => {
+                MempoolRejection::InvalidScript(reason.to_string())
+            }
+            _ => MempoolRejection::Other(reason.to_string()),
+        }
+    }
+}
+
+impl Display for MempoolRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MempoolRejection::InsufficientFee(reason) => write!(f, "Fee too low: {}", reason),
+            MempoolRejection::Dust => write!(f, "An output is dust"),
+            MempoolRejection::NonStandard(rule) => write!(f, "Non standard transaction: {}", rule),
+            MempoolRejection::NonFinal => write!(f, "The transaction is not final yet"),
+            MempoolRejection::MissingInputs => write!(f, "Inputs missing or already spent"),
+            MempoolRejection::Conflict => write!(f, "Conflicts with a transaction in the mempool"),
+            MempoolRejection::AlreadyKnown => write!(f, "Already in the mempool"),
+            MempoolRejection::InvalidScript(reason) => write!(f, "Invalid script: {}", reason),
+            MempoolRejection::FeeTooHigh => write!(f, "Fee over the maximum accepted"),
+            MempoolRejection::Other(reason) => write!(f, "Rejected: {}", reason),
+        }
+    }
+}
+
+/// The index of a child key, normal or hardened. Both take indexes
+/// from 0 to 2^31 - 1, hardened ones are serialized with the
+/// [HARDENED_OFFSET] bit set as BIP32 does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "u32", into = "u32")]
+pub enum ChildNumber {
+    /// a child whose public key can be derived from the public key of its parent
+    Normal(u32),
+    /// a child only derivable from the private key of its parent
+    Hardened(u32),
+}
+
+impl ChildNumber {
+    /// a normal child, failing for indexes with the hardened bit set
+    pub fn normal(index: u32) -> Result<Self, KeyError> {
+        match index < HARDENED_OFFSET {
+            true => Ok(ChildNumber::Normal(index)),
+            false => Err(KeyError::IndexOutOfRange),
+        }
+    }
+
+    /// a hardened child at an index of the hardened range, 0 being `0'`
+    pub fn hardened(index: u32) -> Result<Self, KeyError> {
+        match index < HARDENED_OFFSET {
+            true => Ok(ChildNumber::Hardened(index)),
+            false => Err(KeyError::IndexOutOfRange),
+        }
+    }
+
+    /// the index within the normal or hardened range
+    pub fn index(&self) -> u32 {
+        match self {
+            ChildNumber::Normal(index) | ChildNumber::Hardened(index) => *index,
+        }
+    }
+
+    pub fn is_hardened(&self) -> bool {
+        matches!(self, ChildNumber::Hardened(_))
+    }
+
+    /// the child number as serialized, failing for an index out of its range
+    pub fn to_u32(&self) -> Result<u32, KeyError> {
+        match self.index() < HARDENED_OFFSET {
+            true => Ok(u32::from(*self)),
+            false => Err(KeyError::IndexOutOfRange),
+        }
+    }
+}
+
+/// read a serialized child number, hardened when the [HARDENED_OFFSET] bit is set
+impl From<u32> for ChildNumber {
+    fn from(number: u32) -> Self {
+        match number < HARDENED_OFFSET {
+            true => ChildNumber::Normal(number),
+            false => ChildNumber::Hardened(number - HARDENED_OFFSET),
+        }
+    }
+}
+
+impl From<ChildNumber> for u32 {
+    fn from(child: ChildNumber) -> Self {
+        match child {
+            ChildNumber::Normal(index) => index,
+            ChildNumber::Hardened(index) => index | HARDENED_OFFSET,
+        }
+    }
+}
+
+/// written as in a derivation path, `0` or `0'`
+impl Display for ChildNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChildNumber::Normal(index) => write!(f, "{}", index),
+            ChildNumber::Hardened(index) => write!(f, "{}'", index),
+        }
+    }
 }
 
 /// The type of key
@@ -73,6 +796,8 @@ pub enum KeyType {
     Master,
     Normal,
     Hardened,
+    /// a key imported on its own, not derived from the master key
+    Imported,
 }
 
 /// An HD Key pair that can derive children keys
@@ -81,5 +806,8 @@ pub struct KeyPair {
     pub private_key: Key,
     pub public_key: Vec<u8>,
     pub key_type: KeyType,
-    pub index: Option<usize>,
+    pub index: Option<ChildNumber>,
+    /// the sealed private key while the wallet is locked
+    #[serde(default)]
+    pub encrypted_private_key: Option<Vec<u8>>,
 }
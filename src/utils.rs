@@ -1,36 +1,136 @@
 use hmac_sha512::HMAC;
-use rand::prelude::*;
 use ripemd160::Ripemd160;
 use sha2::{Digest, Sha256, Sha512};
 
+use crate::{EntropySource, KeyError, OsEntropy, TransactionError};
+
 #[inline]
 #[doc(hidden)]
 pub fn get_random_bytes(num_bytes: usize) -> Vec<u8> {
-    let rng = thread_rng();
-    let mut rng = rand::rngs::StdRng::from_rng(rng).unwrap();
-    let mut buf = vec![0; num_bytes];
-    rng.fill(buf.as_mut_slice());
-    buf
+    OsEntropy.random_bytes(num_bytes)
+}
+
+/// compare two byte strings in a time depending on their lengths only, not
+/// on where they differ, for secrets
+#[inline]
+#[doc(hidden)]
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// the bytes in reverse order, eg a txid between the order it's displayed
+/// in and the order it's serialized in
+#[inline]
+#[doc(hidden)]
+pub fn reverse_bytes(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().rev().copied().collect()
 }
 
+/// reverse the bytes of a hex string, see [reverse_bytes]
 #[inline]
 #[doc(hidden)]
 pub fn reverse_byte_order(s: String) -> String {
-    let string_to_bytes = hex::decode(s).unwrap();
-    let mut bytes_reversed = Vec::new();
+    hex::encode(reverse_bytes(&hex::decode(s).unwrap()))
+}
 
-    for i in string_to_bytes.iter().rev() {
-        bytes_reversed.push(*i);
+/// append a bitcoin compact size unsigned integer, one byte below 0xfd
+/// and a marker followed by 2, 4 or 8 little endian bytes above
+#[inline]
+#[doc(hidden)]
+pub fn write_varint(bytes: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => bytes.push(value as u8),
+        0xfd..=0xffff => {
+            bytes.push(0xfd);
+            write_u16_le(bytes, value as u16);
+        }
+        0x10000..=0xffffffff => {
+            bytes.push(0xfe);
+            write_u32_le(bytes, value as u32);
+        }
+        _ => {
+            bytes.push(0xff);
+            write_u64_le(bytes, value);
+        }
     }
+}
+
+/// Read a compact size unsigned integer from the start of some bytes,
+/// returning it with the number of bytes it took. Values written with
+/// more bytes than needed are refused, as Bitcoin Core does
+#[inline]
+#[doc(hidden)]
+pub fn read_varint(bytes: &[u8]) -> Result<(u64, usize), TransactionError> {
+    let read = |width: usize| {
+        let mut value = [0; 8];
+        value[..width].copy_from_slice(bytes.get(1..1 + width).ok_or_else(|| {
+            TransactionError::Decode(format!(
+                "a compact size of {} bytes, {} given",
+                1 + width,
+                bytes.len()
+            ))
+        })?);
+        Ok::<_, TransactionError>((u64::from_le_bytes(value), 1 + width))
+    };
+
+    let (value, length, minimum) = match bytes.first() {
+        None => {
+            return Err(TransactionError::Decode(
+                "a compact size wanted, no bytes given".to_string(),
+            ))
+        }
+        Some(0xfd) => read(2).map(|(value, length)| (value, length, 0xfd))?,
+        Some(0xfe) => read(4).map(|(value, length)| (value, length, 0x10000))?,
+        Some(0xff) => read(8).map(|(value, length)| (value, length, 0x100000000))?,
+        Some(value) => return Ok((*value as u64, 1)),
+    };
+    match value >= minimum {
+        true => Ok((value, length)),
+        false => Err(TransactionError::Decode(format!(
+            "{} written as a compact size of {} bytes",
+            value, length
+        ))),
+    }
+}
+
+#[inline]
+#[doc(hidden)]
+pub fn write_u16_le(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+#[doc(hidden)]
+pub fn write_u32_le(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+#[doc(hidden)]
+pub fn write_i32_le(bytes: &mut Vec<u8>, value: i32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+#[doc(hidden)]
+pub fn write_u64_le(bytes: &mut Vec<u8>, value: u64) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
 
-    hex::encode(bytes_reversed)
+#[inline]
+#[doc(hidden)]
+pub fn write_i64_le(bytes: &mut Vec<u8>, value: i64) {
+    bytes.extend_from_slice(&value.to_le_bytes());
 }
 
 #[inline]
 #[doc(hidden)]
 pub fn sha256_hash_twice(input: &Vec<u8>) -> Vec<u8> {
     let mut hasher = Sha256::new();
-    hasher.update(&input);
+    hasher.update(input);
     let hash = hasher.finalize();
 
     let mut hasher = Sha256::new();
@@ -71,3 +171,48 @@ pub fn hmac_sha512_hash(input: &Vec<u8>, key: &Vec<u8>) -> Vec<u8> {
     let digest = HMAC::mac(input, key);
     digest.to_vec()
 }
+
+#[inline]
+#[doc(hidden)]
+pub fn hash160(input: &Vec<u8>) -> Vec<u8> {
+    ripemd160_hash(&sha256_hash(input))
+}
+
+#[inline]
+#[doc(hidden)]
+pub fn base58check_encode(input: &[u8]) -> String {
+    let mut bytes = input.to_vec();
+    let checksum = sha256_hash_twice(&bytes);
+    bytes.extend_from_slice(&checksum[..4]);
+    bs58::encode(bytes).into_string()
+}
+
+/// decode a base58 string and check its trailing four byte checksum, which is dropped
+#[inline]
+#[doc(hidden)]
+pub fn base58check_decode(input: &str) -> Result<Vec<u8>, KeyError> {
+    let mut bytes = bs58::decode(input)
+        .into_vec()
+        .map_err(|_| KeyError::Decode)?;
+    if bytes.len() < 4 {
+        return Err(KeyError::InvalidFormat);
+    }
+
+    let checksum = bytes.split_off(bytes.len() - 4);
+    match sha256_hash_twice(&bytes)[..4] == checksum[..] {
+        true => Ok(bytes),
+        false => Err(KeyError::ChecksumMismatch),
+    }
+}
+
+#[inline]
+#[doc(hidden)]
+pub fn tagged_hash(tag: &str, input: &[u8]) -> Vec<u8> {
+    let tag_hash = sha256_hash(&tag.as_bytes().to_vec());
+
+    let mut hasher = Sha256::new();
+    hasher.update(&tag_hash);
+    hasher.update(&tag_hash);
+    hasher.update(input);
+    hasher.finalize().to_vec()
+}
@@ -0,0 +1,395 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{OutPoint, Script, ScriptType, Transaction, TransactionInput, TransactionOutput};
+
+/// confirmations before the output of a coinbase transaction can be spent
+pub const COINBASE_MATURITY: u32 = 100;
+
+/// An unspent output paying to one of the wallet's addresses
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Utxo {
+    outpoint: OutPoint,
+    output: TransactionOutput,
+    address: String,
+    /// the height of the block confirming the output
+    #[serde(default)]
+    height: Option<u32>,
+    /// whether the output was minted by a coinbase transaction
+    #[serde(default)]
+    coinbase: bool,
+}
+
+impl Utxo {
+    pub fn new(outpoint: OutPoint, output: TransactionOutput, address: String) -> Self {
+        Self {
+            outpoint,
+            output,
+            address,
+            height: None,
+            coinbase: false,
+        }
+    }
+
+    /// the transaction and output index holding the coins
+    pub fn outpoint(&self) -> &OutPoint {
+        &self.outpoint
+    }
+
+    /// the output being spent
+    pub fn output(&self) -> &TransactionOutput {
+        &self.output
+    }
+
+    /// the template of the script of the output, telling how it's spent
+    pub fn script_type(&self) -> ScriptType {
+        Script::new(self.output.pk_script().to_vec()).classify()
+    }
+
+    /// the wallet address the output pays to
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// number of satoshis held by the output
+    pub fn value(&self) -> i64 {
+        self.output.value()
+    }
+
+    /// the height of the block confirming the output, none while in the mempool
+    pub fn height(&self) -> Option<u32> {
+        self.height
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.height.is_some()
+    }
+
+    pub(crate) fn confirm(&mut self, height: u32) {
+        self.height = Some(height);
+    }
+
+    /// whether the output was minted by a coinbase transaction, a block reward
+    pub fn is_coinbase(&self) -> bool {
+        self.coinbase
+    }
+
+    pub(crate) fn set_coinbase(&mut self) {
+        self.coinbase = true;
+    }
+
+    /// Whether the output can be spent in the block after the tip, at a
+    /// height. Coinbase outputs need [COINBASE_MATURITY] confirmations first
+    pub fn is_mature(&self, tip_height: u32) -> bool {
+        match (self.coinbase, self.height) {
+            (false, _) => true,
+            (true, Some(height)) => tip_height.saturating_sub(height) + 1 >= COINBASE_MATURITY,
+            (true, None) => false,
+        }
+    }
+
+    /// create an unsigned transaction input spending this output
+    pub fn to_input(&self) -> TransactionInput {
+        TransactionInput::new(
+            self.output.clone(),
+            self.outpoint.hash(),
+            self.outpoint.index(),
+        )
+    }
+}
+
+/// The coins of a wallet by whether they can be spent, see [crate::Wallet::balance]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Balance {
+    /// satoshis of confirmed coins
+    pub confirmed: i64,
+    /// satoshis of coins still in the mempool
+    pub unconfirmed: i64,
+    /// satoshis of coinbase outputs under [COINBASE_MATURITY] confirmations,
+    /// which can't be spent yet
+    pub immature: i64,
+}
+
+impl Balance {
+    /// satoshis coin selection can spend, confirmed or not
+    pub fn spendable(&self) -> i64 {
+        self.confirmed + self.unconfirmed
+    }
+
+    pub fn total(&self) -> i64 {
+        self.spendable() + self.immature
+    }
+}
+
+/// A self-spend of [crate::Wallet::consolidate] merging small coins, with
+/// what it costs and the coins the account is left with
+#[derive(Debug, Clone)]
+pub struct Consolidation {
+    /// the unsigned transaction paying the coins spent to a change address
+    pub tx: Transaction,
+    /// the coins merged, smallest first
+    pub spent: Vec<Utxo>,
+    /// the coins of the account left as they are
+    pub kept: Vec<Utxo>,
+    /// satoshis of the output the coins are merged into
+    pub merged: i64,
+    /// satoshis paid in fees
+    pub fee: i64,
+}
+
+impl Consolidation {
+    /// how many coins the account holds once the transaction confirms
+    pub fn utxo_count(&self) -> usize {
+        self.kept.len() + 1
+    }
+}
+
+/// A transaction paying to or spending from the wallet, found by [crate::Wallet::rescan]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TxRecord {
+    tx_id: String,
+    /// the height of the block confirming the transaction
+    height: Option<u32>,
+    /// satoshis paid to the wallet's addresses
+    received: i64,
+    /// satoshis of the wallet's outputs spent
+    sent: i64,
+    /// satoshis paid in fees, only known when the wallet funded every input
+    #[serde(default)]
+    fee: Option<i64>,
+    /// the first address paid outside the wallet by a transaction spending from it
+    #[serde(default)]
+    counterparty: Option<String>,
+    /// the wallet's addresses paid by the transaction
+    #[serde(default)]
+    addresses: Vec<String>,
+    /// set once a transaction spending the same coins confirmed
+    #[serde(default)]
+    conflicted: bool,
+    /// satoshis of `received` paid to the change chains of the wallet
+    #[serde(default)]
+    change: i64,
+    /// the timestamp of the block confirming the transaction, unix seconds
+    #[serde(default)]
+    time: Option<u64>,
+    /// the virtual size of the transaction, known for those the wallet archived
+    #[serde(default)]
+    vsize: Option<u64>,
+}
+
+impl TxRecord {
+    pub(crate) fn new(
+        tx_id: String,
+        height: Option<u32>,
+        received: i64,
+        sent: i64,
+        fee: Option<i64>,
+        counterparty: Option<String>,
+        addresses: Vec<String>,
+    ) -> Self {
+        Self {
+            tx_id,
+            height,
+            received,
+            sent,
+            fee,
+            counterparty,
+            addresses,
+            conflicted: false,
+            change: 0,
+            time: None,
+            vsize: None,
+        }
+    }
+
+    pub fn tx_id(&self) -> &str {
+        &self.tx_id
+    }
+
+    /// the height of the block confirming the transaction, none while in the mempool
+    pub fn height(&self) -> Option<u32> {
+        self.height
+    }
+
+    pub fn received(&self) -> i64 {
+        self.received
+    }
+
+    pub fn sent(&self) -> i64 {
+        self.sent
+    }
+
+    /// The timestamp of the block confirming the transaction, unix seconds.
+    /// None while in the mempool, or when confirmed by [crate::Wallet::confirm_transaction]
+    pub fn time(&self) -> Option<u64> {
+        self.time
+    }
+
+    /// how much the transaction changed the wallet's balance by
+    pub fn net(&self) -> i64 {
+        self.received - self.sent
+    }
+
+    /// satoshis paid back to an address of the change chain of an account,
+    /// see [crate::Wallet::is_change]
+    pub fn change(&self) -> i64 {
+        self.change
+    }
+
+    /// The satoshis a spend paid, what it spent less its change and fee,
+    /// the amount to show as sent. Payments to receive addresses of the
+    /// wallet count, they move coins between its accounts
+    pub fn paid(&self) -> i64 {
+        (self.sent - self.change - self.fee.unwrap_or_default()).max(0)
+    }
+
+    /// satoshis paid in fees, only known when the wallet funded every input
+    pub fn fee(&self) -> Option<i64> {
+        self.fee
+    }
+
+    /// the virtual size of the transaction, only known for those sent with
+    /// [crate::Wallet::archive_transaction] before they confirmed
+    pub fn vsize(&self) -> Option<u64> {
+        self.vsize
+    }
+
+    /// the fee paid in satoshis per virtual byte, when the fee and size are known
+    pub fn fee_rate(&self) -> Option<f64> {
+        Some(self.fee? as f64 / self.vsize? as f64)
+    }
+
+    /// the first address paid outside the wallet by a transaction spending from it
+    pub fn counterparty(&self) -> Option<&str> {
+        self.counterparty.as_deref()
+    }
+
+    /// the wallet's addresses paid by the transaction
+    pub fn addresses(&self) -> &Vec<String> {
+        &self.addresses
+    }
+
+    /// whether a transaction spending the same coins confirmed, this one never will
+    pub fn is_conflicted(&self) -> bool {
+        self.conflicted
+    }
+
+    pub(crate) fn confirm(&mut self, height: u32) {
+        self.height = Some(height);
+    }
+
+    pub(crate) fn mark_conflicted(&mut self) {
+        self.conflicted = true;
+    }
+
+    pub(crate) fn set_change(&mut self, change: i64) {
+        self.change = change;
+    }
+
+    pub(crate) fn set_time(&mut self, time: u64) {
+        self.time = Some(time);
+    }
+
+    pub(crate) fn set_vsize(&mut self, vsize: u64) {
+        self.vsize = Some(vsize);
+    }
+}
+
+/// A signed transaction of the wallet waiting to confirm, rebroadcast by
+/// [crate::Wallet::rebroadcast_pending] in case mempools dropped it
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SignedTx {
+    tx_id: String,
+    /// the hex encoded transaction
+    raw_tx: String,
+    /// the outputs it spends, to tell once another transaction spending them confirmed
+    inputs: Vec<OutPoint>,
+}
+
+impl SignedTx {
+    pub(crate) fn new(tx: &Transaction) -> Self {
+        Self {
+            tx_id: tx.tx_id(),
+            raw_tx: tx.to_hex(),
+            inputs: tx
+                .inputs()
+                .iter()
+                .map(|input| input.previous_output().clone())
+                .collect(),
+        }
+    }
+
+    pub fn tx_id(&self) -> &str {
+        &self.tx_id
+    }
+
+    pub fn raw_tx(&self) -> &str {
+        &self.raw_tx
+    }
+
+    pub fn inputs(&self) -> &[OutPoint] {
+        &self.inputs
+    }
+}
+
+/// An output of the wallet spent by a transaction. Spends are tracked by
+/// the outpoint spent, so a fee bump (RBF) or a malleated copy of a
+/// transaction is known to spend the same coins under another txid
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Spend {
+    utxo: Utxo,
+    tx_id: String,
+    /// the height of the block confirming the spending transaction
+    #[serde(default)]
+    height: Option<u32>,
+    /// set once another transaction spending the output confirmed
+    #[serde(default)]
+    conflicted: bool,
+}
+
+impl Spend {
+    pub(crate) fn new(utxo: Utxo, tx_id: String) -> Self {
+        Self {
+            utxo,
+            tx_id,
+            height: None,
+            conflicted: false,
+        }
+    }
+
+    /// the output spent
+    pub fn outpoint(&self) -> &OutPoint {
+        self.utxo.outpoint()
+    }
+
+    /// the output spent, as it was tracked before
+    pub fn utxo(&self) -> &Utxo {
+        &self.utxo
+    }
+
+    /// the transaction spending the output
+    pub fn tx_id(&self) -> &str {
+        &self.tx_id
+    }
+
+    /// the height of the block confirming the spending transaction, none while in the mempool
+    pub fn height(&self) -> Option<u32> {
+        self.height
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.height.is_some()
+    }
+
+    /// whether another transaction spending the output confirmed, this one never will
+    pub fn is_conflicted(&self) -> bool {
+        self.conflicted
+    }
+
+    pub(crate) fn set_height(&mut self, height: Option<u32>) {
+        self.height = height;
+    }
+
+    pub(crate) fn mark_conflicted(&mut self) {
+        self.conflicted = true;
+    }
+}
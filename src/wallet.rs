@@ -1,128 +1,4463 @@
-use std::{fs, path::PathBuf};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+use hmac::{Hmac, Mac, NewMac};
+use libarena::{Arena, Node};
+use secp256k1::{All, Secp256k1};
+use serde::{de::DeserializeOwned, Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use crate::{
+    bip322_to_sign, bip322_to_spend, combine_shares, compress_public_key, decode_transaction,
+    decode_transaction_with, decompress_public_key, decrypt, deserialize_arena,
+    encode_bip322_signature, encode_psbt, encrypt, estimate_mixed_vsize, generate_mnemonic_with,
+    hash160, key_fingerprint, keyindex::KeyIndex, lock_for_flush, mnemonic_to_seed,
+    parse_core_dump, reserves_transaction, serialize_arena, serialize_xpub, spends_witness,
+    split_secret, trace::REDACTED, validate_addresses, write_rows, Account, AccountReport,
+    AccountStats, AccountType, AccountView, AccountXpub, AddressReport, AddressValidation,
+    AgeBucket, AnnotatedInput, AnnotatedOutput, AnnotatedTransaction, ArenaNode, Backend,
+    BackendError, Balance, Birthday, BlockId, BlockTransaction, CacheFormat, Chain, ChildNumber,
+    Clock, Compaction, Consolidation, CoreDumpImport, Currency, Decimal, Digest32,
+    EncryptionParams, EntropySource, EventSink, EventSinks, ExportFormat, FeeBump, FeeLimits,
+    FiatHistoryRow, HistoryRow, InputSignature, KdfParams, Key, KeyCache, KeyCacheStatus,
+    KeyCreationOutput, KeyError, KeyPair, KeyRecord, KeyRecords, KeyType, KeyView, KeystoreBackend,
+    KeystoreSigner, MasterKeyDerivation, MemorySigner, MempoolAcceptance, MempoolRejection,
+    Network, OsEntropy, OutPoint, OwnershipProof, PaperWallet, PreviewInput, PreviewOutput,
+    RateProvider, ReadOnlyWallet, Rebroadcast, Recipient, RecoveryReport, ReserveProof,
+    RetentionPolicy, Script, ScriptType, SigHashType, SighashCache, SighashMode, SignedTx,
+    SignerError, SigningRequest, SigningResponse, SilentPaymentAddress, SilentPaymentKeys,
+    SilentPaymentOutput, SkippedEntry, Spend, SpendPreview, StatsInterval, SyncDiff, SystemClock,
+    Transaction, TransactionBuilder, TransactionError, TransactionOutput, TxRecord, TxSummary,
+    TxWatch, TxWatches, Utxo, UtxoRow, WalletConfig, WalletError, WalletEvent, WalletLock,
+    WalletSection, WalletSnapshot, WalletStats, INPUT_BASE_WEIGHT, LARGEST_UTXOS,
+    MAX_STANDARD_TX_WEIGHT, OP_CHECKSIG, OP_DUP, OP_EQUALVERIFY, OP_HASH160, UTXO_AGE_BUCKETS,
+};
+#[cfg(feature = "payjoin")]
+use crate::{PayjoinClient, PayjoinError, PayjoinParams, PayjoinRequest};
+
+/// A bitcoin hardened wallet
+/// keys are stored in a graph using arena allocation
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Wallet {
+    network: Network,
+    /// the version of the file the wallet was read from, see [WALLET_VERSION]
+    #[serde(default)]
+    version: u32,
+    /// how the master key was derived from the mnemonic, see [Wallet::migrate]
+    #[serde(default = "bip32_derivation")]
+    master_key_derivation: MasterKeyDerivation,
+    /// where the wallet is flushed to, older files hold a single `path`
+    #[serde(alias = "path")]
+    config: WalletConfig,
+    compress_public_keys: bool,
+    /// written as [KeyRecords], older files hold the arena itself
+    #[serde(
+        rename = "keys",
+        alias = "arena",
+        serialize_with = "serialize_arena",
+        deserialize_with = "deserialize_arena"
+    )]
+    arena: Arena<KeyPair, String>,
+    /// the nodes of the arena by address, script, public key and fingerprint
+    #[serde(skip)]
+    key_index: KeyIndex,
+    encrypted: bool,
+    #[serde(default)]
+    encryption: Option<EncryptionParams>,
+    #[serde(default)]
+    locked: bool,
+    /// the key sealing private keys, only held while unlocked
+    #[serde(skip)]
+    unlock_key: Option<[u8; 32]>,
+    /// the key of the mac of the wallet file, kept once unlocked so a locked
+    /// wallet still authenticates the files it writes
+    #[serde(skip)]
+    mac_key: Option<[u8; 32]>,
+    #[serde(default)]
+    accounts: Vec<Account>,
+    /// flushed to the cache file, read from the wallet file of older versions
+    #[serde(default, skip_serializing)]
+    utxos: Vec<Utxo>,
+    /// transactions found paying to or spending from the wallet
+    #[serde(default, skip_serializing)]
+    history: Vec<TxRecord>,
+    /// the outputs of the wallet spent, by outpoint, flushed to the cache file
+    #[serde(default, skip_serializing)]
+    spends: Vec<Spend>,
+    /// the signed transactions waiting to confirm, flushed to the cache file
+    #[serde(default, skip_serializing)]
+    archive: Vec<SignedTx>,
+    /// the silent payments received and not spent, flushed to the cache file
+    #[serde(default, skip_serializing)]
+    silent_payment_outputs: Vec<SilentPaymentOutput>,
+    /// the last [KEPT_BLOCK_HASHES] blocks scanned, oldest first, flushed to the cache file
+    #[serde(default, skip_serializing)]
+    scanned_blocks: Vec<BlockId>,
+    /// the labels given to addresses with [Wallet::set_label]
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    birthday: Birthday,
+    #[serde(default)]
+    fee_limits: FeeLimits,
+    /// whether the transactions built are locked to the tip, see [Wallet::set_anti_fee_snipe]
+    #[serde(default = "enabled")]
+    anti_fee_snipe: bool,
+    /// whether syncs scan for silent payments, see [Wallet::silent_payment_address]
+    #[serde(default)]
+    silent_payments: bool,
+    #[serde(skip)]
+    events: EventSinks,
+    /// the transactions waited on with [Wallet::watch_tx]
+    #[serde(skip)]
+    watches: TxWatches,
+    /// set once the private keys were moved out of the key graph
+    #[serde(default)]
+    watch_only: bool,
+    /// the id of the master key in a [KeystoreBackend], when it's kept out of the wallet file
+    #[serde(default)]
+    master_key_id: Option<String>,
+    /// signs for the wallet in place of the keys of its key graph
+    #[serde(skip)]
+    signer: Option<Arc<dyn KeystoreSigner>>,
+    /// the time lock times of the transactions the wallet builds are read from
+    #[serde(skip, default = "system_clock")]
+    clock: Arc<dyn Clock>,
+    /// the randomness of the mnemonics and transactions of the wallet
+    #[serde(skip, default = "os_entropy")]
+    entropy: Arc<dyn EntropySource>,
+    /// the digest and mac of the file the wallet was loaded from, checked by [Wallet::unlock]
+    #[serde(skip)]
+    file_mac: Option<FileMac>,
+    /// the files last written by [Wallet::flush]
+    #[serde(skip)]
+    flushed: FlushedFiles,
+    /// the sections changed since the wallet last wrote them, none once loaded
+    #[serde(skip)]
+    changes: Changes,
+    /// the keys unsealed while unlocked, when set with [Wallet::set_key_cache]
+    #[serde(skip)]
+    key_cache: Option<Arc<Mutex<KeyCache>>>,
+    /// the lock on the wallet file, when opened with [Wallet::open_exclusive]
+    #[serde(skip)]
+    file_lock: Option<Arc<WalletLock>>,
+}
+
+/// the unlock key is redacted, so are the private keys through [Key]
+impl fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wallet")
+            .field("network", &self.network)
+            .field("version", &self.version)
+            .field("master_key_derivation", &self.master_key_derivation)
+            .field("config", &self.config)
+            .field("compress_public_keys", &self.compress_public_keys)
+            .field("arena", &self.arena)
+            .field("encrypted", &self.encrypted)
+            .field("encryption", &self.encryption)
+            .field("locked", &self.locked)
+            .field("unlock_key", &self.unlock_key.map(|_| REDACTED))
+            .field("mac_key", &self.mac_key.map(|_| REDACTED))
+            .field("accounts", &self.accounts)
+            .field("utxos", &self.utxos)
+            .field("history", &self.history)
+            .field("spends", &self.spends)
+            .field("archive", &self.archive)
+            .field("silent_payment_outputs", &self.silent_payment_outputs)
+            .field("scanned_blocks", &self.scanned_blocks)
+            .field("labels", &self.labels)
+            .field("birthday", &self.birthday)
+            .field("fee_limits", &self.fee_limits)
+            .field("silent_payments", &self.silent_payments)
+            .field("events", &self.events)
+            .field("watch_only", &self.watch_only)
+            .field("master_key_id", &self.master_key_id)
+            .field("signer", &self.signer)
+            .field("clock", &self.clock)
+            .field("entropy", &self.entropy)
+            .field(
+                "file_mac",
+                &self.file_mac.as_ref().map(|(_, mac)| hex::encode(mac)),
+            )
+            .field("flushed", &self.flushed)
+            .field("changes", &self.changes)
+            .field("key_cache", &self.key_cache)
+            .finish()
+    }
+}
+
+/// scripts watched by [Wallet::rescan], with their address and, for the
+/// addresses of accounts, the account, chain and index they're derived at
+type WatchedScripts = HashMap<Vec<u8>, (String, Option<(u32, Chain, u32)>)>;
+
+/// the digest of the fields of a wallet file its mac covers, and that mac
+type FileMac = (Vec<u8>, Vec<u8>);
+
+/// the digest of a written file and its modification time, at its path
+type FileDigests = HashMap<PathBuf, (Vec<u8>, SystemTime)>;
+
+/// The files written by [Wallet::flush], shared by the clones of a wallet.
+/// A file is only written again when its content changed or it was
+/// modified since, eg by another wallet flushed to the same path
+#[derive(Debug, Clone, Default)]
+struct FlushedFiles(Arc<Mutex<FileDigests>>);
+
+impl FlushedFiles {
+    /// whether a file still holds the content of a digest, as last written
+    fn holds(&self, file: &Path, digest: &[u8]) -> bool {
+        let modified = match fs::metadata(file).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return false,
+        };
+        match self.0.lock() {
+            Ok(files) => {
+                matches!(files.get(file), Some((written, at)) if written == digest && *at == modified)
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// remember the digest of a file just written
+    fn record(&self, file: &Path, digest: Vec<u8>) {
+        if let (Ok(mut files), Ok(modified)) = (
+            self.0.lock(),
+            fs::metadata(file).and_then(|metadata| metadata.modified()),
+        ) {
+            files.insert(file.to_path_buf(), (digest, modified));
+        }
+    }
+}
+
+/// The parts of a wallet [Wallet::flush] writes, the first three to the
+/// wallet file and the others to the cache file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    /// the key graph, accounts and encryption of the wallet
+    Keys,
+    Labels,
+    /// the config, birthday, fee limits and flags of the wallet
+    Settings,
+    /// the unspent outputs, spends and silent payments
+    Utxos,
+    /// the transactions found, archived and the blocks scanned
+    History,
+}
+
+impl Section {
+    const ALL: [Section; 5] = [
+        Section::Keys,
+        Section::Labels,
+        Section::Settings,
+        Section::Utxos,
+        Section::History,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    fn in_cache(self) -> bool {
+        matches!(self, Section::Utxos | Section::History)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ChangeState {
+    /// the bits of the sections changed since they were last written
+    changed: u8,
+    /// the digests of the wallet and cache files last written by this wallet
+    wallet_file: Option<Vec<u8>>,
+    cache_file: Option<Vec<u8>>,
+}
+
+/// The sections of a wallet changed since it last wrote them, unlike
+/// [FlushedFiles] each clone tracks its own. A flush doesn't serialize a
+/// file when none of its sections changed and it still holds what this
+/// wallet last wrote there
+#[derive(Debug, Default)]
+struct Changes(Mutex<ChangeState>);
+
+impl Clone for Changes {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.state().clone()))
+    }
+}
+
+impl Changes {
+    /// every section changed, for a wallet never written
+    fn all() -> Self {
+        let changes = Self::default();
+        changes.state().changed = Section::ALL.iter().fold(0, |bits, s| bits | s.bit());
+        changes
+    }
+
+    fn state(&self) -> MutexGuard<'_, ChangeState> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn mark(&mut self, sections: &[Section]) {
+        let state = self
+            .0
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for section in sections {
+            state.changed |= section.bit();
+        }
+    }
+
+    fn any(&self) -> bool {
+        self.state().changed != 0
+    }
+
+    /// whether the wallet or cache file needs serializing, ie one of its
+    /// sections changed or the file no longer holds what was last written
+    fn pending(&self, cache: bool, file: &Path, flushed: &FlushedFiles) -> bool {
+        let state = self.state();
+        let changed = Section::ALL
+            .iter()
+            .any(|s| s.in_cache() == cache && state.changed & s.bit() != 0);
+        let written = match cache {
+            true => &state.cache_file,
+            false => &state.wallet_file,
+        };
+        changed
+            || !written
+                .as_ref()
+                .is_some_and(|digest| flushed.holds(file, digest))
+    }
+
+    /// the sections of the wallet or cache file were written with a digest
+    fn written(&self, cache: bool, digest: Vec<u8>) {
+        let mut state = self.state();
+        for s in Section::ALL.iter().filter(|s| s.in_cache() == cache) {
+            state.changed &= !s.bit();
+        }
+        match cache {
+            true => state.cache_file = Some(digest),
+            false => state.wallet_file = Some(digest),
+        }
+    }
+}
+
+/// The signing context and unsealed keys shared by the transactions
+/// signed together, the keys are wiped when the session is dropped
+struct SigningSession {
+    secp: Secp256k1<All>,
+    keys: HashMap<Vec<u8>, Key>,
+}
+
+impl Default for SigningSession {
+    fn default() -> Self {
+        SigningSession {
+            secp: Secp256k1::new(),
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl Drop for SigningSession {
+    fn drop(&mut self) {
+        for key in self.keys.values_mut() {
+            key.wipe();
+        }
+    }
+}
+
+fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+fn os_entropy() -> Arc<dyn EntropySource> {
+    Arc::new(OsEntropy)
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// the master key derivation of wallets with none in their file, set to legacy when migrated
+fn bip32_derivation() -> MasterKeyDerivation {
+    MasterKeyDerivation::Bip32
+}
+
+/// the part of a wallet flushed to its [crate::CACHE_FILE_NAME], rebuilt by a rescan when lost
+#[derive(Deserialize, Serialize)]
+struct WalletCache<'a> {
+    #[serde(default)]
+    utxos: Cow<'a, [Utxo]>,
+    #[serde(default)]
+    history: Cow<'a, [TxRecord]>,
+    #[serde(default)]
+    spends: Cow<'a, [Spend]>,
+    #[serde(default)]
+    archive: Cow<'a, [SignedTx]>,
+    #[serde(default)]
+    silent_payment_outputs: Cow<'a, [SilentPaymentOutput]>,
+    #[serde(default)]
+    scanned_blocks: Cow<'a, [BlockId]>,
+}
+
+impl WalletCache<'_> {
+    /// stream the cache to a writer in a format
+    fn write<W: Write>(&self, format: CacheFormat, writer: W) -> Result<(), WalletError> {
+        let error = |e: String| WalletError::Write(format!("Failed to serialize cache: {}", e));
+        match format {
+            CacheFormat::Json => {
+                serde_json::to_writer(writer, self).map_err(|e| error(e.to_string()))
+            }
+            #[cfg(feature = "compact-cache")]
+            CacheFormat::Cbor => {
+                ciborium::ser::into_writer(self, writer).map_err(|e| error(e.to_string()))
+            }
+            #[cfg(not(feature = "compact-cache"))]
+            CacheFormat::Cbor => Err(error(NO_COMPACT_CACHE.to_string())),
+        }
+    }
+}
+
+#[cfg(not(feature = "compact-cache"))]
+const NO_COMPACT_CACHE: &str = "CBOR caches need the compact-cache feature";
+
+/// read the cache file of a config, streamed from disk
+fn read_cache<T: DeserializeOwned>(config: &WalletConfig) -> Result<T, WalletError> {
+    let reader = File::open(config.cache_file())
+        .map(BufReader::new)
+        .map_err(|e| WalletError::Read(format!("Failed to read cache: {}", e)))?;
+    let error = |e: String| WalletError::Read(format!("Failed to deserialize cache: {}", e));
+    match config.cache_format() {
+        CacheFormat::Json => serde_json::from_reader(reader).map_err(|e| error(e.to_string())),
+        #[cfg(feature = "compact-cache")]
+        CacheFormat::Cbor => ciborium::de::from_reader(reader).map_err(|e| error(e.to_string())),
+        #[cfg(not(feature = "compact-cache"))]
+        CacheFormat::Cbor => Err(error(NO_COMPACT_CACHE.to_string())),
+    }
+}
+
+/// the field of a wallet file holding the sha256 of the rest of the file
+const CHECKSUM_FIELD: &str = "checksum";
+/// the field of an encrypted wallet file holding an HMAC of its key fields
+const MAC_FIELD: &str = "mac";
+/// The fields of a wallet file addresses are derived from, authenticated by
+/// its mac. They only change while the wallet is unlocked or once its mac
+/// was checked, a wallet never unlocked writes them with the mac it read
+const KEY_FIELDS: [&str; 9] = [
+    "network",
+    "compress_public_keys",
+    "master_key_derivation",
+    "keys",
+    "encrypted",
+    "encryption",
+    "accounts",
+    "watch_only",
+    "master_key_id",
+];
+
+/// the sha256 of a wallet file without its checksum and mac, as serde_json writes it
+fn payload_digest(file: &Value) -> Vec<u8> {
+    fields_digest(file, |field| field != CHECKSUM_FIELD && field != MAC_FIELD)
+}
+
+/// The digest the mac of a wallet file covers, its key fields. Files written
+/// before the mac had its own key, see [EncryptionParams], authenticated the
+/// whole file
+fn mac_digest(file: &Value) -> Vec<u8> {
+    match file["encryption"]["mac_key"].as_array() {
+        Some(mac_key) if !mac_key.is_empty() => {
+            fields_digest(file, |field| KEY_FIELDS.contains(&field))
+        }
+        _ => payload_digest(file),
+    }
+}
+
+/// the sha256 of some fields of a wallet file, as serde_json writes them
+fn fields_digest(file: &Value, filter: fn(&str) -> bool) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    match file.as_object() {
+        Some(fields) => serde_json::to_writer(&mut hasher, &Payload(fields, filter)),
+        None => serde_json::to_writer(&mut hasher, file),
+    }
+    .expect("json values always serialize");
+    hasher.finalize().to_vec()
+}
+
+/// some fields of a wallet file, serialized without copying them
+struct Payload<'a>(&'a Map<String, Value>, fn(&str) -> bool);
+
+impl Serialize for Payload<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().filter(|(field, _)| (self.1)(field)))
+    }
+}
+
+/// the HMAC-SHA256 of the digest of a wallet file, keyed with the key of its mac
+fn payload_mac(key: &[u8; 32], digest: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(b"waller wallet file");
+    mac.update(digest);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// the mac of the key fields of a wallet file
+pub(crate) fn wallet_file_mac(file: &Value, key: &[u8; 32]) -> Vec<u8> {
+    payload_mac(key, &mac_digest(file))
+}
+
+/// Add the checksum of a wallet file to it, and its mac when one is given,
+/// see [wallet_file_mac]. Returns the digest and mac, which identify the sealed file
+pub(crate) fn seal_wallet_file(file: &mut Value, mac: Option<&[u8]>) -> Vec<u8> {
+    let mut seal = payload_digest(file);
+    let checksum = hex::encode(&seal);
+    if let Some(fields) = file.as_object_mut() {
+        if let Some(mac) = mac {
+            fields.insert(MAC_FIELD.to_string(), hex::encode(mac).into());
+            seal.extend(mac);
+        }
+        fields.insert(CHECKSUM_FIELD.to_string(), checksum.into());
+    }
+    seal
+}
+
+/// Check the checksum of a wallet file, returning its mac and the digest it covers when it has one.
+/// Unencrypted files written before wallets were checksummed have neither and
+/// aren't checked, an encrypted file without a mac was changed without the passphrase
+fn check_wallet_file(file: &Value) -> Result<Option<FileMac>, WalletError> {
+    let encrypted = !file["encryption"].is_null();
+    if encrypted && (file.get(CHECKSUM_FIELD).is_none() || file.get(MAC_FIELD).is_none()) {
+        return Err(WalletError::Corrupted {
+            expected: MAC_FIELD.to_string(),
+            actual: String::new(),
+        });
+    }
+    let expected = match file.get(CHECKSUM_FIELD) {
+        Some(checksum) => checksum.as_str().unwrap_or_default().to_string(),
+        None => return Ok(None),
+    };
+    let actual = hex::encode(payload_digest(file));
+    if expected != actual {
+        return Err(WalletError::Corrupted { expected, actual });
+    }
+
+    Ok(file
+        .get(MAC_FIELD)
+        .and_then(|mac| mac.as_str())
+        .map(|mac| (mac_digest(file), hex::decode(mac).unwrap_or_default())))
+}
+
+/// derive the address of an account type at a path from a master key and
+/// check it's the one stored
+fn check_derived_address(
+    master: &Key,
+    account_type: AccountType,
+    path: &[ChildNumber],
+    stored: String,
+) -> Result<String, WalletError> {
+    let mut key = master.clone();
+    for child in path.iter() {
+        key = key
+            .derive_child_private_key(*child)
+            .map_err(|e| WalletError::Key(e.to_string()))?;
+    }
+    let derived = account_type
+        .address(&key)
+        .map_err(|e| WalletError::Key(e.to_string()))?;
+    match derived == stored {
+        true => Ok(stored),
+        false => Err(WalletError::AddressMismatch { stored, derived }),
+    }
+}
+
+/// the address and keypair of a standalone key paying to an account type
+fn imported_keypair(key: Key, account_type: AccountType) -> Result<(String, KeyPair), WalletError> {
+    let address = account_type
+        .address(&key)
+        .map_err(|e| WalletError::Key(e.to_string()))?;
+    let keypair = KeyPair {
+        public_key: key
+            .new_public_key()
+            .map_err(|e| WalletError::Key(e.to_string()))?,
+        private_key: key,
+        key_type: KeyType::Imported,
+        index: None,
+        encrypted_private_key: None,
+    };
+    Ok((address, keypair))
+}
+
+/// a copy of a key graph with every private key still in memory sealed
+fn sealed_arena(
+    arena: &Arena<KeyPair, String>,
+    key: &[u8; 32],
+    source: &dyn EntropySource,
+) -> Result<Arena<KeyPair, String>, WalletError> {
+    let mut sealed = arena.clone();
+    for index in 0..sealed.count() {
+        if let Some(keypair) = sealed.get_inner_mut(index) {
+            if !keypair.private_key.is_wiped() {
+                keypair.encrypted_private_key =
+                    Some(encrypt(key, keypair.private_key.bytes(), source)?);
+                keypair.private_key.wipe();
+            }
+        }
+    }
+    Ok(sealed)
+}
+
+/// Write a file through a temporary file renamed over it, creating its
+/// directory if needed. The content is streamed to the temporary file
+fn write_atomically<F>(file: &Path, write: F) -> Result<(), WalletError>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), WalletError>,
+{
+    let error = |e: std::io::Error| WalletError::Write(format!("Failed to write file: {}", e));
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+
+    let mut temp = file.as_os_str().to_owned();
+    temp.push(".tmp");
+    let mut writer = File::create(&temp).map(BufWriter::new).map_err(error)?;
+    let written = write(&mut writer).and_then(|_| writer.flush().map_err(error));
+    drop(writer);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+
+    fs::rename(&temp, file).map_err(error)
+}
+
+/// Insert the nodes of a key graph into a new arena, skipping the missing
+/// ones and any node whose parent isn't kept. Parentless nodes are only kept
+/// if they're the root or imported. Returns the arena, rooted at the root, and
+/// the new index of every node kept. Parents are always inserted before their children
+fn rebuild_arena(
+    records: &[Option<KeyRecord>],
+    root: Option<usize>,
+) -> (Arena<KeyPair, String>, Vec<Option<usize>>) {
+    let mut arena = Arena::new();
+    let mut moved: Vec<Option<usize>> = Vec::with_capacity(records.len());
+    for (index, record) in records.iter().enumerate() {
+        let parent = match record {
+            Some(record) => match record.parent {
+                Some(parent) => moved.get(parent).copied().flatten().map(Some),
+                None if root == Some(index) => Some(None),
+                None if matches!(record.keypair.key_type, KeyType::Imported) => Some(None),
+                None => None,
+            },
+            None => None,
+        };
+        moved.push(parent.and_then(|parent| {
+            record
+                .as_ref()
+                .map(|record| arena.insert(record.keypair.clone(), record.address.clone(), parent))
+        }));
+    }
+
+    arena.set_root(root.and_then(|root| moved.get(root).copied().flatten()));
+    (arena, moved)
+}
+
+/// Give the derived keys of a wallet written before keys held their depth,
+/// parent fingerprint and child number the ones of their place in the key graph.
+/// Parents come before their children in the arena
+fn fill_key_origins(arena: &mut Arena<KeyPair, String>) {
+    for index in 0..arena.count() {
+        let origin = match arena.get(index) {
+            Some(node) if node.data.private_key.child_number().is_none() => {
+                match (
+                    node.data.index,
+                    node.parent().and_then(|p| arena.get_inner(p)),
+                ) {
+                    (Some(child), Some(parent)) => {
+                        key_fingerprint(&parent.public_key).ok().map(|fingerprint| {
+                            (
+                                parent.private_key.depth().saturating_add(1),
+                                fingerprint,
+                                child,
+                            )
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let (Some((depth, fingerprint, child)), Some(keypair)) =
+            (origin, arena.get_inner_mut(index))
+        {
+            keypair.private_key.set_origin(depth, fingerprint, child);
+        }
+    }
+}
+
+/// Read a field of a wallet file, reporting it when it's there but invalid.
+/// Missing fields are left to their default, as older files lack them
+fn recover_field<T: DeserializeOwned>(
+    file: &Value,
+    field: &str,
+    section: WalletSection,
+    report: &mut RecoveryReport,
+) -> Option<T> {
+    serde_json::from_value(file.get(field)?.clone())
+        .map_err(|e| report.push(section, None, format!("{}: {}", field, e)))
+        .ok()
+}
+
+/// Read a list of a wallet file entry by entry, with their index. Entries
+/// that are invalid are reported, a missing list is empty
+fn recover_entries<T: DeserializeOwned>(
+    file: &Value,
+    field: &str,
+    section: WalletSection,
+    report: &mut RecoveryReport,
+) -> Vec<(usize, T)> {
+    match file.get(field) {
+        None | Some(Value::Null) => vec![],
+        Some(Value::Array(entries)) => entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                serde_json::from_value(entry.clone())
+                    .map_err(|e| report.push(section, Some(index.to_string()), e.to_string()))
+                    .ok()
+                    .map(|entry| (index, entry))
+            })
+            .collect(),
+        Some(entries) => {
+            report.push(section, None, format!("{}: not a list: {}", field, entries));
+            vec![]
+        }
+    }
+}
+
+/// the name of the file a wallet is flushed to inside its data directory
+pub const WALLET_FILE_NAME: &str = "wallet.json";
+
+/// how many addresses past the last one used [Wallet::rescan] watches on every
+/// chain of every account, the gap limit of BIP44
+pub const RESCAN_LOOKAHEAD: u32 = 20;
+
+/// how far the timestamp of a block may be behind the time it was mined
+/// at, blocks are compared against a [Birthday] time less this
+const BIRTHDAY_TIME_MARGIN: u64 = 2 * 60 * 60;
+
+/// how many of the last blocks scanned [Wallet::sync] keeps the hash of, a
+/// reorg deeper than this is scanned again from the wallet birthday
+pub const KEPT_BLOCK_HASHES: usize = 100;
+
+/// the smallest change output [Wallet::new_transaction] makes, in satoshis,
+/// anything less costs more to spend than it's worth and is left to the fee
+pub const DUST_LIMIT: i64 = 546;
+
+/// The version of the wallet files written. Files without one were written
+/// before version 1, whose master keys were derived with [MasterKeyDerivation::Legacy]
+pub const WALLET_VERSION: u32 = 1;
+
+/// index of the normal key derived by [Wallet::init] from the first hardened
+/// key of the master key
+const KEY_CHAIN_NORMAL_INDEX: ChildNumber = ChildNumber::Normal(1);
+
+impl Wallet {
+    /// Create a new wallet, kept in a [WalletConfig] or a single data directory
+    pub fn new<C: Into<WalletConfig>>(
+        network: Network,
+        config: C,
+        compress_public_keys: bool,
+        encrypted: bool,
+    ) -> Self {
+        Self {
+            arena: Arena::new(),
+            key_index: KeyIndex::default(),
+            network,
+            version: WALLET_VERSION,
+            master_key_derivation: MasterKeyDerivation::Bip32,
+            config: config.into(),
+            compress_public_keys,
+            encrypted,
+            encryption: None,
+            locked: false,
+            unlock_key: None,
+            mac_key: None,
+            accounts: vec![],
+            utxos: vec![],
+            history: vec![],
+            spends: vec![],
+            archive: vec![],
+            silent_payment_outputs: vec![],
+            scanned_blocks: vec![],
+            labels: BTreeMap::new(),
+            birthday: Birthday::default(),
+            fee_limits: FeeLimits::default(),
+            anti_fee_snipe: true,
+            silent_payments: false,
+            events: EventSinks::default(),
+            watches: TxWatches::default(),
+            watch_only: false,
+            master_key_id: None,
+            signer: None,
+            clock: system_clock(),
+            entropy: os_entropy(),
+            file_mac: None,
+            flushed: FlushedFiles::default(),
+            changes: Changes::all(),
+            key_cache: None,
+            file_lock: None,
+        }
+    }
+
+    /// Restore an HD wallet, all keys lost can be recovered
+    /// from the mnemonic seed used to build it, however generating
+    /// every key can be very expensive computationally
+    pub fn restore<C: Into<WalletConfig>>(
+        mnemonic: String,
+        network: Network,
+        compress_public_keys: bool,
+        data_path: C,
+        encrypted: bool,
+    ) -> Result<Self, WalletError> {
+        Self::restore_with_derivation(
+            mnemonic,
+            MasterKeyDerivation::Bip32,
+            network,
+            compress_public_keys,
+            data_path,
+            encrypted,
+        )
+    }
+
+    /// [Wallet::restore] with the master key derived from the mnemonic as
+    /// [MasterKeyDerivation] says, [MasterKeyDerivation::Legacy] recovering
+    /// the addresses of a wallet made before version 1, see [WALLET_VERSION]
+    pub fn restore_with_derivation<C: Into<WalletConfig>>(
+        mnemonic: String,
+        derivation: MasterKeyDerivation,
+        network: Network,
+        compress_public_keys: bool,
+        data_path: C,
+        encrypted: bool,
+    ) -> Result<Self, WalletError> {
+        let key = Key::new_with(mnemonic.clone(), derivation, network, compress_public_keys)
+            .map_err(|e| WalletError::Key(e.to_string()))?;
+
+        let mut wallet = Wallet::new(network, data_path, compress_public_keys, encrypted);
+        wallet.master_key_derivation = derivation;
+
+        wallet.set_master_key(key.clone())?;
+        let _ = wallet.create_key_chain(key, mnemonic)?;
+
+        Ok(wallet)
+    }
+
+    /// Restore a wallet from SLIP-39 shares made with [Wallet::export_shamir_shares],
+    /// the passphrase must be the one the shares were made with
+    pub fn restore_from_shamir_shares<S: AsRef<str>, C: Into<WalletConfig>>(
+        shares: &[S],
+        passphrase: &str,
+        network: Network,
+        compress_public_keys: bool,
+        data_path: C,
+        encrypted: bool,
+    ) -> Result<Self, WalletError> {
+        let secret = combine_shares(shares, passphrase).map_err(WalletError::Shamir)?;
+        let key = Key::from_extended_private_key(&secret, network, compress_public_keys)
+            .map_err(|e| WalletError::Key(e.to_string()))?;
+
+        let mut wallet = Wallet::new(network, data_path, compress_public_keys, encrypted);
+
+        wallet.set_master_key(key.clone())?;
+        let _ = wallet.create_key_chain(key, String::new())?;
+
+        Ok(wallet)
+    }
+
+    /// Create a wallet from an existing backed up json wallet file
+    /// This is a serde serialized string of the [Wallet] type.
+    /// The utxos and history are read from the [crate::CACHE_FILE_NAME] of its
+    /// cache directory when there is one. Fails with [WalletError::Corrupted]
+    /// when the file doesn't match the checksum written by [Wallet::flush]
+    pub fn from_wallet_file(path: PathBuf) -> Result<Self, WalletError> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| WalletError::Read(format!("Failed to read file: {}", e)))?;
+
+        let file: Value = serde_json::from_str(&data)
+            .map_err(|e| WalletError::Read(format!("Failed to deserialize data: {}", e)))?;
+        let file_mac = check_wallet_file(&file)?;
+        let mut wallet: Self = serde_json::from_value(file)
+            .map_err(|e| WalletError::Read(format!("Failed to deserialize data: {}", e)))?;
+        wallet.file_mac = file_mac;
+        wallet.migrate();
+        fill_key_origins(&mut wallet.arena);
+        wallet.key_index.update(&wallet.arena, wallet.network);
+
+        if wallet.config.cache_file().is_file() {
+            let cache: WalletCache = read_cache(&wallet.config)?;
+
+            wallet.utxos = cache.utxos.into_owned();
+            wallet.history = cache.history.into_owned();
+            wallet.spends = cache.spends.into_owned();
+            wallet.archive = cache.archive.into_owned();
+            wallet.silent_payment_outputs = cache.silent_payment_outputs.into_owned();
+            wallet.scanned_blocks = cache.scanned_blocks.into_owned();
+        }
+
+        Ok(wallet)
+    }
+
+    /// Load a wallet file as [Wallet::from_wallet_file] does, holding a lock
+    /// on it until the wallet is dropped so that no other process opens or
+    /// flushes it meanwhile. Fails with [WalletError::FileInUse] while another
+    /// process holds it, see [Wallet::open_exclusive_with] to take it anyway
+    pub fn open_exclusive(path: PathBuf) -> Result<Self, WalletError> {
+        Self::open_exclusive_with(path, false)
+    }
+
+    /// [Wallet::open_exclusive], taking the lock from the process holding it
+    /// when forced, eg when it hangs. A lock left by a process that died
+    /// holding it is replaced either way, see [WalletLock::stale_owner]
+    pub fn open_exclusive_with(path: PathBuf, force: bool) -> Result<Self, WalletError> {
+        let lock = WalletLock::acquire(&path, force)?;
+        let mut wallet = Self::from_wallet_file(path)?;
+        wallet.file_lock = Some(Arc::new(lock));
+        Ok(wallet)
+    }
+
+    /// Load a wallet file for reporting or monitoring, eg next to the process
+    /// keeping the wallet. The [ReadOnlyWallet] returned can't derive, track
+    /// outputs or flush, nor sign when the wallet is encrypted
+    pub fn open_read_only(path: PathBuf) -> Result<ReadOnlyWallet, WalletError> {
+        Self::from_wallet_file(path).map(ReadOnlyWallet::from)
+    }
+
+    /// the lock held on the wallet file, see [Wallet::open_exclusive]
+    pub fn file_lock(&self) -> Option<&WalletLock> {
+        self.file_lock.as_deref()
+    }
+
+    /// Load a wallet file that may be partly corrupted. Every section is
+    /// read on its own, and lists entry by entry, what can't be read is
+    /// dropped and reported instead of failing the load. Keys are dropped
+    /// with the keys derived from them and accounts with their key, lost
+    /// utxos and history are found again with [Wallet::rescan].
+    /// Fails only when the file isn't json or its network can't be read
+    pub fn from_wallet_file_lenient(path: PathBuf) -> Result<(Self, RecoveryReport), WalletError> {
+        let data = fs::read_to_string(&path)
+            .map_err(|e| WalletError::Read(format!("Failed to read file: {}", e)))?;
+        let file: Value = serde_json::from_str(&data)
+            .map_err(|e| WalletError::Read(format!("Failed to deserialize data: {}", e)))?;
+        let network: Network = serde_json::from_value(file["network"].clone())
+            .map_err(|e| WalletError::Read(format!("Failed to read network: {}", e)))?;
+
+        let mut report = RecoveryReport::default();
+        let file_mac = match check_wallet_file(&file) {
+            Ok(file_mac) => file_mac,
+            Err(WalletError::Corrupted { expected, actual }) => {
+                report.push(
+                    WalletSection::Checksum,
+                    None,
+                    format!("expected {}, found {}", expected, actual),
+                );
+                None
+            }
+            Err(e) => return Err(e),
+        };
+
+        // every key record that can be read, at its index, from the arena of older files
+        let (keys, entries, legacy) = match &file["keys"] {
+            Value::Null => (&file["arena"], "nodes", true),
+            keys => (keys, "records", false),
+        };
+        let records: Vec<Option<KeyRecord>> = match keys[entries].as_array() {
+            Some(entries) => entries
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| {
+                    let record = match legacy {
+                        true => {
+                            serde_json::from_value::<ArenaNode>(entry.clone()).map(KeyRecord::from)
+                        }
+                        false => serde_json::from_value(entry.clone()),
+                    };
+                    record
+                        .map_err(|e| {
+                            report.push(WalletSection::Keys, Some(index.to_string()), e.to_string())
+                        })
+                        .ok()
+                })
+                .collect(),
+            None => {
+                report.push(WalletSection::Keys, None, "no key records".to_string());
+                vec![]
+            }
+        };
+        let root = recover_field::<Option<usize>>(keys, "root", WalletSection::Keys, &mut report)
+            .unwrap_or_else(|| {
+                records.iter().position(|record| {
+                    matches!(record, Some(record) if record.parent.is_none() && matches!(record.keypair.key_type, KeyType::Master))
+                })
+            });
+
+        let (mut arena, moved) = rebuild_arena(&records, root);
+        fill_key_origins(&mut arena);
+        for (index, record) in records.iter().enumerate() {
+            if record.is_some() && moved[index].is_none() {
+                report.push(
+                    WalletSection::Keys,
+                    Some(index.to_string()),
+                    "derived from a lost key".to_string(),
+                );
+            }
+        }
+
+        let accounts =
+            recover_entries::<Account>(&file, "accounts", WalletSection::Accounts, &mut report)
+                .into_iter()
+                .filter_map(|(index, mut account)| {
+                    match moved.get(account.node()).copied().flatten() {
+                        Some(node) => {
+                            account.set_node(node);
+                            Some(account)
+                        }
+                        None => {
+                            report.push(
+                                WalletSection::Accounts,
+                                Some(index.to_string()),
+                                "the account key was lost".to_string(),
+                            );
+                            None
+                        }
+                    }
+                })
+                .collect();
+
+        let labels = match &file["labels"] {
+            Value::Null => BTreeMap::new(),
+            Value::Object(labels) => labels
+                .iter()
+                .filter_map(|(address, label)| match label.as_str() {
+                    Some(label) => Some((address.clone(), label.to_string())),
+                    None => {
+                        report.push(
+                            WalletSection::Labels,
+                            Some(address.clone()),
+                            format!("invalid label {}", label),
+                        );
+                        None
+                    }
+                })
+                .collect(),
+            labels => {
+                report.push(
+                    WalletSection::Labels,
+                    None,
+                    format!("invalid labels {}", labels),
+                );
+                BTreeMap::new()
+            }
+        };
+
+        let encryption: Option<EncryptionParams> =
+            recover_field(&file, "encryption", WalletSection::Encryption, &mut report).flatten();
+        let sealed = arena
+            .nodes()
+            .iter()
+            .any(|node| node.data.encrypted_private_key.is_some());
+
+        let config = match file.get("config") {
+            Some(_) => recover_field(&file, "config", WalletSection::Settings, &mut report),
+            None => recover_field(&file, "path", WalletSection::Settings, &mut report),
+        }
+        .unwrap_or_else(|| WalletConfig::new(path.parent().map(PathBuf::from).unwrap_or_default()));
+
+        // the utxos and history are in the cache file, or in the wallet file of older versions
+        let cache = match config.cache_file().is_file() {
+            true => read_cache(&config).unwrap_or_else(|e| {
+                for section in [WalletSection::Utxos, WalletSection::History] {
+                    report.push(section, None, format!("{:?}", e));
+                }
+                Value::Null
+            }),
+            false => file.clone(),
+        };
+
+        let mut wallet = Self::new(
+            network,
+            config,
+            recover_field(
+                &file,
+                "compress_public_keys",
+                WalletSection::Settings,
+                &mut report,
+            )
+            .unwrap_or(true),
+            recover_field(&file, "encrypted", WalletSection::Settings, &mut report)
+                .unwrap_or_else(|| encryption.is_some()),
+        );
+        wallet.arena = arena;
+        wallet.key_index.update(&wallet.arena, wallet.network);
+        wallet.encryption = encryption;
+        // the private keys are sealed while locked
+        wallet.locked =
+            recover_field(&file, "locked", WalletSection::Settings, &mut report).unwrap_or(sealed);
+        wallet.watch_only =
+            recover_field(&file, "watch_only", WalletSection::Settings, &mut report)
+                .unwrap_or_default();
+        wallet.master_key_id =
+            recover_field(&file, "master_key_id", WalletSection::Settings, &mut report).flatten();
+        wallet.accounts = accounts;
+        wallet.utxos = recover_entries(&cache, "utxos", WalletSection::Utxos, &mut report)
+            .into_iter()
+            .map(|(_, utxo)| utxo)
+            .collect();
+        wallet.history = recover_entries(&cache, "history", WalletSection::History, &mut report)
+            .into_iter()
+            .map(|(_, tx)| tx)
+            .collect();
+        wallet.spends = recover_entries(&cache, "spends", WalletSection::History, &mut report)
+            .into_iter()
+            .map(|(_, spend)| spend)
+            .collect();
+        wallet.archive = recover_entries(&cache, "archive", WalletSection::History, &mut report)
+            .into_iter()
+            .map(|(_, tx)| tx)
+            .collect();
+        wallet.silent_payment_outputs = recover_entries(
+            &cache,
+            "silent_payment_outputs",
+            WalletSection::Utxos,
+            &mut report,
+        )
+        .into_iter()
+        .map(|(_, output)| output)
+        .collect();
+        wallet.scanned_blocks = recover_entries(
+            &cache,
+            "scanned_blocks",
+            WalletSection::History,
+            &mut report,
+        )
+        .into_iter()
+        .map(|(_, block)| block)
+        .collect();
+        wallet.labels = labels;
+        wallet.birthday = recover_field(&file, "birthday", WalletSection::Settings, &mut report)
+            .unwrap_or_default();
+        wallet.file_mac = file_mac;
+        wallet.fee_limits =
+            recover_field(&file, "fee_limits", WalletSection::Settings, &mut report)
+                .unwrap_or_default();
+        wallet.anti_fee_snipe = recover_field(
+            &file,
+            "anti_fee_snipe",
+            WalletSection::Settings,
+            &mut report,
+        )
+        .unwrap_or(true);
+        wallet.silent_payments = recover_field(
+            &file,
+            "silent_payments",
+            WalletSection::Settings,
+            &mut report,
+        )
+        .unwrap_or_default();
+        wallet.version = recover_field(&file, "version", WalletSection::Settings, &mut report)
+            .unwrap_or_default();
+        wallet.master_key_derivation = recover_field(
+            &file,
+            "master_key_derivation",
+            WalletSection::Settings,
+            &mut report,
+        )
+        .unwrap_or(MasterKeyDerivation::Bip32);
+        wallet.migrate();
+
+        Ok((wallet, report))
+    }
+
+    /// Bring a wallet read from a file of an older version up to
+    /// [WALLET_VERSION]. The keys of a file are kept as they are, so its
+    /// addresses don't change, and files before version 1 are marked as
+    /// derived with [MasterKeyDerivation::Legacy]
+    fn migrate(&mut self) {
+        if self.version < 1 {
+            self.master_key_derivation = MasterKeyDerivation::Legacy;
+        }
+        self.version = WALLET_VERSION;
+    }
+
+    /// how the master key of the wallet was derived from its mnemonic, see [Wallet::migrate]
+    pub fn master_key_derivation(&self) -> MasterKeyDerivation {
+        self.master_key_derivation
+    }
+
+    /// Write the wallet to [WALLET_FILE_NAME] in its data directory, and its
+    /// utxos and history to the cache file of its [CacheFormat] in its cache
+    /// directory, creating them if needed. The files are streamed to disk and
+    /// replaced atomically, a crash leaves either the old or the new wallet on
+    /// disk. A file is skipped when it holds what this wallet, or a clone of
+    /// it, last wrote there, and isn't even serialized when none of its
+    /// sections changed since this wallet wrote it, see [Wallet::is_dirty],
+    /// so flushing after each of many small changes stays cheap. The wallet
+    /// file holds the sha256 of its content, and when the wallet is encrypted an
+    /// HMAC of the fields addresses are derived from keyed with a key sealed
+    /// in its [EncryptionParams], kept once unlocked. An encrypted wallet never
+    /// unlocked writes the mac it was loaded with, and fails with
+    /// [WalletError::Locked] when those fields changed. An encrypted
+    /// wallet is always written locked, its private keys sealed even while
+    /// it's unlocked. Fails with [WalletError::FileInUse] while
+    /// another process holds the wallet file, see [Wallet::open_exclusive].
+    /// Returns the path of the wallet file
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(path = %self.config.data_dir().display()), err(Debug))
+    )]
+    pub fn flush(&self) -> Result<PathBuf, WalletError> {
+        // a wallet not opened exclusively only writes while no other process holds the file
+        let file = self.config.wallet_file();
+        let _flush_lock = match &self.file_lock {
+            Some(lock) if lock.holds(&file) => None,
+            _ => lock_for_flush(&file)?,
+        };
+
+        // the cache is written first, a wallet file is never newer than its cache
+        let format = self.config.cache_format();
+        let cache_file = self.config.cache_file();
+        if self.changes.pending(true, &cache_file, &self.flushed) {
+            let cache = WalletCache {
+                utxos: Cow::Borrowed(&self.utxos),
+                history: Cow::Borrowed(&self.history),
+                spends: Cow::Borrowed(&self.spends),
+                archive: Cow::Borrowed(&self.archive),
+                silent_payment_outputs: Cow::Borrowed(&self.silent_payment_outputs),
+                scanned_blocks: Cow::Borrowed(&self.scanned_blocks),
+            };
+            let mut hasher = Sha256::new();
+            cache.write(format, &mut hasher)?;
+            let cache_digest = hasher.finalize().to_vec();
+
+            if !self.flushed.holds(&cache_file, &cache_digest) {
+                write_atomically(&cache_file, |writer| cache.write(format, writer))?;
+                self.flushed.record(&cache_file, cache_digest.clone());
+                debug!(utxos = self.utxos.len(), "cache written");
+            }
+            self.changes.written(true, cache_digest);
+        }
+        // a cache left in another format is out of date
+        for stale in [CacheFormat::Json, CacheFormat::Cbor] {
+            let stale = self.config.cache_dir().join(stale.file_name());
+            if stale != cache_file && stale.is_file() {
+                let _ = fs::remove_file(stale);
+            }
+        }
+
+        if self.changes.pending(false, &file, &self.flushed) {
+            let error = |e: serde_json::Error| {
+                WalletError::Write(format!("Failed to serialize wallet: {}", e))
+            };
+            let wallet = match self.master_key_id {
+                Some(_) => Cow::Owned(self.without_master_key()?),
+                None => Cow::Borrowed(self),
+            };
+            let mut data = serde_json::to_value(&*wallet).map_err(error)?;
+            // an encrypted wallet is written locked, the keys it holds while
+            // it's unlocked are sealed in a copy of the key graph
+            if self.encryption.is_some() {
+                if let Some(key) = &self.unlock_key {
+                    let sealed = sealed_arena(&wallet.arena, key, self.entropy.as_ref())?;
+                    data["keys"] =
+                        serde_json::to_value(KeyRecords::from_arena(&sealed)).map_err(error)?;
+                }
+                data["locked"] = Value::Bool(true);
+            }
+            let mac = match (&self.encryption, &self.mac_key, &self.file_mac) {
+                (None, _, _) => None,
+                (Some(_), Some(key), _) => Some(wallet_file_mac(&data, key)),
+                // a wallet never unlocked keeps the mac it read while its key fields are unchanged
+                (Some(_), None, Some((digest, mac))) if *digest == mac_digest(&data) => {
+                    Some(mac.clone())
+                }
+                (Some(_), None, _) => return Err(WalletError::Locked),
+            };
+            let seal = seal_wallet_file(&mut data, mac.as_deref());
+
+            if !self.flushed.holds(&file, &seal) {
+                write_atomically(&file, |writer| {
+                    serde_json::to_writer(writer, &data).map_err(error)
+                })?;
+                self.flushed.record(&file, seal.clone());
+                debug!(keys = self.arena.nodes().len(), "wallet written");
+            }
+            self.changes.written(false, seal);
+        }
+
+        self.events.emit(WalletEvent::WalletFlushed(file.clone()));
+        Ok(file)
+    }
+
+    /// whether the wallet changed since it was last flushed, a wallet just
+    /// loaded from its file hasn't
+    pub fn is_dirty(&self) -> bool {
+        self.changes.any()
+    }
+
+    /// Register a callback receiving every [WalletEvent], eg
+    /// `wallet.on_event(|event| println!("{:?}", event))`.
+    /// Callbacks are not persisted and must be registered again after loading
+    pub fn on_event<S: EventSink + 'static>(&mut self, sink: S) {
+        self.events.push(Arc::new(sink));
+    }
+
+    /// initialize a new wallet
+    /// on success, returns the mnemonic used to create the wallet
+    pub fn init(&mut self) -> Result<String, WalletError> {
+        self.changes.mark(&[Section::Settings]);
+        let KeyCreationOutput { mnemonic, key } =
+            self.generate_master_key(self.compress_public_keys)?;
+        self.birthday = Birthday {
+            height: None,
+            time: Some(self.clock.now()),
+        };
+
+        self.create_key_chain(key, mnemonic)
+    }
+
+    /// The nodes of the key arena, indexed as the arena stores them. UIs
+    /// should walk [Wallet::account_views] and [Wallet::imported_keys] instead
+    pub fn keys(&self) -> &Vec<Node<KeyPair, String>> {
+        self.arena.nodes()
+    }
+
+    /// return a reference to the network this
+    /// wallet is associated with
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    /// whether keys created by the wallet use compressed public keys,
+    /// keys imported with [Wallet::import_wif] may differ
+    pub fn compress_public_keys(&self) -> bool {
+        self.compress_public_keys
+    }
+
+    /// get the path where keys are being saved to disk, the data directory of its [WalletConfig]
+    pub fn path(&self) -> &PathBuf {
+        self.config.data_dir()
+    }
+
+    /// change the path to a new location, the cache moves along when kept in the same directory
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.changes.mark(&[Section::Settings]);
+        self.config = match self.config.cache_dir() == self.config.data_dir() {
+            true => WalletConfig::new(path),
+            false => self.config.with_data_dir(path),
+        };
+    }
+
+    pub fn config(&self) -> &WalletConfig {
+        &self.config
+    }
+
+    /// change where the wallet and its cache are flushed to
+    pub fn set_config(&mut self, config: WalletConfig) {
+        self.changes.mark(&[Section::Settings]);
+        self.config = config;
+    }
+
+    /// returns a vec of addresses of all keys in the wallet
+    /// addresses stay available while the wallet is locked
+    pub fn addresses(&self) -> Result<Vec<String>, KeyError> {
+        Ok(self
+            .arena
+            .nodes()
+            .iter()
+            .map(|node| node.key.clone())
+            .collect())
+    }
+
+    /// Change and set the use of encryption or none
+    pub fn set_encryption(&mut self, encrypted: bool) {
+        self.changes.mark(&[Section::Keys]);
+        self.encrypted = encrypted;
+    }
+
+    /// the cost of deriving the key of an encrypted wallet, set from the
+    /// [KdfParams] of its config when encrypted or its passphrase changed
+    pub fn kdf(&self) -> Option<KdfParams> {
+        self.encryption.as_ref().map(EncryptionParams::kdf)
+    }
+
+    /// Encrypt the wallet with a passphrase, mirroring `encryptwallet`
+    /// the wallet is locked once encrypted, its key derived with the [KdfParams] of its config.
+    /// Only private keys are sealed, public keys, addresses, labels, coins and
+    /// history stay readable in the wallet file without the passphrase
+    pub fn encrypt(&mut self, passphrase: &str) -> Result<(), WalletError> {
+        if self.encryption.is_some() {
+            return Err(WalletError::AlreadyEncrypted);
+        }
+
+        let (params, key) =
+            EncryptionParams::new(passphrase, self.config.kdf(), self.entropy.as_ref())?;
+        self.changes.mark(&[Section::Keys]);
+        self.mac_key = params.mac_key(&key)?;
+        self.encryption = Some(params);
+        self.encrypted = true;
+        self.unlock_key = Some(key);
+
+        self.lock()
+    }
+
+    /// Seal every private key in memory, signing and deriving new
+    /// accounts fail with [WalletError::Locked] until [Wallet::unlock].
+    /// A locked wallet still issues addresses and syncs, deriving normal
+    /// children from public keys, once they were authenticated by unlocking
+    /// it, see [Wallet::unlock]
+    pub fn lock(&mut self) -> Result<(), WalletError> {
+        if self.encryption.is_none() {
+            return Err(WalletError::Unencrypted);
+        }
+
+        let key = match self.unlock_key.take() {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        self.changes.mark(&[Section::Keys]);
+
+        self.seal_keys(&key)?;
+        self.purge_key_cache();
+        self.locked = true;
+        Ok(())
+    }
+
+    /// seal every private key of the key graph still in memory
+    fn seal_keys(&mut self, key: &[u8; 32]) -> Result<(), WalletError> {
+        for index in 0..self.arena.count() {
+            if let Some(keypair) = self.arena.get_inner_mut(index) {
+                if keypair.private_key.is_wiped() {
+                    continue;
+                }
+
+                self.changes.mark(&[Section::Keys]);
+                keypair.encrypted_private_key = Some(encrypt(
+                    key,
+                    keypair.private_key.bytes(),
+                    self.entropy.as_ref(),
+                )?);
+                keypair.private_key.wipe();
+            }
+        }
+        Ok(())
+    }
+
+    /// unseal every sealed private key of the key graph
+    fn unseal_keys(&mut self, key: &[u8; 32]) -> Result<(), WalletError> {
+        for index in 0..self.arena.count() {
+            if let Some(keypair) = self.arena.get_inner_mut(index) {
+                if let Some(sealed) = keypair.encrypted_private_key.take() {
+                    self.changes.mark(&[Section::Keys]);
+                    keypair.private_key.restore(decrypt(key, &sealed)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Seal the private keys of children derived from public keys while the
+    /// wallet was locked, see [Wallet::new_receive_address]. Each is derived
+    /// from its parent, in the order they were added so a parent is sealed
+    /// before its children, and checked against its public key. Children of
+    /// a key not held, eg the master key in a credential store, stay wiped
+    fn seal_public_children(&mut self, key: &[u8; 32]) -> Result<(), WalletError> {
+        for index in 0..self.arena.count() {
+            let (parent, child, public_key) = match self.arena.get(index) {
+                Some(node)
+                    if node.data.private_key.is_wiped()
+                        && node.data.encrypted_private_key.is_none() =>
+                {
+                    match (node.parent(), node.data.index) {
+                        (Some(parent), Some(child)) => {
+                            (parent, child, node.data.public_key.clone())
+                        }
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            let mut parent_key = match self.arena.get_inner(parent) {
+                Some(keypair) => match &keypair.encrypted_private_key {
+                    Some(sealed) => {
+                        let mut parent_key = keypair.private_key.clone();
+                        parent_key.restore(decrypt(key, sealed)?);
+                        parent_key
+                    }
+                    None => continue,
+                },
+                None => continue,
+            };
+
+            let derived = parent_key.derive_child_private_key(child);
+            parent_key.wipe();
+            let mut child_key = derived.map_err(|e| WalletError::Key(e.to_string()))?;
+            let derived_public_key = child_key
+                .new_public_key()
+                .map_err(|e| WalletError::Key(e.to_string()))?;
+            if derived_public_key != public_key {
+                child_key.wipe();
+                return Err(WalletError::Corrupted {
+                    expected: hex::encode(&public_key),
+                    actual: hex::encode(derived_public_key),
+                });
+            }
+
+            let sealed = encrypt(key, child_key.bytes(), self.entropy.as_ref())?;
+            child_key.wipe();
+            self.changes.mark(&[Section::Keys]);
+            if let Some(keypair) = self.arena.get_inner_mut(index) {
+                keypair.encrypted_private_key = Some(sealed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Unseal the private keys with the wallet passphrase, mirroring `walletpassphrase`.
+    /// Fails with [WalletError::Corrupted] when the wallet was loaded from a file
+    /// whose mac doesn't match the key, a file changed by someone without the passphrase.
+    /// A wallet loaded locked derives addresses from its public keys only
+    /// once they're authenticated this way, it stays so once locked again
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), WalletError> {
+        let key = match &self.encryption {
+            Some(params) => params.unlock(passphrase)?,
+            None => return Err(WalletError::Unencrypted),
+        };
+
+        let sealed_mac_key = self.check_file_mac(&key)?;
+        self.mac_key = match (sealed_mac_key, self.encryption.as_mut()) {
+            (None, Some(params)) => {
+                self.changes.mark(&[Section::Keys]);
+                Some(params.add_mac_key(&key, self.entropy.as_ref())?)
+            }
+            (mac_key, _) => mac_key,
+        };
+
+        if !self.locked {
+            self.unlock_key = Some(key);
+            return Ok(());
+        }
+
+        self.changes.mark(&[Section::Keys]);
+        self.seal_public_children(&key)?;
+        // with a key cache the keys stay sealed, each is unsealed when used
+        if self.key_cache.is_none() {
+            self.unseal_keys(&key)?;
+        }
+
+        self.unlock_key = Some(key);
+        self.locked = false;
+        Ok(())
+    }
+
+    /// Check the mac of the file the wallet was loaded from with the key of
+    /// its mac unsealed with the wallet key, returning it. Files written before
+    /// the mac had its own key were authenticated with the wallet key
+    fn check_file_mac(&self, key: &[u8; 32]) -> Result<Option<[u8; 32]>, WalletError> {
+        let sealed_mac_key = match &self.encryption {
+            Some(params) => params.mac_key(key)?,
+            None => None,
+        };
+        let mac_key = sealed_mac_key.unwrap_or(*key);
+        if let Some((digest, mac)) = &self.file_mac {
+            let actual = payload_mac(&mac_key, digest);
+            if &actual != mac {
+                return Err(WalletError::Corrupted {
+                    expected: hex::encode(mac),
+                    actual: hex::encode(actual),
+                });
+            }
+        }
+        Ok(sealed_mac_key)
+    }
+
+    /// Change the passphrase of an encrypted wallet, mirroring
+    /// `walletpassphrasechange`. Every sealed key is sealed again with a key
+    /// derived from the new passphrase with a fresh salt and the recommended
+    /// scrypt parameters, the [KeyCache] is purged and the wallet is flushed,
+    /// the keys an unlocked wallet holds unsealed written sealed with the new
+    /// key. The wallet stays locked or unlocked as it was. Fails while the master
+    /// key is in a credential store, its copy there is sealed with the old passphrase,
+    /// and with [WalletError::Corrupted] as [Wallet::unlock] does
+    pub fn change_passphrase(&mut self, old: &str, new: &str) -> Result<(), WalletError> {
+        let mut old_key = match &self.encryption {
+            Some(params) => params.unlock(old)?,
+            None => return Err(WalletError::Unencrypted),
+        };
+        // a file changed without the passphrase isn't authenticated again with the new one
+        self.check_file_mac(&old_key)?;
+        if self.master_key_id.is_some() {
+            return Err(WalletError::Encryption(
+                "the master key in the credential store is sealed with the old passphrase"
+                    .to_string(),
+            ));
+        }
+        let (params, new_key) =
+            EncryptionParams::new(new, self.config.kdf(), self.entropy.as_ref())?;
+        let mac_key = params.mac_key(&new_key)?;
+
+        // every key is sealed again before any is replaced, a failure leaves the wallet as it was.
+        // A key held unsealed is sealed from its private key, the keys of an
+        // unlocked wallet without a seal are sealed with the new key when flushed
+        let mut resealed = vec![];
+        for index in 0..self.arena.count() {
+            let keypair = match self.arena.get_inner(index) {
+                Some(keypair) => keypair,
+                None => continue,
+            };
+            let mut secret = match &keypair.encrypted_private_key {
+                Some(_) if !keypair.private_key.is_wiped() => keypair.private_key.bytes().to_vec(),
+                Some(sealed) => decrypt(&old_key, sealed)?,
+                None => continue,
+            };
+            resealed.push((index, encrypt(&new_key, &secret, self.entropy.as_ref())?));
+            secret.zeroize();
+        }
+        old_key.zeroize();
+        self.changes.mark(&[Section::Keys]);
+        for (index, sealed) in resealed {
+            if let Some(keypair) = self.arena.get_inner_mut(index) {
+                keypair.encrypted_private_key = Some(sealed);
+            }
+        }
+
+        self.encryption = Some(params);
+        self.mac_key = mac_key;
+        if self.unlock_key.is_some() {
+            self.unlock_key = Some(new_key);
+        }
+        self.purge_key_cache();
+        self.file_mac = None;
+        self.flush().map(|_| ())
+    }
+
+    /// Keep the private keys of an encrypted wallet sealed while it's unlocked,
+    /// unsealing each when it's used into a [KeyCache] holding up to `capacity`
+    /// keys. `None` drops the cache, [Wallet::unlock] then unseals every key
+    /// again. The cache isn't saved with the wallet, and keys returned by
+    /// [Wallet::get_address] stay wiped while it's set
+    pub fn set_key_cache(&mut self, capacity: Option<usize>) -> Result<(), WalletError> {
+        if self.encryption.is_none() {
+            return Err(WalletError::Unencrypted);
+        }
+
+        self.key_cache = capacity.map(|capacity| Arc::new(Mutex::new(KeyCache::new(capacity))));
+        if let Some(key) = self.unlock_key {
+            match self.key_cache {
+                Some(_) => self.seal_keys(&key)?,
+                None => self.unseal_keys(&key)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Zero and drop every key of the [KeyCache], the wallet stays unlocked
+    /// and unseals keys again as they're used
+    pub fn purge_key_cache(&self) {
+        if let Some(cache) = &self.key_cache {
+            if let Ok(mut cache) = cache.lock() {
+                cache.purge();
+            }
+        }
+    }
+
+    /// the capacity and use of the [KeyCache], when one is set
+    pub fn key_cache_status(&self) -> Option<KeyCacheStatus> {
+        self.key_cache
+            .as_ref()
+            .and_then(|cache| cache.lock().ok().map(|cache| cache.status()))
+    }
+
+    /// The private key of a keypair. With a [KeyCache] set, a sealed key is
+    /// unsealed into the cache, or read from it, while the wallet is unlocked.
+    /// Otherwise the key is returned as it is in the key graph, wiped when sealed
+    fn unsealed_key(&self, keypair: &KeyPair) -> Result<Key, WalletError> {
+        let mut key = keypair.private_key.clone();
+        let (cache, unlock_key, sealed) = match (
+            &self.key_cache,
+            &self.unlock_key,
+            &keypair.encrypted_private_key,
+        ) {
+            (Some(cache), Some(unlock_key), Some(sealed)) if key.is_wiped() => {
+                (cache, unlock_key, sealed)
+            }
+            _ => return Ok(key),
+        };
+
+        let mut cache = cache.lock().map_err(|_| WalletError::Poisoned)?;
+        match cache.get(&keypair.public_key) {
+            Some(secret) => key.restore(secret.to_vec()),
+            None => {
+                let secret = decrypt(unlock_key, sealed)?;
+                cache.insert(&keypair.public_key, &secret);
+                key.restore(secret);
+            }
+        }
+        Ok(key)
+    }
+
+    /// seal a key just added to the key graph when keys are only unsealed into the [KeyCache]
+    fn seal_cached_key(&mut self, index: usize) -> Result<(), WalletError> {
+        let (cache, key) = match (&self.key_cache, self.unlock_key) {
+            (Some(cache), Some(key)) => (cache.clone(), key),
+            _ => return Ok(()),
+        };
+
+        if let Some(keypair) = self.arena.get_inner_mut(index) {
+            if keypair.private_key.is_wiped() {
+                return Ok(());
+            }
+            self.changes.mark(&[Section::Keys]);
+            cache
+                .lock()
+                .map_err(|_| WalletError::Poisoned)?
+                .insert(&keypair.public_key, keypair.private_key.bytes());
+            keypair.encrypted_private_key = Some(encrypt(
+                &key,
+                keypair.private_key.bytes(),
+                self.entropy.as_ref(),
+            )?);
+            keypair.private_key.wipe();
+        }
+        Ok(())
+    }
+
+    /// check if the private keys are currently sealed
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Sign a 32 byte digest with the key owning an address, through
+    /// the signer of the wallet when one is set
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(address = %address), err(Debug))
+    )]
+    pub fn sign_data(&self, address: String, data: Vec<u8>) -> Result<Vec<u8>, WalletError> {
+        let keypair = self
+            .find_node(&address)
+            .map(|node| &node.data)
+            .ok_or_else(|| WalletError::Key("address is not in this wallet".to_string()))?;
+
+        let signature = self.sign_digest(keypair, &data)?;
+        Ok(hex::encode(signature).into_bytes())
+    }
+
+    /// Sign the tagged hash of data with the key owning an address, see
+    /// [Digest32::tagged], so the signature can't be taken for one of other
+    /// data, eg of a transaction
+    pub fn sign_raw_digest_with_tag(
+        &self,
+        address: String,
+        tag: &str,
+        data: &[u8],
+    ) -> Result<Vec<u8>, WalletError> {
+        self.sign_data(address, Digest32::tagged(tag, data).as_bytes().to_vec())
+    }
+
+    /// DER sign a digest with a key of the wallet, through its signer when one is set
+    fn sign_digest(&self, keypair: &KeyPair, digest: &[u8]) -> Result<Vec<u8>, WalletError> {
+        self.sign_digest_with(&mut SigningSession::default(), keypair, digest)
+    }
+
+    /// [Wallet::sign_digest] keeping the keys unsealed in a session
+    fn sign_digest_with(
+        &self,
+        session: &mut SigningSession,
+        keypair: &KeyPair,
+        digest: &[u8],
+    ) -> Result<Vec<u8>, WalletError> {
+        match &self.signer {
+            Some(signer) => signer
+                .sign(&keypair.public_key, digest)
+                .map_err(WalletError::Signer),
+            None if self.watch_only => Err(WalletError::WatchOnly),
+            None => {
+                let key = self.session_key(&mut session.keys, keypair)?;
+                key.sign_der_with(&session.secp, digest)
+                    .map_err(|e| WalletError::Key(e.to_string()))
+            }
+        }
+    }
+
+    /// Sign a taproot key path sighash with the BIP86 tweaked key of a keypair.
+    /// A signer only signs ECDSA, so the input can't be signed through one
+    fn sign_taproot_with(
+        &self,
+        session: &mut SigningSession,
+        keypair: &KeyPair,
+        input: usize,
+        digest: &[u8],
+    ) -> Result<Vec<u8>, WalletError> {
+        match &self.signer {
+            Some(_) => Err(WalletError::UnsupportedInput(input)),
+            None if self.watch_only => Err(WalletError::WatchOnly),
+            None => {
+                let mut aux = [0; 32];
+                self.entropy.fill_bytes(&mut aux);
+                let key = self.session_key(&mut session.keys, keypair)?;
+                key.sign_taproot_with(&session.secp, digest, &aux)
+                    .map_err(|e| WalletError::Key(e.to_string()))
+            }
+        }
+    }
+
+    /// the private key of a keypair, unsealed once per session
+    fn session_key<'a>(
+        &self,
+        keys: &'a mut HashMap<Vec<u8>, Key>,
+        keypair: &KeyPair,
+    ) -> Result<&'a Key, WalletError> {
+        self.ensure_unlocked()?;
+        if !keys.contains_key(&keypair.public_key) {
+            let key = self.unsealed_key(keypair)?;
+            keys.insert(keypair.public_key.clone(), key);
+        }
+        Ok(&keys[&keypair.public_key])
+    }
+
+    /// Sign every input of a transaction spending a coin of the wallet with
+    /// [SigHashType::All], each with the key of the address of the output it spends.
+    /// P2PKH, P2WPKH, P2SH-P2WPKH and P2PK inputs are signed, through the signer of
+    /// the wallet when one is set, and BIP86 P2TR inputs through their key path
+    /// with SIGHASH_DEFAULT, which a signer can't do. Inputs spending coins of other wallets are
+    /// left as they are, eg for co-signers. Returns how many inputs were signed
+    pub fn sign_transaction(&self, tx: &mut Transaction) -> Result<usize, WalletError> {
+        self.sign_transaction_with(tx, SigHashType::All)
+    }
+
+    /// [Wallet::sign_transaction] with a sighash type, eg [SigHashType::AllPlusAnyoneCanPay]
+    /// so others may add inputs after. Taproot inputs take [SigHashType::All] as
+    /// SIGHASH_DEFAULT, which commits to the same without a flag on the signature
+    pub fn sign_transaction_with(
+        &self,
+        tx: &mut Transaction,
+        sighash_type: SigHashType,
+    ) -> Result<usize, WalletError> {
+        self.sign_in_session(&mut SigningSession::default(), tx, sighash_type)
+    }
+
+    /// Sign a batch of transactions as [Wallet::sign_transaction] does, eg
+    /// the withdrawals of an exchange. The lock is checked once and the keys
+    /// and signing context are shared by the batch, each key is unsealed once.
+    /// Either every transaction is signed or, on an error, none is changed.
+    /// Returns how many inputs of each transaction were signed
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(transactions = txs.len()), err(Debug))
+    )]
+    pub fn sign_all(&self, txs: &mut [Transaction]) -> Result<Vec<usize>, WalletError> {
+        if self.signer.is_none() {
+            match self.watch_only {
+                true => return Err(WalletError::WatchOnly),
+                false => self.ensure_unlocked()?,
+            }
+        }
+
+        let mut session = SigningSession::default();
+        let mut signed_txs = txs.to_vec();
+        let signed = signed_txs
+            .iter_mut()
+            .map(|tx| self.sign_in_session(&mut session, tx, SigHashType::All))
+            .collect::<Result<Vec<usize>, WalletError>>()?;
+        txs.clone_from_slice(&signed_txs);
+
+        Ok(signed)
+    }
+
+    fn sign_in_session(
+        &self,
+        session: &mut SigningSession,
+        tx: &mut Transaction,
+        sighash_type: SigHashType,
+    ) -> Result<usize, WalletError> {
+        self.sign_inputs_with(session, tx, sighash_type)
+            .map(|signatures| signatures.len())
+    }
+
+    /// [Wallet::sign_transaction], returning the signature and public key of every input signed
+    fn sign_inputs_with(
+        &self,
+        session: &mut SigningSession,
+        tx: &mut Transaction,
+        sighash_type: SigHashType,
+    ) -> Result<Vec<InputSignature>, WalletError> {
+        let mut signed = vec![];
+        let mut cache = SighashCache::new();
+
+        for (index, input) in tx.inputs().iter().enumerate() {
+            let pk_script = Script::new(input.utxo_pk_script().to_vec());
+            let keypair = match self
+                .find_by_script(pk_script.as_bytes())
+                .or_else(|| {
+                    pk_script
+                        .p2pk_public_key()
+                        .and_then(|key| self.find_by_pubkey(key))
+                })
+                .map(|node| &node.data)
+            {
+                Some(keypair) => keypair,
+                None => continue,
+            };
+
+            // P2TR is spent through its key path, SIGHASH_DEFAULT implied by a
+            // 64 byte signature alone in the witness, other types appended to it
+            let mode = tx
+                .sighash_mode(index, &[])
+                .map_err(WalletError::Transaction)?;
+            if mode == SighashMode::Taproot {
+                let taproot_type = match sighash_type {
+                    SigHashType::All => SigHashType::TaprootDefault,
+                    sighash_type => sighash_type,
+                };
+                let sighash = tx
+                    .sighash_with(&mut cache, index, &[], taproot_type)
+                    .map_err(WalletError::Transaction)?;
+                let mut signature = self.sign_taproot_with(session, keypair, index, &sighash)?;
+                if taproot_type != SigHashType::TaprootDefault {
+                    signature.push(taproot_type.to_byte());
+                }
+                tx.set_witness(index, vec![signature.clone()])
+                    .map_err(WalletError::Transaction)?;
+                signed.push(InputSignature {
+                    input: index,
+                    signature: hex::encode(signature),
+                    public_key: hex::encode(&pk_script.as_bytes()[2..]),
+                });
+                continue;
+            }
+
+            // P2PKH commits to the key as it was created, P2PK to the key of its
+            // output, segwit only takes compressed keys
+            let pubkey = match mode {
+                _ if pk_script.classify() == ScriptType::P2pkh => keypair.public_key.clone(),
+                _ => match pk_script.p2pk_public_key() {
+                    Some(key) => key.to_vec(),
+                    None => compress_public_key(&keypair.public_key)
+                        .map_err(|e| WalletError::Key(e.to_string()))?,
+                },
+            };
+            // P2PK signs its own output script, the others the script code of
+            // P2PKH and of P2WPKH, nested or not
+            let script_code = match pk_script.classify() {
+                ScriptType::P2pk => pk_script.clone(),
+                _ => Script::builder()
+                    .push_opcode(OP_DUP)
+                    .push_opcode(OP_HASH160)
+                    .push_slice(&hash160(&pubkey))
+                    .push_opcode(OP_EQUALVERIFY)
+                    .push_opcode(OP_CHECKSIG)
+                    .build(),
+            };
+
+            let sighash = tx
+                .sighash_with(&mut cache, index, script_code.as_bytes(), sighash_type)
+                .map_err(WalletError::Transaction)?;
+            let mut signature = self.sign_digest_with(session, keypair, &sighash)?;
+            signature.push(sighash_type.to_byte());
+            tx.set_signature_with(&session.secp, index, &signature, &pubkey)
+                .map_err(WalletError::Transaction)?;
+            signed.push(InputSignature {
+                input: index,
+                signature: hex::encode(signature),
+                public_key: hex::encode(pubkey),
+            });
+        }
+
+        Ok(signed)
+    }
+
+    /// Describe an unsigned transaction for an air-gapped wallet holding the
+    /// keys to sign with [Wallet::sign_signing_request], eg from a watch-only
+    /// wallet. Each input spending a coin of an account carries the path of
+    /// its key, and outputs paying the change chain are marked, see
+    /// [crate::SigningOutput::is_change]. The signatures come back through
+    /// [Wallet::apply_signing_response]
+    pub fn export_signing_request(&self, tx: &Transaction) -> Result<SigningRequest, WalletError> {
+        let fingerprint = match self
+            .arena
+            .root()
+            .and_then(|root| self.arena.get_inner(root))
+        {
+            Some(master) => Some(
+                key_fingerprint(&master.public_key).map_err(|e| WalletError::Key(e.to_string()))?,
+            ),
+            None => None,
+        };
+        let paths = tx
+            .inputs()
+            .iter()
+            .map(|input| {
+                Script::new(input.utxo_pk_script().to_vec())
+                    .to_address(self.network)
+                    .and_then(|address| self.key_path(&address))
+            })
+            .collect();
+
+        let mut request = SigningRequest::new(tx, fingerprint, paths);
+        for (output, signing) in tx.outputs().iter().zip(request.outputs.iter_mut()) {
+            if self.is_change(output.pk_script()) {
+                signing.set_change();
+            }
+        }
+        Ok(request)
+    }
+
+    /// Sign a [SigningRequest] exported by the online copy of the wallet, on
+    /// a machine that never goes online. The keys of inputs not derived yet
+    /// are derived from their path when the request comes from this wallet,
+    /// a path only counts when its key pays to the output spent. Inputs of
+    /// other wallets are left unsigned. Fails with [TransactionError::TxIdMismatch]
+    /// when the transaction of the request was changed
+    pub fn sign_signing_request(
+        &mut self,
+        request: &SigningRequest,
+    ) -> Result<SigningResponse, WalletError> {
+        let mut tx = request.transaction().map_err(WalletError::Transaction)?;
+        let root = self.arena.root().ok_or(WalletError::Uninitialized)?;
+        let master = self
+            .arena
+            .get_inner(root)
+            .ok_or(WalletError::Uninitialized)?;
+        let fingerprint =
+            key_fingerprint(&master.public_key).map_err(|e| WalletError::Key(e.to_string()))?;
+
+        for (input, signing) in tx.inputs().iter().zip(request.inputs.iter()) {
+            let path = match (&request.fingerprint, &signing.path) {
+                (Some(from), Some(path)) if *from == fingerprint => path,
+                _ => continue,
+            };
+            let script = Script::new(input.utxo_pk_script().to_vec());
+            let address = match script.to_address(self.network) {
+                Some(address) if self.node_id(&address).is_none() => address,
+                _ => continue,
+            };
+            let account_type = match script.classify() {
+                ScriptType::P2pkh => AccountType::Legacy,
+                ScriptType::P2sh => AccountType::NestedSegwit,
+                ScriptType::P2wpkh => AccountType::NativeSegwit,
+                ScriptType::P2tr => AccountType::Taproot,
+                _ => continue,
+            };
+            let key = self.derive_path(path)?;
+            if account_type.address(&key).ok().as_deref() != Some(address.as_str()) {
+                debug!(address = %address, "signing request path doesn't derive its address");
+                continue;
+            }
+
+            let mut node = root;
+            for child in path.iter() {
+                node = self.child(node, *child, account_type)?;
+            }
+        }
+
+        let signatures =
+            self.sign_inputs_with(&mut SigningSession::default(), &mut tx, SigHashType::All)?;
+        Ok(SigningResponse {
+            tx_id: request.tx_id.clone(),
+            signatures,
+            proprietary: request.proprietary.clone(),
+        })
+    }
+
+    /// Add the signatures of a [SigningResponse] to the transaction they were
+    /// made for, checking each of them. Either every signature is added or,
+    /// on an error, the transaction isn't changed. Returns how many were added
+    pub fn apply_signing_response(
+        &self,
+        tx: &mut Transaction,
+        response: &SigningResponse,
+    ) -> Result<usize, WalletError> {
+        if tx.tx_id() != response.tx_id {
+            return Err(WalletError::Transaction(TransactionError::TxIdMismatch(
+                response.tx_id.clone(),
+            )));
+        }
+
+        let mut signed = tx.clone();
+        for signature in response.signatures.iter() {
+            let invalid =
+                || WalletError::Transaction(TransactionError::InvalidSignature(signature.input));
+            let der = hex::decode(&signature.signature).map_err(|_| invalid())?;
+            let public_key = hex::decode(&signature.public_key).map_err(|_| invalid())?;
+            signed
+                .set_signature(signature.input, &der, &public_key)
+                .map_err(WalletError::Transaction)?;
+        }
+        *tx = signed;
+
+        Ok(response.signatures.len())
+    }
+
+    /// the path of the key of an address from the master key, none for imported keys
+    fn key_path(&self, address: &str) -> Option<Vec<ChildNumber>> {
+        let mut node = self.node_id(address)?;
+        let mut path = vec![];
+        while Some(node) != self.arena.root() {
+            let current = self.arena.get(node)?;
+            path.push(current.data.index?);
+            node = current.parent()?;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// derive the key at a path from the master key, without adding it to the key graph
+    fn derive_path(&self, path: &[ChildNumber]) -> Result<Key, WalletError> {
+        let root = self.arena.root().ok_or(WalletError::Uninitialized)?;
+        let master = self
+            .arena
+            .get_inner(root)
+            .ok_or(WalletError::Uninitialized)?;
+        let mut key = self.unsealed_key(master)?;
+        if key.is_wiped() {
+            return Err(WalletError::MasterKeyNotLoaded);
+        }
+        for child in path.iter() {
+            key = key
+                .derive_child_private_key(*child)
+                .map_err(|e| WalletError::Key(e.to_string()))?;
+        }
+        Ok(key)
+    }
+
+    /// Describe a transaction drafted by the wallet, eg by [Wallet::new_transaction],
+    /// with the coins it spends and where its outputs go, to review it before
+    /// it's signed and broadcast, see [SpendPreview::privacy_report]
+    pub fn preview_spend(&self, tx: &Transaction) -> SpendPreview {
+        let address_of = |script: &[u8]| Script::new(script.to_vec()).to_address(self.network);
+
+        let inputs = tx
+            .inputs()
+            .iter()
+            .map(|input| {
+                let address = address_of(input.utxo_pk_script());
+                PreviewInput {
+                    previous_output: input.previous_output().clone(),
+                    value: input.utxo_value(),
+                    script_type: Script::new(input.utxo_pk_script().to_vec()).classify(),
+                    account: address
+                        .as_deref()
+                        .and_then(|address| self.account_of(address)),
+                    // the coin spent is one of them
+                    reused: address
+                        .as_deref()
+                        .is_some_and(|address| self.payments_to(address).len() > 1),
+                    address,
+                }
+            })
+            .collect();
+
+        let outputs = tx
+            .outputs()
+            .iter()
+            .map(|output| {
+                let address = address_of(output.pk_script());
+                let is_mine = self.is_mine(output.pk_script());
+                let reused = match (&address, is_mine) {
+                    (Some(address), true) => !self.payments_to(address).is_empty(),
+                    (Some(address), false) => self
+                        .history
+                        .iter()
+                        .any(|tx| tx.counterparty() == Some(address.as_str())),
+                    (None, _) => false,
+                };
+                PreviewOutput {
+                    value: output.value(),
+                    script_type: Script::new(output.pk_script().to_vec()).classify(),
+                    is_mine,
+                    is_change: self.is_change(output.pk_script()),
+                    reused,
+                    address,
+                }
+            })
+            .collect();
+
+        SpendPreview {
+            tx_id: tx.tx_id(),
+            inputs,
+            outputs,
+            fee: tx.fee(),
+        }
+    }
+
+    /// Start a payjoin (BIP78) paying the address of the params from a signed
+    /// transaction of the wallet, eg made with [Wallet::new_transaction]. It
+    /// is the original posted to the receiver, and broadcast when the payjoin
+    /// fails. The output the receiver may take fees from pays to the wallet
+    #[cfg(feature = "payjoin")]
+    pub fn payjoin_request(
+        &self,
+        original: &Transaction,
+        params: PayjoinParams,
+    ) -> Result<PayjoinRequest, PayjoinError> {
+        if let Some((_, index)) = params.fee_contribution() {
+            match original.get_output(index) {
+                Some(output) if self.is_mine(output.pk_script()) => {}
+                _ => {
+                    return Err(PayjoinError::InvalidOriginal(format!(
+                        "output {} doesn't pay to the wallet",
+                        index
+                    )))
+                }
+            }
+        }
+        if let Some(index) = original
+            .inputs()
+            .iter()
+            .position(|input| !self.is_mine(input.utxo_pk_script()))
+        {
+            return Err(PayjoinError::InvalidOriginal(format!(
+                "input {} doesn't spend a coin of the wallet",
+                index
+            )));
+        }
+
+        PayjoinRequest::new(original, params, self.network)
+    }
+
+    /// Check the base64 PSBT a payjoin receiver answered a request with, as
+    /// BIP78 asks of senders, and sign the inputs of the wallet in it. Fails
+    /// with [PayjoinError::InvalidProposal] when the proposal must not be
+    /// signed, the original of the request is broadcast instead
+    #[cfg(feature = "payjoin")]
+    pub fn process_payjoin_proposal(
+        &self,
+        request: &PayjoinRequest,
+        proposal: &str,
+    ) -> Result<Transaction, PayjoinError> {
+        let mut tx = request.check_proposal(proposal, self.network)?;
+        let signed = self
+            .sign_transaction(&mut tx)
+            .map_err(PayjoinError::Wallet)?;
+        if signed != request.original().tx_in_count() {
+            return Err(PayjoinError::InvalidProposal(format!(
+                "{} inputs of the wallet, the original has {}",
+                signed,
+                request.original().tx_in_count()
+            )));
+        }
+        request.check_fee_rate(&tx)?;
+
+        Ok(tx)
+    }
+
+    /// Send a payjoin: post the original to the receiver with a client, eg a
+    /// [crate::BackendConfig] for onion services, then check and sign its
+    /// proposal with [Wallet::process_payjoin_proposal]. The payjoin returned
+    /// is for the caller to broadcast, on an error the original is
+    #[cfg(feature = "payjoin")]
+    pub fn send_payjoin(
+        &self,
+        original: &Transaction,
+        params: PayjoinParams,
+        client: &dyn PayjoinClient,
+    ) -> Result<Transaction, PayjoinError> {
+        let request = self.payjoin_request(original, params)?;
+        let proposal = client.post(&request.url(), request.psbt())?;
+        self.process_payjoin_proposal(&request, &proposal)
+    }
+
+    /// Annotate the inputs and outputs of a transaction with their addresses,
+    /// labels and whether they belong to the wallet, see [AnnotatedTransaction].
+    /// Inputs without the coin they spend, eg of a decoded transaction, are
+    /// looked up in the outputs of the wallet, spent or not
+    pub fn annotate(&self, tx: &Transaction) -> AnnotatedTransaction {
+        let address_of = |script: &[u8]| Script::new(script.to_vec()).to_address(self.network);
+        let label_of =
+            |address: &Option<String>| address.as_deref().and_then(|address| self.label(address));
+
+        let inputs = tx
+            .inputs()
+            .iter()
+            .map(|input| {
+                let outpoint = input.previous_output();
+                let known = match input.utxo_pk_script().is_empty() {
+                    true => self
+                        .utxos
+                        .iter()
+                        .chain(self.spends.iter().map(|spend| spend.utxo()))
+                        .find(|utxo| utxo.outpoint() == outpoint)
+                        .map(|utxo| (utxo.value(), Some(utxo.address().to_string()))),
+                    false => Some((input.utxo_value(), address_of(input.utxo_pk_script()))),
+                };
+                let (value, address) = known.unwrap_or((0, None));
+                AnnotatedInput {
+                    previous_output: outpoint.clone(),
+                    value,
+                    is_mine: address
+                        .as_deref()
+                        .is_some_and(|address| self.owns_address(address)),
+                    label: label_of(&address).map(str::to_string),
+                    address,
+                }
+            })
+            .collect();
+
+        let outputs = tx
+            .outputs()
+            .iter()
+            .map(|output| {
+                let address = address_of(output.pk_script());
+                AnnotatedOutput {
+                    value: output.value(),
+                    is_mine: self.is_mine(output.pk_script()),
+                    is_change: self.is_change(output.pk_script()),
+                    label: label_of(&address).map(str::to_string),
+                    address,
+                }
+            })
+            .collect();
+
+        AnnotatedTransaction::new(tx.clone(), inputs, outputs)
+    }
+
+    /// the transactions known to have paid to an address of the wallet
+    fn payments_to(&self, address: &str) -> Vec<String> {
+        let mut tx_ids: Vec<String> = self
+            .history
+            .iter()
+            .filter(|tx| tx.addresses().iter().any(|paid| paid == address))
+            .map(|tx| tx.tx_id().to_string())
+            .collect();
+        for utxo in self.utxos.iter().filter(|utxo| utxo.address() == address) {
+            let tx_id = utxo.outpoint().hash();
+            if !tx_ids.contains(&tx_id) {
+                tx_ids.push(tx_id);
+            }
+        }
+        tx_ids
+    }
+
+    /// Decode a raw transaction, eg one built by the wallet, with the coins of
+    /// the wallet it spends. The fee is known when it only spends wallet coins
+    pub fn decode_transaction(&self, hex: &str) -> Result<TxSummary, WalletError> {
+        decode_transaction_with(hex, self.network, |outpoint| {
+            self.utxos
+                .iter()
+                .find(|utxo| utxo.outpoint() == outpoint)
+                .map(|utxo| utxo.output().clone())
+        })
+        .map_err(WalletError::Transaction)
+    }
+
+    /// Sign with a [KeystoreSigner] instead of the keys of the wallet,
+    /// the signer isn't saved with the wallet and is set again once loaded
+    pub fn set_signer(&mut self, signer: Arc<dyn KeystoreSigner>) {
+        self.signer = Some(signer);
+    }
+
+    /// the signer set with [Wallet::set_signer]
+    pub fn signer(&self) -> Option<&Arc<dyn KeystoreSigner>> {
+        self.signer.as_ref()
+    }
+
+    /// when the wallet was created, recorded by [Wallet::init]
+    pub fn birthday(&self) -> Birthday {
+        self.birthday
+    }
+
+    /// Set the height of a block mined before the wallet was created, eg
+    /// for a restored wallet whose age is roughly known. [Wallet::rescan]
+    /// starts there, coins received before it won't be found
+    pub fn set_birthday(&mut self, height: u32) {
+        self.changes.mark(&[Section::Settings]);
+        self.birthday.height = Some(height);
+    }
+
+    /// the bounds on the fee of the transactions built by the wallet
+    pub fn fee_limits(&self) -> FeeLimits {
+        self.fee_limits
+    }
+
+    /// Change the bounds on the fee of [Wallet::new_transaction] and
+    /// [Wallet::drain_account], [FeeLimits::none] to accept any fee
+    pub fn set_fee_limits(&mut self, fee_limits: FeeLimits) {
+        self.changes.mark(&[Section::Settings]);
+        self.fee_limits = fee_limits;
+    }
+
+    /// whether the transactions built are locked to the tip, see [Wallet::set_anti_fee_snipe]
+    pub fn anti_fee_snipe(&self) -> bool {
+        self.anti_fee_snipe
+    }
+
+    /// Lock the transactions built by [Wallet::new_transaction], [Wallet::drain_account]
+    /// and [Wallet::consolidate] to the tip last scanned, see
+    /// [TransactionBuilder::anti_fee_snipe]. On by default, a wallet that never
+    /// scanned the chain builds them without a lock time
+    pub fn set_anti_fee_snipe(&mut self, enabled: bool) {
+        self.changes.mark(&[Section::Settings]);
+        self.anti_fee_snipe = enabled;
+    }
+
+    /// lock a transaction being built to the tip last scanned, when enabled
+    fn lock_to_tip(&self, builder: &mut TransactionBuilder) {
+        if let (true, Some(tip)) = (self.anti_fee_snipe, self.scanned_blocks.last()) {
+            builder.anti_fee_snipe(true).tip_height(tip.height);
+        }
+    }
+
+    /// replace the clock the wallet reads the time from, the system clock by default
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Replace the source of the randomness of the mnemonics the wallet
+    /// generates and of the order of the transactions it builds, the os by
+    /// default. A [crate::SeededEntropy] makes them the same on every run, for tests
+    pub fn set_entropy(&mut self, entropy: Arc<dyn EntropySource>) {
+        self.entropy = entropy;
+    }
+
+    pub fn entropy(&self) -> &Arc<dyn EntropySource> {
+        &self.entropy
+    }
+
+    /// Move every private key out of the wallet into a [MemorySigner],
+    /// leaving a watch-only wallet. The keys can then be stored apart,
+    /// eg in an [crate::EncryptedFileSigner], and set back with [Wallet::set_signer].
+    /// New keys can't be derived once the private keys are gone
+    pub fn split_keystore(&mut self) -> Result<MemorySigner, WalletError> {
+        self.ensure_unlocked()?;
+
+        let mut signer = MemorySigner::default();
+        for index in 0..self.arena.count() {
+            let key = match self.arena.get_inner(index) {
+                Some(keypair) => self.unsealed_key(keypair)?,
+                None => continue,
+            };
+            if key.is_wiped() {
+                continue;
+            }
+
+            signer.insert(key).map_err(WalletError::Signer)?;
+            self.changes.mark(&[Section::Keys]);
+            if let Some(keypair) = self.arena.get_inner_mut(index) {
+                keypair.private_key.wipe();
+                keypair.encrypted_private_key = None;
+            }
+        }
+        self.purge_key_cache();
+
+        self.watch_only = true;
+        Ok(signer)
+    }
+
+    /// Move the master key out of the wallet into a credential store, eg an
+    /// [crate::OsKeychain]. The key is sealed with the wallet passphrase when
+    /// the wallet is encrypted. It's never written to the wallet file again,
+    /// accounts are derived after loading it with [Wallet::load_master_key].
+    /// Returns the id the key is stored under
+    pub fn store_master_key(
+        &mut self,
+        backend: &dyn KeystoreBackend,
+    ) -> Result<String, WalletError> {
+        self.ensure_unlocked()?;
+        if self.watch_only {
+            return Err(WalletError::WatchOnly);
+        }
+
+        let root = self.arena.root().ok_or(WalletError::Uninitialized)?;
+        let master = self
+            .arena
+            .get_inner(root)
+            .ok_or(WalletError::Uninitialized)?;
+        let private_key = self.unsealed_key(master)?;
+        if private_key.is_wiped() {
+            return Err(WalletError::MasterKeyNotLoaded);
+        }
+
+        let fingerprint =
+            key_fingerprint(&master.public_key).map_err(|e| WalletError::Key(e.to_string()))?;
+        let id = format!("master-{}", hex::encode(fingerprint));
+        let secret = match &self.unlock_key {
+            Some(key) => encrypt(key, private_key.bytes(), self.entropy.as_ref())?,
+            None => private_key.bytes().to_vec(),
+        };
+        backend.store(&id, &secret).map_err(WalletError::Signer)?;
+        self.changes.mark(&[Section::Keys]);
+
+        if let Some(master) = self.arena.get_inner_mut(root) {
+            master.private_key.wipe();
+            master.encrypted_private_key = None;
+        }
+        self.purge_key_cache();
+        self.master_key_id = Some(id.clone());
+
+        Ok(id)
+    }
+
+    /// Bring the master key stored with [Wallet::store_master_key] back into
+    /// memory. It stays sealed until [Wallet::unlock] if the wallet is locked
+    pub fn load_master_key(&mut self, backend: &dyn KeystoreBackend) -> Result<(), WalletError> {
+        let id = match &self.master_key_id {
+            Some(id) => id.clone(),
+            None => return Ok(()),
+        };
+
+        let secret = backend
+            .load(&id)
+            .map_err(WalletError::Signer)?
+            .ok_or(WalletError::Signer(SignerError::UnknownKey(id)))?;
+
+        let root = self.arena.root().ok_or(WalletError::Uninitialized)?;
+        let bytes = match (&self.encryption, &self.unlock_key) {
+            (Some(_), None) => None,
+            (Some(_), Some(key)) => Some(decrypt(key, &secret)?),
+            (None, _) => Some(secret.clone()),
+        };
+
+        let master = self
+            .arena
+            .get_inner_mut(root)
+            .ok_or(WalletError::Uninitialized)?;
+        match bytes {
+            Some(bytes) => master.private_key.restore(bytes),
+            None => master.encrypted_private_key = Some(secret),
+        }
+
+        self.seal_cached_key(root)
+    }
+
+    /// the id of the master key in a credential store, see [Wallet::store_master_key]
+    pub fn master_key_id(&self) -> Option<&str> {
+        self.master_key_id.as_deref()
+    }
+
+    /// a copy of the wallet without the master private key, written in place of
+    /// the wallet once the master key lives in a [KeystoreBackend]
+    fn without_master_key(&self) -> Result<Self, WalletError> {
+        let mut wallet = self.clone();
+        let root = wallet.arena.root().ok_or(WalletError::Uninitialized)?;
+
+        if let Some(master) = wallet.arena.get_inner_mut(root) {
+            master.private_key.wipe();
+            master.encrypted_private_key = None;
+        }
+
+        Ok(wallet)
+    }
+
+    /// Export the key owning an address as a [PaperWallet], BIP38
+    /// encrypting the private key when a passphrase is given
+    pub fn export_paper_wallet(
+        &self,
+        address: &str,
+        passphrase: Option<&str>,
+    ) -> Result<PaperWallet, WalletError> {
+        self.ensure_unlocked()?;
+
+        let key = self.unsealed_key(
+            self.find_node(address)
+                .map(|node| &node.data)
+                .ok_or_else(|| WalletError::UnknownAddress(address.to_string()))?,
+        )?;
+        if key.is_wiped() {
+            return Err(WalletError::WatchOnly);
+        }
+
+        let account_type = self
+            .account_of(address)
+            .and_then(|account| self.account(account))
+            .map(|account| account.account_type())
+            .unwrap_or(AccountType::Legacy);
+
+        PaperWallet::new(&key, account_type, passphrase)
+            .map_err(|e| WalletError::Key(e.to_string()))
+    }
+
+    /// Back up the wallet as SLIP-39 mnemonic shares, any `threshold` of
+    /// the `shares` restore it with [Wallet::restore_from_shamir_shares].
+    /// The secret shared is the extended master key, not a BIP32 seed
+    pub fn export_shamir_shares(
+        &self,
+        threshold: u8,
+        shares: u8,
+        passphrase: &str,
+    ) -> Result<Vec<String>, WalletError> {
+        self.ensure_unlocked()?;
+
+        let root = self.arena.root().ok_or(WalletError::Uninitialized)?;
+        let master = self.unsealed_key(
+            self.arena
+                .get_inner(root)
+                .ok_or(WalletError::Uninitialized)?,
+        )?;
+        if master.is_wiped() {
+            return Err(match self.watch_only {
+                true => WalletError::WatchOnly,
+                false => WalletError::MasterKeyNotLoaded,
+            });
+        }
+
+        split_secret(
+            &master.extended_private_key(),
+            threshold,
+            shares,
+            passphrase,
+        )
+        .map_err(WalletError::Shamir)
+    }
+
+    /// check if the private keys were moved out with [Wallet::split_keystore]
+    pub fn is_watch_only(&self) -> bool {
+        self.watch_only
+    }
+
+    /// fail with [WalletError::Locked] when private keys are sealed
+    fn ensure_unlocked(&self) -> Result<(), WalletError> {
+        match self.locked {
+            true => Err(WalletError::Locked),
+            false => Ok(()),
+        }
+    }
+
+    /// Fail with [WalletError::Locked] when the public keys of an encrypted
+    /// wallet weren't authenticated, its file mac unchecked until it's unlocked
+    /// once. Addresses derived from them could pay someone else
+    fn ensure_authenticated(&self) -> Result<(), WalletError> {
+        match self.encryption.is_some() && self.mac_key.is_none() {
+            true => Err(WalletError::Locked),
+            false => Ok(()),
+        }
+    }
+
+    /// Check a batch of addresses can be paid to from the network of the
+    /// wallet, see [validate_address]. Use before building a transaction to
+    /// show why each recipient is rejected
+    pub fn validate_addresses<I, S>(&self, addresses: I) -> Vec<AddressValidation>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        validate_addresses(addresses, self.network)
+    }
+
+    /// return the accounts of the wallet, the position of an
+    /// account is its account number within the wallet
+    pub fn accounts(&self) -> &[Account] {
+        &self.accounts
+    }
+
+    /// The accounts with the chains and keys derived from them, in
+    /// account number order, to show the hierarchy of the wallet
+    pub fn account_views(&self) -> Vec<AccountView<'_>> {
+        self.accounts
+            .iter()
+            .enumerate()
+            .map(|(number, account)| {
+                AccountView::new(&self.arena, self.network, number as u32, account)
+            })
+            .collect()
+    }
+
+    /// an account with the chains and keys derived from it
+    pub fn account_view(&self, number: u32) -> Option<AccountView<'_>> {
+        self.account(number)
+            .map(|account| AccountView::new(&self.arena, self.network, number, account))
+    }
+
+    /// the keys imported on their own, outside of any account
+    pub fn imported_keys(&self) -> Vec<KeyView<'_>> {
+        self.arena
+            .nodes()
+            .iter()
+            .filter(|node| matches!(node.data.key_type, KeyType::Imported))
+            .map(|node| KeyView::imported(&node.key, &node.data))
+            .collect()
+    }
+
+    /// get an account by its account number
+    pub fn account(&self, number: u32) -> Option<&Account> {
+        self.accounts.get(number as usize)
+    }
+
+    /// Create the next account of a type at `m/purpose'/coin'/account'`
+    /// returns the new account number
+    pub fn new_account(&mut self, account_type: AccountType) -> Result<u32, WalletError> {
+        self.ensure_unlocked()?;
+
+        let root = self.arena.root().ok_or(WalletError::Uninitialized)?;
+        let index = self
+            .accounts
+            .iter()
+            .filter(|account| account.account_type() == account_type)
+            .count() as u32;
+
+        let mut node = root;
+        for child in account_type.account_path(self.network, index).iter() {
+            node = self.child(node, *child, AccountType::Legacy)?;
+        }
+
+        self.changes.mark(&[Section::Keys]);
+        self.accounts.push(Account::new(index, account_type, node));
+        Ok(self.accounts.len() as u32 - 1)
+    }
+
+    /// Create a new account and archive the most recent active one
+    /// archived accounts keep their funds but hand out no new receive addresses.
+    /// The new account has the same type as the archived one.
+    /// returns the new account number
+    pub fn rotate_account(&mut self) -> Result<u32, WalletError> {
+        let previous = self
+            .accounts
+            .iter()
+            .rposition(|account| !account.is_archived());
+        let account_type = previous
+            .map(|previous| self.accounts[previous].account_type())
+            .unwrap_or_default();
+
+        let number = self.new_account(account_type)?;
+
+        if let Some(previous) = previous {
+            self.accounts[previous].archive();
+        }
+
+        Ok(number)
+    }
+
+    /// Export the extended public key of an account with its origin
+    /// works while locked, no private key material is used
+    pub fn account_xpub(&self, account: u32) -> Result<AccountXpub, WalletError> {
+        let account = self
+            .account(account)
+            .ok_or(WalletError::AccountNotFound(account))?;
+        let node = account.node();
+
+        let root = self.arena.root().ok_or(WalletError::Uninitialized)?;
+        let master = self
+            .arena
+            .get_inner(root)
+            .ok_or(WalletError::Uninitialized)?;
+        let keypair = self
+            .arena
+            .get_inner(node)
+            .ok_or(WalletError::Uninitialized)?;
+        let key_error = |e: KeyError| WalletError::Key(e.to_string());
+        let key = &keypair.private_key;
+        let xpub = serialize_xpub(
+            self.network,
+            key.depth(),
+            key.parent_fingerprint(),
+            key.child_number().map(u32::from).unwrap_or_default(),
+            key.chain_code(),
+            &keypair.public_key,
+        )
+        .map_err(key_error)?;
+
+        Ok(AccountXpub {
+            fingerprint: key_fingerprint(&master.public_key).map_err(key_error)?,
+            path: account.path(self.network),
+            xpub,
+        })
+    }
+
+    /// The output descriptors of an account's receive and change chains,
+    /// for importing the account into a watch-only wallet
+    pub fn account_descriptors(&self, account: u32) -> Result<Vec<String>, WalletError> {
+        let account_type = self
+            .account(account)
+            .ok_or(WalletError::AccountNotFound(account))?
+            .account_type();
+        let xpub = self.account_xpub(account)?;
+
+        Ok(vec![
+            account_type.descriptor(&xpub, 0),
+            account_type.descriptor(&xpub, 1),
+        ])
+    }
+
+    /// derive a new address on the external chain of an account
+    pub fn new_receive_address(&mut self, account: u32) -> Result<String, WalletError> {
+        let address = self.new_address(account, Chain::External)?;
+
+        self.events.emit(WalletEvent::NewAddressIssued {
+            account,
+            address: address.clone(),
+        });
+
+        Ok(address)
+    }
+
+    /// derive a new address on the internal chain of an account, for change outputs
+    pub fn new_change_address(&mut self, account: u32) -> Result<String, WalletError> {
+        self.new_address(account, Chain::Internal)
+    }
+
+    /// Derive the address at the next index of an account's chain.
+    /// Every chain of every account keeps its own index, stored with
+    /// the account so issuing resumes where it stopped after a reload
+    fn new_address(&mut self, account: u32, chain: Chain) -> Result<String, WalletError> {
+        let (node, account_type, index) = match self.account(account) {
+            Some(found) if found.is_archived() => {
+                return Err(WalletError::AccountArchived(account))
+            }
+            Some(found) => (found.node(), found.account_type(), found.next_index(chain)),
+            None => return Err(WalletError::AccountNotFound(account)),
+        };
+        self.changes.mark(&[Section::Keys]);
+
+        let chain_node = self.child(
+            node,
+            ChildNumber::Normal(chain.index()),
+            AccountType::Legacy,
+        )?;
+
+        // wallets written before accounts kept their own counters may
+        // already hold addresses past it, never hand those out again
+        let mut index = index;
+        while self.has_child(chain_node, ChildNumber::Normal(index)) {
+            self.accounts[account as usize].advance(chain);
+            index += 1;
+        }
+
+        // an index without a valid key is skipped, as BIP32 requires
+        let address_node = loop {
+            match self.insert_child(chain_node, ChildNumber::Normal(index), account_type) {
+                Err(WalletError::InvalidChild(_)) => {
+                    self.accounts[account as usize].advance(chain);
+                    index += 1;
+                }
+                inserted => break inserted?,
+            }
+        };
+        self.accounts[account as usize].advance(chain);
+
+        let address = self.arena.nodes()[address_node].key.clone();
+        debug!(account, ?chain, index, address = %address, "address issued");
+        Ok(address)
+    }
+
+    /// Derive the address at an index of an account chain again from the
+    /// master key, walking the descriptor path `m/purpose'/coin'/account'/chain/index`
+    /// without the keys cached in the key graph, and check it matches the address
+    /// stored. Returns the address once verified, see
+    /// [Wallet::verify_address_with_mnemonic] to derive it from the mnemonic instead
+    pub fn verify_address(
+        &self,
+        account: u32,
+        chain: Chain,
+        index: u32,
+    ) -> Result<String, WalletError> {
+        self.ensure_unlocked()?;
+        let (stored, account_type, path) = self.stored_address(account, chain, index)?;
+
+        let root = self.arena.root().ok_or(WalletError::Uninitialized)?;
+        let master = self.unsealed_key(
+            self.arena
+                .get_inner(root)
+                .ok_or(WalletError::Uninitialized)?,
+        )?;
+        if master.is_wiped() {
+            return Err(match self.watch_only {
+                true => WalletError::WatchOnly,
+                false => WalletError::MasterKeyNotLoaded,
+            });
+        }
+
+        check_derived_address(&master, account_type, &path, stored)
+    }
+
+    /// [Wallet::verify_address] from the mnemonic of the wallet rather than
+    /// its master key, so a bug turning the seed into the master key is caught
+    /// too. With the `cross-check` feature the address is also derived from
+    /// the seed with rust-bitcoin, unless the master key was derived with
+    /// [MasterKeyDerivation::Legacy], which only waller does. Works while locked
+    pub fn verify_address_with_mnemonic(
+        &self,
+        mnemonic: &str,
+        account: u32,
+        chain: Chain,
+        index: u32,
+    ) -> Result<String, WalletError> {
+        let (stored, account_type, path) = self.stored_address(account, chain, index)?;
+
+        let seed = mnemonic_to_seed(mnemonic.to_string(), "")
+            .map_err(|e| WalletError::Key(e.to_string()))?;
+        let master = Key::from_seed_with(
+            &seed,
+            self.master_key_derivation,
+            self.network,
+            self.compress_public_keys,
+        )
+        .map_err(|e| WalletError::Key(e.to_string()))?;
+        let stored = check_derived_address(&master, account_type, &path, stored)?;
+
+        #[cfg(feature = "cross-check")]
+        if self.master_key_derivation == MasterKeyDerivation::Bip32 {
+            let derived = crate::cross_check::derive_address(
+                &seed,
+                self.network,
+                self.compress_public_keys,
+                account_type,
+                &path,
+            )
+            .map_err(|e| WalletError::Key(e.to_string()))?;
+            if derived != stored {
+                return Err(WalletError::AddressMismatch { stored, derived });
+            }
+        }
+
+        Ok(stored)
+    }
+
+    /// the address stored at an index of an account chain, with the
+    /// account type and the descriptor path it's derived at
+    fn stored_address(
+        &self,
+        account: u32,
+        chain: Chain,
+        index: u32,
+    ) -> Result<(String, AccountType, Vec<ChildNumber>), WalletError> {
+        let found = self
+            .account(account)
+            .ok_or(WalletError::AccountNotFound(account))?;
+        let account_type = found.account_type();
+        let stored = self
+            .find_child(found.node(), ChildNumber::Normal(chain.index()))
+            .and_then(|chain_node| self.find_child(chain_node, ChildNumber::Normal(index)))
+            .map(|node| self.arena.nodes()[node].key.clone())
+            .ok_or_else(|| {
+                WalletError::UnknownAddress(format!("{}/{}/{}", account, chain.index(), index))
+            })?;
+
+        let mut path = account_type
+            .account_path(self.network, found.index())
+            .to_vec();
+        path.push(ChildNumber::Normal(chain.index()));
+        path.push(ChildNumber::Normal(index));
+        Ok((stored, account_type, path))
+    }
+
+    /// find the account an address was derived under
+    pub fn account_of(&self, address: &str) -> Option<u32> {
+        let mut current = self.node_id(address)?;
+
+        loop {
+            if let Some(number) = self.accounts.iter().position(|a| a.node() == current) {
+                return Some(number as u32);
+            }
+            current = self.arena.get(current)?.parent()?;
+        }
+    }
+
+    /// whether an output script pays to an address of the wallet, derived or imported
+    pub fn is_mine(&self, script: &[u8]) -> bool {
+        Script::new(script.to_vec())
+            .to_address(self.network)
+            .is_some_and(|address| self.owns_address(&address))
+    }
+
+    /// Whether an output script pays to an address of the change chain of an
+    /// account, as [Wallet::new_transaction] makes for its change. Imported
+    /// keys and receive addresses aren't change
+    pub fn is_change(&self, script: &[u8]) -> bool {
+        let address = match Script::new(script.to_vec()).to_address(self.network) {
+            Some(address) => address,
+            None => return false,
+        };
+        let chain = self
+            .find_node(&address)
+            .and_then(|node| node.parent())
+            .and_then(|chain| self.arena.get(chain));
+
+        match chain {
+            Some(chain) => {
+                chain.data.index == Some(ChildNumber::Normal(Chain::Internal.index()))
+                    && self
+                        .accounts
+                        .iter()
+                        .any(|account| chain.parent() == Some(account.node()))
+            }
+            None => false,
+        }
+    }
+
+    /// return the unspent outputs owned by the wallet
+    pub fn utxos(&self) -> &Vec<Utxo> {
+        &self.utxos
+    }
+
+    /// The balance of every coin of the wallet, of its accounts and imported
+    /// keys. Coinbase outputs are counted apart until they have
+    /// [crate::COINBASE_MATURITY] confirmations at the last block synced
+    pub fn balance(&self) -> Balance {
+        let tip_height = self.tip_height();
+        let mut balance = Balance::default();
+        for utxo in self.utxos.iter() {
+            match utxo.is_mature(tip_height) {
+                false => balance.immature += utxo.value(),
+                true if utxo.is_confirmed() => balance.confirmed += utxo.value(),
+                true => balance.unconfirmed += utxo.value(),
+            }
+        }
+        balance
+    }
+
+    /// track an unspent output paying to one of the wallet's addresses,
+    /// an output already tracked is left as it is
+    pub fn add_utxo(&mut self, utxo: Utxo) -> Result<(), WalletError> {
+        if !self.owns_address(utxo.address()) {
+            return Err(WalletError::UnknownAddress(utxo.address().to_string()));
+        }
+        if self
+            .utxos
+            .iter()
+            .any(|held| held.outpoint() == utxo.outpoint())
+        {
+            return Ok(());
+        }
+
+        self.changes.mark(&[Section::Utxos]);
+        self.utxos.push(utxo.clone());
+        self.events.emit(WalletEvent::UtxoReceived(utxo));
+        Ok(())
+    }
+
+    pub(crate) fn owns_address(&self, address: &str) -> bool {
+        self.node_id(address).is_some()
+    }
+
+    pub(crate) fn take_utxos(&mut self) -> Vec<Utxo> {
+        self.changes.mark(&[Section::Utxos]);
+        std::mem::take(&mut self.utxos)
+    }
+
+    pub(crate) fn set_utxos(&mut self, utxos: Vec<Utxo>) {
+        self.changes.mark(&[Section::Utxos]);
+        self.utxos = utxos;
+    }
+
+    pub(crate) fn emit(&self, event: WalletEvent) {
+        self.events.emit(event);
+    }
+
+    /// stop tracking an output once a transaction spending it is seen,
+    /// returns the output if it was tracked
+    pub fn spend_utxo(&mut self, outpoint: &OutPoint) -> Option<Utxo> {
+        let position = self
+            .utxos
+            .iter()
+            .position(|utxo| utxo.outpoint() == outpoint)?;
+        self.changes.mark(&[Section::Utxos]);
+        let utxo = self.utxos.remove(position);
+
+        self.events
+            .emit(WalletEvent::UtxoSpent(utxo.outpoint().clone()));
+        Some(utxo)
+    }
+
+    /// Mark the outputs created by a transaction as confirmed at a block
+    /// height. Transactions spending the same outputs can't confirm anymore,
+    /// their outputs are forgotten and their records marked conflicted
+    pub fn confirm_transaction(&mut self, tx_id: String, height: u32) {
+        self.confirm(tx_id, height);
+    }
+
+    /// [Wallet::confirm_transaction], returning the transactions it conflicts
+    fn confirm(&mut self, tx_id: String, height: u32) -> Vec<String> {
+        self.changes.mark(&[Section::Utxos, Section::History]);
+        let mut found = false;
+        for utxo in self.utxos.iter_mut() {
+            if utxo.outpoint().hash() == tx_id {
+                utxo.confirm(height);
+                found = true;
+            }
+        }
+        for tx in self.history.iter_mut() {
+            if tx.tx_id() == tx_id {
+                tx.confirm(height);
+            }
+        }
+
+        let mut conflicts = vec![];
+        for spend in self.spends.iter_mut() {
+            if spend.tx_id() == tx_id {
+                spend.set_height(Some(height));
+            }
+        }
+        for spend in self.spends.iter() {
+            let double_spent = spend.tx_id() != tx_id
+                && !spend.is_confirmed()
+                && !spend.is_conflicted()
+                && self.spends.iter().any(|confirmed| {
+                    confirmed.tx_id() == tx_id && confirmed.outpoint() == spend.outpoint()
+                });
+            if double_spent && !conflicts.contains(&spend.tx_id().to_string()) {
+                conflicts.push(spend.tx_id().to_string());
+            }
+        }
+        for conflict in conflicts.iter() {
+            debug!(tx_id = %conflict, "conflicting transaction");
+            self.utxos
+                .retain(|utxo| utxo.outpoint().hash() != *conflict);
+            for tx in self.history.iter_mut() {
+                if tx.tx_id() == conflict && tx.height().is_none() {
+                    tx.mark_conflicted();
+                }
+            }
+            for spend in self.spends.iter_mut() {
+                if spend.tx_id() == conflict {
+                    spend.mark_conflicted();
+                }
+            }
+        }
+
+        if found {
+            self.events.emit(WalletEvent::TxConfirmed { tx_id, height });
+        }
+        conflicts
+    }
+
+    /// Forget the outputs and pending record of a transaction replaced by a
+    /// fee bump (RBF), the outputs of the replacement are added once it is
+    /// seen. The outputs of the wallet the replacement spends are recorded
+    /// as spent by it, see [Wallet::conflicts_of]
+    pub fn replace_transaction(&mut self, tx_id: String, replacement: &Transaction) {
+        self.changes.mark(&[Section::Utxos, Section::History]);
+        self.forget_transaction(&tx_id);
+        self.record_spends(replacement);
+
+        self.events.emit(WalletEvent::FeeBumped {
+            tx_id,
+            replacement_tx_id: replacement.tx_id(),
+        });
+    }
+
+    /// drop the outputs and pending record of a transaction replaced by the wallet
+    fn forget_transaction(&mut self, tx_id: &str) {
+        self.changes.mark(&[Section::Utxos, Section::History]);
+        self.utxos.retain(|utxo| utxo.outpoint().hash() != tx_id);
+        self.history
+            .retain(|tx| tx.tx_id() != tx_id || tx.height().is_some());
+        self.archive.retain(|tx| tx.tx_id() != tx_id);
+    }
+
+    /// Keep a fully signed transaction to relay it again with
+    /// [Wallet::rebroadcast_pending] until it confirms, in case mempools
+    /// drop it, eg when they fill up. Archived transactions are flushed to
+    /// the cache file
+    pub fn archive_transaction(&mut self, tx: &Transaction) -> Result<(), WalletError> {
+        if let Some(index) = tx
+            .inputs()
+            .iter()
+            .position(|input| input.signature_script().is_empty() && input.witness().is_empty())
+        {
+            return Err(WalletError::Transaction(TransactionError::UnsignedInput(
+                index,
+            )));
+        }
+
+        let signed = SignedTx::new(tx);
+        if !self
+            .archive
+            .iter()
+            .any(|archived| archived.tx_id() == signed.tx_id())
+        {
+            self.changes.mark(&[Section::History]);
+            self.archive.push(signed);
+        }
+        Ok(())
+    }
+
+    /// the signed transactions waiting to confirm, see [Wallet::archive_transaction]
+    pub fn archived_transactions(&self) -> &[SignedTx] {
+        &self.archive
+    }
+
+    /// Relay the archived transactions again, called on each [Wallet::sync].
+    /// Transactions that confirmed, or were replaced by a transaction
+    /// spending the same coins that confirmed, are dropped from the archive
+    /// first. Those the backend refuses are kept to try again
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(archived = self.archive.len()))
+    )]
+    pub fn rebroadcast_pending(&mut self, backend: &dyn Backend) -> Rebroadcast {
+        self.changes.mark(&[Section::History]);
+        let mut report = Rebroadcast::default();
+
+        let history = &self.history;
+        let spends = &self.spends;
+        let settled = |tx: &SignedTx| {
+            let confirmed = history.iter().any(|record| {
+                record.tx_id() == tx.tx_id()
+                    && (record.height().is_some() || record.is_conflicted())
+            });
+            let replaced = spends.iter().any(|spend| {
+                spend.tx_id() != tx.tx_id()
+                    && spend.is_confirmed()
+                    && tx.inputs().contains(spend.outpoint())
+            });
+            confirmed || replaced
+        };
+        let (dropped, archive): (Vec<SignedTx>, Vec<SignedTx>) =
+            self.archive.drain(..).partition(|tx| settled(tx));
+        self.archive = archive;
+        report.dropped = dropped.iter().map(|tx| tx.tx_id().to_string()).collect();
+
+        for tx in self.archive.iter() {
+            match backend.broadcast(tx.raw_tx()) {
+                // already relayed, or mined in a block not scanned yet
+                Ok(_) | Err(BackendError::Rpc { code: -27, .. }) => {
+                    report.relayed.push(tx.tx_id().to_string())
+                }
+                Err(BackendError::Rpc { message, .. })
+                    if MempoolRejection::from_reason(&message)
+                        == MempoolRejection::AlreadyKnown =>
+                {
+                    report.relayed.push(tx.tx_id().to_string())
+                }
+                Err(e) => {
+                    debug!(tx_id = %tx.tx_id(), error = %e, "rebroadcast failed");
+                    report.failed.push((tx.tx_id().to_string(), e));
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Stop tracking the outputs of the wallet a transaction spends, eg
+    /// once it's broadcast, remembering it spends them. Outputs already
+    /// spent by another transaction are recorded as spent by this one too,
+    /// it replaces the other. Returns the outputs no longer tracked
+    pub fn record_spends(&mut self, tx: &Transaction) -> Vec<Utxo> {
+        let tx_id = tx.tx_id();
+        let mut spent = vec![];
+        for input in tx.inputs() {
+            let outpoint = input.previous_output();
+            let tracked = self.utxos.iter().any(|utxo| utxo.outpoint() == outpoint);
+            match self.record_spend(outpoint, &tx_id) {
+                Some(utxo) if tracked => spent.push(utxo),
+                _ => {}
+            }
+        }
+        spent
+    }
+
+    /// Record a transaction spending an output of the wallet, tracked or
+    /// already spent by another transaction. Returns the output, none when
+    /// it isn't the wallet's
+    fn record_spend(&mut self, outpoint: &OutPoint, tx_id: &str) -> Option<Utxo> {
+        let utxo = match self.spend_utxo(outpoint) {
+            Some(utxo) => utxo,
+            None => self
+                .spends
+                .iter()
+                .find(|spend| spend.outpoint() == outpoint)?
+                .utxo()
+                .clone(),
+        };
+        if !self
+            .spends
+            .iter()
+            .any(|spend| spend.outpoint() == outpoint && spend.tx_id() == tx_id)
+        {
+            self.changes.mark(&[Section::Utxos]);
+            self.spends
+                .push(Spend::new(utxo.clone(), tx_id.to_string()));
+        }
+        Some(utxo)
+    }
+
+    /// the outputs of the wallet seen spent, with the transactions spending them
+    pub fn spends(&self) -> &Vec<Spend> {
+        &self.spends
+    }
+
+    /// The other transactions spending an output the transaction spends,
+    /// directly or through another replacement of it, in the order they
+    /// were seen. A fee bump chain A, B, C gives A and C for B. Empty for
+    /// a transaction without conflicts
+    pub fn conflicts_of(&self, tx_id: &str) -> Vec<String> {
+        let mut chain = vec![tx_id.to_string()];
+        loop {
+            let found: Vec<String> = self
+                .spends
+                .iter()
+                .filter(|spend| !chain.iter().any(|tx_id| tx_id == spend.tx_id()))
+                .filter(|spend| {
+                    self.spends.iter().any(|other| {
+                        other.outpoint() == spend.outpoint()
+                            && chain.iter().any(|tx_id| tx_id == other.tx_id())
+                    })
+                })
+                .map(|spend| spend.tx_id().to_string())
+                .collect();
+            if found.is_empty() {
+                break;
+            }
+            chain.extend(found);
+        }
+
+        // in the order seen, without the transaction itself
+        let mut conflicts: Vec<String> = vec![];
+        for spend in self.spends.iter() {
+            if spend.tx_id() != tx_id
+                && chain.iter().any(|found| found == spend.tx_id())
+                && !conflicts.iter().any(|found| found == spend.tx_id())
+            {
+                conflicts.push(spend.tx_id().to_string());
+            }
+        }
+        conflicts
+    }
+
+    /// the transactions found paying to or spending from the wallet, oldest first
+    pub fn history(&self) -> &Vec<TxRecord> {
+        &self.history
+    }
+
+    /// Label an address of the wallet, eg with who it was handed out to.
+    /// An empty label removes the label of the address
+    pub fn set_label(&mut self, address: &str, label: &str) -> Result<(), WalletError> {
+        if !self.owns_address(address) {
+            return Err(WalletError::UnknownAddress(address.to_string()));
+        }
+
+        self.changes.mark(&[Section::Labels]);
+        if label.is_empty() {
+            self.labels.remove(address);
+        } else {
+            self.labels.insert(address.to_string(), label.to_string());
+        }
+        Ok(())
+    }
+
+    /// the label of an address, see [Wallet::set_label]
+    pub fn label(&self, address: &str) -> Option<&str> {
+        self.labels.get(address).map(String::as_str)
+    }
+
+    /// Take a read-only [WalletSnapshot] of the balances of the accounts, the
+    /// addresses handed out, the unspent outputs and the pending transactions.
+    /// Works while locked, no private key material is read
+    pub fn snapshot(&self) -> WalletSnapshot {
+        self.snapshot_with(&self.utxos)
+    }
+
+    /// a snapshot of the wallet holding some outputs, the UTXO set of a
+    /// [crate::SharedWallet] is kept apart from its wallet
+    pub(crate) fn snapshot_with(&self, utxos: &[Utxo]) -> WalletSnapshot {
+        let accounts = self
+            .accounts
+            .iter()
+            .enumerate()
+            .map(|(number, account)| {
+                let number = number as u32;
+                let (confirmed, unconfirmed) = utxos
+                    .iter()
+                    .filter(|utxo| self.account_of(utxo.address()) == Some(number))
+                    .fold((0, 0), |(confirmed, unconfirmed), utxo| {
+                        match utxo.is_confirmed() {
+                            true => (confirmed + utxo.value(), unconfirmed),
+                            false => (confirmed, unconfirmed + utxo.value()),
+                        }
+                    });
+
+                AccountReport {
+                    number,
+                    account_type: account.account_type(),
+                    path: account.path(self.network),
+                    archived: account.is_archived(),
+                    confirmed,
+                    unconfirmed,
+                    next_external_index: account.next_index(Chain::External),
+                    next_internal_index: account.next_index(Chain::Internal),
+                }
+            })
+            .collect();
+
+        let mut addresses = vec![];
+        for (number, account) in self.accounts.iter().enumerate() {
+            for chain in [Chain::External, Chain::Internal].iter() {
+                let chain_node =
+                    match self.find_child(account.node(), ChildNumber::Normal(chain.index())) {
+                        Some(node) => node,
+                        None => continue,
+                    };
+                for index in 0..account.next_index(*chain) {
+                    if let Some(node) = self.find_child(chain_node, ChildNumber::Normal(index)) {
+                        addresses.push((
+                            self.arena.nodes()[node].key.clone(),
+                            Some((number as u32, *chain, index)),
+                        ));
+                    }
+                }
+            }
+        }
+        addresses.extend(
+            self.arena
+                .nodes()
+                .iter()
+                .filter(|node| matches!(node.data.key_type, KeyType::Imported))
+                .map(|node| (node.key.clone(), None)),
+        );
+
+        let addresses = addresses
+            .into_iter()
+            .map(|(address, derivation)| {
+                let held: Vec<&Utxo> = utxos
+                    .iter()
+                    .filter(|utxo| utxo.address() == address)
+                    .collect();
+
+                AddressReport {
+                    label: self.labels.get(&address).cloned(),
+                    utxos: held.len(),
+                    balance: held.iter().map(|utxo| utxo.value()).sum(),
+                    address,
+                    derivation,
+                }
+            })
+            .collect();
+
+        WalletSnapshot {
+            network: self.network,
+            locked: self.is_locked(),
+            watch_only: self.watch_only,
+            accounts,
+            addresses,
+            utxos: utxos.to_vec(),
+            pending: self
+                .history
+                .iter()
+                .filter(|tx| tx.height().is_none())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Prove the wallet owns one of its addresses by signing a message, eg
+    /// the challenge of an exchange, with BIP322. Checked with
+    /// [crate::verify_ownership]. P2PKH, P2WPKH, P2SH-P2WPKH and P2TR addresses can be
+    /// proven, others fail with [WalletError::UnsupportedInput]
+    pub fn prove_ownership(
+        &self,
+        address: &str,
+        message: &str,
+    ) -> Result<OwnershipProof, WalletError> {
+        if !self.owns_address(address) {
+            return Err(WalletError::UnknownAddress(address.to_string()));
+        }
+        let to_spend = bip322_to_spend(address, message, self.network)
+            .map_err(|_| WalletError::InvalidAddress(address.to_string()))?;
+        let mut to_sign = bip322_to_sign(&to_spend);
+        if self.sign_transaction(&mut to_sign)? == 0 {
+            return Err(WalletError::UnsupportedInput(0));
+        }
+
+        Ok(OwnershipProof {
+            address: address.to_string(),
+            message: message.to_string(),
+            signature: encode_bip322_signature(&to_sign),
+        })
+    }
+
+    /// Prove the reserves of the wallet to an auditor without moving funds,
+    /// signing a transaction that spends every coin of the wallet along with
+    /// the commitment of a message, see [ReserveProof]. The signatures of coins
+    /// not spent with a witness don't commit to their values, the proof carries
+    /// the transactions paying them, read from a backend. Fails with
+    /// [WalletError::InsufficientFunds] without coins and with
+    /// [WalletError::UnsupportedInput] for a coin the wallet can't sign for
+    pub fn prove_reserves(
+        &self,
+        message: &str,
+        backend: &dyn Backend,
+    ) -> Result<ReserveProof, WalletError> {
+        if self.utxos.is_empty() {
+            return Err(WalletError::InsufficientFunds);
+        }
+        let mut spent = HashMap::new();
+        for utxo in self.utxos.iter() {
+            let tx_id = utxo.outpoint().hash();
+            if spends_witness(utxo.output().pk_script(), &[]) || spent.contains_key(&tx_id) {
+                continue;
+            }
+            let raw = backend.transaction(&tx_id).map_err(WalletError::Backend)?;
+            let paid = decode_transaction(&raw, self.network)
+                .ok()
+                .filter(|paying| paying.tx_id == tx_id)
+                .and_then(|paying| {
+                    paying
+                        .outputs
+                        .into_iter()
+                        .nth(utxo.outpoint().index() as usize)
+                });
+            match paid {
+                Some(paid)
+                    if paid.value == utxo.value()
+                        && paid.pk_script == utxo.output().pk_script() => {}
+                _ => {
+                    return Err(WalletError::Backend(BackendError::InvalidResponse(
+                        format!("{} doesn't pay output {}", tx_id, utxo.outpoint().index()),
+                    )))
+                }
+            }
+            let raw = hex::decode(raw.trim())
+                .map_err(|e| WalletError::Backend(BackendError::InvalidResponse(e.to_string())))?;
+            spent.insert(tx_id, raw);
+        }
+
+        let mut tx = reserves_transaction(message, &self.utxos);
+        self.sign_transaction(&mut tx)?;
+        // the commitment is never signed
+        if let Some(index) = tx.inputs()[1..]
+            .iter()
+            .position(|input| input.signature_script().is_empty() && input.witness().is_empty())
+        {
+            return Err(WalletError::UnsupportedInput(index + 1));
+        }
+
+        Ok(ReserveProof {
+            message: message.to_string(),
+            psbt: encode_psbt(&tx, &spent),
+        })
+    }
+
+    /// Sum the history and coins of the wallet for dashboards, see
+    /// [WalletStats]: what each account received and sent over each period
+    /// of an interval, the fees paid and the unspent outputs by age and size.
+    /// Works while locked, no private key material is read
+    pub fn stats(&self, interval: StatsInterval) -> WalletStats {
+        let mut accounts: Vec<AccountStats> = (0..self.accounts.len() as u32)
+            .map(|account| AccountStats {
+                account: Some(account),
+                ..AccountStats::default()
+            })
+            .collect();
+        let mut imported = AccountStats::default();
+
+        let (mut fees_paid, mut sized_fees, mut vsize) = (0, 0, 0);
+        for tx in self.history.iter().filter(|tx| !tx.is_conflicted()) {
+            if let Some(fee) = tx.fee() {
+                fees_paid += fee;
+                if let Some(size) = tx.vsize() {
+                    sized_fees += fee;
+                    vsize += size;
+                }
+            }
+
+            // the outputs the transaction paid to the wallet, spent since or
+            // not, less the outputs of the wallet it spent
+            let mut nets: BTreeMap<Option<u32>, i64> = BTreeMap::new();
+            let mut paid: Vec<&OutPoint> = vec![];
+            for utxo in self
+                .utxos
+                .iter()
+                .chain(self.spends.iter().map(Spend::utxo))
+                .filter(|utxo| utxo.outpoint().hash() == tx.tx_id())
+            {
+                if !paid.contains(&utxo.outpoint()) {
+                    paid.push(utxo.outpoint());
+                    *nets.entry(self.account_of(utxo.address())).or_default() += utxo.value();
+                }
+            }
+            for spend in self
+                .spends
+                .iter()
+                .filter(|spend| spend.tx_id() == tx.tx_id())
+            {
+                *nets
+                    .entry(self.account_of(spend.utxo().address()))
+                    .or_default() -= spend.utxo().value();
+            }
+
+            let start = tx.time().map(|time| interval.period_start(time));
+            for (account, net) in nets {
+                match account.and_then(|account| accounts.get_mut(account as usize)) {
+                    Some(stats) => stats.add(start, net),
+                    None => imported.add(start, net),
+                }
+            }
+        }
+        if imported.transactions > 0 {
+            accounts.push(imported);
+        }
+        // the transactions without a block time go last
+        for stats in accounts.iter_mut() {
+            stats
+                .periods
+                .sort_by_key(|period| (period.start.is_none(), period.start));
+        }
+
+        let tip = self.tip_height();
+        let mut utxo_ages: Vec<AgeBucket> = UTXO_AGE_BUCKETS
+            .iter()
+            .map(|max| Some(*max))
+            .chain([None])
+            .map(|max_confirmations| AgeBucket {
+                max_confirmations,
+                ..AgeBucket::default()
+            })
+            .collect();
+        for utxo in self.utxos.iter() {
+            let confirmations = utxo
+                .height()
+                .map_or(0, |height| tip.saturating_sub(height) + 1);
+            let bucket = UTXO_AGE_BUCKETS
+                .iter()
+                .position(|max| confirmations <= *max)
+                .unwrap_or(UTXO_AGE_BUCKETS.len());
+            utxo_ages[bucket].count += 1;
+            utxo_ages[bucket].value += utxo.value();
+        }
+
+        let mut largest_utxos = self.utxos.clone();
+        largest_utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.value()));
+        largest_utxos.truncate(LARGEST_UTXOS);
+
+        WalletStats {
+            interval,
+            accounts,
+            fees_paid,
+            average_fee_rate: match vsize {
+                0 => None,
+                vsize => Some(sized_fees as f64 / vsize as f64),
+            },
+            utxo_count: self.utxos.len(),
+            utxo_ages,
+            largest_utxos,
+        }
+    }
+
+    /// Write the history, oldest first, for accounting and tax tools,
+    /// see [HistoryRow] for the columns. Amounts are in satoshis
+    pub fn export_history<W: Write>(
+        &self,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<(), WalletError> {
+        let rows: Vec<HistoryRow> = self.history.iter().map(|tx| self.history_row(tx)).collect();
+        write_rows(format, &rows, writer)
+    }
+
+    /// [Wallet::export_history] with the amounts valued in a fiat currency,
+    /// at the price of the time of each transaction and at the current
+    /// price, see [FiatHistoryRow]. The prices come from the application,
+    /// the wallet doesn't fetch any
+    pub fn export_history_with_rates<W: Write>(
+        &self,
+        format: ExportFormat,
+        rates: &dyn RateProvider,
+        fiat: Currency,
+        writer: W,
+    ) -> Result<(), WalletError> {
+        let value = |amount: i64, price: Option<Decimal>| {
+            Decimal::value_of(amount, price?, fiat.decimals())
+                .map(|value| value.to_string_with(fiat.decimals()))
+        };
+        let now = rates.rate(fiat);
+
+        let rows: Vec<FiatHistoryRow> = self
+            .history
+            .iter()
+            .map(|tx| FiatHistoryRow {
+                row: self.history_row(tx),
+                currency: fiat.code().to_string(),
+                value_at_time: value(
+                    tx.net(),
+                    tx.time().and_then(|time| rates.rate_at(fiat, time)),
+                ),
+                value_now: value(tx.net(), now),
+            })
+            .collect();
+        write_rows(format, &rows, writer)
+    }
+
+    fn history_row(&self, tx: &TxRecord) -> HistoryRow {
+        HistoryRow {
+            txid: tx.tx_id().to_string(),
+            height: tx.height(),
+            amount: tx.net(),
+            fee: tx.fee(),
+            counterparty: tx.counterparty().map(str::to_string),
+            label: tx
+                .addresses()
+                .iter()
+                .find_map(|address| self.label(address))
+                .map(str::to_string),
+        }
+    }
+
+    /// Write the unspent outputs, see [UtxoRow] for the columns. Amounts are in satoshis
+    pub fn export_utxos<W: Write>(
+        &self,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<(), WalletError> {
+        let rows: Vec<UtxoRow> = self
+            .utxos
+            .iter()
+            .map(|utxo| UtxoRow {
+                txid: utxo.outpoint().hash(),
+                vout: utxo.outpoint().index(),
+                height: utxo.height(),
+                amount: utxo.value(),
+                address: utxo.address().to_string(),
+                label: self.label(utxo.address()).map(str::to_string),
+            })
+            .collect();
+
+        write_rows(format, &rows, writer)
+    }
+
+    /// Call a function once a transaction reaches a number of confirmations,
+    /// eg to ship an order once its payment is buried deep enough. The
+    /// confirmations are checked when the chain is scanned by [Wallet::sync]
+    /// or [Wallet::rescan], and right away for a transaction already deep
+    /// enough. The function gets the txid and the height of the block
+    /// confirming it and is called once, the watch is then dropped. A
+    /// transaction rolled back by a reorg waits until it's deep enough again.
+    /// Watches are kept in memory only, and wait for at least 1 confirmation
+    pub fn watch_tx<F>(&mut self, tx_id: &str, confirmations: u32, callback: F)
+    where
+        F: Fn(&str, u32) + Send + Sync + 'static,
+    {
+        self.watches.0.push(TxWatch {
+            tx_id: tx_id.to_string(),
+            confirmations: confirmations.max(1),
+            callback: Arc::new(callback),
+        });
+        self.check_watches();
+    }
+
+    /// stop watching a transaction, returns whether it was watched
+    pub fn unwatch_tx(&mut self, tx_id: &str) -> bool {
+        let watched = self.watches.0.len();
+        self.watches.0.retain(|watch| watch.tx_id != tx_id);
+        self.watches.0.len() != watched
+    }
+
+    /// The confirmations of a transaction of the wallet, counting the block
+    /// confirming it, up to the last block scanned. None while it's
+    /// unconfirmed or unknown to the wallet
+    pub fn confirmations(&self, tx_id: &str) -> Option<u32> {
+        let height = self.confirmation_height(tx_id)?;
+        let tip = self.scanned_blocks.last()?.height;
+
+        tip.checked_sub(height).map(|depth| depth + 1)
+    }
+
+    /// the height of the block confirming a transaction of the wallet
+    fn confirmation_height(&self, tx_id: &str) -> Option<u32> {
+        self.history
+            .iter()
+            .filter(|tx| tx.tx_id() == tx_id)
+            .find_map(|tx| tx.height())
+            .or_else(|| {
+                self.spends
+                    .iter()
+                    .filter(|spend| spend.tx_id() == tx_id)
+                    .find_map(|spend| spend.height())
+            })
+            .or_else(|| {
+                self.utxos
+                    .iter()
+                    .filter(|utxo| utxo.outpoint().hash() == tx_id)
+                    .find_map(|utxo| utxo.height())
+            })
+    }
+
+    /// call and drop the watches of transactions deep enough
+    fn check_watches(&mut self) {
+        let mut reached = vec![];
+        let watches = std::mem::take(&mut self.watches.0);
+        for watch in watches {
+            match self.confirmations(&watch.tx_id) {
+                Some(confirmations) if confirmations >= watch.confirmations => reached.push(watch),
+                _ => self.watches.0.push(watch),
+            }
+        }
+
+        for watch in reached {
+            let height = self.confirmation_height(&watch.tx_id).unwrap_or_default();
+            debug!(tx_id = %watch.tx_id, height, "watched transaction confirmed");
+            (watch.callback)(&watch.tx_id, height);
+        }
+    }
+
+    /// Ask the mempool of a backend whether it would accept a signed
+    /// transaction, without relaying it, to catch non standard or low fee
+    /// transactions before broadcasting them. A refusal is returned as
+    /// [WalletError::Rejected]
+    pub fn test_accept(
+        &self,
+        backend: &dyn Backend,
+        tx: &Transaction,
+    ) -> Result<MempoolAcceptance, WalletError> {
+        let acceptance = backend
+            .test_accept(&tx.to_hex())
+            .map_err(WalletError::Backend)?;
+        debug!(tx_id = %tx.tx_id(), allowed = acceptance.allowed, "tested mempool acceptance");
+
+        match (acceptance.allowed, &acceptance.reject_reason) {
+            (true, _) => Ok(acceptance),
+            (false, reason) => Err(WalletError::Rejected(MempoolRejection::from_reason(
+                reason.as_deref().unwrap_or_default(),
+            ))),
+        }
+    }
+
+    /// The BIP352 silent payment address of the wallet, the same on every
+    /// call, from keys derived at `m/352'/coin_type'/0'`. Handing it out turns
+    /// on scanning the blocks synced for payments to it, see
+    /// [Wallet::silent_payment_outputs], which needs the master key and a
+    /// backend serving [Backend::silent_payment_tweaks]. Blocks synced before
+    /// are scanned by a [Wallet::rescan]. Experimental, the wallet can't spend
+    /// the payments yet
+    pub fn silent_payment_address(&mut self) -> Result<SilentPaymentAddress, WalletError> {
+        let address = self
+            .silent_payment_keys()?
+            .address(self.network)
+            .map_err(|e| WalletError::Key(e.to_string()))?;
+        self.changes.mark(&[Section::Settings]);
+        self.silent_payments = true;
+        Ok(address)
+    }
+
+    /// the silent payments received and not spent, see [Wallet::silent_payment_address]
+    pub fn silent_payment_outputs(&self) -> Vec<&SilentPaymentOutput> {
+        self.silent_payment_outputs
+            .iter()
+            .filter(|output| output.spent_height().is_none())
+            .collect()
+    }
+
+    /// the private scan key and public spend key of the silent payment address
+    fn silent_payment_keys(&self) -> Result<SilentPaymentKeys, WalletError> {
+        // the scan key is private, unlike the keys of addresses
+        self.ensure_unlocked()?;
+        let key_error = |e: KeyError| WalletError::Key(e.to_string());
+        let derive = |scan: bool| self.derive_path(&SilentPaymentKeys::path(self.network, scan));
+
+        let scan = derive(true)?;
+        let spend = derive(false)?
+            .new_public_key()
+            .and_then(|key| compress_public_key(&key))
+            .map_err(key_error)?;
+        SilentPaymentKeys::new(scan.bytes(), &spend).map_err(key_error)
+    }
+
+    /// Scan the blocks mined since the last scan, see [Wallet::rescan]. When
+    /// the chain of the backend no longer holds the last blocks scanned, eg
+    /// after a reorg or an `invalidateblock`, the confirmations, outputs and
+    /// history found in them are rolled back to the last block both chains
+    /// share and the chain is scanned again from there, emitting
+    /// [WalletEvent::Reorg]. A wallet never scanned, or a reorg deeper than
+    /// the [KEPT_BLOCK_HASHES] kept, is scanned from its birthday. The
+    /// archived transactions still pending are relayed again, see
+    /// [Wallet::rebroadcast_pending]. Returns what changed, see [SyncDiff]
+    pub fn sync(&mut self, backend: &dyn Backend) -> Result<SyncDiff, WalletError> {
+        let utxos = self.utxos.clone();
+        let heights: HashMap<String, Option<u32>> = self
+            .history
+            .iter()
+            .map(|record| (record.tx_id().to_string(), record.height()))
+            .collect();
+
+        let mut fee_bumps = vec![];
+        let tip = self.sync_blocks(backend, &mut fee_bumps)?;
 
-use libarena::{Arena, Node};
-use serde::{Deserialize, Serialize};
+        let tracked = |utxos: &[Utxo], utxo: &Utxo| {
+            utxos
+                .iter()
+                .any(|tracked| tracked.outpoint() == utxo.outpoint())
+        };
+        let new_utxos: Vec<Utxo> = self
+            .utxos
+            .iter()
+            .filter(|utxo| !tracked(&utxos, utxo))
+            .cloned()
+            .collect();
+        let spent_utxos: Vec<Utxo> = utxos
+            .iter()
+            .filter(|utxo| !tracked(&self.utxos, utxo))
+            .cloned()
+            .collect();
+        let confirmations = self
+            .history
+            .iter()
+            .filter_map(
+                |record| match (record.height(), heights.get(record.tx_id())) {
+                    (Some(height), Some(Some(before))) if height == *before => None,
+                    (Some(height), _) => Some((record.tx_id().to_string(), height)),
+                    (None, _) => None,
+                },
+            )
+            .collect();
+        let value = |utxos: &[Utxo]| utxos.iter().map(|utxo| utxo.value()).sum::<i64>();
+        let rebroadcast = self.rebroadcast_pending(backend).relayed;
 
-use crate::{
-    generate_mnemonic, ChildKeyType, Key, KeyCreationOutput, KeyError, KeyPair, KeyType, Network,
-    Transaction, TransactionInput, TransactionOutput, TransactionType, WalletError,
-};
+        Ok(SyncDiff {
+            tip,
+            balance_delta: value(&new_utxos) - value(&spent_utxos),
+            new_utxos,
+            spent_utxos,
+            confirmations,
+            fee_bumps,
+            rebroadcast,
+        })
+    }
 
-/// A bitcoin hardened wallet
-/// keys are stored in a graph using arena allocation
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct Wallet {
-    network: Network,
-    path: PathBuf,
-    next_hardened_index: usize,
-    next_normal_index: usize,
-    compress_public_keys: bool,
-    arena: Arena<KeyPair, String>,
-    encrypted: bool,
-}
+    /// scan the blocks of [Wallet::sync], returning the tip scanned to
+    fn sync_blocks(
+        &mut self,
+        backend: &dyn Backend,
+        fee_bumps: &mut Vec<FeeBump>,
+    ) -> Result<u32, WalletError> {
+        let last = match self.scanned_blocks.last() {
+            Some(last) => last.height,
+            None => return self.scan(backend, 0, fee_bumps),
+        };
+        let tip = backend.tip_height().map_err(WalletError::Backend)?;
 
-impl Wallet {
-    /// Create a new wallet
-    pub fn new(
-        network: Network,
-        path: PathBuf,
-        compress_public_keys: bool,
-        encrypted: bool,
-    ) -> Self {
-        Self {
-            arena: Arena::new(),
-            network,
-            path,
-            next_hardened_index: 2147483647,
-            next_normal_index: 1,
-            compress_public_keys,
-            encrypted,
+        let mut fork = None;
+        for block in self.scanned_blocks.iter().rev() {
+            if block.height <= tip
+                && backend
+                    .block_hash(block.height)
+                    .map_err(WalletError::Backend)?
+                    == block.hash
+            {
+                fork = Some(block.height);
+                break;
+            }
         }
+
+        // the genesis block is shared by every chain
+        let fork_height = match fork {
+            Some(fork) if fork == last => return self.scan(backend, last + 1, fee_bumps),
+            Some(fork) => fork,
+            None => 0,
+        };
+        info!(fork_height, depth = last - fork_height, "chain reorganized");
+        self.events.emit(WalletEvent::Reorg {
+            fork_height,
+            depth: last - fork_height,
+        });
+        self.scan(backend, fork_height + 1, fee_bumps)
     }
 
-    /// Restore an HD wallet, all keys lost can be recovered
-    /// from the mnemonic seed used to build it, however generating
-    /// every key can be very expensive computationally
-    pub fn restore(
-        mnemonic: String,
-        network: Network,
-        compress_public_keys: bool,
-        data_path: PathBuf,
-        encrypted: bool,
-    ) -> Result<Self, WalletError> {
-        let key = Key::new(mnemonic.clone(), network, compress_public_keys)
-            .map_err(|e| WalletError::Key(e.to_string()))?;
+    /// Scan the chain from a height to its tip for transactions paying to or
+    /// spending from the wallet, eg after restoring an old mnemonic. Confirmed
+    /// outputs and history from `from_height` on are dropped and found again,
+    /// scanning from 0 rebuilds them all. The scan never starts before the
+    /// [Birthday] of the wallet, when only its time is known the blocks older
+    /// than it are skipped and the height of the first one kept as the birthday. Every chain of every account is watched
+    /// [RESCAN_LOOKAHEAD] addresses past its last used one, addresses found in
+    /// use are marked as issued. Silent payments are looked for once the
+    /// wallet handed out its [Wallet::silent_payment_address], in the blocks
+    /// the backend serves tweaks for. Returns the height of the tip scanned to
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, fields(from_height = from_height), err(Debug))
+    )]
+    pub fn rescan(&mut self, backend: &dyn Backend, from_height: u32) -> Result<u32, WalletError> {
+        self.scan(backend, from_height, &mut vec![])
+    }
 
-        let mut wallet = Wallet::new(network, data_path, compress_public_keys, encrypted);
+    /// [Wallet::rescan], recording the pending transactions replaced by
+    /// a confirmed transaction of the wallet
+    fn scan(
+        &mut self,
+        backend: &dyn Backend,
+        from_height: u32,
+        fee_bumps: &mut Vec<FeeBump>,
+    ) -> Result<u32, WalletError> {
+        self.changes.mark(&[
+            Section::Keys,
+            Section::Settings,
+            Section::Utxos,
+            Section::History,
+        ]);
+        let tip = backend.tip_height().map_err(WalletError::Backend)?;
+        let from_height = from_height.max(self.birthday.height.unwrap_or_default());
+        debug!(tip, from_height, "scanning to the chain tip");
 
-        let _ = wallet.create_key_chain(key, mnemonic)?;
+        // outputs and transactions still in the mempool are kept
+        let kept = |height: Option<u32>| match height {
+            Some(height) => height < from_height,
+            None => true,
+        };
+        self.utxos.retain(|utxo| kept(utxo.height()));
+        self.history.retain(|tx| kept(tx.height()));
+        self.scanned_blocks
+            .retain(|block| block.height < from_height);
+        // spends found again are confirmed again, the outputs they spent stay known
+        for spend in self.spends.iter_mut() {
+            if !kept(spend.height()) {
+                spend.set_height(None);
+            }
+        }
+        self.silent_payment_outputs
+            .retain(|output| kept(output.height()));
+        for output in self.silent_payment_outputs.iter_mut() {
+            if !kept(output.spent_height()) {
+                output.set_spent(None);
+            }
+        }
+        let silent_payment_keys = match self.silent_payments {
+            true => Some(self.silent_payment_keys()?),
+            false => None,
+        };
 
-        Ok(wallet)
-    }
+        let mut watched = HashMap::new();
+        self.watch_imported_keys(&mut watched)?;
+        for account in 0..self.accounts.len() as u32 {
+            for chain in [Chain::External, Chain::Internal] {
+                let end = self.accounts[account as usize].next_index(chain) + RESCAN_LOOKAHEAD;
+                self.watch_chain(&mut watched, account, chain, 0..end)?;
+            }
+        }
 
-    /// Create a wallet from an existing backed up json wallet file
-    /// This is a serde serialized string of the [Wallet] type
-    pub fn from_wallet_file(path: PathBuf) -> Result<Self, WalletError> {
-        let data = fs::read_to_string(path)
-            .map_err(|e| WalletError::Read(format!("Failed to read file: {}", e.to_string())))?;
+        for height in from_height..=tip {
+            let block = backend.block(height).map_err(WalletError::Backend)?;
+            self.scanned_blocks.push(BlockId {
+                height,
+                hash: block.hash.clone(),
+            });
+            if self.scanned_blocks.len() > KEPT_BLOCK_HASHES {
+                self.scanned_blocks.remove(0);
+            }
+            if let Birthday {
+                height: None,
+                time: Some(time),
+            } = self.birthday
+            {
+                if block.time.saturating_add(BIRTHDAY_TIME_MARGIN) < time {
+                    trace!(height, "block before the wallet birthday");
+                    continue;
+                }
+                self.birthday.height = Some(height);
+            }
+
+            let tweaks = match &silent_payment_keys {
+                Some(_) => match backend.silent_payment_tweaks(height) {
+                    Ok(tweaks) => tweaks,
+                    Err(BackendError::Unsupported(_)) => {
+                        trace!(height, "no silent payment tweaks");
+                        HashMap::new()
+                    }
+                    Err(e) => return Err(WalletError::Backend(e)),
+                },
+                None => HashMap::new(),
+            };
+
+            for BlockTransaction {
+                tx_id,
+                inputs,
+                outputs,
+            } in block.transactions
+            {
+                for outpoint in inputs.iter() {
+                    if let Some(output) = self
+                        .silent_payment_outputs
+                        .iter_mut()
+                        .find(|output| output.outpoint() == outpoint)
+                    {
+                        output.set_spent(Some(height));
+                    }
+                }
+                if let (Some(keys), Some(tweak)) = (&silent_payment_keys, tweaks.get(&tx_id)) {
+                    let paid = keys
+                        .scan(tweak, &outputs)
+                        .map_err(|e| WalletError::Key(e.to_string()))?;
+                    for (vout, tweak) in paid {
+                        let outpoint = OutPoint::new(tx_id.clone(), vout as i32);
+                        if self
+                            .silent_payment_outputs
+                            .iter()
+                            .any(|found| *found.outpoint() == outpoint)
+                        {
+                            continue;
+                        }
+                        info!(tx_id = %tx_id, vout, height, "silent payment found");
+                        self.silent_payment_outputs.push(SilentPaymentOutput::new(
+                            outpoint,
+                            outputs[vout].clone(),
+                            &tweak,
+                            height,
+                        ));
+                    }
+                }
 
-        let imports = serde_json::from_str(&data).map_err(|e| {
-            WalletError::Read(format!("Failed to deserialize data: {}", e.to_string()))
-        })?;
+                // spends the wallet recorded before the transaction was found
+                let authored = self.spends.iter().any(|spend| spend.tx_id() == tx_id);
+                // by outpoint, a malleated or replaced copy of a transaction spends the same coins
+                let spent: Vec<Utxo> = inputs
+                    .iter()
+                    .filter_map(|outpoint| self.record_spend(outpoint, &tx_id))
+                    .collect();
+                let sent: i64 = spent.iter().map(|utxo| utxo.value()).sum();
+                // the fee is only known when the value of every input is
+                let fee = match !inputs.is_empty() && spent.len() == inputs.len() {
+                    true => Some(sent - outputs.iter().map(|output| output.value()).sum::<i64>()),
+                    false => None,
+                };
 
-        Ok(imports)
+                let mut received = 0;
+                let mut change = 0;
+                let mut counterparty = None;
+                let mut addresses = vec![];
+                for (vout, output) in outputs.into_iter().enumerate() {
+                    let (address, path) = match watched.get(output.pk_script()) {
+                        Some(found) => found.clone(),
+                        None => {
+                            if counterparty.is_none() && sent > 0 {
+                                counterparty = Script::new(output.pk_script().to_vec())
+                                    .to_address(self.network);
+                            }
+                            continue;
+                        }
+                    };
+                    if let Some((account, chain, index)) = path {
+                        if chain == Chain::Internal {
+                            change += output.value();
+                        }
+                        self.issue_address(account, chain, index)?;
+                        let end = index + 1 + RESCAN_LOOKAHEAD;
+                        self.watch_chain(&mut watched, account, chain, index + 1..end)?;
+                    }
+
+                    received += output.value();
+                    if !addresses.contains(&address) {
+                        addresses.push(address.clone());
+                    }
+                    let outpoint = OutPoint::new(tx_id.clone(), vout as i32);
+                    if !self.utxos.iter().any(|utxo| *utxo.outpoint() == outpoint) {
+                        let mut utxo = Utxo::new(outpoint, output, address);
+                        // a coinbase spends no output
+                        if inputs.is_empty() {
+                            utxo.set_coinbase();
+                        }
+                        self.add_utxo(utxo)?;
+                    }
+                }
+
+                if sent == 0 && received == 0 {
+                    continue;
+                }
+                debug!(tx_id = %tx_id, height = block.height, received, sent, "wallet transaction found");
+                if !self.history.iter().any(|record| record.tx_id() == tx_id) {
+                    let mut record = TxRecord::new(
+                        tx_id.clone(),
+                        None,
+                        received,
+                        sent,
+                        fee,
+                        counterparty,
+                        addresses,
+                    );
+                    record.set_change(change);
+                    // the size of a transaction is only known from its archived copy
+                    if let Some(summary) = self
+                        .archive
+                        .iter()
+                        .find(|archived| archived.tx_id() == tx_id)
+                        .and_then(|archived| self.decode_transaction(archived.raw_tx()).ok())
+                    {
+                        record.set_vsize(summary.vsize);
+                    }
+                    self.history.push(record);
+                }
+                let conflicted = self.confirm(tx_id.clone(), block.height);
+                if let Some(record) = self
+                    .history
+                    .iter_mut()
+                    .find(|record| record.tx_id() == tx_id)
+                {
+                    record.set_time(block.time);
+                }
+                if authored {
+                    fee_bumps.extend(conflicted.into_iter().map(|replaced| FeeBump {
+                        tx_id: replaced,
+                        replacement_tx_id: tx_id.clone(),
+                    }));
+                } else if !conflicted.is_empty() {
+                    info!(tx_id = %tx_id, ?conflicted, "pending transactions double spent");
+                    self.events
+                        .emit(WalletEvent::Conflict { tx_id, conflicted });
+                }
+            }
+            trace!(height, "block scanned");
+        }
+
+        info!(
+            tip,
+            utxos = self.utxos.len(),
+            history = self.history.len(),
+            "rescan finished"
+        );
+        self.check_watches();
+        Ok(tip)
     }
 
-    /// initialize a new wallet
-    /// on success, returns the mnemonic used to create the wallet
-    pub fn init(&mut self) -> Result<String, WalletError> {
-        let KeyCreationOutput { mnemonic, key } =
-            self.generate_master_key(self.compress_public_keys)?;
+    /// Watch the scripts of the addresses of keys imported with [Wallet::import_wif]
+    /// or [Wallet::import_core_dump], and the P2PK scripts of their keys,
+    /// compressed or not, which old coins of legacy keys were paid to
+    fn watch_imported_keys(&self, watched: &mut WatchedScripts) -> Result<(), WalletError> {
+        for node in self.arena.nodes() {
+            if !matches!(node.data.key_type, KeyType::Imported) {
+                continue;
+            }
+            let script = Script::from_address(&node.key, self.network)
+                .map_err(|e| WalletError::Key(e.to_string()))?;
+            watched.insert(script.as_bytes().to_vec(), (node.key.clone(), None));
 
-        self.create_key_chain(key, mnemonic)
+            let public_key = &node.data.public_key;
+            for key in [
+                compress_public_key(public_key),
+                decompress_public_key(public_key),
+            ] {
+                let key = key.map_err(|e| WalletError::Key(e.to_string()))?;
+                let script = Script::builder()
+                    .push_slice(&key)
+                    .push_opcode(OP_CHECKSIG)
+                    .build();
+                watched.insert(script.into_bytes(), (node.key.clone(), None));
+            }
+        }
+        Ok(())
     }
 
-    /// return a list of keys in the wallet
-    pub fn keys(&self) -> &Vec<Node<KeyPair, String>> {
-        &self.arena.nodes()
+    /// watch the scripts of a range of indexes of an account chain, addresses
+    /// not issued yet are derived without being added to the key graph
+    fn watch_chain(
+        &mut self,
+        watched: &mut WatchedScripts,
+        account: u32,
+        chain: Chain,
+        indexes: std::ops::Range<u32>,
+    ) -> Result<(), WalletError> {
+        let (node, account_type) = match self.account(account) {
+            Some(found) => (found.node(), found.account_type()),
+            None => return Err(WalletError::AccountNotFound(account)),
+        };
+        let chain_node = self.child(
+            node,
+            ChildNumber::Normal(chain.index()),
+            AccountType::Legacy,
+        )?;
+        let chain_keys = &self.arena.nodes()[chain_node].data;
+
+        // addresses are derived from public keys, so a locked wallet syncs too once authenticated
+        for index in indexes {
+            let child = ChildNumber::Normal(index);
+            let address = match self.find_child(chain_node, child) {
+                Some(found) => self.arena.nodes()[found].key.clone(),
+                None => {
+                    self.ensure_authenticated()?;
+                    let public_key = match chain_keys
+                        .private_key
+                        .derive_wiped_child(&chain_keys.public_key, child)
+                    {
+                        Ok((_, public_key)) => public_key,
+                        // BIP32 skips an index without a valid key
+                        Err(KeyError::InvalidChild(_)) => continue,
+                        Err(e) => return Err(WalletError::Key(e.to_string())),
+                    };
+                    account_type
+                        .public_key_address(&public_key, self.network)
+                        .map_err(|e| WalletError::Key(e.to_string()))?
+                }
+            };
+
+            let script = Script::from_address(&address, self.network)
+                .map_err(|e| WalletError::Key(e.to_string()))?;
+            watched.insert(
+                script.into_bytes(),
+                (address, Some((account, chain, index))),
+            );
+        }
+
+        Ok(())
     }
 
-    /// return a reference to the network this
-    /// wallet is associated with
-    pub fn network(&self) -> &Network {
-        &self.network
+    /// add the address at an index of an account chain to the key graph
+    /// and move the chain's next index past it
+    fn issue_address(&mut self, account: u32, chain: Chain, index: u32) -> Result<(), WalletError> {
+        let (node, account_type) = match self.account(account) {
+            Some(found) => (found.node(), found.account_type()),
+            None => return Err(WalletError::AccountNotFound(account)),
+        };
+        self.changes.mark(&[Section::Keys]);
+        let chain_node = self.child(
+            node,
+            ChildNumber::Normal(chain.index()),
+            AccountType::Legacy,
+        )?;
+        self.child(chain_node, ChildNumber::Normal(index), account_type)?;
+
+        while self.accounts[account as usize].next_index(chain) <= index {
+            self.accounts[account as usize].advance(chain);
+        }
+        Ok(())
     }
 
-    /// get the path where keys are being saved to disk
-    pub fn path(&self) -> &PathBuf {
-        &self.path
+    /// Shrink the wallet and write it back to its file. Transactions buried
+    /// under [RetentionPolicy::history_confirmations] blocks at `tip_height`
+    /// leave the history once the wallet holds none of their outputs, and key
+    /// nodes cut off from the master key are dropped from the key graph
+    pub fn compact(
+        &mut self,
+        tip_height: u32,
+        policy: RetentionPolicy,
+    ) -> Result<Compaction, WalletError> {
+        self.changes
+            .mark(&[Section::Keys, Section::Utxos, Section::History]);
+        let KeyRecords { root, records } = KeyRecords::from_arena(&self.arena);
+        let records: Vec<Option<KeyRecord>> = records.into_iter().map(Some).collect();
+        let (arena, moved) = rebuild_arena(&records, root);
+
+        let account_nodes = self
+            .accounts
+            .iter()
+            .map(|account| {
+                moved
+                    .get(account.node())
+                    .copied()
+                    .flatten()
+                    .ok_or(WalletError::Uninitialized)
+            })
+            .collect::<Result<Vec<usize>, WalletError>>()?;
+
+        let history = self.history.len();
+        if let Some(confirmations) = policy.history_confirmations {
+            let utxos = &self.utxos;
+            self.history.retain(|tx| {
+                let buried = match tx.height() {
+                    Some(height) => matches!(
+                        tip_height.checked_sub(height),
+                        Some(depth) if depth + 1 >= confirmations
+                    ),
+                    None => false,
+                };
+                !buried
+                    || utxos
+                        .iter()
+                        .any(|utxo| utxo.outpoint().hash() == tx.tx_id())
+            });
+
+            // the spends of an output go once its confirmed spend is pruned
+            let (history, spends) = (&self.history, self.spends.clone());
+            self.spends.retain(|spend| {
+                match spends.iter().find(|confirmed| {
+                    confirmed.outpoint() == spend.outpoint() && confirmed.is_confirmed()
+                }) {
+                    Some(confirmed) => history.iter().any(|tx| tx.tx_id() == confirmed.tx_id()),
+                    None => true,
+                }
+            });
+        }
+
+        let compaction = Compaction {
+            history_pruned: history - self.history.len(),
+            keys_dropped: self.arena.count() - arena.count(),
+        };
+
+        for (account, node) in self.accounts.iter_mut().zip(account_nodes) {
+            account.set_node(node);
+        }
+        self.arena = arena;
+        self.key_index = KeyIndex::default();
+        self.key_index.update(&self.arena, self.network);
+
+        self.flush()?;
+        Ok(compaction)
     }
 
-    /// change the path to a new location
-    pub fn set_path(&mut self, path: PathBuf) {
-        self.path = path;
+    /// The sum of the unspent outputs an account can spend, coinbase
+    /// outputs count once they mature, see [Wallet::balance]
+    pub fn account_balance(&self, account: u32) -> i64 {
+        self.account_utxos(account)
+            .iter()
+            .map(|utxo| utxo.value())
+            .sum()
     }
 
-    /// returns a vec of addresses of all keys in the wallet
-    /// if an error occurs, the key that failed is within the error
-    pub fn addresses(&self) -> Result<Vec<String>, KeyError> {
-        let mut output = vec![];
-        for Node { data, .. } in self.arena.nodes() {
-            let address = data.private_key.address().map_err(|e| {
-                KeyError::Other(format!(
-                    "Error converting key `{}`: {}",
-                    data.private_key.hex(),
-                    e.to_string()
-                ))
-            })?;
-            output.push(address);
+    /// Build an unsigned transaction moving every coin of an account to
+    /// a fresh receive address of another account, used after [Wallet::rotate_account].
+    /// The fee rate is in satoshis per vbyte and is taken from the drained amount
+    pub fn drain_account(
+        &mut self,
+        old: u32,
+        new: u32,
+        fee_rate: u64,
+    ) -> Result<Transaction, WalletError> {
+        let tx_type = self
+            .account(old)
+            .map(|account| account.account_type().tx_type())
+            .ok_or(WalletError::AccountNotFound(old))?;
+        let output_type = self
+            .account(new)
+            .map(|account| account.account_type().tx_type())
+            .ok_or(WalletError::AccountNotFound(new))?;
+
+        let utxos: Vec<Utxo> = self.account_utxos(old).into_iter().cloned().collect();
+        let total: i64 = utxos.iter().map(|utxo| utxo.value()).sum();
+        // every coin is sized by its own script, the output by the new account's
+        let inputs: Vec<ScriptType> = utxos.iter().map(|utxo| utxo.script_type()).collect();
+        let output = TransactionOutput::from_script(0, vec![0; output_type.pk_script_len()]);
+        let fee = (estimate_mixed_vsize(&tx_type, &inputs, &[output]) * fee_rate) as i64;
+
+        if utxos.is_empty() || total <= fee {
+            return Err(WalletError::InsufficientFunds);
+        }
+
+        let address = self.new_receive_address(new)?;
+        let output = self.address_output(&address, total - fee)?;
+
+        let mut builder = TransactionBuilder::new(tx_type);
+        builder
+            .clock(self.clock.clone())
+            .entropy(self.entropy.clone())
+            .fee_limits(self.fee_limits);
+        self.lock_to_tip(&mut builder);
+        for utxo in utxos.iter() {
+            builder.add_input(utxo.to_input());
         }
-        Ok(output)
+        builder.add_output(output);
+
+        builder.build().map_err(WalletError::Transaction)
     }
 
-    /// Change and set the use of encryption or none
-    pub fn set_encryption(&mut self, encrypted: bool) {
-        self.encrypted = encrypted;
+    /// the coins of an account coin selection spends, immature coinbase outputs are left out
+    fn account_utxos(&self, account: u32) -> Vec<&Utxo> {
+        let tip_height = self.tip_height();
+        self.utxos
+            .iter()
+            .filter(|utxo| self.account_of(utxo.address()) == Some(account))
+            .filter(|utxo| utxo.is_mature(tip_height))
+            .collect()
+    }
+
+    /// the height of the last block scanned, 0 before the first sync
+    pub(crate) fn tip_height(&self) -> u32 {
+        self.scanned_blocks.last().map_or(0, |block| block.height)
     }
 
     /// create the master key of the wallet, all keys will be derived from this key
@@ -131,44 +4466,256 @@ impl Wallet {
         &mut self,
         compress_public_keys: bool,
     ) -> Result<KeyCreationOutput, WalletError> {
-        let mnemonic = generate_mnemonic();
+        let mnemonic = generate_mnemonic_with(&*self.entropy);
         let key = Key::new(mnemonic.clone(), self.network, compress_public_keys)
             .map_err(|e| WalletError::Key(e.to_string()))?;
 
+        self.set_master_key(key.clone())?;
+        self.master_key_derivation = MasterKeyDerivation::Bip32;
+
+        Ok(KeyCreationOutput { mnemonic, key })
+    }
+
+    /// insert the master key as the root of the key graph
+    fn set_master_key(&mut self, key: Key) -> Result<(), WalletError> {
+        self.changes.mark(&[Section::Keys]);
         let pubkey = key
             .new_public_key()
             .map_err(|e| WalletError::Key(e.to_string()))?;
 
         let keypair = KeyPair {
-            private_key: key.clone(),
+            private_key: key,
             public_key: pubkey,
             key_type: crate::KeyType::Master,
             index: None,
+            encrypted_private_key: None,
         };
 
-        let index = self.insert(keypair, None)?;
+        let index = self.insert(keypair, None, AccountType::Legacy)?;
         self.arena.set_root(Some(index));
 
-        Ok(KeyCreationOutput { mnemonic, key })
+        Ok(())
     }
 
-    /// Create a new transaction using a keypair
-    /// supported transaction types are P2PKH and P2SH
+    /// Build an unsigned transaction paying recipients, addresses and their
+    /// amounts in satoshis, from the coins of an account. Coins are picked
+    /// largest first until the amounts and the fee, at a rate in satoshis
+    /// per vbyte, are covered. What's left goes to a new change address of
+    /// the account, or to the fee when it would be dust. The coins stay in
+    /// the wallet until spent with [Wallet::spend_utxo]. Fails with
+    /// [crate::TransactionError::AbsurdFee] or [crate::TransactionError::AbsurdFeeRate]
+    /// when the fee is over the [Wallet::fee_limits]
     pub fn new_transaction(
-        &self,
-        tx_type: TransactionType,
-        _key: Key,
-        inputs: Vec<TransactionInput>,
-        outputs: Vec<TransactionOutput>,
-        lock_time: Option<u128>,
-    ) -> Transaction {
-        Transaction::new(tx_type, inputs, outputs, lock_time);
-        todo!("create pk and sig scripts? higher level api?")
+        &mut self,
+        account: u32,
+        recipients: &[(String, i64)],
+        fee_rate: u64,
+    ) -> Result<Transaction, WalletError> {
+        let recipients: Vec<Recipient> = recipients
+            .iter()
+            .map(|(address, amount)| Recipient::new(address, *amount))
+            .collect();
+        self.send_many(account, &recipients, fee_rate)
+    }
+
+    /// Build an unsigned transaction paying many recipients from the coins
+    /// of an account, as [Wallet::new_transaction] does. When recipients
+    /// subtract the fee, the coins only cover their amounts and the fee is
+    /// split between them instead, the change keeping what's left. Fails
+    /// with [crate::TransactionError::FeeExceedsRecipients] when their
+    /// share would leave one of them under [DUST_LIMIT]
+    pub fn send_many(
+        &mut self,
+        account: u32,
+        recipients: &[Recipient],
+        fee_rate: u64,
+    ) -> Result<Transaction, WalletError> {
+        let account_type = self
+            .account(account)
+            .map(|account| account.account_type())
+            .ok_or(WalletError::AccountNotFound(account))?;
+        let tx_type = account_type.tx_type();
+
+        let mut outputs = vec![];
+        for recipient in recipients {
+            if recipient.amount <= 0 {
+                return Err(WalletError::InvalidAmount(recipient.amount));
+            }
+            let script = Script::from_address(&recipient.address, self.network)
+                .map_err(|_| WalletError::InvalidAddress(recipient.address.clone()))?;
+            outputs.push(TransactionOutput::from_script(
+                recipient.amount,
+                script.into_bytes(),
+            ));
+        }
+        let subtract_fee = recipients
+            .iter()
+            .any(|recipient| recipient.subtract_fee_from);
+        let target: i64 = outputs.iter().map(|output| output.value()).sum();
+        let fee = |inputs: &[Utxo], outputs: &[TransactionOutput]| {
+            let inputs: Vec<ScriptType> = inputs.iter().map(|utxo| utxo.script_type()).collect();
+            (estimate_mixed_vsize(&tx_type, &inputs, outputs) * fee_rate) as i64
+        };
+        // what the coins selected have to cover, the recipients paying the fee otherwise
+        let needed = |inputs: &[Utxo]| match subtract_fee {
+            true => target,
+            false => target + fee(inputs, &outputs),
+        };
+
+        // coins of scripts the wallet can't sign for are left alone
+        let mut utxos: Vec<Utxo> = self
+            .account_utxos(account)
+            .into_iter()
+            .filter(|utxo| utxo.script_type().tx_type().is_some())
+            .cloned()
+            .collect();
+        utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.value()));
+        let mut selected = vec![];
+        let mut total = 0;
+        for utxo in utxos {
+            if total >= needed(&selected) {
+                break;
+            }
+            total += utxo.value();
+            selected.push(utxo);
+        }
+        if selected.is_empty() || total < needed(&selected) {
+            return Err(WalletError::InsufficientFunds);
+        }
+
+        let mut with_change = outputs.clone();
+        with_change.push(TransactionOutput::from_script(
+            0,
+            vec![0; tx_type.pk_script_len()],
+        ));
+        // the recipients paying the fee pay it all, less what's too little to change
+        let (change, subtracted) = match subtract_fee {
+            true if total - target >= DUST_LIMIT => (total - target, fee(&selected, &with_change)),
+            true => (0, (fee(&selected, &outputs) - (total - target)).max(0)),
+            false => (total - target - fee(&selected, &with_change), 0),
+        };
+
+        let mut builder = TransactionBuilder::new(tx_type.clone());
+        builder
+            .clock(self.clock.clone())
+            .entropy(self.entropy.clone())
+            .fee_limits(self.fee_limits);
+        self.lock_to_tip(&mut builder);
+        for utxo in selected.iter() {
+            builder.add_input(utxo.to_input());
+        }
+        for (position, output) in outputs.into_iter().enumerate() {
+            builder.add_output(output);
+            if recipients[position].subtract_fee_from {
+                builder.subtract_fee_from(position);
+            }
+        }
+        if subtract_fee {
+            builder
+                .subtract_fee(subtracted)
+                .map_err(WalletError::Transaction)?;
+        }
+        if change >= DUST_LIMIT {
+            let address = self.new_change_address(account)?;
+            builder.add_output(self.address_output(&address, change)?);
+        }
+
+        builder.build().map_err(WalletError::Transaction)
+    }
+
+    /// An output paying to an address of the wallet, built from its script
+    /// so it needs no private key, eg for change while the wallet is locked
+    fn address_output(&self, address: &str, value: i64) -> Result<TransactionOutput, WalletError> {
+        let script = Script::from_address(address, self.network)
+            .map_err(|_| WalletError::UnknownAddress(address.to_string()))?;
+        Ok(TransactionOutput::from_script(value, script.into_bytes()))
+    }
+
+    /// Build an unsigned self-spend merging the smallest coins of an account
+    /// into one output of a new change address, leaving it with
+    /// `target_utxo_count` coins, eg while fees are low to save on later spends.
+    /// The fee is paid at `max_fee_rate` satoshis per vbyte, coins worth less
+    /// than what spending them costs at that rate are left alone, as are
+    /// coins past what fits in a standard transaction. Fails with
+    /// [WalletError::NothingToConsolidate] when fewer than two coins would be merged
+    pub fn consolidate(
+        &mut self,
+        account: u32,
+        max_fee_rate: u64,
+        target_utxo_count: usize,
+    ) -> Result<Consolidation, WalletError> {
+        let account_type = self
+            .account(account)
+            .map(|account| account.account_type())
+            .ok_or(WalletError::AccountNotFound(account))?;
+        let tx_type = account_type.tx_type();
+        let change = TransactionOutput::from_script(0, vec![0; tx_type.pk_script_len()]);
+        let vsize = |coins: &[Utxo]| {
+            let inputs: Vec<ScriptType> = coins.iter().map(|utxo| utxo.script_type()).collect();
+            estimate_mixed_vsize(&tx_type, &inputs, std::slice::from_ref(&change))
+        };
+        // what an input adds to the fee, the coins worth less only lose value merged
+        let worth_spending = |utxo: &Utxo| {
+            let script_type = utxo.script_type();
+            match (script_type.tx_type(), script_type.max_satisfaction_weight()) {
+                (Some(_), Some(weight)) => {
+                    let cost = (INPUT_BASE_WEIGHT + weight).div_ceil(4) * max_fee_rate;
+                    utxo.value() > cost as i64
+                }
+                _ => false,
+            }
+        };
+
+        let mut coins: Vec<Utxo> = self.account_utxos(account).into_iter().cloned().collect();
+        coins.sort_by_key(|utxo| utxo.value());
+        let (mut spent, mut kept): (Vec<Utxo>, Vec<Utxo>) =
+            coins.into_iter().partition(|utxo| worth_spending(utxo));
+        let merged = (spent.len() + kept.len() + 1).saturating_sub(target_utxo_count.max(1));
+        kept.extend(spent.drain(merged.min(spent.len())..));
+        while vsize(&spent) * 4 > MAX_STANDARD_TX_WEIGHT {
+            kept.extend(spent.pop());
+        }
+        if spent.len() < 2 {
+            return Err(WalletError::NothingToConsolidate);
+        }
+
+        let total: i64 = spent.iter().map(|utxo| utxo.value()).sum();
+        let fee = (vsize(&spent) * max_fee_rate) as i64;
+        if total - fee < DUST_LIMIT {
+            return Err(WalletError::InsufficientFunds);
+        }
+
+        let address = self.new_change_address(account)?;
+        let output = self.address_output(&address, total - fee)?;
+        let mut builder = TransactionBuilder::new(tx_type.clone());
+        builder
+            .clock(self.clock.clone())
+            .entropy(self.entropy.clone())
+            .fee_limits(self.fee_limits);
+        self.lock_to_tip(&mut builder);
+        for utxo in spent.iter() {
+            builder.add_input(utxo.to_input());
+        }
+        builder.add_output(output);
+        let tx = builder.build().map_err(WalletError::Transaction)?;
+
+        kept.sort_by_key(|utxo| utxo.value());
+        Ok(Consolidation {
+            fee: tx.fee(),
+            merged: total - fee,
+            tx,
+            spent,
+            kept,
+        })
     }
 
     fn create_key_chain(&mut self, key: Key, mnemonic: String) -> Result<String, WalletError> {
+        self.ensure_unlocked()?;
+
+        let root = self.arena.root().ok_or(WalletError::Uninitialized)?;
+        let hardened_index = self.next_hardened_index(root)?;
         let hardened_key = key
-            .derive_child_private_key(self.next_hardened_index, ChildKeyType::Hardened)
+            .derive_child_private_key(hardened_index)
             .map_err(|e| WalletError::Key(e.to_string()))?;
 
         let hardened_key_pair = KeyPair {
@@ -177,15 +4724,15 @@ impl Wallet {
                 .new_public_key()
                 .map_err(|e| WalletError::Key(e.to_string()))?,
             key_type: KeyType::Hardened,
-            index: Some(self.next_hardened_index),
+            index: Some(hardened_index),
+            encrypted_private_key: None,
         };
 
-        self.next_hardened_index += 1;
-
-        let hardened_index = self.insert(hardened_key_pair.clone(), self.arena.root())?;
+        let hardened_node =
+            self.insert(hardened_key_pair.clone(), Some(root), AccountType::Legacy)?;
 
         let child_key = hardened_key
-            .derive_child_private_key(self.next_normal_index, ChildKeyType::Normal)
+            .derive_child_private_key(KEY_CHAIN_NORMAL_INDEX)
             .map_err(|e| WalletError::Key(e.to_string()))?;
 
         let child_key_pair = KeyPair {
@@ -194,61 +4741,337 @@ impl Wallet {
                 .new_public_key()
                 .map_err(|e| WalletError::Key(e.to_string()))?,
             key_type: KeyType::Normal,
-            index: Some(self.next_normal_index),
+            index: Some(KEY_CHAIN_NORMAL_INDEX),
+            encrypted_private_key: None,
         };
 
-        self.next_normal_index += 1;
-
-        let _ = self.insert(child_key_pair, Some(hardened_index));
+        let _ = self.insert(child_key_pair, Some(hardened_node), AccountType::Legacy);
 
         Ok(mnemonic)
     }
 
+    /// the node of the child of a node at an index, if it was already derived
+    fn find_child(&self, parent: usize, child: ChildNumber) -> Option<usize> {
+        match self.key_index.is_current(&self.arena) {
+            true => self.key_index.by_child(parent, child),
+            false => {
+                self.arena.nodes().iter().position(|node| {
+                    node.parent() == Some(parent) && node.data.index == Some(child)
+                })
+            }
+        }
+    }
+
+    /// whether the child of a node at an index was already derived
+    fn has_child(&self, parent: usize, child: ChildNumber) -> bool {
+        self.find_child(parent, child).is_some()
+    }
+
+    /// the first hardened child of a node not derived yet, counting from `0'`
+    fn next_hardened_index(&self, parent: usize) -> Result<ChildNumber, WalletError> {
+        let mut index = 0;
+        while self.has_child(parent, ChildNumber::Hardened(index)) {
+            index += 1;
+        }
+        ChildNumber::hardened(index).map_err(|e| WalletError::Key(e.to_string()))
+    }
+
+    /// get the child of a node at an index, deriving it if it doesn't exist yet
+    fn child(
+        &mut self,
+        parent: usize,
+        child: ChildNumber,
+        account_type: AccountType,
+    ) -> Result<usize, WalletError> {
+        match self.find_child(parent, child) {
+            Some(node) => Ok(node),
+            None => self.insert_child(parent, child, account_type),
+        }
+    }
+
+    /// Derive a child of a node and insert it into the arena,
+    /// the node is keyed by its address in the given account type.
+    /// While the wallet is locked normal children are derived from the
+    /// public key of their parent, their private keys are sealed on [Wallet::unlock].
+    /// A wallet loaded locked derives them once unlocked once, see [Wallet::flush]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(parent = parent, child = %child))
+    )]
+    fn insert_child(
+        &mut self,
+        parent: usize,
+        child: ChildNumber,
+        account_type: AccountType,
+    ) -> Result<usize, WalletError> {
+        if self.watch_only {
+            return Err(WalletError::WatchOnly);
+        }
+
+        let parent_key = self.unsealed_key(
+            self.arena
+                .get_inner(parent)
+                .ok_or(WalletError::Uninitialized)?,
+        )?;
+
+        if parent_key.is_wiped()
+            && self.master_key_id.is_some()
+            && self.arena.root() == Some(parent)
+        {
+            return Err(WalletError::MasterKeyNotLoaded);
+        }
+
+        let derive_error = |e| match e {
+            KeyError::InvalidChild(child) => WalletError::InvalidChild(child),
+            e => WalletError::Key(e.to_string()),
+        };
+        let (key, public_key) = match (parent_key.is_wiped() && self.locked, child) {
+            (true, ChildNumber::Normal(_)) => {
+                self.ensure_authenticated()?;
+                let parent_public_key = &self.arena.nodes()[parent].data.public_key;
+                parent_key
+                    .derive_wiped_child(parent_public_key, child)
+                    .map_err(derive_error)?
+            }
+            (true, ChildNumber::Hardened(_)) => return Err(WalletError::Locked),
+            (false, _) => {
+                let key = parent_key
+                    .derive_child_private_key(child)
+                    .map_err(derive_error)?;
+                let public_key = key
+                    .new_public_key()
+                    .map_err(|e| WalletError::Key(e.to_string()))?;
+                (key, public_key)
+            }
+        };
+
+        let keypair = KeyPair {
+            private_key: key,
+            public_key,
+            key_type: match child {
+                ChildNumber::Normal(_) => KeyType::Normal,
+                ChildNumber::Hardened(_) => KeyType::Hardened,
+            },
+            index: Some(child),
+            encrypted_private_key: None,
+        };
+
+        let index = self.insert(keypair, Some(parent), account_type)?;
+        self.seal_cached_key(index)?;
+
+        Ok(index)
+    }
+
     /// insert a keypair node to self.keys
-    fn insert(&mut self, keys: KeyPair, parent: Option<usize>) -> Result<usize, WalletError> {
-        Ok(self.arena.insert(
+    fn insert(
+        &mut self,
+        keys: KeyPair,
+        parent: Option<usize>,
+        account_type: AccountType,
+    ) -> Result<usize, WalletError> {
+        self.changes.mark(&[Section::Keys]);
+        let node = self.arena.insert(
             keys.clone(),
-            keys.private_key
-                .address()
+            account_type
+                .public_key_address(&keys.public_key, self.network)
                 .map_err(|e| WalletError::Key(e.to_string()))?,
             parent,
-        ))
+        );
+        self.key_index.update(&self.arena, self.network);
+        Ok(node)
     }
 
-    /// get a keypair by its internal node id
-    fn get(&self, index: usize) -> Option<KeyPair> {
-        self.arena.get_inner(index).cloned()
+    /// the node of the key of an address, derived or imported
+    fn node_id(&self, address: &str) -> Option<usize> {
+        match self.key_index.is_current(&self.arena) {
+            true => self.key_index.by_address(address),
+            false => self
+                .arena
+                .nodes()
+                .iter()
+                .position(|node| node.key == address),
+        }
     }
 
-    /// get a key in the wallet by an address
-    pub fn get_address(&self, address: String) -> Option<Key> {
-        if let Some(id) = self.arena.root().clone() {
-            match self.get(id) {
-                Some(node) => {
-                    // check root
-                    let key = node.clone().private_key;
-                    let node_address = key.address();
-
-                    if node_address.is_err() {
-                        return None;
-                    }
+    fn find_node(&self, address: &str) -> Option<&Node<KeyPair, String>> {
+        self.node_id(address).and_then(|node| self.arena.get(node))
+    }
+
+    /// The key paying to an output script, derived or imported, found
+    /// without walking the key graph
+    pub fn find_by_script(&self, script: &[u8]) -> Option<&Node<KeyPair, String>> {
+        match self.key_index.is_current(&self.arena) {
+            true => self
+                .key_index
+                .by_script(script)
+                .and_then(|node| self.arena.get(node)),
+            false => Script::new(script.to_vec())
+                .to_address(self.network)
+                .and_then(|address| self.find_node(&address)),
+        }
+    }
+
+    /// the key of a public key, compressed or not
+    pub fn find_by_pubkey(&self, public_key: &[u8]) -> Option<&Node<KeyPair, String>> {
+        match self.key_index.is_current(&self.arena) {
+            true => self
+                .key_index
+                .by_pubkey(public_key)
+                .and_then(|node| self.arena.get(node)),
+            false => {
+                let compressed = compress_public_key(public_key).ok()?;
+                self.arena.nodes().iter().find(|node| {
+                    compress_public_key(&node.data.public_key).is_ok_and(|key| key == compressed)
+                })
+            }
+        }
+    }
 
-                    let node_address = node_address.unwrap();
+    /// the keys with a fingerprint, eg from a BIP32 derivation of a PSBT
+    pub fn find_by_fingerprint(&self, fingerprint: [u8; 4]) -> Vec<&Node<KeyPair, String>> {
+        match self.key_index.is_current(&self.arena) {
+            true => self
+                .key_index
+                .by_fingerprint(fingerprint)
+                .iter()
+                .filter_map(|node| self.arena.get(*node))
+                .collect(),
+            false => self
+                .arena
+                .nodes()
+                .iter()
+                .filter(|node| key_fingerprint(&node.data.public_key).ok() == Some(fingerprint))
+                .collect(),
+        }
+    }
 
-                    // root is the key
-                    if address == node_address {
-                        return Some(key);
-                    }
+    /// Import a standalone key from a WIF, outside of the key graph derived
+    /// from the master key. The key keeps the compression of its WIF unless
+    /// `compress_public_keys` overrides it, which changes its address.
+    /// Returns the P2PKH address of the key
+    pub fn import_wif(
+        &mut self,
+        wif: &str,
+        compress_public_keys: Option<bool>,
+    ) -> Result<String, WalletError> {
+        self.ensure_unlocked()?;
+        if self.watch_only {
+            return Err(WalletError::WatchOnly);
+        }
+
+        let key = Key::from_wif(wif.to_string()).map_err(|e| WalletError::Key(e.to_string()))?;
+        if *key.network() != self.network {
+            return Err(WalletError::Key(KeyError::InvalidNetworkByte.to_string()));
+        }
+        let key = match compress_public_keys {
+            Some(true) => key.to_compressed(),
+            Some(false) => key.to_uncompressed(),
+            None => key,
+        };
+
+        self.import_key(key, AccountType::Legacy)
+    }
+
+    /// Import the private keys of a Bitcoin Core wallet, from the file
+    /// written by `dumpwallet` or the json printed by `listdescriptors true`.
+    /// Each key is imported like [Wallet::import_wif], paying to the first
+    /// address Core listed for it or to the type of its descriptor, with its
+    /// label. Scripts, extended keys and keys of another network are skipped
+    /// and reported. The birthday of the wallet is moved back to the time of
+    /// the oldest key imported. Every key is checked before any is imported,
+    /// on an error the wallet isn't changed
+    pub fn import_core_dump<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<CoreDumpImport, WalletError> {
+        self.ensure_unlocked()?;
+        if self.watch_only {
+            return Err(WalletError::WatchOnly);
+        }
+
+        let contents = fs::read_to_string(path)
+            .map_err(|e| WalletError::Read(format!("Failed to read file: {}", e)))?;
+        let (keys, skipped) = parse_core_dump(&contents)?;
+
+        let mut report = CoreDumpImport {
+            skipped,
+            ..CoreDumpImport::default()
+        };
+        let mut imports = vec![];
+        for dumped in keys {
+            if *dumped.key.network() != self.network {
+                report.skipped.push(SkippedEntry {
+                    entry: dumped.entry,
+                    reason: KeyError::InvalidNetworkByte.to_string(),
+                });
+                continue;
+            }
+
+            let (address, keypair) = imported_keypair(dumped.key, dumped.account_type)?;
+            report.earliest_key_time = match (report.earliest_key_time, dumped.time) {
+                (Some(earliest), Some(time)) => Some(earliest.min(time)),
+                (earliest, time) => earliest.or(time),
+            };
+            imports.push((address, keypair, dumped.account_type, dumped.label));
+        }
+        report.skipped.sort_by_key(|skipped| skipped.entry);
 
-                    // recursively check node's children from first child to last child
-                    let _current_node = node.clone();
-                    loop {}
+        // the keys added before a failure go with the graph they were added to
+        let (arena, changes) = (self.arena.clone(), self.changes.clone());
+        let mut labels = vec![];
+        for (address, keypair, account_type, label) in imports {
+            let address = match self.add_imported_key(address, keypair, account_type) {
+                Ok(address) => address,
+                Err(e) => {
+                    self.arena = arena;
+                    self.changes = changes;
+                    self.key_index = KeyIndex::default();
+                    self.key_index.update(&self.arena, self.network);
+                    return Err(e);
                 }
-                None => return None,
+            };
+            if let Some(label) = label.filter(|label| !label.is_empty()) {
+                labels.push((address.clone(), label));
             }
-        } else {
-            return None;
+            report.imported.push(address);
+        }
+
+        self.changes
+            .mark(&[Section::Keys, Section::Labels, Section::Settings]);
+        self.labels.extend(labels);
+        if let Some(time) = report.earliest_key_time {
+            self.birthday.time = Some(self.birthday.time.map_or(time, |birth| birth.min(time)));
         }
+        Ok(report)
+    }
+
+    /// add a standalone key paying to an account type, returns its address
+    fn import_key(&mut self, key: Key, account_type: AccountType) -> Result<String, WalletError> {
+        let (address, keypair) = imported_keypair(key, account_type)?;
+        self.add_imported_key(address, keypair, account_type)
+    }
+
+    /// add the keypair of an imported key unless its address is in the wallet already
+    fn add_imported_key(
+        &mut self,
+        address: String,
+        keypair: KeyPair,
+        account_type: AccountType,
+    ) -> Result<String, WalletError> {
+        if self.node_id(&address).is_some() {
+            return Ok(address);
+        }
+
+        let index = self.insert(keypair, None, account_type)?;
+        self.seal_cached_key(index)?;
+
+        Ok(self.arena.nodes()[index].key.clone())
     }
-}
 
+    /// get a key in the wallet by an address
+    /// the private key is wiped while the wallet is locked
+    pub fn get_address(&self, address: String) -> Option<Key> {
+        self.find_node(&address)
+            .map(|node| node.data.private_key.clone())
+    }
+}
@@ -0,0 +1,120 @@
+//! End to end spends against a regtest node driven by [RegtestHarness].
+//! The node is configured as in [RegtestHarness::from_env], eg
+//! `bitcoind -regtest -rpcuser=waller -rpcpassword=waller -fallbackfee=0.0001`
+
+use std::fs;
+
+use serde_json::json;
+use waller::{
+    compress_public_key, hash160, AccountType, Backend, Digest32, Network, OutPoint,
+    RegtestHarness, SigHashType, Transaction, TransactionOutput, TransactionType,
+    TransactionVersion, Utxo, Wallet, SIGHASH_ALL,
+};
+
+/// a new wallet on regtest, in its own data directory
+fn regtest_wallet(name: &str) -> Wallet {
+    let path = std::env::temp_dir().join(format!("waller-e2e-{}", name));
+    fs::create_dir_all(&path).unwrap();
+
+    let mut wallet = Wallet::new(Network::Regtest, path, true, false);
+    wallet.init().unwrap();
+    wallet
+}
+
+#[test]
+#[ignore = "needs a regtest node"]
+fn test_p2wpkh_spend() {
+    let harness = RegtestHarness::from_env().unwrap();
+    let mut wallet = regtest_wallet("p2wpkh");
+    let account = wallet.new_account(AccountType::NativeSegwit).unwrap();
+
+    // receive coins and wait for them to confirm
+    let address = wallet.new_receive_address(account).unwrap();
+    assert!(address.starts_with("bcrt1q"));
+    let utxo = harness.fund(&mut wallet, &address, 1_000_000).unwrap();
+    harness.confirm(&mut wallet, 1).unwrap();
+    assert!(wallet.utxos()[0].is_confirmed());
+    assert_eq!(1_000_000, wallet.account_balance(account));
+
+    // pay most of it to the change chain, the rest is the fee
+    let change = wallet.new_change_address(account).unwrap();
+    let output = TransactionOutput::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        wallet.get_address(change.clone()).unwrap(),
+        990_000,
+    );
+    let mut tx = Transaction::new(
+        TransactionType::Pay2WitnessPubKeyHash,
+        vec![utxo.to_input()],
+        vec![output.clone()],
+        None,
+    );
+    tx.set_version(TransactionVersion::Two);
+
+    // the script code of a P2WPKH input is the P2PKH script of its key
+    let key = wallet.get_address(address).unwrap();
+    let pubkey = compress_public_key(&key.new_public_key().unwrap()).unwrap();
+    let script_code = [
+        &[0x76, 0xa9, 0x14][..],
+        &hash160(&pubkey),
+        &[0x88, 0xac][..],
+    ]
+    .concat();
+    let sighash = tx.sighash(0, &script_code, SigHashType::All).unwrap();
+    let mut signature = hex::decode(key.sign_data(Digest32::from(sighash)).unwrap()).unwrap();
+    signature.push(SIGHASH_ALL as u8);
+    tx.set_signature(0, &signature, &pubkey).unwrap();
+
+    let accepted = harness
+        .rpc()
+        .call("testmempoolaccept", json!([[tx.to_hex()]]))
+        .unwrap();
+    assert_eq!(Some(true), accepted[0]["allowed"].as_bool(), "{}", accepted);
+
+    let tx_id = harness.broadcast(&tx.to_hex()).unwrap();
+    assert_eq!(tx.tx_id(), tx_id);
+
+    wallet.spend_utxo(utxo.outpoint()).unwrap();
+    wallet
+        .add_utxo(Utxo::new(OutPoint::new(tx_id.clone(), 0), output, change))
+        .unwrap();
+    let height = harness.confirm(&mut wallet, 1).unwrap();
+
+    assert_eq!(Some(height), wallet.utxos()[0].height());
+    assert_eq!(990_000, wallet.account_balance(account));
+    let spent = harness.rpc().call("gettxout", json!([tx_id, 0])).unwrap();
+    assert_eq!(Some(1), spent["confirmations"].as_u64());
+}
+
+#[test]
+#[ignore = "needs a regtest node"]
+fn test_nested_segwit_spend() {
+    let harness = RegtestHarness::from_env().unwrap();
+    let mut wallet = regtest_wallet("nested-segwit");
+    let account = wallet.new_account(AccountType::NestedSegwit).unwrap();
+
+    let address = wallet.new_receive_address(account).unwrap();
+    let utxo = harness.fund(&mut wallet, &address, 500_000).unwrap();
+    harness.confirm(&mut wallet, 1).unwrap();
+
+    let change = wallet.new_change_address(account).unwrap();
+    let mut tx = Transaction::new(
+        TransactionType::NestedPay2WitnessPubKeyHash,
+        vec![utxo.to_input()],
+        vec![TransactionOutput::new(
+            TransactionType::NestedPay2WitnessPubKeyHash,
+            wallet.get_address(change).unwrap(),
+            490_000,
+        )],
+        None,
+    );
+    tx.sign_nested_segwit_input(0, &wallet.get_address(address).unwrap(), SigHashType::All)
+        .unwrap();
+
+    let tx_id = harness.broadcast(&tx.to_hex()).unwrap();
+    assert_eq!(tx.tx_id(), tx_id);
+    harness.generate(1).unwrap();
+
+    let spent = harness.rpc().call("gettxout", json!([tx_id, 0])).unwrap();
+    assert_eq!(Some(490_000.0 / 100_000_000.0), spent["value"].as_f64());
+}